@@ -24,6 +24,10 @@ contract ForcedExit is Ownable, ReentrancyGuard {
 
     event FundsReceived(uint256 _amount);
 
+    // Emitted instead of `FundsReceived` when the payer has supplied
+    // the id of the request explicitly in the calldata
+    event FundsReceivedWithRequestId(uint256 _amount, uint256 _requestId);
+
     function setReceiver(address payable _newReceiver) external {
         requireMaster(msg.sender);
 
@@ -45,7 +49,15 @@ contract ForcedExit is Ownable, ReentrancyGuard {
     // We have to use fallback instead of `receive` since the ethabi
     // library can't decode the receive function:
     // https://github.com/rust-ethereum/ethabi/issues/185
+    //
+    // The calldata may optionally contain the abi-encoded id of the request
+    // that is being paid for. Any other calldata is ignored.
     fallback() external payable {
-        emit FundsReceived(msg.value);
+        if (msg.data.length == 32) {
+            uint256 requestId = abi.decode(msg.data, (uint256));
+            emit FundsReceivedWithRequestId(msg.value, requestId);
+        } else {
+            emit FundsReceived(msg.value);
+        }
     }
 }
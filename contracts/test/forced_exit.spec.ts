@@ -50,4 +50,26 @@ describe('ForcedExit unit tests', function () {
         // The pending funds have been received
         expect(withdrawTxHandle).to.changeEtherBalance(wallet4, TX_AMOUNT);
     });
+
+    it('Check explicit request id in calldata', async () => {
+        const requestId = 1212;
+
+        // User sends funds to the contract along with the id of the request
+        const transferTxHandle = await wallet3.sendTransaction({
+            to: forcedExitContract.address,
+            value: TX_AMOUNT,
+            data: utils.defaultAbiCoder.encode(['uint256'], [requestId])
+        });
+        expect(transferTxHandle)
+            .to.emit(forcedExitContract, 'FundsReceivedWithRequestId')
+            .withArgs(TX_AMOUNT, requestId);
+
+        // Calldata of any other shape is ignored
+        const malformedTxHandle = await wallet3.sendTransaction({
+            to: forcedExitContract.address,
+            value: TX_AMOUNT,
+            data: '0x1212'
+        });
+        expect(malformedTxHandle).to.emit(forcedExitContract, 'FundsReceived').withArgs(TX_AMOUNT);
+    });
 });
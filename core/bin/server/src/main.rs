@@ -9,7 +9,9 @@ use serde::{Deserialize, Serialize};
 use zksync_api::fee_ticker::{run_updaters, FeeTicker, TickerInfo};
 use zksync_core::{genesis_init, run_core, wait_for_tasks};
 use zksync_eth_client::EthereumGateway;
-use zksync_forced_exit_requests::run_forced_exit_requests_actors;
+use zksync_forced_exit_requests::{
+    run_forced_exit_requests_actors, shutdown::ShutdownSignal, spawner::ForcedExitSpawner,
+};
 use zksync_gateway_watcher::run_gateway_watcher_if_multiplexed;
 use zksync_witness_generator::run_prover_server;
 
@@ -230,6 +232,9 @@ async fn run_server(components: &ComponentsToRun) {
                 chain_config.state_keeper.miniblock_iteration_interval(),
                 mempool_tx_request_sender,
                 eth_watch_config.confirmations_for_eth_event,
+                connection_pool.clone(),
+                &ForcedExitRequestsConfig::from_env(),
+                contracts_config.forced_exit_addr,
             ));
         }
 
@@ -250,6 +255,9 @@ async fn run_server(components: &ComponentsToRun) {
                 &token_config,
                 mempool_tx_request_sender,
                 eth_watch_config.confirmations_for_eth_event,
+                connection_pool.clone(),
+                &ForcedExitRequestsConfig::from_env(),
+                contracts_config.forced_exit_addr,
             ));
         }
 
@@ -310,8 +318,13 @@ async fn run_server(components: &ComponentsToRun) {
         }
     }
 
+    // The requests being fulfilled are drained before the server exits
+    let mut forced_exit_shutdown = None;
     if components.0.contains(&Component::ForcedExit) {
-        tasks.append(&mut run_forced_exit(connection_pool.clone()));
+        let drain_timeout = ForcedExitRequestsConfig::from_env().shutdown_drain_timeout();
+        let (shutdown_handle, shutdown) = ShutdownSignal::new(drain_timeout);
+        forced_exit_shutdown = Some(shutdown_handle);
+        tasks.append(&mut run_forced_exit(connection_pool.clone(), shutdown));
     }
 
     if components.0.contains(&Component::RejectedTaskCleaner) {
@@ -336,9 +349,15 @@ async fn run_server(components: &ComponentsToRun) {
             vlog::warn!("Stop signal received, shutting down");
         }
     };
+    if let Some(shutdown_handle) = forced_exit_shutdown {
+        shutdown_handle.shutdown().await;
+    }
 }
 
-pub fn run_forced_exit(connection_pool: ConnectionPool) -> Vec<JoinHandle<()>> {
+pub fn run_forced_exit(
+    connection_pool: ConnectionPool,
+    shutdown: ShutdownSignal,
+) -> Vec<JoinHandle<()>> {
     vlog::info!("Starting the ForcedExitRequests actors");
     let config = ForcedExitRequestsConfig::from_env();
     let common_config = CommonApiConfig::from_env();
@@ -346,22 +365,36 @@ pub fn run_forced_exit(connection_pool: ConnectionPool) -> Vec<JoinHandle<()>> {
     let eth_client_config = ETHClientConfig::from_env();
     let chain_config = ChainConfig::from_env();
 
+    let spawner = ForcedExitSpawner::from_config(&config);
+    // The connections are bound to the runtime they were opened on, so the isolated
+    // actors do not share them with the API server either
+    let connection_pool = if spawner.is_dedicated() {
+        ConnectionPool::new(None)
+    } else {
+        connection_pool
+    };
+
     let (mempool_tx_request_sender, mempool_tx_request_receiver) =
         mpsc::channel(DEFAULT_CHANNEL_CAPACITY);
-    let mempool_task = run_mempool_tx_handler(
-        connection_pool.clone(),
-        mempool_tx_request_receiver,
-        chain_config.state_keeper.block_chunk_sizes,
-    );
-    let forced_exit_task = run_forced_exit_requests_actors(
+    let mempool_task = spawner.enter(|| {
+        run_mempool_tx_handler(
+            connection_pool.clone(),
+            mempool_tx_request_receiver,
+            chain_config.state_keeper.block_chunk_sizes,
+        )
+    });
+    let mut tasks = run_forced_exit_requests_actors(
+        &spawner,
         connection_pool,
         mempool_tx_request_sender,
         config,
         common_config,
         contract_config,
         eth_client_config.web3_url(),
+        shutdown,
     );
-    vec![mempool_task, forced_exit_task]
+    tasks.push(mempool_task);
+    tasks
 }
 
 pub fn run_witness_generator(connection_pool: ConnectionPool) -> JoinHandle<()> {
@@ -1,14 +1,14 @@
 use crate::api_server::tx_sender::SubmitError;
 
 use zksync_storage::StorageProcessor;
-use zksync_types::Address;
+use zksync_types::{forced_exit_requests::ForcedExitTargetCheck, Address};
 
 use crate::internal_error;
 
 use chrono::Utc;
 
 #[async_trait::async_trait]
-pub trait ForcedExitAccountAgeChecker {
+pub trait ForcedExitAccountAgeChecker: Send + Sync {
     async fn check_forced_exit(
         &self,
         storage: &mut StorageProcessor<'_>,
@@ -20,6 +20,28 @@ pub trait ForcedExitAccountAgeChecker {
         storage: &mut StorageProcessor<'_>,
         target_account_address: Address,
     ) -> Result<(), SubmitError>;
+
+    /// Loads the state of the target account the forced exit requests depend on.
+    async fn check_forced_exit_target(
+        &self,
+        storage: &mut StorageProcessor<'_>,
+        target_account_address: Address,
+    ) -> Result<ForcedExitTargetCheck, SubmitError> {
+        let nonce = storage
+            .chain()
+            .account_schema()
+            .committed_nonce_by_address(target_account_address)
+            .await
+            .map_err(|err| internal_error!(err, target_account_address))?;
+
+        // The age is unknown for the account which does not exist in the network
+        let old_enough = nonce.is_some()
+            && self
+                .check_forced_exit(storage, target_account_address)
+                .await?;
+
+        Ok(ForcedExitTargetCheck { old_enough, nonce })
+    }
 }
 
 #[derive(Clone)]
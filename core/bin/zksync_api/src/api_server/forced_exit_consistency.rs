@@ -0,0 +1,377 @@
+//! Checks of the forced exit requests against the records backing their statuses.
+//!
+//! The sender checks the requests changed recently in the background, while the admin API
+//! runs the same check on demand.
+
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use num::BigUint;
+
+use zksync_types::forced_exit_requests::{
+    ForcedExitConsistencyReport, ForcedExitInvariant, ForcedExitInvariantViolation,
+    ForcedExitRefundStatus, ForcedExitRequestEvent, ForcedExitRequestEvidence,
+};
+
+/// Checks the requests against the invariants, the records are left as they are.
+pub fn consistency_report(
+    created_at: DateTime<Utc>,
+    since: DateTime<Utc>,
+    evidence: &[ForcedExitRequestEvidence],
+) -> ForcedExitConsistencyReport {
+    ForcedExitConsistencyReport {
+        created_at,
+        since,
+        checked_requests: evidence.len() as u32,
+        violations: evidence.iter().flat_map(violations).collect(),
+    }
+}
+
+/// Returns the violations of all the invariants by the request.
+pub fn violations(evidence: &ForcedExitRequestEvidence) -> Vec<ForcedExitInvariantViolation> {
+    let mut violations = Vec::new();
+    violations.extend(check_fulfillments(evidence));
+    violations.extend(check_payment(evidence));
+    violations.extend(check_failure_event(evidence));
+    violations.extend(check_refunded_payments(evidence));
+    violations.extend(check_balance(evidence));
+    violations
+}
+
+fn violation(
+    evidence: &ForcedExitRequestEvidence,
+    invariant: ForcedExitInvariant,
+    details: String,
+) -> ForcedExitInvariantViolation {
+    ForcedExitInvariantViolation {
+        request_id: evidence.request.id,
+        invariant,
+        details,
+    }
+}
+
+// The transactions are recorded before the request is marked as fulfilled,
+// the escalated requests have no transactions on L2 at all
+fn check_fulfillments(
+    evidence: &ForcedExitRequestEvidence,
+) -> Option<ForcedExitInvariantViolation> {
+    if evidence.request.fulfilled_at.is_none()
+        || evidence.escalated
+        || evidence.fulfilled_tokens == evidence.request.tokens
+    {
+        return None;
+    }
+    Some(violation(
+        evidence,
+        ForcedExitInvariant::FulfilledWithTransactions,
+        format!(
+            "fulfilled with the transactions for the tokens {:?} instead of {:?}",
+            evidence.fulfilled_tokens, evidence.request.tokens
+        ),
+    ))
+}
+
+// The match of the payment made without the known L1 transaction is only recorded
+// as the paid amount, both are written together with the match itself
+fn check_payment(evidence: &ForcedExitRequestEvidence) -> Option<ForcedExitInvariantViolation> {
+    if evidence.request.matched_at.is_none()
+        || evidence.request.paid_amount.is_some()
+        || !evidence.matched_payments.is_empty()
+    {
+        return None;
+    }
+    Some(violation(
+        evidence,
+        ForcedExitInvariant::PaidWithPayment,
+        "matched without the payment recorded".to_owned(),
+    ))
+}
+
+fn check_failure_event(
+    evidence: &ForcedExitRequestEvidence,
+) -> Option<ForcedExitInvariantViolation> {
+    let failed_at = evidence.failed_at?;
+    if evidence.events.contains(&ForcedExitRequestEvent::Failed) {
+        return None;
+    }
+    Some(violation(
+        evidence,
+        ForcedExitInvariant::FailedWithEvent,
+        format!("failed at {} without the notification", failed_at),
+    ))
+}
+
+fn check_refunded_payments(
+    evidence: &ForcedExitRequestEvidence,
+) -> Vec<ForcedExitInvariantViolation> {
+    evidence
+        .refunds
+        .iter()
+        .filter(|refund| !refund.payment_recorded)
+        .map(|refund| {
+            violation(
+                evidence,
+                ForcedExitInvariant::RefundedPayment,
+                format!(
+                    "refund {} returns the unknown payment {:?}",
+                    refund.refund_id, refund.payment_tx_hash
+                ),
+            )
+        })
+        .collect()
+}
+
+fn check_balance(evidence: &ForcedExitRequestEvidence) -> Vec<ForcedExitInvariantViolation> {
+    let mut violations = Vec::new();
+
+    let paid_amount = evidence
+        .request
+        .paid_amount
+        .as_deref()
+        .and_then(|amount| BigUint::from_str(amount).ok());
+    match paid_amount {
+        Some(paid_amount) if paid_amount < evidence.request.price_in_wei => {
+            violations.push(violation(
+                evidence,
+                ForcedExitInvariant::Balance,
+                format!(
+                    "paid {} for the price of {}",
+                    paid_amount, evidence.request.price_in_wei
+                ),
+            ));
+        }
+        None if evidence.request.paid_amount.is_some() => {
+            violations.push(violation(
+                evidence,
+                ForcedExitInvariant::Balance,
+                format!("invalid paid amount {:?}", evidence.request.paid_amount),
+            ));
+        }
+        _ => {}
+    }
+
+    let fulfilled = evidence.request.fulfilled_at.is_some();
+    for refund in &evidence.refunds {
+        // The transfer which has failed has not returned anything
+        if refund.status == ForcedExitRefundStatus::Failed {
+            continue;
+        }
+        if fulfilled && evidence.matched_payments.contains(&refund.payment_tx_hash) {
+            violations.push(violation(
+                evidence,
+                ForcedExitInvariant::Balance,
+                format!(
+                    "refund {} returns the payment {:?} the request was fulfilled for",
+                    refund.refund_id, refund.payment_tx_hash
+                ),
+            ));
+        }
+        match &refund.payment_amount {
+            Some(payment_amount) if &refund.amount + &refund.fee > *payment_amount => {
+                violations.push(violation(
+                    evidence,
+                    ForcedExitInvariant::Balance,
+                    format!(
+                        "refund {} of {} with the fee of {} exceeds the payment of {}",
+                        refund.refund_id, refund.amount, refund.fee, payment_amount
+                    ),
+                ));
+            }
+            _ => {}
+        }
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use zksync_types::{
+        forced_exit_requests::{ForcedExitRefundEvidence, ForcedExitRequest, PaymentMatchScheme},
+        Address, TokenId, H256,
+    };
+
+    use super::*;
+
+    fn consistent_evidence() -> ForcedExitRequestEvidence {
+        let now = Utc::now();
+        ForcedExitRequestEvidence {
+            request: ForcedExitRequest {
+                id: 12,
+                public_id: 12,
+                target: Address::repeat_byte(0x12),
+                tokens: vec![TokenId(0), TokenId(3)],
+                price_in_wei: BigUint::from(20000u32),
+                pay_exactly: "20125".to_owned(),
+                valid_until: now + chrono::Duration::days(1),
+                created_at: now,
+                fulfilled_by: None,
+                fulfilled_at: Some(now),
+                match_scheme: Some(PaymentMatchScheme::AmountDigits),
+                matched_at: Some(now),
+                cancellation: None,
+                paid_amount: Some("20125".to_owned()),
+                metadata: None,
+                status: Default::default(),
+                payment_terms: None,
+            },
+            fulfilled_tokens: vec![TokenId(0), TokenId(3)],
+            escalated: false,
+            failed_at: None,
+            events: vec![
+                ForcedExitRequestEvent::Submitted,
+                ForcedExitRequestEvent::Fulfilled,
+            ],
+            matched_payments: vec![H256::repeat_byte(0x01)],
+            // The payment of the wrong amount made before the right one
+            refunds: vec![ForcedExitRefundEvidence {
+                refund_id: 1,
+                payment_tx_hash: H256::repeat_byte(0x02),
+                amount: BigUint::from(9000u32),
+                fee: BigUint::from(1000u32),
+                status: ForcedExitRefundStatus::Completed,
+                payment_recorded: true,
+                payment_amount: Some(BigUint::from(10000u32)),
+            }],
+        }
+    }
+
+    fn violated(evidence: &ForcedExitRequestEvidence) -> Vec<ForcedExitInvariant> {
+        violations(evidence)
+            .into_iter()
+            .map(|violation| {
+                assert_eq!(violation.request_id, evidence.request.id);
+                violation.invariant
+            })
+            .collect()
+    }
+
+    #[test]
+    fn consistent_request_violates_nothing() {
+        assert!(violated(&consistent_evidence()).is_empty());
+
+        // Nothing is required from the request that has not been processed
+        let mut evidence = consistent_evidence();
+        evidence.request.fulfilled_at = None;
+        evidence.request.matched_at = None;
+        evidence.request.paid_amount = None;
+        evidence.fulfilled_tokens.clear();
+        evidence.events.clear();
+        evidence.matched_payments.clear();
+        evidence.refunds.clear();
+        assert!(violated(&evidence).is_empty());
+    }
+
+    #[test]
+    fn fulfilled_request_without_transactions() {
+        let mut evidence = consistent_evidence();
+        evidence.fulfilled_tokens.pop();
+        assert_eq!(
+            violated(&evidence),
+            vec![ForcedExitInvariant::FulfilledWithTransactions]
+        );
+        evidence.fulfilled_tokens.clear();
+        assert_eq!(
+            violated(&evidence),
+            vec![ForcedExitInvariant::FulfilledWithTransactions]
+        );
+
+        // The escalated request is fulfilled on L1
+        evidence.escalated = true;
+        assert!(violated(&evidence).is_empty());
+        // The transactions of the request being sent are recorded before it is fulfilled
+        evidence.escalated = false;
+        evidence.request.fulfilled_at = None;
+        evidence.fulfilled_tokens = vec![TokenId(0)];
+        assert!(violated(&evidence).is_empty());
+    }
+
+    #[test]
+    fn paid_request_without_payment() {
+        let mut evidence = consistent_evidence();
+        evidence.request.paid_amount = None;
+        evidence.matched_payments.clear();
+        assert_eq!(
+            violated(&evidence),
+            vec![ForcedExitInvariant::PaidWithPayment]
+        );
+
+        // The payment without the known L1 transaction only has the amount recorded
+        evidence.request.paid_amount = Some("20125".to_owned());
+        assert!(violated(&evidence).is_empty());
+    }
+
+    #[test]
+    fn failed_request_without_event() {
+        let mut evidence = consistent_evidence();
+        evidence.request.fulfilled_at = None;
+        evidence.fulfilled_tokens.clear();
+        evidence.events.clear();
+        evidence.failed_at = Some(Utc::now());
+        assert_eq!(
+            violated(&evidence),
+            vec![ForcedExitInvariant::FailedWithEvent]
+        );
+
+        evidence.events.push(ForcedExitRequestEvent::Failed);
+        assert!(violated(&evidence).is_empty());
+    }
+
+    #[test]
+    fn refund_of_unknown_payment() {
+        let mut evidence = consistent_evidence();
+        evidence.refunds[0].payment_recorded = false;
+        evidence.refunds[0].payment_amount = None;
+        assert_eq!(
+            violated(&evidence),
+            vec![ForcedExitInvariant::RefundedPayment]
+        );
+    }
+
+    #[test]
+    fn unbalanced_requests() {
+        // Underpaid
+        let mut evidence = consistent_evidence();
+        evidence.request.paid_amount = Some("19999".to_owned());
+        assert_eq!(violated(&evidence), vec![ForcedExitInvariant::Balance]);
+        evidence.request.paid_amount = Some("0x4e20".to_owned());
+        assert_eq!(violated(&evidence), vec![ForcedExitInvariant::Balance]);
+
+        // Refunded more than paid
+        let mut evidence = consistent_evidence();
+        evidence.refunds[0].amount = BigUint::from(9001u32);
+        assert_eq!(violated(&evidence), vec![ForcedExitInvariant::Balance]);
+        // The failed transfer has not returned anything
+        evidence.refunds[0].status = ForcedExitRefundStatus::Failed;
+        assert!(violated(&evidence).is_empty());
+
+        // Refunded the payment the request was fulfilled for
+        let mut evidence = consistent_evidence();
+        evidence.refunds[0].payment_tx_hash = evidence.matched_payments[0];
+        assert_eq!(violated(&evidence), vec![ForcedExitInvariant::Balance]);
+        // Unless the request has not been fulfilled
+        evidence.request.fulfilled_at = None;
+        evidence.fulfilled_tokens.clear();
+        assert!(violated(&evidence).is_empty());
+    }
+
+    #[test]
+    fn consistency_report() {
+        let mut underpaid = consistent_evidence();
+        underpaid.request.id = 13;
+        underpaid.request.paid_amount = Some("1".to_owned());
+        underpaid.refunds[0].payment_recorded = false;
+        let evidence = vec![consistent_evidence(), underpaid];
+
+        let now = Utc::now();
+        let report = consistency_report(now, now - chrono::Duration::hours(1), &evidence);
+        assert_eq!(report.checked_requests, 2);
+        assert_eq!(report.violations.len(), 2);
+        assert!(report
+            .violations
+            .iter()
+            .all(|violation| violation.request_id == 13));
+        assert_eq!(report.count(ForcedExitInvariant::Balance), 1);
+        assert_eq!(report.count(ForcedExitInvariant::RefundedPayment), 1);
+        assert_eq!(report.count(ForcedExitInvariant::FailedWithEvent), 0);
+    }
+}
@@ -0,0 +1,124 @@
+//! The scheduled maintenance windows, during which no transactions are sent for the requests.
+//!
+//! The sender pauses the submissions for the maintenance, while the API reports it to the clients.
+
+use chrono::{DateTime, Utc};
+
+use zksync_types::forced_exit_requests::{ForcedExitMaintenance, MaintenanceWindow};
+
+/// Returns the end of the occurrence of the window, which is in progress at the time.
+/// The occurrences are considered started `lead_time` before their actual starts.
+fn active_until(
+    window: &MaintenanceWindow,
+    now: DateTime<Utc>,
+    lead_time: chrono::Duration,
+) -> Option<DateTime<Utc>> {
+    let start = window.start - lead_time;
+    if now < start {
+        return None;
+    }
+    // The latest occurrence started by now
+    let end = match window.recurrence {
+        Some(recurrence) => {
+            let period = recurrence.period().num_milliseconds();
+            let occurrences = (now - start).num_milliseconds() / period;
+            window.end + chrono::Duration::milliseconds(occurrences * period)
+        }
+        None => window.end,
+    };
+    if now < end {
+        Some(end)
+    } else {
+        None
+    }
+}
+
+/// Returns the maintenance in progress at the time, the overlapping windows
+/// are merged into the one lasting until the latest of their ends.
+pub fn maintenance_at(
+    windows: &[MaintenanceWindow],
+    lead_time: chrono::Duration,
+    now: DateTime<Utc>,
+) -> Option<ForcedExitMaintenance> {
+    windows
+        .iter()
+        .filter_map(|window| {
+            active_until(window, now, lead_time).map(|until| (until, &window.reason))
+        })
+        .max_by_key(|(until, _)| *until)
+        .map(|(until, reason)| ForcedExitMaintenance {
+            until,
+            reason: reason.clone(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use zksync_types::forced_exit_requests::MaintenanceRecurrence;
+
+    use super::*;
+
+    #[test]
+    fn maintenance_windows() {
+        let time = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        let lead_time = chrono::Duration::minutes(5);
+        let migration = MaintenanceWindow {
+            start: time("2022-09-01T03:00:00Z"),
+            end: time("2022-09-01T04:00:00Z"),
+            reason: "Database migration".to_owned(),
+            recurrence: None,
+        };
+        let backups = MaintenanceWindow {
+            start: time("2022-08-01T03:30:00Z"),
+            end: time("2022-08-01T04:30:00Z"),
+            reason: "Backups".to_owned(),
+            recurrence: Some(MaintenanceRecurrence::Daily),
+        };
+
+        assert_eq!(
+            active_until(&migration, time("2022-09-01T02:54:59Z"), lead_time),
+            None
+        );
+        // The submissions pause shortly before the window
+        assert_eq!(
+            active_until(&migration, time("2022-09-01T02:55:00Z"), lead_time),
+            Some(migration.end)
+        );
+        assert_eq!(
+            active_until(&migration, time("2022-09-01T04:00:00Z"), lead_time),
+            None
+        );
+
+        // The recurring window is in progress every day
+        assert_eq!(
+            active_until(&backups, time("2022-07-31T04:00:00Z"), lead_time),
+            None
+        );
+        assert_eq!(
+            active_until(&backups, time("2022-08-20T04:00:00Z"), lead_time),
+            Some(time("2022-08-20T04:30:00Z"))
+        );
+        assert_eq!(
+            active_until(&backups, time("2022-08-20T05:00:00Z"), lead_time),
+            None
+        );
+
+        let windows = [migration, backups];
+        assert_eq!(
+            maintenance_at(&windows, lead_time, time("2022-09-01T03:45:00Z")),
+            Some(ForcedExitMaintenance {
+                until: time("2022-09-01T04:30:00Z"),
+                reason: "Backups".to_owned(),
+            })
+        );
+        assert_eq!(
+            maintenance_at(&windows, lead_time, time("2022-09-01T03:00:00Z"))
+                .map(|maintenance| maintenance.reason),
+            Some("Database migration".to_owned())
+        );
+        assert_eq!(
+            maintenance_at(&windows, lead_time, time("2022-09-01T12:00:00Z")),
+            None
+        );
+    }
+}
@@ -0,0 +1,1322 @@
+//! Matching of the payments with the forced exit requests they are made for.
+//!
+//! The matcher is run by the sender for every payment it receives, and by the admin API
+//! to explain to the operators how the payment is (or would be) matched. Every decision
+//! made along the way is reported to the `MatchObserver` with the inputs it was made on,
+//! the sender logs the suspicious ones, while the explanation collects them all.
+
+use std::{convert::TryInto, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use num::{BigUint, Zero};
+
+use zksync_config::ForcedExitRequestsConfig;
+use zksync_storage::ConnectionPool;
+use zksync_types::{
+    forced_exit_requests::{
+        check_digit, id_space_size, legacy_pay_exactly, overpayment_tolerance, AmountIdError,
+        ExpectedForcedExitPayment, ForcedExitPaymentTerms, ForcedExitRequest, ForcedExitRequestId,
+        FundsReceivedEvent, PaymentMatchScheme, PaymentMatchStep, PaymentRejection,
+        MAX_DIGITS_IN_ID,
+    },
+    H256,
+};
+
+/// The requests and the registered payments the matcher looks up.
+#[async_trait::async_trait]
+pub trait PaymentMatchSource: Send + Sync {
+    async fn get_request_by_id(
+        &self,
+        id: ForcedExitRequestId,
+    ) -> anyhow::Result<Option<ForcedExitRequest>>;
+    async fn get_request_by_public_id(
+        &self,
+        public_id: ForcedExitRequestId,
+    ) -> anyhow::Result<Option<ForcedExitRequest>>;
+    /// Loads the requests payable at `submission_time`, the exact amounts of which are
+    /// the closest ones not exceeding `max_amount`, the closest one first. At most two
+    /// requests are loaded, the next one tells whether the closest is the only candidate.
+    async fn get_requests_by_closest_amount(
+        &self,
+        max_amount: &BigUint,
+        submission_time: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<ForcedExitRequest>>;
+    async fn get_expected_payment(
+        &self,
+        eth_tx_hash: H256,
+    ) -> anyhow::Result<Option<ExpectedForcedExitPayment>>;
+}
+
+#[async_trait::async_trait]
+impl PaymentMatchSource for ConnectionPool {
+    async fn get_request_by_id(
+        &self,
+        id: ForcedExitRequestId,
+    ) -> anyhow::Result<Option<ForcedExitRequest>> {
+        let mut storage = self.access_storage().await?;
+        let request = storage
+            .forced_exit_requests_schema()
+            .get_request_by_id(id)
+            .await?;
+        Ok(request)
+    }
+
+    async fn get_request_by_public_id(
+        &self,
+        public_id: ForcedExitRequestId,
+    ) -> anyhow::Result<Option<ForcedExitRequest>> {
+        let mut storage = self.access_storage().await?;
+        let request = storage
+            .forced_exit_requests_schema()
+            .get_request_by_public_id(public_id)
+            .await?;
+        Ok(request)
+    }
+
+    async fn get_requests_by_closest_amount(
+        &self,
+        max_amount: &BigUint,
+        submission_time: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<ForcedExitRequest>> {
+        let mut storage = self.access_storage().await?;
+        let requests = storage
+            .forced_exit_requests_schema()
+            .get_requests_by_closest_amount(max_amount, submission_time)
+            .await?;
+        Ok(requests)
+    }
+
+    async fn get_expected_payment(
+        &self,
+        eth_tx_hash: H256,
+    ) -> anyhow::Result<Option<ExpectedForcedExitPayment>> {
+        let mut storage = self.access_storage().await?;
+        let expected = storage
+            .forced_exit_requests_schema()
+            .get_expected_payment(eth_tx_hash)
+            .await?;
+        Ok(expected)
+    }
+}
+
+/// Receives the steps of the matching as they are made.
+pub trait MatchObserver: Send {
+    fn observe(&mut self, step: &PaymentMatchStep);
+}
+
+/// Collects the steps for the explanation.
+impl MatchObserver for Vec<PaymentMatchStep> {
+    fn observe(&mut self, step: &PaymentMatchStep) {
+        self.push(step.clone());
+    }
+}
+
+/// Ignores the steps.
+impl MatchObserver for () {
+    fn observe(&mut self, _step: &PaymentMatchStep) {}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaymentMatchOutcome {
+    Matched(ForcedExitRequest, PaymentMatchScheme),
+    /// The transaction registered in advance has paid another amount than the request asks for.
+    /// Such a payment is not matched with any other request either.
+    ExpectedAmountMismatch(ExpectedForcedExitPayment),
+    Unmatched,
+}
+
+/// Returns the reason the request can not be paid for by the payment submitted at the given time.
+pub fn payment_rejection(
+    request: &ForcedExitRequest,
+    submission_time: DateTime<Utc>,
+) -> Option<PaymentRejection> {
+    if request.fulfilled_at.is_some() {
+        // We should not re-process requests that were fulfilled before
+        Some(PaymentRejection::Fulfilled)
+    } else if matches!(request.cancellation, Some(kind) if !kind.allows_reprocessing()) {
+        // The request is only processed again once the operators extend it
+        Some(PaymentRejection::Cancelled)
+    } else if request.valid_until <= submission_time {
+        Some(PaymentRejection::Expired)
+    } else {
+        None
+    }
+}
+
+/// Checks that the price does not overlap with the id of the request added to it.
+///
+/// Otherwise the amount paid for the request yields another id and another price
+/// once the id is extracted from it, so the payment can not be matched by the amount.
+pub fn is_price_aligned(price: &BigUint, digits_in_id: u8) -> bool {
+    (price % id_space_size(digits_in_id)).is_zero()
+}
+
+/// Extracts the id of the request followed by its check digit from the lowest
+/// `digits_in_id + 1` digits of the amount, see `pay_exactly`.
+pub fn extract_id_from_amount(
+    amount: BigUint,
+    digits_in_id: u32,
+) -> Result<(ForcedExitRequestId, BigUint), AmountIdError> {
+    let digits = digits_in_id
+        .checked_add(1)
+        .ok_or(AmountIdError::TooManyDigits {
+            digits: digits_in_id,
+        })?;
+    let (encoded, amount) = extract_legacy_id_from_amount(amount, digits)?;
+    let id = encoded / 10;
+
+    if encoded % 10 == i64::from(check_digit(id)) {
+        Ok((id, amount))
+    } else {
+        Err(AmountIdError::CheckDigitMismatch)
+    }
+}
+
+/// Extracts the id of the request from the lowest `digits_in_id` digits of the amount,
+/// the way the requests created before the check digit was added are paid for.
+pub fn extract_legacy_id_from_amount(
+    amount: BigUint,
+    digits_in_id: u32,
+) -> Result<(ForcedExitRequestId, BigUint), AmountIdError> {
+    let id_space_size = 10_i64
+        .checked_pow(digits_in_id)
+        .ok_or(AmountIdError::TooManyDigits {
+            digits: digits_in_id,
+        })?;
+    let id_space_size = BigUint::from(id_space_size as u64);
+
+    // The whole amount would be taken for the id otherwise
+    if amount < id_space_size {
+        return Err(AmountIdError::AmountTooSmall);
+    }
+
+    // After extracting the id we need to delete it
+    // to make sure that amount is the same as in the db
+    let id = &amount % &id_space_size;
+    let amount = amount - &id;
+
+    // The id is below the id space, which fits into `i64`
+    let id = id.try_into().map_err(|_| AmountIdError::TooManyDigits {
+        digits: digits_in_id,
+    })?;
+    Ok((id, amount))
+}
+
+/// Extracts the id of the request from the amount the way the payment terms
+/// of the request encode it.
+pub fn extract_id_by_terms(
+    amount: BigUint,
+    terms: &ForcedExitPaymentTerms,
+) -> Result<(ForcedExitRequestId, BigUint), AmountIdError> {
+    if terms.check_digit {
+        extract_id_from_amount(amount, terms.digits_in_id.into())
+    } else {
+        extract_legacy_id_from_amount(amount, terms.digits_in_id.into())
+    }
+}
+
+/// Returns the distinct ids the amount decodes to under any of the supported schemes,
+/// the ones decoded with the given number of digits come first. The amounts of
+/// the requests created with another number of digits are decoded to their ids too.
+pub fn candidate_ids_from_amount(amount: &BigUint, digits_in_id: u32) -> Vec<ForcedExitRequestId> {
+    let supported = 1..=MAX_DIGITS_IN_ID;
+    let preferred = Some(digits_in_id).filter(|digits| supported.contains(digits));
+    let digits = preferred
+        .into_iter()
+        .chain(supported.clone().filter(|&digits| digits != digits_in_id));
+
+    let mut ids = Vec::new();
+    for digits in digits {
+        let decoded = [
+            extract_id_from_amount(amount.clone(), digits),
+            extract_legacy_id_from_amount(amount.clone(), digits),
+        ];
+        for (id, _) in decoded.iter().flatten() {
+            if !ids.contains(id) {
+                ids.push(*id);
+            }
+        }
+    }
+    ids
+}
+
+/// The terms the payments for the request created before they were stored along with it
+/// are matched by. The scheme of its amount is recognized by `pay_exactly`, the rest is
+/// taken from the current config.
+pub fn request_payment_terms(
+    config: &ForcedExitRequestsConfig,
+    request: &ForcedExitRequest,
+) -> ForcedExitPaymentTerms {
+    ForcedExitPaymentTerms {
+        digits_in_id: config.digits_in_id,
+        check_digit: request.pay_exactly
+            != legacy_pay_exactly(&request.price_in_wei, request.public_id),
+        overpayment_tolerance: overpayment_tolerance(
+            &request.price_in_wei,
+            config.overpayment_tolerance,
+            config.overpayment_tolerance_percent,
+        ),
+        expiration_grace_period: config.expiration_grace_period,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PaymentMatcher<'a> {
+    config: &'a ForcedExitRequestsConfig,
+    /// Whether the transactions registered in advance are looked up.
+    expected_payments: bool,
+    /// Whether the payments exceeding the exact amount of the request are matched.
+    overpayments: bool,
+}
+
+impl<'a> PaymentMatcher<'a> {
+    pub fn new(
+        config: &'a ForcedExitRequestsConfig,
+        expected_payments: bool,
+        overpayments: bool,
+    ) -> Self {
+        Self {
+            config,
+            expected_payments,
+            overpayments,
+        }
+    }
+
+    /// Finds the request the payment was made for.
+    ///
+    /// The transaction registered in advance is matched by its hash, otherwise if the
+    /// explicit id is present, the amount is not used to look for another request.
+    /// The outcome is reported to the observer as the last step.
+    pub async fn match_payment<S, O>(
+        &self,
+        source: &S,
+        payment: &FundsReceivedEvent,
+        submission_time: DateTime<Utc>,
+        observer: &mut O,
+    ) -> anyhow::Result<PaymentMatchOutcome>
+    where
+        S: PaymentMatchSource + ?Sized,
+        O: MatchObserver,
+    {
+        let outcome = self
+            .find_request(source, payment, submission_time, observer)
+            .await?;
+        match &outcome {
+            PaymentMatchOutcome::Matched(request, match_scheme) => {
+                observer.observe(&PaymentMatchStep::Matched {
+                    request_id: request.id,
+                    match_scheme: *match_scheme,
+                })
+            }
+            _ => observer.observe(&PaymentMatchStep::Unmatched),
+        }
+        Ok(outcome)
+    }
+
+    async fn find_request<S, O>(
+        &self,
+        source: &S,
+        payment: &FundsReceivedEvent,
+        submission_time: DateTime<Utc>,
+        observer: &mut O,
+    ) -> anyhow::Result<PaymentMatchOutcome>
+    where
+        S: PaymentMatchSource + ?Sized,
+        O: MatchObserver,
+    {
+        if let Some(expected) = self.expected_payment(source, payment).await? {
+            observer.observe(&PaymentMatchStep::ExpectedPaymentLookup {
+                eth_tx_hash: expected.eth_tx_hash,
+                request_id: Some(expected.request_id),
+            });
+            return self
+                .match_expected_payment(source, payment, expected, submission_time, observer)
+                .await;
+        }
+        if let (Some(eth_tx_hash), true) = (payment.eth_tx_hash, self.expected_payments) {
+            observer.observe(&PaymentMatchStep::ExpectedPaymentLookup {
+                eth_tx_hash,
+                request_id: None,
+            });
+        }
+
+        let public_id = match payment.request_id {
+            Some(public_id) => public_id,
+            None => {
+                return self
+                    .match_amount_payment(source, &payment.amount, submission_time, observer)
+                    .await
+            }
+        };
+        observer.observe(&PaymentMatchStep::ExplicitId { public_id });
+
+        let request = self
+            .lookup_request(source, public_id, observer)
+            .await?
+            .filter(|request| {
+                self.check_request_with_explicit_id(
+                    &payment.amount,
+                    submission_time,
+                    request,
+                    observer,
+                )
+            });
+        Ok(request.map_or(PaymentMatchOutcome::Unmatched, |request| {
+            PaymentMatchOutcome::Matched(request, PaymentMatchScheme::ExplicitId)
+        }))
+    }
+
+    /// Loads the registration of the payment, if the partner has registered
+    /// its transaction in advance.
+    pub async fn expected_payment<S>(
+        &self,
+        source: &S,
+        payment: &FundsReceivedEvent,
+    ) -> anyhow::Result<Option<ExpectedForcedExitPayment>>
+    where
+        S: PaymentMatchSource + ?Sized,
+    {
+        match payment.eth_tx_hash {
+            Some(eth_tx_hash) if self.expected_payments => {
+                source.get_expected_payment(eth_tx_hash).await
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // The transaction registered in advance pays exactly the amount of the payment
+    // instructions. Any other amount is flagged for the partner rather than accepted,
+    // since the hash binds the payment to the request regardless of the amount
+    async fn match_expected_payment<S, O>(
+        &self,
+        source: &S,
+        payment: &FundsReceivedEvent,
+        expected: ExpectedForcedExitPayment,
+        submission_time: DateTime<Utc>,
+        observer: &mut O,
+    ) -> anyhow::Result<PaymentMatchOutcome>
+    where
+        S: PaymentMatchSource + ?Sized,
+        O: MatchObserver,
+    {
+        let request = match source.get_request_by_id(expected.request_id).await? {
+            Some(request) => request,
+            None => return Ok(PaymentMatchOutcome::Unmatched),
+        };
+        let matches = payment.amount.to_string() == request.pay_exactly;
+        observer.observe(&PaymentMatchStep::ExpectedPaymentAmount {
+            request_id: request.id,
+            paid: payment.amount.clone(),
+            pay_exactly: request.pay_exactly.clone(),
+            matches,
+        });
+        if !matches {
+            return Ok(PaymentMatchOutcome::ExpectedAmountMismatch(expected));
+        }
+
+        if self.is_request_payable(submission_time, &request, observer) {
+            Ok(PaymentMatchOutcome::Matched(
+                request,
+                PaymentMatchScheme::ExpectedPayment,
+            ))
+        } else {
+            Ok(PaymentMatchOutcome::Unmatched)
+        }
+    }
+
+    /// Finds the request by the id encoded in the amount.
+    ///
+    /// The amount is decoded to every id it may be paid for, and each request found
+    /// decodes it once again by its own payment terms: the amount is only matched with
+    /// the request it yields the id and the price of. The ids decoded by the current
+    /// config are looked up first, the amount is matched with the first payable request.
+    async fn match_amount_payment<S, O>(
+        &self,
+        source: &S,
+        paid: &BigUint,
+        submission_time: DateTime<Utc>,
+        observer: &mut O,
+    ) -> anyhow::Result<PaymentMatchOutcome>
+    where
+        S: PaymentMatchSource + ?Sized,
+        O: MatchObserver,
+    {
+        let digits_in_id = self.config.digits_in_id as u32;
+        let ids = candidate_ids_from_amount(paid, digits_in_id);
+        observer.observe(&PaymentMatchStep::CandidateIds {
+            digits_in_id,
+            ids: ids.clone(),
+        });
+
+        for id in ids {
+            let request = match self.lookup_request(source, id, observer).await? {
+                Some(request) => request,
+                None => continue,
+            };
+            let terms = match self.payment_terms(&request, observer) {
+                Some(terms) => terms,
+                None => continue,
+            };
+            let decoded = extract_id_by_terms(paid.clone(), &terms);
+            observer.observe(&PaymentMatchStep::AmountDecoding {
+                request_id: request.id,
+                public_id: id,
+                decoded_id: decoded.as_ref().ok().map(|(decoded_id, _)| *decoded_id),
+                error: decoded.as_ref().err().copied(),
+            });
+            let decoded = match decoded {
+                Ok((decoded_id, amount)) if decoded_id == id => amount,
+                _ => continue,
+            };
+            self.check_price_alignment(&request, &terms, observer);
+
+            if self.check_request(&decoded, submission_time, &request, observer)
+                && self.check_pay_exactly(paid, &request, observer)
+            {
+                return Ok(PaymentMatchOutcome::Matched(
+                    request,
+                    PaymentMatchScheme::AmountDigits,
+                ));
+            }
+        }
+
+        let overpaid = self
+            .match_overpayment(source, paid, submission_time, observer)
+            .await?;
+        Ok(overpaid.map_or(PaymentMatchOutcome::Unmatched, |request| {
+            PaymentMatchOutcome::Matched(request, PaymentMatchScheme::AmountDigits)
+        }))
+    }
+
+    /// Finds the request paid for with more than its exact amount.
+    ///
+    /// The excess shifts the digits of the id, so the request is looked up by the closest
+    /// exact amount not exceeding the paid one. The underpayments are never matched.
+    ///
+    /// The exact amounts of the requests of the same price are only a few wei apart, since
+    /// they differ by the id in the lowest digits. So the payment is only matched if no other
+    /// payable request has the exact amount within the tolerance, otherwise the payment may
+    /// as well be the overpayment of the other request and is left to the operators.
+    async fn match_overpayment<S, O>(
+        &self,
+        source: &S,
+        paid: &BigUint,
+        submission_time: DateTime<Utc>,
+        observer: &mut O,
+    ) -> anyhow::Result<Option<ForcedExitRequest>>
+    where
+        S: PaymentMatchSource + ?Sized,
+        O: MatchObserver,
+    {
+        if !self.overpayments {
+            return Ok(None);
+        }
+
+        let mut requests = source
+            .get_requests_by_closest_amount(paid, submission_time)
+            .await?
+            .into_iter();
+        let (request, next) = (requests.next(), requests.next());
+        observer.observe(&PaymentMatchStep::OverpaymentLookup {
+            request_id: request.as_ref().map(|request| request.id),
+        });
+        let request = match request {
+            Some(request) => request,
+            None => return Ok(None),
+        };
+        let pay_exactly = BigUint::from_str(&request.pay_exactly)?;
+        let tolerance = match self.payment_terms(&request, observer) {
+            Some(terms) => terms.overpayment_tolerance,
+            None => return Ok(None),
+        };
+        // The closest request paid for exactly has already been rejected above,
+        // it is not turned into an overpayment of itself
+        let accepted = *paid > pay_exactly && paid - &pay_exactly <= tolerance;
+        observer.observe(&PaymentMatchStep::OverpaymentComparison {
+            request_id: request.id,
+            paid: paid.clone(),
+            pay_exactly: request.pay_exactly.clone(),
+            tolerance: tolerance.clone(),
+            accepted,
+        });
+        if !accepted {
+            return Ok(None);
+        }
+
+        // The next request is the closest one below, so no other request is within
+        // the tolerance unless this one is
+        if let Some(next) = next {
+            if paid - BigUint::from_str(&next.pay_exactly)? <= tolerance {
+                observer.observe(&PaymentMatchStep::AmbiguousOverpayment {
+                    request_id: request.id,
+                    other_request_id: next.id,
+                });
+                return Ok(None);
+            }
+        }
+
+        if self.is_request_payable(submission_time, &request, observer) {
+            Ok(Some(request))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn lookup_request<S, O>(
+        &self,
+        source: &S,
+        public_id: ForcedExitRequestId,
+        observer: &mut O,
+    ) -> anyhow::Result<Option<ForcedExitRequest>>
+    where
+        S: PaymentMatchSource + ?Sized,
+        O: MatchObserver,
+    {
+        let request = source.get_request_by_public_id(public_id).await?;
+        observer.observe(&PaymentMatchStep::RequestLookup {
+            public_id,
+            request_id: request.as_ref().map(|request| request.id),
+        });
+        Ok(request)
+    }
+
+    // Checks that the request still can be paid for
+    fn is_request_payable<O: MatchObserver>(
+        &self,
+        submission_time: DateTime<Utc>,
+        request: &ForcedExitRequest,
+        observer: &mut O,
+    ) -> bool {
+        let rejection = payment_rejection(request, submission_time);
+        observer.observe(&PaymentMatchStep::RequestPayability {
+            request_id: request.id,
+            valid_until: request.valid_until,
+            submission_time,
+            fulfilled_at: request.fulfilled_at,
+            cancellation: request.cancellation,
+            rejection,
+        });
+        rejection.is_none()
+    }
+
+    // Checks that the request should be fulfilled for the amount left once the id is extracted
+    fn check_request<O: MatchObserver>(
+        &self,
+        amount: &BigUint,
+        submission_time: DateTime<Utc>,
+        request: &ForcedExitRequest,
+        observer: &mut O,
+    ) -> bool {
+        self.is_request_payable(submission_time, request, observer)
+            && self.compare_price(amount, request, false, observer)
+    }
+
+    /// Same as `check_request`, but for the payments which contain the id of the request
+    /// in the calldata. The amount does not carry the id in this case, so it is enough
+    /// for the payer to send at least the price of the request.
+    pub fn check_request_with_explicit_id<O: MatchObserver>(
+        &self,
+        amount: &BigUint,
+        submission_time: DateTime<Utc>,
+        request: &ForcedExitRequest,
+        observer: &mut O,
+    ) -> bool {
+        self.is_request_payable(submission_time, request, observer)
+            && self.compare_price(amount, request, true, observer)
+    }
+
+    fn compare_price<O: MatchObserver>(
+        &self,
+        paid: &BigUint,
+        request: &ForcedExitRequest,
+        explicit_id: bool,
+        observer: &mut O,
+    ) -> bool {
+        let matches = if explicit_id {
+            *paid >= request.price_in_wei
+        } else {
+            *paid == request.price_in_wei
+        };
+        observer.observe(&PaymentMatchStep::PriceComparison {
+            request_id: request.id,
+            paid: paid.clone(),
+            price: request.price_in_wei.clone(),
+            explicit_id,
+            matches,
+        });
+        matches
+    }
+
+    // The amount the id was extracted from must be the one stored with the request.
+    // Both are computed from the price and the id, so a mismatch means that one of
+    // the computations is wrong and the payment is better left unmatched
+    fn check_pay_exactly<O: MatchObserver>(
+        &self,
+        paid: &BigUint,
+        request: &ForcedExitRequest,
+        observer: &mut O,
+    ) -> bool {
+        let matches = paid.to_string() == request.pay_exactly;
+        observer.observe(&PaymentMatchStep::PayExactlyComparison {
+            request_id: request.id,
+            paid: paid.clone(),
+            pay_exactly: request.pay_exactly.clone(),
+            matches,
+        });
+        matches
+    }
+
+    // The requests created before the prices were validated may have the price
+    // overlapping with the id, such requests can only be paid for with the explicit id
+    fn check_price_alignment<O: MatchObserver>(
+        &self,
+        request: &ForcedExitRequest,
+        terms: &ForcedExitPaymentTerms,
+        observer: &mut O,
+    ) {
+        if !is_price_aligned(&request.price_in_wei, terms.amount_id_digits()) {
+            observer.observe(&PaymentMatchStep::MisalignedPrice {
+                request_id: request.id,
+                price: request.price_in_wei.clone(),
+                amount_id_digits: terms.amount_id_digits(),
+            });
+        }
+    }
+
+    /// Returns the terms the payments for the request are matched by the amount with.
+    ///
+    /// The terms are stored along with the request once it is created, so the changes
+    /// of the config do not affect the requests created before. The requests stored
+    /// without them are matched by the current config, including the ones paid for
+    /// with the id alone, which are accepted as long as `legacy_amount_ids_enabled` is set.
+    fn payment_terms<O: MatchObserver>(
+        &self,
+        request: &ForcedExitRequest,
+        observer: &mut O,
+    ) -> Option<ForcedExitPaymentTerms> {
+        let terms = match &request.payment_terms {
+            Some(terms) => Some(terms.clone()),
+            None => Some(request_payment_terms(self.config, request))
+                .filter(|terms| terms.check_digit || self.config.legacy_amount_ids_enabled),
+        };
+        observer.observe(&PaymentMatchStep::PaymentTerms {
+            request_id: request.id,
+            stored: request.payment_terms.is_some(),
+            legacy_amount_ids_enabled: self.config.legacy_amount_ids_enabled,
+            terms: terms.clone(),
+        });
+        terms
+    }
+
+    /// Returns the public id of the request the payment is made for, the amount paid for
+    /// the request itself and the way the id was determined.
+    ///
+    /// The id supplied by the payer in the calldata takes precedence over the one
+    /// encoded in the lowest digits of the amount. The amount is decoded as the amounts
+    /// of the requests created with the current config are, without the check digit
+    /// if it does not match. The amount no id can be decoded from is reported as paid
+    /// for the id 0, which no request has.
+    pub fn payment_target(
+        &self,
+        payment: &FundsReceivedEvent,
+    ) -> (ForcedExitRequestId, BigUint, PaymentMatchScheme) {
+        match payment.request_id {
+            Some(id) => (id, payment.amount.clone(), PaymentMatchScheme::ExplicitId),
+            None => {
+                let digits_in_id = self.config.digits_in_id as u32;
+                let (id, amount) = extract_id_from_amount(payment.amount.clone(), digits_in_id)
+                    .or_else(|_| {
+                        extract_legacy_id_from_amount(payment.amount.clone(), digits_in_id)
+                    })
+                    .unwrap_or_else(|_| (0, payment.amount.clone()));
+                (id, amount, PaymentMatchScheme::AmountDigits)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Add;
+
+    use zksync_types::{forced_exit_requests::pay_exactly, Address, TokenId};
+
+    use super::*;
+
+    const DIGITS_IN_ID: u8 = 8;
+
+    #[derive(Default)]
+    struct InMemorySource {
+        requests: Vec<ForcedExitRequest>,
+        expected_payments: Vec<ExpectedForcedExitPayment>,
+    }
+
+    #[async_trait::async_trait]
+    impl PaymentMatchSource for InMemorySource {
+        async fn get_request_by_id(
+            &self,
+            id: ForcedExitRequestId,
+        ) -> anyhow::Result<Option<ForcedExitRequest>> {
+            Ok(self.requests.iter().find(|r| r.id == id).cloned())
+        }
+
+        async fn get_request_by_public_id(
+            &self,
+            public_id: ForcedExitRequestId,
+        ) -> anyhow::Result<Option<ForcedExitRequest>> {
+            Ok(self
+                .requests
+                .iter()
+                .find(|r| r.public_id == public_id)
+                .cloned())
+        }
+
+        async fn get_requests_by_closest_amount(
+            &self,
+            max_amount: &BigUint,
+            submission_time: DateTime<Utc>,
+        ) -> anyhow::Result<Vec<ForcedExitRequest>> {
+            let amount = |r: &ForcedExitRequest| BigUint::from_str(&r.pay_exactly).unwrap();
+            let mut requests: Vec<_> = self
+                .requests
+                .iter()
+                .filter(|r| amount(r) <= *max_amount)
+                .filter(|r| payment_rejection(r, submission_time).is_none())
+                .cloned()
+                .collect();
+            requests.sort_by_key(|r| std::cmp::Reverse(amount(r)));
+            requests.truncate(2);
+            Ok(requests)
+        }
+
+        async fn get_expected_payment(
+            &self,
+            eth_tx_hash: H256,
+        ) -> anyhow::Result<Option<ExpectedForcedExitPayment>> {
+            Ok(self
+                .expected_payments
+                .iter()
+                .find(|p| p.eth_tx_hash == eth_tx_hash)
+                .cloned())
+        }
+    }
+
+    fn price() -> BigUint {
+        BigUint::from(10u32).pow(20)
+    }
+
+    fn terms() -> ForcedExitPaymentTerms {
+        ForcedExitPaymentTerms {
+            digits_in_id: DIGITS_IN_ID,
+            check_digit: true,
+            overpayment_tolerance: BigUint::from(0u32),
+            expiration_grace_period: 0,
+        }
+    }
+
+    fn request(id: ForcedExitRequestId, public_id: ForcedExitRequestId) -> ForcedExitRequest {
+        ForcedExitRequest {
+            id,
+            public_id,
+            target: Address::zero(),
+            tokens: vec![TokenId(1)],
+            price_in_wei: price(),
+            pay_exactly: pay_exactly(&price(), public_id),
+            valid_until: Utc::now() + chrono::Duration::days(1),
+            created_at: Utc::now(),
+            fulfilled_by: None,
+            fulfilled_at: None,
+            match_scheme: None,
+            matched_at: None,
+            cancellation: None,
+            paid_amount: None,
+            metadata: None,
+            status: Default::default(),
+            payment_terms: Some(terms()),
+        }
+    }
+
+    fn payment(amount: BigUint, eth_tx_hash: Option<H256>) -> FundsReceivedEvent {
+        FundsReceivedEvent {
+            amount,
+            request_id: None,
+            block_number: 0,
+            eth_tx_hash,
+            payer: None,
+            recipient: None,
+        }
+    }
+
+    fn config() -> ForcedExitRequestsConfig {
+        ForcedExitRequestsConfig {
+            digits_in_id: DIGITS_IN_ID,
+            legacy_amount_ids_enabled: false,
+            ..ForcedExitRequestsConfig::from_env()
+        }
+    }
+
+    // The request 123 is paid for with 10^20 + 1230, its check digit being 0
+    fn tricky_source() -> InMemorySource {
+        let expired = ForcedExitRequest {
+            valid_until: Utc::now() - chrono::Duration::hours(1),
+            ..request(2, 456)
+        };
+        InMemorySource {
+            // The amount with the id mistyped as 124 decodes to 1240 without the check digit
+            requests: vec![request(1, 123), expired, request(3, 1240)],
+            ..Default::default()
+        }
+    }
+
+    async fn explain(
+        config: &ForcedExitRequestsConfig,
+        source: &InMemorySource,
+        payment: FundsReceivedEvent,
+    ) -> (PaymentMatchOutcome, Vec<PaymentMatchStep>) {
+        let mut steps = Vec::new();
+        let outcome = PaymentMatcher::new(config, true, true)
+            .match_payment(source, &payment, Utc::now(), &mut steps)
+            .await
+            .unwrap();
+        (outcome, steps)
+    }
+
+    #[tokio::test]
+    async fn explains_the_matched_payment() {
+        let (config, source) = (config(), tricky_source());
+        let paid = price() + 1230u32;
+        let (outcome, steps) = explain(&config, &source, payment(paid.clone(), None)).await;
+
+        assert!(matches!(
+            outcome,
+            PaymentMatchOutcome::Matched(ref request, PaymentMatchScheme::AmountDigits) if request.id == 1
+        ));
+        assert!(matches!(
+            &steps[0],
+            PaymentMatchStep::CandidateIds { digits_in_id: 8, ids } if ids[0] == 123
+        ));
+        assert_eq!(
+            steps[1..3],
+            [
+                PaymentMatchStep::RequestLookup {
+                    public_id: 123,
+                    request_id: Some(1),
+                },
+                PaymentMatchStep::PaymentTerms {
+                    request_id: 1,
+                    stored: true,
+                    legacy_amount_ids_enabled: false,
+                    terms: Some(terms()),
+                },
+            ]
+        );
+        assert_eq!(
+            steps[3],
+            PaymentMatchStep::AmountDecoding {
+                request_id: 1,
+                public_id: 123,
+                decoded_id: Some(123),
+                error: None,
+            }
+        );
+        assert!(matches!(
+            &steps[4],
+            PaymentMatchStep::RequestPayability {
+                rejection: None,
+                ..
+            }
+        ));
+        assert_eq!(
+            steps[5..],
+            [
+                PaymentMatchStep::PriceComparison {
+                    request_id: 1,
+                    paid: price(),
+                    price: price(),
+                    explicit_id: false,
+                    matches: true,
+                },
+                PaymentMatchStep::PayExactlyComparison {
+                    request_id: 1,
+                    paid,
+                    pay_exactly: pay_exactly(&price(), 123),
+                    matches: true,
+                },
+                PaymentMatchStep::Matched {
+                    request_id: 1,
+                    match_scheme: PaymentMatchScheme::AmountDigits,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn explains_the_tricky_payments() {
+        let (config, source) = (config(), tricky_source());
+
+        // The mistyped id fails the check digit under the current config, while the amount
+        // decoded without it yields the request which does not accept such amounts
+        let (outcome, steps) = explain(&config, &source, payment(price() + 1240u32, None)).await;
+        assert_eq!(outcome, PaymentMatchOutcome::Unmatched);
+        match &steps[0] {
+            PaymentMatchStep::CandidateIds { ids, .. } => {
+                assert!(!ids.contains(&123) && !ids.contains(&124));
+                assert_eq!(ids[0], 1240);
+            }
+            step => panic!("Unexpected step {:?}", step),
+        }
+        assert!(steps.contains(&PaymentMatchStep::AmountDecoding {
+            request_id: 3,
+            public_id: 1240,
+            decoded_id: None,
+            error: Some(AmountIdError::CheckDigitMismatch),
+        }));
+        // The closest request is paid for exactly, it is not overpaid by the mistyped amount
+        assert!(steps.contains(&PaymentMatchStep::OverpaymentLookup {
+            request_id: Some(1)
+        }));
+        assert!(steps.iter().any(|step| matches!(
+            step,
+            PaymentMatchStep::OverpaymentComparison {
+                request_id: 1,
+                accepted: false,
+                ..
+            }
+        )));
+        assert_eq!(steps.last(), Some(&PaymentMatchStep::Unmatched));
+
+        // The expired request is found, but not paid for, the price is not even compared
+        let paid = BigUint::from_str(&pay_exactly(&price(), 456)).unwrap();
+        let (outcome, steps) = explain(&config, &source, payment(paid, None)).await;
+        assert_eq!(outcome, PaymentMatchOutcome::Unmatched);
+        assert!(steps.iter().any(|step| matches!(
+            step,
+            PaymentMatchStep::RequestPayability {
+                request_id: 2,
+                rejection: Some(PaymentRejection::Expired),
+                ..
+            }
+        )));
+        assert!(!steps.iter().any(|step| matches!(
+            step,
+            PaymentMatchStep::PriceComparison { request_id: 2, .. }
+        )));
+        assert_eq!(steps.last(), Some(&PaymentMatchStep::Unmatched));
+
+        // The steps are not collected by the production observer, the outcome is the same
+        let paid = price() + 1230u32;
+        let outcome = PaymentMatcher::new(&config, true, true)
+            .match_payment(&source, &payment(paid, None), Utc::now(), &mut ())
+            .await
+            .unwrap();
+        assert!(matches!(outcome, PaymentMatchOutcome::Matched(request, _) if request.id == 1));
+    }
+
+    #[tokio::test]
+    async fn overpayment_of_adjacent_requests() {
+        let config = config();
+        let tolerant_request = |public_id| ForcedExitRequest {
+            payment_terms: Some(ForcedExitPaymentTerms {
+                overpayment_tolerance: BigUint::from(100u32),
+                ..terms()
+            }),
+            ..request(public_id, public_id)
+        };
+        // The requests 123..=127 are paid for with 1230, 1248, 1255, 1263 and 1271 over the price
+        let source = InMemorySource {
+            requests: (123..=127).map(tolerant_request).collect(),
+            ..Default::default()
+        };
+
+        // The request 125 overpaid by 15 wei reads as the id 127 failing the check digit,
+        // the closest request 126 is within the tolerance, but so is the request 125
+        let paid = price() + 1270u32;
+        let (outcome, steps) = explain(&config, &source, payment(paid.clone(), None)).await;
+        assert_eq!(outcome, PaymentMatchOutcome::Unmatched);
+        assert!(steps.contains(&PaymentMatchStep::OverpaymentLookup {
+            request_id: Some(126)
+        }));
+        assert!(steps.contains(&PaymentMatchStep::AmbiguousOverpayment {
+            request_id: 126,
+            other_request_id: 125,
+        }));
+        assert!(!steps
+            .iter()
+            .any(|step| matches!(step, PaymentMatchStep::Matched { .. })));
+
+        // The neighbours which can no longer be paid for are not the candidates
+        let mut source = source;
+        for request in &mut source.requests {
+            if request.id != 125 {
+                request.fulfilled_at = Some(Utc::now());
+            }
+        }
+        let (outcome, _) = explain(&config, &source, payment(paid, None)).await;
+        assert!(matches!(outcome, PaymentMatchOutcome::Matched(request, _) if request.id == 125));
+    }
+
+    #[tokio::test]
+    async fn expected_payment_with_another_amount() {
+        let config = config();
+        let eth_tx_hash = H256::repeat_byte(1);
+        let mut source = tricky_source();
+        source.expected_payments.push(ExpectedForcedExitPayment {
+            request_id: 1,
+            eth_tx_hash,
+            registered_at: Utc::now(),
+            mismatched_amount: None,
+        });
+
+        // The registered transaction is not matched by the amount with any other request
+        let paid = price() + 1240u32;
+        let (outcome, steps) =
+            explain(&config, &source, payment(paid.clone(), Some(eth_tx_hash))).await;
+        assert!(matches!(
+            outcome,
+            PaymentMatchOutcome::ExpectedAmountMismatch(ref expected) if expected.request_id == 1
+        ));
+        assert_eq!(
+            steps,
+            vec![
+                PaymentMatchStep::ExpectedPaymentLookup {
+                    eth_tx_hash,
+                    request_id: Some(1),
+                },
+                PaymentMatchStep::ExpectedPaymentAmount {
+                    request_id: 1,
+                    paid,
+                    pay_exactly: pay_exactly(&price(), 123),
+                    matches: false,
+                },
+                PaymentMatchStep::Unmatched,
+            ]
+        );
+    }
+
+    #[test]
+    fn price_alignment() {
+        assert!(is_price_aligned(&BigUint::from(12000u32), 3));
+        assert!(is_price_aligned(&BigUint::zero(), 3));
+        assert!(!is_price_aligned(&BigUint::from(12001u32), 3));
+        assert!(!is_price_aligned(&BigUint::from(1_000_000_000u32), 13));
+    }
+
+    fn test_extraction_for_id_amount(
+        amount: BigUint,
+        digits_in_id: u32,
+        expected_id: i64,
+        expected_amount: BigUint,
+    ) {
+        let (id, remain_amount) = extract_legacy_id_from_amount(amount, digits_in_id).unwrap();
+
+        assert_eq!(id, expected_id);
+        assert_eq!(remain_amount, expected_amount);
+    }
+
+    #[test]
+    fn test_extract_id_from_amount() {
+        // Basic extraction
+        test_extraction_for_id_amount(
+            BigUint::from_str("12211").unwrap(),
+            3,
+            211,
+            BigUint::from_str("12000").unwrap(),
+        );
+
+        // The amount of the id space itself is paid for the id 0
+        test_extraction_for_id_amount(
+            BigUint::from_str("1000").unwrap(),
+            3,
+            0,
+            BigUint::from_str("1000").unwrap(),
+        );
+
+        // Here we test with some really large number, which could not possible
+        // fit into 2^64
+        let ten = BigUint::from_str("10").unwrap();
+        let id: u32 = 211;
+        let expected_amount = ten.pow(100);
+        let amount = expected_amount.clone().add(id);
+        test_extraction_for_id_amount(amount, 3, id.try_into().unwrap(), expected_amount);
+    }
+
+    #[test]
+    fn test_extract_checked_id_from_amount() {
+        // 2113 is the id 211 followed by its check digit
+        assert_eq!(
+            extract_id_from_amount(BigUint::from_str("120000002113").unwrap(), 3),
+            Ok((211, BigUint::from_str("120000000000").unwrap()))
+        );
+        assert_eq!(
+            extract_id_from_amount(BigUint::from_str("120000002117").unwrap(), 3),
+            Err(AmountIdError::CheckDigitMismatch)
+        );
+        // The mistyped id does not match the check digit
+        assert_eq!(
+            extract_id_from_amount(BigUint::from_str("120000002123").unwrap(), 3),
+            Err(AmountIdError::CheckDigitMismatch)
+        );
+
+        // The amount to pay for the request is decoded back to its id and price
+        let price = BigUint::from_str("30000000000000000").unwrap();
+        for id in [1, 9, 10, 211, 9_999_999_999_999] {
+            let amount = BigUint::from_str(&pay_exactly(&price, id)).unwrap();
+            assert_eq!(extract_id_from_amount(amount, 13), Ok((id, price.clone())));
+        }
+    }
+
+    // A simple deterministic generator, so the failures are reproducible
+    fn pseudo_random_amounts(count: usize) -> impl Iterator<Item = BigUint> {
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        (0..count).map(move |_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            BigUint::from(state) * 1_000_003u32 + (state >> 11)
+        })
+    }
+
+    #[test]
+    fn random_amounts_rarely_pass_the_check() {
+        // A single check digit lets a tenth of the arbitrary amounts through
+        let count = 20_000;
+        let passed = pseudo_random_amounts(count)
+            .filter(|amount| extract_id_from_amount(amount.clone(), 13).is_ok())
+            .count();
+        assert!(passed * 100 < count * 11, "{} of {} passed", passed, count);
+        assert!(passed * 100 > count * 9, "{} of {} passed", passed, count);
+
+        // The ones which pass are the exact amounts of the requests with the decoded ids,
+        // so they are still rejected unless the price matches as well
+        for amount in pseudo_random_amounts(count) {
+            if let Ok((id, price)) = extract_id_from_amount(amount.clone(), 13) {
+                assert_eq!(pay_exactly(&price, id), amount.to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn mistyped_amounts_never_pass_the_check() {
+        let price = BigUint::from_str("30000000000000000").unwrap();
+        for amount in pseudo_random_amounts(200) {
+            let id: i64 = (amount % 10_000_000_000_000u64).try_into().unwrap();
+            let digits = pay_exactly(&price, id).into_bytes();
+            let tail = digits.len() - 14;
+
+            // Any single digit of the id or the check digit changed
+            for position in tail..digits.len() {
+                for digit in b'0'..=b'9' {
+                    if digit == digits[position] {
+                        continue;
+                    }
+                    let mut mistyped = digits.clone();
+                    mistyped[position] = digit;
+                    let mistyped = BigUint::from_str(std::str::from_utf8(&mistyped).unwrap());
+                    assert!(extract_id_from_amount(mistyped.unwrap(), 13).is_err());
+                }
+            }
+            // The adjacent digits swapped, except for 09 and 90 which Luhn does not tell apart
+            for position in tail..digits.len() - 1 {
+                let pair = (digits[position], digits[position + 1]);
+                if pair.0 == pair.1 || pair == (b'0', b'9') || pair == (b'9', b'0') {
+                    continue;
+                }
+                let mut swapped = digits.clone();
+                swapped.swap(position, position + 1);
+                let swapped = BigUint::from_str(std::str::from_utf8(&swapped).unwrap());
+                assert!(extract_id_from_amount(swapped.unwrap(), 13).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn amounts_without_price_are_rejected() {
+        // Nothing is paid for the zero amount
+        assert_eq!(
+            extract_id_from_amount(BigUint::zero(), 3),
+            Err(AmountIdError::AmountTooSmall)
+        );
+        assert_eq!(
+            extract_legacy_id_from_amount(BigUint::zero(), 3),
+            Err(AmountIdError::AmountTooSmall)
+        );
+
+        // The amount equal to the id leaves no price to pay
+        assert_eq!(
+            extract_legacy_id_from_amount(BigUint::from(211u32), 3),
+            Err(AmountIdError::AmountTooSmall)
+        );
+        let amount = BigUint::from_str(&pay_exactly(&BigUint::zero(), 211)).unwrap();
+        assert_eq!(amount, BigUint::from(2113u32));
+        assert_eq!(
+            extract_id_from_amount(amount, 3),
+            Err(AmountIdError::AmountTooSmall)
+        );
+    }
+
+    #[test]
+    fn max_digits_in_id() {
+        // The largest ids are decoded with the most digits allowed
+        let price = BigUint::from(10u32).pow(40);
+        let id = 10_i64.pow(MAX_DIGITS_IN_ID) - 1;
+        let amount = BigUint::from_str(&pay_exactly(&price, id)).unwrap();
+        assert_eq!(
+            extract_id_from_amount(amount.clone(), MAX_DIGITS_IN_ID),
+            Ok((id, price))
+        );
+
+        // The id space of one more digit does not fit into i64 along with the check digit
+        let too_many = AmountIdError::TooManyDigits {
+            digits: MAX_DIGITS_IN_ID + 2,
+        };
+        assert_eq!(
+            extract_id_from_amount(amount.clone(), MAX_DIGITS_IN_ID + 1),
+            Err(too_many)
+        );
+        assert_eq!(
+            extract_id_from_amount(amount.clone(), u32::MAX),
+            Err(AmountIdError::TooManyDigits { digits: u32::MAX })
+        );
+        assert!(extract_legacy_id_from_amount(amount.clone(), MAX_DIGITS_IN_ID + 1).is_ok());
+        assert_eq!(
+            extract_legacy_id_from_amount(amount, MAX_DIGITS_IN_ID + 2),
+            Err(too_many)
+        );
+    }
+
+    #[test]
+    fn candidate_ids() {
+        let price = BigUint::from(10u32).pow(20);
+        // The amount is decoded to the same id with any number of digits fitting it
+        let amount = BigUint::from_str(&pay_exactly(&price, 123)).unwrap();
+        let candidates = candidate_ids_from_amount(&amount, 9);
+        assert_eq!(candidates[0], 123);
+        let mut distinct = candidates.clone();
+        distinct.sort_unstable();
+        distinct.dedup();
+        assert_eq!(distinct.len(), candidates.len());
+
+        let terms = ForcedExitPaymentTerms {
+            digits_in_id: 5,
+            check_digit: true,
+            overpayment_tolerance: BigUint::zero(),
+            expiration_grace_period: 0,
+        };
+        assert_eq!(
+            extract_id_by_terms(amount.clone(), &terms),
+            Ok((123, price.clone()))
+        );
+
+        // The legacy amount is decoded by the terms without the check digit
+        let legacy = price.clone() + 123u32;
+        assert!(candidate_ids_from_amount(&legacy, 9).contains(&123));
+        let legacy_terms = ForcedExitPaymentTerms {
+            check_digit: false,
+            ..terms
+        };
+        assert_eq!(extract_id_by_terms(legacy, &legacy_terms), Ok((123, price)));
+
+        // Nothing is decoded from the amount without a price
+        assert!(candidate_ids_from_amount(&BigUint::zero(), 9).is_empty());
+    }
+}
@@ -0,0 +1,524 @@
+//! Planning of the transactions the paid forced exit requests are fulfilled with.
+//!
+//! The sender plans the transactions it is about to send, while the admin API plans
+//! the same ones for the backlog without sending anything, so both report the same outcome.
+
+use chrono::{DateTime, Utc};
+use num::{BigUint, Zero};
+
+use zksync_types::{
+    forced_exit_requests::{
+        ForcedExitBacklogReport, ForcedExitBlockedRequests, ForcedExitBlocker, ForcedExitPreflight,
+        ForcedExitRequest, ForcedExitTargetCheck, ForcedExitTokenFees, PlannedFeePayment,
+        PlannedForcedExit, SkippedForcedExit,
+    },
+    Nonce, TokenId,
+};
+
+/// The reason the account can not be forced to exit, `None` if it can.
+///
+/// Only the accounts which have never sent a transaction can be forced to exit. The eligibility
+/// of the target is checked when the request is created, so the account with a non-zero nonce
+/// has set its signing key since then.
+pub fn target_blocker(target: &ForcedExitTargetCheck) -> Option<ForcedExitBlocker> {
+    match target.nonce {
+        Some(nonce) if *nonce > 0 => Some(ForcedExitBlocker::TargetBecameActive),
+        Some(_) if target.old_enough => None,
+        _ => Some(ForcedExitBlocker::NotPossible),
+    }
+}
+
+/// Whether the request is blocked regardless of the target account.
+///
+/// The blockers are checked in the same order as when the payment is processed,
+/// so the target is not looked up for the requests which are blocked anyway.
+pub fn blocker_before_target(
+    request: &ForcedExitRequest,
+    escalated: bool,
+    now: DateTime<Utc>,
+) -> Option<ForcedExitBlocker> {
+    if request.fulfilled_at.is_some() {
+        Some(ForcedExitBlocker::Fulfilled)
+    } else if request
+        .cancellation
+        .map_or(false, |kind| !kind.allows_reprocessing())
+    {
+        Some(ForcedExitBlocker::Cancelled)
+    } else if request.valid_until <= now {
+        Some(ForcedExitBlocker::Expired)
+    } else if escalated {
+        Some(ForcedExitBlocker::Escalated)
+    } else {
+        None
+    }
+}
+
+pub fn blocked_preflight(
+    request: &ForcedExitRequest,
+    blocker: ForcedExitBlocker,
+    target: Option<ForcedExitTargetCheck>,
+) -> ForcedExitPreflight {
+    ForcedExitPreflight {
+        request_id: request.id,
+        blocker: Some(blocker),
+        target,
+        transactions: Vec::new(),
+        fee_payment: None,
+        total_fee: BigUint::zero(),
+        skipped: Vec::new(),
+    }
+}
+
+/// Plans the `ForcedExit` transactions for the tokens of the request.
+///
+/// The transactions use the consecutive nonces of the sender starting with the
+/// given one and have no fee, since the requester has already paid for them on L1.
+/// The fee charged by the server is paid separately, see `with_fee_payment`.
+pub fn plan_forced_exits(
+    request: &ForcedExitRequest,
+    target: ForcedExitTargetCheck,
+    sender_nonce: Nonce,
+) -> ForcedExitPreflight {
+    if let Some(blocker) = target_blocker(&target) {
+        return blocked_preflight(request, blocker, Some(target));
+    }
+
+    let transactions: Vec<_> = request
+        .tokens
+        .iter()
+        .zip(*sender_nonce..)
+        .map(|(token, nonce)| PlannedForcedExit {
+            token: *token,
+            nonce: Nonce(nonce),
+            fee: BigUint::zero(),
+        })
+        .collect();
+    let total_fee = transactions.iter().map(|tx| &tx.fee).sum();
+
+    ForcedExitPreflight {
+        request_id: request.id,
+        blocker: None,
+        target: Some(target),
+        transactions,
+        fee_payment: None,
+        total_fee,
+        skipped: Vec::new(),
+    }
+}
+
+/// Leaves the transactions for the skipped tokens out of the plan. The rest of the
+/// transactions keep using the consecutive nonces starting with the first planned one.
+pub fn skip_tokens(
+    mut preflight: ForcedExitPreflight,
+    skipped: Vec<SkippedForcedExit>,
+) -> ForcedExitPreflight {
+    let first_nonce = match preflight.transactions.first() {
+        Some(tx) => tx.nonce,
+        None => return preflight,
+    };
+
+    preflight
+        .transactions
+        .retain(|tx| !skipped.iter().any(|skipped| skipped.token == tx.token));
+    preflight.skipped = skipped;
+    if preflight.transactions.is_empty() {
+        preflight.fee_payment = None;
+    }
+    preflight.total_fee = preflight
+        .transactions
+        .iter()
+        .map(|tx| &tx.fee)
+        .sum::<BigUint>()
+        + preflight
+            .fee_payment
+            .as_ref()
+            .map_or_else(BigUint::zero, |payment| payment.fee.clone());
+    renumber(preflight, first_nonce)
+}
+
+/// Pays the fee of the batch with the transfer following the planned transactions.
+/// Nothing is paid for the batch without the transactions, since it is not sent.
+pub fn with_fee_payment(
+    mut preflight: ForcedExitPreflight,
+    token: TokenId,
+    fee: BigUint,
+) -> ForcedExitPreflight {
+    let nonce = match preflight.transactions.last() {
+        Some(tx) => tx.nonce + 1,
+        None => return preflight,
+    };
+    preflight.total_fee += &fee;
+    preflight.fee_payment = Some(PlannedFeePayment { token, nonce, fee });
+    preflight
+}
+
+/// Moves the planned transactions to the consecutive nonces starting with the given one,
+/// e.g. once the planned nonces have been taken by the transactions sent meanwhile.
+pub fn renumber(mut preflight: ForcedExitPreflight, first_nonce: Nonce) -> ForcedExitPreflight {
+    for (tx, nonce) in preflight.transactions.iter_mut().zip(*first_nonce..) {
+        tx.nonce = Nonce(nonce);
+    }
+    if let Some(payment) = &mut preflight.fee_payment {
+        payment.nonce = first_nonce + preflight.transactions.len() as u32;
+    }
+    preflight
+}
+
+/// Splits the planned transactions into the consecutive chunks, each of which fits into
+/// a batch of at most `max_batch_size` transactions along with its own fee payment.
+/// The chunks are planned without the fee payments, since the fee of a smaller batch
+/// has to be quoted anew, and the skipped tokens stay with the whole plan.
+pub fn split_into_batches(
+    preflight: &ForcedExitPreflight,
+    max_batch_size: usize,
+) -> Vec<ForcedExitPreflight> {
+    if preflight.batch_size() <= max_batch_size {
+        return vec![preflight.clone()];
+    }
+    let chunk_size = max_batch_size
+        .saturating_sub(usize::from(preflight.fee_payment.is_some()))
+        .max(1);
+    preflight
+        .transactions
+        .chunks(chunk_size)
+        .map(|transactions| ForcedExitPreflight {
+            request_id: preflight.request_id,
+            blocker: None,
+            target: preflight.target,
+            transactions: transactions.to_vec(),
+            fee_payment: None,
+            total_fee: transactions.iter().map(|tx| &tx.fee).sum(),
+            skipped: Vec::new(),
+        })
+        .collect()
+}
+
+/// Accounts for the request in the backlog report, `empty_tokens` is the number of its tokens
+/// the target has zero balances of.
+pub fn add_to_backlog_report(
+    report: &mut ForcedExitBacklogReport,
+    preflight: &ForcedExitPreflight,
+    empty_tokens: usize,
+) {
+    report.requests += 1;
+    if let Some(blocker) = preflight.blocker {
+        match report
+            .blocked
+            .iter_mut()
+            .find(|blocked| blocked.blocker == blocker)
+        {
+            Some(blocked) => blocked.requests += 1,
+            None => report.blocked.push(ForcedExitBlockedRequests {
+                blocker,
+                requests: 1,
+            }),
+        }
+        return;
+    }
+
+    report.fulfilled += 1;
+    if empty_tokens == preflight.transactions.len() {
+        report.nothing_to_withdraw += 1;
+    }
+    let fee_payment = preflight
+        .fee_payment
+        .iter()
+        .map(|payment| (payment.token, &payment.fee));
+    for (token, fee) in preflight
+        .transactions
+        .iter()
+        .map(|tx| (tx.token, &tx.fee))
+        .chain(fee_payment)
+    {
+        report.transactions += 1;
+        let position = match report.fees.binary_search_by_key(&token, |fees| fees.token) {
+            Ok(position) => position,
+            Err(position) => {
+                report.fees.insert(
+                    position,
+                    ForcedExitTokenFees {
+                        token,
+                        transactions: 0,
+                        total_fee: BigUint::zero(),
+                    },
+                );
+                position
+            }
+        };
+        report.fees[position].transactions += 1;
+        report.fees[position].total_fee += fee;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zksync_types::{
+        forced_exit_requests::{ForcedExitCancellationKind, ForcedExitTokenSkipReason},
+        Address,
+    };
+
+    use super::*;
+
+    #[test]
+    fn preflight_plan() {
+        let now = Utc::now();
+        let request = ForcedExitRequest {
+            id: 12,
+            public_id: 12,
+            target: Address::repeat_byte(0x12),
+            tokens: vec![TokenId(0), TokenId(3)],
+            price_in_wei: BigUint::from(20000u32),
+            pay_exactly: "20012".to_owned(),
+            valid_until: now + chrono::Duration::days(1),
+            created_at: now,
+            fulfilled_by: None,
+            fulfilled_at: None,
+            match_scheme: None,
+            matched_at: None,
+            cancellation: None,
+            paid_amount: None,
+            metadata: None,
+            status: Default::default(),
+            payment_terms: None,
+        };
+        let target = ForcedExitTargetCheck {
+            old_enough: true,
+            nonce: Some(Nonce(0)),
+        };
+
+        assert_eq!(blocker_before_target(&request, false, now), None);
+        assert_eq!(
+            blocker_before_target(&request, true, now),
+            Some(ForcedExitBlocker::Escalated)
+        );
+        assert_eq!(
+            blocker_before_target(&request, false, request.valid_until),
+            Some(ForcedExitBlocker::Expired)
+        );
+        // Only the requests cancelled to be sent again are processed afterwards
+        let cancelled = |kind| ForcedExitRequest {
+            cancellation: Some(kind),
+            ..request.clone()
+        };
+        assert_eq!(
+            blocker_before_target(
+                &cancelled(ForcedExitCancellationKind::UserCancelled),
+                false,
+                now
+            ),
+            Some(ForcedExitBlocker::Cancelled)
+        );
+        assert_eq!(
+            blocker_before_target(
+                &cancelled(ForcedExitCancellationKind::SystemRetry),
+                false,
+                now
+            ),
+            None
+        );
+
+        let preflight = plan_forced_exits(&request, target, Nonce(7));
+        assert_eq!(preflight.blocker, None);
+        let planned: Vec<_> = preflight
+            .transactions
+            .iter()
+            .map(|tx| (tx.token, tx.nonce))
+            .collect();
+        assert_eq!(
+            planned,
+            vec![(TokenId(0), Nonce(7)), (TokenId(3), Nonce(8))]
+        );
+        assert!(preflight.total_fee.is_zero());
+
+        // The request without tokens is not blocked, there is just nothing to plan
+        let empty = ForcedExitRequest {
+            tokens: Vec::new(),
+            ..request.clone()
+        };
+        let preflight = plan_forced_exits(&empty, target, Nonce(7));
+        assert_eq!(preflight.blocker, None);
+        assert!(preflight.transactions.is_empty());
+        assert!(preflight.total_fee.is_zero());
+
+        // The account which has sent a transaction can not be forced to exit
+        let target = ForcedExitTargetCheck {
+            old_enough: true,
+            nonce: Some(Nonce(1)),
+        };
+        let preflight = plan_forced_exits(&request, target, Nonce(7));
+        assert_eq!(
+            preflight.blocker,
+            Some(ForcedExitBlocker::TargetBecameActive)
+        );
+        assert!(preflight.transactions.is_empty());
+
+        let target = ForcedExitTargetCheck {
+            old_enough: false,
+            nonce: Some(Nonce(0)),
+        };
+        let preflight = plan_forced_exits(&request, target, Nonce(7));
+        assert_eq!(preflight.blocker, Some(ForcedExitBlocker::NotPossible));
+        // The account which does not exist can not be forced to exit either
+        let target = ForcedExitTargetCheck {
+            old_enough: true,
+            nonce: None,
+        };
+        assert_eq!(
+            target_blocker(&target),
+            Some(ForcedExitBlocker::NotPossible)
+        );
+    }
+
+    #[test]
+    fn preflight_skip_tokens() {
+        let now = Utc::now();
+        let request = ForcedExitRequest {
+            id: 12,
+            public_id: 12,
+            target: Address::repeat_byte(0x12),
+            tokens: vec![TokenId(0), TokenId(3), TokenId(5)],
+            price_in_wei: BigUint::from(30000u32),
+            pay_exactly: "30012".to_owned(),
+            valid_until: now + chrono::Duration::days(1),
+            created_at: now,
+            fulfilled_by: None,
+            fulfilled_at: None,
+            match_scheme: None,
+            matched_at: None,
+            cancellation: None,
+            paid_amount: None,
+            metadata: None,
+            status: Default::default(),
+            payment_terms: None,
+        };
+        let target = ForcedExitTargetCheck {
+            old_enough: true,
+            nonce: Some(Nonce(0)),
+        };
+        let restricted = |token| SkippedForcedExit {
+            token,
+            reason: ForcedExitTokenSkipReason::L1TransferRestricted,
+        };
+
+        // The nonce of the skipped token is taken by the next one
+        let preflight = skip_tokens(
+            plan_forced_exits(&request, target, Nonce(7)),
+            vec![restricted(TokenId(3))],
+        );
+        let planned: Vec<_> = preflight
+            .transactions
+            .iter()
+            .map(|tx| (tx.token, tx.nonce))
+            .collect();
+        assert_eq!(
+            planned,
+            vec![(TokenId(0), Nonce(7)), (TokenId(5), Nonce(8))]
+        );
+        assert_eq!(preflight.skipped, vec![restricted(TokenId(3))]);
+
+        // The nonces taken meanwhile are replaced by the following ones
+        let renumbered: Vec<_> = renumber(preflight, Nonce(10))
+            .transactions
+            .iter()
+            .map(|tx| (tx.token, tx.nonce))
+            .collect();
+        assert_eq!(
+            renumbered,
+            vec![(TokenId(0), Nonce(10)), (TokenId(5), Nonce(11))]
+        );
+
+        let skipped: Vec<_> = request.tokens.iter().copied().map(restricted).collect();
+        let preflight = skip_tokens(
+            plan_forced_exits(&request, target, Nonce(7)),
+            skipped.clone(),
+        );
+        assert_eq!(preflight.blocker, None);
+        assert!(preflight.transactions.is_empty());
+        assert_eq!(preflight.skipped, skipped);
+
+        // Nothing is skipped for the blocked request
+        let preflight = skip_tokens(
+            blocked_preflight(&request, ForcedExitBlocker::Expired, None),
+            vec![restricted(TokenId(3))],
+        );
+        assert!(preflight.skipped.is_empty());
+
+        // The fee payment follows the transactions left after skipping and renumbering
+        let paid = with_fee_payment(
+            plan_forced_exits(&request, target, Nonce(7)),
+            TokenId(0),
+            BigUint::from(100u32),
+        );
+        let preflight = renumber(
+            skip_tokens(paid.clone(), vec![restricted(TokenId(3))]),
+            Nonce(10),
+        );
+        assert_eq!(
+            preflight.fee_payment,
+            Some(PlannedFeePayment {
+                token: TokenId(0),
+                nonce: Nonce(12),
+                fee: BigUint::from(100u32),
+            })
+        );
+        assert_eq!(preflight.total_fee, BigUint::from(100u32));
+        let preflight = skip_tokens(paid, skipped);
+        assert_eq!(preflight.fee_payment, None);
+        assert!(preflight.total_fee.is_zero());
+    }
+
+    #[test]
+    fn preflight_split() {
+        let now = Utc::now();
+        let request = ForcedExitRequest {
+            id: 12,
+            public_id: 12,
+            target: Address::repeat_byte(0x12),
+            tokens: (0..5).map(TokenId).collect(),
+            price_in_wei: BigUint::from(50000u32),
+            pay_exactly: "50012".to_owned(),
+            valid_until: now + chrono::Duration::days(1),
+            created_at: now,
+            fulfilled_by: None,
+            fulfilled_at: None,
+            match_scheme: None,
+            matched_at: None,
+            cancellation: None,
+            paid_amount: None,
+            metadata: None,
+            status: Default::default(),
+            payment_terms: None,
+        };
+        let target = ForcedExitTargetCheck {
+            old_enough: true,
+            nonce: Some(Nonce(0)),
+        };
+        let tokens = |chunks: &[ForcedExitPreflight]| -> Vec<Vec<u32>> {
+            chunks
+                .iter()
+                .map(|chunk| chunk.transactions.iter().map(|tx| *tx.token).collect())
+                .collect()
+        };
+
+        // The plan fitting into a single batch is kept as it is
+        let preflight = with_fee_payment(
+            plan_forced_exits(&request, target, Nonce(7)),
+            TokenId(0),
+            BigUint::from(100u32),
+        );
+        assert_eq!(preflight.batch_size(), 6);
+        assert_eq!(split_into_batches(&preflight, 6), vec![preflight.clone()]);
+
+        // A place in every batch is left for its fee payment
+        let chunks = split_into_batches(&preflight, 3);
+        assert_eq!(tokens(&chunks), vec![vec![0, 1], vec![2, 3], vec![4]]);
+        assert!(chunks.iter().all(|chunk| chunk.fee_payment.is_none()));
+        assert_eq!(chunks[1].transactions[0].nonce, Nonce(9));
+
+        let preflight = plan_forced_exits(&request, target, Nonce(7));
+        assert_eq!(
+            tokens(&split_into_batches(&preflight, 2)),
+            vec![vec![0, 1], vec![2, 3], vec![4]]
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Receipts of the transactions sent for the forced exit requests.
+//!
+//! The sender settles the requests by the receipts of their transactions, while the API
+//! reports the same statuses to the users waiting for their requests to be fulfilled.
+
+use zksync_storage::{chain::operations_ext::records::TxReceiptResponse, StorageProcessor};
+use zksync_types::{forced_exit_requests::ForcedExitTxStatus, tx::TxHash};
+
+pub async fn load_receipt(
+    storage: &mut StorageProcessor<'_>,
+    tx_hash: TxHash,
+) -> anyhow::Result<Option<TxReceiptResponse>> {
+    storage
+        .chain()
+        .operations_ext_schema()
+        .tx_receipt(tx_hash.as_ref())
+        .await
+}
+
+/// Loads the receipts of the transactions at once, the ones not executed yet have none.
+pub async fn load_receipts(
+    storage: &mut StorageProcessor<'_>,
+    tx_hashes: &[TxHash],
+) -> anyhow::Result<Vec<TxReceiptResponse>> {
+    storage
+        .chain()
+        .operations_ext_schema()
+        .tx_receipts(tx_hashes)
+        .await
+}
+
+/// The status of the transaction with the given receipt, if any.
+pub fn tx_status(receipt: Option<&TxReceiptResponse>) -> ForcedExitTxStatus {
+    match receipt {
+        None => ForcedExitTxStatus::Pending,
+        Some(receipt) if !receipt.success => ForcedExitTxStatus::Failed,
+        Some(receipt) if receipt.verified => ForcedExitTxStatus::Verified,
+        Some(_) => ForcedExitTxStatus::Committed,
+    }
+}
+
+/// Returns the receipt of every transaction in the order of the hashes.
+pub fn match_receipts<'a>(
+    tx_hashes: &[TxHash],
+    receipts: &'a [TxReceiptResponse],
+) -> Vec<Option<&'a TxReceiptResponse>> {
+    tx_hashes
+        .iter()
+        .map(|tx_hash| {
+            // The receipts refer to the transactions by the plain hex of their hashes
+            let tx_hash = hex::encode(tx_hash.as_ref());
+            receipts.iter().find(|receipt| receipt.tx_hash == tx_hash)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receipt(tx_hash: TxHash, success: bool, verified: bool) -> TxReceiptResponse {
+        TxReceiptResponse {
+            tx_hash: hex::encode(tx_hash.as_ref()),
+            block_number: 1,
+            success,
+            verified,
+            fail_reason: None,
+            prover_run: None,
+        }
+    }
+
+    #[test]
+    fn statuses_in_the_order_of_the_hashes() {
+        let hashes: Vec<_> = (1..=4u8)
+            .map(|byte| TxHash::from_slice(&[byte; 32]).unwrap())
+            .collect();
+        let receipts = vec![
+            receipt(hashes[3], false, false),
+            receipt(hashes[1], true, true),
+            receipt(hashes[0], true, false),
+        ];
+
+        let statuses: Vec<_> = match_receipts(&hashes, &receipts)
+            .into_iter()
+            .map(tx_status)
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ForcedExitTxStatus::Committed,
+                ForcedExitTxStatus::Verified,
+                ForcedExitTxStatus::Pending,
+                ForcedExitTxStatus::Failed,
+            ]
+        );
+        assert!(!statuses[2].is_executed());
+        assert!(statuses[3].is_executed());
+    }
+}
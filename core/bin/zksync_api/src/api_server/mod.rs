@@ -6,6 +6,11 @@
 
 mod event_notify;
 pub mod forced_exit_checker;
+pub mod forced_exit_consistency;
+pub mod forced_exit_maintenance;
+pub mod forced_exit_matcher;
+pub mod forced_exit_preflight;
+pub mod forced_exit_receipts;
 mod helpers;
 pub mod rest;
 pub mod rpc_server;
@@ -0,0 +1,2743 @@
+//! Operators part of the forced exit requests API.
+//!
+//! All the endpoints require the JWT auth token signed with the admin secret.
+
+// Built-in uses
+use std::time::Instant;
+
+// External uses
+use actix_web::{
+    dev::ServiceRequest,
+    web::{self, Json},
+    HttpRequest, Scope,
+};
+use actix_web_httpauth::{
+    extractors::{
+        bearer::{BearerAuth, Config},
+        AuthenticationError,
+    },
+    middleware::HttpAuthentication,
+};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{decode, errors::Error as JwtError, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+
+// Workspace uses
+use zksync_api_client::rest::forced_exit_requests::{
+    AddRequestNoteRequest, CreateApiKeyRequest, CreatedApiKey, ExplainPaymentRequest,
+    FinalizeEscalationRequest, ForcedExitRequestAdminDetails, InjectPaymentRequest,
+    SetPacingRequest, SetPaymentSourceRequest,
+};
+use zksync_storage::ConnectionPool;
+use zksync_types::forced_exit_requests::{
+    ForcedExitBacklogReport, ForcedExitCancellationKind, ForcedExitConfigCandidate,
+    ForcedExitConfigImpactReport, ForcedExitConfigSnapshot, ForcedExitConsistencyReport,
+    ForcedExitFeature, ForcedExitPacing, ForcedExitPaymentExplanation, ForcedExitPipelineVersion,
+    ForcedExitPreflight, ForcedExitRefund, ForcedExitRefundId, ForcedExitRequest,
+    ForcedExitRequestDelivery, ForcedExitRequestDeliveryId, ForcedExitRequestEscalation,
+    ForcedExitRequestId, ForcedExitRequestNote, ForcedExitRequestsApiKey,
+    ForcedExitRequestsApiKeyId, ForcedExitRetry, ForcedExitRetryId, ForcedExitSingletonHolder,
+    FundsReceivedEvent, InjectedForcedExitPayment, PaymentSource, PaymentSourceState,
+    SaveForcedExitRequestsApiKeyQuery, SaveInjectedForcedExitPaymentQuery,
+};
+
+// Local uses
+use super::{
+    api_key,
+    error::ApiError,
+    service::{api_key_hash, ForcedExitRequestsService},
+    JsonResult,
+};
+
+/// The number of the latest backlog reports returned.
+const BACKLOG_REPORTS_LIMIT: u32 = 10;
+/// The number of the latest consistency reports returned.
+const CONSISTENCY_REPORTS_LIMIT: u32 = 10;
+/// The number of the latest notes with the tag returned.
+const NOTES_BY_TAG_LIMIT: u32 = 100;
+/// The number of the latest callbacks given up on returned.
+const FAILED_DELIVERIES_LIMIT: u32 = 100;
+/// The requests changed within this window are checked, unless the start is given.
+const CONSISTENCY_CHECK_WINDOW_SECS: i64 = 60 * 60;
+/// How long the retry waits for the sender to send the transactions of the request.
+const RETRY_WAIT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PayloadAuthToken {
+    /// Subject (whom auth token refers to).
+    sub: String,
+    /// Expiration time (as UTC timestamp).
+    exp: usize,
+}
+
+/// Checks that the auth token was signed with the admin secret.
+pub(super) async fn validate_auth_token(
+    req: ServiceRequest,
+    credentials: BearerAuth,
+    secret_auth: String,
+) -> actix_web::Result<ServiceRequest> {
+    let config = req.app_data::<Config>().cloned().unwrap_or_default();
+
+    let validation: Result<_, JwtError> = decode::<PayloadAuthToken>(
+        credentials.token(),
+        &DecodingKey::from_secret(secret_auth.as_ref()),
+        &Validation::default(),
+    );
+    validation.map_err(|_| AuthenticationError::from(config))?;
+
+    Ok(req)
+}
+
+/// Shared data between `/admin/forced_exit_requests/` endpoints.
+struct ApiForcedExitRequestsAdminData {
+    connection_pool: ConnectionPool,
+    service: ForcedExitRequestsService,
+}
+
+/// Returns the request along with the notes left on it by the operators.
+async fn get_request(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    request_id: web::Path<ForcedExitRequestId>,
+) -> JsonResult<ForcedExitRequestAdminDetails> {
+    let start = Instant::now();
+    let details = data
+        .service
+        .get_admin_request_details(*request_id)
+        .await
+        .map_err(ApiError::from)?;
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "get_forced_exit_request");
+    Ok(Json(details))
+}
+
+/// Appends the note to the request, the API key identifies the author of the note.
+/// The notes are for the operators only, the request is processed the same way.
+async fn add_request_note(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    req: HttpRequest,
+    request_id: web::Path<ForcedExitRequestId>,
+    params: web::Json<AddRequestNoteRequest>,
+) -> JsonResult<ForcedExitRequestNote> {
+    let start = Instant::now();
+    let params = params.into_inner();
+    let note = data
+        .service
+        .add_note(*request_id, params.text, params.tags, api_key(&req))
+        .await
+        .map_err(ApiError::from)?;
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "add_forced_exit_request_note");
+    Ok(Json(note))
+}
+
+#[derive(Debug, Deserialize)]
+struct NotesQuery {
+    tag: String,
+}
+
+/// Returns the latest notes with the tag, the newest ones first.
+async fn get_notes(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    query: web::Query<NotesQuery>,
+) -> JsonResult<Vec<ForcedExitRequestNote>> {
+    let start = Instant::now();
+
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let notes = storage
+        .forced_exit_requests_schema()
+        .load_notes_by_tag(&query.tag, NOTES_BY_TAG_LIMIT)
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "get_forced_exit_request_notes");
+    Ok(Json(notes))
+}
+
+/// Cancels the request that has not been paid for yet.
+async fn cancel_request(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    request_id: web::Path<ForcedExitRequestId>,
+) -> JsonResult<ForcedExitRequest> {
+    let start = Instant::now();
+    let request = data
+        .service
+        .cancel(*request_id, ForcedExitCancellationKind::OperatorCancelled)
+        .await
+        .map_err(ApiError::from)?;
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "cancel_forced_exit_request");
+    Ok(Json(request))
+}
+
+/// Evaluates what fulfilling the request would do right now, nothing is sent or stored.
+async fn preflight_request(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    request_id: web::Path<ForcedExitRequestId>,
+) -> JsonResult<ForcedExitPreflight> {
+    let start = Instant::now();
+    let preflight = data
+        .service
+        .preflight(*request_id)
+        .await
+        .map_err(ApiError::from)?;
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "preflight_forced_exit_request");
+    Ok(Json(preflight))
+}
+
+#[derive(Debug, Deserialize)]
+struct RetryQuery {
+    /// Whether the expired request is sent anyway, since it was payable when paid for.
+    #[serde(default)]
+    force: bool,
+}
+
+/// Sends the transactions of the paid request once again, once the sender has given up on it.
+/// The retry is returned with the hashes of the transactions once they are sent, or as is
+/// if the sender has not picked it up in time, so it can be checked later.
+async fn retry_request(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    request_id: web::Path<ForcedExitRequestId>,
+    query: web::Query<RetryQuery>,
+) -> JsonResult<ForcedExitRetry> {
+    let start = Instant::now();
+    let retry = data
+        .service
+        .retry(*request_id, query.force)
+        .await
+        .map_err(ApiError::from)?;
+    let retry = data
+        .service
+        .await_retry(retry.id, RETRY_WAIT_TIMEOUT)
+        .await
+        .map_err(ApiError::from)?;
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "retry_forced_exit_request");
+    Ok(Json(retry))
+}
+
+async fn get_retry(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    retry_id: web::Path<ForcedExitRetryId>,
+) -> JsonResult<ForcedExitRetry> {
+    let start = Instant::now();
+    let retry = data
+        .service
+        .get_retry(*retry_id)
+        .await
+        .map_err(ApiError::from)?;
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "get_forced_exit_retry");
+    Ok(Json(retry))
+}
+
+/// Returns the refunds above the approval threshold, which are not sent until approved.
+async fn get_refunds_awaiting_approval(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+) -> JsonResult<Vec<ForcedExitRefund>> {
+    let start = Instant::now();
+    data.service
+        .check_feature(ForcedExitFeature::Refunds)
+        .map_err(ApiError::from)?;
+
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let refunds = storage
+        .forced_exit_requests_schema()
+        .load_refunds_awaiting_approval()
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "get_forced_exit_refunds_awaiting_approval");
+    Ok(Json(refunds))
+}
+
+/// Approves the refund, the API key identifies the operator approving it.
+/// The approved refund is sent by the sender regardless of the hourly limit.
+async fn approve_refund(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    req: HttpRequest,
+    refund_id: web::Path<ForcedExitRefundId>,
+) -> JsonResult<ForcedExitRefund> {
+    let start = Instant::now();
+    let refund = data
+        .service
+        .approve_refund(*refund_id, api_key(&req))
+        .await
+        .map_err(ApiError::from)?;
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "approve_forced_exit_refund");
+    Ok(Json(refund))
+}
+
+/// Estimates the outcome of fulfilling the paid requests, which have not been sent yet.
+/// Every request is evaluated the same way as with the preflight, nothing is sent.
+async fn simulate_backlog(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+) -> JsonResult<ForcedExitBacklogReport> {
+    let start = Instant::now();
+    let report = data
+        .service
+        .simulate_backlog()
+        .await
+        .map_err(ApiError::from)?;
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "simulate_forced_exit_backlog");
+    Ok(Json(report))
+}
+
+/// Estimates the impact of the config candidate on the requests awaiting the payment.
+/// The report is only returned, neither the config nor the requests are changed.
+async fn simulate_config(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    candidate: web::Json<ForcedExitConfigCandidate>,
+) -> JsonResult<ForcedExitConfigImpactReport> {
+    let start = Instant::now();
+    let report = data
+        .service
+        .simulate_config(&candidate)
+        .await
+        .map_err(ApiError::from)?;
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "simulate_forced_exit_config");
+    Ok(Json(report))
+}
+
+#[derive(Debug, Deserialize)]
+struct ConfigHistoryQuery {
+    at: Option<DateTime<Utc>>,
+}
+
+/// Returns the money config in effect at the given time (now by default),
+/// `null` if no config had been recorded by then.
+async fn get_config_at(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    query: web::Query<ConfigHistoryQuery>,
+) -> JsonResult<Option<ForcedExitConfigSnapshot>> {
+    let start = Instant::now();
+    let snapshot = data
+        .service
+        .config_at(query.at.unwrap_or_else(Utc::now))
+        .await
+        .map_err(ApiError::from)?;
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "get_forced_exit_config_at");
+    Ok(Json(snapshot))
+}
+
+/// Returns the latest backlog reports, the newest ones first.
+async fn get_backlog_reports(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+) -> JsonResult<Vec<ForcedExitBacklogReport>> {
+    let start = Instant::now();
+
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let reports = storage
+        .forced_exit_requests_schema()
+        .load_backlog_reports(BACKLOG_REPORTS_LIMIT)
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "get_forced_exit_backlog_reports");
+    Ok(Json(reports))
+}
+
+#[derive(Debug, Deserialize)]
+struct ConsistencyCheckQuery {
+    since: Option<DateTime<Utc>>,
+}
+
+/// Checks the requests changed since the given time (the last hour by default)
+/// against the invariants. The violations are reported, nothing is fixed.
+async fn check_consistency(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    query: web::Query<ConsistencyCheckQuery>,
+) -> JsonResult<ForcedExitConsistencyReport> {
+    let start = Instant::now();
+    let since = query
+        .since
+        .unwrap_or_else(|| Utc::now() - Duration::seconds(CONSISTENCY_CHECK_WINDOW_SECS));
+    let report = data
+        .service
+        .check_consistency(since)
+        .await
+        .map_err(ApiError::from)?;
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "check_forced_exit_consistency");
+    Ok(Json(report))
+}
+
+/// Returns the latest consistency reports, including the ones of the periodic checks,
+/// the newest ones first.
+async fn get_consistency_reports(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+) -> JsonResult<Vec<ForcedExitConsistencyReport>> {
+    let start = Instant::now();
+
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let reports = storage
+        .forced_exit_requests_schema()
+        .load_consistency_reports(CONSISTENCY_REPORTS_LIMIT)
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "get_forced_exit_consistency_reports");
+    Ok(Json(reports))
+}
+
+/// Returns the status transitions of the request ordered by their sequence numbers,
+/// along with the state of the delivery of the notifications about them.
+async fn get_request_events(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    request_id: web::Path<ForcedExitRequestId>,
+) -> JsonResult<Vec<ForcedExitRequestDelivery>> {
+    let start = Instant::now();
+
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let events = storage
+        .forced_exit_requests_schema()
+        .load_request_deliveries(*request_id)
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "get_forced_exit_request_events");
+    Ok(Json(events))
+}
+
+/// Returns the callbacks, which have run out of the delivery attempts, the latest first.
+async fn get_failed_deliveries(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+) -> JsonResult<Vec<ForcedExitRequestDelivery>> {
+    let start = Instant::now();
+    data.service
+        .check_feature(ForcedExitFeature::Callbacks)
+        .map_err(ApiError::from)?;
+
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let deliveries = storage
+        .forced_exit_requests_schema()
+        .load_failed_deliveries(FAILED_DELIVERIES_LIMIT)
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "get_forced_exit_failed_deliveries");
+    Ok(Json(deliveries))
+}
+
+/// Posts the callback given up on once again, starting the attempts over.
+async fn redeliver(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    delivery_id: web::Path<ForcedExitRequestDeliveryId>,
+) -> JsonResult<ForcedExitRequestDelivery> {
+    let start = Instant::now();
+    let delivery = data
+        .service
+        .redeliver(*delivery_id)
+        .await
+        .map_err(ApiError::from)?;
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "redeliver_forced_exit_notification");
+    Ok(Json(delivery))
+}
+
+/// Returns the versions of the pipeline the request has been created and fulfilled with,
+/// so the changes of the behavior can be correlated with the requests affected by them.
+async fn get_request_pipeline_versions(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    request_id: web::Path<ForcedExitRequestId>,
+) -> JsonResult<Vec<ForcedExitPipelineVersion>> {
+    let start = Instant::now();
+
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let versions = storage
+        .forced_exit_requests_schema()
+        .load_pipeline_versions(*request_id)
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "get_forced_exit_request_pipeline_versions");
+    Ok(Json(versions))
+}
+
+/// Returns the escalated requests, the `FullExit` operations of which
+/// still have to be sent on L1.
+async fn get_pending_escalations(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+) -> JsonResult<Vec<ForcedExitRequestEscalation>> {
+    let start = Instant::now();
+    data.service
+        .check_feature(ForcedExitFeature::L1Escalation)
+        .map_err(ApiError::from)?;
+
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let escalations = storage
+        .forced_exit_requests_schema()
+        .get_pending_escalations()
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "get_pending_escalations");
+    Ok(Json(escalations))
+}
+
+/// Records the L1 transaction with the `FullExit` operations of the escalated request
+/// and marks the request as fulfilled.
+async fn finalize_escalation(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    request_id: web::Path<ForcedExitRequestId>,
+    params: web::Json<FinalizeEscalationRequest>,
+) -> JsonResult<ForcedExitRequestEscalation> {
+    let start = Instant::now();
+    data.service
+        .check_feature(ForcedExitFeature::L1Escalation)
+        .map_err(ApiError::from)?;
+    let request_id = *request_id;
+
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let mut fe_schema = storage.forced_exit_requests_schema();
+
+    let escalation = fe_schema
+        .get_escalation(request_id)
+        .await
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::not_found("Request with such id has not been escalated"))?;
+    if !escalation.is_awaiting() {
+        return Err(ApiError::bad_request(
+            "The escalation of the request has already been finalized",
+        ));
+    }
+
+    let finalized_at = Utc::now();
+    fe_schema
+        .finalize_escalation(request_id, params.l1_tx_hash, finalized_at)
+        .await
+        .map_err(ApiError::internal)?;
+    vlog::info!(
+        "ForcedExit request {} was fulfilled on L1 by the tx {:?}",
+        request_id,
+        params.l1_tx_hash
+    );
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "finalize_escalation");
+    Ok(Json(ForcedExitRequestEscalation {
+        l1_tx_hash: Some(params.l1_tx_hash),
+        finalized_at: Some(finalized_at),
+        ..escalation
+    }))
+}
+
+/// Issues a new API key to the trusted partner.
+async fn create_api_key(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    params: web::Json<CreateApiKeyRequest>,
+) -> JsonResult<CreatedApiKey> {
+    let start = Instant::now();
+    let params = params.into_inner();
+
+    let key = hex::encode(zksync_crypto::rand::random::<[u8; 32]>());
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let api_key = storage
+        .forced_exit_requests_schema()
+        .store_api_key(SaveForcedExitRequestsApiKeyQuery {
+            label: params.label,
+            key_hash: api_key_hash(&key),
+            max_tokens_per_request: params.max_tokens_per_request,
+            max_requests_per_hour: params.max_requests_per_hour,
+            created_at: Utc::now(),
+        })
+        .await
+        .map_err(ApiError::internal)?;
+    vlog::info!(
+        "API key {} for ForcedExit requests was issued to `{}`",
+        api_key.id,
+        api_key.label
+    );
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "create_api_key");
+    Ok(Json(CreatedApiKey { key, api_key }))
+}
+
+/// Returns all the issued API keys, including the revoked ones.
+async fn get_api_keys(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+) -> JsonResult<Vec<ForcedExitRequestsApiKey>> {
+    let start = Instant::now();
+
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let api_keys = storage
+        .forced_exit_requests_schema()
+        .load_api_keys()
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "get_api_keys");
+    Ok(Json(api_keys))
+}
+
+async fn revoke_api_key(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    api_key_id: web::Path<ForcedExitRequestsApiKeyId>,
+) -> JsonResult<ForcedExitRequestsApiKey> {
+    let start = Instant::now();
+    let api_key_id = *api_key_id;
+
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let mut fe_schema = storage.forced_exit_requests_schema();
+
+    let api_key = fe_schema
+        .get_api_key(api_key_id)
+        .await
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::not_found("API key with such id does not exist"))?;
+    if api_key.is_revoked() {
+        return Err(ApiError::bad_request(
+            "The API key has already been revoked",
+        ));
+    }
+
+    let api_key = fe_schema
+        .revoke_api_key(api_key_id, Utc::now())
+        .await
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::bad_request("The API key has already been revoked"))?;
+    vlog::info!(
+        "API key {} for ForcedExit requests issued to `{}` was revoked",
+        api_key.id,
+        api_key.label
+    );
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "revoke_api_key");
+    Ok(Json(api_key))
+}
+
+/// Stores the payment to be processed by the watcher, e.g. the one which
+/// has not been recognized on L1.
+async fn inject_payment(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    params: web::Json<InjectPaymentRequest>,
+) -> JsonResult<InjectedForcedExitPayment> {
+    let start = Instant::now();
+    let params = params.into_inner();
+
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let payment = storage
+        .forced_exit_requests_schema()
+        .store_injected_payment(SaveInjectedForcedExitPaymentQuery {
+            amount: params.amount,
+            request_id: params.request_id,
+            block_number: params.block_number,
+            eth_tx_hash: params.eth_tx_hash,
+            created_at: Utc::now(),
+        })
+        .await
+        .map_err(ApiError::internal)?;
+    vlog::info!(
+        "ForcedExit payment {} of {} wei was injected",
+        payment.id,
+        payment.amount
+    );
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "inject_payment");
+    Ok(Json(payment))
+}
+
+/// Returns the payment sources enabled or disabled at runtime,
+/// the rest of the sources follow the config.
+async fn get_payment_sources(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+) -> JsonResult<Vec<PaymentSourceState>> {
+    let start = Instant::now();
+
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let states = storage
+        .forced_exit_requests_schema()
+        .load_payment_source_states()
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "get_payment_sources");
+    Ok(Json(states))
+}
+
+/// Enables or disables the processing of the payments of the source,
+/// the watcher takes it into account starting from the next poll.
+async fn set_payment_source(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    source: web::Path<PaymentSource>,
+    params: web::Json<SetPaymentSourceRequest>,
+) -> JsonResult<PaymentSourceState> {
+    let start = Instant::now();
+    let source = source.into_inner();
+
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let state = storage
+        .forced_exit_requests_schema()
+        .set_payment_source_enabled(source, params.enabled, Utc::now())
+        .await
+        .map_err(ApiError::internal)?;
+    vlog::info!(
+        "ForcedExit payments of the source {} were {}",
+        source,
+        if state.enabled { "enabled" } else { "disabled" }
+    );
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "set_payment_source");
+    Ok(Json(state))
+}
+
+/// Explains how the payment would be matched with the request by the sender, nothing is written.
+async fn explain_payment(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    params: web::Json<ExplainPaymentRequest>,
+) -> JsonResult<ForcedExitPaymentExplanation> {
+    let start = Instant::now();
+    let params = params.into_inner();
+    let payment = FundsReceivedEvent {
+        amount: params.amount,
+        request_id: params.request_id,
+        block_number: 0,
+        eth_tx_hash: params.eth_tx_hash,
+        payer: None,
+        recipient: None,
+    };
+    let explanation = data
+        .service
+        .explain_payment(&payment, params.timestamp)
+        .await
+        .map_err(ApiError::from)?;
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "explain_forced_exit_payment");
+    Ok(Json(explanation))
+}
+
+/// Returns the pacing of the sender set by the operators, if any.
+async fn get_pacing(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+) -> JsonResult<Option<ForcedExitPacing>> {
+    let start = Instant::now();
+
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let pacing = storage
+        .forced_exit_requests_schema()
+        .load_pacing_override()
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "get_pacing");
+    Ok(Json(pacing))
+}
+
+/// Overrides the pacing of the sender or restores the configured one, the watcher applies it
+/// starting from the next poll, including to the batches already waiting for their turn.
+async fn set_pacing(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+    params: web::Json<SetPacingRequest>,
+) -> JsonResult<Option<ForcedExitPacing>> {
+    let start = Instant::now();
+    let pacing = params.into_inner().pacing;
+
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    storage
+        .forced_exit_requests_schema()
+        .set_pacing_override(pacing, Utc::now())
+        .await
+        .map_err(ApiError::internal)?;
+    match pacing {
+        Some(pacing) => vlog::info!(
+            "ForcedExit sender pacing was set to {} batches per minute and {} batches per block",
+            pacing.max_batches_per_minute,
+            pacing.batches_per_block
+        ),
+        None => vlog::info!("ForcedExit sender pacing was reset to the configured one"),
+    }
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "set_pacing");
+    Ok(Json(pacing))
+}
+
+/// Returns the server instance processing the requests, if any.
+async fn get_singleton_holder(
+    data: web::Data<ApiForcedExitRequestsAdminData>,
+) -> JsonResult<Option<ForcedExitSingletonHolder>> {
+    let start = Instant::now();
+
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let holder = storage
+        .forced_exit_requests_schema()
+        .load_singleton_holder()
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "get_singleton_holder");
+    Ok(Json(holder))
+}
+
+pub fn api_scope(service: ForcedExitRequestsService, secret_auth: String) -> Scope {
+    let data = ApiForcedExitRequestsAdminData {
+        connection_pool: service.connection_pool.clone(),
+        service,
+    };
+    let auth = HttpAuthentication::bearer(move |req, credentials| {
+        validate_auth_token(req, credentials, secret_auth.clone())
+    });
+
+    web::scope("")
+        .wrap(auth)
+        .app_data(web::Data::new(data))
+        .route("/requests/{id}", web::get().to(get_request))
+        .route("/requests/{id}/notes", web::post().to(add_request_note))
+        .route("/requests/{id}/cancel", web::post().to(cancel_request))
+        .route("/requests/{id}/preflight", web::get().to(preflight_request))
+        .route("/requests/{id}/retry", web::post().to(retry_request))
+        .route("/requests/{id}/events", web::get().to(get_request_events))
+        .route(
+            "/requests/{id}/pipeline_versions",
+            web::get().to(get_request_pipeline_versions),
+        )
+        .route("/retries/{id}", web::get().to(get_retry))
+        .route(
+            "/refunds/awaiting_approval",
+            web::get().to(get_refunds_awaiting_approval),
+        )
+        .route("/refunds/{id}/approve", web::post().to(approve_refund))
+        .route("/deliveries/failed", web::get().to(get_failed_deliveries))
+        .route("/deliveries/{id}/redeliver", web::post().to(redeliver))
+        .route("/backlog/simulate", web::post().to(simulate_backlog))
+        .route("/backlog/reports", web::get().to(get_backlog_reports))
+        .route("/config/simulate", web::post().to(simulate_config))
+        .route("/config/history", web::get().to(get_config_at))
+        .route("/consistency/check", web::post().to(check_consistency))
+        .route(
+            "/consistency/reports",
+            web::get().to(get_consistency_reports),
+        )
+        .route("/escalations", web::get().to(get_pending_escalations))
+        .route(
+            "/escalations/{id}/finalize",
+            web::post().to(finalize_escalation),
+        )
+        .route("/api_keys", web::post().to(create_api_key))
+        .route("/api_keys", web::get().to(get_api_keys))
+        .route("/api_keys/{id}/revoke", web::post().to(revoke_api_key))
+        .route("/payments", web::post().to(inject_payment))
+        .route("/payment_sources", web::get().to(get_payment_sources))
+        .route(
+            "/payment_sources/{source}",
+            web::post().to(set_payment_source),
+        )
+        .route("/explain_payment", web::post().to(explain_payment))
+        .route("/pacing", web::get().to(get_pacing))
+        .route("/pacing", web::post().to(set_pacing))
+        .route("/notes", web::get().to(get_notes))
+        .route("/singleton", web::get().to(get_singleton_holder))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chrono::{Duration, SecondsFormat, TimeZone, Timelike};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use num::BigUint;
+
+    use zksync_config::{ForcedExitRequestsConfig, ZkSyncConfig};
+    use zksync_storage::StorageProcessor;
+    use zksync_types::{
+        forced_exit_requests::{
+            ForcedExitBlocker, ForcedExitInvariant, ForcedExitMoneyConfig, ForcedExitPaymentTerms,
+            ForcedExitProcessingFailure, ForcedExitRefundReason, ForcedExitRefundStatus,
+            ForcedExitRequestEvent, ForcedExitTargetCheck, PaymentMatchScheme, PaymentMatchStep,
+            PaymentRejection, PreparedFullExit, SaveForcedExitRefundQuery,
+            SaveForcedExitRequestQuery, SubmissionError, SubmissionErrorKind,
+            MAX_SUBMISSION_ERROR_MESSAGE_LENGTH,
+        },
+        tx::TxHash,
+        AccountId, Address, TokenId, H256,
+    };
+
+    use zksync_api_client::rest::{error::ErrorBody, forced_exit_requests::API_KEY_HEADER};
+
+    use super::*;
+    use crate::api_server::{
+        forced_exit_checker::DummyForcedExitChecker,
+        rest::{
+            forced_exit_requests::error::ForcedExitRequestsError,
+            v02::{test_utils::TestServerConfig, SharedData},
+        },
+    };
+
+    const TEST_SECRET_AUTH: &str = "sample";
+
+    fn test_service(cfg: &TestServerConfig) -> ForcedExitRequestsService {
+        ForcedExitRequestsService::new(
+            cfg.pool.clone(),
+            &ForcedExitRequestsConfig {
+                enabled: true,
+                ..cfg.config.forced_exit_requests.clone()
+            },
+            cfg.config.contracts.forced_exit_addr,
+            Box::new(DummyForcedExitChecker),
+        )
+    }
+
+    fn auth_token(secret: &str) -> String {
+        let payload = PayloadAuthToken {
+            sub: "operator".into(),
+            exp: (Utc::now() + Duration::minutes(1)).timestamp() as usize,
+        };
+        encode(
+            &Header::default(),
+            &payload,
+            &EncodingKey::from_secret(secret.as_ref()),
+        )
+        .unwrap()
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn test_finalize_escalation() -> anyhow::Result<()> {
+        let cfg = TestServerConfig {
+            config: ZkSyncConfig::from_env(),
+            pool: ConnectionPool::new(Some(1)),
+        };
+
+        let target = Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap();
+        let escalation = {
+            let mut storage = cfg.pool.access_storage().await?;
+            let now = Utc::now().with_nanosecond(0).unwrap();
+            let request = storage
+                .forced_exit_requests_schema()
+                .store_request(SaveForcedExitRequestQuery {
+                    target,
+                    tokens: vec![TokenId(1)],
+                    price_in_wei: BigUint::from(212u32),
+                    created_at: now,
+                    valid_until: now + Duration::days(1),
+                    metadata: None,
+                    payment_terms: None,
+                    callback_url: None,
+                })
+                .await?;
+
+            let escalation = ForcedExitRequestEscalation {
+                request_id: request.id,
+                full_exits: vec![PreparedFullExit {
+                    owner: target,
+                    account_id: AccountId(12),
+                    token: TokenId(1),
+                    token_address: Address::repeat_byte(0x01),
+                    contract: cfg.config.contracts.contract_addr,
+                    calldata: vec![0x12, 0x34],
+                }],
+                created_at: now,
+                l1_tx_hash: None,
+                finalized_at: None,
+            };
+            storage
+                .forced_exit_requests_schema()
+                .store_escalation(escalation.clone())
+                .await?;
+            escalation
+        };
+        let request_id = escalation.request_id;
+
+        let (_client, server) = cfg.start_server_with_scope(
+            String::from("admin/forced_exit_requests"),
+            |cfg| api_scope(test_service(cfg), TEST_SECRET_AUTH.to_owned()),
+            Option::<SharedData>::None,
+        );
+
+        // Requests without the valid token are rejected
+        let response = server
+            .get("/admin/forced_exit_requests/escalations")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+        let response = server
+            .get("/admin/forced_exit_requests/escalations")
+            .bearer_auth(auth_token("wrong secret"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+
+        // The escalated request is in the queue
+        let pending: Vec<ForcedExitRequestEscalation> = server
+            .get("/admin/forced_exit_requests/escalations")
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(pending.contains(&escalation));
+
+        let l1_tx_hash = H256::repeat_byte(0x12);
+        let finalize_path = format!(
+            "/admin/forced_exit_requests/escalations/{}/finalize",
+            request_id
+        );
+        let finalized: ForcedExitRequestEscalation = server
+            .post(&finalize_path)
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send_json(&FinalizeEscalationRequest { l1_tx_hash })
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(finalized.l1_tx_hash, Some(l1_tx_hash));
+        assert!(!finalized.is_awaiting());
+
+        // The request is fulfilled and is not in the queue anymore
+        let request = cfg
+            .pool
+            .access_storage()
+            .await?
+            .forced_exit_requests_schema()
+            .get_request_by_id(request_id)
+            .await?
+            .unwrap();
+        assert!(request.fulfilled_at.is_some());
+        let pending: Vec<ForcedExitRequestEscalation> = server
+            .get("/admin/forced_exit_requests/escalations")
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(pending
+            .iter()
+            .all(|escalation| escalation.request_id != request_id));
+
+        // The escalation can not be finalized twice
+        let response = server
+            .post(&finalize_path)
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send_json(&FinalizeEscalationRequest { l1_tx_hash })
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn test_cancel_request() -> anyhow::Result<()> {
+        let cfg = TestServerConfig {
+            config: ZkSyncConfig::from_env(),
+            pool: ConnectionPool::new(Some(1)),
+        };
+
+        let (request, fulfilled_request) = {
+            let mut storage = cfg.pool.access_storage().await?;
+            let mut fe_schema = storage.forced_exit_requests_schema();
+            let now = Utc::now().with_nanosecond(0).unwrap();
+            let query = SaveForcedExitRequestQuery {
+                target: Address::repeat_byte(0x31),
+                tokens: vec![TokenId(1)],
+                price_in_wei: BigUint::from(212u32),
+                created_at: now,
+                valid_until: now + Duration::days(1),
+                metadata: None,
+                payment_terms: None,
+                callback_url: None,
+            };
+            let request = fe_schema.store_request(query.clone()).await?;
+            let fulfilled_request = fe_schema.store_request(query).await?;
+            fe_schema
+                .set_fulfilled_by(fulfilled_request.id, Some(vec![Default::default()]))
+                .await?;
+            (request, fulfilled_request)
+        };
+
+        let (_client, server) = cfg.start_server_with_scope(
+            String::from("admin/forced_exit_requests"),
+            |cfg| api_scope(test_service(cfg), TEST_SECRET_AUTH.to_owned()),
+            Option::<SharedData>::None,
+        );
+
+        let cancel_path = |id| format!("/admin/forced_exit_requests/requests/{}/cancel", id);
+        let response = server.post(&cancel_path(request.id)).send().await.unwrap();
+        assert_eq!(response.status(), 401);
+
+        let cancelled: ForcedExitRequest = server
+            .post(&cancel_path(request.id))
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(cancelled.id, request.id);
+        assert!(cancelled.valid_until <= Utc::now());
+
+        // The request which is already being fulfilled can not be cancelled
+        let response = server
+            .post(&cancel_path(fulfilled_request.id))
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        let response = server
+            .post(&cancel_path(-1))
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn test_request_notes() -> anyhow::Result<()> {
+        let cfg = TestServerConfig {
+            config: ZkSyncConfig::from_env(),
+            pool: ConnectionPool::new(Some(1)),
+        };
+
+        let key = hex::encode(zksync_crypto::rand::random::<[u8; 32]>());
+        let request = {
+            let mut storage = cfg.pool.access_storage().await?;
+            let mut fe_schema = storage.forced_exit_requests_schema();
+            fe_schema
+                .store_api_key(SaveForcedExitRequestsApiKeyQuery {
+                    label: "on-call".to_owned(),
+                    key_hash: api_key_hash(&key),
+                    max_tokens_per_request: None,
+                    max_requests_per_hour: None,
+                    created_at: Utc::now(),
+                })
+                .await?;
+            let now = Utc::now().with_nanosecond(0).unwrap();
+            fe_schema
+                .store_request(SaveForcedExitRequestQuery {
+                    target: Address::repeat_byte(0x35),
+                    tokens: vec![TokenId(1)],
+                    price_in_wei: BigUint::from(212u32),
+                    created_at: now,
+                    valid_until: now + Duration::days(1),
+                    metadata: None,
+                    payment_terms: None,
+                    callback_url: None,
+                })
+                .await?
+        };
+
+        let (_client, server) = cfg.start_server_with_scope(
+            String::from("admin/forced_exit_requests"),
+            |cfg| api_scope(test_service(cfg), TEST_SECRET_AUTH.to_owned()),
+            Option::<SharedData>::None,
+        );
+
+        let notes_path = |id| format!("/admin/forced_exit_requests/requests/{}/notes", id);
+        let note = |text: &str, tags: &[&str]| AddRequestNoteRequest {
+            text: text.to_owned(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        };
+        let response = server
+            .post(&notes_path(request.id))
+            .insert_header((API_KEY_HEADER, key.as_str()))
+            .send_json(&note("customer contacted", &[]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+        // The author of the note is only known from the API key
+        let response = server
+            .post(&notes_path(request.id))
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send_json(&note("customer contacted", &[]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        let mut added = Vec::new();
+        for (text, tags) in [
+            ("customer contacted", &["follow-up", "follow-up"][..]),
+            ("refund issued manually via tx 0x12", &[][..]),
+        ] {
+            let added_note: ForcedExitRequestNote = server
+                .post(&notes_path(request.id))
+                .bearer_auth(auth_token(TEST_SECRET_AUTH))
+                .insert_header((API_KEY_HEADER, key.as_str()))
+                .send_json(&note(text, tags))
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            assert_eq!(added_note.author, "on-call");
+            added.push(added_note);
+        }
+        assert_eq!(added[0].tags, vec!["follow-up".to_owned()]);
+
+        // The empty notes and the malformed tags are rejected
+        for invalid in [note(" ", &[]), note("customer contacted", &["follow up"])] {
+            let response = server
+                .post(&notes_path(request.id))
+                .bearer_auth(auth_token(TEST_SECRET_AUTH))
+                .insert_header((API_KEY_HEADER, key.as_str()))
+                .send_json(&invalid)
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 400);
+        }
+        let response = server
+            .post(&notes_path(-1))
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .insert_header((API_KEY_HEADER, key.as_str()))
+            .send_json(&note("customer contacted", &[]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+
+        let details: ForcedExitRequestAdminDetails = server
+            .get(&format!(
+                "/admin/forced_exit_requests/requests/{}",
+                request.id
+            ))
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(details.details.request.id, request.id);
+        assert_eq!(details.notes, added);
+
+        let tagged: Vec<ForcedExitRequestNote> = server
+            .get("/admin/forced_exit_requests/notes?tag=follow-up")
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(tagged.first(), Some(&added[0]));
+        server.stop().await;
+
+        // The notes are never shown to the users
+        let (client, server) = cfg.start_server_with_scope(
+            String::from("api/forced_exit_requests"),
+            |cfg| {
+                super::super::v02::api_scope(
+                    cfg.pool.clone(),
+                    &ForcedExitRequestsConfig {
+                        enabled: true,
+                        ..cfg.config.forced_exit_requests.clone()
+                    },
+                    cfg.config.contracts.forced_exit_addr,
+                    Box::new(DummyForcedExitChecker),
+                    cfg.config.chain.eth.network,
+                )
+            },
+            Option::<SharedData>::None,
+        );
+        let response = client.forced_exit_request_by_id(request.public_id).await?;
+        let details = response.result.unwrap();
+        assert_eq!(details["id"], serde_json::json!(request.id));
+        assert!(details.get("notes").is_none());
+        assert!(!details.to_string().contains("customer contacted"));
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn test_submission_error_details() -> anyhow::Result<()> {
+        let cfg = TestServerConfig {
+            config: ZkSyncConfig::from_env(),
+            pool: ConnectionPool::new(Some(1)),
+        };
+
+        let submission_error = SubmissionError::http(
+            503,
+            Some(607),
+            format!("Core server is unavailable\n{}", "x".repeat(1000)),
+        );
+        let request = {
+            let mut storage = cfg.pool.access_storage().await?;
+            let mut fe_schema = storage.forced_exit_requests_schema();
+            let now = Utc::now().with_nanosecond(0).unwrap();
+            let request = fe_schema
+                .store_request(SaveForcedExitRequestQuery {
+                    target: Address::repeat_byte(0x36),
+                    tokens: vec![TokenId(1)],
+                    price_in_wei: BigUint::from(212u32),
+                    created_at: now,
+                    valid_until: now + Duration::days(1),
+                    metadata: None,
+                    payment_terms: None,
+                    callback_url: None,
+                })
+                .await?;
+            fe_schema
+                .store_processing_failure(&ForcedExitProcessingFailure {
+                    request_id: request.id,
+                    attempts: 3,
+                    error: submission_error.to_string(),
+                    failed_at: now,
+                    submission_error: Some(submission_error.clone()),
+                })
+                .await?;
+            request
+        };
+
+        let (_client, server) = cfg.start_server_with_scope(
+            String::from("admin/forced_exit_requests"),
+            |cfg| api_scope(test_service(cfg), TEST_SECRET_AUTH.to_owned()),
+            Option::<SharedData>::None,
+        );
+        let details: ForcedExitRequestAdminDetails = server
+            .get(&format!(
+                "/admin/forced_exit_requests/requests/{}",
+                request.id
+            ))
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        // Every layer of the failure is kept, only the message is cut
+        let shown = details.submission_error.unwrap();
+        assert_eq!(shown.kind, SubmissionErrorKind::Http);
+        assert_eq!(shown.http_status, Some(503));
+        assert_eq!(shown.api_code, Some(607));
+        assert!(shown.retryable);
+        assert!(shown.message.starts_with("Core server is unavailable"));
+        assert!(!shown.message.contains('\n'));
+        assert_eq!(
+            shown.message.chars().count(),
+            MAX_SUBMISSION_ERROR_MESSAGE_LENGTH
+        );
+        server.stop().await;
+
+        // The users only see that the processing has failed
+        let (client, server) = cfg.start_server_with_scope(
+            String::from("api/forced_exit_requests"),
+            |cfg| {
+                super::super::v02::api_scope(
+                    cfg.pool.clone(),
+                    &ForcedExitRequestsConfig {
+                        enabled: true,
+                        ..cfg.config.forced_exit_requests.clone()
+                    },
+                    cfg.config.contracts.forced_exit_addr,
+                    Box::new(DummyForcedExitChecker),
+                    cfg.config.chain.eth.network,
+                )
+            },
+            Option::<SharedData>::None,
+        );
+        let response = client.forced_exit_request_by_id(request.public_id).await?;
+        let details = response.result.unwrap();
+        assert_eq!(
+            details["processingFailure"]["attempts"],
+            serde_json::json!(3)
+        );
+        assert_eq!(
+            details["processingFailure"]["submissionError"],
+            serde_json::Value::Null
+        );
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn test_preflight_request() -> anyhow::Result<()> {
+        let cfg = TestServerConfig {
+            config: ZkSyncConfig::from_env(),
+            pool: ConnectionPool::new(Some(1)),
+        };
+
+        let (pending, expired, escalated) = {
+            let mut storage = cfg.pool.access_storage().await?;
+            let mut fe_schema = storage.forced_exit_requests_schema();
+            let now = Utc::now().with_nanosecond(0).unwrap();
+            let query = SaveForcedExitRequestQuery {
+                // The account which does not exist in the network
+                target: Address::repeat_byte(0x32),
+                tokens: vec![TokenId(1), TokenId(2)],
+                price_in_wei: BigUint::from(212u32),
+                created_at: now,
+                valid_until: now + Duration::days(1),
+                metadata: None,
+                payment_terms: None,
+                callback_url: None,
+            };
+            let pending = fe_schema.store_request(query.clone()).await?;
+            let expired = fe_schema
+                .store_request(SaveForcedExitRequestQuery {
+                    valid_until: now - Duration::minutes(1),
+                    ..query.clone()
+                })
+                .await?;
+            let escalated = fe_schema.store_request(query).await?;
+            fe_schema
+                .store_escalation(ForcedExitRequestEscalation {
+                    request_id: escalated.id,
+                    full_exits: vec![],
+                    created_at: now,
+                    l1_tx_hash: None,
+                    finalized_at: None,
+                })
+                .await?;
+            (pending, expired, escalated)
+        };
+
+        let (_client, server) = cfg.start_server_with_scope(
+            String::from("admin/forced_exit_requests"),
+            |cfg| api_scope(test_service(cfg), TEST_SECRET_AUTH.to_owned()),
+            Option::<SharedData>::None,
+        );
+
+        let preflight_path = |id| format!("/admin/forced_exit_requests/requests/{}/preflight", id);
+        let response = server
+            .get(&preflight_path(pending.id))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+
+        let preflight = |id| {
+            server
+                .get(&preflight_path(id))
+                .bearer_auth(auth_token(TEST_SECRET_AUTH))
+                .send()
+        };
+        let result: ForcedExitPreflight =
+            preflight(pending.id).await.unwrap().json().await.unwrap();
+        assert_eq!(result.request_id, pending.id);
+        assert_eq!(result.blocker, Some(ForcedExitBlocker::NotPossible));
+        assert_eq!(
+            result.target,
+            Some(ForcedExitTargetCheck {
+                old_enough: false,
+                nonce: None,
+            })
+        );
+        assert!(result.transactions.is_empty());
+
+        let result: ForcedExitPreflight =
+            preflight(expired.id).await.unwrap().json().await.unwrap();
+        assert_eq!(result.blocker, Some(ForcedExitBlocker::Expired));
+        assert_eq!(result.target, None);
+
+        let result: ForcedExitPreflight =
+            preflight(escalated.id).await.unwrap().json().await.unwrap();
+        assert_eq!(result.blocker, Some(ForcedExitBlocker::Escalated));
+
+        // Nothing has been changed by the evaluation
+        let stored = cfg
+            .pool
+            .access_storage()
+            .await?
+            .forced_exit_requests_schema()
+            .get_request_by_id(pending.id)
+            .await?
+            .unwrap();
+        assert_eq!(stored, pending);
+
+        let response = preflight(-1).await.unwrap();
+        assert_eq!(response.status(), 404);
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn test_request_events() -> anyhow::Result<()> {
+        let cfg = TestServerConfig {
+            config: ZkSyncConfig::from_env(),
+            pool: ConnectionPool::new(Some(1)),
+        };
+
+        let request = {
+            let mut storage = cfg.pool.access_storage().await?;
+            let mut fe_schema = storage.forced_exit_requests_schema();
+            let now = Utc::now().with_nanosecond(0).unwrap();
+            let request = fe_schema
+                .store_request(SaveForcedExitRequestQuery {
+                    target: Address::repeat_byte(0x33),
+                    tokens: vec![TokenId(1)],
+                    price_in_wei: BigUint::from(212u32),
+                    created_at: now,
+                    valid_until: now + Duration::days(1),
+                    metadata: None,
+                    payment_terms: None,
+                    callback_url: None,
+                })
+                .await?;
+            // The transitions made at the same time are still ordered
+            fe_schema
+                .set_fulfilled_by(request.id, Some(vec![Default::default()]))
+                .await?;
+            fe_schema.set_fulfilled_at(request.id, now).await?;
+            request
+        };
+
+        let (_client, server) = cfg.start_server_with_scope(
+            String::from("admin/forced_exit_requests"),
+            |cfg| api_scope(test_service(cfg), TEST_SECRET_AUTH.to_owned()),
+            Option::<SharedData>::None,
+        );
+
+        let events_path = format!("/admin/forced_exit_requests/requests/{}/events", request.id);
+        let response = server.get(&events_path).send().await.unwrap();
+        assert_eq!(response.status(), 401);
+
+        let events: Vec<ForcedExitRequestDelivery> = server
+            .get(&events_path)
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let events: Vec<_> = events
+            .iter()
+            .map(|event| (event.sequence, event.event))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (1, ForcedExitRequestEvent::Submitted),
+                (2, ForcedExitRequestEvent::Fulfilled),
+            ]
+        );
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn test_api_keys_management() -> anyhow::Result<()> {
+        let cfg = TestServerConfig {
+            config: ZkSyncConfig::from_env(),
+            pool: ConnectionPool::new(Some(1)),
+        };
+        let (_client, server) = cfg.start_server_with_scope(
+            String::from("admin/forced_exit_requests"),
+            |cfg| api_scope(test_service(cfg), TEST_SECRET_AUTH.to_owned()),
+            Option::<SharedData>::None,
+        );
+
+        // Keys can not be issued without the valid token
+        let create_request = CreateApiKeyRequest {
+            label: "partner".to_owned(),
+            max_tokens_per_request: Some(20),
+            max_requests_per_hour: None,
+        };
+        let response = server
+            .post("/admin/forced_exit_requests/api_keys")
+            .send_json(&create_request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+
+        let created: CreatedApiKey = server
+            .post("/admin/forced_exit_requests/api_keys")
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send_json(&create_request)
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(created.api_key.label, "partner");
+        assert_eq!(created.api_key.max_tokens_per_request, Some(20));
+        assert!(!created.api_key.is_revoked());
+
+        // Only the hash of the key is stored
+        let stored = cfg
+            .pool
+            .access_storage()
+            .await?
+            .forced_exit_requests_schema()
+            .get_api_key_by_hash(api_key_hash(&created.key))
+            .await?;
+        assert_eq!(stored, Some(created.api_key.clone()));
+
+        let api_keys: Vec<ForcedExitRequestsApiKey> = server
+            .get("/admin/forced_exit_requests/api_keys")
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(api_keys.contains(&created.api_key));
+
+        let revoke_path = format!(
+            "/admin/forced_exit_requests/api_keys/{}/revoke",
+            created.api_key.id
+        );
+        let revoked: ForcedExitRequestsApiKey = server
+            .post(&revoke_path)
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(revoked.is_revoked());
+
+        // The key can not be revoked twice, the unknown keys are not found
+        let response = server
+            .post(&revoke_path)
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        let response = server
+            .post("/admin/forced_exit_requests/api_keys/-1/revoke")
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn test_payment_sources_management() -> anyhow::Result<()> {
+        let cfg = TestServerConfig {
+            config: ZkSyncConfig::from_env(),
+            pool: ConnectionPool::new(Some(1)),
+        };
+        let (_client, server) = cfg.start_server_with_scope(
+            String::from("admin/forced_exit_requests"),
+            |cfg| api_scope(test_service(cfg), TEST_SECRET_AUTH.to_owned()),
+            Option::<SharedData>::None,
+        );
+
+        let source_path = "/admin/forced_exit_requests/payment_sources/l1Event";
+        let response = server
+            .post(source_path)
+            .send_json(&SetPaymentSourceRequest { enabled: false })
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+
+        let state: PaymentSourceState = server
+            .post(source_path)
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send_json(&SetPaymentSourceRequest { enabled: false })
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(state.source, PaymentSource::L1Event);
+        assert!(!state.enabled);
+
+        let states: Vec<PaymentSourceState> = server
+            .get("/admin/forced_exit_requests/payment_sources")
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(states.contains(&state));
+
+        // The unknown sources can not be set
+        let response = server
+            .post("/admin/forced_exit_requests/payment_sources/l2Transfer")
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send_json(&SetPaymentSourceRequest { enabled: false })
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+
+        let inject_request = InjectPaymentRequest {
+            amount: BigUint::from(1_000_000_012u64),
+            request_id: Some(12),
+            block_number: 10,
+            eth_tx_hash: Some(H256::repeat_byte(0x12)),
+        };
+        let response = server
+            .post("/admin/forced_exit_requests/payments")
+            .send_json(&inject_request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+
+        let injected: InjectedForcedExitPayment = server
+            .post("/admin/forced_exit_requests/payments")
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send_json(&inject_request)
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(injected.amount, inject_request.amount);
+        assert_eq!(injected.request_id, Some(12));
+
+        // The payment waits for the watcher
+        let mut storage = cfg.pool.access_storage().await?;
+        let mut fe_schema = storage.forced_exit_requests_schema();
+        assert!(fe_schema
+            .load_injected_payments(u32::MAX)
+            .await?
+            .contains(&injected));
+
+        // Restore the state of the shared database
+        fe_schema.delete_injected_payment(injected.id).await?;
+        fe_schema
+            .set_payment_source_enabled(PaymentSource::L1Event, true, Utc::now())
+            .await?;
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn test_config_history() -> anyhow::Result<()> {
+        let cfg = TestServerConfig {
+            config: ZkSyncConfig::from_env(),
+            pool: ConnectionPool::new(Some(1)),
+        };
+        // The snapshots are recorded long before the ones of the servers sharing the database
+        let reload = Utc.ymd(2000, 1, 1).and_hms(0, 0, 0);
+        let config = cfg
+            .config
+            .forced_exit_requests
+            .money_config(cfg.config.contracts.forced_exit_addr);
+        let repriced = ForcedExitMoneyConfig {
+            price_per_token: config.price_per_token * 2,
+            ..config.clone()
+        };
+        {
+            let mut storage = cfg.pool.access_storage().await?;
+            let mut fe_schema = storage.forced_exit_requests_schema();
+            fe_schema.record_config(&config, reload).await?;
+            fe_schema
+                .record_config(&repriced, reload + Duration::hours(1))
+                .await?;
+            fe_schema
+                .record_config(&config, reload + Duration::hours(2))
+                .await?;
+        }
+
+        let (_client, server) = cfg.start_server_with_scope(
+            String::from("admin/forced_exit_requests"),
+            |cfg| api_scope(test_service(cfg), TEST_SECRET_AUTH.to_owned()),
+            Option::<SharedData>::None,
+        );
+        let history_path = |at: DateTime<Utc>| {
+            format!(
+                "/admin/forced_exit_requests/config/history?at={}",
+                at.to_rfc3339_opts(SecondsFormat::Secs, true)
+            )
+        };
+
+        let response = server.get(history_path(reload)).send().await.unwrap();
+        assert_eq!(response.status(), 401);
+
+        let config_at = |at: DateTime<Utc>| {
+            let request = server
+                .get(history_path(at))
+                .bearer_auth(auth_token(TEST_SECRET_AUTH))
+                .send();
+            async move {
+                request
+                    .await
+                    .unwrap()
+                    .json::<Option<ForcedExitConfigSnapshot>>()
+                    .await
+                    .unwrap()
+            }
+        };
+
+        assert_eq!(config_at(reload - Duration::minutes(1)).await, None);
+        let snapshot = config_at(reload + Duration::minutes(30)).await.unwrap();
+        assert_eq!(snapshot.effective_from, reload);
+        assert_eq!(snapshot.config, config);
+        let snapshot = config_at(reload + Duration::minutes(90)).await.unwrap();
+        assert_eq!(snapshot.effective_from, reload + Duration::hours(1));
+        assert_eq!(snapshot.config, repriced);
+        let snapshot = config_at(reload + Duration::minutes(150)).await.unwrap();
+        assert_eq!(snapshot.effective_from, reload + Duration::hours(2));
+        assert_eq!(snapshot.config, config);
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn test_explain_payment() -> anyhow::Result<()> {
+        let cfg = TestServerConfig {
+            config: ZkSyncConfig::from_env(),
+            pool: ConnectionPool::new(Some(1)),
+        };
+
+        let (pending, expired) = {
+            let mut storage = cfg.pool.access_storage().await?;
+            let mut fe_schema = storage.forced_exit_requests_schema();
+            let now = Utc::now().with_nanosecond(0).unwrap();
+            let query = SaveForcedExitRequestQuery {
+                target: Address::repeat_byte(0x33),
+                tokens: vec![TokenId(1)],
+                price_in_wei: BigUint::from(10u32).pow(20),
+                created_at: now,
+                valid_until: now + Duration::days(1),
+                metadata: None,
+                payment_terms: Some(ForcedExitPaymentTerms {
+                    digits_in_id: cfg.config.forced_exit_requests.digits_in_id,
+                    check_digit: true,
+                    overpayment_tolerance: BigUint::from(0u32),
+                    expiration_grace_period: 0,
+                }),
+                callback_url: None,
+            };
+            let pending = fe_schema.store_request(query.clone()).await?;
+            let expired = fe_schema
+                .store_request(SaveForcedExitRequestQuery {
+                    valid_until: now - Duration::minutes(1),
+                    ..query
+                })
+                .await?;
+            (pending, expired)
+        };
+
+        let (_client, server) = cfg.start_server_with_scope(
+            String::from("admin/forced_exit_requests"),
+            |cfg| api_scope(test_service(cfg), TEST_SECRET_AUTH.to_owned()),
+            Option::<SharedData>::None,
+        );
+        let explain_path = "/admin/forced_exit_requests/explain_payment";
+        let params = |amount: &str| ExplainPaymentRequest {
+            amount: BigUint::from_str(amount).unwrap(),
+            timestamp: Utc::now(),
+            eth_tx_hash: None,
+            request_id: None,
+        };
+
+        let response = server
+            .post(explain_path)
+            .send_json(&params(&pending.pay_exactly))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+
+        let explain = |amount: String| {
+            let request = server
+                .post(explain_path)
+                .bearer_auth(auth_token(TEST_SECRET_AUTH))
+                .send_json(&params(&amount));
+            async move {
+                request
+                    .await
+                    .unwrap()
+                    .json::<ForcedExitPaymentExplanation>()
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let explanation = explain(pending.pay_exactly.clone()).await;
+        assert_eq!(explanation.request_id, Some(pending.id));
+        assert_eq!(
+            explanation.match_scheme,
+            Some(PaymentMatchScheme::AmountDigits)
+        );
+        assert!(explanation
+            .steps
+            .contains(&PaymentMatchStep::AmountDecoding {
+                request_id: pending.id,
+                public_id: pending.public_id,
+                decoded_id: Some(pending.public_id),
+                error: None,
+            }));
+        assert_eq!(
+            explanation.steps.last(),
+            Some(&PaymentMatchStep::Matched {
+                request_id: pending.id,
+                match_scheme: PaymentMatchScheme::AmountDigits,
+            })
+        );
+
+        // The expired request is found by the amount, but not paid for
+        let explanation = explain(expired.pay_exactly.clone()).await;
+        assert_eq!(explanation.request_id, None);
+        assert!(explanation.steps.iter().any(|step| matches!(
+            step,
+            PaymentMatchStep::RequestPayability {
+                request_id,
+                rejection: Some(PaymentRejection::Expired),
+                ..
+            } if *request_id == expired.id
+        )));
+        assert_eq!(explanation.steps.last(), Some(&PaymentMatchStep::Unmatched));
+
+        // The mistyped check digit does not let the amount be matched with the request
+        let exact = BigUint::from_str(&pending.pay_exactly).unwrap();
+        let mistyped = if pending.pay_exactly.ends_with('9') {
+            exact - 1u32
+        } else {
+            exact + 1u32
+        };
+        let explanation = explain(mistyped.to_string()).await;
+        assert_eq!(explanation.request_id, None);
+        match &explanation.steps[0] {
+            PaymentMatchStep::CandidateIds { digits_in_id, ids } => {
+                assert_eq!(
+                    *digits_in_id,
+                    u32::from(cfg.config.forced_exit_requests.digits_in_id)
+                );
+                assert_ne!(ids.first(), Some(&pending.public_id));
+            }
+            step => panic!("Unexpected step {:?}", step),
+        }
+        assert_eq!(explanation.steps.last(), Some(&PaymentMatchStep::Unmatched));
+
+        // Nothing has been changed by the explanations
+        let stored = cfg
+            .pool
+            .access_storage()
+            .await?
+            .forced_exit_requests_schema()
+            .get_request_by_id(pending.id)
+            .await?
+            .unwrap();
+        assert_eq!(stored, pending);
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn test_pacing_management() -> anyhow::Result<()> {
+        let cfg = TestServerConfig {
+            config: ZkSyncConfig::from_env(),
+            pool: ConnectionPool::new(Some(1)),
+        };
+        let (_client, server) = cfg.start_server_with_scope(
+            String::from("admin/forced_exit_requests"),
+            |cfg| api_scope(test_service(cfg), TEST_SECRET_AUTH.to_owned()),
+            Option::<SharedData>::None,
+        );
+        let pacing_path = "/admin/forced_exit_requests/pacing";
+        let pacing = ForcedExitPacing {
+            max_batches_per_minute: 6,
+            batches_per_block: 2,
+        };
+
+        let response = server
+            .post(pacing_path)
+            .send_json(&SetPacingRequest {
+                pacing: Some(pacing),
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+
+        let set: Option<ForcedExitPacing> = server
+            .post(pacing_path)
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send_json(&SetPacingRequest {
+                pacing: Some(pacing),
+            })
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(set, Some(pacing));
+        let current: Option<ForcedExitPacing> = server
+            .get(pacing_path)
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(current, Some(pacing));
+
+        // The configured pacing is restored
+        let reset: Option<ForcedExitPacing> = server
+            .post(pacing_path)
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send_json(&SetPacingRequest { pacing: None })
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(reset, None);
+        let mut storage = cfg.pool.access_storage().await?;
+        assert!(storage
+            .forced_exit_requests_schema()
+            .load_pacing_override()
+            .await?
+            .is_none());
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn test_singleton_holder() -> anyhow::Result<()> {
+        let cfg = TestServerConfig {
+            config: ZkSyncConfig::from_env(),
+            pool: ConnectionPool::new(Some(1)),
+        };
+        let (_client, server) = cfg.start_server_with_scope(
+            String::from("admin/forced_exit_requests"),
+            |cfg| api_scope(test_service(cfg), TEST_SECRET_AUTH.to_owned()),
+            Option::<SharedData>::None,
+        );
+
+        let response = server
+            .get("/admin/forced_exit_requests/singleton")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+
+        // The lock is taken the way the processing instance does it
+        let mut session = StorageProcessor::establish_connection().await?;
+        assert!(
+            session
+                .forced_exit_requests_schema()
+                .try_acquire_singleton_lock("test-instance:1")
+                .await?
+        );
+
+        let holder: Option<ForcedExitSingletonHolder> = server
+            .get("/admin/forced_exit_requests/singleton")
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(holder.unwrap().instance, "test-instance:1");
+
+        session
+            .forced_exit_requests_schema()
+            .release_singleton_lock()
+            .await?;
+        let holder: Option<ForcedExitSingletonHolder> = server
+            .get("/admin/forced_exit_requests/singleton")
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(holder, None);
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn test_refund_approvals() -> anyhow::Result<()> {
+        let mut cfg = TestServerConfig {
+            config: ZkSyncConfig::from_env(),
+            pool: ConnectionPool::new(Some(1)),
+        };
+        cfg.config.forced_exit_requests.refunds_enabled = true;
+
+        let key = hex::encode(zksync_crypto::rand::random::<[u8; 32]>());
+        let (approved_id, pending_id) = {
+            let mut storage = cfg.pool.access_storage().await?;
+            let mut fe_schema = storage.forced_exit_requests_schema();
+            fe_schema
+                .store_api_key(SaveForcedExitRequestsApiKeyQuery {
+                    label: "treasury".to_owned(),
+                    key_hash: api_key_hash(&key),
+                    max_tokens_per_request: None,
+                    max_requests_per_hour: None,
+                    created_at: Utc::now(),
+                })
+                .await?;
+            let now = Utc::now().with_nanosecond(0).unwrap();
+            let request = fe_schema
+                .store_request(SaveForcedExitRequestQuery {
+                    target: Address::repeat_byte(0x36),
+                    tokens: vec![TokenId(1)],
+                    price_in_wei: BigUint::from(212u32),
+                    created_at: now,
+                    valid_until: now + Duration::days(1),
+                    metadata: None,
+                    payment_terms: None,
+                    callback_url: None,
+                })
+                .await?;
+            let mut ids = Vec::new();
+            for requires_approval in [true, false] {
+                let refund = fe_schema
+                    .store_refund(SaveForcedExitRefundQuery {
+                        request_id: request.id,
+                        payment_tx_hash: H256::random(),
+                        recipient: Address::repeat_byte(0x42),
+                        amount: BigUint::from(200u32),
+                        fee: BigUint::from(12u32),
+                        reason: ForcedExitRefundReason::IncorrectAmount,
+                        requires_approval,
+                        created_at: now,
+                    })
+                    .await?
+                    .unwrap();
+                ids.push(refund.id);
+            }
+            (ids[0], ids[1])
+        };
+
+        let (_client, server) = cfg.start_server_with_scope(
+            String::from("admin/forced_exit_requests"),
+            |cfg| api_scope(test_service(cfg), TEST_SECRET_AUTH.to_owned()),
+            Option::<SharedData>::None,
+        );
+
+        let awaiting: Vec<ForcedExitRefund> = server
+            .get("/admin/forced_exit_requests/refunds/awaiting_approval")
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(awaiting.iter().any(|refund| refund.id == approved_id));
+        assert!(awaiting.iter().all(|refund| refund.id != pending_id));
+
+        let approve_path = |id| format!("/admin/forced_exit_requests/refunds/{}/approve", id);
+        // The operator approving the refund is only known from the API key
+        let response = server
+            .post(&approve_path(approved_id))
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        let approved: ForcedExitRefund = server
+            .post(&approve_path(approved_id))
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .insert_header((API_KEY_HEADER, key.as_str()))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(approved.status, ForcedExitRefundStatus::Pending);
+        assert_eq!(approved.approved_by.as_deref(), Some("treasury"));
+        assert!(approved.approved_at.is_some());
+
+        // Neither the approved refunds nor the ones below the threshold are approved again
+        for id in [approved_id, pending_id] {
+            let response = server
+                .post(&approve_path(id))
+                .bearer_auth(auth_token(TEST_SECRET_AUTH))
+                .insert_header((API_KEY_HEADER, key.as_str()))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 400);
+        }
+        let response = server
+            .post(&approve_path(-1))
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .insert_header((API_KEY_HEADER, key.as_str()))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn test_callback_redelivery() -> anyhow::Result<()> {
+        let cfg = TestServerConfig {
+            config: ZkSyncConfig::from_env(),
+            pool: ConnectionPool::new(Some(1)),
+        };
+
+        let delivery_id = {
+            let mut storage = cfg.pool.access_storage().await?;
+            let mut fe_schema = storage.forced_exit_requests_schema();
+            let now = Utc::now().with_nanosecond(0).unwrap();
+            let request = fe_schema
+                .store_request(SaveForcedExitRequestQuery {
+                    target: Address::repeat_byte(0x37),
+                    tokens: vec![TokenId(1)],
+                    price_in_wei: BigUint::from(212u32),
+                    created_at: now,
+                    valid_until: now + Duration::days(1),
+                    metadata: None,
+                    payment_terms: None,
+                    callback_url: Some("https://example.com/fulfilled".to_owned()),
+                })
+                .await?;
+            fe_schema.set_fulfilled_at(request.id, now).await?;
+
+            let callback = fe_schema
+                .load_request_deliveries(request.id)
+                .await?
+                .into_iter()
+                .find(ForcedExitRequestDelivery::is_callback)
+                .unwrap();
+            fe_schema
+                .mark_delivery_failed(callback.id, "connection refused", now)
+                .await?;
+            callback.id
+        };
+
+        let (_client, server) = cfg.start_server_with_scope(
+            String::from("admin/forced_exit_requests"),
+            |cfg| api_scope(test_service(cfg), TEST_SECRET_AUTH.to_owned()),
+            Option::<SharedData>::None,
+        );
+
+        let failed: Vec<ForcedExitRequestDelivery> = server
+            .get("/admin/forced_exit_requests/deliveries/failed")
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(failed.iter().any(|delivery| delivery.id == delivery_id));
+
+        let redeliver_path =
+            |id| format!("/admin/forced_exit_requests/deliveries/{}/redeliver", id);
+        let delivery: ForcedExitRequestDelivery = server
+            .post(&redeliver_path(delivery_id))
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(delivery.attempts, 0);
+        assert!(!delivery.is_failed());
+
+        // The callback is only redelivered once given up on
+        let response = server
+            .post(&redeliver_path(delivery_id))
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        let response = server
+            .post(&redeliver_path(-1))
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn test_config_simulation() -> anyhow::Result<()> {
+        let cfg = TestServerConfig {
+            config: ZkSyncConfig::from_env(),
+            pool: ConnectionPool::new(Some(1)),
+        };
+        let (_client, server) = cfg.start_server_with_scope(
+            String::from("admin/forced_exit_requests"),
+            |cfg| api_scope(test_service(cfg), TEST_SECRET_AUTH.to_owned()),
+            Option::<SharedData>::None,
+        );
+
+        let report: ForcedExitConfigImpactReport = server
+            .post("/admin/forced_exit_requests/config/simulate")
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send_json(&serde_json::json!({ "maxMetadataLength": 1024 }))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(report.changes.len(), 1);
+        assert_eq!(report.changes[0].key, "maxMetadataLength");
+        assert_eq!(
+            report.current_pipeline_config_hash,
+            report.candidate_pipeline_config_hash
+        );
+        assert!(report.unmatchable.is_empty());
+
+        // Neither the values which can not be used together nor the unknown keys are accepted
+        for candidate in [
+            serde_json::json!({ "pricePerToken": 1 }),
+            serde_json::json!({ "digitsInId": 0 }),
+            serde_json::json!({ "pricePerTokn": 1000 }),
+        ] {
+            let response = server
+                .post("/admin/forced_exit_requests/config/simulate")
+                .bearer_auth(auth_token(TEST_SECRET_AUTH))
+                .send_json(&candidate)
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 400);
+        }
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn test_consistency_check() -> anyhow::Result<()> {
+        let cfg = TestServerConfig {
+            config: ZkSyncConfig::from_env(),
+            pool: ConnectionPool::new(Some(1)),
+        };
+
+        // The refund of the payment which has never been received
+        let request_id = {
+            let mut storage = cfg.pool.access_storage().await?;
+            let now = Utc::now().with_nanosecond(0).unwrap();
+            let request = storage
+                .forced_exit_requests_schema()
+                .store_request(SaveForcedExitRequestQuery {
+                    target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+                    tokens: vec![TokenId(1)],
+                    price_in_wei: BigUint::from(212u32),
+                    created_at: now,
+                    valid_until: now + Duration::days(1),
+                    metadata: None,
+                    payment_terms: None,
+                    callback_url: None,
+                })
+                .await?;
+            storage
+                .forced_exit_requests_schema()
+                .store_refund(SaveForcedExitRefundQuery {
+                    request_id: request.id,
+                    payment_tx_hash: H256::random(),
+                    recipient: Address::repeat_byte(0x42),
+                    amount: BigUint::from(200u32),
+                    fee: BigUint::from(12u32),
+                    reason: ForcedExitRefundReason::Expired,
+                    requires_approval: false,
+                    created_at: now,
+                })
+                .await?;
+            request.id
+        };
+
+        let (_client, server) = cfg.start_server_with_scope(
+            String::from("admin/forced_exit_requests"),
+            |cfg| api_scope(test_service(cfg), TEST_SECRET_AUTH.to_owned()),
+            Option::<SharedData>::None,
+        );
+
+        let response = server
+            .post("/admin/forced_exit_requests/consistency/check")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+
+        let report: ForcedExitConsistencyReport = server
+            .post("/admin/forced_exit_requests/consistency/check")
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(report.checked_requests >= 1);
+        let violations: Vec<_> = report
+            .violations
+            .iter()
+            .filter(|violation| violation.request_id == request_id)
+            .map(|violation| violation.invariant)
+            .collect();
+        assert_eq!(violations, vec![ForcedExitInvariant::RefundedPayment]);
+
+        // The report is kept along with the ones of the periodic checks
+        let reports: Vec<ForcedExitConsistencyReport> = server
+            .get("/admin/forced_exit_requests/consistency/reports")
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(reports.first(), Some(&report));
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn test_retry_request() -> anyhow::Result<()> {
+        let cfg = TestServerConfig {
+            config: ZkSyncConfig::from_env(),
+            pool: ConnectionPool::new(Some(1)),
+        };
+
+        // The fulfilled request, the unpaid one and the expired one stuck with its transactions
+        let now = Utc::now().with_nanosecond(0).unwrap();
+        let stuck_tx_hash = TxHash::from_str(
+            "sync-tx:6ab1d6ec8d7d3b9f0e5b7b2dd3d6e4b7f4b0ddba8a1c0b8f7e2e7e4d9d7f6e5a",
+        )
+        .unwrap();
+        let (fulfilled_id, unpaid_id, stuck_id) = {
+            let mut storage = cfg.pool.access_storage().await?;
+            let mut fe_schema = storage.forced_exit_requests_schema();
+            let mut ids = Vec::new();
+            for valid_until in [now + Duration::days(1), now + Duration::days(1), now] {
+                let request = fe_schema
+                    .store_request(SaveForcedExitRequestQuery {
+                        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2")
+                            .unwrap(),
+                        tokens: vec![TokenId(1)],
+                        price_in_wei: BigUint::from(212u32),
+                        created_at: now - Duration::days(2),
+                        valid_until,
+                        metadata: None,
+                        payment_terms: None,
+                        callback_url: None,
+                    })
+                    .await?;
+                ids.push(request.id);
+            }
+            for id in [ids[0], ids[2]] {
+                fe_schema
+                    .set_match_scheme(id, PaymentMatchScheme::ExplicitId, now - Duration::days(1))
+                    .await?;
+                fe_schema
+                    .set_paid_amount(id, &BigUint::from(212u32))
+                    .await?;
+            }
+            fe_schema.set_fulfilled_at(ids[0], now).await?;
+            fe_schema
+                .set_fulfilled_by(ids[2], Some(vec![stuck_tx_hash]), true)
+                .await?;
+            (ids[0], ids[1], ids[2])
+        };
+
+        // The sender picks up the retries and sends the transactions
+        let sent_tx_hash = TxHash::default();
+        let sender = tokio::spawn(async move {
+            let mut storage = StorageProcessor::establish_connection().await.unwrap();
+            loop {
+                let retries = storage
+                    .forced_exit_requests_schema()
+                    .load_pending_retries(10)
+                    .await
+                    .unwrap();
+                for retry in retries {
+                    storage
+                        .forced_exit_requests_schema()
+                        .complete_retry(retry.id, &[sent_tx_hash], None, Utc::now())
+                        .await
+                        .unwrap();
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            }
+        });
+
+        let (_client, server) = cfg.start_server_with_scope(
+            String::from("admin/forced_exit_requests"),
+            |cfg| api_scope(test_service(cfg), TEST_SECRET_AUTH.to_owned()),
+            Option::<SharedData>::None,
+        );
+        let retry_url =
+            |id: ForcedExitRequestId| format!("/admin/forced_exit_requests/requests/{}/retry", id);
+
+        let response = server.post(retry_url(stuck_id)).send().await.unwrap();
+        assert_eq!(response.status(), 401);
+
+        for id in [fulfilled_id, unpaid_id, stuck_id] {
+            let response = server
+                .post(retry_url(id))
+                .bearer_auth(auth_token(TEST_SECRET_AUTH))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 400);
+        }
+
+        // The expired request was payable when paid for
+        let retry: ForcedExitRetry = server
+            .post(format!("{}?force=true", retry_url(stuck_id)))
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(retry.request_id, stuck_id);
+        assert!(retry.force);
+        assert_eq!(retry.tx_hashes, vec![sent_tx_hash]);
+        let stored: ForcedExitRetry = server
+            .get(format!("/admin/forced_exit_requests/retries/{}", retry.id))
+            .bearer_auth(auth_token(TEST_SECRET_AUTH))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(stored, retry);
+
+        // The transactions the request was stuck with are forgotten
+        let mut storage = cfg.pool.access_storage().await?;
+        let request = storage
+            .forced_exit_requests_schema()
+            .get_request_by_id(stuck_id)
+            .await?
+            .unwrap();
+        assert_eq!(request.fulfilled_by, None);
+
+        sender.abort();
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn test_disabled_features() -> anyhow::Result<()> {
+        let mut cfg = TestServerConfig {
+            config: ZkSyncConfig::from_env(),
+            pool: ConnectionPool::new(Some(1)),
+        };
+        cfg.config.forced_exit_requests.l1_escalation_enabled = false;
+        cfg.config.forced_exit_requests.refunds_enabled = false;
+        cfg.config.forced_exit_requests.callbacks_enabled = false;
+
+        let (_client, server) = cfg.start_server_with_scope(
+            String::from("admin/forced_exit_requests"),
+            |cfg| api_scope(test_service(cfg), TEST_SECRET_AUTH.to_owned()),
+            Option::<SharedData>::None,
+        );
+
+        let finalize = FinalizeEscalationRequest {
+            l1_tx_hash: H256::repeat_byte(0x12),
+        };
+        let responses = vec![
+            (
+                ForcedExitFeature::Refunds,
+                server
+                    .get("/admin/forced_exit_requests/refunds/awaiting_approval")
+                    .bearer_auth(auth_token(TEST_SECRET_AUTH))
+                    .send()
+                    .await,
+            ),
+            (
+                ForcedExitFeature::Refunds,
+                server
+                    .post("/admin/forced_exit_requests/refunds/1/approve")
+                    .bearer_auth(auth_token(TEST_SECRET_AUTH))
+                    .send()
+                    .await,
+            ),
+            (
+                ForcedExitFeature::Callbacks,
+                server
+                    .get("/admin/forced_exit_requests/deliveries/failed")
+                    .bearer_auth(auth_token(TEST_SECRET_AUTH))
+                    .send()
+                    .await,
+            ),
+            (
+                ForcedExitFeature::Callbacks,
+                server
+                    .post("/admin/forced_exit_requests/deliveries/1/redeliver")
+                    .bearer_auth(auth_token(TEST_SECRET_AUTH))
+                    .send()
+                    .await,
+            ),
+            (
+                ForcedExitFeature::L1Escalation,
+                server
+                    .get("/admin/forced_exit_requests/escalations")
+                    .bearer_auth(auth_token(TEST_SECRET_AUTH))
+                    .send()
+                    .await,
+            ),
+            (
+                ForcedExitFeature::L1Escalation,
+                server
+                    .post("/admin/forced_exit_requests/escalations/1/finalize")
+                    .bearer_auth(auth_token(TEST_SECRET_AUTH))
+                    .send_json(&finalize)
+                    .await,
+            ),
+        ];
+        for (feature, response) in responses {
+            let mut response = response.unwrap();
+            assert_eq!(response.status(), 400);
+            let body: ErrorBody = response.json().await.unwrap();
+            assert_eq!(
+                body.title,
+                ForcedExitRequestsError::FeatureDisabled(feature).to_string()
+            );
+        }
+
+        server.stop().await;
+        Ok(())
+    }
+}
@@ -4,10 +4,16 @@ use std::fmt::{self, Display};
 // External uses
 use actix_web::{dev::Body, http::HeaderValue, HttpResponse, ResponseError};
 use reqwest::{header::CONTENT_TYPE, StatusCode};
+use thiserror::Error;
 
 // Workspace uses
 use zksync_api_client::rest::error::ErrorBody;
+use zksync_api_types::v02::pagination::MAX_LIMIT;
+use zksync_types::{forced_exit_requests::ForcedExitFeature, TokenId};
 // Local uses
+use super::service::{
+    MAX_CALLBACK_URL_LENGTH, MAX_NOTE_LENGTH, MAX_NOTE_TAGS, MAX_NOTE_TAG_LENGTH,
+};
 use crate::api_server::tx_sender::SubmitError;
 
 /// An HTTP error structure.
@@ -35,6 +41,16 @@ impl ApiError {
         Self::with_code(StatusCode::NOT_FOUND, title)
     }
 
+    /// Creates a new Error with the TOO_MANY_REQUESTS (429) status code.
+    pub fn too_many_requests(title: impl Display) -> Self {
+        Self::with_code(StatusCode::TOO_MANY_REQUESTS, title)
+    }
+
+    /// Creates a new Error with the SERVICE_UNAVAILABLE (503) status code.
+    pub fn service_unavailable(title: impl Display) -> Self {
+        Self::with_code(StatusCode::SERVICE_UNAVAILABLE, title)
+    }
+
     fn with_code(http_code: StatusCode, title: impl Display) -> Self {
         Self {
             http_code,
@@ -126,3 +142,109 @@ impl From<SubmitError> for ApiError {
         .code(internal_code)
     }
 }
+
+/// Errors of the forced exit requests logic shared between the API versions.
+#[derive(Debug, Error)]
+pub enum ForcedExitRequestsError {
+    #[error("ForcedExit requests feature is disabled")]
+    Disabled,
+    #[error("Maximum number of tokens per ForcedExit request exceeded")]
+    TooManyTokens,
+    #[error("ForcedExit request should withdraw at least one token")]
+    NoTokens,
+    #[error("The amount should be exactly the price of the supplied withdrawals")]
+    IncorrectPrice,
+    #[error("One of the tokens does no exist")]
+    TokenNotFound,
+    #[error("ForcedExit requests can not be created for the token {0}")]
+    TokenNotAllowed(TokenId),
+    #[error("The token {0} is an NFT, ForcedExit can only withdraw the fungible tokens")]
+    NftNotForceable(TokenId),
+    #[error("Metadata of the ForcedExit request should be at most {0} bytes long")]
+    MetadataTooLong(usize),
+    #[error("The {0:?} feature of ForcedExit requests is disabled")]
+    FeatureDisabled(ForcedExitFeature),
+    #[error(
+        "Callback URL should be an HTTPS URL of at most {} characters",
+        MAX_CALLBACK_URL_LENGTH
+    )]
+    InvalidCallbackUrl,
+    #[error("At most {0} ForcedExit requests may await the payment for the same account")]
+    TooManyActiveRequests(u32),
+    #[error("Request with such id does not exist")]
+    RequestNotFound,
+    #[error("Payment with such transaction hash has not been seen")]
+    PaymentNotFound,
+    #[error("Request has already been paid for")]
+    RequestNotPending,
+    #[error("Request has already been fulfilled")]
+    RequestAlreadyFulfilled,
+    #[error("Request has not been paid for")]
+    RequestNotPaid,
+    #[error("Request has been cancelled, it is only processed again once extended")]
+    RequestCancelled,
+    #[error("Request has expired, retry it with `force=true` to check it as of the time it was paid for")]
+    RequestExpired,
+    #[error("Retry with such id does not exist")]
+    RetryNotFound,
+    #[error("Refund with such id does not exist")]
+    RefundNotFound,
+    #[error("Refund does not await the approval")]
+    RefundNotAwaitingApproval,
+    #[error("Notification with such id does not exist")]
+    DeliveryNotFound,
+    #[error("Only the callbacks which have run out of the attempts can be redelivered")]
+    DeliveryNotFailed,
+    #[error("Invalid config candidate: {0}")]
+    InvalidConfigCandidate(String),
+    #[error("Limit for pagination should be less than or equal to {}", MAX_LIMIT)]
+    PaginationLimitTooBig,
+    #[error("API key is invalid or has been revoked")]
+    InvalidApiKey,
+    #[error("Request was not created with the supplied API key")]
+    ApiKeyMismatch,
+    #[error("Payment with such transaction hash is expected for another request")]
+    PaymentExpectedForAnotherRequest,
+    #[error("Too many ForcedExit requests have been created in the last hour")]
+    RateLimitExceeded,
+    #[error("Too many ForcedExit requests are awaiting the payment at the moment, retry later")]
+    IdSpaceExhausted,
+    #[error(
+        "Note should have the text of at most {} characters and at most {} tags of at most {} characters without whitespace",
+        MAX_NOTE_LENGTH,
+        MAX_NOTE_TAGS,
+        MAX_NOTE_TAG_LENGTH
+    )]
+    InvalidNote,
+    #[error(transparent)]
+    Submit(#[from] SubmitError),
+    #[error("{0}")]
+    Storage(String),
+}
+
+impl ForcedExitRequestsError {
+    pub fn storage(err: impl Display) -> Self {
+        vlog::warn!("Internal Server Error: '{}';", err);
+        Self::Storage(err.to_string())
+    }
+}
+
+impl From<ForcedExitRequestsError> for ApiError {
+    fn from(inner: ForcedExitRequestsError) -> Self {
+        match inner {
+            ForcedExitRequestsError::Submit(err) => err.into(),
+            ForcedExitRequestsError::Storage(err) => ApiError::internal(err),
+            ForcedExitRequestsError::RequestNotFound
+            | ForcedExitRequestsError::PaymentNotFound
+            | ForcedExitRequestsError::RetryNotFound
+            | ForcedExitRequestsError::RefundNotFound
+            | ForcedExitRequestsError::DeliveryNotFound => ApiError::not_found(inner),
+            ForcedExitRequestsError::RateLimitExceeded
+            | ForcedExitRequestsError::TooManyActiveRequests(_) => {
+                ApiError::too_many_requests(inner)
+            }
+            ForcedExitRequestsError::IdSpaceExhausted => ApiError::service_unavailable(inner),
+            _ => ApiError::bad_request(inner),
+        }
+    }
+}
@@ -1,32 +1,92 @@
 // External uses
-use actix_web::{web, Scope};
+use actix_web::{web, HttpRequest, Scope};
 
 // Workspace uses
 pub use zksync_api_client::rest::client::{Client, ClientError};
+use zksync_api_client::rest::forced_exit_requests::API_KEY_HEADER;
 use zksync_config::ForcedExitRequestsConfig;
 use zksync_storage::ConnectionPool;
+use zksync_types::network::Network;
 
 // Local uses
 use crate::api_server::forced_exit_checker::ForcedExitChecker;
 use error::ApiError;
 use ethabi::Address;
+use service::ForcedExitRequestsService;
 
-mod error;
+mod admin;
+pub(crate) mod error;
+mod remote;
+pub(crate) mod service;
 mod v01;
+pub(crate) mod v02;
 
 pub type JsonResult<T> = std::result::Result<web::Json<T>, ApiError>;
 
+/// Returns the API key of the trusted partner supplied with the request.
+fn api_key(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+}
+
 pub(crate) fn api_scope(
     connection_pool: ConnectionPool,
     forced_exit_minimum_account_age_secs: u64,
     config: &ForcedExitRequestsConfig,
     contract: Address,
+    network: Network,
 ) -> Scope {
     let fe_age_checker = ForcedExitChecker::new(forced_exit_minimum_account_age_secs);
-    web::scope("/api/forced_exit_requests").service(v01::api_scope(
+    web::scope("/api/forced_exit_requests")
+        .service(v01::api_scope(
+            connection_pool.clone(),
+            config,
+            contract,
+            Box::new(fe_age_checker.clone()),
+        ))
+        .service(v02::api_scope(
+            connection_pool,
+            config,
+            contract,
+            Box::new(fe_age_checker),
+            network,
+        ))
+}
+
+/// Creates the forced exit requests service for the front-ends other than the public REST API.
+pub(crate) fn create_service(
+    connection_pool: ConnectionPool,
+    forced_exit_minimum_account_age_secs: u64,
+    config: &ForcedExitRequestsConfig,
+    contract: Address,
+) -> ForcedExitRequestsService {
+    let fe_age_checker = ForcedExitChecker::new(forced_exit_minimum_account_age_secs);
+    ForcedExitRequestsService::new(connection_pool, config, contract, Box::new(fe_age_checker))
+}
+
+pub(crate) fn admin_scope(
+    connection_pool: ConnectionPool,
+    forced_exit_minimum_account_age_secs: u64,
+    config: &ForcedExitRequestsConfig,
+    contract: Address,
+    secret_auth: String,
+) -> Scope {
+    let service = create_service(
+        connection_pool.clone(),
+        forced_exit_minimum_account_age_secs,
+        config,
+        contract,
+    );
+    let remote_service = create_service(
         connection_pool,
+        forced_exit_minimum_account_age_secs,
         config,
         contract,
-        Box::new(fe_age_checker),
-    ))
+    );
+    // The remote scope goes first, since the admin one is mounted at the root
+    // and would take its requests otherwise
+    web::scope("/admin/forced_exit_requests")
+        .service(remote::api_scope(remote_service, secret_auth.clone()))
+        .service(admin::api_scope(service, secret_auth))
 }
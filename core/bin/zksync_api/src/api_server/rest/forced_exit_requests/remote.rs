@@ -0,0 +1,459 @@
+//! Part of the operators API used by the forced exit requests component running
+//! separately from the server, see `zksync_forced_exit_requests::remote`.
+//!
+//! The component watches the payments, matches them and sends the `ForcedExit`
+//! transactions, while the requests themselves stay in the database of the server.
+//! These endpoints give it access to the data it needs for that and nothing else.
+//! All the endpoints require the JWT auth token signed with the admin secret.
+
+// Built-in uses
+use std::{str::FromStr, time::Instant};
+
+// External uses
+use actix_web::{
+    web::{self, Json},
+    Scope,
+};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use chrono::Utc;
+use num::BigUint;
+
+// Workspace uses
+use zksync_api_client::rest::forced_exit_requests::remote::{
+    BeginFulfillmentRequest, CancelRequestRequest, DeleteOldRequestsRequest, ForcedExitTxReceipt,
+    SetFulfilledByRequest, SetMatchSchemeRequest, TxReceiptsRequest,
+};
+use zksync_types::{
+    forced_exit_requests::{
+        ForcedExitCancellationKind, ForcedExitRequest, ForcedExitRequestId, ForcedExitTargetCheck,
+    },
+    tx::TxHash,
+    AccountId, Address, Nonce,
+};
+
+// Local uses
+use super::{
+    admin::validate_auth_token, error::ApiError, service::ForcedExitRequestsService, JsonResult,
+};
+
+async fn get_unconfirmed_requests(
+    data: web::Data<ForcedExitRequestsService>,
+) -> JsonResult<Vec<ForcedExitRequest>> {
+    let start = Instant::now();
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let requests = storage
+        .forced_exit_requests_schema()
+        .get_unconfirmed_requests()
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "remote_get_unconfirmed_requests");
+    Ok(Json(requests))
+}
+
+async fn get_oldest_unfulfilled_request(
+    data: web::Data<ForcedExitRequestsService>,
+) -> JsonResult<Option<ForcedExitRequest>> {
+    let start = Instant::now();
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let request = storage
+        .forced_exit_requests_schema()
+        .get_oldest_unfulfilled_request()
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "remote_get_oldest_unfulfilled_request");
+    Ok(Json(request))
+}
+
+async fn get_request(
+    data: web::Data<ForcedExitRequestsService>,
+    request_id: web::Path<ForcedExitRequestId>,
+) -> JsonResult<Option<ForcedExitRequest>> {
+    let start = Instant::now();
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let request = storage
+        .forced_exit_requests_schema()
+        .get_request_by_id(*request_id)
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "remote_get_request");
+    Ok(Json(request))
+}
+
+async fn get_request_by_public_id(
+    data: web::Data<ForcedExitRequestsService>,
+    public_id: web::Path<ForcedExitRequestId>,
+) -> JsonResult<Option<ForcedExitRequest>> {
+    let start = Instant::now();
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let request = storage
+        .forced_exit_requests_schema()
+        .get_request_by_public_id(*public_id)
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "remote_get_request_by_public_id");
+    Ok(Json(request))
+}
+
+async fn set_fulfilled(
+    data: web::Data<ForcedExitRequestsService>,
+    request_id: web::Path<ForcedExitRequestId>,
+) -> JsonResult<()> {
+    let start = Instant::now();
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    storage
+        .forced_exit_requests_schema()
+        .set_fulfilled_at(*request_id, Utc::now())
+        .await
+        .map_err(ApiError::internal)?;
+    vlog::info!("ForcedExit request with id {} was fulfilled", *request_id);
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "remote_set_fulfilled");
+    Ok(Json(()))
+}
+
+async fn set_fulfilled_by(
+    data: web::Data<ForcedExitRequestsService>,
+    request_id: web::Path<ForcedExitRequestId>,
+    params: web::Json<SetFulfilledByRequest>,
+) -> JsonResult<()> {
+    let start = Instant::now();
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let mut fe_schema = storage.forced_exit_requests_schema();
+    match params.into_inner().fulfilled_by {
+        Some(tx_hashes) => {
+            fe_schema
+                .set_fulfilled_by(
+                    *request_id,
+                    Some(tx_hashes),
+                    data.legacy_fulfilled_by_enabled,
+                )
+                .await
+        }
+        // The components preceding the cancellations reset the failed transactions this way
+        None => fe_schema
+            .cancel_request(
+                *request_id,
+                ForcedExitCancellationKind::SystemRetry,
+                Utc::now(),
+                data.legacy_fulfilled_by_enabled,
+            )
+            .await
+            .map(drop),
+    }
+    .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "remote_set_fulfilled_by");
+    Ok(Json(()))
+}
+
+async fn cancel_request(
+    data: web::Data<ForcedExitRequestsService>,
+    request_id: web::Path<ForcedExitRequestId>,
+    params: web::Json<CancelRequestRequest>,
+) -> JsonResult<Option<ForcedExitRequest>> {
+    let start = Instant::now();
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let request = storage
+        .forced_exit_requests_schema()
+        .cancel_request(
+            *request_id,
+            params.kind,
+            Utc::now(),
+            data.legacy_fulfilled_by_enabled,
+        )
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "remote_cancel_request");
+    Ok(Json(request))
+}
+
+async fn set_match_scheme(
+    data: web::Data<ForcedExitRequestsService>,
+    request_id: web::Path<ForcedExitRequestId>,
+    params: web::Json<SetMatchSchemeRequest>,
+) -> JsonResult<()> {
+    let start = Instant::now();
+    let paid_amount: Option<BigUint> = params
+        .paid_amount
+        .as_deref()
+        .map(BigUint::from_str)
+        .transpose()
+        .map_err(ApiError::bad_request)?;
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let mut transaction = storage
+        .start_transaction()
+        .await
+        .map_err(ApiError::internal)?;
+    let matched_at = Utc::now();
+
+    let mut fe_schema = transaction.forced_exit_requests_schema();
+    fe_schema
+        .set_match_scheme(*request_id, params.match_scheme, matched_at)
+        .await
+        .map_err(ApiError::internal)?;
+    if let Some(paid_amount) = &paid_amount {
+        fe_schema
+            .set_paid_amount(*request_id, paid_amount)
+            .await
+            .map_err(ApiError::internal)?;
+    }
+    if let Some(payment_tx_hash) = params.payment_tx_hash {
+        fe_schema
+            .store_payment_match(*request_id, payment_tx_hash, matched_at)
+            .await
+            .map_err(ApiError::internal)?;
+    }
+    transaction.commit().await.map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "remote_set_match_scheme");
+    Ok(Json(()))
+}
+
+async fn begin_fulfillment(
+    data: web::Data<ForcedExitRequestsService>,
+    request_id: web::Path<ForcedExitRequestId>,
+    params: web::Json<BeginFulfillmentRequest>,
+) -> JsonResult<bool> {
+    let start = Instant::now();
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let started = storage
+        .forced_exit_requests_schema()
+        .begin_fulfillment(*request_id, params.payment_tx_hash, Utc::now())
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "remote_begin_fulfillment");
+    Ok(Json(started))
+}
+
+async fn delete_old_requests(
+    data: web::Data<ForcedExitRequestsService>,
+    params: web::Json<DeleteOldRequestsRequest>,
+) -> JsonResult<()> {
+    let start = Instant::now();
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    storage
+        .forced_exit_requests_schema()
+        .delete_old_unfulfilled_requests(chrono::Duration::milliseconds(
+            params.deleting_threshold_millis,
+        ))
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "remote_delete_old_requests");
+    Ok(Json(()))
+}
+
+async fn get_tx_receipts(
+    data: web::Data<ForcedExitRequestsService>,
+    params: web::Json<TxReceiptsRequest>,
+) -> JsonResult<Vec<ForcedExitTxReceipt>> {
+    let start = Instant::now();
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let receipts = storage
+        .chain()
+        .operations_ext_schema()
+        .tx_receipts(&params.tx_hashes)
+        .await
+        .map_err(ApiError::internal)?;
+
+    let receipts = receipts
+        .into_iter()
+        .map(|receipt| {
+            let tx_hash = hex::decode(&receipt.tx_hash)
+                .ok()
+                .and_then(|hash| TxHash::from_slice(&hash))
+                .ok_or_else(|| ApiError::internal("Invalid hash of the executed transaction"))?;
+            Ok(ForcedExitTxReceipt {
+                tx_hash,
+                block_number: receipt.block_number,
+                success: receipt.success,
+                verified: receipt.verified,
+                fail_reason: receipt.fail_reason,
+            })
+        })
+        .collect::<Result<_, ApiError>>()?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "remote_get_tx_receipts");
+    Ok(Json(receipts))
+}
+
+async fn check_target(
+    data: web::Data<ForcedExitRequestsService>,
+    target: web::Path<Address>,
+) -> JsonResult<ForcedExitTargetCheck> {
+    let start = Instant::now();
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let target_check = data
+        .forced_exit_checker
+        .check_forced_exit_target(&mut storage, *target)
+        .await?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "remote_check_target");
+    Ok(Json(target_check))
+}
+
+async fn get_account_id(
+    data: web::Data<ForcedExitRequestsService>,
+    address: web::Path<Address>,
+) -> JsonResult<Option<AccountId>> {
+    let start = Instant::now();
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let account_id = storage
+        .chain()
+        .account_schema()
+        .account_id_by_address(*address)
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "remote_get_account_id");
+    Ok(Json(account_id))
+}
+
+async fn get_account_nonce(
+    data: web::Data<ForcedExitRequestsService>,
+    account_id: web::Path<AccountId>,
+) -> JsonResult<Option<Nonce>> {
+    let start = Instant::now();
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let (_, account) = storage
+        .chain()
+        .account_schema()
+        .last_committed_state_for_account(*account_id)
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "remote_get_account_nonce");
+    Ok(Json(account.map(|account| account.nonce)))
+}
+
+async fn get_account_pending_nonce(
+    data: web::Data<ForcedExitRequestsService>,
+    account_id: web::Path<AccountId>,
+) -> JsonResult<Option<Nonce>> {
+    let start = Instant::now();
+    let mut storage = data
+        .connection_pool
+        .access_storage()
+        .await
+        .map_err(ApiError::internal)?;
+    let nonce = storage
+        .chain()
+        .mempool_schema()
+        .get_pending_nonce(*account_id)
+        .await
+        .map_err(ApiError::internal)?;
+
+    metrics::histogram!("api", start.elapsed(), "type" => "admin", "endpoint_name" => "remote_get_account_pending_nonce");
+    Ok(Json(nonce))
+}
+
+pub fn api_scope(service: ForcedExitRequestsService, secret_auth: String) -> Scope {
+    let auth = HttpAuthentication::bearer(move |req, credentials| {
+        validate_auth_token(req, credentials, secret_auth.clone())
+    });
+
+    web::scope("/remote")
+        .wrap(auth)
+        .app_data(web::Data::new(service))
+        .route(
+            "/requests/unconfirmed",
+            web::get().to(get_unconfirmed_requests),
+        )
+        .route(
+            "/requests/oldest_unfulfilled",
+            web::get().to(get_oldest_unfulfilled_request),
+        )
+        .route("/requests/delete_old", web::post().to(delete_old_requests))
+        .route(
+            "/requests/by_public_id/{id}",
+            web::get().to(get_request_by_public_id),
+        )
+        .route("/requests/{id}", web::get().to(get_request))
+        .route("/requests/{id}/fulfilled", web::post().to(set_fulfilled))
+        .route(
+            "/requests/{id}/fulfilled_by",
+            web::post().to(set_fulfilled_by),
+        )
+        .route("/requests/{id}/cancel", web::post().to(cancel_request))
+        .route(
+            "/requests/{id}/match_scheme",
+            web::post().to(set_match_scheme),
+        )
+        .route(
+            "/requests/{id}/fulfillment",
+            web::post().to(begin_fulfillment),
+        )
+        .route("/receipts", web::post().to(get_tx_receipts))
+        .route(
+            "/accounts/{address}/target_check",
+            web::get().to(check_target),
+        )
+        .route("/accounts/{address}/id", web::get().to(get_account_id))
+        .route("/accounts/{id}/nonce", web::get().to(get_account_nonce))
+        .route(
+            "/accounts/{id}/pending_nonce",
+            web::get().to(get_account_pending_nonce),
+        )
+}
@@ -0,0 +1,3178 @@
+//! Forced exit requests logic shared by all the front-ends.
+//!
+//! The REST API versions, the JSON RPC methods and the admin endpoints are thin
+//! adapters over `ForcedExitRequestsService`, so the validation and the limits
+//! are implemented (and tested) only here.
+
+// Built-in uses
+use std::{collections::HashMap, convert::TryInto, ops::Add, str::FromStr, time::Instant};
+
+// External uses
+use chrono::{DateTime, Duration, Utc};
+use futures::{stream, StreamExt};
+use num::{BigUint, Zero};
+use tiny_keccak::keccak256;
+
+// Workspace uses
+use zksync_api_client::rest::forced_exit_requests::{
+    ConfigInfo, ForcedExitCreatedRequest, ForcedExitPaymentInstructions, ForcedExitPaymentLookup,
+    ForcedExitRegisterRequest, ForcedExitRequestAdminDetails, ForcedExitRequestDetails,
+    ForcedExitRequestProgress, ForcedExitRequestQueueInfo, ForcedExitRequestQuote,
+    ForcedExitRequestStatus, ForcedExitTokenProgress, IdSpaceUsage,
+};
+use zksync_api_types::v02::pagination::{
+    ForcedExitRequestsQuery, Paginated, PaginationQuery, MAX_LIMIT,
+};
+use zksync_api_types::Either;
+use zksync_config::ForcedExitRequestsConfig;
+use zksync_storage::{
+    forced_exit_requests::ForcedExitRequestsSchema, ConnectionPool, StorageProcessor,
+};
+use zksync_types::{
+    forced_exit_requests::{
+        amount_id_digits, id_space_size, overpayment_tolerance, ActiveTargetPolicy, CreationLimits,
+        ForcedExitBacklogReport, ForcedExitCancellationKind, ForcedExitConfigCandidate,
+        ForcedExitConfigChange, ForcedExitConfigImpactReport, ForcedExitConfigSnapshot,
+        ForcedExitConsistencyReport, ForcedExitEligibilityResponse, ForcedExitFeature,
+        ForcedExitInvariant, ForcedExitMaintenance, ForcedExitPaymentExplanation,
+        ForcedExitPaymentTerms, ForcedExitPipelineStage, ForcedExitPipelineVersion,
+        ForcedExitPreflight, ForcedExitQuoteChange, ForcedExitRefund, ForcedExitRefundId,
+        ForcedExitRejectedRequest, ForcedExitRequest, ForcedExitRequestDelivery,
+        ForcedExitRequestDeliveryId, ForcedExitRequestId, ForcedExitRequestLedger,
+        ForcedExitRequestNote, ForcedExitRequestsApiKey, ForcedExitRetry, ForcedExitRetryId,
+        ForcedExitUnmatchableRequest, FundsReceivedEvent, MaintenanceWindow, PaymentAddressWindow,
+        PaymentMatchStep, SaveForcedExitRequestNoteQuery, SaveForcedExitRequestQuery,
+        FORCED_EXIT_PIPELINE_VERSION,
+    },
+    network::Network,
+    Address, TokenId, TokenLike, H256,
+};
+
+// Local uses
+use super::error::ForcedExitRequestsError;
+use crate::api_server::forced_exit_checker::ForcedExitAccountAgeChecker;
+use crate::api_server::forced_exit_consistency::consistency_report;
+use crate::api_server::forced_exit_maintenance::maintenance_at;
+use crate::api_server::forced_exit_matcher::{PaymentMatchOutcome, PaymentMatcher};
+use crate::api_server::forced_exit_preflight::{
+    add_to_backlog_report, blocked_preflight, blocker_before_target, plan_forced_exits,
+    target_blocker,
+};
+use crate::api_server::forced_exit_receipts::{load_receipts, match_receipts, tx_status};
+use crate::utils::shared_lru_cache::SharedLruCache;
+
+/// The number of the requests of the backlog evaluated at once, each takes a connection.
+const BACKLOG_SIMULATION_CONCURRENCY: usize = 4;
+/// The maximum number of the requests awaiting the payment the config candidate is evaluated against.
+const CONFIG_SIMULATION_REQUESTS_LIMIT: u32 = 10_000;
+/// The number of the requests matched at once during the simulation, each takes a connection.
+const CONFIG_SIMULATION_CONCURRENCY: usize = 4;
+/// The maximum number of the requests checked against the invariants at once.
+const CONSISTENCY_CHECKED_REQUESTS_LIMIT: u32 = 1000;
+
+/// The queue moves slowly, so the positions are not recomputed on every status check.
+const QUEUE_INFO_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(5);
+const QUEUE_INFO_CACHE_SIZE: usize = 1000;
+/// How often the retry is checked while the sender processes it.
+const RETRY_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// The longest text of the note left by the operators, in characters.
+pub(crate) const MAX_NOTE_LENGTH: usize = 4096;
+pub(crate) const MAX_NOTE_TAGS: usize = 16;
+pub(crate) const MAX_NOTE_TAG_LENGTH: usize = 64;
+/// The longest URL the request is posted to once fulfilled, in characters.
+pub(crate) const MAX_CALLBACK_URL_LENGTH: usize = 2048;
+
+type CachedQueueInfo = (Instant, Option<ForcedExitRequestQueueInfo>);
+
+/// Creates, validates and manages the forced exit requests.
+pub struct ForcedExitRequestsService {
+    pub(crate) connection_pool: ConnectionPool,
+    pub(crate) forced_exit_checker: Box<dyn ForcedExitAccountAgeChecker>,
+
+    pub(crate) is_enabled: bool,
+    /// The limits of the new requests, see `CreationLimits`.
+    pub(crate) creation_limits: CreationLimits,
+    pub(crate) max_requests_per_hour: u32,
+    pub(crate) digits_in_id: u8,
+    pub(crate) recomended_tx_interval_millisecs: i64,
+    pub(crate) price_per_token: i64,
+    /// The payment address advertised to the payers.
+    pub(crate) forced_exit_contract_address: Address,
+    pub(crate) payment_addresses: Vec<PaymentAddressWindow>,
+    pub(crate) sender_account_address: Address,
+    pub(crate) wait_confirmations: u64,
+    pub(crate) id_space_alert_utilization: u8,
+    pub(crate) id_space_max_utilization: u8,
+    pub(crate) active_target_policy: ActiveTargetPolicy,
+    /// Whether the transactions sent by the remote component are written
+    /// to the deprecated `fulfilled_by` column as well.
+    pub(crate) legacy_fulfilled_by_enabled: bool,
+    /// The scheduled maintenance of the component, reported to the users waiting for
+    /// their requests to be fulfilled.
+    pub(crate) maintenance_windows: Vec<MaintenanceWindow>,
+    pub(crate) maintenance_lead_time: Duration,
+    /// The chain the payments are sent on, advertised in the payment instructions.
+    pub(crate) chain_id: Option<u64>,
+    /// The hash of the config the requests are created with, see `ForcedExitPipelineConfig`.
+    pub(crate) pipeline_config_hash: String,
+    pub(crate) enabled_features: Vec<ForcedExitFeature>,
+    /// The parts of the config the payment terms of the new requests are taken from,
+    /// see `ForcedExitPaymentTerms`.
+    pub(crate) overpayment_tolerance: u64,
+    pub(crate) overpayment_tolerance_percent: u8,
+    pub(crate) expiration_grace_period: u64,
+    /// The config the payments are matched with the requests by, see `PaymentMatcher`.
+    pub(crate) matching_config: ForcedExitRequestsConfig,
+
+    queue_cache: SharedLruCache<ForcedExitRequestId, CachedQueueInfo>,
+}
+
+impl ForcedExitRequestsService {
+    pub fn new(
+        connection_pool: ConnectionPool,
+        config: &ForcedExitRequestsConfig,
+        contract: Address,
+        forced_exit_checker: Box<dyn ForcedExitAccountAgeChecker>,
+    ) -> Self {
+        Self {
+            connection_pool,
+            forced_exit_checker,
+
+            is_enabled: config.enabled,
+            price_per_token: config.price_per_token,
+            creation_limits: config.creation_limits(),
+            max_requests_per_hour: config.max_requests_per_hour,
+            recomended_tx_interval_millisecs: config.recomended_tx_interval,
+            forced_exit_contract_address: config.active_payment_address(contract),
+            payment_addresses: config.payment_address_schedule(contract),
+            sender_account_address: config.sender_account_address,
+            digits_in_id: config.digits_in_id,
+            wait_confirmations: config.wait_confirmations,
+            id_space_alert_utilization: config.id_space_alert_utilization,
+            id_space_max_utilization: config.id_space_max_utilization,
+            active_target_policy: config.active_target_policy,
+            legacy_fulfilled_by_enabled: config.legacy_fulfilled_by_enabled,
+            maintenance_windows: config.maintenance_windows.clone(),
+            maintenance_lead_time: config.maintenance_lead_time(),
+            chain_id: None,
+            pipeline_config_hash: config.pipeline_config().hash(),
+            enabled_features: config.enabled_features(),
+            overpayment_tolerance: config.overpayment_tolerance,
+            overpayment_tolerance_percent: config.overpayment_tolerance_percent,
+            expiration_grace_period: config.expiration_grace_period,
+            matching_config: config.clone(),
+
+            queue_cache: SharedLruCache::new(QUEUE_INFO_CACHE_SIZE),
+        }
+    }
+
+    /// Sets the chain the payments are sent on, the test networks have no chain id.
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.chain_id = match network {
+            Network::Test | Network::Unknown => None,
+            network => Some(network.chain_id()),
+        };
+        self
+    }
+
+    pub async fn get_status(&self) -> Result<ForcedExitRequestStatus, ForcedExitRequestsError> {
+        if !self.is_enabled {
+            return Ok(ForcedExitRequestStatus::Disabled);
+        }
+
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let active_requests = storage
+            .forced_exit_requests_schema()
+            .count_awaiting_payment(Utc::now())
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+
+        Ok(ForcedExitRequestStatus::Enabled(ConfigInfo {
+            request_fee: BigUint::from(self.price_per_token as u64),
+            max_tokens_per_request: self.creation_limits.max_tokens_per_request,
+            recomended_tx_interval_millis: self.recomended_tx_interval_millisecs,
+            forced_exit_contract_address: self.forced_exit_contract_address,
+            wait_confirmations: self.wait_confirmations,
+            id_space: self.id_space_usage(active_requests),
+            payment_addresses: self.payment_addresses.clone(),
+            active_target_policy: self.active_target_policy,
+            maintenance: self.maintenance(),
+            enabled_features: self.enabled_features.clone(),
+            creation_limits: Some(self.creation_limits.clone()),
+        }))
+    }
+
+    fn maintenance(&self) -> Option<ForcedExitMaintenance> {
+        maintenance_at(
+            &self.maintenance_windows,
+            self.maintenance_lead_time,
+            Utc::now(),
+        )
+    }
+
+    fn id_space_usage(&self, active_requests: u32) -> IdSpaceUsage {
+        let id_space_size = 10_u64.saturating_pow(self.digits_in_id.into());
+        IdSpaceUsage {
+            active_requests,
+            id_space_size,
+            alert: exceeds_utilization(
+                active_requests,
+                id_space_size,
+                self.id_space_alert_utilization,
+            ),
+        }
+    }
+
+    // The amounts paid for the requests are matched by the lowest digits, so the more
+    // requests await the payment at once, the more likely their ids are to collide
+    fn check_id_space(&self, active_requests: u32) -> Result<(), ForcedExitRequestsError> {
+        // The usage once the new request is created
+        let usage = self.id_space_usage(active_requests.saturating_add(1));
+        metrics::gauge!(
+            "forced_exit_requests.id_space_utilization",
+            usage.active_requests as f64 / usage.id_space_size as f64
+        );
+        if exceeds_utilization(
+            usage.active_requests,
+            usage.id_space_size,
+            self.id_space_max_utilization,
+        ) {
+            metrics::increment_counter!("forced_exit_requests.id_space_exhausted");
+            return Err(ForcedExitRequestsError::IdSpaceExhausted);
+        }
+        if usage.alert {
+            vlog::warn!(
+                "{} ForcedExit requests are awaiting the payment with the id space of {}",
+                usage.active_requests,
+                usage.id_space_size
+            );
+            metrics::increment_counter!("forced_exit_requests.id_space_alert");
+        }
+
+        Ok(())
+    }
+
+    fn ensure_enabled(&self) -> Result<(), ForcedExitRequestsError> {
+        if self.is_enabled {
+            Ok(())
+        } else {
+            Err(ForcedExitRequestsError::Disabled)
+        }
+    }
+
+    /// The endpoints of the optional features are only served while the feature is enabled.
+    pub(crate) fn check_feature(
+        &self,
+        feature: ForcedExitFeature,
+    ) -> Result<(), ForcedExitRequestsError> {
+        if self.enabled_features.contains(&feature) {
+            Ok(())
+        } else {
+            Err(ForcedExitRequestsError::FeatureDisabled(feature))
+        }
+    }
+
+    /// The id of the request followed by its check digit is added to the price paid for it,
+    /// so the price is rounded up to keep the lowest `digits_in_id + 1` digits free. Otherwise the amount yields another
+    /// id and another price once the id is extracted and the payment is never matched.
+    ///
+    /// The requests without tokens are refused before they are priced, such a request
+    /// would cost nothing and have nothing to withdraw.
+    fn request_price(&self, tokens_count: usize) -> BigUint {
+        price_for_tokens(self.price_per_token, self.digits_in_id, tokens_count)
+    }
+
+    /// The terms the payments for the request created now with the given price are matched by.
+    /// They are stored along with the request, so the config may change while it is being paid.
+    fn payment_terms(&self, price: &BigUint) -> ForcedExitPaymentTerms {
+        ForcedExitPaymentTerms {
+            digits_in_id: self.digits_in_id,
+            check_digit: true,
+            overpayment_tolerance: overpayment_tolerance(
+                price,
+                self.overpayment_tolerance,
+                self.overpayment_tolerance_percent,
+            ),
+            expiration_grace_period: self.expiration_grace_period,
+        }
+    }
+
+    /// Returns the price of the request to withdraw the given number of tokens.
+    pub fn quote(
+        &self,
+        tokens_count: usize,
+    ) -> Result<ForcedExitRequestQuote, ForcedExitRequestsError> {
+        self.ensure_enabled()?;
+
+        if tokens_count == 0 {
+            return Err(ForcedExitRequestsError::NoTokens);
+        }
+        if tokens_count > self.creation_limits.max_tokens_per_request as usize {
+            return Err(ForcedExitRequestsError::TooManyTokens);
+        }
+
+        let price_in_wei = self.request_price(tokens_count);
+        Ok(ForcedExitRequestQuote {
+            tokens_count,
+            price_in_wei,
+            forced_exit_contract_address: self.forced_exit_contract_address,
+            chain_id: self.chain_id,
+        })
+    }
+
+    /// Attaches the instructions to pay for the created request. The payment is
+    /// matched by the exact amount sent to the advertised payment address.
+    pub fn with_payment_instructions(
+        &self,
+        request: ForcedExitRequest,
+    ) -> ForcedExitCreatedRequest {
+        let address = self.forced_exit_contract_address;
+        let payment = ForcedExitPaymentInstructions {
+            uri: payment_uri(address, &request.pay_exactly, self.chain_id),
+            address,
+            amount: request.pay_exactly.clone(),
+            chain_id: self.chain_id,
+            expires_at: request.valid_until,
+        };
+        ForcedExitCreatedRequest { request, payment }
+    }
+
+    /// Checks the request against the limits, which do not depend on the stored requests.
+    /// The API key may allow more tokens than the default limit.
+    fn check_creation_limits(
+        &self,
+        params: &ForcedExitRegisterRequest,
+        max_tokens_per_request: u8,
+    ) -> Result<(), ForcedExitRequestsError> {
+        check_limits(
+            &self.creation_limits,
+            &params.tokens,
+            params.metadata.as_deref(),
+            max_tokens_per_request,
+        )
+    }
+
+    /// The callbacks are only posted over HTTPS, the URL is checked once the request is created.
+    fn check_callback_url(
+        &self,
+        callback_url: Option<&str>,
+    ) -> Result<(), ForcedExitRequestsError> {
+        let callback_url = match callback_url {
+            Some(callback_url) => callback_url,
+            None => return Ok(()),
+        };
+        self.check_feature(ForcedExitFeature::Callbacks)?;
+        if callback_url.chars().count() > MAX_CALLBACK_URL_LENGTH {
+            return Err(ForcedExitRequestsError::InvalidCallbackUrl);
+        }
+        match reqwest::Url::parse(callback_url) {
+            Ok(url) if url.scheme() == "https" && url.host().is_some() => Ok(()),
+            _ => Err(ForcedExitRequestsError::InvalidCallbackUrl),
+        }
+    }
+
+    fn validity(&self) -> Duration {
+        Duration::milliseconds(self.creation_limits.max_validity)
+    }
+
+    /// Creates the request, the limits of the API key are applied instead of
+    /// the default ones if the key is supplied.
+    pub async fn create_request(
+        &self,
+        params: ForcedExitRegisterRequest,
+        api_key: Option<&str>,
+    ) -> Result<ForcedExitRequest, ForcedExitRequestsError> {
+        self.ensure_enabled()?;
+
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+
+        let api_key = match api_key {
+            Some(key) => Some(Self::resolve_api_key(&mut storage, key).await?),
+            None => None,
+        };
+        let max_tokens_per_request = api_key
+            .as_ref()
+            .and_then(|api_key| api_key.max_tokens_per_request)
+            .unwrap_or(self.creation_limits.max_tokens_per_request);
+        let max_requests_per_hour = api_key
+            .as_ref()
+            .and_then(|api_key| api_key.max_requests_per_hour)
+            .unwrap_or(self.max_requests_per_hour);
+
+        self.check_creation_limits(&params, max_tokens_per_request)?;
+        self.check_callback_url(params.callback_url.as_deref())?;
+
+        self.forced_exit_checker
+            .validate_forced_exit(&mut storage, params.target)
+            .await?;
+
+        if params.price_in_wei != self.request_price(params.tokens.len()) {
+            return Err(ForcedExitRequestsError::IncorrectPrice);
+        }
+
+        let mut tokens_schema = storage.tokens_schema();
+
+        for token_id in params.tokens.iter() {
+            // This is simply to make sure that the tokens
+            // that were supplied do indeed exist
+            let token = tokens_schema
+                .get_token(TokenLike::Id(*token_id))
+                .await
+                .ok()
+                .flatten()
+                .ok_or(ForcedExitRequestsError::TokenNotFound)?;
+            // Neither `ForcedExit` of the NFT is accepted by the circuit, nor `WithdrawNFT`
+            // can be sent without the signature of the owner, so the NFT would be paid for
+            // and never withdrawn
+            if token.is_nft {
+                return Err(ForcedExitRequestsError::NftNotForceable(*token_id));
+            }
+        }
+
+        let mut fe_schema = storage.forced_exit_requests_schema();
+
+        let created_at = Utc::now();
+        let valid_until = created_at.add(self.validity());
+
+        let active_for_target = fe_schema
+            .count_awaiting_payment_for_target(params.target, created_at)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        if active_for_target >= self.creation_limits.max_active_per_target {
+            return Err(ForcedExitRequestsError::TooManyActiveRequests(
+                self.creation_limits.max_active_per_target,
+            ));
+        }
+
+        let created_last_hour = fe_schema
+            .count_requests_created_since(
+                api_key.as_ref().map(|api_key| api_key.id),
+                created_at - Duration::hours(1),
+            )
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        if created_last_hour >= max_requests_per_hour {
+            return Err(ForcedExitRequestsError::RateLimitExceeded);
+        }
+        let active_requests = fe_schema
+            .count_awaiting_payment(created_at)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        self.check_id_space(active_requests)?;
+
+        let request = SaveForcedExitRequestQuery {
+            target: params.target,
+            tokens: params.tokens,
+            payment_terms: Some(self.payment_terms(&params.price_in_wei)),
+            price_in_wei: params.price_in_wei,
+            created_at,
+            valid_until,
+            metadata: params.metadata,
+            callback_url: params.callback_url,
+        };
+        let saved_fe_request = match &api_key {
+            Some(api_key) => {
+                fe_schema
+                    .store_request_with_api_key(request, api_key.id)
+                    .await
+            }
+            None => fe_schema.store_request(request).await,
+        }
+        .map_err(|err| {
+            vlog::error!("Store forced exit error {:?}", err);
+            ForcedExitRequestsError::Storage("Database error".to_owned())
+        })?;
+
+        let pipeline_version = ForcedExitPipelineVersion {
+            request_id: saved_fe_request.id,
+            stage: ForcedExitPipelineStage::Created,
+            version: FORCED_EXIT_PIPELINE_VERSION,
+            config_hash: self.pipeline_config_hash.clone(),
+            recorded_at: created_at,
+        };
+        // The request is stored already, so it is returned even without the version
+        if let Err(err) = fe_schema.store_pipeline_version(&pipeline_version).await {
+            vlog::warn!(
+                "Failed to record the pipeline version of the ForcedExit request {}: {:?}",
+                saved_fe_request.id,
+                err
+            );
+        }
+
+        if let Some(api_key) = api_key {
+            vlog::info!(
+                "ForcedExit request {} was created with the API key `{}`",
+                saved_fe_request.id,
+                api_key.label
+            );
+            metrics::increment_counter!("forced_exit_requests.partner_requests", "api_key" => api_key.label);
+        }
+
+        check_address_space_overflow(saved_fe_request.public_id, self.digits_in_id);
+        Ok(saved_fe_request)
+    }
+
+    /// Loads the active API key by its value.
+    async fn resolve_api_key(
+        storage: &mut StorageProcessor<'_>,
+        key: &str,
+    ) -> Result<ForcedExitRequestsApiKey, ForcedExitRequestsError> {
+        let api_key = storage
+            .forced_exit_requests_schema()
+            .get_api_key_by_hash(api_key_hash(key))
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+
+        match api_key {
+            Some(api_key) if !api_key.is_revoked() => Ok(api_key),
+            _ => Err(ForcedExitRequestsError::InvalidApiKey),
+        }
+    }
+
+    /// Loads the request by the public id the client refers to it with.
+    pub async fn get_request(
+        &self,
+        public_id: ForcedExitRequestId,
+    ) -> Result<ForcedExitRequest, ForcedExitRequestsError> {
+        self.ensure_enabled()?;
+
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+
+        storage
+            .forced_exit_requests_schema()
+            .get_request_by_public_id(public_id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+            .ok_or(ForcedExitRequestsError::RequestNotFound)
+    }
+
+    /// Returns the request along with its place in the queue.
+    pub async fn get_request_details(
+        &self,
+        public_id: ForcedExitRequestId,
+    ) -> Result<ForcedExitRequestDetails, ForcedExitRequestsError> {
+        let request = self.get_request(public_id).await?;
+        self.request_details(request).await
+    }
+
+    /// Returns the transactions sent for the request along with their statuses.
+    pub async fn get_request_progress(
+        &self,
+        public_id: ForcedExitRequestId,
+    ) -> Result<ForcedExitRequestProgress, ForcedExitRequestsError> {
+        let request = self.get_request(public_id).await?;
+        let tx_hashes = request.fulfilled_by.clone().unwrap_or_default();
+
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let skipped_tokens = storage
+            .forced_exit_requests_schema()
+            .load_skipped_tokens(request.id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let receipts = load_receipts(&mut storage, &tx_hashes)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+
+        // The transactions are sent for the tokens which are not skipped, in their order
+        let withdrawn_tokens = request.tokens.iter().filter(|token| {
+            !skipped_tokens
+                .iter()
+                .any(|skipped| skipped.token == **token)
+        });
+        let transactions = withdrawn_tokens
+            .zip(&tx_hashes)
+            .zip(match_receipts(&tx_hashes, &receipts))
+            .map(|((token, tx_hash), receipt)| ForcedExitTokenProgress {
+                token: *token,
+                tx_hash: *tx_hash,
+                status: tx_status(receipt),
+                block_number: receipt.map(|receipt| receipt.block_number),
+            })
+            .collect();
+
+        Ok(ForcedExitRequestProgress {
+            id: request.public_id,
+            target: request.target,
+            tokens: request.tokens,
+            status: request.status,
+            fulfilled_at: request.fulfilled_at,
+            transactions,
+        })
+    }
+
+    /// Returns the ledger of the request built out of its payments, the transactions
+    /// sent for it and its refunds.
+    pub async fn get_request_ledger(
+        &self,
+        public_id: ForcedExitRequestId,
+    ) -> Result<ForcedExitRequestLedger, ForcedExitRequestsError> {
+        let request = self.get_request(public_id).await?;
+
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let mut fe_schema = storage.forced_exit_requests_schema();
+        let payments = fe_schema
+            .load_request_payments(request.id, request.public_id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let fulfillments = fe_schema
+            .load_fulfillments(request.id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let refunds = fe_schema
+            .load_request_refunds(request.id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+
+        Ok(ForcedExitRequestLedger::new(
+            &request,
+            &payments,
+            &fulfillments,
+            &refunds,
+        ))
+    }
+
+    /// Looks up the requests paid for by the given L1 transaction. The payments which
+    /// have not paid for any request are reported along with the reasons, if recorded.
+    pub async fn lookup_payment(
+        &self,
+        eth_tx_hash: H256,
+    ) -> Result<ForcedExitPaymentLookup, ForcedExitRequestsError> {
+        self.ensure_enabled()?;
+
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let mut fe_schema = storage.forced_exit_requests_schema();
+
+        let requests = fe_schema
+            .load_requests_by_payment(eth_tx_hash)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        if !requests.is_empty() {
+            drop(storage);
+            let mut details = Vec::with_capacity(requests.len());
+            for request in requests {
+                details.push(self.request_details(request).await?);
+            }
+            return Ok(ForcedExitPaymentLookup::Matched { requests: details });
+        }
+
+        // The payments rescanned after the reorgs are recorded more than once
+        let mut reasons = Vec::new();
+        for payment in fe_schema
+            .load_unmatched_payments_by_tx_hash(eth_tx_hash)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+        {
+            if !reasons.contains(&payment.reason) {
+                reasons.push(payment.reason);
+            }
+        }
+        let recorded = fe_schema
+            .is_payment_recorded(eth_tx_hash)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+
+        if reasons.is_empty() && !recorded {
+            return Err(ForcedExitRequestsError::PaymentNotFound);
+        }
+        Ok(ForcedExitPaymentLookup::Unmatched { reasons })
+    }
+
+    async fn request_details(
+        &self,
+        request: ForcedExitRequest,
+    ) -> Result<ForcedExitRequestDetails, ForcedExitRequestsError> {
+        let queue = self.queue_info(&request).await?;
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let mut fe_schema = storage.forced_exit_requests_schema();
+        let active_target = fe_schema
+            .get_active_target(request.id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let skipped_tokens = fe_schema
+            .load_skipped_tokens(request.id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let cancellations = fe_schema
+            .load_cancellations(request.id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let expected_payments = fe_schema
+            .load_expected_payments(request.id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        // The details of the submission are only shown to the operators
+        let processing_failure = fe_schema
+            .get_processing_failure(request.id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+            .map(|failure| ForcedExitProcessingFailure {
+                submission_error: None,
+                ..failure
+            });
+        // Only the requests still to be fulfilled wait for the maintenance
+        let pending = request.fulfilled_at.is_none()
+            && request
+                .cancellation
+                .map_or(true, |cancellation| cancellation.allows_reprocessing());
+        let maintenance = if pending { self.maintenance() } else { None };
+
+        Ok(ForcedExitRequestDetails {
+            request,
+            queue,
+            active_target,
+            skipped_tokens,
+            cancellations,
+            expected_payments,
+            maintenance,
+            processing_failure,
+        })
+    }
+
+    async fn queue_info(
+        &self,
+        request: &ForcedExitRequest,
+    ) -> Result<Option<ForcedExitRequestQueueInfo>, ForcedExitRequestsError> {
+        // Only the paid requests are queued
+        if request.matched_at.is_none() || request.fulfilled_at.is_some() {
+            return Ok(None);
+        }
+        if let Some((cached_at, queue)) = self.queue_cache.get(&request.id) {
+            if cached_at.elapsed() < QUEUE_INFO_CACHE_TTL {
+                return Ok(queue);
+            }
+        }
+
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let mut fe_schema = storage.forced_exit_requests_schema();
+
+        let queue = match fe_schema
+            .get_queue_position(request.id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+        {
+            Some(position) => {
+                let fulfilled_last_hour = fe_schema
+                    .count_fulfilled_since(Utc::now() - Duration::hours(1))
+                    .await
+                    .map_err(ForcedExitRequestsError::storage)?;
+                Some(ForcedExitRequestQueueInfo {
+                    position,
+                    eta_secs: estimate_eta_secs(position, fulfilled_last_hour),
+                })
+            }
+            // The request is escalated to L1
+            None => None,
+        };
+
+        self.queue_cache
+            .insert(request.id, (Instant::now(), queue.clone()));
+        Ok(queue)
+    }
+
+    /// Gives the user the full interval to pay for the request once again.
+    pub async fn extend(
+        &self,
+        public_id: ForcedExitRequestId,
+    ) -> Result<ForcedExitRequest, ForcedExitRequestsError> {
+        let request = self.get_request(public_id).await?;
+        let valid_until = Utc::now().add(self.validity());
+        self.set_valid_until(request.id, valid_until).await
+    }
+
+    /// Registers the L1 transaction the partner is going to pay for the request with,
+    /// so the payment is matched by its hash instead of the amount. Only the partner
+    /// the request was created by can register its payments.
+    pub async fn register_expected_payment(
+        &self,
+        public_id: ForcedExitRequestId,
+        eth_tx_hash: H256,
+        api_key: Option<&str>,
+    ) -> Result<ForcedExitRequest, ForcedExitRequestsError> {
+        self.ensure_enabled()?;
+
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let api_key = match api_key {
+            Some(key) => Self::resolve_api_key(&mut storage, key).await?,
+            None => return Err(ForcedExitRequestsError::InvalidApiKey),
+        };
+        let mut fe_schema = storage.forced_exit_requests_schema();
+
+        let request = fe_schema
+            .get_request_by_public_id(public_id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+            .ok_or(ForcedExitRequestsError::RequestNotFound)?;
+        let request_id = request.id;
+        let request_api_key_id = fe_schema
+            .get_request_api_key_id(request_id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        if request_api_key_id != Some(api_key.id) {
+            return Err(ForcedExitRequestsError::ApiKeyMismatch);
+        }
+        // The request which has been paid for expects no more payments
+        if request.matched_at.is_some()
+            || request.fulfilled_at.is_some()
+            || request.valid_until < Utc::now()
+        {
+            return Err(ForcedExitRequestsError::RequestNotPending);
+        }
+
+        let registered = fe_schema
+            .store_expected_payment(request_id, eth_tx_hash, Utc::now())
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        if !registered {
+            return Err(ForcedExitRequestsError::PaymentExpectedForAnotherRequest);
+        }
+        vlog::info!(
+            "Payment {:?} for the ForcedExit request {} was registered with the API key `{}`",
+            eth_tx_hash,
+            request_id,
+            api_key.label
+        );
+        Ok(request)
+    }
+
+    /// Returns the request the operators refer to by its id along with the notes left on it.
+    pub async fn get_admin_request_details(
+        &self,
+        request_id: ForcedExitRequestId,
+    ) -> Result<ForcedExitRequestAdminDetails, ForcedExitRequestsError> {
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let mut fe_schema = storage.forced_exit_requests_schema();
+
+        let request = fe_schema
+            .get_request_by_id(request_id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+            .ok_or(ForcedExitRequestsError::RequestNotFound)?;
+        let notes = fe_schema
+            .load_request_notes(request_id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let submission_error = fe_schema
+            .get_processing_failure(request_id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+            .and_then(|failure| failure.submission_error)
+            .map(|submission_error| submission_error.sanitized());
+        drop(storage);
+
+        let details = self.request_details(request).await?;
+        Ok(ForcedExitRequestAdminDetails {
+            details,
+            notes,
+            submission_error,
+        })
+    }
+
+    /// Appends the note of the operator to the request, the note is authored by the label
+    /// of the API key. The repeated tags are only kept once.
+    pub async fn add_note(
+        &self,
+        request_id: ForcedExitRequestId,
+        text: String,
+        tags: Vec<String>,
+        api_key: Option<&str>,
+    ) -> Result<ForcedExitRequestNote, ForcedExitRequestsError> {
+        let text = text.trim().to_string();
+        let mut unique_tags: Vec<String> = Vec::with_capacity(tags.len());
+        for tag in tags {
+            if tag.is_empty()
+                || tag.chars().count() > MAX_NOTE_TAG_LENGTH
+                || tag.contains(char::is_whitespace)
+            {
+                return Err(ForcedExitRequestsError::InvalidNote);
+            }
+            if !unique_tags.contains(&tag) {
+                unique_tags.push(tag);
+            }
+        }
+        if text.is_empty()
+            || text.chars().count() > MAX_NOTE_LENGTH
+            || unique_tags.len() > MAX_NOTE_TAGS
+        {
+            return Err(ForcedExitRequestsError::InvalidNote);
+        }
+
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let api_key = match api_key {
+            Some(key) => Self::resolve_api_key(&mut storage, key).await?,
+            None => return Err(ForcedExitRequestsError::InvalidApiKey),
+        };
+        let mut fe_schema = storage.forced_exit_requests_schema();
+
+        fe_schema
+            .get_request_by_id(request_id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+            .ok_or(ForcedExitRequestsError::RequestNotFound)?;
+        let note = fe_schema
+            .store_note(SaveForcedExitRequestNoteQuery {
+                request_id,
+                author: api_key.label,
+                text,
+                tags: unique_tags,
+                created_at: Utc::now(),
+            })
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        vlog::info!(
+            "Note {} was added to the ForcedExit request {} by `{}`",
+            note.id,
+            request_id,
+            note.author
+        );
+        Ok(note)
+    }
+
+    /// Makes the request expire right away, so the payments sent for it afterwards
+    /// are not processed. The cancellation is recorded with the given kind, the request
+    /// is only processed again once extended.
+    pub async fn cancel(
+        &self,
+        request_id: ForcedExitRequestId,
+        kind: ForcedExitCancellationKind,
+    ) -> Result<ForcedExitRequest, ForcedExitRequestsError> {
+        self.ensure_enabled()?;
+
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let mut fe_schema = storage.forced_exit_requests_schema();
+
+        match fe_schema
+            .cancel_request(
+                request_id,
+                kind,
+                Utc::now(),
+                self.legacy_fulfilled_by_enabled,
+            )
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+        {
+            Some(request) => {
+                vlog::info!("ForcedExit request {} was cancelled: {}", request_id, kind);
+                Ok(request)
+            }
+            None => Err(Self::not_pending(&mut fe_schema, request_id).await),
+        }
+    }
+
+    /// Sends the transactions of the paid request once again, e.g. once the sender has
+    /// given up on it. The transactions sent before are forgotten and the request is checked
+    /// the way its payment was, as of the time of the payment if the retry is forced.
+    /// The retry is processed by the sender, see `await_retry`.
+    pub async fn retry(
+        &self,
+        request_id: ForcedExitRequestId,
+        force: bool,
+    ) -> Result<ForcedExitRetry, ForcedExitRequestsError> {
+        self.ensure_enabled()?;
+
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let mut fe_schema = storage.forced_exit_requests_schema();
+
+        let request = fe_schema
+            .get_request_by_id(request_id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+            .ok_or(ForcedExitRequestsError::RequestNotFound)?;
+        if request.fulfilled_at.is_some() {
+            return Err(ForcedExitRequestsError::RequestAlreadyFulfilled);
+        }
+        let matched_at = match (&request.paid_amount, request.matched_at) {
+            (Some(_), Some(matched_at)) => matched_at,
+            _ => return Err(ForcedExitRequestsError::RequestNotPaid),
+        };
+        if matches!(request.cancellation, Some(kind) if !kind.allows_reprocessing()) {
+            return Err(ForcedExitRequestsError::RequestCancelled);
+        }
+        let checked_at = if force { matched_at } else { Utc::now() };
+        if request.valid_until <= checked_at {
+            return Err(ForcedExitRequestsError::RequestExpired);
+        }
+
+        fe_schema
+            .set_fulfilled_by(request_id, None, self.legacy_fulfilled_by_enabled)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let retry = fe_schema
+            .store_retry(request_id, force, Utc::now())
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+
+        vlog::info!(
+            "ForcedExit request {} is retried (force: {})",
+            request_id,
+            force
+        );
+        Ok(retry)
+    }
+
+    /// Waits for the sender to process the retry, returns it as is once the timeout is over.
+    pub async fn await_retry(
+        &self,
+        retry_id: ForcedExitRetryId,
+        timeout: std::time::Duration,
+    ) -> Result<ForcedExitRetry, ForcedExitRequestsError> {
+        let started_at = Instant::now();
+        loop {
+            let retry = self.get_retry(retry_id).await?;
+            if retry.is_processed() || started_at.elapsed() >= timeout {
+                return Ok(retry);
+            }
+            tokio::time::sleep(RETRY_POLL_INTERVAL).await;
+        }
+    }
+
+    pub async fn get_retry(
+        &self,
+        retry_id: ForcedExitRetryId,
+    ) -> Result<ForcedExitRetry, ForcedExitRequestsError> {
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        storage
+            .forced_exit_requests_schema()
+            .get_retry(retry_id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+            .ok_or(ForcedExitRequestsError::RetryNotFound)
+    }
+
+    /// Approves the refund above the approval threshold, so the sender sends it
+    /// regardless of the hourly limit. The approval is recorded with the label of the key.
+    pub async fn approve_refund(
+        &self,
+        refund_id: ForcedExitRefundId,
+        api_key: Option<&str>,
+    ) -> Result<ForcedExitRefund, ForcedExitRequestsError> {
+        self.check_feature(ForcedExitFeature::Refunds)?;
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let api_key = match api_key {
+            Some(key) => Self::resolve_api_key(&mut storage, key).await?,
+            None => return Err(ForcedExitRequestsError::InvalidApiKey),
+        };
+        let mut fe_schema = storage.forced_exit_requests_schema();
+
+        fe_schema
+            .get_refund(refund_id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+            .ok_or(ForcedExitRequestsError::RefundNotFound)?;
+        let refund = fe_schema
+            .approve_refund(refund_id, &api_key.label, Utc::now())
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+            .ok_or(ForcedExitRequestsError::RefundNotAwaitingApproval)?;
+
+        vlog::info!(
+            "Refund {} of {} wei for ForcedExit request {} was approved by `{}`",
+            refund.id,
+            refund.amount,
+            refund.request_id,
+            api_key.label
+        );
+        Ok(refund)
+    }
+
+    /// Makes the callback, which has run out of the attempts, due right away.
+    pub async fn redeliver(
+        &self,
+        delivery_id: ForcedExitRequestDeliveryId,
+    ) -> Result<ForcedExitRequestDelivery, ForcedExitRequestsError> {
+        self.check_feature(ForcedExitFeature::Callbacks)?;
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let mut fe_schema = storage.forced_exit_requests_schema();
+
+        fe_schema
+            .get_delivery(delivery_id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+            .ok_or(ForcedExitRequestsError::DeliveryNotFound)?;
+        let delivery = fe_schema
+            .redeliver(delivery_id, Utc::now())
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+            .ok_or(ForcedExitRequestsError::DeliveryNotFailed)?;
+
+        vlog::info!(
+            "Callback {} for ForcedExit request {} is redelivered",
+            delivery.id,
+            delivery.request_id
+        );
+        Ok(delivery)
+    }
+
+    /// Explains how the payment is matched with the request, running the same matcher
+    /// as the sender does. Every step is reported with its inputs, nothing is written.
+    pub async fn explain_payment(
+        &self,
+        payment: &FundsReceivedEvent,
+        submission_time: DateTime<Utc>,
+    ) -> Result<ForcedExitPaymentExplanation, ForcedExitRequestsError> {
+        let mut steps = Vec::new();
+        let outcome = PaymentMatcher::new(&self.matching_config, true, true)
+            .match_payment(&self.connection_pool, payment, submission_time, &mut steps)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+
+        let (request_id, match_scheme) = match outcome {
+            PaymentMatchOutcome::Matched(request, match_scheme) => {
+                (Some(request.id), Some(match_scheme))
+            }
+            _ => (None, None),
+        };
+        let config = self.config_at(submission_time).await?;
+        Ok(ForcedExitPaymentExplanation {
+            amount: payment.amount.clone(),
+            submission_time,
+            eth_tx_hash: payment.eth_tx_hash,
+            request_id,
+            match_scheme,
+            steps,
+            config,
+        })
+    }
+
+    /// The money config in effect at the given time, as recorded on the starts of the server.
+    pub async fn config_at(
+        &self,
+        time: DateTime<Utc>,
+    ) -> Result<Option<ForcedExitConfigSnapshot>, ForcedExitRequestsError> {
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        storage
+            .forced_exit_requests_schema()
+            .config_at(time)
+            .await
+            .map_err(ForcedExitRequestsError::storage)
+    }
+
+    /// Evaluates what fulfilling the request would do right now, without sending anything.
+    pub async fn preflight(
+        &self,
+        request_id: ForcedExitRequestId,
+    ) -> Result<ForcedExitPreflight, ForcedExitRequestsError> {
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let (_, preflight) = self.evaluate(&mut storage, request_id).await?;
+        Ok(preflight)
+    }
+
+    async fn evaluate(
+        &self,
+        storage: &mut StorageProcessor<'_>,
+        request_id: ForcedExitRequestId,
+    ) -> Result<(ForcedExitRequest, ForcedExitPreflight), ForcedExitRequestsError> {
+        let mut fe_schema = storage.forced_exit_requests_schema();
+
+        let request = fe_schema
+            .get_request_by_id(request_id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+            .ok_or(ForcedExitRequestsError::RequestNotFound)?;
+        let escalated = fe_schema
+            .get_escalation(request_id)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+            .is_some();
+        if let Some(blocker) = blocker_before_target(&request, escalated, Utc::now()) {
+            let preflight = blocked_preflight(&request, blocker, None);
+            return Ok((request, preflight));
+        }
+
+        let target = self
+            .forced_exit_checker
+            .check_forced_exit_target(storage, request.target)
+            .await?;
+        if let Some(blocker) = target_blocker(&target) {
+            let preflight = blocked_preflight(&request, blocker, Some(target));
+            return Ok((request, preflight));
+        }
+        let sender_nonce = storage
+            .chain()
+            .account_schema()
+            .committed_nonce_by_address(self.sender_account_address)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+            .ok_or_else(|| {
+                ForcedExitRequestsError::storage("ForcedExit sender account does not exist")
+            })?;
+
+        let preflight = plan_forced_exits(&request, target, sender_nonce);
+        Ok((request, preflight))
+    }
+
+    /// Evaluates the request of the backlog, returns its preflight along with
+    /// the number of its tokens the target has zero balances of.
+    async fn simulate_request(
+        &self,
+        request_id: ForcedExitRequestId,
+    ) -> Result<(ForcedExitPreflight, usize), ForcedExitRequestsError> {
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let (request, preflight) = self.evaluate(&mut storage, request_id).await?;
+        if preflight.blocker.is_some() {
+            return Ok((preflight, 0));
+        }
+
+        // Only the balances of the tokens to withdraw are loaded, the target may hold many more
+        let tokens: Vec<TokenId> = preflight.transactions.iter().map(|tx| tx.token).collect();
+        let balances = storage
+            .chain()
+            .account_schema()
+            .committed_balances_by_address(request.target, &tokens)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let empty_tokens = tokens
+            .iter()
+            .filter(|token| {
+                balances
+                    .get(token)
+                    .map_or(true, |balance| balance.is_zero())
+            })
+            .count();
+        Ok((preflight, empty_tokens))
+    }
+
+    /// Estimates the outcome of fulfilling the paid requests, which have not been sent yet,
+    /// nothing is sent or changed. The report is stored to be compared with the actual outcome.
+    pub async fn simulate_backlog(
+        &self,
+    ) -> Result<ForcedExitBacklogReport, ForcedExitRequestsError> {
+        let created_at = Utc::now();
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let mut fe_schema = storage.forced_exit_requests_schema();
+        let request_ids = fe_schema
+            .get_backlog_request_ids()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let fulfilled_last_hour = fe_schema
+            .count_fulfilled_since(created_at - Duration::hours(1))
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        // The evaluations take the connections of their own
+        drop(storage);
+
+        let simulations = stream::iter(request_ids)
+            .map(|request_id| async move { (request_id, self.simulate_request(request_id).await) })
+            .buffer_unordered(BACKLOG_SIMULATION_CONCURRENCY)
+            .collect::<Vec<_>>()
+            .await;
+        let report = backlog_report(simulations, created_at, fulfilled_last_hour);
+
+        self.connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+            .forced_exit_requests_schema()
+            .store_backlog_report(&report)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        vlog::info!(
+            "ForcedExit backlog of {} requests would be fulfilled with {} transactions",
+            report.requests,
+            report.transactions
+        );
+        Ok(report)
+    }
+
+    /// Estimates the impact of the config candidate on the requests awaiting the payment,
+    /// nothing is applied. The exact amount of every request is matched by the same matcher
+    /// with both configs, and the request is checked against the creation rules of both,
+    /// only the differences are reported.
+    pub async fn simulate_config(
+        &self,
+        candidate: &ForcedExitConfigCandidate,
+    ) -> Result<ForcedExitConfigImpactReport, ForcedExitRequestsError> {
+        let current = &self.matching_config;
+        let candidate_config = current
+            .with_candidate(candidate)
+            .map_err(ForcedExitRequestsError::InvalidConfigCandidate)?;
+        let created_at = Utc::now();
+
+        let mut requests = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+            .forced_exit_requests_schema()
+            .load_awaiting_payment(created_at, CONFIG_SIMULATION_REQUESTS_LIMIT + 1)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let truncated = requests.len() > CONFIG_SIMULATION_REQUESTS_LIMIT as usize;
+        requests.truncate(CONFIG_SIMULATION_REQUESTS_LIMIT as usize);
+
+        let mut report = ForcedExitConfigImpactReport {
+            created_at,
+            changes: config_changes(
+                &serde_json::to_value(current.as_candidate())
+                    .expect("Failed to serialize the config"),
+                &serde_json::to_value(candidate).expect("Failed to serialize the config candidate"),
+            ),
+            current_pipeline_config_hash: current.pipeline_config().hash(),
+            candidate_pipeline_config_hash: candidate_config.pipeline_config().hash(),
+            requests: requests.len() as u32,
+            truncated,
+            still_matched: 0,
+            unmatchable: Vec::new(),
+            rejected: Vec::new(),
+            quotes: quote_changes(current, &candidate_config),
+            evaluation_failures: 0,
+        };
+
+        // The requests are loaded in the order they are created in, so the ones of the same
+        // target counted so far are the ones awaiting the payment once the request is created
+        let mut active_for_target: HashMap<Address, u32> = HashMap::new();
+        for request in &requests {
+            let active = active_for_target.entry(request.target).or_insert(0);
+            let rejections = (
+                creation_rejection(current, request, *active),
+                creation_rejection(&candidate_config, request, *active),
+            );
+            *active += 1;
+            // The requests refused by the current rules as well are the ones created
+            // with the limits of the API keys
+            if let (None, Some(reason)) = rejections {
+                report.rejected.push(ForcedExitRejectedRequest {
+                    request_id: request.id,
+                    public_id: request.public_id,
+                    reason: reason.to_string(),
+                });
+            }
+        }
+
+        let current_matcher = PaymentMatcher::new(current, false, true);
+        let candidate_matcher = PaymentMatcher::new(&candidate_config, false, true);
+        let outcomes = stream::iter(&requests)
+            .map(|request| async move {
+                let (matched, _) = self
+                    .match_exact_amount(current_matcher, request, created_at)
+                    .await?;
+                let (still_matched, steps) = self
+                    .match_exact_amount(candidate_matcher, request, created_at)
+                    .await?;
+                Ok::<_, anyhow::Error>((request, matched, still_matched, steps))
+            })
+            .buffer_unordered(CONFIG_SIMULATION_CONCURRENCY)
+            .collect::<Vec<_>>()
+            .await;
+        for outcome in outcomes {
+            match outcome {
+                Ok((_, true, true, _)) => report.still_matched += 1,
+                Ok((request, true, false, steps)) => {
+                    report.unmatchable.push(ForcedExitUnmatchableRequest {
+                        request_id: request.id,
+                        public_id: request.public_id,
+                        pay_exactly: request.pay_exactly.clone(),
+                        steps,
+                    })
+                }
+                // The requests not matched now are left to the consistency checks
+                Ok(_) => {}
+                Err(err) => {
+                    vlog::warn!(
+                        "Failed to match the exact amount of ForcedExit request: {}",
+                        err
+                    );
+                    report.evaluation_failures += 1;
+                }
+            }
+        }
+        report.unmatchable.sort_by_key(|request| request.request_id);
+
+        vlog::info!(
+            "Config candidate would leave {} of {} ForcedExit requests unmatchable and refuse {}",
+            report.unmatchable.len(),
+            report.requests,
+            report.rejected.len()
+        );
+        Ok(report)
+    }
+
+    /// Matches the exact amount of the request as if it was paid at the given time, returns
+    /// whether it is matched with the request along with the steps of the matching.
+    async fn match_exact_amount(
+        &self,
+        matcher: PaymentMatcher<'_>,
+        request: &ForcedExitRequest,
+        submission_time: DateTime<Utc>,
+    ) -> anyhow::Result<(bool, Vec<PaymentMatchStep>)> {
+        let payment = FundsReceivedEvent {
+            amount: BigUint::from_str(&request.pay_exactly)?,
+            request_id: None,
+            block_number: 0,
+            eth_tx_hash: None,
+            payer: None,
+            recipient: None,
+        };
+        let mut steps = Vec::new();
+        let outcome = matcher
+            .match_payment(&self.connection_pool, &payment, submission_time, &mut steps)
+            .await?;
+        let matched = matches!(
+            outcome,
+            PaymentMatchOutcome::Matched(matched, _) if matched.id == request.id
+        );
+        Ok((matched, steps))
+    }
+
+    /// Checks the requests changed since the given time against the invariants and stores
+    /// the report. The violations are only reported, the records are not changed.
+    pub async fn check_consistency(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<ForcedExitConsistencyReport, ForcedExitRequestsError> {
+        let created_at = Utc::now();
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let mut fe_schema = storage.forced_exit_requests_schema();
+        let evidence = fe_schema
+            .load_consistency_evidence(since, CONSISTENCY_CHECKED_REQUESTS_LIMIT)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let report = consistency_report(created_at, since, &evidence);
+        fe_schema
+            .store_consistency_report(&report)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+
+        for invariant in ForcedExitInvariant::ALL.iter().copied() {
+            metrics::gauge!(
+                "forced_exit_requests.invariant_violations",
+                report.count(invariant) as f64,
+                "invariant" => invariant.as_str()
+            );
+        }
+        vlog::info!(
+            "{} violations of the invariants found among {} ForcedExit requests changed since {}",
+            report.violations.len(),
+            report.checked_requests,
+            since
+        );
+        Ok(report)
+    }
+
+    async fn set_valid_until(
+        &self,
+        request_id: ForcedExitRequestId,
+        valid_until: DateTime<Utc>,
+    ) -> Result<ForcedExitRequest, ForcedExitRequestsError> {
+        self.ensure_enabled()?;
+
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let mut fe_schema = storage.forced_exit_requests_schema();
+
+        if let Some(request) = fe_schema
+            .set_valid_until(request_id, valid_until)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?
+        {
+            return Ok(request);
+        }
+        Err(Self::not_pending(&mut fe_schema, request_id).await)
+    }
+
+    // Nothing was updated, find out whether the request exists at all
+    async fn not_pending(
+        fe_schema: &mut ForcedExitRequestsSchema<'_, '_>,
+        request_id: ForcedExitRequestId,
+    ) -> ForcedExitRequestsError {
+        match fe_schema.get_request_by_id(request_id).await {
+            Ok(Some(_)) => ForcedExitRequestsError::RequestNotPending,
+            Ok(None) => ForcedExitRequestsError::RequestNotFound,
+            Err(err) => ForcedExitRequestsError::storage(err),
+        }
+    }
+
+    /// Loads the page of the requests created for the target account.
+    pub async fn list(
+        &self,
+        query: PaginationQuery<ForcedExitRequestsQuery>,
+    ) -> Result<Paginated<ForcedExitRequest, ForcedExitRequestId>, ForcedExitRequestsError> {
+        self.ensure_enabled()?;
+
+        if query.limit > MAX_LIMIT {
+            return Err(ForcedExitRequestsError::PaginationLimitTooBig);
+        }
+
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let mut fe_schema = storage.forced_exit_requests_schema();
+        let target = query.from.target;
+
+        let request_id = match query.from.request_id.inner {
+            Either::Left(request_id) => request_id,
+            // Right means the latest request of the account
+            Either::Right(_) => {
+                match fe_schema
+                    .get_last_request_id(target)
+                    .await
+                    .map_err(ForcedExitRequestsError::storage)?
+                {
+                    Some(request_id) => request_id,
+                    None => {
+                        return Ok(Paginated::new(
+                            Vec::new(),
+                            Default::default(),
+                            query.limit,
+                            query.direction,
+                            0,
+                        ))
+                    }
+                }
+            }
+        };
+
+        let query = PaginationQuery {
+            from: request_id,
+            limit: query.limit,
+            direction: query.direction,
+        };
+        let requests = fe_schema
+            .load_requests_page(target, &query)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+        let count = fe_schema
+            .get_requests_count(target)
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+
+        Ok(Paginated::new(
+            requests,
+            query.from,
+            query.limit,
+            query.direction,
+            count,
+        ))
+    }
+
+    // Checks if the account is eligible for forced_exit in terms of
+    // existing enough time
+    pub async fn check_account_eligibility(
+        &self,
+        account: Address,
+    ) -> Result<ForcedExitEligibilityResponse, ForcedExitRequestsError> {
+        self.ensure_enabled()?;
+
+        let mut storage = self
+            .connection_pool
+            .access_storage()
+            .await
+            .map_err(ForcedExitRequestsError::storage)?;
+
+        let eligible = self
+            .forced_exit_checker
+            .check_forced_exit(&mut storage, account)
+            .await?;
+
+        Ok(ForcedExitEligibilityResponse { eligible })
+    }
+}
+
+/// Aggregates the evaluations of the requests of the backlog. The time to fulfill
+/// the backlog is estimated for the requests which would be fulfilled on L2.
+fn backlog_report(
+    simulations: Vec<(
+        ForcedExitRequestId,
+        Result<(ForcedExitPreflight, usize), ForcedExitRequestsError>,
+    )>,
+    created_at: DateTime<Utc>,
+    fulfilled_last_hour: u32,
+) -> ForcedExitBacklogReport {
+    let mut report = ForcedExitBacklogReport::new(created_at, fulfilled_last_hour);
+    for (request_id, simulation) in simulations {
+        match simulation {
+            Ok((preflight, empty_tokens)) => {
+                add_to_backlog_report(&mut report, &preflight, empty_tokens)
+            }
+            Err(err) => {
+                vlog::warn!(
+                    "Failed to evaluate the ForcedExit request {} of the backlog: {}",
+                    request_id,
+                    err
+                );
+                report.add_failure();
+            }
+        }
+    }
+    report.eta_secs = match report.fulfilled {
+        0 => Some(0),
+        fulfilled => estimate_eta_secs(fulfilled - 1, fulfilled_last_hour),
+    };
+    report
+}
+
+/// The id of the request followed by its check digit takes the lowest digits of the price,
+/// see `ForcedExitRequestsService::request_price`.
+fn price_for_tokens(price_per_token: i64, digits_in_id: u8, tokens_count: usize) -> BigUint {
+    let price = BigUint::from(price_per_token as u64) * tokens_count;
+    align_price(price, amount_id_digits(digits_in_id))
+}
+
+/// Rounds the price up to the closest one that does not overlap with the ids.
+fn align_price(price: BigUint, digits_in_id: u8) -> BigUint {
+    let id_space = id_space_size(digits_in_id);
+    let remainder = &price % &id_space;
+    if remainder.is_zero() {
+        price
+    } else {
+        price + id_space - remainder
+    }
+}
+
+/// The EIP-681 URI of the payment of the `amount` (decimal, in wei) to the `address`,
+/// e.g. `ethereum:0x…@1?value=2000000000000000123` for the mainnet. The wallets render
+/// it as a QR code, the chain is omitted if not known.
+fn payment_uri(address: Address, amount: &str, chain_id: Option<u64>) -> String {
+    match chain_id {
+        Some(chain_id) => format!("ethereum:{:?}@{}?value={}", address, chain_id, amount),
+        None => format!("ethereum:{:?}?value={}", address, amount),
+    }
+}
+
+/// Lists the keys the candidate sets to other values than the current ones,
+/// both configurations are serialized as `ForcedExitConfigCandidate`.
+fn config_changes(
+    current: &serde_json::Value,
+    candidate: &serde_json::Value,
+) -> Vec<ForcedExitConfigChange> {
+    let (current, candidate) = match (current.as_object(), candidate.as_object()) {
+        (Some(current), Some(candidate)) => (current, candidate),
+        _ => return Vec::new(),
+    };
+    candidate
+        .iter()
+        .filter(|(_, value)| !value.is_null())
+        .filter_map(|(key, value)| {
+            let current = current.get(key).cloned().unwrap_or_default();
+            (current != *value).then(|| ForcedExitConfigChange {
+                key: key.clone(),
+                current,
+                candidate: value.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Checks the tokens and the metadata of the request against the limits,
+/// `max_tokens_per_request` is the one of the API key if it is supplied.
+fn check_limits(
+    limits: &CreationLimits,
+    tokens: &[TokenId],
+    metadata: Option<&str>,
+    max_tokens_per_request: u8,
+) -> Result<(), ForcedExitRequestsError> {
+    if tokens.is_empty() {
+        return Err(ForcedExitRequestsError::NoTokens);
+    }
+    if tokens.len() > max_tokens_per_request as usize {
+        return Err(ForcedExitRequestsError::TooManyTokens);
+    }
+    if let Some(token) = tokens
+        .iter()
+        .find(|token| !limits.is_token_allowed(**token))
+    {
+        return Err(ForcedExitRequestsError::TokenNotAllowed(*token));
+    }
+    let metadata_length = metadata.map_or(0, str::len);
+    if metadata_length > limits.max_metadata_length {
+        return Err(ForcedExitRequestsError::MetadataTooLong(
+            limits.max_metadata_length,
+        ));
+    }
+
+    Ok(())
+}
+
+/// Returns the error the request would be refused with if created with the config,
+/// `active_for_target` is the number of the requests of its target awaiting the payment.
+fn creation_rejection(
+    config: &ForcedExitRequestsConfig,
+    request: &ForcedExitRequest,
+    active_for_target: u32,
+) -> Option<ForcedExitRequestsError> {
+    let limits = config.creation_limits();
+    if let Err(err) = check_limits(
+        &limits,
+        &request.tokens,
+        request.metadata.as_deref(),
+        limits.max_tokens_per_request,
+    ) {
+        return Some(err);
+    }
+    let price = price_for_tokens(
+        config.price_per_token,
+        config.digits_in_id,
+        request.tokens.len(),
+    );
+    if request.price_in_wei != price {
+        return Some(ForcedExitRequestsError::IncorrectPrice);
+    }
+    (active_for_target >= limits.max_active_per_target)
+        .then(|| ForcedExitRequestsError::TooManyActiveRequests(limits.max_active_per_target))
+}
+
+/// The prices for every number of tokens either config allows.
+fn quote_changes(
+    current: &ForcedExitRequestsConfig,
+    candidate: &ForcedExitRequestsConfig,
+) -> Vec<ForcedExitQuoteChange> {
+    let quote = |config: &ForcedExitRequestsConfig, tokens_count: u8| {
+        (tokens_count <= config.max_tokens_per_request).then(|| {
+            price_for_tokens(
+                config.price_per_token,
+                config.digits_in_id,
+                tokens_count.into(),
+            )
+            .into()
+        })
+    };
+    let max_tokens = current
+        .max_tokens_per_request
+        .max(candidate.max_tokens_per_request);
+    (1..=max_tokens)
+        .map(|tokens_count| ForcedExitQuoteChange {
+            tokens_count,
+            current: quote(current, tokens_count),
+            candidate: quote(candidate, tokens_count),
+        })
+        .collect()
+}
+
+/// Estimates the time until the request is fulfilled assuming the requests ahead of it
+/// and the request itself are processed at the rate observed during the last hour.
+fn estimate_eta_secs(position: u32, fulfilled_last_hour: u32) -> Option<u64> {
+    if fulfilled_last_hour == 0 {
+        return None;
+    }
+
+    let requests_left = u64::from(position) + 1;
+    let fulfilled_last_hour = u64::from(fulfilled_last_hour);
+    Some((requests_left * 3600 + fulfilled_last_hour - 1) / fulfilled_last_hour)
+}
+
+/// Checks whether the active requests occupy more than the given share (in percents) of the id space.
+fn exceeds_utilization(active_requests: u32, id_space_size: u64, percents: u8) -> bool {
+    u128::from(active_requests) * 100 > u128::from(id_space_size) * u128::from(percents)
+}
+
+/// Only the hashes of the API keys are stored, so the leaked database does not
+/// allow to impersonate the partners.
+pub fn api_key_hash(key: &str) -> H256 {
+    H256::from(keccak256(key.as_bytes()))
+}
+
+// Checks if the id exceeds half of the address space
+// If it exceeds the half at all the alert should be triggerred
+// since it it a sign of a possible DoS attack
+pub fn check_address_space_overflow(id: i64, digits_in_id: u8) {
+    let address_space = 10_i64.saturating_pow(digits_in_id as u32);
+
+    let exceeding_rate = id.saturating_sub(address_space / 2);
+    // Need this for metrics
+    let exceeding_rate: u64 = exceeding_rate.max(0).try_into().unwrap();
+
+    metrics::histogram!(
+        "forced_exit_requests.address_space_overflow",
+        exceeding_rate as f64
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use zksync_api_types::v02::pagination::{ApiEither, PaginationDirection};
+    use zksync_config::ZkSyncConfig;
+    use zksync_crypto::params::MIN_NFT_TOKEN_ID;
+    use zksync_types::{
+        forced_exit_requests::{
+            check_digit, pay_exactly, ForcedExitBlockedRequests, ForcedExitBlocker,
+            ForcedExitRequestActiveTarget, ForcedExitTargetCheck, ForcedExitTokenFees,
+            PaymentMatchScheme,
+        },
+        Nonce, Token, TokenId, TokenKind,
+    };
+
+    use super::*;
+    use crate::api_server::forced_exit_checker::DummyForcedExitChecker;
+
+    const PRICE_PER_TOKEN: i64 = 1_000_000_000;
+    const DIGITS_IN_ID: u8 = 8;
+
+    fn test_service(enabled: bool) -> ForcedExitRequestsService {
+        test_service_with_price(enabled, PRICE_PER_TOKEN)
+    }
+
+    fn test_service_with_price(enabled: bool, price_per_token: i64) -> ForcedExitRequestsService {
+        let config = ZkSyncConfig::from_env();
+        ForcedExitRequestsService::new(
+            ConnectionPool::new(Some(1)),
+            &ForcedExitRequestsConfig {
+                enabled,
+                price_per_token,
+                digits_in_id: DIGITS_IN_ID,
+                max_tokens_per_request: 3,
+                // The tests share the target of the requests
+                max_active_requests_per_target: u32::MAX,
+                ..config.forced_exit_requests
+            },
+            config.contracts.forced_exit_addr,
+            Box::new(DummyForcedExitChecker),
+        )
+    }
+
+    fn register_request(tokens: Vec<TokenId>) -> ForcedExitRegisterRequest {
+        ForcedExitRegisterRequest {
+            target: Address::repeat_byte(0x43),
+            price_in_wei: BigUint::from(PRICE_PER_TOKEN as u64) * tokens.len(),
+            tokens,
+            metadata: None,
+            callback_url: None,
+        }
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn disabled_service() {
+        let service = test_service(false);
+
+        assert!(matches!(
+            service.get_status().await,
+            Ok(ForcedExitRequestStatus::Disabled)
+        ));
+        assert!(matches!(
+            service.quote(1),
+            Err(ForcedExitRequestsError::Disabled)
+        ));
+        assert!(matches!(
+            service
+                .create_request(register_request(vec![TokenId(0)]), None)
+                .await,
+            Err(ForcedExitRequestsError::Disabled)
+        ));
+        assert!(matches!(
+            service.extend(1).await,
+            Err(ForcedExitRequestsError::Disabled)
+        ));
+        assert!(matches!(
+            service
+                .cancel(1, ForcedExitCancellationKind::OperatorCancelled)
+                .await,
+            Err(ForcedExitRequestsError::Disabled)
+        ));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn payment_address_rotation() -> anyhow::Result<()> {
+        let config = ZkSyncConfig::from_env();
+        let contract = config.contracts.forced_exit_addr;
+        let schedule = vec![
+            PaymentAddressWindow {
+                address: contract,
+                first_block: 0,
+                last_block: Some(200),
+            },
+            PaymentAddressWindow {
+                address: Address::repeat_byte(0x12),
+                first_block: 150,
+                last_block: None,
+            },
+        ];
+        let service = ForcedExitRequestsService::new(
+            ConnectionPool::new(Some(1)),
+            &ForcedExitRequestsConfig {
+                enabled: true,
+                payment_addresses: schedule.clone(),
+                ..config.forced_exit_requests.clone()
+            },
+            contract,
+            Box::new(DummyForcedExitChecker),
+        );
+
+        // The newest address is advertised, while the schedule is exposed as a whole
+        let quote = service.quote(1)?;
+        assert_eq!(
+            quote.forced_exit_contract_address,
+            Address::repeat_byte(0x12)
+        );
+        let config_info = match service.get_status().await? {
+            ForcedExitRequestStatus::Enabled(config_info) => config_info,
+            ForcedExitRequestStatus::Disabled => panic!("The service is enabled"),
+        };
+        assert_eq!(
+            config_info.forced_exit_contract_address,
+            Address::repeat_byte(0x12)
+        );
+        assert_eq!(config_info.payment_addresses, schedule);
+
+        // The contract is the only address unless the address was rotated
+        let config_info = match test_service(true).get_status().await? {
+            ForcedExitRequestStatus::Enabled(config_info) => config_info,
+            ForcedExitRequestStatus::Disabled => panic!("The service is enabled"),
+        };
+        assert_eq!(config_info.forced_exit_contract_address, contract);
+        assert_eq!(
+            config_info.payment_addresses,
+            vec![PaymentAddressWindow {
+                address: contract,
+                first_block: 0,
+                last_block: None,
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn enabled_features_status() -> anyhow::Result<()> {
+        let config = ZkSyncConfig::from_env();
+        let service = |forced_exit_requests| {
+            ForcedExitRequestsService::new(
+                ConnectionPool::new(Some(1)),
+                &forced_exit_requests,
+                config.contracts.forced_exit_addr,
+                Box::new(DummyForcedExitChecker),
+            )
+        };
+        let enabled_features = |service: ForcedExitRequestsService| async move {
+            match service.get_status().await? {
+                ForcedExitRequestStatus::Enabled(config_info) => {
+                    Ok::<_, anyhow::Error>(config_info.enabled_features)
+                }
+                ForcedExitRequestStatus::Disabled => panic!("The service is enabled"),
+            }
+        };
+
+        let features = enabled_features(service(ForcedExitRequestsConfig {
+            enabled: true,
+            l1_escalation_enabled: false,
+            webhook_url: None,
+            overpayment_tolerance: 0,
+            overpayment_tolerance_percent: 0,
+            refunds_enabled: false,
+            callbacks_enabled: false,
+            ..config.forced_exit_requests.clone()
+        }))
+        .await?;
+        assert!(features.is_empty());
+
+        for feature in ForcedExitFeature::ALL.iter().copied() {
+            let features = enabled_features(service(ForcedExitRequestsConfig {
+                enabled: true,
+                l1_escalation_enabled: feature == ForcedExitFeature::L1Escalation,
+                webhook_url: (feature == ForcedExitFeature::Webhooks)
+                    .then(|| "http://127.0.0.1:3080/forced_exit_requests".to_owned()),
+                overpayment_tolerance: 0,
+                overpayment_tolerance_percent: if feature == ForcedExitFeature::Overpayments {
+                    1
+                } else {
+                    0
+                },
+                refunds_enabled: feature == ForcedExitFeature::Refunds,
+                callbacks_enabled: feature == ForcedExitFeature::Callbacks,
+                ..config.forced_exit_requests.clone()
+            }))
+            .await?;
+            assert_eq!(features, vec![feature]);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn maintenance_status() -> anyhow::Result<()> {
+        let config = ZkSyncConfig::from_env();
+        let end = Utc::now() + Duration::hours(1);
+        let service = ForcedExitRequestsService::new(
+            ConnectionPool::new(Some(1)),
+            &ForcedExitRequestsConfig {
+                enabled: true,
+                maintenance_windows: vec![MaintenanceWindow {
+                    start: Utc::now() - Duration::hours(1),
+                    end,
+                    reason: "Database migration".to_owned(),
+                    recurrence: None,
+                }],
+                ..config.forced_exit_requests.clone()
+            },
+            config.contracts.forced_exit_addr,
+            Box::new(DummyForcedExitChecker),
+        );
+        let expected = Some(ForcedExitMaintenance {
+            until: end,
+            reason: "Database migration".to_owned(),
+        });
+
+        let config_info = match service.get_status().await? {
+            ForcedExitRequestStatus::Enabled(config_info) => config_info,
+            ForcedExitRequestStatus::Disabled => panic!("The service is enabled"),
+        };
+        assert_eq!(config_info.maintenance, expected);
+
+        // The requests are accepted meanwhile, waiting for the maintenance to end
+        let request = service
+            .create_request(register_request(vec![TokenId(0)]), None)
+            .await?;
+        let details = service.get_request_details(request.public_id).await?;
+        assert_eq!(details.maintenance, expected);
+
+        // The fulfilled requests have nothing to wait for
+        service
+            .connection_pool
+            .access_storage()
+            .await?
+            .forced_exit_requests_schema()
+            .set_fulfilled_at(request.id, Utc::now())
+            .await?;
+        let details = service.get_request_details(request.public_id).await?;
+        assert_eq!(details.maintenance, None);
+
+        // No maintenance is scheduled by default
+        let config_info = match test_service(true).get_status().await? {
+            ForcedExitRequestStatus::Enabled(config_info) => config_info,
+            ForcedExitRequestStatus::Disabled => panic!("The service is enabled"),
+        };
+        assert_eq!(config_info.maintenance, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn created_request_is_stamped_with_pipeline_version() -> anyhow::Result<()> {
+        let service = test_service(true);
+        let request = service
+            .create_request(register_request(vec![TokenId(0)]), None)
+            .await?;
+
+        let versions = service
+            .connection_pool
+            .access_storage()
+            .await?
+            .forced_exit_requests_schema()
+            .load_pipeline_versions(request.id)
+            .await?;
+        assert_eq!(versions.len(), 1);
+        assert_eq!(versions[0].stage, ForcedExitPipelineStage::Created);
+        assert_eq!(versions[0].version, FORCED_EXIT_PIPELINE_VERSION);
+        assert_eq!(versions[0].config_hash, service.pipeline_config_hash);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn create_request_validation() -> anyhow::Result<()> {
+        let service = test_service(true).with_network(Network::Rinkeby);
+
+        let quote = service.quote(2)?;
+        assert_eq!(
+            quote.price_in_wei,
+            BigUint::from(PRICE_PER_TOKEN as u64 * 2)
+        );
+        assert!(matches!(
+            service.quote(4),
+            Err(ForcedExitRequestsError::TooManyTokens)
+        ));
+        // The request without tokens would cost nothing
+        assert!(matches!(
+            service.quote(0),
+            Err(ForcedExitRequestsError::NoTokens)
+        ));
+
+        // The quoted price is accepted
+        let params = register_request(vec![TokenId(0), TokenId(1)]);
+        assert_eq!(params.price_in_wei, quote.price_in_wei);
+        let request = service.create_request(params, None).await?;
+        assert_eq!(service.get_request(request.public_id).await?, request);
+        // The amount to pay is the quoted price with the zero-padded id followed by
+        // its check digit in the lowest digits
+        let price_digits = quote.price_in_wei.to_string();
+        assert_eq!(
+            request.pay_exactly,
+            format!(
+                "{}{:08}{}",
+                &price_digits[..price_digits.len() - amount_id_digits(DIGITS_IN_ID) as usize],
+                request.id,
+                check_digit(request.id)
+            )
+        );
+        // The payments are matched by the terms of the config the request is created with
+        let terms = request.payment_terms.clone().unwrap();
+        assert_eq!(terms.digits_in_id, DIGITS_IN_ID);
+        assert!(terms.check_digit);
+        assert_eq!(
+            terms.pay_exactly(&request.price_in_wei, request.public_id),
+            request.pay_exactly
+        );
+
+        // The payment URI yields the exact amount matched against the request
+        let created = service.with_payment_instructions(request.clone());
+        assert_eq!(created.request, request);
+        assert_eq!(created.payment.chain_id, Some(4));
+        assert_eq!(created.payment.expires_at, request.valid_until);
+        let (address, chain_id, amount) = parse_payment_uri(&created.payment.uri);
+        assert_eq!(address, service.forced_exit_contract_address);
+        assert_eq!(address, created.payment.address);
+        assert_eq!(chain_id, Some(4));
+        assert_eq!(amount.to_string(), request.pay_exactly);
+        assert_eq!(amount.to_string(), created.payment.amount);
+        assert_eq!(
+            amount,
+            &request.price_in_wei
+                + request.public_id as u64 * 10
+                + u64::from(check_digit(request.public_id))
+        );
+        // The chain is left out for the test networks
+        let created = test_service(true).with_payment_instructions(request.clone());
+        assert_eq!(created.payment.chain_id, None);
+        assert_eq!(
+            parse_payment_uri(&created.payment.uri),
+            (address, None, amount)
+        );
+
+        let result = service
+            .create_request(register_request((0..4).map(TokenId).collect()), None)
+            .await;
+        assert!(matches!(
+            result,
+            Err(ForcedExitRequestsError::TooManyTokens)
+        ));
+
+        let result = service.create_request(register_request(vec![]), None).await;
+        assert!(matches!(result, Err(ForcedExitRequestsError::NoTokens)));
+
+        let mut params = register_request(vec![TokenId(0)]);
+        params.price_in_wei += 1u32;
+        let result = service.create_request(params, None).await;
+        assert!(matches!(
+            result,
+            Err(ForcedExitRequestsError::IncorrectPrice)
+        ));
+
+        let result = service
+            .create_request(register_request(vec![TokenId(u32::MAX)]), None)
+            .await;
+        assert!(matches!(
+            result,
+            Err(ForcedExitRequestsError::TokenNotFound)
+        ));
+
+        // The NFTs held by the target can not be forced to exit
+        let nft = Token::new(
+            TokenId(MIN_NFT_TOKEN_ID),
+            Address::repeat_byte(0x4e),
+            "NFT-65536",
+            0,
+            TokenKind::NFT,
+        );
+        service
+            .connection_pool
+            .access_storage()
+            .await?
+            .tokens_schema()
+            .store_or_update_token(nft)
+            .await?;
+        let result = service
+            .create_request(
+                register_request(vec![TokenId(0), TokenId(MIN_NFT_TOKEN_ID)]),
+                None,
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(ForcedExitRequestsError::NftNotForceable(token)) if token == TokenId(MIN_NFT_TOKEN_ID)
+        ));
+
+        let result = service
+            .create_request(register_request(vec![TokenId(0)]), Some("unknown key"))
+            .await;
+        assert!(matches!(
+            result,
+            Err(ForcedExitRequestsError::InvalidApiKey)
+        ));
+
+        // The fulfilled requests are only posted over HTTPS
+        for callback_url in [
+            "http://example.com/fulfilled".to_owned(),
+            "https:///fulfilled".to_owned(),
+            "not a url".to_owned(),
+            format!(
+                "https://example.com/{}",
+                "a".repeat(MAX_CALLBACK_URL_LENGTH)
+            ),
+        ] {
+            let mut params = register_request(vec![TokenId(0)]);
+            params.callback_url = Some(callback_url);
+            let result = service.create_request(params, None).await;
+            assert!(matches!(
+                result,
+                Err(ForcedExitRequestsError::InvalidCallbackUrl)
+            ));
+        }
+        let with_callback = || {
+            let mut params = register_request(vec![TokenId(0)]);
+            params.callback_url = Some("https://example.com/fulfilled".to_owned());
+            params
+        };
+        service.create_request(with_callback(), None).await?;
+
+        let mut service = service;
+        service
+            .enabled_features
+            .retain(|feature| *feature != ForcedExitFeature::Callbacks);
+        let result = service.create_request(with_callback(), None).await;
+        assert!(matches!(
+            result,
+            Err(ForcedExitRequestsError::FeatureDisabled(
+                ForcedExitFeature::Callbacks
+            ))
+        ));
+
+        Ok(())
+    }
+
+    // Checks that the same requests are accepted or refused depending on the limits
+    // of the environment, none of which is hard-coded
+    #[tokio::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn creation_limit_profiles() -> anyhow::Result<()> {
+        const WEEK_MILLIS: i64 = 7 * 24 * 60 * 60 * 1000;
+
+        let config = ZkSyncConfig::from_env();
+        let profile = |limits: ForcedExitRequestsConfig| {
+            ForcedExitRequestsService::new(
+                ConnectionPool::new(Some(1)),
+                &ForcedExitRequestsConfig {
+                    enabled: true,
+                    price_per_token: PRICE_PER_TOKEN,
+                    digits_in_id: DIGITS_IN_ID,
+                    max_requests_per_hour: u32::MAX,
+                    ..limits
+                },
+                config.contracts.forced_exit_addr,
+                Box::new(DummyForcedExitChecker),
+            )
+        };
+        let strict = profile(ForcedExitRequestsConfig {
+            max_tokens_per_request: 2,
+            max_tx_interval: 60_000,
+            max_metadata_length: 0,
+            max_active_requests_per_target: 1,
+            allowed_tokens: vec![TokenId(0)],
+            ..config.forced_exit_requests.clone()
+        });
+        let permissive = profile(ForcedExitRequestsConfig {
+            max_tokens_per_request: 100,
+            max_tx_interval: WEEK_MILLIS,
+            max_metadata_length: 256,
+            max_active_requests_per_target: 100,
+            allowed_tokens: vec![],
+            ..config.forced_exit_requests.clone()
+        });
+
+        // The limits are reported to the clients as they are applied
+        for service in &[&strict, &permissive] {
+            match service.get_status().await? {
+                ForcedExitRequestStatus::Enabled(info) => {
+                    assert_eq!(info.creation_limits, Some(service.creation_limits.clone()))
+                }
+                ForcedExitRequestStatus::Disabled => panic!("The service is disabled"),
+            }
+        }
+
+        let with_metadata = || ForcedExitRegisterRequest {
+            metadata: Some("partner-ref-42".to_owned()),
+            ..register_request(vec![TokenId(0)])
+        };
+        let suite = vec![
+            register_request(vec![TokenId(0)]),
+            register_request(vec![TokenId(0); 3]),
+            register_request(vec![TokenId(1)]),
+            with_metadata(),
+        ];
+        let outcomes = |service: &ForcedExitRequestsService| -> Vec<Option<String>> {
+            suite
+                .iter()
+                .map(|params| {
+                    let max_tokens = service.creation_limits.max_tokens_per_request;
+                    service
+                        .check_creation_limits(params, max_tokens)
+                        .err()
+                        .map(|err| err.to_string())
+                })
+                .collect()
+        };
+        assert_eq!(outcomes(&permissive), vec![None, None, None, None]);
+        assert_eq!(
+            outcomes(&strict),
+            vec![
+                None,
+                Some(ForcedExitRequestsError::TooManyTokens.to_string()),
+                Some(ForcedExitRequestsError::TokenNotAllowed(TokenId(1)).to_string()),
+                Some(ForcedExitRequestsError::MetadataTooLong(0).to_string()),
+            ]
+        );
+
+        // Only the strict profile limits the requests awaiting the payment for the same target
+        for service in &[&strict, &permissive] {
+            let target = Address::random();
+            let params = || ForcedExitRegisterRequest {
+                target,
+                ..register_request(vec![TokenId(0)])
+            };
+            let first = service.create_request(params(), None).await?;
+            assert_eq!(
+                first.valid_until - first.created_at,
+                Duration::milliseconds(service.creation_limits.max_validity)
+            );
+
+            let second = service.create_request(params(), None).await;
+            if service.creation_limits.max_active_per_target == 1 {
+                assert!(matches!(
+                    second,
+                    Err(ForcedExitRequestsError::TooManyActiveRequests(1))
+                ));
+            } else {
+                second?;
+            }
+        }
+
+        // The metadata is kept with the request
+        let request = permissive.create_request(with_metadata(), None).await?;
+        assert_eq!(request.metadata.as_deref(), Some("partner-ref-42"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn misaligned_price() -> anyhow::Result<()> {
+        // The price, the lowest digits of which overlap with the ids
+        let service = test_service_with_price(true, PRICE_PER_TOKEN + 1);
+
+        // The price is rounded up to the next multiple of the id space
+        let quote = service.quote(2)?;
+        assert_eq!(
+            quote.price_in_wei,
+            BigUint::from(PRICE_PER_TOKEN as u64 * 3)
+        );
+        assert_eq!(
+            service.quote(1)?.price_in_wei,
+            BigUint::from(PRICE_PER_TOKEN as u64 * 2)
+        );
+
+        // The ambiguous amount is rejected
+        let result = service
+            .create_request(
+                ForcedExitRegisterRequest {
+                    price_in_wei: BigUint::from((PRICE_PER_TOKEN as u64 + 1) * 2),
+                    ..register_request(vec![TokenId(0), TokenId(1)])
+                },
+                None,
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(ForcedExitRequestsError::IncorrectPrice)
+        ));
+
+        let request = service
+            .create_request(
+                ForcedExitRegisterRequest {
+                    price_in_wei: quote.price_in_wei.clone(),
+                    ..register_request(vec![TokenId(0), TokenId(1)])
+                },
+                None,
+            )
+            .await?;
+        assert_eq!(request.price_in_wei, quote.price_in_wei);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn extend_and_cancel() -> anyhow::Result<()> {
+        let service = test_service(true);
+
+        let request = service
+            .create_request(register_request(vec![TokenId(0)]), None)
+            .await?;
+        let extended = service.extend(request.public_id).await?;
+        assert!(extended.valid_until >= request.valid_until);
+
+        let cancelled = service
+            .cancel(request.id, ForcedExitCancellationKind::OperatorCancelled)
+            .await?;
+        assert!(cancelled.valid_until <= Utc::now());
+        assert_eq!(
+            cancelled.cancellation,
+            Some(ForcedExitCancellationKind::OperatorCancelled)
+        );
+        // The cancelled request can be paid for again once it is extended
+        let extended = service.extend(request.public_id).await?;
+        assert!(extended.valid_until > Utc::now());
+        assert_eq!(extended.cancellation, None);
+        // The cancellation stays in the details of the request
+        let details = service.get_request_details(request.public_id).await?;
+        assert_eq!(details.cancellations.len(), 1);
+        assert_eq!(
+            details.cancellations[0].kind,
+            ForcedExitCancellationKind::OperatorCancelled
+        );
+
+        // The request that is already being fulfilled can not be changed
+        service
+            .connection_pool
+            .access_storage()
+            .await?
+            .forced_exit_requests_schema()
+            .set_fulfilled_by(request.id, Some(vec![Default::default()]))
+            .await?;
+        assert!(matches!(
+            service.extend(request.public_id).await,
+            Err(ForcedExitRequestsError::RequestNotPending)
+        ));
+        assert!(matches!(
+            service
+                .cancel(request.id, ForcedExitCancellationKind::UserCancelled)
+                .await,
+            Err(ForcedExitRequestsError::RequestNotPending)
+        ));
+
+        assert!(matches!(
+            service.extend(-1).await,
+            Err(ForcedExitRequestsError::RequestNotFound)
+        ));
+        assert!(matches!(
+            service.get_request(-1).await,
+            Err(ForcedExitRequestsError::RequestNotFound)
+        ));
+
+        Ok(())
+    }
+
+    /// Parses the EIP-681 URI of the ETH transfer into the address, the chain and the amount.
+    fn parse_payment_uri(uri: &str) -> (Address, Option<u64>, BigUint) {
+        let uri = uri.strip_prefix("ethereum:0x").expect("Not a payment URI");
+        let (target, amount) = uri.split_once("?value=").expect("No amount in the URI");
+        let (address, chain_id) = match target.split_once('@') {
+            Some((address, chain_id)) => (address, Some(chain_id.parse().unwrap())),
+            None => (target, None),
+        };
+        (
+            Address::from_str(address).unwrap(),
+            chain_id,
+            BigUint::from_str(amount).unwrap(),
+        )
+    }
+
+    #[test]
+    fn id_space_utilization() {
+        assert!(!exceeds_utilization(0, 100, 0));
+        assert!(exceeds_utilization(1, 100, 0));
+        assert!(!exceeds_utilization(80, 100, 80));
+        assert!(exceeds_utilization(81, 100, 80));
+        assert!(!exceeds_utilization(u32::MAX, u64::MAX, 1));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn id_space_exhaustion() -> anyhow::Result<()> {
+        // Only a hundred of ids can be encoded in the amount
+        let config = ZkSyncConfig::from_env();
+        let service = ForcedExitRequestsService::new(
+            ConnectionPool::new(Some(1)),
+            &ForcedExitRequestsConfig {
+                enabled: true,
+                price_per_token: PRICE_PER_TOKEN,
+                digits_in_id: 2,
+                max_tokens_per_request: 3,
+                max_requests_per_hour: u32::MAX,
+                max_tx_interval: 60_000,
+                max_active_requests_per_target: u32::MAX,
+                id_space_alert_utilization: 10,
+                id_space_max_utilization: 20,
+                ..config.forced_exit_requests
+            },
+            config.contracts.forced_exit_addr,
+            Box::new(DummyForcedExitChecker),
+        );
+        let id_space = |status| match status {
+            ForcedExitRequestStatus::Enabled(config) => config.id_space,
+            ForcedExitRequestStatus::Disabled => panic!("The service is disabled"),
+        };
+
+        // The requests may be left by the other tests, so the space is filled until refused
+        let mut requests = Vec::new();
+        let refused = loop {
+            match service
+                .create_request(register_request(vec![TokenId(0)]), None)
+                .await
+            {
+                Ok(request) => requests.push(request),
+                Err(err) => break err,
+            }
+            assert!(requests.len() <= 20, "The id space is overfilled");
+        };
+        assert!(matches!(refused, ForcedExitRequestsError::IdSpaceExhausted));
+        assert!(refused.to_string().contains("retry later"));
+
+        let usage = id_space(service.get_status().await?);
+        assert_eq!(usage.id_space_size, 100);
+        assert!(usage.active_requests >= 20);
+        assert!(usage.alert);
+
+        // The space is freed once the requests are no longer awaiting the payment
+        for request in requests {
+            service
+                .cancel(request.id, ForcedExitCancellationKind::OperatorCancelled)
+                .await?;
+        }
+        let request = service
+            .create_request(register_request(vec![TokenId(0)]), None)
+            .await?;
+        service
+            .cancel(request.id, ForcedExitCancellationKind::OperatorCancelled)
+            .await?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn eta_estimation() {
+        assert_eq!(estimate_eta_secs(0, 0), None);
+        assert_eq!(estimate_eta_secs(10, 0), None);
+        // One request per minute
+        assert_eq!(estimate_eta_secs(0, 60), Some(60));
+        assert_eq!(estimate_eta_secs(4, 60), Some(300));
+        // The estimation is rounded up
+        assert_eq!(estimate_eta_secs(0, 7), Some(515));
+    }
+
+    fn backlog_request(
+        id: ForcedExitRequestId,
+        tokens: Vec<TokenId>,
+        valid_until: DateTime<Utc>,
+    ) -> ForcedExitRequest {
+        let price_in_wei = BigUint::from(PRICE_PER_TOKEN as u64) * tokens.len();
+        ForcedExitRequest {
+            id,
+            public_id: id,
+            target: Address::repeat_byte(0x43),
+            tokens,
+            pay_exactly: pay_exactly(&price_in_wei, id),
+            price_in_wei,
+            valid_until,
+            created_at: valid_until - Duration::hours(1),
+            fulfilled_by: None,
+            fulfilled_at: None,
+            match_scheme: Some(PaymentMatchScheme::AmountDigits),
+            matched_at: Some(valid_until - Duration::minutes(30)),
+            cancellation: None,
+            paid_amount: None,
+            metadata: None,
+            status: Default::default(),
+            payment_terms: None,
+        }
+    }
+
+    #[test]
+    fn backlog_aggregation() {
+        let now = Utc::now();
+        let valid_until = now + Duration::minutes(30);
+        let eligible = ForcedExitTargetCheck {
+            old_enough: true,
+            nonce: Some(Nonce(0)),
+        };
+        let active = ForcedExitTargetCheck {
+            old_enough: true,
+            nonce: Some(Nonce(2)),
+        };
+
+        // The healthy requests, the targets have the balances of all their tokens.
+        // No fee is charged at the moment, so an arbitrary one is set to check the sums
+        let mut healthy = plan_forced_exits(
+            &backlog_request(1, vec![TokenId(0), TokenId(1)], valid_until),
+            eligible,
+            Nonce(10),
+        );
+        healthy.transactions[1].fee = BigUint::from(5u32);
+        let mut another_healthy = plan_forced_exits(
+            &backlog_request(2, vec![TokenId(1)], valid_until),
+            eligible,
+            Nonce(12),
+        );
+        another_healthy.transactions[0].fee = BigUint::from(7u32);
+        // The target has zero balances of both tokens, nothing is withdrawn
+        let dust_only = plan_forced_exits(
+            &backlog_request(3, vec![TokenId(0), TokenId(2)], valid_until),
+            eligible,
+            Nonce(13),
+        );
+        // The ineligible requests
+        let expired = blocked_preflight(
+            &backlog_request(4, vec![TokenId(0)], now - Duration::minutes(1)),
+            ForcedExitBlocker::Expired,
+            None,
+        );
+        let became_active = |id| {
+            plan_forced_exits(
+                &backlog_request(id, vec![TokenId(0)], valid_until),
+                active,
+                Nonce(15),
+            )
+        };
+
+        let simulations = vec![
+            (1, Ok((healthy, 0))),
+            (2, Ok((another_healthy, 0))),
+            (3, Ok((dust_only, 2))),
+            (4, Ok((expired, 0))),
+            (5, Ok((became_active(5), 0))),
+            (6, Ok((became_active(6), 0))),
+            // The request was deleted in the meantime
+            (7, Err(ForcedExitRequestsError::RequestNotFound)),
+        ];
+        let report = backlog_report(simulations, now, 60);
+
+        assert_eq!(report.created_at, now);
+        assert_eq!(report.requests, 7);
+        assert_eq!(report.fulfilled, 3);
+        assert_eq!(report.transactions, 5);
+        assert_eq!(report.nothing_to_withdraw, 1);
+        assert_eq!(
+            report.fees,
+            vec![
+                ForcedExitTokenFees {
+                    token: TokenId(0),
+                    transactions: 2,
+                    total_fee: BigUint::zero(),
+                },
+                ForcedExitTokenFees {
+                    token: TokenId(1),
+                    transactions: 2,
+                    total_fee: BigUint::from(12u32),
+                },
+                ForcedExitTokenFees {
+                    token: TokenId(2),
+                    transactions: 1,
+                    total_fee: BigUint::zero(),
+                },
+            ]
+        );
+        assert_eq!(
+            report.blocked,
+            vec![
+                ForcedExitBlockedRequests {
+                    blocker: ForcedExitBlocker::Expired,
+                    requests: 1,
+                },
+                ForcedExitBlockedRequests {
+                    blocker: ForcedExitBlocker::TargetBecameActive,
+                    requests: 2,
+                },
+            ]
+        );
+        assert_eq!(report.evaluation_failures, 1);
+        // Three requests at one request per minute
+        assert_eq!(report.fulfilled_last_hour, 60);
+        assert_eq!(report.eta_secs, Some(180));
+
+        // Nothing to fulfill takes no time, otherwise it is unknown if nothing has been fulfilled recently
+        let report = backlog_report(vec![(5, Ok((became_active(5), 0)))], now, 0);
+        assert_eq!(report.fulfilled, 0);
+        assert_eq!(report.eta_secs, Some(0));
+        let healthy = plan_forced_exits(
+            &backlog_request(1, vec![TokenId(0)], valid_until),
+            eligible,
+            Nonce(10),
+        );
+        let report = backlog_report(vec![(1, Ok((healthy, 0)))], now, 0);
+        assert_eq!(report.fulfilled, 1);
+        assert_eq!(report.eta_secs, None);
+    }
+
+    // Checks that the config candidate is evaluated against the requests awaiting the payment:
+    // the benign change affects none of them, while the breaking one is reported per request
+    #[tokio::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn config_simulation() -> anyhow::Result<()> {
+        let service = test_service(true);
+        let single = service
+            .create_request(register_request(vec![TokenId(0)]), None)
+            .await?;
+        let double = service
+            .create_request(register_request(vec![TokenId(0), TokenId(1)]), None)
+            .await?;
+        // The request stored without the payment terms is matched by the terms of the config
+        let now = Utc::now();
+        let legacy = service
+            .connection_pool
+            .access_storage()
+            .await?
+            .forced_exit_requests_schema()
+            .store_request(SaveForcedExitRequestQuery {
+                target: Address::repeat_byte(0x43),
+                tokens: vec![TokenId(0)],
+                price_in_wei: BigUint::from(PRICE_PER_TOKEN as u64),
+                created_at: now,
+                valid_until: now + Duration::days(1),
+                metadata: None,
+                payment_terms: None,
+                callback_url: None,
+            })
+            .await?;
+        let seeded = [single.id, double.id, legacy.id];
+        let keys = |report: &ForcedExitConfigImpactReport| -> Vec<String> {
+            report
+                .changes
+                .iter()
+                .map(|change| change.key.clone())
+                .collect()
+        };
+
+        let benign = service
+            .simulate_config(&ForcedExitConfigCandidate {
+                max_metadata_length: Some(1024),
+                overpayment_tolerance_percent: Some(1),
+                // Left as it is
+                digits_in_id: Some(DIGITS_IN_ID),
+                ..Default::default()
+            })
+            .await?;
+        assert_eq!(
+            keys(&benign),
+            vec!["maxMetadataLength", "overpaymentTolerancePercent"]
+        );
+        assert_eq!(
+            benign.current_pipeline_config_hash,
+            service.pipeline_config_hash
+        );
+        assert!(benign.still_matched >= seeded.len() as u32);
+        assert!(benign
+            .unmatchable
+            .iter()
+            .all(|request| !seeded.contains(&request.request_id)));
+        assert!(benign
+            .rejected
+            .iter()
+            .all(|request| !seeded.contains(&request.request_id)));
+        assert_eq!(benign.quotes.len(), 3);
+        assert!(benign
+            .quotes
+            .iter()
+            .all(|quote| quote.current == quote.candidate));
+
+        let breaking = service
+            .simulate_config(&ForcedExitConfigCandidate {
+                price_per_token: Some(PRICE_PER_TOKEN * 10),
+                digits_in_id: Some(DIGITS_IN_ID + 1),
+                max_tokens_per_request: Some(1),
+                ..Default::default()
+            })
+            .await?;
+        assert_eq!(
+            keys(&breaking),
+            vec!["digitsInId", "maxTokensPerRequest", "pricePerToken"]
+        );
+        assert_ne!(
+            breaking.candidate_pipeline_config_hash,
+            breaking.current_pipeline_config_hash
+        );
+        // The stored terms keep the amounts of the requests valid
+        let unmatchable: Vec<_> = breaking
+            .unmatchable
+            .iter()
+            .map(|request| request.request_id)
+            .filter(|id| seeded.contains(id))
+            .collect();
+        assert_eq!(unmatchable, vec![legacy.id]);
+        // None of them would be created with the candidate, the request
+        // with too many tokens is refused before it is priced
+        let rejection = |id| {
+            breaking
+                .rejected
+                .iter()
+                .find(|request| request.request_id == id)
+                .map(|request| request.reason.clone())
+        };
+        let incorrect_price = ForcedExitRequestsError::IncorrectPrice.to_string();
+        assert_eq!(rejection(single.id), Some(incorrect_price.clone()));
+        assert_eq!(
+            rejection(double.id),
+            Some(ForcedExitRequestsError::TooManyTokens.to_string())
+        );
+        assert_eq!(rejection(legacy.id), Some(incorrect_price));
+        let price = |tokens: u64| Some(BigUint::from(PRICE_PER_TOKEN as u64 * tokens).into());
+        assert_eq!(
+            breaking.quotes,
+            vec![
+                ForcedExitQuoteChange {
+                    tokens_count: 1,
+                    current: price(1),
+                    candidate: price(10),
+                },
+                ForcedExitQuoteChange {
+                    tokens_count: 2,
+                    current: price(2),
+                    candidate: None,
+                },
+                ForcedExitQuoteChange {
+                    tokens_count: 3,
+                    current: price(3),
+                    candidate: None,
+                },
+            ]
+        );
+
+        // The price would overlap with the ids of more digits
+        let result = service
+            .simulate_config(&ForcedExitConfigCandidate {
+                digits_in_id: Some(DIGITS_IN_ID + 1),
+                ..Default::default()
+            })
+            .await;
+        assert!(matches!(
+            result,
+            Err(ForcedExitRequestsError::InvalidConfigCandidate(_))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn queue_positions() -> anyhow::Result<()> {
+        let service = test_service(true);
+
+        let mut requests = Vec::new();
+        for _ in 0..4 {
+            requests.push(
+                service
+                    .create_request(register_request(vec![TokenId(0)]), None)
+                    .await?,
+            );
+        }
+        // The requests are paid for in the order other than the one they were created in.
+        // The last one is paid for first, but then it is matched once again
+        let matched_at = Utc::now();
+        let mut storage = service.connection_pool.access_storage().await?;
+        for (index, delay) in &[(3, 0), (1, 1), (0, 2), (3, 3)] {
+            storage
+                .forced_exit_requests_schema()
+                .set_match_scheme(
+                    requests[*index].id,
+                    PaymentMatchScheme::AmountDigits,
+                    matched_at + Duration::milliseconds(*delay),
+                )
+                .await?;
+        }
+        drop(storage);
+
+        let mut positions = Vec::new();
+        for request in &requests {
+            let details = service.get_request_details(request.public_id).await?;
+            assert_eq!(details.request.id, request.id);
+            positions.push(details.queue.map(|queue| queue.position));
+        }
+        // Other requests may be queued by the tests running against the same database,
+        // but they can not get in between
+        let first = positions[3].expect("The paid request is not queued");
+        assert_eq!(positions[1], Some(first + 1));
+        assert_eq!(positions[0], Some(first + 2));
+        assert_eq!(positions[2], None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn active_target_details() -> anyhow::Result<()> {
+        let service = test_service(true);
+
+        let request = service
+            .create_request(register_request(vec![TokenId(0)]), None)
+            .await?;
+        let details = service.get_request_details(request.public_id).await?;
+        assert_eq!(details.active_target, None);
+
+        let eth_tx_hash = H256::random();
+        let now = Utc::now();
+        let active_target = ForcedExitRequestActiveTarget {
+            request_id: request.id,
+            policy: ActiveTargetPolicy::Fail,
+            target_nonce: Nonce(1),
+            payment_amount: request.price_in_wei.clone() + request.id as u64,
+            payment_tx_hash: Some(eth_tx_hash),
+            match_scheme: PaymentMatchScheme::AmountDigits,
+            paid_at: now,
+            detected_at: now,
+            hold_until: None,
+            resumed_at: None,
+            failed_at: Some(now),
+        };
+        service
+            .connection_pool
+            .access_storage()
+            .await?
+            .forced_exit_requests_schema()
+            .store_active_target(active_target)
+            .await?;
+
+        // The failed request is not queued, the payment to be refunded is reported instead
+        let details = service.get_request_details(request.public_id).await?;
+        assert_eq!(details.queue, None);
+        let reported = details.active_target.clone().unwrap();
+        assert!(reported.is_failed());
+        assert_eq!(reported.payment_tx_hash, Some(eth_tx_hash));
+        match service.lookup_payment(eth_tx_hash).await? {
+            ForcedExitPaymentLookup::Matched { requests } => assert_eq!(requests, vec![details]),
+            lookup => panic!("The payment is not matched: {:?}", lookup),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn list_requests() -> anyhow::Result<()> {
+        let service = test_service(true);
+
+        let request = service
+            .create_request(register_request(vec![TokenId(0)]), None)
+            .await?;
+        let page = service
+            .list(PaginationQuery {
+                from: ForcedExitRequestsQuery {
+                    target: request.target,
+                    request_id: ApiEither::from_str("latest")?,
+                },
+                limit: 1,
+                direction: PaginationDirection::Older,
+            })
+            .await?;
+        assert_eq!(page.list, vec![request.clone()]);
+
+        let result = service
+            .list(PaginationQuery {
+                from: ForcedExitRequestsQuery {
+                    target: request.target,
+                    request_id: ApiEither::from(request.id),
+                },
+                limit: MAX_LIMIT + 1,
+                direction: PaginationDirection::Older,
+            })
+            .await;
+        assert!(matches!(
+            result,
+            Err(ForcedExitRequestsError::PaginationLimitTooBig)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn price_alignment() {
+        assert_eq!(
+            align_price(BigUint::from(12000u32), 3),
+            BigUint::from(12000u32)
+        );
+        assert_eq!(
+            align_price(BigUint::from(12001u32), 3),
+            BigUint::from(13000u32)
+        );
+        assert_eq!(
+            align_price(BigUint::from(12999u32), 3),
+            BigUint::from(13000u32)
+        );
+        // Even the tiny prices are not rounded down to zero
+        assert_eq!(align_price(BigUint::from(1u32), 3), BigUint::from(1000u32));
+    }
+
+    #[test]
+    fn payment_uri_format() {
+        let address = Address::repeat_byte(0x12);
+        assert_eq!(
+            payment_uri(address, "2000000000000000123", Some(5)),
+            "ethereum:0x1212121212121212121212121212121212121212@5?value=2000000000000000123"
+        );
+        assert_eq!(
+            payment_uri(address, "2007", None),
+            "ethereum:0x1212121212121212121212121212121212121212?value=2007"
+        );
+    }
+
+    #[test]
+    fn config_changes_are_listed() {
+        let current = ForcedExitConfigCandidate {
+            price_per_token: Some(30_000),
+            digits_in_id: Some(3),
+            max_tokens_per_request: Some(10),
+            max_payment_amount: Some(BigUint::from(1_000_000u32).into()),
+            ..Default::default()
+        };
+        let candidate = ForcedExitConfigCandidate {
+            digits_in_id: Some(4),
+            max_tokens_per_request: Some(10),
+            max_payment_amount: Some(BigUint::from(2_000_000u32).into()),
+            ..Default::default()
+        };
+        let changes = config_changes(
+            &serde_json::to_value(current).unwrap(),
+            &serde_json::to_value(candidate).unwrap(),
+        );
+
+        // Neither the keys left out nor the unchanged ones are reported
+        assert_eq!(
+            changes,
+            vec![
+                ForcedExitConfigChange {
+                    key: "digitsInId".to_owned(),
+                    current: serde_json::json!(3),
+                    candidate: serde_json::json!(4),
+                },
+                ForcedExitConfigChange {
+                    key: "maxPaymentAmount".to_owned(),
+                    current: serde_json::json!("1000000"),
+                    candidate: serde_json::json!("2000000"),
+                },
+            ]
+        );
+    }
+}
@@ -1,219 +1,91 @@
 //! Transactions part of API implementation.
 
 // Built-in uses
+use std::time::Instant;
 
 // External uses
 use actix_web::{
     web::{self, Json},
-    Scope,
+    HttpRequest, Scope,
 };
 
-use bigdecimal::{BigDecimal, FromPrimitive};
-use chrono::{Duration, Utc};
-use num::{bigint::ToBigInt, BigUint};
-use std::time::Instant;
-use std::{convert::TryInto, ops::Add};
 // Workspace uses
 pub use zksync_api_client::rest::forced_exit_requests::{
-    ForcedExitRegisterRequest, ForcedExitRequestStatus,
+    ForcedExitRegisterRequest, ForcedExitRequestDetails, ForcedExitRequestProgress,
+    ForcedExitRequestStatus,
 };
 
-use zksync_api_client::rest::forced_exit_requests::ConfigInfo;
 use zksync_config::ForcedExitRequestsConfig;
 use zksync_storage::ConnectionPool;
 use zksync_types::{
-    forced_exit_requests::{
-        ForcedExitEligibilityResponse, ForcedExitRequest, ForcedExitRequestId,
-        SaveForcedExitRequestQuery,
-    },
-    Address, TokenLike,
+    forced_exit_requests::{ForcedExitEligibilityResponse, ForcedExitRequest, ForcedExitRequestId},
+    Address,
 };
 
 // Local uses
-use super::{error::ApiError, JsonResult};
+use super::{api_key, error::ApiError, service::ForcedExitRequestsService, JsonResult};
 use crate::api_server::forced_exit_checker::ForcedExitAccountAgeChecker;
 
-/// Shared data between `/api/forced_exit_requests/v0.1/` endpoints.
-pub struct ApiForcedExitRequestsData {
-    pub(crate) connection_pool: ConnectionPool,
-    pub(crate) forced_exit_checker: Box<dyn ForcedExitAccountAgeChecker>,
-
-    pub(crate) is_enabled: bool,
-    pub(crate) max_tokens_per_request: u8,
-    pub(crate) digits_in_id: u8,
-    pub(crate) recomended_tx_interval_millisecs: i64,
-    pub(crate) max_tx_interval_millisecs: i64,
-    pub(crate) price_per_token: i64,
-    pub(crate) forced_exit_contract_address: Address,
-    pub(crate) wait_confirmations: u64,
-}
-
-impl ApiForcedExitRequestsData {
-    fn new(
-        connection_pool: ConnectionPool,
-        config: &ForcedExitRequestsConfig,
-        contract: Address,
-        forced_exit_checker: Box<dyn ForcedExitAccountAgeChecker>,
-    ) -> Self {
-        Self {
-            connection_pool,
-            forced_exit_checker,
-
-            is_enabled: config.enabled,
-            price_per_token: config.price_per_token,
-            max_tokens_per_request: config.max_tokens_per_request,
-            recomended_tx_interval_millisecs: config.recomended_tx_interval,
-            max_tx_interval_millisecs: config.max_tx_interval,
-            forced_exit_contract_address: contract,
-            digits_in_id: config.digits_in_id,
-            wait_confirmations: config.wait_confirmations,
-        }
-    }
-}
-
 async fn get_status(
-    data: web::Data<ApiForcedExitRequestsData>,
+    data: web::Data<ForcedExitRequestsService>,
 ) -> JsonResult<ForcedExitRequestStatus> {
     let start = Instant::now();
-
-    let response = if data.is_enabled {
-        ForcedExitRequestStatus::Enabled(ConfigInfo {
-            request_fee: BigUint::from(data.price_per_token as u64),
-            max_tokens_per_request: data.max_tokens_per_request,
-            recomended_tx_interval_millis: data.recomended_tx_interval_millisecs,
-            forced_exit_contract_address: data.forced_exit_contract_address,
-            wait_confirmations: data.wait_confirmations,
-        })
-    } else {
-        ForcedExitRequestStatus::Disabled
-    };
-
+    let response = data.get_status().await.map_err(ApiError::from)?;
     metrics::histogram!("api", start.elapsed(), "type" => "v01", "endpoint_name" => "forced_exit_request_status");
     Ok(Json(response))
 }
 
 pub async fn submit_request(
-    data: web::Data<ApiForcedExitRequestsData>,
+    req: HttpRequest,
+    data: web::Data<ForcedExitRequestsService>,
     params: web::Json<ForcedExitRegisterRequest>,
 ) -> JsonResult<ForcedExitRequest> {
     let start = Instant::now();
-
-    let mut storage = data
-        .connection_pool
-        .access_storage()
-        .await
-        .map_err(warn_err)
-        .map_err(ApiError::internal)?;
-
-    if params.tokens.len() > data.max_tokens_per_request as usize {
-        return Err(ApiError::bad_request(
-            "Maximum number of tokens per ForcedExit request exceeded",
-        ));
-    }
-
-    data.forced_exit_checker
-        .validate_forced_exit(&mut storage, params.target)
+    let saved_fe_request = data
+        .create_request(params.into_inner(), api_key(&req))
         .await
         .map_err(ApiError::from)?;
-
-    let price_of_one_exit = BigDecimal::from(data.price_per_token);
-    let price_of_request = price_of_one_exit * BigDecimal::from_usize(params.tokens.len()).unwrap();
-
-    let user_fee = params.price_in_wei.to_bigint().unwrap();
-    let user_fee = BigDecimal::from(user_fee);
-
-    if user_fee != price_of_request {
-        return Err(ApiError::bad_request(
-            "The amount should be exactly the price of the supplied withdrawals",
-        ));
-    }
-
-    let mut tokens_schema = storage.tokens_schema();
-
-    for token_id in params.tokens.iter() {
-        // The result is going nowhere.
-        // This is simply to make sure that the tokens
-        // that were supplied do indeed exist
-        tokens_schema
-            .get_token(TokenLike::Id(*token_id))
-            .await
-            .map_err(|_| ApiError::bad_request("One of the tokens does no exist"))?;
-    }
-
-    let mut fe_schema = storage.forced_exit_requests_schema();
-
-    let created_at = Utc::now();
-    let valid_until = created_at.add(Duration::milliseconds(data.max_tx_interval_millisecs));
-
-    let saved_fe_request = fe_schema
-        .store_request(SaveForcedExitRequestQuery {
-            target: params.target,
-            tokens: params.tokens.clone(),
-            price_in_wei: params.price_in_wei.clone(),
-            created_at,
-            valid_until,
-        })
-        .await
-        .map_err(|err| {
-            vlog::error!("Store forced exit error {:?}", err);
-            ApiError::internal("Database error")
-        })?;
-
-    check_address_space_overflow(saved_fe_request.id, data.digits_in_id);
-
     metrics::histogram!("api", start.elapsed(), "type" => "v01", "endpoint_name" => "submit_forced_exit_request");
     Ok(Json(saved_fe_request))
 }
 
 pub async fn get_request_by_id(
-    data: web::Data<ApiForcedExitRequestsData>,
+    data: web::Data<ForcedExitRequestsService>,
     request_id: web::Path<ForcedExitRequestId>,
-) -> JsonResult<ForcedExitRequest> {
+) -> JsonResult<ForcedExitRequestDetails> {
     let start = Instant::now();
-
-    let mut storage = data
-        .connection_pool
-        .access_storage()
+    let fe_request = data
+        .get_request_details(*request_id)
         .await
-        .map_err(warn_err)
-        .map_err(ApiError::internal)?;
-
-    let mut fe_requests_schema = storage.forced_exit_requests_schema();
+        .map_err(ApiError::from)?;
+    metrics::histogram!("api", start.elapsed(), "type" => "v01", "endpoint_name" => "get_forced_exit_request_by_id");
+    Ok(Json(fe_request))
+}
 
-    let fe_request_from_db = fe_requests_schema
-        .get_request_by_id(*request_id)
+pub async fn get_request_progress(
+    data: web::Data<ForcedExitRequestsService>,
+    request_id: web::Path<ForcedExitRequestId>,
+) -> JsonResult<ForcedExitRequestProgress> {
+    let start = Instant::now();
+    let progress = data
+        .get_request_progress(*request_id)
         .await
-        .map_err(ApiError::internal)?;
-
-    metrics::histogram!("api", start.elapsed(), "type" => "v01", "endpoint_name" => "get_forced_exit_request_by_id");
-    match fe_request_from_db {
-        Some(fe_request) => Ok(Json(fe_request)),
-        None => Err(ApiError::not_found("Request with such id does not exist")),
-    }
+        .map_err(ApiError::from)?;
+    metrics::histogram!("api", start.elapsed(), "type" => "v01", "endpoint_name" => "get_forced_exit_request_progress");
+    Ok(Json(progress))
 }
 
 // Checks if the account is eligible for forced_exit in terms of
 // existing enough time
 pub async fn check_account_eligibility(
-    data: web::Data<ApiForcedExitRequestsData>,
+    data: web::Data<ForcedExitRequestsService>,
     account: web::Path<Address>,
 ) -> JsonResult<ForcedExitEligibilityResponse> {
     let start = Instant::now();
-    let mut storage = data
-        .connection_pool
-        .access_storage()
-        .await
-        .map_err(warn_err)
-        .map_err(ApiError::internal)?;
-
-    let eligible = data
-        .forced_exit_checker
-        .check_forced_exit(&mut storage, *account)
+    let result = data
+        .check_account_eligibility(*account)
         .await
         .map_err(ApiError::from)?;
-
-    let result = ForcedExitEligibilityResponse { eligible };
-
     metrics::histogram!("api", start.elapsed(), "type" => "v01", "endpoint_name" => "check_account_eligibility");
     Ok(Json(result))
 }
@@ -224,7 +96,7 @@ pub fn api_scope(
     contract: Address,
     fe_checker: Box<dyn ForcedExitAccountAgeChecker>,
 ) -> Scope {
-    let data = ApiForcedExitRequestsData::new(connection_pool, config, contract, fe_checker);
+    let data = ForcedExitRequestsService::new(connection_pool, config, contract, fe_checker);
 
     // `enabled` endpoint should always be there
     let scope = web::scope("v0.1")
@@ -235,6 +107,7 @@ pub fn api_scope(
         scope
             .route("/submit", web::post().to(submit_request))
             .route("/requests/{id}", web::get().to(get_request_by_id))
+            .route("/requests/{id}/status", web::get().to(get_request_progress))
             .route(
                 "/checks/eligibility/{account}",
                 web::get().to(check_account_eligibility),
@@ -249,7 +122,7 @@ mod tests {
     use std::ops::Mul;
     use std::str::FromStr;
 
-    use num::BigUint;
+    use num::{BigUint, FromPrimitive};
 
     use zksync_api_client::rest::client::Client;
     use zksync_config::{ForcedExitRequestsConfig, ZkSyncConfig};
@@ -330,6 +203,8 @@ mod tests {
             target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
             tokens: vec![TokenId(0)],
             price_in_wei: BigUint::from_str("1212").unwrap(),
+            metadata: None,
+            callback_url: None,
         };
 
         client
@@ -402,6 +277,8 @@ mod tests {
             target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
             tokens,
             price_in_wei,
+            metadata: None,
+            callback_url: None,
         };
 
         client
@@ -445,6 +322,8 @@ mod tests {
             target,
             tokens: tokens.clone(),
             price_in_wei: price_in_wei.clone(),
+            metadata: None,
+            callback_url: None,
         };
 
         let submit_result = client.submit_forced_exit_request(fe_request).await?;
@@ -457,24 +336,3 @@ mod tests {
         Ok(())
     }
 }
-
-fn warn_err<T: std::fmt::Display>(err: T) -> T {
-    vlog::warn!("Internal Server Error: '{}';", err);
-    err
-}
-
-// Checks if the id exceeds half of the address space
-// If it exceeds the half at all the alert should be triggerred
-// since it it a sign of a possible DoS attack
-pub fn check_address_space_overflow(id: i64, digits_in_id: u8) {
-    let address_space = 10_i64.saturating_pow(digits_in_id as u32);
-
-    let exceeding_rate = id.saturating_sub(address_space / 2);
-    // Need this for metrics
-    let exceeding_rate: u64 = exceeding_rate.max(0).try_into().unwrap();
-
-    metrics::histogram!(
-        "forced_exit_requests.address_space_overflow",
-        exceeding_rate as f64
-    );
-}
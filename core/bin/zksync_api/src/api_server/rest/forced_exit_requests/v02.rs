@@ -0,0 +1,869 @@
+//! Forced exit requests part of API implementation following the v0.2 conventions.
+
+// Built-in uses
+use std::time::Instant;
+
+// External uses
+use actix_web::{web, HttpRequest, Scope};
+
+// Workspace uses
+use zksync_api_client::rest::forced_exit_requests::{
+    ForcedExitCreatedRequest, ForcedExitExpectedPaymentRequest, ForcedExitPaymentLookup,
+    ForcedExitQuoteQuery, ForcedExitRegisterRequest, ForcedExitRequestDetails,
+    ForcedExitRequestProgress, ForcedExitRequestQuote, ForcedExitRequestStatus,
+};
+use zksync_api_types::v02::{
+    pagination::{parse_query, ForcedExitRequestsQuery, Paginated, PaginationQuery},
+    ApiVersion,
+};
+use zksync_config::ForcedExitRequestsConfig;
+use zksync_storage::ConnectionPool;
+use zksync_types::{
+    forced_exit_requests::{ForcedExitRequest, ForcedExitRequestId, ForcedExitRequestLedger},
+    network::Network,
+    Address, H256,
+};
+
+// Local uses
+use super::{api_key, service::ForcedExitRequestsService};
+use crate::api_server::{
+    forced_exit_checker::ForcedExitAccountAgeChecker,
+    rest::v02::{error::Error, response::ApiResult, SharedData},
+};
+use crate::api_try;
+
+async fn get_status(
+    data: web::Data<ForcedExitRequestsService>,
+) -> ApiResult<ForcedExitRequestStatus> {
+    let start = Instant::now();
+    let res = data.get_status().await.map_err(Error::from).into();
+    metrics::histogram!("api", start.elapsed(), "type" => "v02", "endpoint_name" => "forced_exit_request_status");
+    res
+}
+
+async fn get_quote(
+    data: web::Data<ForcedExitRequestsService>,
+    web::Query(query): web::Query<ForcedExitQuoteQuery>,
+) -> ApiResult<ForcedExitRequestQuote> {
+    let start = Instant::now();
+    let res = data.quote(query.tokens_count).map_err(Error::from).into();
+    metrics::histogram!("api", start.elapsed(), "type" => "v02", "endpoint_name" => "forced_exit_request_quote");
+    res
+}
+
+async fn create_request(
+    req: HttpRequest,
+    data: web::Data<ForcedExitRequestsService>,
+    web::Json(params): web::Json<ForcedExitRegisterRequest>,
+) -> ApiResult<ForcedExitCreatedRequest> {
+    let start = Instant::now();
+    let res = data
+        .create_request(params, api_key(&req))
+        .await
+        .map(|request| data.with_payment_instructions(request))
+        .map_err(Error::from)
+        .into();
+    metrics::histogram!("api", start.elapsed(), "type" => "v02", "endpoint_name" => "create_forced_exit_request");
+    res
+}
+
+async fn get_request_by_id(
+    data: web::Data<ForcedExitRequestsService>,
+    request_id: web::Path<ForcedExitRequestId>,
+) -> ApiResult<ForcedExitRequestDetails> {
+    let start = Instant::now();
+    let res = data
+        .get_request_details(*request_id)
+        .await
+        .map_err(Error::from)
+        .into();
+    metrics::histogram!("api", start.elapsed(), "type" => "v02", "endpoint_name" => "get_forced_exit_request_by_id");
+    res
+}
+
+async fn get_request_progress(
+    data: web::Data<ForcedExitRequestsService>,
+    request_id: web::Path<ForcedExitRequestId>,
+) -> ApiResult<ForcedExitRequestProgress> {
+    let start = Instant::now();
+    let res = data
+        .get_request_progress(*request_id)
+        .await
+        .map_err(Error::from)
+        .into();
+    metrics::histogram!("api", start.elapsed(), "type" => "v02", "endpoint_name" => "get_forced_exit_request_progress");
+    res
+}
+
+async fn get_request_ledger(
+    data: web::Data<ForcedExitRequestsService>,
+    request_id: web::Path<ForcedExitRequestId>,
+) -> ApiResult<ForcedExitRequestLedger> {
+    let start = Instant::now();
+    let res = data
+        .get_request_ledger(*request_id)
+        .await
+        .map_err(Error::from)
+        .into();
+    metrics::histogram!("api", start.elapsed(), "type" => "v02", "endpoint_name" => "get_forced_exit_request_ledger");
+    res
+}
+
+async fn get_requests_by_payment(
+    data: web::Data<ForcedExitRequestsService>,
+    eth_tx_hash: web::Path<H256>,
+) -> ApiResult<ForcedExitPaymentLookup> {
+    let start = Instant::now();
+    let res = data
+        .lookup_payment(*eth_tx_hash)
+        .await
+        .map_err(Error::from)
+        .into();
+    metrics::histogram!("api", start.elapsed(), "type" => "v02", "endpoint_name" => "get_forced_exit_requests_by_payment");
+    res
+}
+
+async fn extend_request(
+    data: web::Data<ForcedExitRequestsService>,
+    request_id: web::Path<ForcedExitRequestId>,
+) -> ApiResult<ForcedExitRequest> {
+    let start = Instant::now();
+    let res = data.extend(*request_id).await.map_err(Error::from).into();
+    metrics::histogram!("api", start.elapsed(), "type" => "v02", "endpoint_name" => "extend_forced_exit_request");
+    res
+}
+
+async fn register_expected_payment(
+    req: HttpRequest,
+    data: web::Data<ForcedExitRequestsService>,
+    request_id: web::Path<ForcedExitRequestId>,
+    web::Json(params): web::Json<ForcedExitExpectedPaymentRequest>,
+) -> ApiResult<ForcedExitRequest> {
+    let start = Instant::now();
+    let res = data
+        .register_expected_payment(*request_id, params.eth_tx_hash, api_key(&req))
+        .await
+        .map_err(Error::from)
+        .into();
+    metrics::histogram!("api", start.elapsed(), "type" => "v02", "endpoint_name" => "register_expected_forced_exit_payment");
+    res
+}
+
+async fn account_requests(
+    data: web::Data<ForcedExitRequestsService>,
+    target: web::Path<Address>,
+    web::Query(query): web::Query<PaginationQuery<String>>,
+) -> ApiResult<Paginated<ForcedExitRequest, ForcedExitRequestId>> {
+    let start = Instant::now();
+    let query = api_try!(parse_query(query).map_err(Error::from));
+    let query = PaginationQuery {
+        from: ForcedExitRequestsQuery {
+            target: *target,
+            request_id: query.from,
+        },
+        limit: query.limit,
+        direction: query.direction,
+    };
+    let res = data.list(query).await.map_err(Error::from).into();
+    metrics::histogram!("api", start.elapsed(), "type" => "v02", "endpoint_name" => "account_forced_exit_requests");
+    res
+}
+
+pub fn api_scope(
+    connection_pool: ConnectionPool,
+    config: &ForcedExitRequestsConfig,
+    contract: Address,
+    fe_checker: Box<dyn ForcedExitAccountAgeChecker>,
+    network: Network,
+) -> Scope {
+    let data = ForcedExitRequestsService::new(connection_pool, config, contract, fe_checker)
+        .with_network(network);
+    let shared_data = SharedData {
+        net: network,
+        api_version: ApiVersion::V02,
+    };
+
+    // `status` endpoint should always be there
+    let scope = web::scope("v0.2")
+        .app_data(web::Data::new(shared_data))
+        .app_data(web::Data::new(data))
+        .route("status", web::get().to(get_status));
+
+    if config.enabled {
+        scope
+            .route("quote", web::get().to(get_quote))
+            .route("requests", web::post().to(create_request))
+            .route("requests/{id}", web::get().to(get_request_by_id))
+            .route("requests/{id}/status", web::get().to(get_request_progress))
+            .route("requests/{id}/ledger", web::get().to(get_request_ledger))
+            .route("requests/{id}/extend", web::post().to(extend_request))
+            .route(
+                "requests/{id}/expected_payment",
+                web::post().to(register_expected_payment),
+            )
+            .route(
+                "by_payment/{eth_tx_hash}",
+                web::get().to(get_requests_by_payment),
+            )
+            .route(
+                "accounts/{address}/requests",
+                web::get().to(account_requests),
+            )
+    } else {
+        scope
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use num::BigUint;
+    use serde_json::json;
+
+    use chrono::Utc;
+    use zksync_api_types::v02::{
+        pagination::{ApiEither, PaginationDirection},
+        Response, ResultStatus,
+    };
+    use zksync_config::ZkSyncConfig;
+    use zksync_types::{
+        forced_exit_requests::{
+            pay_exactly, ForcedExitLifecycleStatus, ForcedExitPayment, ForcedExitTxStatus,
+            PaymentMatchScheme, PaymentSource, SaveForcedExitRequestsApiKeyQuery,
+            UnmatchedPaymentReason,
+        },
+        tx::TxHash,
+        TokenId,
+    };
+
+    use super::*;
+    use crate::api_server::{
+        forced_exit_checker::DummyForcedExitChecker,
+        rest::v02::test_utils::{deserialize_response_result, TestServerConfig},
+        rest::{forced_exit_requests::service::api_key_hash, v02::error::ErrorCode},
+    };
+
+    const PRICE_PER_TOKEN: i64 = 1_000_000_000;
+
+    fn get_test_config() -> TestServerConfig {
+        let config_from_env = ZkSyncConfig::from_env();
+        let config = ZkSyncConfig {
+            forced_exit_requests: ForcedExitRequestsConfig {
+                enabled: true,
+                price_per_token: PRICE_PER_TOKEN,
+                digits_in_id: 8,
+                max_tokens_per_request: 3,
+                ..ForcedExitRequestsConfig::from_env()
+            },
+            ..config_from_env
+        };
+
+        TestServerConfig {
+            config,
+            pool: ConnectionPool::new(Some(1)),
+        }
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn forced_exit_requests_v02_scope() -> anyhow::Result<()> {
+        let cfg = get_test_config();
+        let (client, server) = cfg.start_server_with_scope(
+            String::from("api/forced_exit_requests"),
+            |cfg| {
+                api_scope(
+                    cfg.pool.clone(),
+                    &cfg.config.forced_exit_requests,
+                    cfg.config.contracts.forced_exit_addr,
+                    Box::new(DummyForcedExitChecker {}),
+                    cfg.config.chain.eth.network,
+                )
+            },
+            Option::<SharedData>::None,
+        );
+
+        // Every response is wrapped into the envelope
+        let response = client.forced_exit_requests_status_v02().await?;
+        assert!(matches!(response.status, ResultStatus::Success));
+        assert!(matches!(response.request.api_version, ApiVersion::V02));
+        assert_eq!(response.request.network, cfg.config.chain.eth.network);
+        assert_eq!(
+            response.request.resource,
+            "/api/forced_exit_requests/v0.2/status"
+        );
+        assert!(response.error.is_none());
+        let status: ForcedExitRequestStatus = deserialize_response_result(response)?;
+        assert!(matches!(status, ForcedExitRequestStatus::Enabled(_)));
+
+        let response = client.forced_exit_request_quote(2).await?;
+        assert_eq!(
+            response.request.args.get("tokensCount").map(String::as_str),
+            Some("2")
+        );
+        let quote_json = response.result.clone().unwrap();
+        assert_eq!(
+            quote_json,
+            json!({
+                "tokensCount": 2,
+                "priceInWei": (PRICE_PER_TOKEN * 2).to_string(),
+                "forcedExitContractAddress": cfg.config.contracts.forced_exit_addr,
+                "chainId": cfg.config.chain.eth.network.chain_id(),
+            })
+        );
+        let quote: ForcedExitRequestQuote = deserialize_response_result(response)?;
+
+        // Errors are reported in the envelope as well
+        let response = client.forced_exit_request_quote(4).await?;
+        assert!(matches!(response.status, ResultStatus::Error));
+        assert!(response.result.is_none());
+        let error: Error = serde_json::from_value(response.error.unwrap())?;
+        assert_eq!(error.error_type, "forcedExitRequestError");
+
+        let target = Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap();
+        let mut requests = Vec::new();
+        for _ in 0..3 {
+            let response = client
+                .create_forced_exit_request(&ForcedExitRegisterRequest {
+                    target,
+                    tokens: vec![TokenId(0), TokenId(1)],
+                    price_in_wei: quote.price_in_wei.clone(),
+                    metadata: None,
+                    callback_url: None,
+                })
+                .await?;
+            let created: ForcedExitCreatedRequest = deserialize_response_result(response)?;
+            // The wallets are told to pay exactly the amount the request is matched by
+            assert_eq!(created.payment.address, quote.forced_exit_contract_address);
+            assert_eq!(created.payment.amount, created.request.pay_exactly);
+            assert_eq!(
+                created.payment.uri,
+                format!(
+                    "ethereum:{:?}@{}?value={}",
+                    quote.forced_exit_contract_address,
+                    cfg.config.chain.eth.network.chain_id(),
+                    created.request.pay_exactly
+                )
+            );
+            requests.push(created.request);
+        }
+        let request_json = serde_json::to_value(&requests[0])?;
+        for field in &[
+            "id",
+            "publicId",
+            "target",
+            "tokens",
+            "priceInWei",
+            "payExactly",
+            "validUntil",
+            "createdAt",
+            "fulfilledBy",
+            "fulfilledAt",
+            "matchScheme",
+        ] {
+            assert!(request_json.get(field).is_some(), "{} is missing", field);
+        }
+        // The amount to pay is returned as the decimal string, not as a number
+        assert_eq!(
+            request_json["payExactly"],
+            json!(pay_exactly(&quote.price_in_wei, requests[0].public_id))
+        );
+
+        let response = client
+            .create_forced_exit_request(&ForcedExitRegisterRequest {
+                target,
+                tokens: vec![TokenId(0)],
+                price_in_wei: BigUint::from(1u32),
+                metadata: None,
+                callback_url: None,
+            })
+            .await?;
+        assert!(matches!(response.status, ResultStatus::Error));
+
+        let response = client
+            .forced_exit_request_by_id(requests[1].public_id)
+            .await?;
+        let details: ForcedExitRequestDetails = deserialize_response_result(response)?;
+        assert_eq!(details.request, requests[1]);
+        // The request has not been paid for yet
+        assert_eq!(details.queue, None);
+
+        let response = client
+            .extend_forced_exit_request(requests[1].public_id)
+            .await?;
+        let extended: ForcedExitRequest = deserialize_response_result(response)?;
+        assert_eq!(extended.id, requests[1].id);
+        assert!(extended.valid_until >= requests[1].valid_until);
+        let response = client.extend_forced_exit_request(-1).await?;
+        let error: Error = serde_json::from_value(response.error.unwrap())?;
+        assert_eq!(error.code, ErrorCode::ForcedExitRequestNotFound);
+        requests[1] = extended;
+
+        // No transactions are reported until they are sent
+        let response = client
+            .forced_exit_request_progress(requests[2].public_id)
+            .await?;
+        let progress: ForcedExitRequestProgress = deserialize_response_result(response)?;
+        assert_eq!(progress.id, requests[2].public_id);
+        assert!(progress.transactions.is_empty());
+        let tx_hashes: Vec<_> = (1..=2u8)
+            .map(|byte| TxHash::from_slice(&[byte; 32]).unwrap())
+            .collect();
+        cfg.pool
+            .access_storage()
+            .await?
+            .forced_exit_requests_schema()
+            .set_fulfilled_by(requests[2].id, Some(tx_hashes.clone()), false)
+            .await?;
+        let response = client
+            .forced_exit_request_progress(requests[2].public_id)
+            .await?;
+        let progress: ForcedExitRequestProgress = deserialize_response_result(response)?;
+        assert_eq!(progress.status, ForcedExitLifecycleStatus::TxsSent);
+        assert_eq!(
+            progress
+                .transactions
+                .iter()
+                .map(|tx| (tx.token, tx.tx_hash, tx.status))
+                .collect::<Vec<_>>(),
+            vec![
+                (TokenId(0), tx_hashes[0], ForcedExitTxStatus::Pending),
+                (TokenId(1), tx_hashes[1], ForcedExitTxStatus::Pending),
+            ]
+        );
+        let response = client.forced_exit_request_progress(-1).await?;
+        let error: Error = serde_json::from_value(response.error.unwrap())?;
+        assert_eq!(error.code, ErrorCode::ForcedExitRequestNotFound);
+
+        // Pagination from the latest request to the older ones
+        let query = PaginationQuery {
+            from: ApiEither::from_str("latest")?,
+            limit: 2,
+            direction: PaginationDirection::Older,
+        };
+        let response = client
+            .forced_exit_requests_pagination(target, &query)
+            .await?;
+        let page: Paginated<ForcedExitRequest, ForcedExitRequestId> =
+            deserialize_response_result(response)?;
+        assert_eq!(page.list, vec![requests[2].clone(), requests[1].clone()]);
+        assert_eq!(page.pagination.from, requests[2].id);
+        assert_eq!(page.pagination.direction, PaginationDirection::Older);
+        assert!(page.pagination.count >= 3);
+
+        // Pagination from the first request to the newer ones
+        let query = PaginationQuery {
+            from: ApiEither::from(requests[0].id),
+            limit: 2,
+            direction: PaginationDirection::Newer,
+        };
+        let response: Response = client
+            .forced_exit_requests_pagination(target, &query)
+            .await?;
+        assert_eq!(
+            response.request.args.get("direction").map(String::as_str),
+            Some("newer")
+        );
+        let page: Paginated<ForcedExitRequest, ForcedExitRequestId> =
+            deserialize_response_result(response)?;
+        assert_eq!(page.list, vec![requests[0].clone(), requests[1].clone()]);
+        assert_eq!(page.pagination.from, requests[0].id);
+        assert_eq!(page.pagination.limit, 2);
+
+        // The account without requests has an empty list
+        let response = client
+            .forced_exit_requests_pagination(
+                Address::repeat_byte(0x12),
+                &PaginationQuery {
+                    from: ApiEither::from_str("latest")?,
+                    limit: 2,
+                    direction: PaginationDirection::Older,
+                },
+            )
+            .await?;
+        let page: Paginated<ForcedExitRequest, ForcedExitRequestId> =
+            deserialize_response_result(response)?;
+        assert!(page.list.is_empty());
+        assert_eq!(page.pagination.count, 0);
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn forced_exit_requests_api_keys() -> anyhow::Result<()> {
+        let cfg = get_test_config();
+
+        let key = hex::encode(zksync_crypto::rand::random::<[u8; 32]>());
+        let revoked_key = hex::encode(zksync_crypto::rand::random::<[u8; 32]>());
+        let api_key = {
+            let mut storage = cfg.pool.access_storage().await?;
+            let mut fe_schema = storage.forced_exit_requests_schema();
+            let api_key = fe_schema
+                .store_api_key(SaveForcedExitRequestsApiKeyQuery {
+                    label: "partner".to_owned(),
+                    key_hash: api_key_hash(&key),
+                    max_tokens_per_request: Some(5),
+                    max_requests_per_hour: Some(2),
+                    created_at: Utc::now(),
+                })
+                .await?;
+            let revoked_api_key = fe_schema
+                .store_api_key(SaveForcedExitRequestsApiKeyQuery {
+                    label: "former partner".to_owned(),
+                    key_hash: api_key_hash(&revoked_key),
+                    max_tokens_per_request: Some(5),
+                    max_requests_per_hour: None,
+                    created_at: Utc::now(),
+                })
+                .await?;
+            fe_schema
+                .revoke_api_key(revoked_api_key.id, Utc::now())
+                .await?;
+            api_key
+        };
+
+        let (client, server) = cfg.start_server_with_scope(
+            String::from("api/forced_exit_requests"),
+            |cfg| {
+                api_scope(
+                    cfg.pool.clone(),
+                    &cfg.config.forced_exit_requests,
+                    cfg.config.contracts.forced_exit_addr,
+                    Box::new(DummyForcedExitChecker {}),
+                    cfg.config.chain.eth.network,
+                )
+            },
+            Option::<SharedData>::None,
+        );
+
+        // The request exceeds the default limit of tokens, but not the limit of the key
+        let register_request = ForcedExitRegisterRequest {
+            target: Address::repeat_byte(0x27),
+            tokens: (0..4).map(TokenId).collect(),
+            price_in_wei: BigUint::from(PRICE_PER_TOKEN as u64 * 4),
+            metadata: None,
+            callback_url: None,
+        };
+        let response = client.create_forced_exit_request(&register_request).await?;
+        let error: Error = serde_json::from_value(response.error.unwrap())?;
+        assert_eq!(error.error_type, "forcedExitRequestError");
+
+        let response = client
+            .create_forced_exit_request_with_api_key(&register_request, &key)
+            .await?;
+        let request: ForcedExitRequest = deserialize_response_result(response)?;
+        assert_eq!(request.tokens.len(), 4);
+
+        // The request is attributed to the key
+        let attributed_key = cfg
+            .pool
+            .access_storage()
+            .await?
+            .forced_exit_requests_schema()
+            .get_request_api_key_id(request.id)
+            .await?;
+        assert_eq!(attributed_key, Some(api_key.id));
+
+        // The hourly limit of the key is applied instead of the default one
+        let response = client
+            .create_forced_exit_request_with_api_key(&register_request, &key)
+            .await?;
+        assert!(matches!(response.status, ResultStatus::Success));
+        let response = client
+            .create_forced_exit_request_with_api_key(&register_request, &key)
+            .await?;
+        let error: Error = serde_json::from_value(response.error.unwrap())?;
+        assert_eq!(error.code, ErrorCode::ForcedExitRequestsRateLimitExceeded);
+
+        // Neither the revoked nor the unknown keys are accepted
+        for key in &[revoked_key, "unknown".to_owned()] {
+            let response = client
+                .create_forced_exit_request_with_api_key(&register_request, key)
+                .await?;
+            let error: Error = serde_json::from_value(response.error.unwrap())?;
+            assert_eq!(error.code, ErrorCode::InvalidApiKey);
+        }
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn forced_exit_requests_by_payment() -> anyhow::Result<()> {
+        let cfg = get_test_config();
+        let (client, server) = cfg.start_server_with_scope(
+            String::from("api/forced_exit_requests"),
+            |cfg| {
+                api_scope(
+                    cfg.pool.clone(),
+                    &cfg.config.forced_exit_requests,
+                    cfg.config.contracts.forced_exit_addr,
+                    Box::new(DummyForcedExitChecker {}),
+                    cfg.config.chain.eth.network,
+                )
+            },
+            Option::<SharedData>::None,
+        );
+
+        let mut requests = Vec::new();
+        for _ in 0..2 {
+            let response = client
+                .create_forced_exit_request(&ForcedExitRegisterRequest {
+                    target: Address::repeat_byte(0x29),
+                    tokens: vec![TokenId(0)],
+                    price_in_wei: BigUint::from(PRICE_PER_TOKEN as u64),
+                    metadata: None,
+                    callback_url: None,
+                })
+                .await?;
+            let request: ForcedExitRequest = deserialize_response_result(response)?;
+            requests.push(request);
+        }
+
+        // A single transaction has paid for both requests
+        let split_hash = H256::random();
+        let unmatched_hash = H256::random();
+        let payment = |eth_tx_hash: H256| ForcedExitPayment {
+            amount: BigUint::from(PRICE_PER_TOKEN as u64),
+            request_id: None,
+            block_number: 10,
+            eth_tx_hash: Some(eth_tx_hash),
+            payer: None,
+            received_at: Utc::now(),
+            source: PaymentSource::L1Event,
+        };
+        {
+            let mut storage = cfg.pool.access_storage().await?;
+            let mut fe_schema = storage.forced_exit_requests_schema();
+            for request in &requests {
+                fe_schema
+                    .set_match_scheme(request.id, PaymentMatchScheme::AmountDigits, Utc::now())
+                    .await?;
+                fe_schema
+                    .store_payment_match(request.id, split_hash, Utc::now())
+                    .await?;
+            }
+            fe_schema
+                .store_unmatched_payment(
+                    &payment(unmatched_hash),
+                    UnmatchedPaymentReason::AddressRetired,
+                )
+                .await?;
+        }
+
+        let response = client.forced_exit_requests_by_payment(split_hash).await?;
+        let lookup: ForcedExitPaymentLookup = deserialize_response_result(response)?;
+        match lookup {
+            ForcedExitPaymentLookup::Matched { requests: matched } => {
+                let ids: Vec<_> = matched.iter().map(|details| details.request.id).collect();
+                assert_eq!(ids, vec![requests[0].id, requests[1].id]);
+                assert!(matched.iter().all(|details| details.queue.is_some()));
+            }
+            lookup => panic!("Unexpected lookup result: {:?}", lookup),
+        }
+
+        let response = client
+            .forced_exit_requests_by_payment(unmatched_hash)
+            .await?;
+        let lookup: ForcedExitPaymentLookup = deserialize_response_result(response)?;
+        assert_eq!(
+            lookup,
+            ForcedExitPaymentLookup::Unmatched {
+                reasons: vec![UnmatchedPaymentReason::AddressRetired]
+            }
+        );
+
+        let response = client
+            .forced_exit_requests_by_payment(H256::random())
+            .await?;
+        let error: Error = serde_json::from_value(response.error.unwrap())?;
+        assert_eq!(error.code, ErrorCode::ForcedExitPaymentNotFound);
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn forced_exit_request_ledger() -> anyhow::Result<()> {
+        let cfg = get_test_config();
+        let (client, server) = cfg.start_server_with_scope(
+            String::from("api/forced_exit_requests"),
+            |cfg| {
+                api_scope(
+                    cfg.pool.clone(),
+                    &cfg.config.forced_exit_requests,
+                    cfg.config.contracts.forced_exit_addr,
+                    Box::new(DummyForcedExitChecker {}),
+                    cfg.config.chain.eth.network,
+                )
+            },
+            Option::<SharedData>::None,
+        );
+
+        let response = client
+            .create_forced_exit_request(&ForcedExitRegisterRequest {
+                target: Address::repeat_byte(0x2a),
+                tokens: vec![TokenId(0)],
+                price_in_wei: BigUint::from(PRICE_PER_TOKEN as u64),
+                metadata: None,
+                callback_url: None,
+            })
+            .await?;
+        let request: ForcedExitRequest = deserialize_response_result(response)?;
+
+        // Nothing has been paid yet
+        let response = client.forced_exit_request_ledger(request.public_id).await?;
+        let ledger: ForcedExitRequestLedger = deserialize_response_result(response)?;
+        assert!(ledger.entries.is_empty());
+        assert_eq!(ledger.balance, "0");
+        assert!(ledger.balanced);
+
+        let eth_tx_hash = H256::random();
+        let paid_amount = BigUint::from_str(&request.pay_exactly)?;
+        {
+            let mut storage = cfg.pool.access_storage().await?;
+            let mut fe_schema = storage.forced_exit_requests_schema();
+            fe_schema
+                .store_payment(&ForcedExitPayment {
+                    amount: paid_amount.clone(),
+                    request_id: Some(request.public_id),
+                    block_number: 10,
+                    eth_tx_hash: Some(eth_tx_hash),
+                    payer: None,
+                    received_at: Utc::now(),
+                    source: PaymentSource::L1Event,
+                })
+                .await?;
+            fe_schema
+                .set_match_scheme(request.id, PaymentMatchScheme::AmountDigits, Utc::now())
+                .await?;
+            fe_schema.set_paid_amount(request.id, &paid_amount).await?;
+            fe_schema
+                .store_payment_match(request.id, eth_tx_hash, Utc::now())
+                .await?;
+        }
+
+        // The payment is held until the transactions are sent
+        let response = client.forced_exit_request_ledger(request.public_id).await?;
+        let ledger: ForcedExitRequestLedger = deserialize_response_result(response)?;
+        assert_eq!(ledger.entries.len(), 1);
+        assert_eq!(ledger.entries[0].eth_tx_hash, Some(eth_tx_hash));
+        assert_eq!(ledger.credited, paid_amount);
+        assert_eq!(ledger.balance, paid_amount.to_string());
+
+        let response = client.forced_exit_request_ledger(-1).await?;
+        let error: Error = serde_json::from_value(response.error.unwrap())?;
+        assert_eq!(error.code, ErrorCode::ForcedExitRequestNotFound);
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn forced_exit_requests_expected_payments() -> anyhow::Result<()> {
+        let cfg = get_test_config();
+
+        let key = hex::encode(zksync_crypto::rand::random::<[u8; 32]>());
+        let other_key = hex::encode(zksync_crypto::rand::random::<[u8; 32]>());
+        {
+            let mut storage = cfg.pool.access_storage().await?;
+            let mut fe_schema = storage.forced_exit_requests_schema();
+            for (label, key) in [("expecting partner", &key), ("other partner", &other_key)] {
+                fe_schema
+                    .store_api_key(SaveForcedExitRequestsApiKeyQuery {
+                        label: label.to_owned(),
+                        key_hash: api_key_hash(key),
+                        max_tokens_per_request: None,
+                        max_requests_per_hour: None,
+                        created_at: Utc::now(),
+                    })
+                    .await?;
+            }
+        }
+
+        let (client, server) = cfg.start_server_with_scope(
+            String::from("api/forced_exit_requests"),
+            |cfg| {
+                api_scope(
+                    cfg.pool.clone(),
+                    &cfg.config.forced_exit_requests,
+                    cfg.config.contracts.forced_exit_addr,
+                    Box::new(DummyForcedExitChecker {}),
+                    cfg.config.chain.eth.network,
+                )
+            },
+            Option::<SharedData>::None,
+        );
+
+        let register_request = ForcedExitRegisterRequest {
+            target: Address::repeat_byte(0x2a),
+            tokens: vec![TokenId(0)],
+            price_in_wei: BigUint::from(PRICE_PER_TOKEN as u64),
+            metadata: None,
+            callback_url: None,
+        };
+        let mut requests = Vec::new();
+        for _ in 0..2 {
+            let response = client
+                .create_forced_exit_request_with_api_key(&register_request, &key)
+                .await?;
+            let request: ForcedExitRequest = deserialize_response_result(response)?;
+            requests.push(request);
+        }
+
+        let eth_tx_hash = H256::random();
+        let response = client
+            .register_expected_forced_exit_payment(requests[0].public_id, eth_tx_hash, &key)
+            .await?;
+        let request: ForcedExitRequest = deserialize_response_result(response)?;
+        assert_eq!(request.id, requests[0].id);
+
+        let response = client
+            .forced_exit_request_by_id(requests[0].public_id)
+            .await?;
+        let details: ForcedExitRequestDetails = deserialize_response_result(response)?;
+        assert_eq!(details.expected_payments.len(), 1);
+        assert_eq!(details.expected_payments[0].eth_tx_hash, eth_tx_hash);
+
+        // Only the partner the request was created by may register its payments
+        let response = client
+            .register_expected_forced_exit_payment(
+                requests[0].public_id,
+                H256::random(),
+                &other_key,
+            )
+            .await?;
+        let error: Error = serde_json::from_value(response.error.unwrap())?;
+        assert_eq!(error.code, ErrorCode::InvalidApiKey);
+
+        // The transaction can not pay for two requests
+        let response = client
+            .register_expected_forced_exit_payment(requests[1].public_id, eth_tx_hash, &key)
+            .await?;
+        let error: Error = serde_json::from_value(response.error.unwrap())?;
+        assert_eq!(error.code, ErrorCode::InvalidForcedExitRequest);
+
+        server.stop().await;
+        Ok(())
+    }
+}
@@ -18,7 +18,7 @@ use tokio::task::JoinHandle;
 use zksync_config::ZkSyncConfig;
 use zksync_mempool::MempoolTransactionRequest;
 
-mod forced_exit_requests;
+pub(crate) mod forced_exit_requests;
 mod helpers;
 pub mod network_status;
 mod v01;
@@ -43,6 +43,18 @@ async fn start_server(
                 .forced_exit_minimum_account_age_secs,
             &api_v01.config.forced_exit_requests,
             api_v01.config.contracts.forced_exit_addr,
+            api_v01.config.chain.eth.network,
+        );
+        let forced_exit_requests_admin_scope = forced_exit_requests::admin_scope(
+            api_v01.main_database_connection_pool.clone(),
+            api_v01
+                .config
+                .api
+                .common
+                .forced_exit_minimum_account_age_secs,
+            &api_v01.config.forced_exit_requests,
+            api_v01.config.contracts.forced_exit_addr,
+            api_v01.config.api.admin.secret_auth.clone(),
         );
 
         let api_v02_scope = {
@@ -67,6 +79,7 @@ async fn start_server(
             )
             .service(api_v01.into_scope())
             .service(forced_exit_requests_api_scope)
+            .service(forced_exit_requests_admin_scope)
             .service(api_v02_scope)
             // Endpoint needed for js isReachable
             .route(
@@ -11,7 +11,12 @@ use zksync_api_types::v02::pagination::{UnknownFromParameter, MAX_LIMIT};
 use zksync_crypto::params::MIN_NFT_TOKEN_ID;
 
 // Local uses
-use crate::{api_server::tx_sender::SubmitError, fee_ticker::PriceError};
+use crate::{
+    api_server::{
+        rest::forced_exit_requests::error::ForcedExitRequestsError, tx_sender::SubmitError,
+    },
+    fee_ticker::PriceError,
+};
 
 #[derive(Serialize_repr, Debug, Deserialize_repr, Clone, PartialEq)]
 #[repr(u16)]
@@ -27,6 +32,16 @@ pub enum ErrorCode {
     PaginationLimitTooBig = 206,
     QueryDeserializationError = 207,
     InvalidNFTTokenId = 208,
+    InvalidForcedExitRequest = 209,
+    ForcedExitRequestNotFound = 210,
+    ForcedExitRequestsDisabled = 211,
+    InvalidApiKey = 212,
+    ForcedExitRequestsRateLimitExceeded = 213,
+    ForcedExitRequestNotPending = 214,
+    ForcedExitRequestsIdSpaceExhausted = 215,
+    ForcedExitPaymentNotFound = 216,
+    ForcedExitRequestsTargetLimitExceeded = 217,
+    ForcedExitFeatureDisabled = 218,
     StorageError = 300,
     TokenNotFound = 500,
     ExternalApiError = 501,
@@ -220,6 +235,53 @@ impl ApiError for PriceError {
     }
 }
 
+impl ApiError for ForcedExitRequestsError {
+    fn error_type(&self) -> String {
+        match self {
+            Self::Submit(err) => err.error_type(),
+            Self::Storage(_) => String::from("storageError"),
+            _ => String::from("forcedExitRequestError"),
+        }
+    }
+
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Disabled => ErrorCode::ForcedExitRequestsDisabled,
+            Self::FeatureDisabled(_) => ErrorCode::ForcedExitFeatureDisabled,
+            Self::TooManyTokens
+            | Self::NoTokens
+            | Self::IncorrectPrice
+            | Self::PaymentExpectedForAnotherRequest
+            | Self::TokenNotAllowed(_)
+            | Self::NftNotForceable(_)
+            | Self::MetadataTooLong(_)
+            | Self::InvalidCallbackUrl
+            | Self::DeliveryNotFailed
+            | Self::InvalidConfigCandidate(_)
+            | Self::InvalidNote
+            | Self::RequestAlreadyFulfilled
+            | Self::RequestNotPaid
+            | Self::RequestCancelled
+            | Self::RequestExpired
+            | Self::RefundNotAwaitingApproval => ErrorCode::InvalidForcedExitRequest,
+            Self::TokenNotFound => ErrorCode::TokenNotFound,
+            Self::RequestNotFound
+            | Self::RetryNotFound
+            | Self::RefundNotFound
+            | Self::DeliveryNotFound => ErrorCode::ForcedExitRequestNotFound,
+            Self::PaymentNotFound => ErrorCode::ForcedExitPaymentNotFound,
+            Self::RequestNotPending => ErrorCode::ForcedExitRequestNotPending,
+            Self::PaginationLimitTooBig => ErrorCode::PaginationLimitTooBig,
+            Self::InvalidApiKey | Self::ApiKeyMismatch => ErrorCode::InvalidApiKey,
+            Self::RateLimitExceeded => ErrorCode::ForcedExitRequestsRateLimitExceeded,
+            Self::IdSpaceExhausted => ErrorCode::ForcedExitRequestsIdSpaceExhausted,
+            Self::TooManyActiveRequests(_) => ErrorCode::ForcedExitRequestsTargetLimitExceeded,
+            Self::Submit(err) => err.code(),
+            Self::Storage(_) => ErrorCode::StorageError,
+        }
+    }
+}
+
 impl ApiError for UnknownFromParameter {
     fn error_type(&self) -> String {
         String::from("invalidDataError")
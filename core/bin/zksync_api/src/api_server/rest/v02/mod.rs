@@ -19,7 +19,7 @@ pub mod error;
 mod fee;
 mod paginate_impl;
 mod paginate_trait;
-mod response;
+pub mod response;
 mod status;
 #[cfg(test)]
 pub mod test_utils;
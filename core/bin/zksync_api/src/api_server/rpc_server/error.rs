@@ -3,7 +3,9 @@ use jsonrpc_core::ErrorCode;
 use zksync_types::tx::error::TxAddError;
 // Workspace uses
 // Local uses
-use crate::api_server::tx_sender::SubmitError;
+use crate::api_server::{
+    rest::forced_exit_requests::error::ForcedExitRequestsError, tx_sender::SubmitError,
+};
 
 #[derive(Debug, Clone, Copy)]
 pub enum RpcErrorCodes {
@@ -22,6 +24,16 @@ pub enum RpcErrorCodes {
     OperationsLimitReached = 302,
     UnsupportedFastProcessing = 303,
     Toggle2FA = 304,
+
+    ForcedExitRequestsDisabled = 400,
+    InvalidForcedExitRequest = 401,
+    ForcedExitRequestNotFound = 402,
+    ForcedExitRequestsRateLimitExceeded = 403,
+    ForcedExitRequestNotPending = 404,
+    ForcedExitRequestsIdSpaceExhausted = 405,
+    ForcedExitPaymentNotFound = 406,
+    ForcedExitRequestsTargetLimitExceeded = 407,
+    ForcedExitFeatureDisabled = 408,
 }
 
 impl From<TxAddError> for RpcErrorCodes {
@@ -110,3 +122,61 @@ impl From<SubmitError> for jsonrpc_core::Error {
         }
     }
 }
+
+impl From<ForcedExitRequestsError> for jsonrpc_core::Error {
+    fn from(inner: ForcedExitRequestsError) -> Self {
+        let code = match inner {
+            ForcedExitRequestsError::Submit(err) => return err.into(),
+            ForcedExitRequestsError::PaginationLimitTooBig
+            | ForcedExitRequestsError::InvalidNote
+            | ForcedExitRequestsError::InvalidApiKey
+            | ForcedExitRequestsError::ApiKeyMismatch => {
+                return Self::invalid_params(inner.to_string())
+            }
+            ForcedExitRequestsError::Storage(_) => return Self::internal_error(),
+            ForcedExitRequestsError::Disabled => RpcErrorCodes::ForcedExitRequestsDisabled,
+            ForcedExitRequestsError::FeatureDisabled(_) => RpcErrorCodes::ForcedExitFeatureDisabled,
+            ForcedExitRequestsError::RequestNotFound
+            | ForcedExitRequestsError::RetryNotFound
+            | ForcedExitRequestsError::RefundNotFound
+            | ForcedExitRequestsError::DeliveryNotFound => RpcErrorCodes::ForcedExitRequestNotFound,
+            ForcedExitRequestsError::PaymentNotFound => RpcErrorCodes::ForcedExitPaymentNotFound,
+            ForcedExitRequestsError::RequestNotPending => {
+                RpcErrorCodes::ForcedExitRequestNotPending
+            }
+            ForcedExitRequestsError::RateLimitExceeded => {
+                RpcErrorCodes::ForcedExitRequestsRateLimitExceeded
+            }
+            ForcedExitRequestsError::IdSpaceExhausted => {
+                RpcErrorCodes::ForcedExitRequestsIdSpaceExhausted
+            }
+            ForcedExitRequestsError::TooManyActiveRequests(_) => {
+                RpcErrorCodes::ForcedExitRequestsTargetLimitExceeded
+            }
+            ForcedExitRequestsError::TooManyTokens
+            | ForcedExitRequestsError::NoTokens
+            | ForcedExitRequestsError::IncorrectPrice
+            | ForcedExitRequestsError::TokenNotFound
+            | ForcedExitRequestsError::TokenNotAllowed(_)
+            | ForcedExitRequestsError::NftNotForceable(_)
+            | ForcedExitRequestsError::MetadataTooLong(_)
+            | ForcedExitRequestsError::InvalidCallbackUrl
+            | ForcedExitRequestsError::DeliveryNotFailed
+            | ForcedExitRequestsError::InvalidConfigCandidate(_)
+            | ForcedExitRequestsError::PaymentExpectedForAnotherRequest
+            | ForcedExitRequestsError::RequestAlreadyFulfilled
+            | ForcedExitRequestsError::RequestNotPaid
+            | ForcedExitRequestsError::RequestCancelled
+            | ForcedExitRequestsError::RequestExpired
+            | ForcedExitRequestsError::RefundNotAwaitingApproval => {
+                RpcErrorCodes::InvalidForcedExitRequest
+            }
+        };
+
+        Self {
+            code: code.into(),
+            message: inner.to_string(),
+            data: None,
+        }
+    }
+}
@@ -1,5 +1,5 @@
 // Built-in uses
-use std::time::Instant;
+use std::{sync::Arc, time::Instant};
 
 // External uses
 use futures::channel::mpsc;
@@ -8,7 +8,10 @@ use jsonrpc_http_server::ServerBuilder;
 use tokio::task::JoinHandle;
 
 // Workspace uses
-use zksync_config::configs::api::{CommonApiConfig, JsonRpcConfig, TokenConfig};
+use zksync_config::{
+    configs::api::{CommonApiConfig, JsonRpcConfig, TokenConfig},
+    ForcedExitRequestsConfig,
+};
 use zksync_storage::{
     chain::{
         block::records::StorageBlockDetails, operations::records::StoredExecutedPriorityOperation,
@@ -20,7 +23,11 @@ use zksync_types::{tx::TxHash, Address, BlockNumber};
 use zksync_utils::panic_notify::{spawn_panic_handler, ThreadPanicNotify};
 
 // Local uses
-use crate::{signature_checker::VerifySignatureRequest, utils::shared_lru_cache::AsyncLruCache};
+use crate::{
+    api_server::rest::forced_exit_requests::{self, service::ForcedExitRequestsService},
+    signature_checker::VerifySignatureRequest,
+    utils::shared_lru_cache::AsyncLruCache,
+};
 
 pub mod error;
 mod ip_insert_middleware;
@@ -44,9 +51,11 @@ pub struct RpcApp {
     pub confirmations_for_eth_event: u64,
 
     tx_sender: TxSender,
+    forced_exit_requests: Arc<ForcedExitRequestsService>,
 }
 
 impl RpcApp {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         connection_pool: ConnectionPool,
         sign_verify_request_sender: mpsc::Sender<VerifySignatureRequest>,
@@ -55,6 +64,7 @@ impl RpcApp {
         token_config: &TokenConfig,
         confirmations_for_eth_event: u64,
         mempool_tx_sender: mpsc::Sender<MempoolTransactionRequest>,
+        forced_exit_requests: ForcedExitRequestsService,
     ) -> Self {
         let api_requests_caches_size = config.caches_size;
 
@@ -75,6 +85,7 @@ impl RpcApp {
             confirmations_for_eth_event,
 
             tx_sender,
+            forced_exit_requests: Arc::new(forced_exit_requests),
         }
     }
 
@@ -266,8 +277,18 @@ pub fn start_rpc_server(
     token_config: &TokenConfig,
     mempool_tx_sender: mpsc::Sender<MempoolTransactionRequest>,
     confirmations_for_eth_event: u64,
+    main_connection_pool: ConnectionPool,
+    forced_exit_requests_config: &ForcedExitRequestsConfig,
+    forced_exit_contract: Address,
 ) -> JoinHandle<()> {
     let addr = config.http_bind_addr();
+    // Forced exit requests are stored, so the main database connection is required
+    let forced_exit_requests = forced_exit_requests::create_service(
+        main_connection_pool,
+        common_api_config.forced_exit_minimum_account_age_secs,
+        forced_exit_requests_config,
+        forced_exit_contract,
+    );
     let rpc_app = RpcApp::new(
         connection_pool,
         sign_verify_request_sender,
@@ -276,6 +297,7 @@ pub fn start_rpc_server(
         token_config,
         confirmations_for_eth_event,
         mempool_tx_sender,
+        forced_exit_requests,
     );
 
     let (handler, panic_sender) = spawn_panic_handler();
@@ -296,8 +318,34 @@ pub fn start_rpc_server(
 
 #[cfg(test)]
 mod test {
+    use std::str::FromStr;
+
+    use jsonrpc_core::{ErrorCode, Params};
+    use jsonrpc_core_client::{RawClient, RpcError};
     use serde::{Deserialize, Serialize};
-    use zksync_types::TxFeeTypes;
+    use serde_json::{json, Value};
+
+    use zksync_api_client::rest::forced_exit_requests::{
+        ForcedExitRegisterRequest, ForcedExitRequestQuote,
+    };
+    use zksync_api_types::v02::pagination::{
+        ApiEither, Paginated, PaginationDirection, PaginationQuery,
+    };
+    use zksync_config::ZkSyncConfig;
+    use zksync_types::{
+        forced_exit_requests::{ForcedExitRequest, ForcedExitRequestId},
+        TokenId, TxFeeTypes,
+    };
+
+    use super::*;
+    use crate::api_server::{
+        forced_exit_checker::DummyForcedExitChecker,
+        rest::v02::{
+            test_utils::{dummy_fee_ticker, dummy_sign_verifier, TestServerConfig},
+            SharedData,
+        },
+        rpc_server::error::RpcErrorCodes,
+    };
 
     #[test]
     fn tx_fee_type_serialization() {
@@ -327,4 +375,180 @@ mod test {
             assert_eq!(query, de);
         }
     }
+
+    #[actix_rt::test]
+    #[cfg_attr(
+        not(feature = "api_test"),
+        ignore = "Use `zk test rust-api` command to perform this test"
+    )]
+    async fn forced_exit_requests_methods() -> anyhow::Result<()> {
+        let cfg = TestServerConfig::default();
+        let cfg = TestServerConfig {
+            config: ZkSyncConfig {
+                forced_exit_requests: ForcedExitRequestsConfig {
+                    enabled: true,
+                    max_tokens_per_request: 3,
+                    ..ForcedExitRequestsConfig::from_env()
+                },
+                ..cfg.config
+            },
+            pool: cfg.pool,
+        };
+
+        let (mempool_tx_sender, _mempool_tx_receiver) = mpsc::channel(1);
+        let rpc_app = RpcApp::new(
+            cfg.pool.clone(),
+            dummy_sign_verifier(),
+            dummy_fee_ticker(&[], None),
+            &cfg.config.api.common,
+            &cfg.config.api.token_config,
+            0,
+            mempool_tx_sender,
+            ForcedExitRequestsService::new(
+                cfg.pool.clone(),
+                &cfg.config.forced_exit_requests,
+                cfg.config.contracts.forced_exit_addr,
+                Box::new(DummyForcedExitChecker),
+            ),
+        );
+        let mut io = IoHandler::new();
+        rpc_app.extend(&mut io);
+        let (rpc_client, rpc_server) =
+            jsonrpc_core_client::transports::local::connect::<RawClient, _, _>(io.clone());
+        actix_rt::spawn(async move {
+            rpc_server.await.ok();
+        });
+
+        let (rest_client, rest_server) = cfg.start_server_with_scope(
+            String::from("api/forced_exit_requests"),
+            |cfg| {
+                forced_exit_requests::v02::api_scope(
+                    cfg.pool.clone(),
+                    &cfg.config.forced_exit_requests,
+                    cfg.config.contracts.forced_exit_addr,
+                    Box::new(DummyForcedExitChecker),
+                    cfg.config.chain.eth.network,
+                )
+            },
+            Option::<SharedData>::None,
+        );
+
+        let mut status = rpc_client
+            .call_method("forced_exit_requests_status", Params::Array(vec![]))
+            .await?;
+        let mut rest_status = rest_client
+            .forced_exit_requests_status_v02()
+            .await?
+            .result
+            .expect("No status in the response");
+        // The requests may be created by the other tests in between
+        status["idSpace"]["activeRequests"].take();
+        rest_status["idSpace"]["activeRequests"].take();
+        assert_eq!(status, rest_status);
+
+        let quote = rpc_client
+            .call_method(
+                "forced_exit_requests_quote",
+                Params::Array(vec![json!({ "tokensCount": 2 })]),
+            )
+            .await?;
+        let rest_quote = rest_client.forced_exit_request_quote(2).await?;
+        assert_eq!(Some(quote.clone()), rest_quote.result);
+        let quote: ForcedExitRequestQuote = serde_json::from_value(quote)?;
+
+        let err = rpc_client
+            .call_method(
+                "forced_exit_requests_quote",
+                Params::Array(vec![json!({ "tokensCount": 4 })]),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            RpcError::JsonRpcError(err)
+                if err.code == ErrorCode::from(RpcErrorCodes::InvalidForcedExitRequest)
+        ));
+
+        let target = Address::repeat_byte(0x34);
+        let mut requests = Vec::new();
+        for _ in 0..3 {
+            let register_request = ForcedExitRegisterRequest {
+                target,
+                tokens: vec![TokenId(0), TokenId(1)],
+                price_in_wei: quote.price_in_wei.clone(),
+                metadata: None,
+                callback_url: None,
+            };
+            let request = rpc_client
+                .call_method(
+                    "forced_exit_requests_create",
+                    Params::Array(vec![serde_json::to_value(&register_request)?]),
+                )
+                .await?;
+            let request: ForcedExitRequest = serde_json::from_value(request)?;
+            assert_eq!(request.target, target);
+            requests.push(request);
+        }
+
+        let extended = rpc_client
+            .call_method(
+                "forced_exit_requests_extend",
+                Params::Array(vec![serde_json::to_value(requests[0].id)?]),
+            )
+            .await?;
+        let extended: ForcedExitRequest = serde_json::from_value(extended)?;
+        assert!(extended.valid_until >= requests[0].valid_until);
+
+        for (from, direction) in &[
+            ("latest".to_owned(), PaginationDirection::Older),
+            (requests[0].id.to_string(), PaginationDirection::Newer),
+        ] {
+            let list = rpc_client
+                .call_method(
+                    "forced_exit_requests_list",
+                    Params::Array(vec![serde_json::to_value(ForcedExitRequestsListParams {
+                        target,
+                        from: from.clone(),
+                        limit: 2,
+                        direction: *direction,
+                    })?]),
+                )
+                .await?;
+            let rest_list = rest_client
+                .forced_exit_requests_pagination(
+                    target,
+                    &PaginationQuery {
+                        from: ApiEither::from_str(from)?,
+                        limit: 2,
+                        direction: *direction,
+                    },
+                )
+                .await?;
+            assert_eq!(Some(list.clone()), rest_list.result);
+
+            let list: Paginated<ForcedExitRequest, ForcedExitRequestId> =
+                serde_json::from_value(list)?;
+            assert_eq!(list.list.len(), 2);
+            assert_eq!(list.pagination.count, 3);
+        }
+
+        // Several methods can be called within a single batch
+        let batch = json!([
+            { "jsonrpc": "2.0", "id": 1, "method": "forced_exit_requests_status", "params": [] },
+            {
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "forced_exit_requests_quote",
+                "params": [{ "tokensCount": 2 }]
+            },
+        ]);
+        let response = io.handle_request(&batch.to_string()).await.unwrap();
+        let response: Vec<Value> = serde_json::from_str(&response)?;
+        assert_eq!(response.len(), 2);
+        assert_eq!(Some(&response[0]["result"]), rest_status.result.as_ref());
+        assert_eq!(Some(&response[1]["result"]), rest_quote.result.as_ref());
+
+        rest_server.stop().await;
+        Ok(())
+    }
 }
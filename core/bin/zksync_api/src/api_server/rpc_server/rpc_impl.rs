@@ -1,12 +1,17 @@
 use std::collections::HashMap;
+use std::str::FromStr;
 use std::time::Instant;
 // External uses
 use bigdecimal::BigDecimal;
 use jsonrpc_core::{Error, Result};
 // Workspace uses
+use zksync_api_client::rest::forced_exit_requests::{
+    ForcedExitRegisterRequest, ForcedExitRequestStatus,
+};
 use zksync_api_types::{
     v02::{
         fee::ApiTxFeeTypes,
+        pagination::{ApiEither, ForcedExitRequestsQuery, Paginated, PaginationQuery},
         token::ApiNFT,
         transaction::{Toggle2FA, Toggle2FAResponse},
     },
@@ -14,6 +19,7 @@ use zksync_api_types::{
 };
 use zksync_crypto::params::MIN_NFT_TOKEN_ID;
 use zksync_types::{
+    forced_exit_requests::{ForcedExitRequest, ForcedExitRequestId},
     tx::{EthBatchSignatures, TxEthSignatureVariant, TxHash},
     AccountId, Address, Fee, Token, TokenId, TokenLike, TotalFee, TxFeeTypes, ZkSyncTx,
 };
@@ -451,4 +457,72 @@ impl RpcApp {
         metrics::histogram!("api", start.elapsed(), "type" => "rpc", "endpoint_name" => "get_nft_id_by_tx_hash");
         Ok(response)
     }
+
+    pub async fn _impl_forced_exit_requests_status(self) -> Result<ForcedExitRequestStatus> {
+        let start = Instant::now();
+        let response = self
+            .forced_exit_requests
+            .get_status()
+            .await
+            .map_err(Error::from);
+
+        metrics::histogram!("api", start.elapsed(), "type" => "rpc", "endpoint_name" => "forced_exit_requests_status");
+        response
+    }
+
+    pub async fn _impl_forced_exit_requests_create(
+        self,
+        request: ForcedExitRegisterRequest,
+    ) -> Result<ForcedExitRequest> {
+        let start = Instant::now();
+        // The API keys of the partners are only accepted by the REST API
+        let response = self
+            .forced_exit_requests
+            .create_request(request, None)
+            .await
+            .map_err(Error::from);
+
+        metrics::histogram!("api", start.elapsed(), "type" => "rpc", "endpoint_name" => "forced_exit_requests_create");
+        response
+    }
+
+    pub async fn _impl_forced_exit_requests_extend(
+        self,
+        request_id: ForcedExitRequestId,
+    ) -> Result<ForcedExitRequest> {
+        let start = Instant::now();
+        let response = self
+            .forced_exit_requests
+            .extend(request_id)
+            .await
+            .map_err(Error::from);
+
+        metrics::histogram!("api", start.elapsed(), "type" => "rpc", "endpoint_name" => "forced_exit_requests_extend");
+        response
+    }
+
+    pub async fn _impl_forced_exit_requests_list(
+        self,
+        params: ForcedExitRequestsListParams,
+    ) -> Result<Paginated<ForcedExitRequest, ForcedExitRequestId>> {
+        let start = Instant::now();
+        let request_id = ApiEither::from_str(&params.from)
+            .map_err(|err| Error::invalid_params(err.to_string()))?;
+        let query = PaginationQuery {
+            from: ForcedExitRequestsQuery {
+                target: params.target,
+                request_id,
+            },
+            limit: params.limit,
+            direction: params.direction,
+        };
+        let response = self
+            .forced_exit_requests
+            .list(query)
+            .await
+            .map_err(Error::from);
+
+        metrics::histogram!("api", start.elapsed(), "type" => "rpc", "endpoint_name" => "forced_exit_requests_list");
+        response
+    }
 }
@@ -5,9 +5,14 @@ use jsonrpc_core::{BoxFuture, Result};
 use jsonrpc_derive::rpc;
 
 // Workspace uses
+use zksync_api_client::rest::forced_exit_requests::{
+    ForcedExitQuoteQuery, ForcedExitRegisterRequest, ForcedExitRequestQuote,
+    ForcedExitRequestStatus,
+};
 use zksync_api_types::{
     v02::{
         fee::ApiTxFeeTypes,
+        pagination::Paginated,
         token::ApiNFT,
         transaction::{Toggle2FA, Toggle2FAResponse},
     },
@@ -15,6 +20,7 @@ use zksync_api_types::{
 };
 use zksync_crypto::params::ZKSYNC_VERSION;
 use zksync_types::{
+    forced_exit_requests::{ForcedExitRequest, ForcedExitRequestId},
     tx::{EthBatchSignatures, TxEthSignatureVariant, TxHash},
     AccountId, Address, Fee, Token, TokenId, TokenLike, TotalFee, ZkSyncTx,
 };
@@ -110,6 +116,42 @@ pub trait Rpc {
 
     #[rpc(name = "get_nft_id_by_tx_hash", returns = "Option<TokenId>")]
     fn get_nft_id_by_tx_hash(&self, tx_hash: TxHash) -> BoxFutureResult<Option<TokenId>>;
+
+    #[rpc(
+        name = "forced_exit_requests_status",
+        returns = "ForcedExitRequestStatus"
+    )]
+    fn forced_exit_requests_status(&self) -> BoxFutureResult<ForcedExitRequestStatus>;
+
+    #[rpc(
+        name = "forced_exit_requests_quote",
+        returns = "ForcedExitRequestQuote"
+    )]
+    fn forced_exit_requests_quote(
+        &self,
+        params: ForcedExitQuoteQuery,
+    ) -> Result<ForcedExitRequestQuote>;
+
+    #[rpc(name = "forced_exit_requests_create", returns = "ForcedExitRequest")]
+    fn forced_exit_requests_create(
+        &self,
+        request: ForcedExitRegisterRequest,
+    ) -> BoxFutureResult<ForcedExitRequest>;
+
+    #[rpc(name = "forced_exit_requests_extend", returns = "ForcedExitRequest")]
+    fn forced_exit_requests_extend(
+        &self,
+        request_id: ForcedExitRequestId,
+    ) -> BoxFutureResult<ForcedExitRequest>;
+
+    #[rpc(
+        name = "forced_exit_requests_list",
+        returns = "Paginated<ForcedExitRequest, ForcedExitRequestId>"
+    )]
+    fn forced_exit_requests_list(
+        &self,
+        params: ForcedExitRequestsListParams,
+    ) -> BoxFutureResult<Paginated<ForcedExitRequest, ForcedExitRequestId>>;
 }
 
 impl Rpc for RpcApp {
@@ -210,4 +252,36 @@ impl Rpc for RpcApp {
     fn get_nft_id_by_tx_hash(&self, tx_hash: TxHash) -> BoxFutureResult<Option<TokenId>> {
         spawn!(self._impl_get_nft_id_by_tx_hash(tx_hash))
     }
+
+    fn forced_exit_requests_status(&self) -> BoxFutureResult<ForcedExitRequestStatus> {
+        spawn!(self._impl_forced_exit_requests_status())
+    }
+
+    fn forced_exit_requests_quote(
+        &self,
+        params: ForcedExitQuoteQuery,
+    ) -> Result<ForcedExitRequestQuote> {
+        Ok(self.forced_exit_requests.quote(params.tokens_count)?)
+    }
+
+    fn forced_exit_requests_create(
+        &self,
+        request: ForcedExitRegisterRequest,
+    ) -> BoxFutureResult<ForcedExitRequest> {
+        spawn!(self._impl_forced_exit_requests_create(request))
+    }
+
+    fn forced_exit_requests_extend(
+        &self,
+        request_id: ForcedExitRequestId,
+    ) -> BoxFutureResult<ForcedExitRequest> {
+        spawn!(self._impl_forced_exit_requests_extend(request_id))
+    }
+
+    fn forced_exit_requests_list(
+        &self,
+        params: ForcedExitRequestsListParams,
+    ) -> BoxFutureResult<Paginated<ForcedExitRequest, ForcedExitRequestId>> {
+        spawn!(self._impl_forced_exit_requests_list(params))
+    }
 }
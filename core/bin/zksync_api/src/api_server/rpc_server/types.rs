@@ -7,6 +7,7 @@ use serde::{Deserialize, Serialize};
 // Workspace uses
 use zksync_api_types::v02::{
     account::{DepositingAccountBalances, EthAccountType},
+    pagination::PaginationDirection,
     token::NFT,
 };
 use zksync_crypto::params::{MIN_NFT_TOKEN_ID, NFT_TOKEN_ID_VAL};
@@ -132,3 +133,14 @@ pub struct RequestMetadata {
     /// The ip of the call origin
     pub ip: String,
 }
+
+/// Parameters of the `forced_exit_requests_list` method.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitRequestsListParams {
+    pub target: Address,
+    /// Id of the request or `latest`.
+    pub from: String,
+    pub limit: u32,
+    pub direction: PaginationDirection,
+}
@@ -11,7 +11,10 @@ use jsonrpc_pubsub::{typed::Subscriber, PubSubHandler, Session, SubscriptionId};
 use jsonrpc_ws_server::RequestContext;
 use tokio::task::JoinHandle;
 // Workspace uses
-use zksync_config::configs::api::{CommonApiConfig, JsonRpcConfig, TokenConfig};
+use zksync_config::{
+    configs::api::{CommonApiConfig, JsonRpcConfig, TokenConfig},
+    ForcedExitRequestsConfig,
+};
 use zksync_mempool::MempoolTransactionRequest;
 use zksync_storage::ConnectionPool;
 use zksync_types::{tx::TxHash, ActionType, Address};
@@ -20,6 +23,7 @@ use zksync_utils::panic_notify::{spawn_panic_handler, ThreadPanicNotify};
 use crate::fee_ticker::FeeTicker;
 use crate::{
     api_server::event_notify::{start_sub_notifier, EventNotifierRequest, EventSubscribeRequest},
+    api_server::rest::forced_exit_requests,
     api_server::rpc_server::types::{ETHOpInfoResp, ResponseAccountState, TransactionInfoResp},
     signature_checker::VerifySignatureRequest,
 };
@@ -188,6 +192,9 @@ pub fn start_ws_server(
     miniblock_iteration_interval: Duration,
     mempool_tx_sender: mpsc::Sender<MempoolTransactionRequest>,
     confirmations_for_eth_event: u64,
+    main_db_pool: ConnectionPool,
+    forced_exit_requests_config: &ForcedExitRequestsConfig,
+    forced_exit_contract: Address,
 ) -> JoinHandle<()> {
     let addr = config.ws_bind_addr();
 
@@ -209,6 +216,12 @@ pub fn start_ws_server(
         token_config,
         confirmations_for_eth_event,
         mempool_tx_sender,
+        forced_exit_requests::create_service(
+            main_db_pool,
+            common_config.forced_exit_minimum_account_age_secs,
+            forced_exit_requests_config,
+            forced_exit_contract,
+        ),
     );
 
     let (handler, panic_sender) = spawn_panic_handler();
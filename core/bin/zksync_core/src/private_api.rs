@@ -45,18 +45,29 @@ async fn status(data: web::Data<AppState>) -> actix_web::Result<HttpResponse> {
     // with multiple requests from other API nodes when the cache has been invalidated.
 
     let mut status = data.status_cache.write().await;
-    let main_database_status = data.connection_pool.access_storage().await.is_ok();
+    let main_database = data.connection_pool.access_storage().await;
+    let main_database_status = main_database.is_ok();
     let replica_database_status = data
         .read_only_connection_pool
         .access_storage()
         .await
         .is_ok();
     let eth_status = data.eth_client.block_number().await.is_ok();
+    let forced_exit_sender = match main_database {
+        Ok(mut storage) => storage
+            .forced_exit_requests_schema()
+            .load_sender_status()
+            .await
+            .ok()
+            .flatten(),
+        Err(_) => None,
+    };
 
     let response = CoreStatus {
         main_database_available: main_database_status,
         replica_database_available: replica_database_status,
         web3_available: eth_status,
+        forced_exit_sender,
     };
     *status = Some((response.clone(), Instant::now()));
 
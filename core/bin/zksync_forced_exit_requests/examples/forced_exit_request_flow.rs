@@ -0,0 +1,101 @@
+//! Goes through the whole flow of the forced exit request against the development server:
+//! creates the request, pays for it through the admin API and waits until it is fulfilled.
+//!
+//! The admin API is authorized with the secret of the server, i.e. `API_ADMIN_SECRET_AUTH`:
+//!
+//! ```sh
+//! cargo run --example forced_exit_request_flow -- --target 0x... --tokens 0 1
+//! ```
+
+use std::time::Duration;
+
+use structopt::StructOpt;
+use zksync_config::configs::api::AdminApiConfig;
+use zksync_forced_exit_requests::client::{
+    admin_auth_token, ForcedExitClient, ForcedExitRegisterRequest, InjectPaymentRequest,
+};
+use zksync_types::{Address, TokenId};
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "forced_exit_request_flow",
+    about = "Creates the forced exit request, pays for it through the admin API and waits until it is fulfilled"
+)]
+struct Opt {
+    /// The URL of the server with the forced exit requests API.
+    #[structopt(long, default_value = "http://127.0.0.1:3001")]
+    api_url: String,
+    /// The address of the account to withdraw the tokens of.
+    #[structopt(long)]
+    target: Address,
+    /// The ids of the tokens to withdraw.
+    #[structopt(long, required = true)]
+    tokens: Vec<u32>,
+    /// How long to wait for the request to be fulfilled, in seconds.
+    #[structopt(long, default_value = "300")]
+    timeout: u64,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let opt = Opt::from_args();
+    let client = ForcedExitClient::new(opt.api_url);
+
+    let quote = client.quote(opt.tokens.len()).await?;
+    println!(
+        "Withdrawing {} tokens costs {} wei",
+        opt.tokens.len(),
+        quote.price_in_wei
+    );
+
+    let created = client
+        .create_request(&ForcedExitRegisterRequest {
+            target: opt.target,
+            tokens: opt.tokens.into_iter().map(TokenId).collect(),
+            price_in_wei: quote.price_in_wei,
+            metadata: None,
+            callback_url: None,
+        })
+        .await?;
+    let payment = &created.payment;
+    println!("Created request {}", created.request.public_id);
+    println!(
+        "Pay exactly {} wei to {:?} until {}: {}",
+        payment.amount, payment.address, payment.expires_at, payment.uri
+    );
+
+    // On the real network the payment is made by the user, the development server takes
+    // the injected one as if it was sent to the forced exit contract
+    let secret_auth = AdminApiConfig::from_env().secret_auth;
+    let injected = client
+        .inject_payment(
+            &InjectPaymentRequest {
+                amount: payment.amount.parse()?,
+                request_id: None,
+                block_number: 0,
+                eth_tx_hash: None,
+            },
+            &admin_auth_token(&secret_auth)?,
+        )
+        .await?;
+    println!(
+        "Injected payment {} of {} wei",
+        injected.id, injected.amount
+    );
+
+    let settled = client
+        .wait_until_settled(
+            created.request.public_id,
+            Duration::from_secs(1),
+            Duration::from_secs(opt.timeout),
+        )
+        .await?;
+    println!(
+        "Request {} is {:?}, the transactions: {:?}",
+        settled.request.public_id,
+        settled.request.status,
+        settled.request.fulfilled_by.unwrap_or_default()
+    );
+
+    Ok(())
+}
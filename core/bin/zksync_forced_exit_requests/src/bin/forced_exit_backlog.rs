@@ -0,0 +1,30 @@
+use structopt::StructOpt;
+use zksync_config::configs::api::AdminApiConfig;
+use zksync_forced_exit_requests::remote::ApiCoreInteractionWrapper;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "zkSync forced exit backlog simulation tool",
+    author = "Matter Labs"
+)]
+#[structopt(
+    about = "Estimates the outcome of fulfilling the paid forced exit requests, which have not been sent yet: \
+    the transactions, their fees, the requests which would not be fulfilled and the time it would take. \
+    Nothing is sent, the report is printed as JSON and stored by the server"
+)]
+struct Opt {
+    /// The URL of the server API, the requests are authorized with the admin secret shared with the server.
+    #[structopt(long, env = "FORCED_EXIT_REQUESTS_REMOTE_API_URL")]
+    api_url: String,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let _vlog_guard = vlog::init();
+    let opt = Opt::from_args();
+
+    let api = ApiCoreInteractionWrapper::new(opt.api_url, AdminApiConfig::from_env().secret_auth);
+    let report = api.simulate_backlog().await?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
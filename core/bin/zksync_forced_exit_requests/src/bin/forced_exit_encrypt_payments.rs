@@ -0,0 +1,43 @@
+use structopt::StructOpt;
+use zksync_storage::{encryption::column_cipher, ConnectionPool};
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "zkSync forced exit payments encryption tool",
+    author = "Matter Labs"
+)]
+#[structopt(
+    about = "Tool to encrypt the payers of the forced exit payments stored before the encryption was enabled. \
+    The key is taken from the DATABASE_ENCRYPTION_KEY variable, the server should run in the mixed mode until the tool is finished"
+)]
+struct Opt {
+    /// Number of the rows encrypted within a single database transaction.
+    #[structopt(long, default_value = "1000")]
+    batch_size: u32,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let _vlog_guard = vlog::init();
+    let opt = Opt::from_args();
+    let cipher = column_cipher().expect("DATABASE_ENCRYPTION_KEY must be set");
+    let connection_pool = ConnectionPool::new(Some(1));
+
+    let mut total = 0;
+    loop {
+        let encrypted = connection_pool
+            .access_storage()
+            .await?
+            .forced_exit_requests_schema()
+            .encrypt_payments_batch(cipher, opt.batch_size)
+            .await?;
+        if encrypted == 0 {
+            break;
+        }
+        total += encrypted;
+        vlog::info!("{} payments have been encrypted so far", total);
+    }
+
+    println!("{} payments were encrypted", total);
+    Ok(())
+}
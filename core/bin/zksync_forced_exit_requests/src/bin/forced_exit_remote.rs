@@ -0,0 +1,53 @@
+use structopt::StructOpt;
+use zksync_config::{
+    configs::api::AdminApiConfig, ContractsConfig, ETHClientConfig, ForcedExitRequestsConfig,
+};
+use zksync_forced_exit_requests::{
+    remote::run_remote_forced_exit_contract_watcher, shutdown::ShutdownSignal,
+};
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "zkSync forced exit requests remote component",
+    author = "Matter Labs"
+)]
+#[structopt(
+    about = "Watches the payments for the forced exit requests and fulfills them, talking only to the server API. \
+    The server is selected with the FORCED_EXIT_REQUESTS_REMOTE_API_URL variable, the requests are authorized \
+    with the admin secret shared with the server"
+)]
+struct Opt {}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let _vlog_guard = vlog::init();
+    let _opt = Opt::from_args();
+
+    let config = ForcedExitRequestsConfig::from_env();
+    let api_url = config.remote_api_url.clone().ok_or_else(|| {
+        anyhow::anyhow!(
+            "FORCED_EXIT_REQUESTS_REMOTE_API_URL must be set to run the remote component"
+        )
+    })?;
+    let contracts = ContractsConfig::from_env();
+
+    // The requests being fulfilled are drained once Ctrl+C is received
+    let (shutdown_handle, shutdown) = ShutdownSignal::new(config.shutdown_drain_timeout());
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            vlog::warn!("Stop signal received, shutting down");
+            shutdown_handle.request();
+        }
+    });
+
+    run_remote_forced_exit_contract_watcher(
+        config,
+        api_url,
+        AdminApiConfig::from_env().secret_auth,
+        contracts.forced_exit_addr,
+        contracts.contract_addr,
+        ETHClientConfig::from_env().web3_url(),
+        shutdown,
+    )
+    .await
+}
@@ -0,0 +1,109 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+};
+
+use structopt::StructOpt;
+use zksync_config::{configs::api::CommonApiConfig, ContractsConfig, ForcedExitRequestsConfig};
+use zksync_forced_exit_requests::replay::{
+    export_payments, read_jsonl, replay_payments, write_jsonl,
+};
+use zksync_storage::ConnectionPool;
+use zksync_types::forced_exit_requests::ForcedExitPayment;
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Exports the recorded payments to the JSONL file
+    Export {
+        /// The first block (inclusive) of the payments to export.
+        #[structopt(long)]
+        from_block: u64,
+        /// The last block (inclusive) of the payments to export.
+        #[structopt(long)]
+        to_block: u64,
+        /// The id of the last payment exported before, the export is continued after it.
+        #[structopt(long)]
+        after_id: Option<i64>,
+        /// The maximum number of the payments to export at once.
+        #[structopt(long, default_value = "1000000")]
+        max_rows: usize,
+        #[structopt(long, parse(from_os_str))]
+        output: PathBuf,
+    },
+    /// Replays the exported payments and writes the decision log.
+    /// The database is modified by the replay, so it must be a copy of the production one
+    Replay {
+        #[structopt(long, parse(from_os_str))]
+        input: PathBuf,
+        #[structopt(long, parse(from_os_str))]
+        output: PathBuf,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "zkSync forced exit payments replay tool",
+    author = "Matter Labs"
+)]
+#[structopt(
+    about = "Tool to replay the payments to the forced exit contract against the copy of the database. \
+    The database is selected with the DATABASE_URL variable, the ForcedExit transactions are never sent"
+)]
+struct Opt {
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let _vlog_guard = vlog::init();
+    let opt = Opt::from_args();
+    let connection_pool = ConnectionPool::new(Some(1));
+
+    match opt.command {
+        Command::Export {
+            from_block,
+            to_block,
+            after_id,
+            max_rows,
+            output,
+        } => {
+            let export = export_payments(
+                &connection_pool,
+                from_block,
+                to_block,
+                after_id,
+                max_rows,
+                BufWriter::new(File::create(&output)?),
+            )
+            .await?;
+            println!("{} payments were exported to {:?}", export.exported, output);
+            if let Some(continue_after) = export.continue_after {
+                println!(
+                    "The maximum number of the payments was reached, continue with `--after-id {}`",
+                    continue_after
+                );
+            }
+        }
+        Command::Replay { input, output } => {
+            let payments: Vec<ForcedExitPayment> = read_jsonl(BufReader::new(File::open(&input)?))?;
+            let decision_log = replay_payments(
+                connection_pool,
+                ForcedExitRequestsConfig::from_env(),
+                ContractsConfig::from_env(),
+                CommonApiConfig::from_env().forced_exit_minimum_account_age_secs,
+                payments,
+            )
+            .await?;
+            write_jsonl(BufWriter::new(File::create(&output)?), &decision_log)?;
+            println!(
+                "{} payments were replayed, the decision log is written to {:?}",
+                decision_log.len(),
+                output
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,643 @@
+//! The client of the forced exit requests API for the wallets and the other integrators.
+//!
+//! The client speaks the v0.2 API: the responses are unwrapped from the envelope and
+//! the errors reported in it are returned along with their codes. The types of the
+//! requests and the responses are the ones the server uses, they are re-exported here,
+//! so the integrators need nothing else to go through the whole flow: create the request,
+//! pay for it as the payment instructions say and wait until it is fulfilled.
+//!
+//! The payments can be injected through the admin API on the development servers,
+//! see the `forced_exit_request_flow` example.
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use jsonwebtoken::{encode, EncodingKey, Header};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use zksync_api_types::v02::{Response, ResultStatus};
+
+pub use zksync_api::api_server::rest::v02::error::ErrorCode;
+pub use zksync_api_client::rest::forced_exit_requests::{
+    ConfigInfo, ForcedExitCreatedRequest, ForcedExitPaymentInstructions, ForcedExitQuoteQuery,
+    ForcedExitRegisterRequest, ForcedExitRequestDetails, ForcedExitRequestProgress,
+    ForcedExitRequestQuote, ForcedExitRequestStatus, InjectPaymentRequest, API_KEY_HEADER,
+};
+pub use zksync_types::forced_exit_requests::{
+    ForcedExitLifecycleStatus, ForcedExitRequest, ForcedExitRequestId, InjectedForcedExitPayment,
+};
+
+/// The scope of the API version the client speaks.
+pub const API_SCOPE: &str = "/api/forced_exit_requests/v0.2/";
+const ADMIN_SCOPE: &str = "/admin/forced_exit_requests/";
+
+// The admin tokens are issued for every injected payment
+const ADMIN_TOKEN_LIFETIME: Duration = Duration::from_secs(60);
+
+pub type Result<T> = std::result::Result<T, ClientError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("The API could not be reached: {0}")]
+    Transport(reqwest::Error),
+    /// The server has not returned the envelope, e.g. since the request was not authorized.
+    #[error("The API has responded with HTTP {status}: {body}")]
+    Http { status: u16, body: String },
+    #[error("The API has returned the error {}: {}", .error.code, .error.message)]
+    Api { error: ApiErrorBody },
+    #[error("The response could not be parsed: {0}")]
+    Parse(String),
+    #[error("ForcedExit request {request_id} is still {status:?} after {timeout:?}")]
+    Timeout {
+        request_id: ForcedExitRequestId,
+        status: ForcedExitLifecycleStatus,
+        timeout: Duration,
+    },
+}
+
+impl ClientError {
+    /// The code of the error returned by the API, if it is known to the client.
+    pub fn error_code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::Api { error } => error.error_code(),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(err: reqwest::Error) -> Self {
+        Self::Transport(err)
+    }
+}
+
+/// The error reported in the envelope of the response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiErrorBody {
+    pub error_type: String,
+    /// The code is kept as is, the newer servers may return the codes the client does not know.
+    pub code: u16,
+    pub message: String,
+}
+
+impl ApiErrorBody {
+    pub fn error_code(&self) -> Option<ErrorCode> {
+        serde_json::from_value(self.code.into()).ok()
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AdminTokenPayload {
+    sub: String,
+    exp: usize,
+}
+
+/// Encodes the token the admin API is authorized with, the secret is the one configured
+/// for the admin API of the server. Only meant for the development servers.
+pub fn admin_auth_token(secret_auth: &str) -> anyhow::Result<String> {
+    let exp = SystemTime::now().duration_since(UNIX_EPOCH)? + ADMIN_TOKEN_LIFETIME;
+    let payload = AdminTokenPayload {
+        sub: "forced_exit_requests_client".to_string(),
+        exp: exp.as_secs() as usize,
+    };
+    let token = encode(
+        &Header::default(),
+        &payload,
+        &EncodingKey::from_secret(secret_auth.as_ref()),
+    )?;
+    Ok(token)
+}
+
+/// Thin wrapper over the `reqwest` client for the forced exit requests API.
+#[derive(Debug, Clone)]
+pub struct ForcedExitClient {
+    inner: reqwest::Client,
+    url: String,
+    /// The requests are created with the limits of the trusted partner if set.
+    api_key: Option<String>,
+}
+
+impl ForcedExitClient {
+    /// Creates the client of the server at `url`, e.g. `http://127.0.0.1:3001`.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            inner: reqwest::Client::new(),
+            url: url.into().trim_end_matches('/').to_owned(),
+            api_key: None,
+        }
+    }
+
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    pub async fn status(&self) -> Result<ForcedExitRequestStatus> {
+        let request = self.inner.get(self.endpoint(API_SCOPE, "status"));
+        send_enveloped(request).await
+    }
+
+    /// The price of the request to withdraw the given number of tokens.
+    pub async fn quote(&self, tokens_count: usize) -> Result<ForcedExitRequestQuote> {
+        let request = self
+            .inner
+            .get(self.endpoint(API_SCOPE, "quote"))
+            .query(&ForcedExitQuoteQuery { tokens_count });
+        send_enveloped(request).await
+    }
+
+    /// Creates the request, it is to be paid for as the returned instructions say.
+    pub async fn create_request(
+        &self,
+        register_request: &ForcedExitRegisterRequest,
+    ) -> Result<ForcedExitCreatedRequest> {
+        let mut request = self
+            .inner
+            .post(self.endpoint(API_SCOPE, "requests"))
+            .json(register_request);
+        if let Some(api_key) = &self.api_key {
+            request = request.header(API_KEY_HEADER, api_key);
+        }
+        send_enveloped(request).await
+    }
+
+    pub async fn request(
+        &self,
+        public_id: ForcedExitRequestId,
+    ) -> Result<ForcedExitRequestDetails> {
+        let request = self
+            .inner
+            .get(self.endpoint(API_SCOPE, &format!("requests/{}", public_id)));
+        send_enveloped(request).await
+    }
+
+    /// The transactions sent for the request and their statuses.
+    pub async fn progress(
+        &self,
+        public_id: ForcedExitRequestId,
+    ) -> Result<ForcedExitRequestProgress> {
+        let request = self
+            .inner
+            .get(self.endpoint(API_SCOPE, &format!("requests/{}/status", public_id)));
+        send_enveloped(request).await
+    }
+
+    /// Injects the payment through the admin API, as if it was sent to the forced exit contract.
+    /// The token is the one of `admin_auth_token`.
+    pub async fn inject_payment(
+        &self,
+        payment: &InjectPaymentRequest,
+        auth_token: &str,
+    ) -> Result<InjectedForcedExitPayment> {
+        let request = self
+            .inner
+            .post(self.endpoint(ADMIN_SCOPE, "payments"))
+            .bearer_auth(auth_token)
+            .json(payment);
+        let response = send(request).await?;
+        response
+            .json()
+            .await
+            .map_err(|err| ClientError::Parse(err.to_string()))
+    }
+
+    /// Polls the request until it is fulfilled or has ended up otherwise, i.e. has failed,
+    /// expired or has been cancelled. The request still in progress after the `timeout`
+    /// is reported with `ClientError::Timeout`.
+    pub async fn wait_until_settled(
+        &self,
+        public_id: ForcedExitRequestId,
+        poll_interval: Duration,
+        timeout: Duration,
+    ) -> Result<ForcedExitRequestDetails> {
+        let started_at = Instant::now();
+        loop {
+            let details = self.request(public_id).await?;
+            if is_settled(details.request.status) {
+                return Ok(details);
+            }
+            if started_at.elapsed() >= timeout {
+                return Err(ClientError::Timeout {
+                    request_id: public_id,
+                    status: details.request.status,
+                    timeout,
+                });
+            }
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+
+    fn endpoint(&self, scope: &str, method: &str) -> String {
+        [&self.url, scope, method].concat()
+    }
+}
+
+/// Whether the status of the request is not going to change anymore.
+pub fn is_settled(status: ForcedExitLifecycleStatus) -> bool {
+    matches!(
+        status,
+        ForcedExitLifecycleStatus::Fulfilled
+            | ForcedExitLifecycleStatus::Failed
+            | ForcedExitLifecycleStatus::Expired
+            | ForcedExitLifecycleStatus::Cancelled
+    )
+}
+
+async fn send(request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+    let response = request.send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(ClientError::Http {
+            status: status.as_u16(),
+            body: response.text().await.unwrap_or_default(),
+        });
+    }
+    Ok(response)
+}
+
+/// Sends the request and unwraps the result from the envelope of the response.
+async fn send_enveloped<T: DeserializeOwned>(request: reqwest::RequestBuilder) -> Result<T> {
+    let response: Response = send(request)
+        .await?
+        .json()
+        .await
+        .map_err(|err| ClientError::Parse(err.to_string()))?;
+    match response.status {
+        ResultStatus::Success => serde_json::from_value(response.result.unwrap_or_default())
+            .map_err(|err| ClientError::Parse(err.to_string())),
+        ResultStatus::Error => {
+            let error = serde_json::from_value(response.error.unwrap_or_default())
+                .map_err(|err| ClientError::Parse(err.to_string()))?;
+            Err(ClientError::Api { error })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        ops::Add,
+        sync::atomic::{AtomicI64, Ordering},
+    };
+
+    use actix_web::{web, App, HttpRequest, HttpResponse};
+    use chrono::Utc;
+    use jsonwebtoken::{decode, DecodingKey, Validation};
+    use num::BigUint;
+    use serde_json::json;
+    use tokio::sync::Mutex;
+
+    use zksync_api_types::v02::{ApiVersion, Request};
+    use zksync_config::ForcedExitRequestsConfig;
+    use zksync_types::{
+        forced_exit_requests::pay_exactly, network::Network, AccountId, Address, TokenId,
+    };
+
+    use super::*;
+    use crate::{
+        forced_exit_sender::MempoolForcedExitSender,
+        test::{add_request, MockCoreInteractionWrapper},
+    };
+
+    const TEST_SECRET_AUTH: &str = "sample";
+    const TEST_CONTRACT: Address = Address::repeat_byte(0x34);
+    const PRICE_PER_TOKEN: u64 = 10_000_000_000;
+
+    #[derive(Debug, Deserialize)]
+    struct AdminTokenClaims {
+        #[allow(dead_code)]
+        sub: String,
+        #[allow(dead_code)]
+        exp: usize,
+    }
+
+    /// The API of the server with the requests stored by the mock pipeline, the injected
+    /// payments are processed by the sender in the background as the watcher would do it.
+    struct MockApiState {
+        sender: Mutex<MempoolForcedExitSender<MockCoreInteractionWrapper>>,
+        next_id: AtomicI64,
+        next_payment_id: AtomicI64,
+    }
+
+    impl MockApiState {
+        fn new() -> Self {
+            let config = ForcedExitRequestsConfig {
+                digits_in_id: 9,
+                legacy_amount_ids_enabled: false,
+                overpayment_tolerance: 0,
+                overpayment_tolerance_percent: 0,
+                ..ForcedExitRequestsConfig::from_env()
+            };
+            let sender = MempoolForcedExitSender::new(
+                MockCoreInteractionWrapper::default(),
+                config,
+                AccountId(12),
+                Address::repeat_byte(0x12),
+            );
+            Self {
+                sender: Mutex::new(sender),
+                next_id: AtomicI64::new(12),
+                next_payment_id: AtomicI64::new(1),
+            }
+        }
+
+        async fn find_request(&self, public_id: ForcedExitRequestId) -> Option<ForcedExitRequest> {
+            let sender = self.sender.lock().await;
+            let requests = sender.core_interaction_wrapper.requests.lock().unwrap();
+            requests
+                .iter()
+                .find(|request| request.public_id == public_id)
+                .cloned()
+        }
+    }
+
+    fn envelope(
+        resource: String,
+        result: std::result::Result<serde_json::Value, ApiErrorBody>,
+    ) -> Response {
+        let (status, result, error) = match result {
+            Ok(result) => (ResultStatus::Success, Some(result), None),
+            Err(error) => (ResultStatus::Error, None, Some(json!(error))),
+        };
+        Response {
+            request: Request {
+                network: Network::Localhost,
+                api_version: ApiVersion::V02,
+                resource,
+                args: HashMap::new(),
+                timestamp: Utc::now(),
+            },
+            status,
+            error,
+            result,
+        }
+    }
+
+    fn not_found(public_id: ForcedExitRequestId) -> ApiErrorBody {
+        ApiErrorBody {
+            error_type: "forcedExitRequestError".to_string(),
+            code: ErrorCode::ForcedExitRequestNotFound as u16,
+            message: format!("ForcedExit request {} does not exist", public_id),
+        }
+    }
+
+    async fn create_request(
+        req: HttpRequest,
+        state: web::Data<MockApiState>,
+        params: web::Json<ForcedExitRegisterRequest>,
+    ) -> web::Json<Response> {
+        let params = params.into_inner();
+        let price = BigUint::from(PRICE_PER_TOKEN) * params.tokens.len();
+        if params.price_in_wei != price {
+            return web::Json(envelope(
+                req.path().to_string(),
+                Err(ApiErrorBody {
+                    error_type: "forcedExitRequestError".to_string(),
+                    code: ErrorCode::InvalidForcedExitRequest as u16,
+                    message: "The price is not the one quoted".to_string(),
+                }),
+            ));
+        }
+
+        let id = state.next_id.fetch_add(1, Ordering::SeqCst);
+        let request = ForcedExitRequest {
+            id,
+            public_id: id,
+            target: params.target,
+            tokens: params.tokens,
+            pay_exactly: pay_exactly(&price, id),
+            price_in_wei: price,
+            valid_until: Utc::now().add(chrono::Duration::days(1)),
+            created_at: Utc::now(),
+            fulfilled_by: None,
+            fulfilled_at: None,
+            match_scheme: None,
+            matched_at: None,
+            cancellation: None,
+            paid_amount: None,
+            metadata: params.metadata,
+            status: Default::default(),
+            payment_terms: None,
+        };
+        add_request(
+            &state.sender.lock().await.core_interaction_wrapper.requests,
+            request.clone(),
+        );
+        let created = ForcedExitCreatedRequest {
+            payment: ForcedExitPaymentInstructions {
+                uri: format!("ethereum:{:?}?value={}", TEST_CONTRACT, request.pay_exactly),
+                address: TEST_CONTRACT,
+                amount: request.pay_exactly.clone(),
+                chain_id: None,
+                expires_at: request.valid_until,
+            },
+            request,
+        };
+        web::Json(envelope(req.path().to_string(), Ok(json!(created))))
+    }
+
+    async fn get_request(
+        req: HttpRequest,
+        state: web::Data<MockApiState>,
+        public_id: web::Path<ForcedExitRequestId>,
+    ) -> web::Json<Response> {
+        let result = match state.find_request(*public_id).await {
+            Some(request) => Ok(json!(ForcedExitRequestDetails {
+                request,
+                queue: None,
+                active_target: None,
+                skipped_tokens: Vec::new(),
+                cancellations: Vec::new(),
+                expected_payments: Vec::new(),
+                maintenance: None,
+                processing_failure: None,
+            })),
+            None => Err(not_found(*public_id)),
+        };
+        web::Json(envelope(req.path().to_string(), result))
+    }
+
+    async fn inject_payment(
+        req: HttpRequest,
+        state: web::Data<MockApiState>,
+        params: web::Json<InjectPaymentRequest>,
+    ) -> HttpResponse {
+        let authorized = req
+            .headers()
+            .get("Authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map_or(false, |token| {
+                decode::<AdminTokenClaims>(
+                    token,
+                    &DecodingKey::from_secret(TEST_SECRET_AUTH.as_ref()),
+                    &Validation::default(),
+                )
+                .is_ok()
+            });
+        if !authorized {
+            return HttpResponse::Unauthorized().finish();
+        }
+
+        let params = params.into_inner();
+        let injected = InjectedForcedExitPayment {
+            id: state.next_payment_id.fetch_add(1, Ordering::SeqCst),
+            amount: params.amount,
+            request_id: params.request_id,
+            block_number: params.block_number,
+            eth_tx_hash: params.eth_tx_hash,
+            created_at: Utc::now(),
+        };
+        let event = injected.event();
+        let state = state.into_inner();
+        actix_web::rt::spawn(async move {
+            let mut sender = state.sender.lock().await;
+            sender
+                .process_payment(event, Utc::now())
+                .await
+                .expect("The injected payment is not processed");
+        });
+        HttpResponse::Ok().json(injected)
+    }
+
+    fn start_mock_api(
+        state: web::Data<MockApiState>,
+    ) -> (ForcedExitClient, actix_test::TestServer) {
+        let server = actix_test::start(move || {
+            App::new()
+                .app_data(state.clone())
+                .service(
+                    web::scope("/api/forced_exit_requests/v0.2")
+                        .route("/requests", web::post().to(create_request))
+                        .route("/requests/{id}", web::get().to(get_request)),
+                )
+                .route(
+                    "/admin/forced_exit_requests/payments",
+                    web::post().to(inject_payment),
+                )
+        });
+        let client = ForcedExitClient::new(server.url(""));
+        (client, server)
+    }
+
+    fn register_request(tokens: Vec<TokenId>) -> ForcedExitRegisterRequest {
+        ForcedExitRegisterRequest {
+            target: Address::random(),
+            price_in_wei: BigUint::from(PRICE_PER_TOKEN) * tokens.len(),
+            tokens,
+            metadata: None,
+            callback_url: None,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn paid_request_is_fulfilled() {
+        let (client, server) = start_mock_api(web::Data::new(MockApiState::new()));
+
+        let created = client
+            .create_request(&register_request(vec![TokenId(1), TokenId(2)]))
+            .await
+            .unwrap();
+        assert_eq!(created.request.status, ForcedExitLifecycleStatus::Created);
+        // The request is paid for by the amount from the instructions
+        assert_eq!(created.payment.amount, created.request.pay_exactly);
+        assert_eq!(created.payment.address, TEST_CONTRACT);
+
+        let payment = InjectPaymentRequest {
+            amount: created.payment.amount.parse().unwrap(),
+            request_id: None,
+            block_number: 1,
+            eth_tx_hash: None,
+        };
+        let injected = client
+            .inject_payment(&payment, &admin_auth_token(TEST_SECRET_AUTH).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(injected.amount, payment.amount);
+
+        let settled = client
+            .wait_until_settled(
+                created.request.public_id,
+                Duration::from_millis(10),
+                Duration::from_secs(10),
+            )
+            .await
+            .unwrap();
+        assert_eq!(settled.request.status, ForcedExitLifecycleStatus::Fulfilled);
+        assert!(settled.request.fulfilled_at.is_some());
+        assert_eq!(settled.request.tokens, vec![TokenId(1), TokenId(2)]);
+        assert!(settled.request.fulfilled_by.is_some());
+
+        server.stop().await;
+    }
+
+    #[actix_rt::test]
+    async fn api_errors_are_told_apart() {
+        let (client, server) = start_mock_api(web::Data::new(MockApiState::new()));
+
+        // The errors reported in the envelope keep their codes
+        let err = client.request(42).await.unwrap_err();
+        assert!(matches!(err, ClientError::Api { .. }));
+        assert_eq!(err.error_code(), Some(ErrorCode::ForcedExitRequestNotFound));
+
+        let err = client
+            .create_request(&ForcedExitRegisterRequest {
+                price_in_wei: BigUint::from(1u32),
+                ..register_request(vec![TokenId(1)])
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.error_code(), Some(ErrorCode::InvalidForcedExitRequest));
+
+        // The unknown codes are kept as they are
+        let unknown = ApiErrorBody {
+            error_type: "forcedExitRequestError".to_string(),
+            code: 299,
+            message: "Unknown".to_string(),
+        };
+        assert_eq!(unknown.error_code(), None);
+
+        // The payments are only injected with the admin token
+        let payment = InjectPaymentRequest {
+            amount: BigUint::from(PRICE_PER_TOKEN),
+            request_id: None,
+            block_number: 1,
+            eth_tx_hash: None,
+        };
+        let err = client
+            .inject_payment(&payment, &admin_auth_token("another secret").unwrap())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ClientError::Http { status: 401, .. }));
+
+        server.stop().await;
+    }
+
+    #[actix_rt::test]
+    async fn unpaid_request_is_not_waited_for_forever() {
+        let (client, server) = start_mock_api(web::Data::new(MockApiState::new()));
+
+        let created = client
+            .create_request(&register_request(vec![TokenId(1)]))
+            .await
+            .unwrap();
+        let err = client
+            .wait_until_settled(
+                created.request.public_id,
+                Duration::from_millis(10),
+                Duration::from_millis(50),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ClientError::Timeout {
+                status: ForcedExitLifecycleStatus::Created,
+                ..
+            }
+        ));
+
+        server.stop().await;
+    }
+}
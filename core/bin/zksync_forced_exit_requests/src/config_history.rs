@@ -0,0 +1,42 @@
+//! History of the config the amounts paid by the users depend on.
+//!
+//! The config is only read on startup, so its snapshot is recorded then, unless none of its
+//! values have changed since the previous start. The snapshots allow to tell which price,
+//! payment address and tolerances were in effect when a disputed payment was made.
+
+use chrono::Utc;
+use zksync_config::ForcedExitRequestsConfig;
+use zksync_storage::ConnectionPool;
+use zksync_types::{forced_exit_requests::ForcedExitMoneyConfig, Address};
+
+use crate::spawner::ForcedExitSpawner;
+
+/// Records the snapshot of the money config if it differs from the one in effect.
+pub async fn record_config(
+    connection_pool: &ConnectionPool,
+    config: &ForcedExitMoneyConfig,
+) -> anyhow::Result<bool> {
+    let mut storage = connection_pool.access_storage().await?;
+    let recorded = storage
+        .forced_exit_requests_schema()
+        .record_config(config, Utc::now())
+        .await?;
+    Ok(recorded)
+}
+
+/// Records the config in the background, the task finishes once it is done.
+pub fn run_config_recorder(
+    spawner: &ForcedExitSpawner,
+    connection_pool: ConnectionPool,
+    config: &ForcedExitRequestsConfig,
+    forced_exit_contract: Address,
+) {
+    let config = config.money_config(forced_exit_contract);
+    spawner.spawn(async move {
+        match record_config(&connection_pool, &config).await {
+            Ok(true) => vlog::info!("The changed ForcedExit requests config has been recorded"),
+            Ok(false) => {}
+            Err(err) => vlog::error!("Failed to record the ForcedExit requests config: {}", err),
+        }
+    });
+}
@@ -0,0 +1,85 @@
+//! Consistency of the statuses of the requests with the records backing them.
+//!
+//! The statuses, the fulfillments, the payments and the notifications are written by different
+//! components and (partly) by the servers of the previous versions, so the checker compares
+//! them for the requests changed recently, see `ForcedExitInvariant`. The violations are only
+//! reported: to the metrics, to the log and to the stored reports the operators can read via
+//! the admin API, which also allows to run the check on demand. Nothing is fixed automatically,
+//! since it is not known which of the records is wrong.
+
+use std::time::Duration;
+
+use chrono::Utc;
+use tokio::{task::JoinHandle, time};
+use zksync_api::api_server::forced_exit_consistency::consistency_report;
+use zksync_storage::ConnectionPool;
+use zksync_types::forced_exit_requests::{ForcedExitConsistencyReport, ForcedExitInvariant};
+
+use crate::spawner::ForcedExitSpawner;
+
+const CONSISTENCY_CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// The requests changed within this window are checked, so each change is checked
+/// a few times before it leaves the window.
+const CONSISTENCY_CHECK_WINDOW_SECS: i64 = 60 * 60;
+/// The maximum number of the requests checked at once.
+const CHECKED_REQUESTS_LIMIT: u32 = 1000;
+const LOGGED_VIOLATIONS: usize = 5;
+
+/// Checks the requests changed within the window, stores and returns the report.
+pub async fn check_consistency(
+    connection_pool: &ConnectionPool,
+) -> anyhow::Result<ForcedExitConsistencyReport> {
+    let created_at = Utc::now();
+    let since = created_at - chrono::Duration::seconds(CONSISTENCY_CHECK_WINDOW_SECS);
+
+    let mut storage = connection_pool.access_storage().await?;
+    let mut fe_schema = storage.forced_exit_requests_schema();
+    let evidence = fe_schema
+        .load_consistency_evidence(since, CHECKED_REQUESTS_LIMIT)
+        .await?;
+    let report = consistency_report(created_at, since, &evidence);
+    fe_schema.store_consistency_report(&report).await?;
+
+    for invariant in ForcedExitInvariant::ALL.iter().copied() {
+        metrics::gauge!(
+            "forced_exit_requests.invariant_violations",
+            report.count(invariant) as f64,
+            "invariant" => invariant.as_str()
+        );
+    }
+    for violation in report.violations.iter().take(LOGGED_VIOLATIONS) {
+        vlog::error!(
+            "ForcedExit request {} violates the `{}` invariant: {}",
+            violation.request_id,
+            violation.invariant,
+            violation.details
+        );
+    }
+    if report.checked_requests == CHECKED_REQUESTS_LIMIT {
+        vlog::warn!(
+            "Only {} of the ForcedExit requests changed since {} have been checked",
+            CHECKED_REQUESTS_LIMIT,
+            since
+        );
+    }
+    Ok(report)
+}
+
+/// Runs the check periodically, whether the requests are processed by this server or not.
+pub fn run_consistency_checker(
+    spawner: &ForcedExitSpawner,
+    connection_pool: ConnectionPool,
+) -> JoinHandle<()> {
+    spawner.spawn(async move {
+        let mut timer = time::interval(CONSISTENCY_CHECK_INTERVAL);
+        loop {
+            timer.tick().await;
+            if let Err(err) = check_consistency(&connection_pool).await {
+                vlog::warn!(
+                    "Failed to check the consistency of the ForcedExit requests: {}",
+                    err
+                );
+            }
+        }
+    })
+}
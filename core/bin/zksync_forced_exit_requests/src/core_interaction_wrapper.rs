@@ -1,28 +1,248 @@
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use futures::{
     channel::{mpsc, oneshot},
     SinkExt,
 };
-use num::Zero;
+use num::BigUint;
 
+use zksync_config::ForcedExitRequestsConfig;
 use zksync_storage::{chain::operations_ext::records::TxReceiptResponse, ConnectionPool};
 use zksync_types::{
-    forced_exit_requests::{ForcedExitRequest, ForcedExitRequestId},
-    tx::TxHash,
-    AccountId, Nonce,
+    forced_exit_requests::{
+        ActiveTargetPolicy, ExpectedForcedExitPayment, ForcedExitCancellationKind,
+        ForcedExitPacing, ForcedExitPacingState, ForcedExitPayment, ForcedExitPipelineVersion,
+        ForcedExitProcessingFailure, ForcedExitRefund, ForcedExitRefundId, ForcedExitRefundStatus,
+        ForcedExitRequest, ForcedExitRequestActiveTarget, ForcedExitRequestDelivery,
+        ForcedExitRequestDeliveryId, ForcedExitRequestEscalation, ForcedExitRequestId,
+        ForcedExitRetry, ForcedExitRetryId, ForcedExitTargetCheck, InjectedForcedExitPayment,
+        InjectedForcedExitPaymentId, InterruptedSubmission, PaymentMatchScheme, PaymentSourceState,
+        SaveForcedExitRefundQuery, SaveForcedExitRequestNoteQuery, SkippedForcedExit,
+        SubmissionError, UnmatchedPaymentReason,
+    },
+    tx::{error::TxAddError, TxHash},
+    AccountId, Address, Nonce, Token, TokenId, TokenLike, TxFeeTypes, ZkSyncTx, H256,
 };
 
-use zksync_api::api_server::forced_exit_checker::{ForcedExitAccountAgeChecker, ForcedExitChecker};
+use zksync_api::{
+    api_server::{
+        forced_exit_checker::{ForcedExitAccountAgeChecker, ForcedExitChecker},
+        forced_exit_receipts,
+    },
+    fee_ticker::FeeTicker,
+};
 use zksync_mempool::MempoolTransactionRequest;
 use zksync_types::SignedZkSyncTx;
 
+use crate::db_pools::DbPools;
+
+/// The features of the component, which depend on the data only available
+/// with the direct access to the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The failed transactions are counted and the requests are escalated to L1.
+    pub escalations: bool,
+    /// The processed payments are recorded to be replayed later.
+    pub payment_log: bool,
+    /// The payments injected by the operators are processed.
+    pub injected_payments: bool,
+    /// The requests, the targets of which have become active, are recorded
+    /// and may be held, see `ActiveTargetPolicy`.
+    pub active_targets: bool,
+    /// The tokens of the requests are checked to be known before the transactions are sent.
+    pub tokens: bool,
+    /// The payments registered by the partners in advance are matched by their hashes.
+    pub expected_payments: bool,
+    /// The versions of the pipeline the requests are fulfilled with are recorded.
+    pub pipeline_versions: bool,
+    /// The requests paid for with more than their amount are found by the amount.
+    pub overpayments: bool,
+    /// The payments for the requests which can not be fulfilled are returned to the payers.
+    pub refunds: bool,
+    /// The failed requests are sent again once the operators retry them.
+    pub retries: bool,
+    /// The pacing of the sender is changed by the operators at runtime and reported with the status.
+    pub pacing_overrides: bool,
+    /// The ids the targets were resolved to when the transactions were signed are recorded.
+    pub target_account_ids: bool,
+    /// The transactions are recorded before they are submitted, so the batches accepted
+    /// by the mempool are not lost track of if the server crashes right after.
+    pub submission_records: bool,
+}
+
+impl Capabilities {
+    pub const ALL: Self = Self {
+        escalations: true,
+        payment_log: true,
+        injected_payments: true,
+        active_targets: true,
+        tokens: true,
+        expected_payments: true,
+        pipeline_versions: true,
+        overpayments: true,
+        refunds: true,
+        retries: true,
+        pacing_overrides: true,
+        target_account_ids: true,
+        submission_records: true,
+    };
+
+    /// Checks that the features enabled in the config are supported,
+    /// the ones which are not are refused rather than silently ignored.
+    pub fn ensure_supported(&self, config: &ForcedExitRequestsConfig) -> anyhow::Result<()> {
+        if config.l1_escalation_enabled && !self.escalations {
+            anyhow::bail!("The escalation of the requests to L1 is not supported, disable `l1_escalation_enabled`");
+        }
+        if config.admin_payments_enabled && !self.injected_payments {
+            anyhow::bail!(
+                "The injected payments are not supported, disable `admin_payments_enabled`"
+            );
+        }
+        if config.active_target_policy == ActiveTargetPolicy::Hold && !self.active_targets {
+            anyhow::bail!("The requests can not be held, set `active_target_policy` to `fail`");
+        }
+        let overpayments_tolerated =
+            config.overpayment_tolerance > 0 || config.overpayment_tolerance_percent > 0;
+        if overpayments_tolerated && !self.overpayments {
+            anyhow::bail!("The overpaid requests can not be found, set `overpayment_tolerance` and `overpayment_tolerance_percent` to 0");
+        }
+        if config.refunds_enabled && !self.refunds {
+            anyhow::bail!("The payments can not be refunded, disable `refunds_enabled`");
+        }
+        if config.l1_transfer_check_web3_url.is_some() && !self.tokens {
+            anyhow::bail!("The L1 transfer restrictions of the tokens can not be checked, unset `l1_transfer_check_web3_url`");
+        }
+        if !self.active_targets {
+            vlog::warn!(
+                "The forced exit requests failed because of the active targets are not recorded for the refunds"
+            );
+        }
+        if !self.payment_log {
+            vlog::warn!(
+                "The processed forced exit payments are not recorded, they can not be replayed"
+            );
+        }
+        if !self.expected_payments {
+            vlog::warn!(
+                "The forced exit payments registered in advance are matched by their amounts"
+            );
+        }
+        if !self.retries {
+            vlog::warn!("The retries of the forced exit requests requested by the operators are not processed");
+        }
+        if !self.pacing_overrides {
+            vlog::warn!("The pacing of the forced exit sender is only set by the config and is not reported");
+        }
+        if !self.target_account_ids {
+            vlog::warn!(
+                "The ids the targets of the forced exit requests are resolved to are not recorded"
+            );
+        }
+        if !self.submission_records {
+            vlog::warn!(
+                "The forced exit transactions are only recorded once submitted, the batches \
+                 submitted right before a crash are not settled after the restart"
+            );
+        }
+        if !self.pipeline_versions {
+            vlog::warn!(
+                "The versions of the pipeline the forced exit requests are fulfilled with are not recorded"
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The submitters refuse the empty batches, otherwise the request would be marked
+/// as sent without any transaction to await.
+pub(crate) fn ensure_batch_not_empty(
+    request: &ForcedExitRequest,
+    txs: &[SignedZkSyncTx],
+) -> anyhow::Result<()> {
+    if txs.is_empty() {
+        anyhow::bail!(
+            "Refusing to send an empty batch of ForcedExit transactions for the request {}",
+            request.id
+        );
+    }
+    Ok(())
+}
+
+/// Puts the transactions of several requests into a single batch, returning the hashes
+/// of the transactions of each request in the same order.
+pub(crate) fn combine_batch(
+    parts: Vec<(ForcedExitRequestId, Vec<SignedZkSyncTx>)>,
+) -> anyhow::Result<(Vec<SignedZkSyncTx>, Vec<Vec<TxHash>>)> {
+    let mut txs = Vec::new();
+    let mut hashes = Vec::with_capacity(parts.len());
+    for (id, request_txs) in parts {
+        if request_txs.is_empty() {
+            anyhow::bail!(
+                "Refusing to send a batch without the ForcedExit transactions for the request {}",
+                id
+            );
+        }
+        hashes.push(request_txs.iter().map(|tx| tx.hash()).collect());
+        txs.extend(request_txs);
+    }
+    Ok((txs, hashes))
+}
+
+/// Appends the hashes of the `ForcedExit` transactions of the chunk to the ones sent in
+/// the previous chunks. The fee payment of the chunk is left out, since the hashes are
+/// saved for the tokens of the request by their positions.
+pub(crate) fn append_chunk(sent: &[TxHash], txs: &[SignedZkSyncTx]) -> Vec<TxHash> {
+    sent.iter()
+        .copied()
+        .chain(
+            txs.iter()
+                .filter(|tx| matches!(tx.tx, ZkSyncTx::ForcedExit(_)))
+                .map(|tx| tx.hash()),
+        )
+        .collect()
+}
+
+/// Adds the transactions to the mempool, telling the mempool being unavailable
+/// apart from the transactions being rejected by it.
+async fn submit_to_mempool(
+    mempool_tx_sender: &mut mpsc::Sender<MempoolTransactionRequest>,
+    request: impl FnOnce(oneshot::Sender<Result<(), TxAddError>>) -> MempoolTransactionRequest,
+) -> Result<(), SubmissionError> {
+    let (sender, receiver) = oneshot::channel();
+    mempool_tx_sender
+        .send(request(sender))
+        .await
+        .map_err(|err| {
+            SubmissionError::transport(format!("The mempool is unavailable: {}", err))
+        })?;
+    let added = receiver
+        .await
+        .map_err(|_| SubmissionError::transport("The mempool has dropped the transactions"))?;
+    added.map_err(mempool_rejection)
+}
+
+/// The rejections caused by the state of the server rather than by the transactions
+/// themselves may not happen again, e.g. the nonces are planned anew on the next attempt.
+pub(crate) fn mempool_rejection(err: TxAddError) -> SubmissionError {
+    let retryable = matches!(
+        err,
+        TxAddError::NonceMismatch | TxAddError::DbError | TxAddError::Other
+    );
+    SubmissionError::rejected(None, err.to_string(), retryable)
+}
+
 // We could use `db reset` and test the db the same way as in rust_api
 // but it seemed to be an overkill here, so it was decided to use
 // traits for unit-testing. Also it gives a much broader level of control
 // over what's going on
 #[async_trait::async_trait]
 pub trait CoreInteractionWrapper {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::ALL
+    }
     async fn get_nonce(&self, account_id: AccountId) -> anyhow::Result<Option<Nonce>>;
+    /// The nonce following the transactions of the account still awaiting the execution,
+    /// `None` if there are no such transactions.
+    async fn get_pending_nonce(&self, account_id: AccountId) -> anyhow::Result<Option<Nonce>>;
     async fn get_unconfirmed_requests(&self) -> anyhow::Result<Vec<ForcedExitRequest>>;
     async fn set_fulfilled_at(&self, id: i64) -> anyhow::Result<()>;
     async fn set_fulfilled_by(
@@ -30,26 +250,226 @@ pub trait CoreInteractionWrapper {
         id: ForcedExitRequestId,
         value: Option<Vec<TxHash>>,
     ) -> anyhow::Result<()>;
+    /// Cancels the request; only the requests cancelled by the system to send their
+    /// transactions again are processed afterwards.
+    async fn cancel_request(
+        &self,
+        id: ForcedExitRequestId,
+        kind: ForcedExitCancellationKind,
+    ) -> anyhow::Result<()>;
+    /// Records the way the request was matched, the amount it was paid for with
+    /// and the hash of the L1 transaction the payment was made with, if known.
+    async fn set_match_scheme(
+        &self,
+        id: ForcedExitRequestId,
+        match_scheme: PaymentMatchScheme,
+        paid_amount: &BigUint,
+        payment_tx_hash: Option<H256>,
+    ) -> anyhow::Result<()>;
+    /// Claims the fulfillment of the request caused by the given payment, `false` is
+    /// returned if it has already been claimed and the request must not be sent again.
+    /// The claim is released once the sent transactions are reset.
+    async fn begin_fulfillment(
+        &self,
+        id: ForcedExitRequestId,
+        payment_tx_hash: Option<H256>,
+    ) -> anyhow::Result<bool>;
     async fn get_request_by_id(&self, id: i64) -> anyhow::Result<Option<ForcedExitRequest>>;
+    /// Loads the request by the id the payers refer to it with, see `ForcedExitRequest::public_id`.
+    async fn get_request_by_public_id(
+        &self,
+        public_id: ForcedExitRequestId,
+    ) -> anyhow::Result<Option<ForcedExitRequest>>;
+    /// Loads the requests payable at `submission_time`, the exact amounts of which are
+    /// the closest ones not exceeding `max_amount`, the closest one first, at most two.
+    async fn get_requests_by_closest_amount(
+        &self,
+        max_amount: &BigUint,
+        submission_time: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<ForcedExitRequest>>;
     async fn get_receipt(&self, tx_hash: TxHash) -> anyhow::Result<Option<TxReceiptResponse>>;
+    async fn get_receipts(&self, tx_hashes: &[TxHash]) -> anyhow::Result<Vec<TxReceiptResponse>>;
     async fn send_and_save_txs_batch(
         &mut self,
         request: &ForcedExitRequest,
         txs: Vec<SignedZkSyncTx>,
     ) -> anyhow::Result<Vec<TxHash>>;
+    /// Sends the transactions of several requests in a single batch. The hashes of the
+    /// transactions of each request are saved for it and returned in the same order.
+    async fn send_and_save_combined_batch(
+        &mut self,
+        parts: Vec<(ForcedExitRequestId, Vec<SignedZkSyncTx>)>,
+    ) -> anyhow::Result<Vec<Vec<TxHash>>>;
+    /// Sends the next chunk of the transactions of the request, which do not fit into a single
+    /// batch. The chunk is saved following the `sent` hashes of the previous chunks, so they are
+    /// not lost track of if the server stops in between. The hashes of the chunk are returned.
+    async fn send_and_save_txs_chunk(
+        &mut self,
+        request: &ForcedExitRequest,
+        sent: &[TxHash],
+        txs: Vec<SignedZkSyncTx>,
+    ) -> anyhow::Result<Vec<TxHash>>;
     async fn get_oldest_unfulfilled_request(&self) -> anyhow::Result<Option<ForcedExitRequest>>;
     async fn delete_old_unfulfilled_requests(
         &self,
         deleting_threshold: chrono::Duration,
     ) -> anyhow::Result<()>;
-    async fn check_forced_exit_request(&self, request: &ForcedExitRequest) -> anyhow::Result<bool>;
+    async fn check_forced_exit_request(
+        &self,
+        request: &ForcedExitRequest,
+    ) -> anyhow::Result<ForcedExitTargetCheck>;
+    async fn record_failure(&self, id: ForcedExitRequestId, token: TokenId) -> anyhow::Result<u32>;
+    /// Records the payment for the request which could not be processed in any of the attempts.
+    async fn record_processing_failure(
+        &self,
+        failure: ForcedExitProcessingFailure,
+    ) -> anyhow::Result<()>;
+    /// Records the version of the pipeline the request has been handled with at the stage.
+    async fn record_pipeline_version(
+        &self,
+        version: ForcedExitPipelineVersion,
+    ) -> anyhow::Result<()>;
+    /// Resolves the id of the account at the latest committed state, it is not cached.
+    async fn get_account_id(&self, address: Address) -> anyhow::Result<Option<AccountId>>;
+    /// Records the id the target was resolved to when the sent transactions of the request were signed.
+    async fn record_target_account_id(
+        &self,
+        id: ForcedExitRequestId,
+        target_account_id: AccountId,
+    ) -> anyhow::Result<()>;
+    /// Loads the batches, the submission of which has been interrupted, and checks whether
+    /// they have reached the mempool. Only meaningful while no batches are being sent.
+    async fn get_interrupted_submissions(&self) -> anyhow::Result<Vec<InterruptedSubmission>>;
+    /// Adds the note to the request for the operators, e.g. why it has been left in flight.
+    async fn store_note(&self, note: SaveForcedExitRequestNoteQuery) -> anyhow::Result<()>;
+    async fn get_token_address(&self, token: TokenId) -> anyhow::Result<Option<Address>>;
+    /// The symbol and the decimals of the token make up the Ethereum signed message of the transaction.
+    async fn get_token(&self, token: TokenId) -> anyhow::Result<Option<Token>>;
+    async fn store_escalation(&self, escalation: ForcedExitRequestEscalation)
+        -> anyhow::Result<()>;
+    async fn get_escalation(
+        &self,
+        id: ForcedExitRequestId,
+    ) -> anyhow::Result<Option<ForcedExitRequestEscalation>>;
+    async fn store_active_target(
+        &self,
+        active_target: ForcedExitRequestActiveTarget,
+    ) -> anyhow::Result<()>;
+    /// Loads the requests held until their targets can be forced to exit again.
+    async fn get_held_active_targets(&self) -> anyhow::Result<Vec<ForcedExitRequestActiveTarget>>;
+    async fn resume_active_target(
+        &self,
+        id: ForcedExitRequestId,
+        resumed_at: DateTime<Utc>,
+    ) -> anyhow::Result<()>;
+    async fn fail_active_target(
+        &self,
+        id: ForcedExitRequestId,
+        failed_at: DateTime<Utc>,
+    ) -> anyhow::Result<()>;
+    /// Leaves the tokens out of the request, recording why they are not withdrawn.
+    async fn skip_tokens(
+        &self,
+        id: ForcedExitRequestId,
+        skipped: &[SkippedForcedExit],
+    ) -> anyhow::Result<()>;
+    async fn store_payment(&self, payment: &ForcedExitPayment) -> anyhow::Result<()>;
+    async fn store_unmatched_payment(
+        &self,
+        payment: &ForcedExitPayment,
+        reason: UnmatchedPaymentReason,
+    ) -> anyhow::Result<()>;
+    async fn get_payment_source_states(&self) -> anyhow::Result<Vec<PaymentSourceState>>;
+    async fn get_expected_payment(
+        &self,
+        eth_tx_hash: H256,
+    ) -> anyhow::Result<Option<ExpectedForcedExitPayment>>;
+    /// Records the amount the transaction registered in advance has actually paid,
+    /// since it is not the price of the request.
+    async fn flag_expected_payment(&self, eth_tx_hash: H256, paid: &BigUint) -> anyhow::Result<()>;
+    async fn get_injected_payments(
+        &self,
+        limit: u32,
+    ) -> anyhow::Result<Vec<InjectedForcedExitPayment>>;
+    async fn delete_injected_payment(&self, id: InjectedForcedExitPaymentId) -> anyhow::Result<()>;
+    async fn get_pending_retries(&self, limit: u32) -> anyhow::Result<Vec<ForcedExitRetry>>;
+    /// Records the transactions sent by the retry or the reason it has failed.
+    async fn complete_retry(
+        &self,
+        id: ForcedExitRetryId,
+        tx_hashes: &[TxHash],
+        error: Option<&str>,
+    ) -> anyhow::Result<()>;
+    /// The pacing set by the operators instead of the configured one, if any.
+    async fn get_pacing_override(&self) -> anyhow::Result<Option<ForcedExitPacing>>;
+    async fn report_pacing(
+        &self,
+        sender: Address,
+        state: &ForcedExitPacingState,
+    ) -> anyhow::Result<()>;
+    /// Loads either the callbacks or the notifications posted to the webhook.
+    async fn get_pending_deliveries(
+        &self,
+        limit: u32,
+        callbacks: bool,
+    ) -> anyhow::Result<Vec<ForcedExitRequestDelivery>>;
+    async fn mark_delivered(&self, id: ForcedExitRequestDeliveryId) -> anyhow::Result<()>;
+    async fn record_delivery_failure(
+        &self,
+        id: ForcedExitRequestDeliveryId,
+        error: String,
+        next_attempt_at: DateTime<Utc>,
+    ) -> anyhow::Result<()>;
+    /// Records the last attempt of the callback, which is not attempted again until redelivered.
+    async fn mark_delivery_failed(
+        &self,
+        id: ForcedExitRequestDeliveryId,
+        error: String,
+    ) -> anyhow::Result<()>;
+    /// Records the refund of the payment, `None` if the payment has already been refunded.
+    async fn store_refund(
+        &self,
+        refund: SaveForcedExitRefundQuery,
+    ) -> anyhow::Result<Option<ForcedExitRefund>>;
+    async fn get_unsettled_refunds(
+        &self,
+        max_attempts: u32,
+    ) -> anyhow::Result<Vec<ForcedExitRefund>>;
+    /// The amount returned by the refunds sent since the time and not failed.
+    async fn get_refunded_amount_since(&self, since: DateTime<Utc>) -> anyhow::Result<BigUint>;
+    /// The committed balance of the account in the token, zero if the account does not exist.
+    async fn get_balance(&self, account_id: AccountId, token: TokenId) -> anyhow::Result<BigUint>;
+    /// The fee of the batch of the `ForcedExit` transactions to the target followed by
+    /// the transfer of the fee payer to itself, quoted in the token.
+    async fn get_forced_exit_batch_fee(
+        &self,
+        target: Address,
+        forced_exits: usize,
+        fee_payer: Address,
+        token: TokenId,
+    ) -> anyhow::Result<BigUint>;
+    /// Sends the transfer returning the payment and marks the refund as sent.
+    async fn send_refund(
+        &mut self,
+        id: ForcedExitRefundId,
+        tx: SignedZkSyncTx,
+    ) -> anyhow::Result<TxHash>;
+    async fn set_refund_status(
+        &self,
+        id: ForcedExitRefundId,
+        status: ForcedExitRefundStatus,
+        tx_hash: Option<TxHash>,
+    ) -> anyhow::Result<()>;
 }
 
 #[derive(Clone)]
 pub struct MempoolCoreInteractionWrapper {
-    connection_pool: ConnectionPool,
+    pools: DbPools,
     forced_exit_checker: ForcedExitChecker,
     mempool_tx_sender: mpsc::Sender<MempoolTransactionRequest>,
+    legacy_fulfilled_by: bool,
+    /// The fees are not quoted without the ticker.
+    fee_ticker: Option<FeeTicker>,
 }
 
 impl MempoolCoreInteractionWrapper {
@@ -60,17 +480,38 @@ impl MempoolCoreInteractionWrapper {
     ) -> Self {
         let forced_exit_checker = ForcedExitChecker::new(forced_exit_minimum_account_age_secs);
         Self {
-            connection_pool,
+            pools: DbPools::new(connection_pool, None),
             forced_exit_checker,
             mempool_tx_sender,
+            legacy_fulfilled_by: true,
+            fee_ticker: None,
         }
     }
+
+    /// Quotes the fees of the batches with the ticker, see `ForcedExitRequestsConfig::fee_token`.
+    pub fn with_fee_ticker(mut self, fee_ticker: FeeTicker) -> Self {
+        self.fee_ticker = Some(fee_ticker);
+        self
+    }
+
+    /// Stops writing the sent transactions to the deprecated `fulfilled_by` column.
+    pub fn with_legacy_fulfilled_by(mut self, enabled: bool) -> Self {
+        self.legacy_fulfilled_by = enabled;
+        self
+    }
+
+    /// Sends the reads which tolerate the replication lag to the given pool of the
+    /// connections to the read replica, see `DbPools`.
+    pub fn with_replica_pool(mut self, replica_pool: ConnectionPool) -> Self {
+        self.pools = DbPools::new(self.pools.primary().clone(), Some(replica_pool));
+        self
+    }
 }
 
 #[async_trait::async_trait]
 impl CoreInteractionWrapper for MempoolCoreInteractionWrapper {
     async fn get_nonce(&self, account_id: AccountId) -> anyhow::Result<Option<Nonce>> {
-        let mut storage = self.connection_pool.access_storage().await?;
+        let mut storage = self.pools.primary().access_storage().await?;
         let mut account_schema = storage.chain().account_schema();
 
         let sender_state = account_schema
@@ -81,18 +522,33 @@ impl CoreInteractionWrapper for MempoolCoreInteractionWrapper {
         Ok(sender_state.map(|state| state.nonce))
     }
 
-    async fn get_unconfirmed_requests(&self) -> anyhow::Result<Vec<ForcedExitRequest>> {
-        let mut storage = self.connection_pool.access_storage().await?;
-        let mut forced_exit_requests_schema = storage.forced_exit_requests_schema();
-        let requests = forced_exit_requests_schema
-            .get_unconfirmed_requests()
+    async fn get_pending_nonce(&self, account_id: AccountId) -> anyhow::Result<Option<Nonce>> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let nonce = storage
+            .chain()
+            .mempool_schema()
+            .get_pending_nonce(account_id)
             .await?;
 
-        Ok(requests)
+        Ok(nonce)
+    }
+
+    async fn get_unconfirmed_requests(&self) -> anyhow::Result<Vec<ForcedExitRequest>> {
+        // The requests missed because of the replication lag are reconciled on the next run.
+        self.pools
+            .read(|pool| async move {
+                let mut storage = pool.try_access_storage().await?;
+                let requests = storage
+                    .forced_exit_requests_schema()
+                    .get_unconfirmed_requests()
+                    .await?;
+                Ok(requests)
+            })
+            .await
     }
 
     async fn set_fulfilled_at(&self, id: i64) -> anyhow::Result<()> {
-        let mut storage = self.connection_pool.access_storage().await?;
+        let mut storage = self.pools.primary().access_storage().await?;
         let mut fe_schema = storage.forced_exit_requests_schema();
 
         fe_schema.set_fulfilled_at(id, Utc::now()).await?;
@@ -107,57 +563,236 @@ impl CoreInteractionWrapper for MempoolCoreInteractionWrapper {
         id: ForcedExitRequestId,
         value: Option<Vec<TxHash>>,
     ) -> anyhow::Result<()> {
-        let mut storage = self.connection_pool.access_storage().await?;
+        let mut storage = self.pools.primary().access_storage().await?;
         let mut forced_exit_requests_schema = storage.forced_exit_requests_schema();
         forced_exit_requests_schema
-            .set_fulfilled_by(id, value)
+            .set_fulfilled_by(id, value, self.legacy_fulfilled_by)
             .await?;
 
         Ok(())
     }
 
-    async fn get_receipt(&self, tx_hash: TxHash) -> anyhow::Result<Option<TxReceiptResponse>> {
-        let mut storage = self.connection_pool.access_storage().await?;
-        let receipt = storage
-            .chain()
-            .operations_ext_schema()
-            .tx_receipt(tx_hash.as_ref())
+    async fn cancel_request(
+        &self,
+        id: ForcedExitRequestId,
+        kind: ForcedExitCancellationKind,
+    ) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let cancelled = storage
+            .forced_exit_requests_schema()
+            .cancel_request(id, kind, Utc::now(), self.legacy_fulfilled_by)
+            .await?;
+        if cancelled.is_some() {
+            vlog::info!("ForcedExit request with id {} was cancelled: {}", id, kind);
+        }
+
+        Ok(())
+    }
+
+    async fn set_match_scheme(
+        &self,
+        id: ForcedExitRequestId,
+        match_scheme: PaymentMatchScheme,
+        paid_amount: &BigUint,
+        payment_tx_hash: Option<H256>,
+    ) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let mut transaction = storage.start_transaction().await?;
+        let matched_at = Utc::now();
+
+        let mut fe_schema = transaction.forced_exit_requests_schema();
+        fe_schema
+            .set_match_scheme(id, match_scheme, matched_at)
             .await?;
+        fe_schema.set_paid_amount(id, paid_amount).await?;
+        if let Some(payment_tx_hash) = payment_tx_hash {
+            fe_schema
+                .store_payment_match(id, payment_tx_hash, matched_at)
+                .await?;
+        }
+        transaction.commit().await?;
 
-        Ok(receipt)
+        Ok(())
+    }
+
+    async fn begin_fulfillment(
+        &self,
+        id: ForcedExitRequestId,
+        payment_tx_hash: Option<H256>,
+    ) -> anyhow::Result<bool> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let started = storage
+            .forced_exit_requests_schema()
+            .begin_fulfillment(id, payment_tx_hash, Utc::now())
+            .await?;
+
+        Ok(started)
+    }
+
+    async fn get_receipt(&self, tx_hash: TxHash) -> anyhow::Result<Option<TxReceiptResponse>> {
+        self.pools
+            .read(|pool| async move {
+                let mut storage = pool.try_access_storage().await?;
+                forced_exit_receipts::load_receipt(&mut storage, tx_hash).await
+            })
+            .await
+    }
+
+    async fn get_receipts(&self, tx_hashes: &[TxHash]) -> anyhow::Result<Vec<TxReceiptResponse>> {
+        self.pools
+            .read(|pool| async move {
+                let mut storage = pool.try_access_storage().await?;
+                forced_exit_receipts::load_receipts(&mut storage, tx_hashes).await
+            })
+            .await
     }
 
     async fn get_request_by_id(&self, id: i64) -> anyhow::Result<Option<ForcedExitRequest>> {
-        let mut storage = self.connection_pool.access_storage().await?;
+        let mut storage = self.pools.primary().access_storage().await?;
         let mut fe_schema = storage.forced_exit_requests_schema();
 
         let request = fe_schema.get_request_by_id(id).await?;
         Ok(request)
     }
 
+    async fn get_request_by_public_id(
+        &self,
+        public_id: ForcedExitRequestId,
+    ) -> anyhow::Result<Option<ForcedExitRequest>> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let request = storage
+            .forced_exit_requests_schema()
+            .get_request_by_public_id(public_id)
+            .await?;
+        Ok(request)
+    }
+
+    async fn get_requests_by_closest_amount(
+        &self,
+        max_amount: &BigUint,
+        submission_time: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<ForcedExitRequest>> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let requests = storage
+            .forced_exit_requests_schema()
+            .get_requests_by_closest_amount(max_amount, submission_time)
+            .await?;
+        Ok(requests)
+    }
+
     async fn send_and_save_txs_batch(
         &mut self,
         request: &ForcedExitRequest,
         txs: Vec<SignedZkSyncTx>,
     ) -> anyhow::Result<Vec<TxHash>> {
-        let mut storage = self.connection_pool.access_storage().await?;
+        ensure_batch_not_empty(request, &txs)?;
+        let mut storage = self.pools.primary().access_storage().await?;
         let mut schema = storage.forced_exit_requests_schema();
 
         let hashes: Vec<TxHash> = txs.iter().map(|tx| tx.hash()).collect();
 
-        let (sender, receiver) = oneshot::channel();
-        let item = MempoolTransactionRequest::NewTxsBatch(txs, vec![], sender);
-        self.mempool_tx_sender.send(item).await?;
-        receiver.await??;
+        // The transactions are recorded first, so the batch is not lost track of
+        // if the mempool accepts it and the server crashes before it is marked as submitted
+        schema.record_submission(request.id, &hashes).await?;
+        let submitted = submit_to_mempool(&mut self.mempool_tx_sender, |sender| {
+            MempoolTransactionRequest::NewTxsBatch(txs, vec![], sender)
+        })
+        .await;
+        if let Err(err) = submitted {
+            // The records left behind are settled by the reconciliation
+            if let Err(abort_err) = schema.abort_submission(request.id).await {
+                vlog::warn!(
+                    "Failed to remove the refused transactions of ForcedExit request {}: {}",
+                    request.id,
+                    abort_err
+                );
+            }
+            return Err(err.into());
+        }
         schema
-            .set_fulfilled_by(request.id, Some(hashes.clone()))
+            .set_fulfilled_by(request.id, Some(hashes.clone()), self.legacy_fulfilled_by)
+            .await?;
+
+        Ok(hashes)
+    }
+
+    async fn send_and_save_combined_batch(
+        &mut self,
+        parts: Vec<(ForcedExitRequestId, Vec<SignedZkSyncTx>)>,
+    ) -> anyhow::Result<Vec<Vec<TxHash>>> {
+        let ids: Vec<ForcedExitRequestId> = parts.iter().map(|(id, _)| *id).collect();
+        let (txs, hashes) = combine_batch(parts)?;
+        let mut storage = self.pools.primary().access_storage().await?;
+        let mut schema = storage.forced_exit_requests_schema();
+
+        for (id, request_hashes) in ids.iter().zip(&hashes) {
+            schema.record_submission(*id, request_hashes).await?;
+        }
+        let submitted = submit_to_mempool(&mut self.mempool_tx_sender, |sender| {
+            MempoolTransactionRequest::NewTxsBatch(txs, vec![], sender)
+        })
+        .await;
+        if let Err(err) = submitted {
+            for id in &ids {
+                if let Err(abort_err) = schema.abort_submission(*id).await {
+                    vlog::warn!(
+                        "Failed to remove the refused transactions of ForcedExit request {}: {}",
+                        id,
+                        abort_err
+                    );
+                }
+            }
+            return Err(err.into());
+        }
+        for (id, request_hashes) in ids.iter().zip(&hashes) {
+            schema
+                .set_fulfilled_by(*id, Some(request_hashes.clone()), self.legacy_fulfilled_by)
+                .await?;
+        }
+
+        Ok(hashes)
+    }
+
+    async fn send_and_save_txs_chunk(
+        &mut self,
+        request: &ForcedExitRequest,
+        sent: &[TxHash],
+        txs: Vec<SignedZkSyncTx>,
+    ) -> anyhow::Result<Vec<TxHash>> {
+        ensure_batch_not_empty(request, &txs)?;
+        let mut storage = self.pools.primary().access_storage().await?;
+        let mut schema = storage.forced_exit_requests_schema();
+
+        let hashes: Vec<TxHash> = txs.iter().map(|tx| tx.hash()).collect();
+        let fulfilled_by = append_chunk(sent, &txs);
+
+        schema
+            .record_chunk_submission(request.id, sent.len() as u32, &fulfilled_by[sent.len()..])
+            .await?;
+        let submitted = submit_to_mempool(&mut self.mempool_tx_sender, |sender| {
+            MempoolTransactionRequest::NewTxsBatch(txs, vec![], sender)
+        })
+        .await;
+        if let Err(err) = submitted {
+            // Only the chunk is removed, the previous ones have been submitted
+            if let Err(abort_err) = schema.abort_submission(request.id).await {
+                vlog::warn!(
+                    "Failed to remove the refused transactions of ForcedExit request {}: {}",
+                    request.id,
+                    abort_err
+                );
+            }
+            return Err(err.into());
+        }
+        schema
+            .set_fulfilled_by(request.id, Some(fulfilled_by), self.legacy_fulfilled_by)
             .await?;
 
         Ok(hashes)
     }
 
     async fn get_oldest_unfulfilled_request(&self) -> anyhow::Result<Option<ForcedExitRequest>> {
-        let mut storage = self.connection_pool.access_storage().await?;
+        let mut storage = self.pools.primary().access_storage().await?;
         let request = storage
             .forced_exit_requests_schema()
             .get_oldest_unfulfilled_request()
@@ -170,7 +805,7 @@ impl CoreInteractionWrapper for MempoolCoreInteractionWrapper {
         &self,
         deleting_threshold: chrono::Duration,
     ) -> anyhow::Result<()> {
-        let mut storage = self.connection_pool.access_storage().await?;
+        let mut storage = self.pools.primary().access_storage().await?;
         storage
             .forced_exit_requests_schema()
             .delete_old_unfulfilled_requests(deleting_threshold)
@@ -179,28 +814,536 @@ impl CoreInteractionWrapper for MempoolCoreInteractionWrapper {
         Ok(())
     }
 
-    async fn check_forced_exit_request(&self, request: &ForcedExitRequest) -> anyhow::Result<bool> {
-        let mut storage = self.connection_pool.access_storage().await?;
-        let target = request.target;
-        let eligible = self
+    async fn check_forced_exit_request(
+        &self,
+        request: &ForcedExitRequest,
+    ) -> anyhow::Result<ForcedExitTargetCheck> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let target_check = self
             .forced_exit_checker
-            .check_forced_exit(&mut storage, target)
+            .check_forced_exit_target(&mut storage, request.target)
             .await?;
 
-        let mut account_schema = storage.chain().account_schema();
+        Ok(target_check)
+    }
+
+    async fn record_failure(&self, id: ForcedExitRequestId, token: TokenId) -> anyhow::Result<u32> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let failures = storage
+            .forced_exit_requests_schema()
+            .record_failure(id, token)
+            .await?;
+
+        Ok(failures)
+    }
+
+    async fn record_processing_failure(
+        &self,
+        failure: ForcedExitProcessingFailure,
+    ) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        storage
+            .forced_exit_requests_schema()
+            .store_processing_failure(&failure)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn record_pipeline_version(
+        &self,
+        version: ForcedExitPipelineVersion,
+    ) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        storage
+            .forced_exit_requests_schema()
+            .store_pipeline_version(&version)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_account_id(&self, address: Address) -> anyhow::Result<Option<AccountId>> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let account_id = storage
+            .chain()
+            .account_schema()
+            .account_id_by_address(address)
+            .await?;
+
+        Ok(account_id)
+    }
+
+    async fn record_target_account_id(
+        &self,
+        id: ForcedExitRequestId,
+        target_account_id: AccountId,
+    ) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        storage
+            .forced_exit_requests_schema()
+            .set_target_account_id(id, target_account_id)
+            .await?;
+
+        Ok(())
+    }
 
-        let target_state = account_schema.account_state_by_address(target).await?;
-        let target_nonce = target_state.committed.map(|state| state.1.nonce);
+    async fn get_interrupted_submissions(&self) -> anyhow::Result<Vec<InterruptedSubmission>> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let fulfillments = storage
+            .forced_exit_requests_schema()
+            .load_unsubmitted_fulfillments()
+            .await?;
 
-        if let Some(nonce) = target_nonce {
-            // The forced exit is possible is the account is eligile (existed for long enough)
-            // and its nonce is zero
-            let possible = nonce.is_zero() && eligible;
-            Ok(possible)
-        } else {
-            // The account does exist. The ForcedExit can not be applied to account
-            // which does not exist in the network
-            Ok(false)
+        let mut submissions: Vec<InterruptedSubmission> = Vec::new();
+        for fulfillment in fulfillments {
+            match submissions.last_mut() {
+                Some(submission) if submission.request_id == fulfillment.request_id => {
+                    submission.tx_hashes.push(fulfillment.tx_hash)
+                }
+                _ => submissions.push(InterruptedSubmission {
+                    request_id: fulfillment.request_id,
+                    tx_hashes: vec![fulfillment.tx_hash],
+                    reached_mempool: false,
+                }),
+            }
         }
+        // The batch is accepted or refused as a whole, the executed transactions
+        // have left the mempool already
+        for submission in &mut submissions {
+            let first_hash = submission.tx_hashes[0];
+            submission.reached_mempool = storage
+                .chain()
+                .mempool_schema()
+                .contains_tx(first_hash)
+                .await?
+                || forced_exit_receipts::load_receipt(&mut storage, first_hash)
+                    .await?
+                    .is_some();
+            // The interrupted chunk follows the ones of the request submitted before it
+            if submission.reached_mempool {
+                submission.tx_hashes = storage
+                    .forced_exit_requests_schema()
+                    .load_fulfillments(submission.request_id)
+                    .await?
+                    .into_iter()
+                    .map(|fulfillment| fulfillment.tx_hash)
+                    .collect();
+            }
+        }
+
+        Ok(submissions)
+    }
+
+    async fn store_note(&self, note: SaveForcedExitRequestNoteQuery) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        storage
+            .forced_exit_requests_schema()
+            .store_note(note)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_token_address(&self, token: TokenId) -> anyhow::Result<Option<Address>> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let token = storage
+            .tokens_schema()
+            .get_token(TokenLike::Id(token))
+            .await?;
+
+        Ok(token.map(|token| token.address))
+    }
+
+    async fn get_token(&self, token: TokenId) -> anyhow::Result<Option<Token>> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let token = storage
+            .tokens_schema()
+            .get_token(TokenLike::Id(token))
+            .await?;
+
+        Ok(token)
+    }
+
+    async fn store_escalation(
+        &self,
+        escalation: ForcedExitRequestEscalation,
+    ) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        storage
+            .forced_exit_requests_schema()
+            .store_escalation(escalation)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_escalation(
+        &self,
+        id: ForcedExitRequestId,
+    ) -> anyhow::Result<Option<ForcedExitRequestEscalation>> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let escalation = storage
+            .forced_exit_requests_schema()
+            .get_escalation(id)
+            .await?;
+
+        Ok(escalation)
+    }
+
+    async fn store_active_target(
+        &self,
+        active_target: ForcedExitRequestActiveTarget,
+    ) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        storage
+            .forced_exit_requests_schema()
+            .store_active_target(active_target)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_held_active_targets(&self) -> anyhow::Result<Vec<ForcedExitRequestActiveTarget>> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let active_targets = storage
+            .forced_exit_requests_schema()
+            .load_held_active_targets()
+            .await?;
+
+        Ok(active_targets)
+    }
+
+    async fn resume_active_target(
+        &self,
+        id: ForcedExitRequestId,
+        resumed_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        storage
+            .forced_exit_requests_schema()
+            .resume_active_target(id, resumed_at)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn fail_active_target(
+        &self,
+        id: ForcedExitRequestId,
+        failed_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        storage
+            .forced_exit_requests_schema()
+            .fail_active_target(id, failed_at)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn skip_tokens(
+        &self,
+        id: ForcedExitRequestId,
+        skipped: &[SkippedForcedExit],
+    ) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        storage
+            .forced_exit_requests_schema()
+            .skip_tokens(id, skipped, Utc::now())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn store_payment(&self, payment: &ForcedExitPayment) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        storage
+            .forced_exit_requests_schema()
+            .store_payment(payment)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn store_unmatched_payment(
+        &self,
+        payment: &ForcedExitPayment,
+        reason: UnmatchedPaymentReason,
+    ) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        storage
+            .forced_exit_requests_schema()
+            .store_unmatched_payment(payment, reason)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_payment_source_states(&self) -> anyhow::Result<Vec<PaymentSourceState>> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let states = storage
+            .forced_exit_requests_schema()
+            .load_payment_source_states()
+            .await?;
+
+        Ok(states)
+    }
+
+    async fn get_expected_payment(
+        &self,
+        eth_tx_hash: H256,
+    ) -> anyhow::Result<Option<ExpectedForcedExitPayment>> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let expected = storage
+            .forced_exit_requests_schema()
+            .get_expected_payment(eth_tx_hash)
+            .await?;
+
+        Ok(expected)
+    }
+
+    async fn flag_expected_payment(&self, eth_tx_hash: H256, paid: &BigUint) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        storage
+            .forced_exit_requests_schema()
+            .flag_expected_payment(eth_tx_hash, paid)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_injected_payments(
+        &self,
+        limit: u32,
+    ) -> anyhow::Result<Vec<InjectedForcedExitPayment>> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let payments = storage
+            .forced_exit_requests_schema()
+            .load_injected_payments(limit)
+            .await?;
+
+        Ok(payments)
+    }
+
+    async fn delete_injected_payment(&self, id: InjectedForcedExitPaymentId) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        storage
+            .forced_exit_requests_schema()
+            .delete_injected_payment(id)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_pending_retries(&self, limit: u32) -> anyhow::Result<Vec<ForcedExitRetry>> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let retries = storage
+            .forced_exit_requests_schema()
+            .load_pending_retries(limit)
+            .await?;
+
+        Ok(retries)
+    }
+
+    async fn complete_retry(
+        &self,
+        id: ForcedExitRetryId,
+        tx_hashes: &[TxHash],
+        error: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        storage
+            .forced_exit_requests_schema()
+            .complete_retry(id, tx_hashes, error, Utc::now())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_pacing_override(&self) -> anyhow::Result<Option<ForcedExitPacing>> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let pacing = storage
+            .forced_exit_requests_schema()
+            .load_pacing_override()
+            .await?;
+
+        Ok(pacing)
+    }
+
+    async fn report_pacing(
+        &self,
+        sender: Address,
+        state: &ForcedExitPacingState,
+    ) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        storage
+            .forced_exit_requests_schema()
+            .store_pacing_state(sender, state)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_pending_deliveries(
+        &self,
+        limit: u32,
+        callbacks: bool,
+    ) -> anyhow::Result<Vec<ForcedExitRequestDelivery>> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let deliveries = storage
+            .forced_exit_requests_schema()
+            .load_pending_deliveries(Utc::now(), limit, callbacks)
+            .await?;
+
+        Ok(deliveries)
+    }
+
+    async fn mark_delivered(&self, id: ForcedExitRequestDeliveryId) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        storage
+            .forced_exit_requests_schema()
+            .mark_delivered(id, Utc::now())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn record_delivery_failure(
+        &self,
+        id: ForcedExitRequestDeliveryId,
+        error: String,
+        next_attempt_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        storage
+            .forced_exit_requests_schema()
+            .record_delivery_failure(id, &error, next_attempt_at)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn mark_delivery_failed(
+        &self,
+        id: ForcedExitRequestDeliveryId,
+        error: String,
+    ) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        storage
+            .forced_exit_requests_schema()
+            .mark_delivery_failed(id, &error, Utc::now())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn store_refund(
+        &self,
+        refund: SaveForcedExitRefundQuery,
+    ) -> anyhow::Result<Option<ForcedExitRefund>> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let refund = storage
+            .forced_exit_requests_schema()
+            .store_refund(refund)
+            .await?;
+
+        Ok(refund)
+    }
+
+    async fn get_unsettled_refunds(
+        &self,
+        max_attempts: u32,
+    ) -> anyhow::Result<Vec<ForcedExitRefund>> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let refunds = storage
+            .forced_exit_requests_schema()
+            .load_unsettled_refunds(max_attempts)
+            .await?;
+
+        Ok(refunds)
+    }
+
+    async fn get_refunded_amount_since(&self, since: DateTime<Utc>) -> anyhow::Result<BigUint> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let amount = storage
+            .forced_exit_requests_schema()
+            .refunded_amount_since(since)
+            .await?;
+
+        Ok(amount)
+    }
+
+    async fn get_balance(&self, account_id: AccountId, token: TokenId) -> anyhow::Result<BigUint> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        let account = storage
+            .chain()
+            .account_schema()
+            .last_committed_state_for_account(account_id)
+            .await?
+            .1;
+
+        Ok(account
+            .map(|account| account.get_balance(token))
+            .unwrap_or_default())
+    }
+
+    async fn get_forced_exit_batch_fee(
+        &self,
+        target: Address,
+        forced_exits: usize,
+        fee_payer: Address,
+        token: TokenId,
+    ) -> anyhow::Result<BigUint> {
+        let fee_ticker = self
+            .fee_ticker
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("The fee ticker is not configured"))?;
+        if !fee_ticker
+            .token_allowed_for_fees(TokenLike::Id(token))
+            .await?
+        {
+            anyhow::bail!("The fees can not be paid in token {}", token);
+        }
+
+        let mut txs = vec![(TxFeeTypes::Withdraw, target); forced_exits];
+        txs.push((TxFeeTypes::Transfer, fee_payer));
+        let fee = fee_ticker
+            .get_batch_from_ticker_in_wei(TokenLike::Id(token), txs)
+            .await?;
+
+        Ok(fee.normal_fee.total_fee)
+    }
+
+    async fn send_refund(
+        &mut self,
+        id: ForcedExitRefundId,
+        tx: SignedZkSyncTx,
+    ) -> anyhow::Result<TxHash> {
+        let tx_hash = tx.hash();
+
+        submit_to_mempool(&mut self.mempool_tx_sender, |sender| {
+            MempoolTransactionRequest::NewTx(Box::new(tx), sender)
+        })
+        .await?;
+        self.set_refund_status(id, ForcedExitRefundStatus::Sent, Some(tx_hash))
+            .await?;
+
+        Ok(tx_hash)
+    }
+
+    async fn set_refund_status(
+        &self,
+        id: ForcedExitRefundId,
+        status: ForcedExitRefundStatus,
+        tx_hash: Option<TxHash>,
+    ) -> anyhow::Result<()> {
+        let mut storage = self.pools.primary().access_storage().await?;
+        storage
+            .forced_exit_requests_schema()
+            .set_refund_status(id, status, tx_hash)
+            .await?;
+
+        Ok(())
     }
 }
@@ -0,0 +1,130 @@
+//! Routing of the database queries between the primary database and its read replica.
+//!
+//! Only the reads which tolerate the replication lag go to the replica: the component
+//! polls the receipts until the transactions are executed and retries the reconciliation
+//! of the sent transactions, so a stale answer only postpones the next step. Everything
+//! which decides whether to send a transaction or to write anything stays on the primary.
+
+use std::future::Future;
+
+use zksync_storage::ConnectionPool;
+
+#[derive(Debug, Clone)]
+pub struct DbPools<P = ConnectionPool> {
+    primary: P,
+    replica: Option<P>,
+}
+
+impl<P> DbPools<P> {
+    pub fn new(primary: P, replica: Option<P>) -> Self {
+        Self { primary, replica }
+    }
+
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+
+    /// Runs the query on the replica if there is one. The query is repeated on the
+    /// primary if the replica fails, so the replica being down only adds the load
+    /// to the primary rather than stopping the component.
+    pub async fn read<'a, R, F, Fut>(&'a self, query: F) -> anyhow::Result<R>
+    where
+        F: Fn(&'a P) -> Fut,
+        Fut: Future<Output = anyhow::Result<R>>,
+    {
+        if let Some(replica) = &self.replica {
+            match query(replica).await {
+                Ok(result) => return Ok(result),
+                Err(err) => {
+                    vlog::warn!(
+                        "Read from the database replica failed, falling back to the primary: {}",
+                        err
+                    );
+                    metrics::increment_counter!("forced_exit_requests.replica_fallback");
+                }
+            }
+        }
+        query(&self.primary).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Records the queries it has served instead of running them.
+    #[derive(Debug, Default)]
+    struct MockPool {
+        name: &'static str,
+        failing: bool,
+        served: Mutex<Vec<&'static str>>,
+    }
+
+    impl MockPool {
+        fn new(name: &'static str, failing: bool) -> Self {
+            Self {
+                name,
+                failing,
+                ..Default::default()
+            }
+        }
+
+        async fn query(&self, query: &'static str) -> anyhow::Result<&'static str> {
+            if self.failing {
+                anyhow::bail!("{} is down", self.name);
+            }
+            self.served.lock().unwrap().push(query);
+            Ok(self.name)
+        }
+
+        fn served(&self) -> Vec<&'static str> {
+            self.served.lock().unwrap().clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_go_to_replica() {
+        let pools = DbPools::new(
+            MockPool::new("primary", false),
+            Some(MockPool::new("replica", false)),
+        );
+
+        let served_by = pools.read(|pool| pool.query("receipts")).await.unwrap();
+        assert_eq!(served_by, "replica");
+        assert_eq!(pools.replica.as_ref().unwrap().served(), vec!["receipts"]);
+        assert!(pools.primary().served().is_empty());
+
+        // The writes are done on the primary pool directly.
+        pools.primary().query("set_fulfilled_at").await.unwrap();
+        assert_eq!(pools.primary().served(), vec!["set_fulfilled_at"]);
+        assert_eq!(pools.replica.as_ref().unwrap().served(), vec!["receipts"]);
+    }
+
+    #[tokio::test]
+    async fn reads_fall_back_to_primary() {
+        let pools = DbPools::new(
+            MockPool::new("primary", false),
+            Some(MockPool::new("replica", true)),
+        );
+        let served_by = pools.read(|pool| pool.query("receipts")).await.unwrap();
+        assert_eq!(served_by, "primary");
+        assert_eq!(pools.primary().served(), vec!["receipts"]);
+
+        let pools = DbPools::new(MockPool::new("primary", false), None);
+        let served_by = pools.read(|pool| pool.query("receipts")).await.unwrap();
+        assert_eq!(served_by, "primary");
+        assert_eq!(pools.primary().served(), vec!["receipts"]);
+    }
+
+    #[tokio::test]
+    async fn primary_error_is_returned() {
+        let pools = DbPools::new(
+            MockPool::new("primary", true),
+            Some(MockPool::new("replica", true)),
+        );
+        let err = pools.read(|pool| pool.query("receipts")).await.unwrap_err();
+        assert_eq!(err.to_string(), "primary is down");
+    }
+}
@@ -1,34 +1,54 @@
 use chrono::{DateTime, TimeZone, Utc};
-use ethabi::{Address, Hash};
+use ethabi::Address;
 use futures::channel::mpsc;
+use rand::Rng;
+use std::convert::TryInto;
 use std::{
-    convert::TryFrom,
+    collections::HashMap,
     ops::Sub,
+    sync::Arc,
     time::{Duration, Instant},
 };
-use std::{convert::TryInto, fmt::Debug};
 use tokio::task::JoinHandle;
 use tokio::time;
 use web3::{
-    contract::Contract,
     transports::Http,
-    types::{BlockNumber, FilterBuilder, Log},
+    types::{BlockNumber, FilterBuilder, Log, TransactionId},
     Web3,
 };
-use zksync_config::ForcedExitRequestsConfig;
+use zksync_api::{
+    api_server::forced_exit_maintenance::maintenance_at,
+    fee_ticker::{FeeTicker, TickerInfo},
+};
+use zksync_config::{ChainConfig, ForcedExitRequestsConfig, TickerConfig};
 use zksync_storage::ConnectionPool;
 
-use zksync_contracts::forced_exit_contract;
-use zksync_types::H160;
-
 use zksync_core::eth_watch::{get_web3_block_number, WatcherMode};
 use zksync_mempool::MempoolTransactionRequest;
-use zksync_types::forced_exit_requests::FundsReceivedEvent;
+use zksync_types::{
+    forced_exit_requests::{
+        ForcedExitMaintenance, ForcedExitPayment, FundsReceivedEvent, PaymentSource,
+        PaymentSourceState, UnmatchedPaymentReason,
+    },
+    AccountId, H256,
+};
 
-use super::prepare_forced_exit_sender::prepare_forced_exit_sender_account;
+use super::prepare_forced_exit_sender::{
+    prepare_forced_exit_sender_account, retry_sender_account_preparation,
+    SENDER_ACCOUNT_RETRY_INTERVAL,
+};
 use crate::{
     core_interaction_wrapper::{CoreInteractionWrapper, MempoolCoreInteractionWrapper},
     forced_exit_sender::MempoolForcedExitSender,
+    l1_transfer_check::L1TransferCheck,
+    pacing::SubmissionPacer,
+    payment_events::PaymentEventDecoder,
+    receipt_poller::ReceiptPoller,
+    sender_accounts::SenderAccounts,
+    shutdown::ShutdownSignal,
+    singleton::SingletonLock,
+    spawner::ForcedExitSpawner,
+    worker_pool::{ForcedExitWorkerPool, PaymentAggregation},
 };
 
 use super::ForcedExitSender;
@@ -37,20 +57,11 @@ use super::ForcedExitSender;
 /// before repeating the request.
 const RATE_LIMIT_DELAY: Duration = Duration::from_secs(30);
 
-struct ContractTopics {
-    pub funds_received: Hash,
-}
+/// The maximum number of the injected payments processed within a single poll.
+const INJECTED_PAYMENTS_BATCH_SIZE: u32 = 100;
 
-impl ContractTopics {
-    fn new(contract: &ethabi::Contract) -> Self {
-        Self {
-            funds_received: contract
-                .event("FundsReceived")
-                .expect("forced_exit contract abi error")
-                .signature(),
-        }
-    }
-}
+/// The maximum number of the retries requested by the operators processed within a single poll.
+const RETRIES_BATCH_SIZE: u32 = 10;
 
 #[async_trait::async_trait]
 pub trait EthClient {
@@ -64,38 +75,27 @@ pub trait EthClient {
 
 pub struct EthHttpClient {
     web3: Web3<Http>,
-    forced_exit_contract: Contract<Http>,
-    topics: ContractTopics,
+    decoder: PaymentEventDecoder,
 }
 
 impl EthHttpClient {
-    pub fn new(web3: Web3<Http>, zksync_contract_addr: H160) -> Self {
-        let forced_exit_contract =
-            Contract::new(web3.eth(), zksync_contract_addr, forced_exit_contract());
-
-        let topics = ContractTopics::new(forced_exit_contract.abi());
-        Self {
-            web3,
-            forced_exit_contract,
-            topics,
-        }
+    pub fn new(web3: Web3<Http>, decoder: PaymentEventDecoder) -> Self {
+        Self { web3, decoder }
     }
 
-    async fn get_events<T>(&self, from: u64, to: u64, topics: Vec<Hash>) -> anyhow::Result<Vec<T>>
-    where
-        T: TryFrom<Log>,
-        T::Error: Debug,
-    {
-        let from = BlockNumber::from(from);
-        let to = BlockNumber::from(to);
-        get_contract_events(
-            &self.web3,
-            self.forced_exit_contract.address(),
-            from,
-            to,
-            topics,
-        )
-        .await
+    /// Creates the client watching the current and the legacy forced exit contracts,
+    /// as well as all the payment addresses of the rotation schedule.
+    pub fn from_config(
+        web3_url: &str,
+        contract: Address,
+        config: &ForcedExitRequestsConfig,
+    ) -> Self {
+        let transport = web3::transports::Http::new(web3_url).unwrap();
+        let web3 = web3::Web3::new(transport);
+        let decoder = PaymentEventDecoder::new(contract, &config.legacy_contracts)
+            .and_then(|decoder| decoder.with_payment_addresses(contract, &config.payment_addresses))
+            .expect("Invalid configuration of the forced exit contracts");
+        Self::new(web3, decoder)
     }
 }
 
@@ -107,15 +107,34 @@ impl EthClient for EthHttpClient {
         to: u64,
     ) -> anyhow::Result<Vec<FundsReceivedEvent>> {
         let start = Instant::now();
-        let result = self
-            .get_events(from, to, vec![self.topics.funds_received])
-            .await;
+        // All the events of the watched contracts are requested, so that the
+        // events which can not be decoded are noticed
+        let logs = get_contract_logs(
+            &self.web3,
+            self.decoder.watched_addresses(),
+            BlockNumber::from(from),
+            BlockNumber::from(to),
+        )
+        .await?;
+
+        let mut payments = self.decoder.decode_payments(logs);
+        // The payer is not a part of the event, but it is needed to investigate the payments
+        for payment in &mut payments {
+            if let Some(tx_hash) = payment.eth_tx_hash {
+                let tx = self
+                    .web3
+                    .eth()
+                    .transaction(TransactionId::Hash(tx_hash))
+                    .await?;
+                payment.payer = tx.and_then(|tx| tx.from);
+            }
+        }
 
         metrics::histogram!(
             "forced_exit_requests.get_funds_received_events",
             start.elapsed()
         );
-        result
+        Ok(payments)
     }
 
     async fn block_number(&self) -> anyhow::Result<u64> {
@@ -133,7 +152,27 @@ where
     config: ForcedExitRequestsConfig,
     eth_client: Client,
     last_viewed_block: u64,
+    /// The payments registered in advance, which have been processed before they were
+    /// confirmed, along with their blocks. They are kept until the blocks are confirmed.
+    fast_tracked: HashMap<H256, u64>,
     forced_exit_sender: Sender,
+    /// Whether the requests sent before the restart have all been settled.
+    unconfirmed_settled: bool,
+    /// When the requests sent before are reconciled again even if they seem settled,
+    /// see `ForcedExitRequestsConfig::reconciliation_interval`.
+    next_reconciliation: Instant,
+    /// The requests are only processed while the lock is held, see the `singleton` module.
+    singleton: SingletonLock,
+    /// The scheduled maintenance in progress, nothing is passed to the sender meanwhile.
+    maintenance: Option<ForcedExitMaintenance>,
+    /// The payments received during the maintenance along with their submission times,
+    /// they are processed once it is over. The payments are kept in memory only, the ones
+    /// lost on restart can be replayed from the payment log.
+    paused_payments: Vec<(FundsReceivedEvent, DateTime<Utc>)>,
+    /// The pacing shared with the senders, the changes made by the operators are applied to it.
+    pacer: Option<Arc<SubmissionPacer>>,
+    /// Stops the polling, the sender is drained before the watcher returns.
+    shutdown: ShutdownSignal,
 
     mode: WatcherMode,
     db_cleanup_interval: chrono::Duration,
@@ -194,8 +233,16 @@ where
             config,
             eth_client,
             forced_exit_sender,
+            unconfirmed_settled: false,
+            next_reconciliation: Instant::now(),
+            singleton: SingletonLock::unguarded(),
+            maintenance: None,
+            paused_payments: Vec::new(),
+            pacer: None,
+            shutdown: ShutdownSignal::never(),
 
             last_viewed_block: 0,
+            fast_tracked: HashMap::new(),
             mode: WatcherMode::Working,
             db_cleanup_interval,
             // Zero timestamp, has never deleted anything
@@ -203,6 +250,21 @@ where
         }
     }
 
+    pub fn with_singleton_lock(mut self, singleton: SingletonLock) -> Self {
+        self.singleton = singleton;
+        self
+    }
+
+    pub fn with_pacer(mut self, pacer: Arc<SubmissionPacer>) -> Self {
+        self.pacer = Some(pacer);
+        self
+    }
+
+    pub fn with_shutdown(mut self, shutdown: ShutdownSignal) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
     pub async fn restore_state_from_eth(&mut self, block: u64) -> anyhow::Result<()> {
         let oldest_request = self
             .core_interaction_wrapper
@@ -287,7 +349,178 @@ where
             .await
     }
 
-    pub async fn poll(&mut self) {
+    // The states set by the operators take precedence over the config
+    fn is_source_enabled(&self, source: PaymentSource, states: &[PaymentSourceState]) -> bool {
+        states
+            .iter()
+            .find(|state| state.source == source)
+            .map(|state| state.enabled)
+            .unwrap_or_else(|| self.config.payment_source_enabled(source))
+    }
+
+    /// Records the payment and passes it to the sender.
+    async fn ingest_payment(
+        &mut self,
+        event: FundsReceivedEvent,
+        source: PaymentSource,
+        submission_time: DateTime<Utc>,
+    ) {
+        let payment = ForcedExitPayment::new(event.clone(), source, submission_time);
+        // Such amounts may not even fit into the storage, so they never reach the matching
+        if payment.amount > self.config.max_payment_amount {
+            self.set_aside_payment(&payment, UnmatchedPaymentReason::AmountOutOfRange)
+                .await;
+            return;
+        }
+        // The payers may still use the address they were given before the rotation
+        let retired = event.recipient.map_or(false, |recipient| {
+            self.config
+                .is_payment_address_retired(recipient, event.block_number)
+        });
+        if retired {
+            self.set_aside_payment(&payment, UnmatchedPaymentReason::AddressRetired)
+                .await;
+            return;
+        }
+
+        // The payments are recorded to be able to replay the processing later
+        if self.core_interaction_wrapper.capabilities().payment_log {
+            if let Err(err) = self.core_interaction_wrapper.store_payment(&payment).await {
+                vlog::warn!("Failed to record the forced exit payment: {}", err);
+            }
+        }
+        metrics::increment_counter!("forced_exit_requests.payments", "source" => source.as_str());
+
+        if self.maintenance.is_some() {
+            self.paused_payments.push((event, submission_time));
+            return;
+        }
+        self.process_payment(event, submission_time).await;
+    }
+
+    async fn process_payment(&mut self, event: FundsReceivedEvent, submission_time: DateTime<Utc>) {
+        // The failure has been recorded for the request by the sender, it is up to the operators now
+        if let Err(err) = self
+            .forced_exit_sender
+            .process_request(event, submission_time)
+            .await
+        {
+            vlog::error!("Failed to process the forced exit payment: {:#}", err);
+            metrics::increment_counter!("forced_exit_requests.failed_payments");
+        }
+    }
+
+    async fn set_aside_payment(&self, payment: &ForcedExitPayment, reason: UnmatchedPaymentReason) {
+        // The amount itself is not logged, it may be arbitrarily long
+        vlog::warn!(
+            "Forced exit payment {:?} in the block {} is set aside: {}",
+            payment.eth_tx_hash,
+            payment.block_number,
+            reason
+        );
+        if self.core_interaction_wrapper.capabilities().payment_log {
+            if let Err(err) = self
+                .core_interaction_wrapper
+                .store_unmatched_payment(payment, reason)
+                .await
+            {
+                vlog::warn!(
+                    "Failed to record the unmatched forced exit payment: {}",
+                    err
+                );
+            }
+        }
+        metrics::increment_counter!(
+            "forced_exit_requests.unmatched_payments",
+            "source" => payment.source.as_str(),
+            "reason" => reason.as_str()
+        );
+    }
+
+    async fn process_injected_payments(&mut self) {
+        let payments = match self
+            .core_interaction_wrapper
+            .get_injected_payments(INJECTED_PAYMENTS_BATCH_SIZE)
+            .await
+        {
+            Ok(payments) => payments,
+            Err(err) => {
+                vlog::warn!("Failed to load the injected forced exit payments: {}", err);
+                return;
+            }
+        };
+
+        for payment in payments {
+            self.ingest_payment(payment.event(), PaymentSource::Admin, payment.created_at)
+                .await;
+            // The fulfilled requests are not processed again,
+            // so it's fine to pick up the payment once more if it is not removed
+            if let Err(err) = self
+                .core_interaction_wrapper
+                .delete_injected_payment(payment.id)
+                .await
+            {
+                vlog::warn!(
+                    "Failed to remove the injected forced exit payment {}: {}",
+                    payment.id,
+                    err
+                );
+                return;
+            }
+        }
+    }
+
+    /// Sends the requests retried by the operators and records the outcomes,
+    /// which the operators are waiting for.
+    async fn process_retries(&mut self) {
+        let retries = match self
+            .core_interaction_wrapper
+            .get_pending_retries(RETRIES_BATCH_SIZE)
+            .await
+        {
+            Ok(retries) => retries,
+            Err(err) => {
+                vlog::warn!("Failed to load the forced exit retries: {}", err);
+                return;
+            }
+        };
+
+        for retry in retries {
+            let outcome = self
+                .forced_exit_sender
+                .retry_request(&retry, Utc::now())
+                .await;
+            let (tx_hashes, error) = match outcome {
+                Ok(tx_hashes) => (tx_hashes, None),
+                Err(err) => {
+                    vlog::warn!(
+                        "Failed to retry the forced exit request {}: {:#}",
+                        retry.request_id,
+                        err
+                    );
+                    (Vec::new(), Some(format!("{:#}", err)))
+                }
+            };
+            metrics::increment_counter!(
+                "forced_exit_requests.retries",
+                "outcome" => if error.is_none() { "sent" } else { "failed" }
+            );
+            if let Err(err) = self
+                .core_interaction_wrapper
+                .complete_retry(retry.id, &tx_hashes, error.as_deref())
+                .await
+            {
+                vlog::warn!(
+                    "Failed to record the outcome of the forced exit retry {}: {}",
+                    retry.id,
+                    err
+                );
+                return;
+            }
+        }
+    }
+
+    async fn process_contract_events(&mut self) {
         if !self.polling_allowed() {
             // Polling is currently disabled, skip it.
             return;
@@ -303,17 +536,31 @@ where
 
         let wait_confirmations = self.config.wait_confirmations;
         let last_confirmed_block = last_block.saturating_sub(wait_confirmations);
-        if last_confirmed_block <= self.last_viewed_block {
+        // The payments registered in advance are processed after fewer confirmations
+        let fast_confirmed_block =
+            last_block.saturating_sub(self.config.expected_payment_confirmations());
+        let fast_track = self
+            .core_interaction_wrapper
+            .capabilities()
+            .expected_payments
+            && fast_confirmed_block > last_confirmed_block;
+        let new_blocks_confirmed = last_confirmed_block > self.last_viewed_block;
+        if !new_blocks_confirmed && !fast_track {
             return;
         };
 
         let block_to_watch_from = self
             .last_viewed_block
             .saturating_sub(self.config.blocks_check_amount);
+        let block_to_watch_to = if fast_track {
+            fast_confirmed_block
+        } else {
+            last_confirmed_block
+        };
 
         let events = self
             .eth_client
-            .get_funds_received_events(block_to_watch_from, last_confirmed_block)
+            .get_funds_received_events(block_to_watch_from, block_to_watch_to)
             .await;
 
         let events = match events {
@@ -325,12 +572,233 @@ where
         };
 
         for e in events {
-            self.forced_exit_sender
-                .process_request(e.amount, lower_bound_block_time(e.block_number, last_block))
+            if e.block_number <= last_confirmed_block {
+                if !new_blocks_confirmed {
+                    continue;
+                }
+            } else if !self.should_fast_track(&e).await {
+                continue;
+            }
+            let submission_time = lower_bound_block_time(e.block_number, last_block);
+            self.ingest_payment(e, PaymentSource::L1Event, submission_time)
                 .await;
         }
+        // The confirmed payments are processed once again the usual way,
+        // the fulfilled requests are not fulfilled twice
+        self.fast_tracked
+            .retain(|_, block_number| *block_number > last_confirmed_block);
 
-        self.last_viewed_block = last_confirmed_block;
+        if new_blocks_confirmed {
+            self.last_viewed_block = last_confirmed_block;
+        }
+    }
+
+    // The payment which has not been confirmed yet is only processed if it has been
+    // registered in advance, and only once before it is confirmed
+    async fn should_fast_track(&mut self, event: &FundsReceivedEvent) -> bool {
+        let eth_tx_hash = match event.eth_tx_hash {
+            Some(eth_tx_hash) => eth_tx_hash,
+            None => return false,
+        };
+        if self.fast_tracked.contains_key(&eth_tx_hash) {
+            return false;
+        }
+        match self
+            .core_interaction_wrapper
+            .get_expected_payment(eth_tx_hash)
+            .await
+        {
+            Ok(Some(_)) => {
+                self.fast_tracked.insert(eth_tx_hash, event.block_number);
+                metrics::increment_counter!("forced_exit_requests.fast_tracked_payments");
+                true
+            }
+            Ok(None) => false,
+            Err(err) => {
+                vlog::warn!(
+                    "Failed to look up the expected forced exit payment {:?}: {}",
+                    eth_tx_hash,
+                    err
+                );
+                false
+            }
+        }
+    }
+
+    /// Settles the requests sent before the restart, so they do not compete with
+    /// the new payments for the nonces of the sender.
+    async fn reconcile_unconfirmed(&mut self, timeout: Duration) {
+        match self.forced_exit_sender.reconcile_unconfirmed(timeout).await {
+            Ok(in_flight) => self.unconfirmed_settled = in_flight == 0,
+            Err(err) => vlog::warn!(
+                "Failed to reconcile the forced exit requests sent before: {}",
+                err
+            ),
+        }
+        // The next pass is scheduled once this one is over, so the slow ones do not pile up
+        if let Some(interval) = self.config.reconciliation_interval() {
+            let jitter = rand::thread_rng().gen_range(0, self.config.reconciliation_jitter + 1);
+            let jitter = Duration::from_millis(jitter);
+            self.next_reconciliation = Instant::now() + interval + jitter;
+        }
+    }
+
+    /// Whether the periodic reconciliation is due, it settles the requests the sender is not
+    /// aware of, e.g. the ones sent by another replica before it has lost the lock.
+    fn reconciliation_due(&self) -> bool {
+        self.config.reconciliation_interval().is_some()
+            && Instant::now() >= self.next_reconciliation
+    }
+
+    /// Enters or leaves the scheduled maintenance. The gauge is set for its whole duration,
+    /// so the alerts on the requests not fulfilled in time can be muted meanwhile.
+    fn update_maintenance(&mut self, now: DateTime<Utc>) {
+        let maintenance = maintenance_at(
+            &self.config.maintenance_windows,
+            self.config.maintenance_lead_time(),
+            now,
+        );
+        match (&self.maintenance, &maintenance) {
+            (None, Some(maintenance)) => vlog::info!(
+                "The forced exit requests are paused for the maintenance until {}: {}",
+                maintenance.until,
+                maintenance.reason
+            ),
+            (Some(_), None) => vlog::info!(
+                "The maintenance is over, resuming the forced exit requests with {} payments received meanwhile",
+                self.paused_payments.len()
+            ),
+            _ => {}
+        }
+        metrics::gauge!(
+            "forced_exit_requests.maintenance",
+            if maintenance.is_some() { 1.0 } else { 0.0 }
+        );
+        self.maintenance = maintenance;
+    }
+
+    /// Applies the pacing set by the operators, or the configured one once the override is reset,
+    /// and reports the state of the pacing along with the status of the sender.
+    async fn update_pacing(&self, now: DateTime<Utc>) {
+        let pacer = match &self.pacer {
+            Some(pacer) => pacer,
+            None => return,
+        };
+        if !self
+            .core_interaction_wrapper
+            .capabilities()
+            .pacing_overrides
+        {
+            return;
+        }
+
+        // The current pacing is kept if the override can not be loaded
+        match self.core_interaction_wrapper.get_pacing_override().await {
+            Ok(pacing_override) => pacer.set_pacing(
+                pacing_override.unwrap_or_else(|| self.config.pacing()),
+                pacing_override.is_some(),
+            ),
+            Err(err) => vlog::warn!("Failed to load the forced exit pacing: {}", err),
+        }
+        let state = pacer.state(now);
+        metrics::gauge!(
+            "forced_exit_requests.paced_batches",
+            state.paced_batches as f64
+        );
+        if let Err(err) = self
+            .core_interaction_wrapper
+            .report_pacing(self.config.sender_account_address, &state)
+            .await
+        {
+            vlog::warn!("Failed to report the forced exit pacing: {}", err);
+        }
+    }
+
+    async fn process_paused_payments(&mut self) {
+        for (payment, submission_time) in std::mem::take(&mut self.paused_payments) {
+            self.process_payment(payment, submission_time).await;
+        }
+    }
+
+    pub async fn poll(&mut self) {
+        self.poll_at(Utc::now()).await;
+    }
+
+    async fn poll_at(&mut self, now: DateTime<Utc>) {
+        // No transactions are sent during the maintenance, the payments are only recorded
+        self.update_maintenance(now);
+        let paused = self.maintenance.is_some();
+        self.update_pacing(now).await;
+
+        // The requests left in flight by the startup phase or by the previous polls,
+        // the transactions of which have not been committed in time, are checked once per poll
+        if self.forced_exit_sender.has_left_in_flight() {
+            self.unconfirmed_settled = false;
+        }
+        // The rest of the requests are reconciled periodically, no new pass is started
+        // once the shutdown is requested
+        if self.reconciliation_due() && !paused && !self.shutdown.is_requested() {
+            metrics::increment_counter!("forced_exit_requests.periodic_reconciliations");
+            self.unconfirmed_settled = false;
+        }
+        if !self.unconfirmed_settled && !paused {
+            self.reconcile_unconfirmed(Duration::from_secs(0)).await;
+        }
+
+        // The sources may be disabled at any moment, e.g. if the Ethereum node returns invalid data
+        let source_states = match self
+            .core_interaction_wrapper
+            .get_payment_source_states()
+            .await
+        {
+            Ok(states) => states,
+            Err(err) => {
+                vlog::warn!("Failed to load the states of the payment sources: {}", err);
+                return;
+            }
+        };
+        for source in PaymentSource::ALL.iter().copied() {
+            let enabled = self.is_source_enabled(source, &source_states);
+            metrics::gauge!(
+                "forced_exit_requests.payment_source_enabled",
+                if enabled { 1.0 } else { 0.0 },
+                "source" => source.as_str()
+            );
+        }
+
+        // The payments received earlier are processed before the new ones
+        if !paused {
+            self.forced_exit_sender.process_deferred_requests().await;
+            self.process_paused_payments().await;
+        }
+
+        // The disabled sources are paused: their payments are neither recorded nor matched,
+        // the contract events are not even requested and the last viewed block is kept
+        // as is, so the payments made in the meantime are picked up once the source is enabled again
+        if self
+            .core_interaction_wrapper
+            .capabilities()
+            .injected_payments
+            && self.is_source_enabled(PaymentSource::Admin, &source_states)
+        {
+            self.process_injected_payments().await;
+        }
+        if self.is_source_enabled(PaymentSource::L1Event, &source_states) {
+            self.process_contract_events().await;
+        }
+        // The held requests are resumed as soon as their targets are reset. Nothing is sent
+        // by itself once the shutdown is requested, the payments only wait for the restart.
+        if !paused && !self.shutdown.is_requested() {
+            if let Err(err) = self.forced_exit_sender.process_held_requests(now).await {
+                vlog::warn!("Failed to process the held forced exit requests: {}", err);
+            }
+            if let Err(err) = self.forced_exit_sender.process_refunds().await {
+                vlog::warn!("Failed to process the forced exit refunds: {}", err);
+            }
+            if self.core_interaction_wrapper.capabilities().retries {
+                self.process_retries().await;
+            }
+        }
 
         if Utc::now().sub(self.db_cleanup_interval) > self.last_db_cleanup_time {
             if let Err(err) = self.delete_expired().await {
@@ -346,7 +814,22 @@ where
         }
     }
 
+    /// Polls unless the lock has been lost at runtime. The last viewed block is kept
+    /// in the meantime, so the payments are picked up once the lock is taken again.
+    async fn poll_if_held(&mut self) {
+        if self.singleton.is_held() {
+            self.poll().await;
+        }
+    }
+
     pub async fn run(mut self) {
+        // Even the startup phase sends the transactions, so it is not started without the lock
+        let mut shutdown = self.shutdown.clone();
+        tokio::select! {
+            _ = self.singleton.wait_held() => {}
+            _ = shutdown.requested() => return,
+        }
+
         // As infura may be not responsive, we want to retry the query until we've actually got the
         // block number.
         // Normally, however, this loop is not expected to last more than one iteration.
@@ -373,99 +856,175 @@ where
         self.restore_state_from_eth(block)
             .await
             .expect("Failed to restore state for ForcedExit eth_watcher");
+        // No new payments are processed until the transactions sent before the restart
+        // are settled or the time given to the startup phase runs out
+        self.reconcile_unconfirmed(self.config.startup_reconciliation_timeout())
+            .await;
 
         let mut timer = time::interval(self.config.poll_interval());
 
         loop {
-            timer.tick().await;
-            self.poll().await;
+            tokio::select! {
+                _ = timer.tick() => {}
+                _ = shutdown.requested() => break,
+            }
+            self.poll_if_held().await;
         }
+
+        vlog::info!("ForcedExit contract watcher is stopping, draining the sent requests");
+        self.forced_exit_sender.drain().await;
     }
 }
 
 pub fn run_forced_exit_contract_watcher(
+    spawner: &ForcedExitSpawner,
+    mut singleton: SingletonLock,
     sender: mpsc::Sender<MempoolTransactionRequest>,
     connection_pool: ConnectionPool,
     config: ForcedExitRequestsConfig,
     forced_exit_minimum_account_age_secs: u64,
     contract: Address,
+    zksync_contract: Address,
     web3_url: String,
+    mut shutdown: ShutdownSignal,
 ) -> JoinHandle<()> {
-    let transport = web3::transports::Http::new(&web3_url).unwrap();
-    let web3 = web3::Web3::new(transport);
-    let eth_client = EthHttpClient::new(web3, contract);
+    let eth_client = EthHttpClient::from_config(&web3_url, contract, &config);
 
-    tokio::spawn(async move {
+    spawner.spawn(async move {
         // We should not proceed if the feature is disabled
         if !config.enabled {
-            infinite_async_loop().await
+            // There is nothing to drain
+            drop(shutdown);
+            infinite_async_loop().await;
+            return;
+        }
+        // The account is prepared with a transaction as well
+        tokio::select! {
+            _ = singleton.wait_held() => {}
+            _ = shutdown.requested() => return,
         }
 
-        // It is fine to unwrap here, since without it there is not way we
-        // can be sure that the forced exit sender will work properly
-        let id =
-            prepare_forced_exit_sender_account(connection_pool.clone(), &config, sender.clone())
-                .await
-                .unwrap();
+        // Nothing can be sent before the sender is prepared, but the account may be funded
+        // after the deployment, so the server waits for it instead of failing
+        let id = match retry_sender_account_preparation(
+            config.sender_account_address,
+            SENDER_ACCOUNT_RETRY_INTERVAL,
+            &mut shutdown,
+            || prepare_forced_exit_sender_account(connection_pool.clone(), &config, sender.clone()),
+        )
+        .await
+        {
+            Some(id) => id,
+            None => return,
+        };
 
-        let core_interaction_wrapper = MempoolCoreInteractionWrapper::new(
+        let mut core_interaction_wrapper = MempoolCoreInteractionWrapper::new(
             forced_exit_minimum_account_age_secs,
             connection_pool.clone(),
             sender,
-        );
-        // It is ok to unwrap here, since if forced_exit_sender is not created, then
-        // the watcher is meaningless
-        let mut forced_exit_sender =
-            MempoolForcedExitSender::new(core_interaction_wrapper.clone(), config.clone(), id);
-
-        // In case there were some transactions which were submitted
-        // but were not committed we will try to wait until they are committed
-        forced_exit_sender.await_unconfirmed().await.expect(
-            "Unexpected error while trying to wait for unconfirmed forced_exit transactions",
-        );
-
-        let contract_watcher = ForcedExitContractWatcher::new(
+        )
+        .with_legacy_fulfilled_by(config.legacy_fulfilled_by_enabled);
+        if let Some(pool_size) = config.read_replica_pool_size {
+            core_interaction_wrapper = core_interaction_wrapper
+                .with_replica_pool(ConnectionPool::new_readonly_pool(Some(pool_size)));
+        }
+        // The prices the fees are quoted with are kept up to date by the API server
+        if config.fee_token.is_some() {
+            let fee_ticker = FeeTicker::new_with_default_validator(
+                Box::new(TickerInfo::new(connection_pool.clone())),
+                TickerConfig::from_env(),
+                ChainConfig::from_env().max_blocks_to_aggregate(),
+                connection_pool.clone(),
+            );
+            core_interaction_wrapper = core_interaction_wrapper.with_fee_ticker(fee_ticker);
+        }
+        run_watcher(
             core_interaction_wrapper,
             config,
             eth_client,
-            forced_exit_sender,
-            chrono::Duration::minutes(5),
-        );
-
-        contract_watcher.run().await;
+            id,
+            zksync_contract,
+            singleton,
+            shutdown,
+        )
+        .await;
     })
 }
 
-pub async fn get_contract_events<T>(
+/// Watches the payments and fulfills the requests through the given backend.
+pub(crate) async fn run_watcher<T>(
+    core_interaction_wrapper: T,
+    config: ForcedExitRequestsConfig,
+    eth_client: EthHttpClient,
+    sender_account_id: AccountId,
+    zksync_contract: Address,
+    singleton: SingletonLock,
+    shutdown: ShutdownSignal,
+) where
+    T: CoreInteractionWrapper + Clone + Send + Sync + 'static,
+{
+    // The poller stops once the sender holding its handle is dropped
+    let (receipt_poller, receipt_poller_task) = ReceiptPoller::from_config(&config);
+    let poller_interaction_wrapper = core_interaction_wrapper.clone();
+    tokio::spawn(async move { receipt_poller_task.run(&poller_interaction_wrapper).await });
+
+    // The workers send the transactions of the same accounts, so they share the nonces and the pacing
+    let sender_accounts = Arc::new(
+        SenderAccounts::resolve(&core_interaction_wrapper, &config, sender_account_id).await,
+    );
+    let pacer = Arc::new(SubmissionPacer::new(config.pacing()));
+    let l1_transfer_check = L1TransferCheck::from_config(&config);
+    let senders = (0..config.processing_workers)
+        .map(|_| {
+            let mut forced_exit_sender = MempoolForcedExitSender::new(
+                core_interaction_wrapper.clone(),
+                config.clone(),
+                sender_account_id,
+                zksync_contract,
+            )
+            .with_receipt_poller(receipt_poller.clone())
+            .with_sender_accounts(Arc::clone(&sender_accounts))
+            .with_pacer(Arc::clone(&pacer))
+            .with_shutdown(shutdown.clone());
+            if let Some(l1_transfer_check) = l1_transfer_check.clone() {
+                forced_exit_sender = forced_exit_sender.with_l1_transfer_check(l1_transfer_check);
+            }
+            forced_exit_sender
+        })
+        .collect();
+    let aggregation = PaymentAggregation {
+        window: config.aggregation_window(),
+        max_payments: config.max_aggregated_requests,
+    };
+    let forced_exit_sender = ForcedExitWorkerPool::aggregating(senders, aggregation);
+
+    let contract_watcher = ForcedExitContractWatcher::new(
+        core_interaction_wrapper,
+        config,
+        eth_client,
+        forced_exit_sender,
+        chrono::Duration::minutes(5),
+    )
+    .with_singleton_lock(singleton)
+    .with_pacer(pacer)
+    .with_shutdown(shutdown);
+
+    contract_watcher.run().await;
+}
+
+pub async fn get_contract_logs(
     web3: &Web3<Http>,
-    contract_address: Address,
+    contract_addresses: Vec<Address>,
     from: BlockNumber,
     to: BlockNumber,
-    topics: Vec<Hash>,
-) -> anyhow::Result<Vec<T>>
-where
-    T: TryFrom<Log>,
-    T::Error: Debug,
-{
+) -> anyhow::Result<Vec<Log>> {
     let filter = FilterBuilder::default()
-        .address(vec![contract_address])
+        .address(contract_addresses)
         .from_block(from)
         .to_block(to)
-        .topics(Some(topics), None, None, None)
         .build();
 
-    web3.eth()
-        .logs(filter)
-        .await?
-        .into_iter()
-        .filter_map(|event| {
-            if let Ok(event) = T::try_from(event) {
-                Some(Ok(event))
-            } else {
-                None
-            }
-        })
-        .collect()
+    Ok(web3.eth().logs(filter).await?)
 }
 
 pub async fn infinite_async_loop() {
@@ -481,9 +1040,17 @@ mod test {
     use num::{BigUint, FromPrimitive};
     use std::{str::FromStr, sync::Mutex};
 
-    use zksync_types::{forced_exit_requests::ForcedExitRequest, Address, TokenId};
+    use zksync_types::{
+        forced_exit_requests::{
+            ExpectedForcedExitPayment, ForcedExitPacing, ForcedExitRequest, ForcedExitRequestId,
+            ForcedExitRetry, InjectedForcedExitPayment, MaintenanceWindow, PaymentAddressWindow,
+        },
+        tx::TxHash,
+        Address, TokenId, H256,
+    };
 
     use super::*;
+    use crate::pacing::PacingDecision;
     use crate::test::{add_request, MockCoreInteractionWrapper};
 
     const TEST_FIRST_CURRENT_BLOCK: u64 = 10000000;
@@ -513,26 +1080,83 @@ mod test {
         }
     }
     struct DummyForcedExitSender {
-        pub processed_requests: Mutex<Vec<(BigUint, DateTime<Utc>)>>,
+        pub processed_requests: Mutex<Vec<(FundsReceivedEvent, DateTime<Utc>)>>,
+        /// The requests sent before the restart, one of them is settled per reconciliation.
+        pub in_flight: usize,
+        /// The number of the processed requests at the time of each reconciliation.
+        pub reconciliations: Vec<usize>,
+        /// The requests retried by the operators, in the order of the retries.
+        pub retried_requests: Vec<ForcedExitRequestId>,
     }
 
     impl DummyForcedExitSender {
         pub fn new() -> Self {
             Self {
                 processed_requests: Mutex::new(vec![]),
+                in_flight: 0,
+                reconciliations: vec![],
+                retried_requests: vec![],
             }
         }
     }
 
     #[async_trait::async_trait]
     impl ForcedExitSender for DummyForcedExitSender {
-        async fn process_request(&mut self, amount: BigUint, submission_time: DateTime<Utc>) {
+        async fn process_request(
+            &mut self,
+            payment: FundsReceivedEvent,
+            submission_time: DateTime<Utc>,
+        ) -> anyhow::Result<()> {
             let mut write_lock = self
                 .processed_requests
                 .lock()
                 .expect("Failed to get write lock for processed_requests");
-            (*write_lock).push((amount, submission_time));
+            (*write_lock).push((payment, submission_time));
+            Ok(())
+        }
+
+        async fn process_requests(
+            &mut self,
+            payments: Vec<(FundsReceivedEvent, DateTime<Utc>)>,
+        ) -> Vec<anyhow::Result<()>> {
+            let mut results = Vec::with_capacity(payments.len());
+            for (payment, submission_time) in payments {
+                results.push(self.process_request(payment, submission_time).await);
+            }
+            results
+        }
+
+        async fn reconcile_unconfirmed(&mut self, _timeout: Duration) -> anyhow::Result<usize> {
+            let processed = self.processed_requests.lock().unwrap().len();
+            self.reconciliations.push(processed);
+            self.in_flight = self.in_flight.saturating_sub(1);
+            Ok(self.in_flight)
+        }
+
+        fn has_left_in_flight(&self) -> bool {
+            false
+        }
+
+        async fn process_held_requests(&mut self, _now: DateTime<Utc>) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn process_deferred_requests(&mut self) {}
+
+        async fn process_refunds(&mut self) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn retry_request(
+            &mut self,
+            retry: &ForcedExitRetry,
+            _now: DateTime<Utc>,
+        ) -> anyhow::Result<Vec<TxHash>> {
+            self.retried_requests.push(retry.request_id);
+            Ok(vec![TxHash::default()])
         }
+
+        async fn drain(&mut self) {}
     }
 
     type TestForcedExitContractWatcher =
@@ -568,14 +1192,23 @@ mod test {
 
         let old_request = ForcedExitRequest {
             id: 1,
+            public_id: 1,
             target: Address::random(),
             tokens: vec![TokenId(0)],
             price_in_wei: BigUint::from_i64(12).unwrap(),
+            pay_exactly: "13".to_owned(),
             valid_until: Utc::now().sub(week),
             // Outdated by far
             created_at: Utc::now().sub(week).sub(three_days),
             fulfilled_at: None,
             fulfilled_by: None,
+            match_scheme: None,
+            matched_at: None,
+            cancellation: None,
+            paid_amount: None,
+            metadata: None,
+            status: Default::default(),
+            payment_terms: None,
         };
 
         add_request(
@@ -630,15 +1263,24 @@ mod test {
         let mut watcher = get_test_forced_exit_contract_watcher();
         watcher.core_interaction_wrapper.requests = Mutex::new(vec![ForcedExitRequest {
             id: 1,
+            public_id: 1,
             target: Address::random(),
             tokens: vec![TokenId(0)],
             price_in_wei: BigUint::from_i64(12).unwrap(),
+            pay_exactly: "13".to_owned(),
             // does not matter in these tests
             valid_until: Utc::now(),
             // millisecond ago is quite young
             created_at: Utc::now().sub(chrono::Duration::milliseconds(1)),
             fulfilled_at: None,
             fulfilled_by: None,
+            match_scheme: None,
+            matched_at: None,
+            cancellation: None,
+            paid_amount: None,
+            metadata: None,
+            status: Default::default(),
+            payment_terms: None,
         }]);
 
         watcher
@@ -655,15 +1297,24 @@ mod test {
         let mut watcher = get_test_forced_exit_contract_watcher();
         watcher.core_interaction_wrapper.requests = Mutex::new(vec![ForcedExitRequest {
             id: 1,
+            public_id: 1,
             target: Address::random(),
             tokens: vec![TokenId(0)],
             price_in_wei: BigUint::from_i64(12).unwrap(),
+            pay_exactly: "13".to_owned(),
             // does not matter in these tests
             valid_until: Utc::now(),
             // 1 week ago is quite old
             created_at: Utc::now().sub(chrono::Duration::weeks(1)),
             fulfilled_at: None,
             fulfilled_by: None,
+            match_scheme: None,
+            matched_at: None,
+            cancellation: None,
+            paid_amount: None,
+            metadata: None,
+            status: Default::default(),
+            payment_terms: None,
         }]);
 
         watcher
@@ -687,17 +1338,29 @@ mod test {
             FundsReceivedEvent {
                 // Should be processed
                 amount: BigUint::from_str("1000000001").unwrap(),
+                request_id: None,
                 block_number: TEST_FIRST_CURRENT_BLOCK - 2 * wait_confirmations,
+                eth_tx_hash: Some(H256::repeat_byte(0x01)),
+                payer: Some(Address::repeat_byte(0x12)),
+                recipient: None,
             },
             FundsReceivedEvent {
                 amount: BigUint::from_str("1000000002").unwrap(),
+                request_id: Some(2),
                 // Should be processed
                 block_number: TEST_FIRST_CURRENT_BLOCK - wait_confirmations - 1,
+                eth_tx_hash: Some(H256::repeat_byte(0x02)),
+                payer: Some(Address::repeat_byte(0x12)),
+                recipient: None,
             },
             FundsReceivedEvent {
                 amount: BigUint::from_str("1000000003").unwrap(),
+                request_id: None,
                 // Should not be processed
                 block_number: TEST_FIRST_CURRENT_BLOCK - 1,
+                eth_tx_hash: Some(H256::repeat_byte(0x03)),
+                payer: Some(Address::repeat_byte(0x12)),
+                recipient: None,
             },
         ];
 
@@ -722,12 +1385,628 @@ mod test {
         // and it is easier to test this way
         assert_eq!(processed_requests.len(), 2);
         assert_eq!(
-            processed_requests[0].0,
+            processed_requests[0].0.amount,
             BigUint::from_str("1000000001").unwrap()
         );
         assert_eq!(
-            processed_requests[1].0,
+            processed_requests[1].0.amount,
             BigUint::from_str("1000000002").unwrap()
         );
+        // The explicit id must be passed to the sender as is
+        assert_eq!(processed_requests[1].0.request_id, Some(2));
+
+        // The processed payments are recorded along with the time they are submitted at
+        let payments = watcher.core_interaction_wrapper.payments.lock().unwrap();
+        assert_eq!(payments.len(), 2);
+        assert_eq!(payments[1].request_id, Some(2));
+        assert_eq!(payments[1].eth_tx_hash, Some(H256::repeat_byte(0x02)));
+        assert_eq!(payments[1].received_at, processed_requests[1].1);
+        assert_eq!(payments[1].source, PaymentSource::L1Event);
+    }
+
+    #[tokio::test]
+    async fn test_watcher_fast_tracks_expected_payments() {
+        let mut watcher = get_test_forced_exit_contract_watcher();
+        watcher.config.wait_confirmations = 5;
+        watcher.config.expected_payment_wait_confirmations = 1;
+
+        let payment = |hash: u8| FundsReceivedEvent {
+            amount: BigUint::from(1_000_000_001u64),
+            request_id: None,
+            block_number: TEST_FIRST_CURRENT_BLOCK - 1,
+            eth_tx_hash: Some(H256::repeat_byte(hash)),
+            payer: Some(Address::repeat_byte(0x12)),
+            recipient: None,
+        };
+        watcher.eth_client.events = vec![payment(0x01), payment(0x02)];
+        watcher
+            .core_interaction_wrapper
+            .lock_expected_payments()
+            .push(ExpectedForcedExitPayment {
+                request_id: 1,
+                eth_tx_hash: H256::repeat_byte(0x01),
+                registered_at: Utc::now(),
+                mismatched_amount: None,
+            });
+        let processed = |watcher: &TestForcedExitContractWatcher| -> Vec<H256> {
+            watcher
+                .forced_exit_sender
+                .processed_requests
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(|(payment, _)| payment.eth_tx_hash)
+                .collect()
+        };
+
+        watcher
+            .restore_state_from_eth(TEST_FIRST_CURRENT_BLOCK)
+            .await
+            .expect("Failed to restore state from eth");
+
+        // Only the registered payment is processed before it is confirmed, and only once
+        watcher.poll().await;
+        watcher.poll().await;
+        assert_eq!(processed(&watcher), vec![H256::repeat_byte(0x01)]);
+        assert_eq!(watcher.last_viewed_block, TEST_FIRST_CURRENT_BLOCK - 5);
+
+        // Once confirmed, both payments are processed the usual way
+        watcher.eth_client.current_block_number = TEST_FIRST_CURRENT_BLOCK + 5;
+        watcher.poll().await;
+        assert_eq!(
+            processed(&watcher),
+            vec![
+                H256::repeat_byte(0x01),
+                H256::repeat_byte(0x01),
+                H256::repeat_byte(0x02)
+            ]
+        );
+        assert!(watcher.fast_tracked.is_empty());
+    }
+
+    fn injected_payment(id: i64, created_at: DateTime<Utc>) -> InjectedForcedExitPayment {
+        InjectedForcedExitPayment {
+            id,
+            amount: BigUint::from(1_000_000_000u64 + id as u64),
+            request_id: None,
+            block_number: 100,
+            eth_tx_hash: None,
+            created_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_watcher_singleton_lock() {
+        let wait_confirmations = 5;
+        let payment = |id: i64, block_number: u64| FundsReceivedEvent {
+            amount: BigUint::from(1_000_000_000u64 + id as u64),
+            request_id: Some(id),
+            block_number,
+            eth_tx_hash: Some(H256::from_low_u64_be(id as u64)),
+            payer: Some(Address::repeat_byte(0x12)),
+            recipient: None,
+        };
+        let processed = |watcher: &TestForcedExitContractWatcher| -> Vec<Option<i64>> {
+            watcher
+                .forced_exit_sender
+                .processed_requests
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(payment, _)| payment.request_id)
+                .collect()
+        };
+
+        // Two instances watch the same payments, only the first one holds the lock
+        let (first_held, first_lock) = SingletonLock::manual(true);
+        let (second_held, second_lock) = SingletonLock::manual(false);
+        let mut instances = Vec::new();
+        for lock in [first_lock, second_lock] {
+            let mut watcher = get_test_forced_exit_contract_watcher().with_singleton_lock(lock);
+            watcher.config.wait_confirmations = wait_confirmations;
+            watcher.eth_client.events = vec![payment(1, 100)];
+            watcher
+                .restore_state_from_eth(100)
+                .await
+                .expect("Failed to restore state from eth");
+            watcher.eth_client.current_block_number = TEST_FIRST_CURRENT_BLOCK;
+            instances.push(watcher);
+        }
+
+        for watcher in &mut instances {
+            watcher.poll_if_held().await;
+        }
+        assert_eq!(processed(&instances[0]), vec![Some(1)]);
+        assert!(processed(&instances[1]).is_empty());
+
+        // The first instance loses the lock and the second one takes it over,
+        // picking up the payments it has not processed while waiting
+        first_held.send(false).unwrap();
+        second_held.send(true).unwrap();
+        for watcher in &mut instances {
+            watcher
+                .eth_client
+                .events
+                .push(payment(2, TEST_FIRST_CURRENT_BLOCK));
+            watcher.eth_client.current_block_number =
+                TEST_FIRST_CURRENT_BLOCK + 2 * wait_confirmations;
+            watcher.poll_if_held().await;
+        }
+        assert_eq!(processed(&instances[0]), vec![Some(1)]);
+        assert_eq!(processed(&instances[1]), vec![Some(1), Some(2)]);
+    }
+
+    #[tokio::test]
+    async fn test_watcher_payment_sources() {
+        let mut watcher = get_test_forced_exit_contract_watcher();
+        // The contract events are disabled by the config
+        watcher.config.l1_payments_enabled = false;
+        watcher.config.wait_confirmations = 0;
+        watcher.eth_client.events = vec![FundsReceivedEvent {
+            amount: BigUint::from_str("2000000001").unwrap(),
+            request_id: None,
+            block_number: TEST_FIRST_CURRENT_BLOCK - 1,
+            eth_tx_hash: Some(H256::repeat_byte(0x01)),
+            payer: Some(Address::repeat_byte(0x12)),
+            recipient: None,
+        }];
+        watcher
+            .restore_state_from_eth(TEST_FIRST_CURRENT_BLOCK - 2)
+            .await
+            .expect("Failed to restore state from eth");
+        let injected_at = Utc::now().sub(chrono::Duration::minutes(1));
+        watcher
+            .core_interaction_wrapper
+            .lock_injected_payments()
+            .push(injected_payment(1, injected_at));
+
+        // Only the injected payment is processed
+        watcher.poll().await;
+        {
+            let processed_requests = watcher
+                .forced_exit_sender
+                .processed_requests
+                .lock()
+                .unwrap();
+            assert_eq!(processed_requests.len(), 1);
+            assert_eq!(
+                processed_requests[0].0.amount,
+                BigUint::from(1_000_000_001u64)
+            );
+            assert_eq!(processed_requests[0].1, injected_at);
+
+            let payments = watcher.core_interaction_wrapper.payments.lock().unwrap();
+            assert_eq!(payments.len(), 1);
+            assert_eq!(payments[0].source, PaymentSource::Admin);
+        }
+        assert!(watcher
+            .core_interaction_wrapper
+            .lock_injected_payments()
+            .is_empty());
+        // The events are picked up once the source is enabled again
+        assert_eq!(watcher.last_viewed_block, TEST_FIRST_CURRENT_BLOCK - 2);
+
+        // The operators enable the contract events and disable the injected payments at runtime
+        *watcher
+            .core_interaction_wrapper
+            .payment_source_states
+            .lock()
+            .unwrap() = vec![
+            PaymentSourceState {
+                source: PaymentSource::L1Event,
+                enabled: true,
+                updated_at: Utc::now(),
+            },
+            PaymentSourceState {
+                source: PaymentSource::Admin,
+                enabled: false,
+                updated_at: Utc::now(),
+            },
+        ];
+        watcher
+            .core_interaction_wrapper
+            .lock_injected_payments()
+            .push(injected_payment(2, injected_at));
+
+        watcher.poll().await;
+        let processed_requests = watcher
+            .forced_exit_sender
+            .processed_requests
+            .lock()
+            .unwrap();
+        assert_eq!(processed_requests.len(), 2);
+        assert_eq!(
+            processed_requests[1].0.amount,
+            BigUint::from(2_000_000_001u64)
+        );
+
+        let payments = watcher.core_interaction_wrapper.payments.lock().unwrap();
+        assert_eq!(payments.len(), 2);
+        assert_eq!(payments[1].source, PaymentSource::L1Event);
+        // The injected payment waits until the source is enabled
+        assert_eq!(
+            watcher
+                .core_interaction_wrapper
+                .lock_injected_payments()
+                .len(),
+            1
+        );
+        assert_eq!(watcher.last_viewed_block, TEST_FIRST_CURRENT_BLOCK);
+    }
+
+    #[tokio::test]
+    async fn test_watcher_payment_amount_out_of_range() {
+        let mut watcher = get_test_forced_exit_contract_watcher();
+        let max_amount = watcher.config.max_payment_amount.clone();
+        let one = BigUint::from(1u32);
+
+        let mut enormous_amounts = vec![
+            &max_amount + &one,
+            &max_amount * 10u32 + 12u32,
+            // The largest amount the contract events can carry
+            (BigUint::from(1u32) << 256) - &one,
+        ];
+        for bits in (80..=4096).step_by(53) {
+            enormous_amounts.push((BigUint::from(1u32) << bits) + 1_000_000_012u64);
+        }
+        for digits in &[25u32, 77, 1000, 200_000] {
+            enormous_amounts.push(BigUint::from(10u32).pow(*digits) + 12u32);
+        }
+        enormous_amounts.retain(|amount| *amount > max_amount);
+
+        let now = Utc::now();
+        for (block_number, amount) in enormous_amounts.iter().enumerate() {
+            let event = FundsReceivedEvent {
+                amount: amount.clone(),
+                request_id: Some(12),
+                block_number: block_number as u64,
+                eth_tx_hash: None,
+                payer: None,
+                recipient: None,
+            };
+            watcher
+                .ingest_payment(event, PaymentSource::L1Event, now)
+                .await;
+        }
+        // The largest amount in range is matched as usual
+        let event = FundsReceivedEvent {
+            amount: max_amount.clone(),
+            request_id: None,
+            block_number: 0,
+            eth_tx_hash: None,
+            payer: None,
+            recipient: None,
+        };
+        watcher
+            .ingest_payment(event, PaymentSource::L1Event, now)
+            .await;
+
+        let processed_requests = watcher
+            .forced_exit_sender
+            .processed_requests
+            .lock()
+            .unwrap();
+        assert_eq!(processed_requests.len(), 1);
+        assert_eq!(processed_requests[0].0.amount, max_amount);
+
+        let payments = watcher.core_interaction_wrapper.payments.lock().unwrap();
+        assert_eq!(payments.len(), 1);
+
+        let unmatched_payments = watcher
+            .core_interaction_wrapper
+            .unmatched_payments
+            .lock()
+            .unwrap();
+        assert_eq!(unmatched_payments.len(), enormous_amounts.len());
+        for ((payment, reason), amount) in unmatched_payments.iter().zip(&enormous_amounts) {
+            assert_eq!(&payment.amount, amount);
+            assert_eq!(payment.request_id, Some(12));
+            assert_eq!(*reason, UnmatchedPaymentReason::AmountOutOfRange);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_watcher_payment_address_rotation() {
+        let mut watcher = get_test_forced_exit_contract_watcher();
+        let old_address = Address::repeat_byte(0x01);
+        let new_address = Address::repeat_byte(0x02);
+        // The addresses overlap within the blocks 150..=200
+        watcher.config.payment_addresses = vec![
+            PaymentAddressWindow {
+                address: old_address,
+                first_block: 0,
+                last_block: Some(200),
+            },
+            PaymentAddressWindow {
+                address: new_address,
+                first_block: 150,
+                last_block: None,
+            },
+        ];
+        assert_eq!(
+            watcher.config.active_payment_address(Address::zero()),
+            new_address
+        );
+
+        let payments = [
+            (old_address, 100, false),
+            (old_address, 180, false),
+            (new_address, 180, false),
+            (new_address, 250, false),
+            (old_address, 201, true),
+            (new_address, 149, true),
+            // The address not listed in the schedule is never retired
+            (Address::repeat_byte(0x03), 250, false),
+        ];
+        let now = Utc::now();
+        for (i, (recipient, block_number, _)) in payments.iter().enumerate() {
+            let event = FundsReceivedEvent {
+                amount: BigUint::from(1_000_000_000u64 + i as u64),
+                request_id: None,
+                block_number: *block_number,
+                eth_tx_hash: Some(H256::from_low_u64_be(i as u64)),
+                payer: None,
+                recipient: Some(*recipient),
+            };
+            watcher
+                .ingest_payment(event, PaymentSource::L1Event, now)
+                .await;
+        }
+
+        let expected_processed: Vec<_> = payments
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, _, retired))| !retired)
+            .map(|(i, _)| H256::from_low_u64_be(i as u64))
+            .collect();
+        let processed: Vec<_> = watcher
+            .forced_exit_sender
+            .processed_requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(event, _)| event.eth_tx_hash.unwrap())
+            .collect();
+        assert_eq!(processed, expected_processed);
+
+        let unmatched_payments = watcher
+            .core_interaction_wrapper
+            .unmatched_payments
+            .lock()
+            .unwrap();
+        let retired: Vec<_> = unmatched_payments
+            .iter()
+            .map(|(payment, reason)| {
+                assert_eq!(*reason, UnmatchedPaymentReason::AddressRetired);
+                payment.block_number
+            })
+            .collect();
+        assert_eq!(retired, vec![201, 149]);
+    }
+
+    #[tokio::test]
+    async fn test_watcher_reconciles_before_processing() {
+        let mut watcher = get_test_forced_exit_contract_watcher();
+        watcher.forced_exit_sender.in_flight = 2;
+        watcher.eth_client.events = vec![FundsReceivedEvent {
+            amount: BigUint::from_str("1000000001").unwrap(),
+            request_id: None,
+            block_number: TEST_FIRST_CURRENT_BLOCK - 2 * watcher.config.wait_confirmations,
+            eth_tx_hash: Some(H256::repeat_byte(0x01)),
+            payer: Some(Address::repeat_byte(0x12)),
+            recipient: None,
+        }];
+
+        watcher
+            .restore_state_from_eth(100)
+            .await
+            .expect("Failed to restore state from eth");
+        watcher
+            .reconcile_unconfirmed(watcher.config.startup_reconciliation_timeout())
+            .await;
+        // One of the requests is still in flight after the startup phase
+        assert!(!watcher.unconfirmed_settled);
+
+        watcher.poll().await;
+        // The poll retries the reconciliation before it processes the new payment
+        assert_eq!(watcher.forced_exit_sender.reconciliations, vec![0, 0]);
+        assert!(watcher.unconfirmed_settled);
+        assert_eq!(
+            watcher
+                .forced_exit_sender
+                .processed_requests
+                .lock()
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // Once everything is settled, the polls do not reconcile anymore
+        watcher.poll().await;
+        assert_eq!(watcher.forced_exit_sender.reconciliations.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_watcher_reconciles_periodically() {
+        let mut watcher = get_test_forced_exit_contract_watcher();
+        watcher.config.reconciliation_interval = 60_000;
+        watcher.config.reconciliation_jitter = 0;
+        watcher
+            .restore_state_from_eth(100)
+            .await
+            .expect("Failed to restore state from eth");
+        watcher
+            .reconcile_unconfirmed(watcher.config.startup_reconciliation_timeout())
+            .await;
+        assert!(watcher.unconfirmed_settled);
+
+        // The settled requests are not reconciled again until the interval passes
+        watcher.poll().await;
+        assert_eq!(watcher.forced_exit_sender.reconciliations.len(), 1);
+        watcher.next_reconciliation = Instant::now();
+        watcher.poll().await;
+        assert_eq!(watcher.forced_exit_sender.reconciliations.len(), 2);
+        // The pass schedules the next one
+        assert!(watcher.next_reconciliation > Instant::now());
+        watcher.poll().await;
+        assert_eq!(watcher.forced_exit_sender.reconciliations.len(), 2);
+
+        // Nothing is reconciled periodically with the zero interval
+        watcher.config.reconciliation_interval = 0;
+        watcher.next_reconciliation = Instant::now();
+        watcher.poll().await;
+        assert_eq!(watcher.forced_exit_sender.reconciliations.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_watcher_pauses_for_maintenance() {
+        let mut watcher = get_test_forced_exit_contract_watcher();
+        let start = Utc::now() + chrono::Duration::hours(1);
+        let end = start + chrono::Duration::hours(1);
+        watcher.config.maintenance_lead_time = 5 * 60 * 1000;
+        watcher.config.maintenance_windows = vec![MaintenanceWindow {
+            start,
+            end,
+            reason: "Database migration".to_owned(),
+            recurrence: None,
+        }];
+        watcher.forced_exit_sender.in_flight = 1;
+        watcher.eth_client.events = vec![FundsReceivedEvent {
+            amount: BigUint::from_str("1000000001").unwrap(),
+            request_id: None,
+            block_number: TEST_FIRST_CURRENT_BLOCK - 2 * watcher.config.wait_confirmations,
+            eth_tx_hash: Some(H256::repeat_byte(0x01)),
+            payer: Some(Address::repeat_byte(0x12)),
+            recipient: None,
+        }];
+        watcher
+            .restore_state_from_eth(100)
+            .await
+            .expect("Failed to restore state from eth");
+
+        // The submissions are paused shortly before the window, the payment is only recorded
+        watcher.poll_at(start - chrono::Duration::minutes(4)).await;
+        watcher.poll_at(start + chrono::Duration::minutes(30)).await;
+        assert_eq!(
+            watcher
+                .maintenance
+                .as_ref()
+                .map(|maintenance| maintenance.until),
+            Some(end)
+        );
+        assert!(watcher
+            .forced_exit_sender
+            .processed_requests
+            .lock()
+            .unwrap()
+            .is_empty());
+        assert!(watcher.forced_exit_sender.reconciliations.is_empty());
+        assert_eq!(
+            watcher
+                .core_interaction_wrapper
+                .payments
+                .lock()
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // Once the window is over, the payment is processed as received at the time it was recorded
+        watcher.poll_at(end).await;
+        assert!(watcher.maintenance.is_none());
+        assert_eq!(watcher.forced_exit_sender.reconciliations, vec![0]);
+        let processed_requests = watcher
+            .forced_exit_sender
+            .processed_requests
+            .lock()
+            .unwrap();
+        assert_eq!(processed_requests.len(), 1);
+        assert_eq!(
+            processed_requests[0].1,
+            watcher.core_interaction_wrapper.payments.lock().unwrap()[0].received_at
+        );
+        assert!(watcher.paused_payments.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_watcher_processes_retries() {
+        let mut watcher = get_test_forced_exit_contract_watcher();
+        watcher
+            .restore_state_from_eth(TEST_FIRST_CURRENT_BLOCK - 2)
+            .await
+            .expect("Failed to restore state from eth");
+        let retry = |id: i64, request_id: i64| ForcedExitRetry {
+            id,
+            request_id,
+            force: false,
+            created_at: Utc::now(),
+            processed_at: None,
+            tx_hashes: vec![],
+            error: None,
+        };
+        watcher
+            .core_interaction_wrapper
+            .lock_retries()
+            .extend(vec![retry(1, 12), retry(2, 34)]);
+
+        // The retries are sent once and completed with the hashes of the transactions
+        watcher.poll().await;
+        watcher.poll().await;
+        assert_eq!(watcher.forced_exit_sender.retried_requests, vec![12, 34]);
+        for retry in watcher.core_interaction_wrapper.lock_retries().iter() {
+            assert!(retry.is_processed());
+            assert_eq!(retry.tx_hashes, vec![TxHash::default()]);
+            assert_eq!(retry.error, None);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_watcher_applies_pacing_overrides() {
+        let config = ForcedExitRequestsConfig {
+            max_batches_per_minute: 10,
+            batches_per_block: 0,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let pacer = Arc::new(SubmissionPacer::new(config.pacing()));
+        let mut watcher = get_test_forced_exit_contract_watcher().with_pacer(pacer.clone());
+        watcher.config = config.clone();
+        watcher
+            .restore_state_from_eth(TEST_FIRST_CURRENT_BLOCK - 2)
+            .await
+            .expect("Failed to restore state from eth");
+
+        // The configured pacing is reported while there is no override
+        watcher.poll().await;
+        let reported = watcher
+            .core_interaction_wrapper
+            .lock_reported_pacing()
+            .clone()
+            .unwrap();
+        assert_eq!(reported.pacing, config.pacing());
+        assert!(!reported.overridden);
+        assert_eq!(reported.available_batches, Some(10));
+
+        // The operators slow the sender down at runtime, the batches sent so far are kept
+        while let PacingDecision::Send = pacer.next_batch() {}
+        let slowed = ForcedExitPacing {
+            max_batches_per_minute: 2,
+            batches_per_block: 1,
+        };
+        *watcher.core_interaction_wrapper.lock_pacing_override() = Some(slowed);
+        watcher.poll().await;
+        let reported = watcher
+            .core_interaction_wrapper
+            .lock_reported_pacing()
+            .clone()
+            .unwrap();
+        assert_eq!(reported.pacing, slowed);
+        assert!(reported.overridden);
+        assert_eq!(reported.available_batches, Some(0));
+
+        // The configured pacing is restored once the override is reset
+        *watcher.core_interaction_wrapper.lock_pacing_override() = None;
+        watcher.poll().await;
+        assert_eq!(pacer.state(Utc::now()).pacing, config.pacing());
+        assert!(!pacer.state(Utc::now()).overridden);
     }
 }
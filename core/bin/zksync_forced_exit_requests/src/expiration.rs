@@ -0,0 +1,74 @@
+//! Expiration of the requests which have not been paid for.
+//!
+//! The ids of the requests are encoded in the lowest `digits_in_id` digits of the paid amounts,
+//! so the ids held by the requests nobody pays for would fill the id space sooner or later.
+//! Once the grace period after the end of its validity period is over, the unpaid request
+//! is expired and its id is released, the new requests are then allocated the released ids
+//! first. The grace period leaves the time for the payments sent just before the end of the
+//! validity period to arrive: until it is over, such a payment is still matched with the request
+//! (and refunded), later it would be matched with the request the id is allocated to.
+
+use chrono::Utc;
+use tokio::{task::JoinHandle, time};
+use zksync_config::ForcedExitRequestsConfig;
+use zksync_storage::ConnectionPool;
+
+use crate::spawner::ForcedExitSpawner;
+
+/// Expires the requests the grace period of which is over, a batch per transaction,
+/// returns the number of the requests expired. The grace period is the one the request
+/// was created with, `default_grace_period` applies to the requests stored without it.
+pub async fn expire_unpaid_requests(
+    connection_pool: &ConnectionPool,
+    default_grace_period: chrono::Duration,
+    batch_size: u32,
+) -> anyhow::Result<u32> {
+    let now = Utc::now();
+
+    let mut storage = connection_pool.access_storage().await?;
+    let mut expired = 0;
+    loop {
+        let batch = storage
+            .forced_exit_requests_schema()
+            .expire_unpaid_requests(now, default_grace_period, batch_size)
+            .await?;
+        expired += batch;
+        metrics::counter!("forced_exit_requests.expired_requests", u64::from(batch));
+        if batch < batch_size {
+            break;
+        }
+    }
+
+    if expired > 0 {
+        vlog::info!(
+            "{} ForcedExit requests have expired unpaid, their ids are released",
+            expired
+        );
+    }
+    Ok(expired)
+}
+
+/// Runs the expiration periodically, whether the requests are processed by this server or not.
+/// The requests are locked while being expired, so the servers running it at once
+/// expire the different ones.
+pub fn run_expiration_sweeper(
+    spawner: &ForcedExitSpawner,
+    connection_pool: ConnectionPool,
+    config: &ForcedExitRequestsConfig,
+) -> JoinHandle<()> {
+    let interval = config.expiration_sweep_interval();
+    let grace_period = config.expiration_grace_period();
+    let batch_size = config.expiration_sweep_batch_size.max(1);
+
+    spawner.spawn(async move {
+        let mut timer = time::interval(interval);
+        loop {
+            timer.tick().await;
+            if let Err(err) =
+                expire_unpaid_requests(&connection_pool, grace_period, batch_size).await
+            {
+                vlog::warn!("Failed to expire the unpaid ForcedExit requests: {}", err);
+            }
+        }
+    })
+}
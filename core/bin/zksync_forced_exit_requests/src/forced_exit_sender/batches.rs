@@ -0,0 +1,985 @@
+//! Sending the transactions of the paid requests, together or in chunks, and awaiting
+//! their commitment.
+
+use std::time::{Duration, Instant};
+
+use zksync_api::api_server::forced_exit_preflight::{
+    renumber, split_into_batches, with_fee_payment,
+};
+use zksync_types::{
+    forced_exit_requests::{
+        ForcedExitCancellationKind, ForcedExitPreflight, ForcedExitRequest, ForcedExitRequestId,
+        PaymentMatchScheme, SubmissionError, SubmissionErrorKind,
+    },
+    tx::TxHash,
+    AccountId,
+};
+
+use crate::{core_interaction_wrapper::CoreInteractionWrapper, sender_accounts::SenderLease};
+
+use super::{
+    committed_prefix, fees::is_fee_too_low, ClaimedRequest, CommitError, MempoolForcedExitSender,
+    PaymentDecision, SentBatch, ShuttingDown, TargetNotFound,
+};
+
+impl<T: CoreInteractionWrapper + Sync> MempoolForcedExitSender<T> {
+    /// Sends the transactions of the claimed requests in a single batch and waits for it
+    /// to be committed. The decisions are returned in the order of the requests, `None` for
+    /// the requests which have been released to be processed on their own.
+    pub(super) async fn send_together(
+        &mut self,
+        requests: Vec<ClaimedRequest>,
+    ) -> Vec<Option<PaymentDecision>> {
+        let mut decisions = vec![None; requests.len()];
+        // The request, the target of which can not be resolved, does not keep the rest from being sent
+        let mut batch = Vec::with_capacity(requests.len());
+        for (i, request) in requests.into_iter().enumerate() {
+            match self.resolve_target(&request.fe_request).await {
+                Ok(target_account_id) => batch.push((i, request, target_account_id)),
+                Err(err) => self.release(request.fe_request.id, &err).await,
+            }
+        }
+        if batch.is_empty() {
+            return decisions;
+        }
+        metrics::histogram!(
+            "forced_exit_requests.aggregated_requests",
+            batch.len() as f64
+        );
+
+        let sent_at = Instant::now();
+        let (hashes, lease) = match self.submit_together(&batch).await {
+            Ok(sent) => sent,
+            Err(err) => {
+                for (_, request, _) in &batch {
+                    self.release(request.fe_request.id, &err).await;
+                }
+                return decisions;
+            }
+        };
+        for (_, request, target_account_id) in &batch {
+            self.record_target_account_id(request.fe_request.id, *target_account_id)
+                .await;
+        }
+        let ids: Vec<ForcedExitRequestId> = batch
+            .iter()
+            .map(|(_, request, _)| request.fe_request.id)
+            .collect();
+
+        // The batch is committed as a whole, none of its parts is empty, see `combine_batch`
+        let err = match self.wait_until_comitted(hashes[0][0]).await {
+            Ok(()) => {
+                let commit_latency = sent_at.elapsed();
+                lease.succeeded();
+                drop(lease);
+                for (i, request, _) in batch {
+                    let id = request.fe_request.id;
+                    match self
+                        .complete_fulfillment(
+                            request.fe_request,
+                            request.match_scheme,
+                            commit_latency,
+                        )
+                        .await
+                    {
+                        Ok(decision) => decisions[i] = Some(decision),
+                        // The next attempt awaits the transactions sent for the request once again
+                        Err(err) => vlog::warn!(
+                            "Failed to mark the ForcedExit request {} as fulfilled: {}",
+                            id,
+                            err
+                        ),
+                    }
+                }
+                return decisions;
+            }
+            Err(err) => err,
+        };
+        match err.downcast_ref::<CommitError>() {
+            // Settled by the reconciliation the same way as the requests sent on their own
+            Some(commit_err @ CommitError::Timeout { .. })
+            | Some(commit_err @ CommitError::Interrupted { .. }) => {
+                vlog::warn!("ForcedExit requests {:?} are left in flight: {}", ids, err);
+                let interrupted = matches!(commit_err, CommitError::Interrupted { .. });
+                if interrupted {
+                    metrics::increment_counter!("forced_exit_requests.left_in_flight_on_shutdown");
+                } else {
+                    metrics::increment_counter!("forced_exit_requests.commit_timeouts");
+                }
+                self.left_in_flight = true;
+                for ((i, request, _), request_hashes) in batch.into_iter().zip(&hashes) {
+                    let id = request.fe_request.id;
+                    if interrupted {
+                        self.note_shutdown(
+                            id,
+                            format!(
+                                "The transactions {:?} have not been committed before the shutdown, \
+                                 the request is settled by the reconciliation after the restart",
+                                request_hashes
+                            ),
+                        )
+                        .await;
+                    }
+                    decisions[i] = Some(PaymentDecision::InFlight {
+                        request_id: id,
+                        match_scheme: request.match_scheme,
+                    });
+                }
+            }
+            // It is not known which of the requests the batch has failed for, so each of them
+            // is sent on its own, the failures of these batches count towards the escalation
+            _ => {
+                vlog::warn!(
+                    "The batch of ForcedExit requests {:?} has not been committed: {}",
+                    ids,
+                    err
+                );
+                metrics::increment_counter!("forced_exit_requests.failed_aggregated_batches");
+                for id in ids {
+                    self.release(id, &err).await;
+                }
+            }
+        }
+        decisions
+    }
+
+    /// Signs and sends the transactions of the requests in a single batch from the least loaded
+    /// of the sender accounts, the transactions of each request follow the ones of the previous.
+    pub(super) async fn submit_together(
+        &mut self,
+        batch: &[(usize, ClaimedRequest, AccountId)],
+    ) -> anyhow::Result<(Vec<Vec<TxHash>>, SenderLease)> {
+        self.pace().await;
+        let mut lease = self.sender_accounts.acquire();
+        let send_lock = lease.account().send_lock.clone();
+        let _send_guard = send_lock.lock().await;
+        if self.shutdown.is_requested() {
+            return Err(ShuttingDown.into());
+        }
+
+        let first_nonce = self.next_nonce(lease.account()).await?;
+        let mut nonce = first_nonce;
+        let mut parts = Vec::with_capacity(batch.len());
+        for (_, request, _) in batch {
+            let preflight = renumber(request.preflight.clone(), nonce);
+            let txs = self
+                .build_transactions(lease.account(), &request.fe_request, &preflight)
+                .await?;
+            nonce = nonce + txs.len() as u32;
+            parts.push((request.fe_request.id, txs));
+        }
+        let txs_count = parts.iter().map(|(_, txs)| txs.len()).sum();
+        let hashes = self
+            .core_interaction_wrapper
+            .send_and_save_combined_batch(parts)
+            .await?;
+        lease.sent(first_nonce, txs_count);
+        if let Some(tx_hash) = hashes.last().and_then(|hashes| hashes.last()) {
+            self.pacer.batch_sent(*tx_hash);
+        }
+        Ok((hashes, lease))
+    }
+
+    /// Releases the claimed request, so its payment is processed again on its own.
+    pub(super) async fn release(&self, id: ForcedExitRequestId, err: &anyhow::Error) {
+        vlog::warn!(
+            "ForcedExit request {} is left out of the batch and is processed on its own: {}",
+            id,
+            err
+        );
+        if let Err(cancel_err) = self
+            .core_interaction_wrapper
+            .cancel_request(id, ForcedExitCancellationKind::SystemRetry)
+            .await
+        {
+            vlog::warn!(
+                "Failed to release the ForcedExit request {}: {}",
+                id,
+                cancel_err
+            );
+        }
+    }
+
+    /// Signs and sends the transactions planned by the preflight from the least loaded
+    /// of the sender accounts. The account is leased until the transactions are awaited.
+    ///
+    /// The planned nonces may have been taken by another sender of the account since
+    /// the preflight, so the transactions are moved to the nonces following the sent ones.
+    /// The batch is saved following the `sent` ones if the request is sent in several batches.
+    /// The id the target was resolved to is returned along with the hashes.
+    pub(super) async fn send_planned(
+        &mut self,
+        fe_request: &ForcedExitRequest,
+        preflight: &ForcedExitPreflight,
+        sent: Option<&[TxHash]>,
+    ) -> anyhow::Result<(Vec<TxHash>, SenderLease, AccountId)> {
+        self.pace().await;
+        let mut lease = self.sender_accounts.acquire();
+        let send_lock = lease.account().send_lock.clone();
+        let _send_guard = send_lock.lock().await;
+        // The shutdown may have been requested while the batch waited for its turn
+        if self.shutdown.is_requested() {
+            return Err(ShuttingDown.into());
+        }
+
+        let first_nonce = self.next_nonce(lease.account()).await?;
+        let preflight = renumber(preflight.clone(), first_nonce);
+        // The address may resolve to another account than when the request was matched
+        let target_account_id = self.resolve_target(fe_request).await?;
+        let txs = self
+            .build_transactions(lease.account(), fe_request, &preflight)
+            .await?;
+        let txs_count = txs.len();
+        let submitted = match sent {
+            Some(sent) => {
+                self.core_interaction_wrapper
+                    .send_and_save_txs_chunk(fe_request, sent, txs)
+                    .await
+            }
+            None => {
+                self.core_interaction_wrapper
+                    .send_and_save_txs_batch(fe_request, txs)
+                    .await
+            }
+        };
+        let hashes = match submitted {
+            Ok(hashes) => hashes,
+            Err(err) => {
+                // The transactions rejected for good may be the fault of the account,
+                // e.g. it has nothing left to pay the fees with, unlike the outdated fee
+                if matches!(
+                    err.downcast_ref::<SubmissionError>(),
+                    Some(submission_error @ SubmissionError {
+                        kind: SubmissionErrorKind::Rejected,
+                        retryable: false,
+                        ..
+                    }) if !is_fee_too_low(submission_error)
+                ) {
+                    lease.failed();
+                }
+                return Err(err);
+            }
+        };
+        lease.sent(first_nonce, txs_count);
+        if let Some(tx_hash) = hashes.last() {
+            self.pacer.batch_sent(*tx_hash);
+        }
+        self.record_target_account_id(fe_request.id, target_account_id)
+            .await;
+        Ok((hashes, lease, target_account_id))
+    }
+
+    /// Awaits the transactions sent for the request before. Returns the decision once they
+    /// are committed or are still in flight. The request sent in several batches may have been
+    /// stopped in between or its last batch may have failed, then the transactions committed
+    /// by the batches before are returned, so only the rest of them are sent again.
+    pub(super) async fn await_sent_batch(
+        &mut self,
+        fe_request: &ForcedExitRequest,
+        match_scheme: PaymentMatchScheme,
+    ) -> anyhow::Result<SentBatch> {
+        let id = fe_request.id;
+        let hashes = fe_request.fulfilled_by.clone().unwrap_or_default();
+        // The batches of the request are sent one after another, so the last one is awaited
+        let last_hash = match hashes.last() {
+            Some(hash) => *hash,
+            None => {
+                return Ok(SentBatch::Unsent {
+                    committed: Vec::new(),
+                })
+            }
+        };
+        vlog::warn!(
+            "The transactions {:?} have already been sent for the ForcedExit request {}, \
+             they are awaited instead of sending new ones",
+            hashes,
+            id
+        );
+        metrics::increment_counter!("forced_exit_requests.prevented_duplicates");
+
+        let err = match self.wait_until_comitted(last_hash).await {
+            Ok(()) if hashes.len() < fe_request.tokens.len() => {
+                return Ok(SentBatch::Unsent { committed: hashes });
+            }
+            Ok(()) => {
+                self.set_fulfilled(id).await?;
+                return Ok(SentBatch::Settled(PaymentDecision::Fulfilled {
+                    request_id: id,
+                    match_scheme,
+                    tokens: fe_request.tokens.clone(),
+                }));
+            }
+            Err(err) => err,
+        };
+        match err.downcast_ref::<CommitError>() {
+            Some(CommitError::Failed { .. }) => {
+                vlog::error!("ForcedExit request {} has failed: {}", id, err);
+                self.handle_failed_batch(fe_request, &hashes, &mut self.token_cache())
+                    .await?;
+                let receipts = self.core_interaction_wrapper.get_receipts(&hashes).await?;
+                let committed = committed_prefix(&hashes, &receipts);
+                self.release_unsent(id, &committed).await?;
+                Ok(SentBatch::Unsent { committed })
+            }
+            Some(CommitError::Timeout { .. }) | Some(CommitError::Interrupted { .. }) => {
+                vlog::warn!("ForcedExit request {} is left in flight: {}", id, err);
+                self.left_in_flight = true;
+                Ok(SentBatch::Settled(PaymentDecision::InFlight {
+                    request_id: id,
+                    match_scheme,
+                }))
+            }
+            None => Err(err),
+        }
+    }
+
+    /// Sends the transactions of the claimed request and waits for them to be committed.
+    /// The transactions, which do not fit into a single batch, are sent in several ones,
+    /// each of them is awaited before the next one is sent.
+    pub(super) async fn send_claimed(
+        &mut self,
+        claimed: ClaimedRequest,
+    ) -> anyhow::Result<PaymentDecision> {
+        let ClaimedRequest {
+            fe_request,
+            preflight,
+            match_scheme,
+            committed,
+        } = claimed;
+        let id = fe_request.id;
+
+        let chunks = match self.split_batch(&fe_request, &preflight).await {
+            Ok(chunks) => chunks,
+            Err(err) => {
+                // Nothing was sent, so the request can be fulfilled on the next attempt
+                self.release_unsent(id, &committed).await?;
+                return Err(err);
+            }
+        };
+        if chunks.len() > 1 {
+            vlog::info!(
+                "The transactions of ForcedExit request {} are sent in {} batches",
+                id,
+                chunks.len()
+            );
+            metrics::histogram!(
+                "forced_exit_requests.batches_per_request",
+                chunks.len() as f64
+            );
+        }
+
+        let sent_at = Instant::now();
+        // The hashes of the `ForcedExit` transactions of the batches committed so far
+        let mut sent: Option<Vec<TxHash>> =
+            (chunks.len() > 1 || !committed.is_empty()).then(|| committed);
+        for chunk in &chunks {
+            match self.send_chunk(&fe_request, chunk, sent.as_deref()).await? {
+                Some(hashes) => {
+                    if let Some(sent) = &mut sent {
+                        sent.extend_from_slice(&hashes[..chunk.transactions.len()]);
+                    }
+                }
+                None => {
+                    return Ok(PaymentDecision::InFlight {
+                        request_id: id,
+                        match_scheme,
+                    })
+                }
+            }
+        }
+        let commit_latency = sent_at.elapsed();
+        self.complete_fulfillment(fe_request, match_scheme, commit_latency)
+            .await
+    }
+
+    /// Splits the transactions of the request into the batches of at most `max_batch_size`
+    /// transactions. The fee of every batch is quoted on its own, since it depends on the
+    /// number of the transactions in it.
+    pub(super) async fn split_batch(
+        &self,
+        fe_request: &ForcedExitRequest,
+        preflight: &ForcedExitPreflight,
+    ) -> anyhow::Result<Vec<ForcedExitPreflight>> {
+        let chunks = split_into_batches(preflight, self.config.max_batch_size);
+        let fee_token = match &preflight.fee_payment {
+            Some(fee_payment) if chunks.len() > 1 => fee_payment.token,
+            _ => return Ok(chunks),
+        };
+        let mut paid_chunks = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
+            let fee = self
+                .quote_batch_fee(fe_request, chunk.transactions.len(), fee_token)
+                .await?;
+            paid_chunks.push(with_fee_payment(chunk, fee_token, fee));
+        }
+        Ok(paid_chunks)
+    }
+
+    /// Sends the batch of the transactions of the request and waits for it to be committed,
+    /// `sent` are the transactions of the batches committed before if the request is sent
+    /// in several ones. The hashes of the batch are returned once it is committed, `None`
+    /// if it is left in flight. The request is released to be sent again if the batch fails,
+    /// keeping the `sent` transactions.
+    pub(super) async fn send_chunk(
+        &mut self,
+        fe_request: &ForcedExitRequest,
+        preflight: &ForcedExitPreflight,
+        sent: Option<&[TxHash]>,
+    ) -> anyhow::Result<Option<Vec<TxHash>>> {
+        let id = fe_request.id;
+        let mut target_resolved_again = false;
+        loop {
+            let (hashes, lease, target_account_id) =
+                match self.send_planned(fe_request, preflight, sent).await {
+                    Ok(submitted) => submitted,
+                    Err(err) => {
+                        // Nothing was sent, so the request can be fulfilled on the next attempt
+                        self.release_unsent(id, sent.unwrap_or_default()).await?;
+                        return Err(err);
+                    }
+                };
+
+            // We wait only for the first transaction to complete since the transactions
+            // are sent in a batch
+            let first_hash = match hashes.first() {
+                Some(hash) => *hash,
+                None => {
+                    self.release_unsent(id, sent.unwrap_or_default()).await?;
+                    anyhow::bail!(
+                        "No transactions were sent for the ForcedExit request {}",
+                        id
+                    );
+                }
+            };
+            let err = match self.wait_until_comitted(first_hash).await {
+                Ok(()) => {
+                    lease.succeeded();
+                    return Ok(Some(hashes));
+                }
+                Err(err) => err,
+            };
+            // The batch is only sent again if the target resolves to another account than it was
+            // sent to, e.g. once the block creating the account has been reverted. The failure is
+            // not counted towards the escalation then. The target which has no account would fail
+            // every batch, so the request fails for good instead. No batches are sent once
+            // the shutdown is requested, the request is retried after the restart.
+            let target_mismatch = err
+                .downcast_ref::<CommitError>()
+                .map_or(false, CommitError::is_target_mismatch);
+            if target_mismatch && !self.shutdown.is_requested() {
+                match self.resolve_target(fe_request).await {
+                    Ok(resolved) if resolved != target_account_id && !target_resolved_again => {
+                        vlog::warn!(
+                            "ForcedExit request {} is sent again with its target resolved anew: {}",
+                            id,
+                            err
+                        );
+                        metrics::increment_counter!("forced_exit_requests.target_mismatch_retries");
+                        target_resolved_again = true;
+                        continue;
+                    }
+                    Ok(_) => {}
+                    // Not counted towards the escalation either, the payment is refunded instead
+                    Err(resolve_err) if resolve_err.is::<TargetNotFound>() => {
+                        vlog::error!("ForcedExit request {} has failed: {}", id, err);
+                        self.release_unsent(id, sent.unwrap_or_default()).await?;
+                        return Err(resolve_err);
+                    }
+                    Err(resolve_err) => {
+                        vlog::warn!(
+                            "Failed to resolve the target of the ForcedExit request {} anew: {}",
+                            id,
+                            resolve_err
+                        );
+                    }
+                }
+            }
+            match err.downcast_ref::<CommitError>() {
+                // The transactions may still be committed, so they are neither cancelled
+                // nor sent again until the reconciliation learns their outcome
+                Some(CommitError::Timeout { .. }) => {
+                    vlog::warn!("ForcedExit request {} is left in flight: {}", id, err);
+                    metrics::increment_counter!("forced_exit_requests.commit_timeouts");
+                    self.left_in_flight = true;
+                    return Ok(None);
+                }
+                // Settled by the reconciliation after the restart the same way
+                Some(CommitError::Interrupted { .. }) => {
+                    vlog::warn!("ForcedExit request {} is left in flight: {}", id, err);
+                    metrics::increment_counter!("forced_exit_requests.left_in_flight_on_shutdown");
+                    self.left_in_flight = true;
+                    self.note_shutdown(
+                        id,
+                        format!(
+                            "The transactions {:?} have not been committed before the shutdown, \
+                             the request is settled by the reconciliation after the restart",
+                            hashes
+                        ),
+                    )
+                    .await;
+                    return Ok(None);
+                }
+                Some(commit_err @ CommitError::Failed { .. }) => {
+                    vlog::error!("ForcedExit request {} has failed: {}", id, err);
+                    // The target resolved to another account is not the fault of the sender
+                    if !commit_err.is_target_mismatch() {
+                        lease.failed();
+                    }
+                }
+                None => {
+                    vlog::warn!("Failed to await the ForcedExit request {}: {}", id, err);
+                }
+            }
+            // The transactions are matched with the tokens of the request by their positions
+            let hashes = match sent {
+                Some(sent) => [sent, &hashes[..preflight.transactions.len()]].concat(),
+                None => hashes,
+            };
+            self.handle_failed_batch(fe_request, &hashes, &mut self.token_cache())
+                .await?;
+            self.release_unsent(id, sent.unwrap_or_default()).await?;
+            return Err(err);
+        }
+    }
+
+    /// Releases the request to be sent again. The transactions `committed` by the batches
+    /// sent before are kept, so only the tokens of the rest of the batches are sent again.
+    ///
+    /// A batch is executed atomically, so none of the tokens of the failed batch has been
+    /// withdrawn. The request sent in several batches is committed in part though once one
+    /// of its later batches fails or the sender stops in between.
+    pub(super) async fn release_unsent(
+        &self,
+        id: ForcedExitRequestId,
+        committed: &[TxHash],
+    ) -> anyhow::Result<()> {
+        if committed.is_empty() {
+            return self
+                .core_interaction_wrapper
+                .cancel_request(id, ForcedExitCancellationKind::SystemRetry)
+                .await;
+        }
+        vlog::warn!(
+            "{} ForcedExit transactions of the request {} have been committed, \
+             only the rest of them are sent again",
+            committed.len(),
+            id
+        );
+        metrics::increment_counter!("forced_exit_requests.partially_released_requests");
+        self.core_interaction_wrapper
+            .set_fulfilled_by(id, Some(committed.to_vec()))
+            .await
+    }
+
+    /// Marks the request, the transactions of which have been committed, as fulfilled.
+    pub(super) async fn complete_fulfillment(
+        &self,
+        fe_request: ForcedExitRequest,
+        match_scheme: PaymentMatchScheme,
+        commit_latency: Duration,
+    ) -> anyhow::Result<PaymentDecision> {
+        self.set_fulfilled(fe_request.id).await?;
+
+        // The transactions of the batch are committed together
+        for token in &fe_request.tokens {
+            let token = self.token_labels.label(*token);
+            metrics::histogram!(
+                "forced_exit_requests.commit_latency",
+                commit_latency,
+                "token" => token.clone()
+            );
+            metrics::increment_counter!("forced_exit_requests.fulfilled_tokens", "token" => token);
+        }
+
+        Ok(PaymentDecision::Fulfilled {
+            request_id: fe_request.id,
+            match_scheme,
+            tokens: fe_request.tokens,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+    use num::BigUint;
+
+    use zksync_config::ForcedExitRequestsConfig;
+    use zksync_types::{Nonce, TokenId, ZkSyncTx};
+
+    use crate::{
+        forced_exit_sender::{
+            fees::batch_fee,
+            test_utils::{
+                failed_receipt, get_stored_request, get_test_forced_exit_sender, get_test_request,
+                payment,
+            },
+        },
+        test::{add_request, MockCoreInteractionWrapper},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn payments_received_together_are_sent_in_one_batch() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            ForcedExitRequest {
+                tokens: vec![TokenId(1), TokenId(3)],
+                ..get_test_request(13, "10000000000")
+            },
+        );
+
+        // The payment without the request does not keep the rest from being sent
+        let now = Utc::now();
+        let decisions: Vec<_> = forced_exit_sender
+            .process_payments(vec![
+                (payment("10000000012", None), now),
+                (payment("10000000015", None), now),
+                (payment("10000000013", None), now),
+            ])
+            .await
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert!(matches!(
+            decisions[0],
+            PaymentDecision::Fulfilled { request_id: 12, .. }
+        ));
+        assert!(matches!(
+            decisions[1],
+            PaymentDecision::Unmatched { request_id: 15, .. }
+        ));
+        assert!(matches!(
+            decisions[2],
+            PaymentDecision::Fulfilled { request_id: 13, .. }
+        ));
+        assert_eq!(
+            *forced_exit_sender
+                .core_interaction_wrapper
+                .sent_batches
+                .lock()
+                .unwrap(),
+            vec![vec![12, 13]]
+        );
+
+        // Each request is attributed the transactions of its own, which follow each other
+        let sent_txs = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_sent_txs()
+            .clone();
+        let nonces: Vec<_> = sent_txs.iter().map(|tx| tx.nonce()).collect();
+        assert_eq!(nonces, vec![Nonce(0), Nonce(1), Nonce(2)]);
+        let request = get_stored_request(&forced_exit_sender, 12);
+        assert_eq!(request.fulfilled_by, Some(vec![sent_txs[0].hash()]));
+        assert!(request.fulfilled_at.is_some());
+        let request = get_stored_request(&forced_exit_sender, 13);
+        assert_eq!(
+            request.fulfilled_by,
+            Some(vec![sent_txs[1].hash(), sent_txs[2].hash()])
+        );
+        assert!(request.fulfilled_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn failed_batch_is_sent_again_one_request_at_a_time() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            processing_attempts: 1,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        forced_exit_sender.core_interaction_wrapper.tx_receipt = Some(failed_receipt());
+        for id in [12, 13] {
+            add_request(
+                &forced_exit_sender.core_interaction_wrapper.requests,
+                get_test_request(id, "10000000000"),
+            );
+        }
+
+        let now = Utc::now();
+        let results = forced_exit_sender
+            .process_payments(vec![
+                (payment("10000000012", None), now),
+                (payment("10000000013", None), now),
+            ])
+            .await;
+        assert!(results.iter().all(Result::is_err));
+        assert_eq!(
+            *forced_exit_sender
+                .core_interaction_wrapper
+                .sent_batches
+                .lock()
+                .unwrap(),
+            vec![vec![12, 13], vec![12], vec![13]]
+        );
+        // It is not known which request the shared batch has failed for,
+        // only the failures of the batches of their own are counted
+        let failures = forced_exit_sender
+            .core_interaction_wrapper
+            .failures
+            .lock()
+            .unwrap()
+            .clone();
+        assert_eq!(failures.get(&(12, TokenId(1))), Some(&1));
+        assert_eq!(failures.get(&(13, TokenId(1))), Some(&1));
+    }
+
+    #[tokio::test]
+    async fn request_exceeding_batch_size_is_sent_in_chunks() {
+        let config = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            fee_token: Some(TokenId(0)),
+            fee_multiplier_percent: 120,
+            max_batch_size: 3,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(config.clone()));
+        forced_exit_sender
+            .core_interaction_wrapper
+            .tx_fees
+            .lock()
+            .unwrap()
+            .insert(TokenId(0), BigUint::from(1000u32));
+        let tokens: Vec<_> = (1..=5).map(TokenId).collect();
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            ForcedExitRequest {
+                tokens: tokens.clone(),
+                ..get_test_request(12, "50000000000")
+            },
+        );
+
+        let decision = forced_exit_sender
+            .process_payment(payment("50000000012", None), Utc::now())
+            .await
+            .unwrap();
+        assert!(matches!(decision, PaymentDecision::Fulfilled { .. }));
+        assert_eq!(
+            *forced_exit_sender
+                .core_interaction_wrapper
+                .sent_batches
+                .lock()
+                .unwrap(),
+            vec![vec![12], vec![12], vec![12]]
+        );
+
+        // Every chunk pays the fee of its own transactions
+        let sent_txs = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_sent_txs()
+            .clone();
+        let mut forced_exits = Vec::new();
+        let mut fees = Vec::new();
+        for (nonce, tx) in sent_txs.iter().enumerate() {
+            assert_eq!(tx.nonce(), Nonce(nonce as u32));
+            match &tx.tx {
+                ZkSyncTx::ForcedExit(forced_exit) => {
+                    forced_exits.push((forced_exit.token, tx.hash()))
+                }
+                ZkSyncTx::Transfer(transfer) => fees.push(transfer.fee.clone()),
+                _ => panic!("Only the ForcedExits and their fee payments are sent"),
+            }
+        }
+        assert_eq!(
+            fees,
+            vec![
+                batch_fee(&BigUint::from(3000u32), config.fee_multiplier_percent),
+                batch_fee(&BigUint::from(3000u32), config.fee_multiplier_percent),
+                batch_fee(&BigUint::from(2000u32), config.fee_multiplier_percent),
+            ]
+        );
+
+        // The hashes of the chunks are saved for the tokens in order, without the fee payments
+        let (exited_tokens, hashes): (Vec<_>, Vec<_>) = forced_exits.into_iter().unzip();
+        assert_eq!(exited_tokens, tokens);
+        let request = get_stored_request(&forced_exit_sender, 12);
+        assert_eq!(request.fulfilled_by, Some(hashes));
+        assert!(request.fulfilled_at.is_some());
+    }
+
+    /// The `ForcedExit` transactions sent since the start of the test, with their tokens.
+    fn sent_forced_exits(
+        sender: &MempoolForcedExitSender<MockCoreInteractionWrapper>,
+    ) -> Vec<(TokenId, TxHash)> {
+        sender
+            .core_interaction_wrapper
+            .lock_sent_txs()
+            .iter()
+            .filter_map(|tx| match &tx.tx {
+                ZkSyncTx::ForcedExit(forced_exit) => Some((forced_exit.token, tx.hash())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn request_stopped_between_chunks_sends_only_the_rest() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            max_batch_size: 2,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        let hash = |byte: u8| TxHash::from_slice(&[byte; 32]).unwrap();
+        // Only the first chunk has been sent and committed before the restart
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            ForcedExitRequest {
+                tokens: vec![TokenId(1), TokenId(2), TokenId(3)],
+                fulfilled_by: Some(vec![hash(1), hash(2)]),
+                ..get_test_request(12, "30000000000")
+            },
+        );
+
+        let in_flight = forced_exit_sender
+            .reconcile_unconfirmed(Duration::from_millis(0))
+            .await
+            .unwrap();
+        assert_eq!(in_flight, 0);
+
+        // The committed chunk is kept
+        let request = get_stored_request(&forced_exit_sender, 12);
+        assert_eq!(request.fulfilled_at, None);
+        assert_eq!(request.fulfilled_by, Some(vec![hash(1), hash(2)]));
+        assert_eq!(request.cancellation, None);
+
+        // Only the token left is sent once the payment is observed again
+        let decision = forced_exit_sender
+            .process_payment(payment("30000000012", None), Utc::now())
+            .await
+            .unwrap();
+        assert!(matches!(
+            decision,
+            PaymentDecision::Fulfilled { request_id: 12, .. }
+        ));
+        let sent = sent_forced_exits(&forced_exit_sender);
+        assert_eq!(
+            sent.iter().map(|(token, _)| *token).collect::<Vec<_>>(),
+            vec![TokenId(3)]
+        );
+        let request = get_stored_request(&forced_exit_sender, 12);
+        assert_eq!(
+            request.fulfilled_by,
+            Some(vec![hash(1), hash(2), sent[0].1])
+        );
+        assert!(request.fulfilled_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn only_failed_chunk_is_sent_again() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            max_batch_size: 2,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        let hash = |byte: u8| TxHash::from_slice(&[byte; 32]).unwrap();
+        // The first chunk has been committed, the second one has failed
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            ForcedExitRequest {
+                tokens: vec![TokenId(1), TokenId(2), TokenId(3)],
+                fulfilled_by: Some(vec![hash(1), hash(2), hash(3)]),
+                ..get_test_request(12, "30000000000")
+            },
+        );
+        forced_exit_sender
+            .core_interaction_wrapper
+            .lock_receipts()
+            .insert(hash(3), failed_receipt());
+
+        let in_flight = forced_exit_sender
+            .reconcile_unconfirmed(Duration::from_millis(0))
+            .await
+            .unwrap();
+        assert_eq!(in_flight, 0);
+
+        // The failure is counted only for the token of the failed chunk
+        let failures = forced_exit_sender
+            .core_interaction_wrapper
+            .failures
+            .lock()
+            .unwrap()
+            .clone();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures.get(&(12, TokenId(3))), Some(&1));
+        let request = get_stored_request(&forced_exit_sender, 12);
+        assert_eq!(request.fulfilled_at, None);
+        assert_eq!(request.fulfilled_by, Some(vec![hash(1), hash(2)]));
+        assert_eq!(request.cancellation, None);
+
+        let decision = forced_exit_sender
+            .process_payment(payment("30000000012", None), Utc::now())
+            .await
+            .unwrap();
+        assert!(matches!(
+            decision,
+            PaymentDecision::Fulfilled { request_id: 12, .. }
+        ));
+        let sent = sent_forced_exits(&forced_exit_sender);
+        assert_eq!(
+            sent.iter().map(|(token, _)| *token).collect::<Vec<_>>(),
+            vec![TokenId(3)]
+        );
+        let request = get_stored_request(&forced_exit_sender, 12);
+        assert_eq!(
+            request.fulfilled_by,
+            Some(vec![hash(1), hash(2), sent[0].1])
+        );
+        assert!(request.fulfilled_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn failed_chunk_awaited_on_payment_is_sent_again_alone() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            max_batch_size: 2,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        let hash = |byte: u8| TxHash::from_slice(&[byte; 32]).unwrap();
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            ForcedExitRequest {
+                tokens: vec![TokenId(1), TokenId(2), TokenId(3)],
+                fulfilled_by: Some(vec![hash(1), hash(2), hash(3)]),
+                ..get_test_request(12, "30000000000")
+            },
+        );
+        forced_exit_sender
+            .core_interaction_wrapper
+            .lock_receipts()
+            .insert(hash(3), failed_receipt());
+
+        // The payment observed again awaits the last chunk and sends only its token once more
+        let decision = forced_exit_sender
+            .process_payment(payment("30000000012", None), Utc::now())
+            .await
+            .unwrap();
+        assert!(matches!(
+            decision,
+            PaymentDecision::Fulfilled { request_id: 12, .. }
+        ));
+        let sent = sent_forced_exits(&forced_exit_sender);
+        assert_eq!(
+            sent.iter().map(|(token, _)| *token).collect::<Vec<_>>(),
+            vec![TokenId(3)]
+        );
+        let request = get_stored_request(&forced_exit_sender, 12);
+        assert_eq!(
+            request.fulfilled_by,
+            Some(vec![hash(1), hash(2), sent[0].1])
+        );
+        assert!(request.fulfilled_at.is_some());
+    }
+}
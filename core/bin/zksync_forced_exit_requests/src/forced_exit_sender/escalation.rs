@@ -0,0 +1,411 @@
+//! Escalating the requests, the batches of which keep failing, to the full exits on L1.
+
+use chrono::Utc;
+use ethabi::Token;
+
+use zksync_api::api_server::forced_exit_receipts::tx_status;
+use zksync_contracts::zksync_contract;
+use zksync_types::{
+    forced_exit_requests::{
+        ForcedExitRequest, ForcedExitRequestEscalation, ForcedExitTxStatus, PreparedFullExit,
+    },
+    tx::TxHash,
+    AccountId, Address, TokenId, U256,
+};
+
+use crate::{core_interaction_wrapper::CoreInteractionWrapper, token_cache::TokenCache};
+
+use super::MempoolForcedExitSender;
+
+impl<T: CoreInteractionWrapper + Sync> MempoolForcedExitSender<T> {
+    /// Records the permanent failures of the `ForcedExit` transactions and escalates
+    /// the request to L1 once the transaction for some token has failed too many times.
+    ///
+    /// The `tokens` are shared by all the requests handled in the current cycle.
+    pub async fn handle_failed_batch(
+        &self,
+        request: &ForcedExitRequest,
+        hashes: &[TxHash],
+        tokens: &mut TokenCache,
+    ) -> anyhow::Result<()> {
+        let escalations = self.core_interaction_wrapper.capabilities().escalations;
+        let mut should_escalate = false;
+
+        for (token, hash) in request.tokens.iter().zip(hashes) {
+            let receipt = self.core_interaction_wrapper.get_receipt(*hash).await?;
+            // Only the rejected transactions are counted, the transactions
+            // which have not been processed yet are not known to fail
+            if tx_status(receipt.as_ref()) != ForcedExitTxStatus::Failed {
+                continue;
+            }
+            metrics::increment_counter!(
+                "forced_exit_requests.failed_transactions",
+                "token" => self.token_labels.label(*token)
+            );
+            // The failures are only recorded to decide on the escalation
+            if !escalations {
+                continue;
+            }
+
+            let failures = self
+                .core_interaction_wrapper
+                .record_failure(request.id, *token)
+                .await?;
+            should_escalate |= self.config.l1_escalation_enabled
+                && failures >= self.config.l1_escalation_failures_threshold;
+        }
+
+        if should_escalate {
+            self.escalate_to_l1(request, tokens).await?;
+        }
+        Ok(())
+    }
+
+    /// Prepares the `FullExit` operations for all the tokens of the request and hands
+    /// them over to the operators. The transactions are sent in an atomic batch,
+    /// so no token of the request can be withdrawn on L2 anymore.
+    pub async fn escalate_to_l1(
+        &self,
+        request: &ForcedExitRequest,
+        tokens: &mut TokenCache,
+    ) -> anyhow::Result<()> {
+        let account_id = self.resolve_target(request).await?;
+
+        let mut full_exits = Vec::with_capacity(request.tokens.len());
+        for token in &request.tokens {
+            let token_address = tokens
+                .token_address(&self.core_interaction_wrapper, *token)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Token {} not found", token))?;
+
+            full_exits.push(self.prepare_full_exit(
+                request.target,
+                account_id,
+                *token,
+                token_address,
+            ));
+        }
+
+        self.core_interaction_wrapper
+            .store_escalation(ForcedExitRequestEscalation {
+                request_id: request.id,
+                full_exits,
+                created_at: Utc::now(),
+                l1_tx_hash: None,
+                finalized_at: None,
+            })
+            .await?;
+
+        vlog::error!(
+            "ForcedExit request {} keeps failing on L2, FullExit operations \
+            for it are awaiting to be sent by the operators",
+            request.id
+        );
+        metrics::increment_counter!("forced_exit_requests.l1_escalations");
+        Ok(())
+    }
+
+    pub fn prepare_full_exit(
+        &self,
+        owner: Address,
+        account_id: AccountId,
+        token: TokenId,
+        token_address: Address,
+    ) -> PreparedFullExit {
+        let calldata = zksync_contract()
+            .function("requestFullExit")
+            .and_then(|function| {
+                function.encode_input(&[
+                    Token::Uint(U256::from(*account_id)),
+                    Token::Address(token_address),
+                ])
+            })
+            .expect("Failed to encode the requestFullExit call");
+
+        PreparedFullExit {
+            owner,
+            account_id,
+            token,
+            token_address,
+            contract: self.zksync_contract,
+            calldata,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use zksync_config::ForcedExitRequestsConfig;
+    use zksync_types::H256;
+
+    use crate::{
+        forced_exit_sender::{
+            test_utils::{
+                failed_receipt, get_stored_request, get_test_forced_exit_sender, get_test_request,
+                payment, sent_txs_count, TEST_ZKSYNC_CONTRACT,
+            },
+            ForcedExitSender, PaymentDecision,
+        },
+        test::{add_request, TEST_TARGET_ACCOUNT_ID},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_forced_exit_sender_escalation() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            l1_escalation_enabled: true,
+            l1_escalation_failures_threshold: 2,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        forced_exit_sender.core_interaction_wrapper.tx_receipt = Some(failed_receipt());
+
+        let request = get_test_request(12, "10000000000");
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            request.clone(),
+        );
+
+        // The first failure does not reach the threshold
+        let result = forced_exit_sender
+            .try_process_request(payment("10000000012", None), Utc::now())
+            .await;
+        assert!(result.is_err());
+        assert!(forced_exit_sender
+            .core_interaction_wrapper
+            .lock_escalations()
+            .is_empty());
+
+        // The second one escalates the request
+        let result = forced_exit_sender
+            .try_process_request(payment("10000000012", None), Utc::now())
+            .await;
+        assert!(result.is_err());
+        assert_eq!(sent_txs_count(&forced_exit_sender), 2);
+
+        let escalation = forced_exit_sender
+            .core_interaction_wrapper
+            .get_escalation(12)
+            .await
+            .unwrap()
+            .expect("The request must be escalated");
+        assert!(escalation.is_awaiting());
+        assert_eq!(escalation.full_exits.len(), 1);
+
+        let full_exit = &escalation.full_exits[0];
+        assert_eq!(full_exit.owner, request.target);
+        assert_eq!(full_exit.account_id, TEST_TARGET_ACCOUNT_ID);
+        assert_eq!(full_exit.token, TokenId(1));
+        assert_eq!(full_exit.token_address, Address::from_low_u64_be(1));
+        assert_eq!(full_exit.contract, TEST_ZKSYNC_CONTRACT);
+
+        // The calldata is the `requestFullExit` call for the target account and the token
+        let function = zksync_contract()
+            .function("requestFullExit")
+            .unwrap()
+            .clone();
+        assert_eq!(&full_exit.calldata[..4], &function.short_signature()[..]);
+        let params = function.decode_input(&full_exit.calldata[4..]).unwrap();
+        assert_eq!(
+            params,
+            vec![
+                Token::Uint(U256::from(*TEST_TARGET_ACCOUNT_ID)),
+                Token::Address(Address::from_low_u64_be(1)),
+            ]
+        );
+
+        // The escalated request is not processed on L2 anymore
+        forced_exit_sender
+            .process_request(payment("10000000012", None), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 2);
+    }
+
+    #[tokio::test]
+    async fn test_escalation_backlog_shares_tokens() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            l1_escalation_enabled: true,
+            l1_escalation_failures_threshold: 1,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        forced_exit_sender.core_interaction_wrapper.tx_receipt = Some(failed_receipt());
+
+        // A large backlog of the failed batches for the same few tokens
+        let tokens = vec![TokenId(1), TokenId(2), TokenId(3)];
+        let requests_count = 200;
+        for id in 0..requests_count {
+            let hashes = tokens
+                .iter()
+                .map(|token| {
+                    let hash = H256::from_low_u64_be(((id as u64) << 32) | token.0 as u64);
+                    TxHash::from_slice(hash.as_bytes()).unwrap()
+                })
+                .collect();
+            add_request(
+                &forced_exit_sender.core_interaction_wrapper.requests,
+                ForcedExitRequest {
+                    tokens: tokens.clone(),
+                    fulfilled_by: Some(hashes),
+                    ..get_test_request(id, "10000000000")
+                },
+            );
+        }
+
+        let in_flight = forced_exit_sender
+            .reconcile_unconfirmed(Duration::from_millis(0))
+            .await
+            .unwrap();
+        assert_eq!(in_flight, 0);
+        assert_eq!(
+            forced_exit_sender
+                .core_interaction_wrapper
+                .lock_escalations()
+                .len(),
+            requests_count as usize
+        );
+
+        // The metadata is queried once per distinct token, not once per request
+        assert_eq!(
+            forced_exit_sender.core_interaction_wrapper.token_lookups(),
+            tokens
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tokens_unavailable_mid_backlog() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            token_cache_max_staleness: 60 * 60 * 1000,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        for (id, token) in [(12, TokenId(1)), (13, TokenId(1)), (14, TokenId(2))] {
+            add_request(
+                &forced_exit_sender.core_interaction_wrapper.requests,
+                ForcedExitRequest {
+                    tokens: vec![token],
+                    ..get_test_request(id, "10000000000")
+                },
+            );
+        }
+
+        let decision = forced_exit_sender
+            .process_payment(payment("10000000012", None), Utc::now())
+            .await
+            .unwrap();
+        assert!(matches!(decision, PaymentDecision::Fulfilled { .. }));
+
+        forced_exit_sender
+            .core_interaction_wrapper
+            .set_tokens_available(false);
+        // The token loaded for the previous request is recent enough
+        let decision = forced_exit_sender
+            .process_payment(payment("10000000013", None), Utc::now())
+            .await
+            .unwrap();
+        assert!(matches!(decision, PaymentDecision::Fulfilled { .. }));
+        // The token never loaded is not known to be valid, the request is neither sent nor failed
+        let paid_at = Utc::now();
+        let decision = forced_exit_sender
+            .process_payment(payment("10000000014", None), paid_at)
+            .await
+            .unwrap();
+        assert_eq!(
+            decision,
+            PaymentDecision::Deferred {
+                request_id: 14,
+                dependency: "tokens".to_string(),
+            }
+        );
+        assert_eq!(sent_txs_count(&forced_exit_sender), 2);
+        assert_eq!(forced_exit_sender.deferred.len(), 1);
+
+        // The deferred payment stays deferred until the tokens are available again
+        forced_exit_sender.process_deferred_requests().await;
+        assert_eq!(forced_exit_sender.deferred.len(), 1);
+        assert!(get_stored_request(&forced_exit_sender, 14)
+            .fulfilled_at
+            .is_none());
+
+        forced_exit_sender
+            .core_interaction_wrapper
+            .set_tokens_available(true);
+        forced_exit_sender.process_deferred_requests().await;
+        assert!(forced_exit_sender.deferred.is_empty());
+        assert_eq!(sent_txs_count(&forced_exit_sender), 3);
+        assert!(get_stored_request(&forced_exit_sender, 14)
+            .fulfilled_at
+            .is_some());
+
+        // Without the cache allowed, even the known tokens are not used during the outage
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            token_cache_max_staleness: 0,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        for id in [12, 13] {
+            add_request(
+                &forced_exit_sender.core_interaction_wrapper.requests,
+                get_test_request(id, "10000000000"),
+            );
+        }
+        forced_exit_sender
+            .process_payment(payment("10000000012", None), Utc::now())
+            .await
+            .unwrap();
+        forced_exit_sender
+            .core_interaction_wrapper
+            .set_tokens_available(false);
+        let decision = forced_exit_sender
+            .process_payment(payment("10000000013", None), Utc::now())
+            .await
+            .unwrap();
+        assert!(matches!(
+            decision,
+            PaymentDecision::Deferred { request_id: 13, .. }
+        ));
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+    }
+
+    #[tokio::test]
+    async fn test_forced_exit_sender_escalation_disabled() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            l1_escalation_enabled: false,
+            l1_escalation_failures_threshold: 1,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        forced_exit_sender.core_interaction_wrapper.tx_receipt = Some(failed_receipt());
+
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+
+        // All the processing attempts fail, but nothing is escalated
+        let result = forced_exit_sender
+            .process_request(payment("10000000012", None), Utc::now())
+            .await;
+        assert!(result.is_err());
+        assert_eq!(
+            sent_txs_count(&forced_exit_sender),
+            forced_exit_sender.config.processing_attempts as usize
+        );
+        assert!(forced_exit_sender
+            .core_interaction_wrapper
+            .lock_escalations()
+            .is_empty());
+    }
+}
@@ -0,0 +1,400 @@
+//! Quoting the fees of the `ForcedExit` batches, the fee is raised once the batch is rejected
+//! for the outdated one.
+
+use chrono::Utc;
+use num::{BigUint, Zero};
+
+use zksync_types::{
+    forced_exit_requests::{
+        ForcedExitRequest, ForcedExitRequestId, SaveForcedExitRequestNoteQuery, SubmissionError,
+        SubmissionErrorKind,
+    },
+    helpers::closest_greater_or_eq_packable_fee_amount,
+    TokenId,
+};
+
+use crate::{core_interaction_wrapper::CoreInteractionWrapper, token_cache::DependencyUnavailable};
+
+use super::{FeeLimitExceeded, MempoolForcedExitSender};
+
+/// The reasons the transactions are rejected with once the fee paid for them is below the one
+/// the server quotes at the moment, e.g. after the prices have moved since the fee was quoted.
+const FEE_TOO_LOW_REASONS: [&str; 2] = [
+    "Transaction fee is too low",
+    "Transactions batch summary fee is too low",
+];
+
+/// Whether the transactions have been rejected since the fee they pay is not enough anymore.
+pub(super) fn is_fee_too_low(err: &SubmissionError) -> bool {
+    err.kind == SubmissionErrorKind::Rejected
+        && FEE_TOO_LOW_REASONS
+            .iter()
+            .any(|reason| err.message.contains(reason))
+}
+
+/// The fee paid for the batch quoted for the given fee, scaled up by `multiplier_percent`
+/// and rounded up to the closest fee which can be packed into the transaction.
+pub(super) fn batch_fee(quoted_fee: &BigUint, multiplier_percent: u32) -> BigUint {
+    // Rounded up, so the scaled fee is never below the quoted one
+    let scaled = (quoted_fee * multiplier_percent + 99u32) / 100u32;
+    closest_greater_or_eq_packable_fee_amount(&scaled)
+}
+
+/// The fee raised by `bump_percent` as many times as the batch has been rejected for its fee,
+/// rounded up to the closest fee which can be packed into the transaction.
+fn bump_fee(fee: &BigUint, bump_percent: u32, rejections: u32) -> BigUint {
+    let mut bumped = fee.clone();
+    for _ in 0..rejections {
+        bumped = (&bumped * bump_percent + 99u32) / 100u32;
+    }
+    closest_greater_or_eq_packable_fee_amount(&bumped)
+}
+
+impl<T: CoreInteractionWrapper + Sync> MempoolForcedExitSender<T> {
+    /// Quotes the fee of the batch and scales it up by the multiplier of the config.
+    /// Once the batch of the request has been rejected for its outdated fee, the fee is
+    /// raised by `fee_bump_percent` for every rejection, up to `max_batch_fee`.
+    ///
+    /// The batch without the fee would be rejected, so the payment is deferred until
+    /// the fee can be quoted again, see `DependencyUnavailable`.
+    pub(super) async fn quote_batch_fee(
+        &self,
+        fe_request: &ForcedExitRequest,
+        forced_exits: usize,
+        fee_token: TokenId,
+    ) -> anyhow::Result<BigUint> {
+        // The fee does not depend on which of the accounts sends the batch
+        let fee_payer = self.sender_accounts.main().address;
+        let quoted_fee = self
+            .core_interaction_wrapper
+            .get_forced_exit_batch_fee(fe_request.target, forced_exits, fee_payer, fee_token)
+            .await
+            .map_err(|err| DependencyUnavailable::new("fee quotes", err))?;
+        let fee = batch_fee(&quoted_fee, self.config.fee_multiplier_percent);
+        if self.fee_rejections == 0 {
+            return Ok(fee);
+        }
+
+        let fee = bump_fee(&fee, self.config.fee_bump_percent, self.fee_rejections);
+        let max_fee = &self.config.max_batch_fee;
+        if !max_fee.is_zero() && &fee > max_fee {
+            return Err(FeeLimitExceeded {
+                fee,
+                max_fee: max_fee.clone(),
+            }
+            .into());
+        }
+        // Every fee raised is kept with the request, so the operators can follow the prices
+        self.note_fee(
+            fe_request.id,
+            format!(
+                "The batch of {} ForcedExit transactions pays the fee of {} in token {}, \
+                 raised after {} rejections for the outdated fee from the quoted {}",
+                forced_exits, fee, fee_token, self.fee_rejections, quoted_fee
+            ),
+        )
+        .await;
+        Ok(fee)
+    }
+
+    pub(super) async fn note_fee(&self, request_id: ForcedExitRequestId, text: String) {
+        let note = SaveForcedExitRequestNoteQuery {
+            request_id,
+            author: String::from("forced_exit_sender"),
+            text,
+            tags: vec![String::from("fee")],
+            created_at: Utc::now(),
+        };
+        if let Err(err) = self.core_interaction_wrapper.store_note(note).await {
+            vlog::warn!(
+                "Failed to note the fee for the ForcedExit request {}: {}",
+                request_id,
+                err
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use zksync_config::ForcedExitRequestsConfig;
+    use zksync_types::{helpers::is_fee_amount_packable, ZkSyncTx};
+
+    use crate::{
+        forced_exit_sender::{
+            test_utils::{
+                get_stored_request, get_test_forced_exit_sender, get_test_request, payment,
+                sent_txs_count,
+            },
+            PaymentDecision,
+        },
+        test::{add_request, MockCoreInteractionWrapper},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn batch_fee_is_paid_in_the_fee_token() {
+        let config = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            fee_token: Some(TokenId(0)),
+            fee_multiplier_percent: 120,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(config.clone()));
+        forced_exit_sender
+            .core_interaction_wrapper
+            .tx_fees
+            .lock()
+            .unwrap()
+            .insert(TokenId(0), BigUint::from(1000u32));
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            ForcedExitRequest {
+                tokens: vec![TokenId(1), TokenId(3)],
+                ..get_test_request(12, "10000000000")
+            },
+        );
+
+        let decision = forced_exit_sender
+            .process_payment(payment("10000000012", None), Utc::now())
+            .await
+            .unwrap();
+        assert!(matches!(decision, PaymentDecision::Fulfilled { .. }));
+
+        // The ForcedExits are sent without the fee, the transfer closing the batch pays it
+        let sent_txs = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_sent_txs()
+            .clone();
+        assert_eq!(sent_txs.len(), 3);
+        for tx in &sent_txs[..2] {
+            match &tx.tx {
+                ZkSyncTx::ForcedExit(forced_exit) => assert!(forced_exit.fee.is_zero()),
+                _ => panic!("The batch starts with the ForcedExit transactions"),
+            }
+        }
+        match &sent_txs[2].tx {
+            ZkSyncTx::Transfer(transfer) => {
+                assert_eq!(transfer.from, transfer.to);
+                assert_eq!(transfer.token, TokenId(0));
+                assert!(transfer.amount.is_zero());
+                assert_eq!(
+                    transfer.fee,
+                    batch_fee(&BigUint::from(3000u32), config.fee_multiplier_percent)
+                );
+                assert_eq!(transfer.nonce, sent_txs[1].nonce() + 1);
+            }
+            _ => panic!("The fee is paid by the Transfer closing the batch"),
+        }
+    }
+
+    #[tokio::test]
+    async fn payment_is_deferred_without_fee_quote() {
+        let config = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            fee_token: Some(TokenId(0)),
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(config));
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+
+        // Sending the batch without the fee would only get it rejected
+        let decision = forced_exit_sender
+            .process_payment(payment("10000000012", None), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(
+            decision,
+            PaymentDecision::Deferred {
+                request_id: 12,
+                dependency: "fee quotes".to_string(),
+            }
+        );
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+        assert_eq!(forced_exit_sender.deferred.len(), 1);
+
+        forced_exit_sender
+            .core_interaction_wrapper
+            .tx_fees
+            .lock()
+            .unwrap()
+            .insert(TokenId(0), BigUint::from(1000u32));
+        forced_exit_sender.process_deferred_requests().await;
+        assert!(forced_exit_sender.deferred.is_empty());
+        assert_eq!(sent_txs_count(&forced_exit_sender), 2);
+        assert!(get_stored_request(&forced_exit_sender, 12)
+            .fulfilled_at
+            .is_some());
+    }
+
+    fn outdated_fee_config() -> ForcedExitRequestsConfig {
+        ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            fee_token: Some(TokenId(0)),
+            fee_multiplier_percent: 100,
+            fee_bump_percent: 150,
+            processing_attempts: 3,
+            processing_retry_base_delay: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        }
+    }
+
+    fn fee_notes(sender: &MempoolForcedExitSender<MockCoreInteractionWrapper>) -> Vec<String> {
+        sender
+            .core_interaction_wrapper
+            .notes
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|note| note.tags.contains(&String::from("fee")))
+            .map(|note| note.text.clone())
+            .collect()
+    }
+
+    #[test]
+    fn quoted_fee_is_scaled_up() {
+        assert_eq!(
+            batch_fee(&BigUint::from(100u32), 120),
+            BigUint::from(120u32)
+        );
+        // The fractions of the smallest unit are rounded up
+        assert_eq!(
+            batch_fee(&BigUint::from(101u32), 120),
+            BigUint::from(122u32)
+        );
+        assert_eq!(
+            batch_fee(&BigUint::from(101u32), 100),
+            BigUint::from(101u32)
+        );
+        assert!(batch_fee(&BigUint::zero(), 150).is_zero());
+
+        // The fee which can not be packed is rounded up to the one which can
+        let quoted = BigUint::from(123_456_789u32);
+        let fee = batch_fee(&quoted, 120);
+        assert!(fee >= BigUint::from(148_148_147u32));
+        assert!(is_fee_amount_packable(&fee));
+        assert!(fee < BigUint::from(149_000_000u32));
+    }
+
+    #[test]
+    fn fee_is_bumped_for_every_rejection() {
+        assert_eq!(
+            bump_fee(&BigUint::from(100u32), 150, 0),
+            BigUint::from(100u32)
+        );
+        assert_eq!(
+            bump_fee(&BigUint::from(100u32), 150, 2),
+            BigUint::from(225u32)
+        );
+        // The fractions of the smallest unit are rounded up
+        assert_eq!(
+            bump_fee(&BigUint::from(101u32), 150, 1),
+            BigUint::from(152u32)
+        );
+        assert!(is_fee_too_low(&SubmissionError::rejected(
+            Some(605),
+            "Transaction fee is too low",
+            false
+        )));
+        assert!(!is_fee_too_low(&SubmissionError::transport(
+            "Transaction fee is too low"
+        )));
+    }
+
+    #[tokio::test]
+    async fn outdated_fee_is_raised_until_batch_is_accepted() {
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(outdated_fee_config()));
+        forced_exit_sender
+            .core_interaction_wrapper
+            .tx_fees
+            .lock()
+            .unwrap()
+            .insert(TokenId(0), BigUint::from(1000u32));
+        // The prices have moved since the quote, the batch requires twice as much
+        *forced_exit_sender
+            .core_interaction_wrapper
+            .required_batch_fee
+            .lock()
+            .unwrap() = Some(BigUint::from(4000u32));
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+
+        let decision = forced_exit_sender
+            .process_payment(payment("10000000012", None), Utc::now())
+            .await
+            .unwrap();
+        assert!(matches!(
+            decision,
+            PaymentDecision::Fulfilled { request_id: 12, .. }
+        ));
+        // 2000 quoted, then 3000 and 4500 after the rejections
+        let sent_txs = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_sent_txs()
+            .clone();
+        assert_eq!(sent_txs.len(), 2);
+        match &sent_txs[1].tx {
+            ZkSyncTx::Transfer(transfer) => assert_eq!(transfer.fee, BigUint::from(4500u32)),
+            _ => panic!("The fee is paid by the Transfer closing the batch"),
+        }
+        // Every raised fee is recorded with the request
+        let notes = fee_notes(&forced_exit_sender);
+        assert_eq!(notes.len(), 2);
+        assert!(notes[0].contains("fee of 3000 in token 0"));
+        assert!(notes[1].contains("fee of 4500 in token 0"));
+        assert!(forced_exit_sender
+            .core_interaction_wrapper
+            .processing_failures
+            .lock()
+            .unwrap()
+            .is_empty());
+        // The next payment starts from the quoted fee
+        assert_eq!(forced_exit_sender.fee_rejections, 0);
+    }
+
+    #[tokio::test]
+    async fn fee_is_not_raised_above_the_limit() {
+        let config = ForcedExitRequestsConfig {
+            max_batch_fee: BigUint::from(4000u32),
+            ..outdated_fee_config()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(config));
+        forced_exit_sender
+            .core_interaction_wrapper
+            .tx_fees
+            .lock()
+            .unwrap()
+            .insert(TokenId(0), BigUint::from(1000u32));
+        *forced_exit_sender
+            .core_interaction_wrapper
+            .required_batch_fee
+            .lock()
+            .unwrap() = Some(BigUint::from(4000u32));
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+
+        let err = forced_exit_sender
+            .process_payment(payment("10000000012", None), Utc::now())
+            .await
+            .unwrap_err();
+        assert!(err.is::<FeeLimitExceeded>());
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+        let failures = forced_exit_sender
+            .core_interaction_wrapper
+            .processing_failures
+            .lock()
+            .unwrap()
+            .clone();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].attempts, 3);
+        assert_eq!(fee_notes(&forced_exit_sender).len(), 1);
+    }
+}
@@ -0,0 +1,837 @@
+//! Matching the received payments with the requests they pay for.
+
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use num::BigUint;
+
+use zksync_api::api_server::forced_exit_matcher::{
+    MatchObserver, PaymentMatchOutcome, PaymentMatchSource, PaymentMatcher,
+};
+use zksync_types::{
+    forced_exit_requests::{
+        ExpectedForcedExitPayment, ForcedExitRequest, ForcedExitRequestId, FundsReceivedEvent,
+        PaymentMatchScheme, PaymentMatchStep,
+    },
+    H256,
+};
+
+use crate::core_interaction_wrapper::CoreInteractionWrapper;
+
+use super::MempoolForcedExitSender;
+
+/// Lets the payments be matched with the requests loaded through the wrapper.
+struct WrapperMatchSource<'a, T>(&'a T);
+
+#[async_trait::async_trait]
+impl<'a, T: CoreInteractionWrapper + Sync> PaymentMatchSource for WrapperMatchSource<'a, T> {
+    async fn get_request_by_id(
+        &self,
+        id: ForcedExitRequestId,
+    ) -> anyhow::Result<Option<ForcedExitRequest>> {
+        self.0.get_request_by_id(id).await
+    }
+
+    async fn get_request_by_public_id(
+        &self,
+        public_id: ForcedExitRequestId,
+    ) -> anyhow::Result<Option<ForcedExitRequest>> {
+        self.0.get_request_by_public_id(public_id).await
+    }
+
+    async fn get_requests_by_closest_amount(
+        &self,
+        max_amount: &BigUint,
+        submission_time: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<ForcedExitRequest>> {
+        self.0
+            .get_requests_by_closest_amount(max_amount, submission_time)
+            .await
+    }
+
+    async fn get_expected_payment(
+        &self,
+        eth_tx_hash: H256,
+    ) -> anyhow::Result<Option<ExpectedForcedExitPayment>> {
+        self.0.get_expected_payment(eth_tx_hash).await
+    }
+}
+
+/// Reports the steps of the matching which point at the inconsistent requests
+/// or at the payments the operators should know about.
+#[derive(Debug, Default)]
+struct MatchLog {
+    expected_tx_hash: Option<H256>,
+    // The request is only reported as overpaid once it is matched
+    overpaid: Option<(ForcedExitRequestId, BigUint)>,
+}
+
+impl MatchObserver for MatchLog {
+    fn observe(&mut self, step: &PaymentMatchStep) {
+        match step {
+            PaymentMatchStep::ExpectedPaymentLookup { eth_tx_hash, .. } => {
+                self.expected_tx_hash = Some(*eth_tx_hash);
+            }
+            PaymentMatchStep::ExpectedPaymentAmount {
+                request_id,
+                paid,
+                pay_exactly,
+                matches: false,
+            } => {
+                vlog::error!(
+                    "The payment {:?} registered for ForcedExit request {} has paid {} instead of {}",
+                    self.expected_tx_hash,
+                    request_id,
+                    paid,
+                    pay_exactly
+                );
+                metrics::increment_counter!("forced_exit_requests.expected_payment_mismatches");
+            }
+            PaymentMatchStep::MisalignedPrice {
+                request_id, price, ..
+            } => {
+                vlog::error!(
+                    "ForcedExit request {} has the price {} overlapping with the id, \
+                     the payments can not be matched by the amount",
+                    request_id,
+                    price
+                );
+                metrics::increment_counter!("forced_exit_requests.misaligned_price");
+            }
+            PaymentMatchStep::PayExactlyComparison {
+                request_id,
+                paid,
+                pay_exactly,
+                matches: false,
+            } => {
+                vlog::error!(
+                    "The amount {} paid for ForcedExit request {} differs from the stored one {}",
+                    paid,
+                    request_id,
+                    pay_exactly
+                );
+                metrics::increment_counter!("forced_exit_requests.pay_exactly_mismatch");
+            }
+            PaymentMatchStep::OverpaymentComparison {
+                request_id,
+                paid,
+                pay_exactly,
+                accepted: true,
+                ..
+            } => {
+                let pay_exactly = BigUint::from_str(pay_exactly).unwrap_or_default();
+                self.overpaid = Some((*request_id, paid - pay_exactly));
+            }
+            PaymentMatchStep::AmbiguousOverpayment {
+                request_id,
+                other_request_id,
+            } => {
+                vlog::warn!(
+                    "The payment may overpay either ForcedExit request {} or {}, \
+                     it is left unmatched",
+                    request_id,
+                    other_request_id
+                );
+                metrics::increment_counter!("forced_exit_requests.ambiguous_overpayments");
+            }
+            PaymentMatchStep::Matched { request_id, .. } => {
+                if let Some((id, excess)) = self.overpaid.take().filter(|(id, _)| id == request_id)
+                {
+                    vlog::info!("ForcedExit request {} is overpaid by {} wei", id, excess);
+                    metrics::increment_counter!("forced_exit_requests.overpaid_requests");
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<T: CoreInteractionWrapper + Sync> MempoolForcedExitSender<T> {
+    /// The matcher of the payments, limited to the features the wrapper supports.
+    pub(super) fn matcher(&self) -> PaymentMatcher<'_> {
+        let capabilities = self.core_interaction_wrapper.capabilities();
+        PaymentMatcher::new(
+            &self.config,
+            capabilities.expected_payments,
+            capabilities.overpayments,
+        )
+    }
+
+    pub(super) fn match_source(&self) -> WrapperMatchSource<'_, T> {
+        WrapperMatchSource(&self.core_interaction_wrapper)
+    }
+
+    /// Finds the request the payment was made for, see `PaymentMatcher::match_payment`.
+    ///
+    /// The transaction registered in advance, which has paid another amount than
+    /// the request asks for, is flagged for the partner.
+    pub async fn match_payment(
+        &self,
+        payment: FundsReceivedEvent,
+        submission_time: DateTime<Utc>,
+    ) -> anyhow::Result<Option<(ForcedExitRequest, PaymentMatchScheme)>> {
+        let outcome = self
+            .matcher()
+            .match_payment(
+                &self.match_source(),
+                &payment,
+                submission_time,
+                &mut MatchLog::default(),
+            )
+            .await?;
+
+        match outcome {
+            PaymentMatchOutcome::Matched(request, match_scheme) => {
+                Ok(Some((request, match_scheme)))
+            }
+            PaymentMatchOutcome::ExpectedAmountMismatch(expected) => {
+                self.core_interaction_wrapper
+                    .flag_expected_payment(expected.eth_tx_hash, &payment.amount)
+                    .await?;
+                Ok(None)
+            }
+            PaymentMatchOutcome::Unmatched => Ok(None),
+        }
+    }
+
+    /// Returns the id the request with the given public id is stored with. The payment
+    /// for the id no request has is still reported, the public id is returned then.
+    pub(super) async fn stored_request_id(
+        &self,
+        public_id: ForcedExitRequestId,
+    ) -> anyhow::Result<ForcedExitRequestId> {
+        let request = self
+            .core_interaction_wrapper
+            .get_request_by_public_id(public_id)
+            .await?;
+        Ok(request.map_or(public_id, |request| request.id))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use zksync_config::ForcedExitRequestsConfig;
+    use zksync_types::forced_exit_requests::{pay_exactly, ForcedExitPaymentTerms};
+
+    use crate::{
+        forced_exit_sender::{
+            test_utils::{
+                get_checked_test_request, get_stored_request, get_test_forced_exit_sender,
+                get_test_request, payment, sent_txs_count,
+            },
+            ForcedExitSender, PaymentDecision,
+        },
+        test::{add_request, MockCoreInteractionWrapper},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn overpayments_within_tolerance() {
+        let config = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            overpayment_tolerance: 5,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(config));
+
+        for id in [12, 20] {
+            add_request(
+                &forced_exit_sender.core_interaction_wrapper.requests,
+                get_test_request(id, "10000000000"),
+            );
+        }
+
+        // The request 13 does not exist, the closest one is overpaid by 1 wei
+        forced_exit_sender
+            .process_request(payment("10000000013", None), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+        let overpaid = get_stored_request(&forced_exit_sender, 12);
+        assert_eq!(
+            overpaid.match_scheme,
+            Some(PaymentMatchScheme::AmountDigits)
+        );
+        assert_eq!(overpaid.paid_amount.as_deref(), Some("10000000013"));
+
+        // The fulfilled request is not paid for again, nor is the payment
+        // reassigned to another request
+        forced_exit_sender
+            .process_request(payment("10000000014", None), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+
+        // The excess exceeds the tolerance
+        forced_exit_sender
+            .process_request(payment("10000000026", None), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+
+        // The underpayments are never matched
+        forced_exit_sender
+            .process_request(payment("10000000019", None), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+
+        // The request paid for exactly takes precedence over the overpaid one
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(21, "10000000000"),
+        );
+        forced_exit_sender
+            .process_request(payment("10000000021", None), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 2);
+        assert!(get_stored_request(&forced_exit_sender, 20)
+            .fulfilled_at
+            .is_none());
+        assert!(get_stored_request(&forced_exit_sender, 21)
+            .fulfilled_at
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn overpayments_of_adjacent_requests_are_not_matched() {
+        let config = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            overpayment_tolerance: 5,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(config));
+
+        for id in [11, 12, 13] {
+            add_request(
+                &forced_exit_sender.core_interaction_wrapper.requests,
+                get_test_request(id, "10000000000"),
+            );
+        }
+
+        // The request 14 does not exist, the payment may overpay any of the requests
+        // 12 and 13, so neither of them is fulfilled by it
+        forced_exit_sender
+            .process_request(payment("10000000014", None), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+        for id in [11, 12, 13] {
+            assert!(get_stored_request(&forced_exit_sender, id)
+                .fulfilled_at
+                .is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn overpayments_are_rejected_without_tolerance() {
+        let config = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            overpayment_tolerance: 0,
+            overpayment_tolerance_percent: 0,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(config));
+
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        forced_exit_sender
+            .process_request(payment("10000000013", None), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+    }
+
+    #[tokio::test]
+    async fn test_forced_exit_sender_conflicting_ids() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(13, "10000000000"),
+        );
+
+        // The amount points to the request 13, but the explicit id points to
+        // the non-existing request. The amount must not be used as a fallback
+        forced_exit_sender
+            .process_request(payment("10000000013", Some(14)), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+
+        // The amount points to the request 13, but the explicit id
+        // takes precedence
+        forced_exit_sender
+            .process_request(payment("10000000013", Some(12)), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+
+        let paid_request = get_stored_request(&forced_exit_sender, 12);
+        assert!(paid_request.fulfilled_by.is_some());
+        assert_eq!(
+            paid_request.match_scheme,
+            Some(PaymentMatchScheme::ExplicitId)
+        );
+
+        let other_request = get_stored_request(&forced_exit_sender, 13);
+        assert!(other_request.fulfilled_by.is_none());
+        assert!(other_request.match_scheme.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_forced_exit_sender_misaligned_price() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+
+        // The legacy request, the price of which overlaps with the id
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000005"),
+        );
+
+        // Neither the price with the id added, nor the id on its own yield the request
+        forced_exit_sender
+            .process_request(payment("10000000017", None), Utc::now())
+            .await
+            .unwrap();
+        forced_exit_sender
+            .process_request(payment("10000000012", None), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+
+        // The request can still be paid for with the explicit id
+        forced_exit_sender
+            .process_request(payment("10000000005", Some(12)), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+    }
+
+    #[tokio::test]
+    async fn test_forced_exit_sender_pay_exactly_mismatch() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+
+        // The stored amount disagrees with the price and the id
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            ForcedExitRequest {
+                pay_exactly: "100000000012".to_owned(),
+                ..get_test_request(12, "10000000000")
+            },
+        );
+
+        let decision = forced_exit_sender
+            .try_process_request(payment("10000000012", None), Utc::now())
+            .await
+            .unwrap();
+        assert!(matches!(decision, PaymentDecision::Unmatched { .. }));
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+
+        // The explicit id does not rely on the amount
+        forced_exit_sender
+            .process_request(payment("10000000000", Some(12)), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+    }
+
+    fn expect_payment(
+        sender: &MempoolForcedExitSender<MockCoreInteractionWrapper>,
+        request_id: ForcedExitRequestId,
+        eth_tx_hash: H256,
+    ) {
+        sender
+            .core_interaction_wrapper
+            .lock_expected_payments()
+            .push(ExpectedForcedExitPayment {
+                request_id,
+                eth_tx_hash,
+                registered_at: Utc::now(),
+                mismatched_amount: None,
+            });
+    }
+
+    #[tokio::test]
+    async fn expected_payment_is_matched_by_hash() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+
+        // The amount to pay does not yield the request, yet the hash does
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            ForcedExitRequest {
+                pay_exactly: "100000000012".to_owned(),
+                ..get_test_request(12, "10000000000")
+            },
+        );
+        let eth_tx_hash = H256::repeat_byte(0x21);
+        expect_payment(&forced_exit_sender, 12, eth_tx_hash);
+
+        let decision = forced_exit_sender
+            .try_process_request(
+                FundsReceivedEvent {
+                    eth_tx_hash: Some(eth_tx_hash),
+                    ..payment("100000000012", None)
+                },
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            decision,
+            PaymentDecision::Fulfilled {
+                request_id: 12,
+                match_scheme: PaymentMatchScheme::ExpectedPayment,
+                ..
+            }
+        ));
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+        assert_eq!(
+            get_stored_request(&forced_exit_sender, 12).match_scheme,
+            Some(PaymentMatchScheme::ExpectedPayment)
+        );
+    }
+
+    #[tokio::test]
+    async fn expected_payment_amount_mismatch_is_flagged() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        let eth_tx_hash = H256::repeat_byte(0x21);
+        expect_payment(&forced_exit_sender, 12, eth_tx_hash);
+
+        // Paying more than the price is fine for the explicit id, but not for the registered payment
+        let decision = forced_exit_sender
+            .try_process_request(
+                FundsReceivedEvent {
+                    eth_tx_hash: Some(eth_tx_hash),
+                    ..payment("10000000005", Some(12))
+                },
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            decision,
+            PaymentDecision::Unmatched {
+                request_id: 12,
+                match_scheme: PaymentMatchScheme::ExpectedPayment,
+            }
+        );
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+        assert_eq!(
+            forced_exit_sender
+                .core_interaction_wrapper
+                .lock_expected_payments()[0]
+                .mismatched_amount
+                .as_deref(),
+            Some("10000000005")
+        );
+    }
+
+    #[tokio::test]
+    async fn unregistered_payment_is_matched_by_amount() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        expect_payment(&forced_exit_sender, 12, H256::repeat_byte(0x21));
+
+        // The request is paid for by another transaction than the registered one
+        forced_exit_sender
+            .process_request(
+                FundsReceivedEvent {
+                    eth_tx_hash: Some(H256::repeat_byte(0x22)),
+                    ..payment("10000000012", None)
+                },
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+        assert_eq!(
+            get_stored_request(&forced_exit_sender, 12).match_scheme,
+            Some(PaymentMatchScheme::AmountDigits)
+        );
+        assert_eq!(
+            forced_exit_sender
+                .core_interaction_wrapper
+                .lock_expected_payments()[0]
+                .mismatched_amount,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn checked_amounts_are_matched() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 9,
+            legacy_amount_ids_enabled: false,
+            overpayment_tolerance: 0,
+            overpayment_tolerance_percent: 0,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_checked_test_request(12, "10000000000"),
+        );
+        assert_eq!(
+            pay_exactly(&BigUint::from(10_000_000_000u64), 12),
+            "10000000125"
+        );
+
+        // The legacy amount and the ones with a mistyped digit are not matched
+        for amount in ["10000000012", "10000000124", "10000000135", "10000000215"] {
+            forced_exit_sender
+                .process_request(payment(amount, None), Utc::now())
+                .await
+                .unwrap();
+        }
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+
+        forced_exit_sender
+            .process_request(payment("10000000125", None), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+        assert_eq!(
+            get_stored_request(&forced_exit_sender, 12).match_scheme,
+            Some(PaymentMatchScheme::AmountDigits)
+        );
+    }
+
+    #[tokio::test]
+    async fn legacy_amounts_are_matched_when_enabled() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 9,
+            legacy_amount_ids_enabled: true,
+            overpayment_tolerance: 0,
+            overpayment_tolerance_percent: 0,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_checked_test_request(13, "10000000000"),
+        );
+
+        // Each request is paid for only with its own kind of the amount
+        for amount in ["10000000013", "10000000125"] {
+            forced_exit_sender
+                .process_request(payment(amount, None), Utc::now())
+                .await
+                .unwrap();
+        }
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+
+        for amount in ["10000000012", "10000000133"] {
+            forced_exit_sender
+                .process_request(payment(amount, None), Utc::now())
+                .await
+                .unwrap();
+        }
+        assert_eq!(sent_txs_count(&forced_exit_sender), 2);
+    }
+
+    #[tokio::test]
+    async fn payments_are_matched_by_public_ids() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 9,
+            legacy_amount_ids_enabled: false,
+            overpayment_tolerance: 0,
+            overpayment_tolerance_percent: 0,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        let price = BigUint::from(10_000_000_000u64);
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            ForcedExitRequest {
+                public_id: 1234,
+                pay_exactly: pay_exactly(&price, 1234),
+                ..get_checked_test_request(12, "10000000000")
+            },
+        );
+
+        // The key of the stored request means nothing to the payers
+        for (amount, request_id) in [
+            (pay_exactly(&price, 12), None),
+            (price.to_string(), Some(12)),
+        ] {
+            forced_exit_sender
+                .process_request(payment(&amount, request_id), Utc::now())
+                .await
+                .unwrap();
+        }
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+
+        forced_exit_sender
+            .process_request(payment(&pay_exactly(&price, 1234), None), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+        assert_eq!(
+            get_stored_request(&forced_exit_sender, 12).match_scheme,
+            Some(PaymentMatchScheme::AmountDigits)
+        );
+    }
+
+    #[tokio::test]
+    async fn payments_below_id_space_are_unmatched() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 9,
+            legacy_amount_ids_enabled: true,
+            processing_attempts: 4,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_checked_test_request(12, "10000000000"),
+        );
+
+        // Neither the zero amount nor the id alone pay for anything
+        for amount in ["0", "12", "125"] {
+            let decision = forced_exit_sender
+                .process_payment(payment(amount, None), Utc::now())
+                .await
+                .unwrap();
+            assert!(matches!(
+                decision,
+                PaymentDecision::Unmatched { request_id: 0, .. }
+            ));
+        }
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+        assert!(forced_exit_sender
+            .core_interaction_wrapper
+            .lock_processing_failures()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn requests_are_matched_by_their_own_terms() {
+        let terms =
+            |digits_in_id, check_digit, overpayment_tolerance: u32| ForcedExitPaymentTerms {
+                digits_in_id,
+                check_digit,
+                overpayment_tolerance: BigUint::from(overpayment_tolerance),
+                expiration_grace_period: 0,
+            };
+        // The configs the requests were created with over time: the number of digits,
+        // the scheme of the amount, the price and the tolerance all differ
+        let historical = [
+            (terms(4, true, 0), "300000", 1234),
+            (terms(8, true, 0), "7000000000", 5678),
+            (terms(6, false, 0), "5000000", 4321),
+            (terms(10, true, 1000), "900000000000", 8765),
+        ];
+        // None of which is current
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 7,
+            legacy_amount_ids_enabled: false,
+            overpayment_tolerance: 0,
+            overpayment_tolerance_percent: 0,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        for (terms, price, id) in historical.iter() {
+            let price_in_wei = BigUint::from_str(price).unwrap();
+            add_request(
+                &forced_exit_sender.core_interaction_wrapper.requests,
+                ForcedExitRequest {
+                    pay_exactly: terms.pay_exactly(&price_in_wei, *id),
+                    payment_terms: Some(terms.clone()),
+                    ..get_checked_test_request(*id, price)
+                },
+            );
+        }
+
+        // The payment formed by the terms and the price of another config is not matched
+        for (_, _, id) in historical.iter() {
+            for (terms, price, other_id) in historical.iter() {
+                if id == other_id {
+                    continue;
+                }
+                let amount = terms.pay_exactly(&BigUint::from_str(price).unwrap(), *id);
+                forced_exit_sender
+                    .process_request(payment(&amount, None), Utc::now())
+                    .await
+                    .unwrap();
+            }
+        }
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+
+        // The overpayment is tolerated by the request created with the tolerance
+        let (terms, price, id) = &historical[3];
+        let overpaid =
+            BigUint::from_str(&terms.pay_exactly(&BigUint::from_str(price).unwrap(), *id)).unwrap()
+                + 500u32;
+        forced_exit_sender
+            .process_request(payment(&overpaid.to_string(), None), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+        assert!(get_stored_request(&forced_exit_sender, *id)
+            .fulfilled_at
+            .is_some());
+
+        // Each of the rest is matched by the payment formed by its own terms
+        for (sent, (terms, price, id)) in historical[..3].iter().enumerate() {
+            let amount = terms.pay_exactly(&BigUint::from_str(price).unwrap(), *id);
+            forced_exit_sender
+                .process_request(payment(&amount, None), Utc::now())
+                .await
+                .unwrap();
+            assert_eq!(sent_txs_count(&forced_exit_sender), sent + 2);
+            assert_eq!(
+                get_stored_request(&forced_exit_sender, *id).match_scheme,
+                Some(PaymentMatchScheme::AmountDigits)
+            );
+        }
+    }
+}
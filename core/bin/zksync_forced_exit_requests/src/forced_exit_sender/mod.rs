@@ -0,0 +1,2259 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use num::BigUint;
+use serde::{Deserialize, Serialize};
+use tokio::time;
+
+use zksync_api::api_server::forced_exit_receipts::{match_receipts, tx_status};
+use zksync_config::ForcedExitRequestsConfig;
+use zksync_storage::chain::operations_ext::records::TxReceiptResponse;
+use zksync_types::{
+    forced_exit_requests::{
+        ActiveTargetPolicy, ForcedExitBlocker, ForcedExitCancellationKind, ForcedExitPipelineStage,
+        ForcedExitPipelineVersion, ForcedExitPreflight, ForcedExitProcessingFailure,
+        ForcedExitRequest, ForcedExitRequestId, ForcedExitRetry, ForcedExitTxStatus,
+        FundsReceivedEvent, PaymentMatchScheme, SaveForcedExitRequestNoteQuery, SkippedForcedExit,
+        SubmissionError, FORCED_EXIT_PIPELINE_VERSION,
+    },
+    tx::TxHash,
+    AccountId, Address, Nonce, TokenId, H256,
+};
+
+use crate::{
+    core_interaction_wrapper::CoreInteractionWrapper,
+    l1_transfer_check::L1TransferCheck,
+    metrics as sender_metrics,
+    pacing::SubmissionPacer,
+    receipt_poller::ReceiptPoller,
+    refund_budget::RefundBudget,
+    sender_accounts::{SenderAccount, SenderAccounts},
+    shutdown::ShutdownSignal,
+    token_cache::{DependencyUnavailable, LastKnownTokens, TokenCache},
+    token_labels::TokenLabels,
+};
+
+use self::fees::is_fee_too_low;
+
+mod batches;
+mod escalation;
+mod fees;
+mod matching;
+mod pacing;
+mod reconcile;
+mod refunds;
+mod targets;
+mod transactions;
+
+#[cfg(test)]
+mod test_utils;
+
+/// The outcome of processing the payment.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "decision", rename_all = "camelCase")]
+pub enum PaymentDecision {
+    /// There is no payable request the payment could be matched with.
+    #[serde(rename_all = "camelCase")]
+    Unmatched {
+        request_id: ForcedExitRequestId,
+        match_scheme: PaymentMatchScheme,
+    },
+    /// The request has been escalated and is fulfilled on L1 by the operators.
+    #[serde(rename_all = "camelCase")]
+    Escalated { request_id: ForcedExitRequestId },
+    /// The target account of the request can not be forced to exit.
+    #[serde(rename_all = "camelCase")]
+    NotPossible { request_id: ForcedExitRequestId },
+    /// The target account has set its signing key after the request was created,
+    /// the request has failed or is held according to the policy.
+    #[serde(rename_all = "camelCase")]
+    TargetBecameActive {
+        request_id: ForcedExitRequestId,
+        policy: ActiveTargetPolicy,
+    },
+    /// The `ForcedExit` transactions for the tokens of the request have been committed.
+    #[serde(rename_all = "camelCase")]
+    Fulfilled {
+        request_id: ForcedExitRequestId,
+        match_scheme: PaymentMatchScheme,
+        tokens: Vec<TokenId>,
+    },
+    /// The request has no tokens to withdraw, e.g. all of them have been skipped,
+    /// so it is fulfilled without any transactions.
+    #[serde(rename_all = "camelCase")]
+    NothingToExit {
+        request_id: ForcedExitRequestId,
+        match_scheme: PaymentMatchScheme,
+    },
+    /// The data the processing depends on is unavailable, the payment is processed again
+    /// once it is available. Such failures do not count as the processing attempts.
+    #[serde(rename_all = "camelCase")]
+    Deferred {
+        request_id: ForcedExitRequestId,
+        dependency: String,
+    },
+    /// The transactions have been sent, but have not been committed in time. The request
+    /// stays in flight until the reconciliation settles it.
+    #[serde(rename_all = "camelCase")]
+    InFlight {
+        request_id: ForcedExitRequestId,
+        match_scheme: PaymentMatchScheme,
+    },
+    /// All the processing attempts have failed.
+    Failed { error: String },
+}
+
+/// The transaction awaited by the sender has not been committed successfully.
+#[derive(Debug, thiserror::Error)]
+pub enum CommitError {
+    /// The receipt has not appeared in time, the transaction may still be committed.
+    #[error("ForcedExit transaction {tx_hash:?} has not been committed within {timeout:?}")]
+    Timeout { tx_hash: TxHash, timeout: Duration },
+    /// The transaction has been executed and has failed.
+    #[error("ForcedExit transaction {tx_hash:?} has failed: {reason}")]
+    Failed { tx_hash: TxHash, reason: String },
+    /// The drain deadline of the shutdown has passed before the receipt appeared.
+    #[error("ForcedExit transaction {tx_hash:?} has not been committed before the shutdown")]
+    Interrupted { tx_hash: TxHash },
+}
+
+/// The reasons the `ForcedExit` is failed with by the state once its target address is not
+/// the account it was resolved to, e.g. after the block creating the account has been reverted.
+const TARGET_MISMATCH_REASONS: [&str; 2] =
+    ["Target account does not exist", "Target account is invalid"];
+
+impl CommitError {
+    /// Whether the transaction has failed since its target is not the account it was
+    /// resolved to. The reason of the failed batch includes the one of the transaction.
+    pub fn is_target_mismatch(&self) -> bool {
+        match self {
+            Self::Failed { reason, .. } => TARGET_MISMATCH_REASONS
+                .iter()
+                .any(|mismatch| reason.contains(mismatch)),
+            Self::Timeout { .. } | Self::Interrupted { .. } => false,
+        }
+    }
+}
+
+/// How long the processing waits after the failed attempt with the given number (starting from 1),
+/// the delay doubles with every next attempt up to `processing_retry_max_delay`.
+fn processing_retry_delay(config: &ForcedExitRequestsConfig, attempt: u32) -> Duration {
+    let factor = 1u64
+        .checked_shl(attempt.saturating_sub(1))
+        .unwrap_or(u64::MAX);
+    let delay = config.processing_retry_base_delay.saturating_mul(factor);
+    Duration::from_millis(delay.min(config.processing_retry_max_delay))
+}
+
+/// The request with only the tokens left after the `committed` transactions, which are matched
+/// with the tokens by their positions.
+fn unsent_part(fe_request: &ForcedExitRequest, committed: usize) -> ForcedExitRequest {
+    ForcedExitRequest {
+        tokens: fe_request.tokens[committed.min(fe_request.tokens.len())..].to_vec(),
+        ..fe_request.clone()
+    }
+}
+
+/// The transactions of the request committed before the first one, which has failed or has not
+/// been executed yet. The batches of the request are sent once the ones before are committed.
+fn committed_prefix(hashes: &[TxHash], receipts: &[TxReceiptResponse]) -> Vec<TxHash> {
+    hashes
+        .iter()
+        .zip(match_receipts(hashes, receipts))
+        .take_while(|(_, receipt)| {
+            matches!(
+                tx_status(*receipt),
+                ForcedExitTxStatus::Committed | ForcedExitTxStatus::Verified
+            )
+        })
+        .map(|(hash, _)| *hash)
+        .collect()
+}
+
+/// The payment is not processed since the shutdown has been requested, it is processed
+/// once the payment is replayed after the restart.
+#[derive(Debug, thiserror::Error)]
+#[error("The payment is not processed, the forced exit sender is shutting down")]
+pub struct ShuttingDown;
+
+/// There are no `ForcedExit` transactions to build for the request.
+#[derive(Debug, thiserror::Error)]
+#[error("ForcedExit request {request_id} has no tokens to withdraw")]
+pub struct NothingToExit {
+    pub request_id: ForcedExitRequestId,
+}
+
+/// The fee raised after the batch has been rejected for its outdated fee exceeds `max_batch_fee`.
+#[derive(Debug, thiserror::Error)]
+#[error("The fee {fee} of the ForcedExit batch exceeds the maximum of {max_fee}")]
+pub struct FeeLimitExceeded {
+    pub fee: BigUint,
+    pub max_fee: BigUint,
+}
+
+/// The target address of the request has no account, so the `ForcedExit` transactions
+/// would fail on every attempt.
+#[derive(Debug, thiserror::Error)]
+#[error("Target of the ForcedExit request {request_id} not found")]
+pub struct TargetNotFound {
+    pub request_id: ForcedExitRequestId,
+}
+
+/// The outcome of preparing the paid request to be sent, see `MempoolForcedExitSender::claim`.
+enum PreparedPayment {
+    /// The transactions of the request are to be sent, its fulfillment has been claimed.
+    Claimed(Box<ClaimedRequest>),
+    /// The payment has been settled without sending anything.
+    Settled(PaymentDecision),
+}
+
+/// The request, the fulfillment of which has been claimed, with the transactions planned for it.
+/// The transactions `committed` by the batches sent before are kept, only the rest are planned.
+struct ClaimedRequest {
+    fe_request: ForcedExitRequest,
+    preflight: ForcedExitPreflight,
+    match_scheme: PaymentMatchScheme,
+    committed: Vec<TxHash>,
+}
+
+/// The outcome of awaiting the transactions sent for the request before, see
+/// `MempoolForcedExitSender::await_sent_batch`.
+enum SentBatch {
+    /// The transactions are committed or are still in flight.
+    Settled(PaymentDecision),
+    /// The rest of the transactions of the request are to be sent following the `committed` ones.
+    Unsent { committed: Vec<TxHash> },
+}
+
+#[async_trait::async_trait]
+pub trait ForcedExitSender {
+    /// Processes the payment, the error means it has been given up on after all the attempts.
+    async fn process_request(
+        &mut self,
+        payment: FundsReceivedEvent,
+        submission_time: DateTime<Utc>,
+    ) -> anyhow::Result<()>;
+
+    /// Processes the payments received within the aggregation window, the transactions
+    /// of their requests are sent together where possible. The results are the ones
+    /// of `process_request` for each of the payments, in the same order.
+    async fn process_requests(
+        &mut self,
+        payments: Vec<(FundsReceivedEvent, DateTime<Utc>)>,
+    ) -> Vec<anyhow::Result<()>>;
+
+    /// Settles the requests sent before, returns the number of the ones still in flight.
+    async fn reconcile_unconfirmed(&mut self, timeout: Duration) -> anyhow::Result<usize>;
+
+    /// Whether the requests have been left in flight since the last reconciliation,
+    /// e.g. since their transactions have not been committed in time.
+    fn has_left_in_flight(&self) -> bool;
+
+    /// Resumes or fails the requests held because of their active targets.
+    async fn process_held_requests(&mut self, now: DateTime<Utc>) -> anyhow::Result<()>;
+
+    /// Processes again the payments deferred because of the unavailable data.
+    async fn process_deferred_requests(&mut self);
+
+    /// Sends the refunds of the payments and settles the ones sent before.
+    async fn process_refunds(&mut self) -> anyhow::Result<()>;
+
+    /// Sends the transactions of the failed request once again, returns their hashes.
+    async fn retry_request(
+        &mut self,
+        retry: &ForcedExitRetry,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<TxHash>>;
+
+    /// Waits until the payments taken before the shutdown are done with, see `ShutdownSignal`.
+    async fn drain(&mut self);
+}
+
+pub struct MempoolForcedExitSender<T: CoreInteractionWrapper> {
+    pub(crate) core_interaction_wrapper: T,
+    config: ForcedExitRequestsConfig,
+    /// The accounts the transactions are sent from, shared with the other senders.
+    sender_accounts: Arc<SenderAccounts>,
+    zksync_contract: Address,
+    last_known_tokens: LastKnownTokens,
+    /// The payments are kept in memory only, the ones lost on restart
+    /// can be replayed from the payment log.
+    deferred: Vec<(FundsReceivedEvent, DateTime<Utc>)>,
+    /// The receipts are polled by the sender itself if the poller is not set.
+    receipt_poller: Option<ReceiptPoller>,
+    /// The tokens are not checked for the restrictions on L1 if the check is not set.
+    l1_transfer_check: Option<L1TransferCheck>,
+    token_labels: TokenLabels,
+    /// Whether the requests have been left in flight since the last reconciliation.
+    left_in_flight: bool,
+    /// The hash of the config the requests are fulfilled with, see `ForcedExitPipelineConfig`.
+    pipeline_config_hash: String,
+    /// Shared with the other senders of the account and the watcher applying the changes of the pacing.
+    pacer: Arc<SubmissionPacer>,
+    /// Only the first of the senders of the account sends the refunds.
+    refund_budget: RefundBudget,
+    shutdown: ShutdownSignal,
+    /// The time the validity of the transactions is counted from, fixed by the tests.
+    clock: fn() -> DateTime<Utc>,
+    /// How many times the batch of the payment being processed has been rejected for its
+    /// outdated fee, the fee is raised as many times, see `quote_batch_fee`.
+    fee_rejections: u32,
+}
+
+#[async_trait::async_trait]
+impl<T: CoreInteractionWrapper + Sync + Send> ForcedExitSender for MempoolForcedExitSender<T> {
+    async fn process_request(
+        &mut self,
+        payment: FundsReceivedEvent,
+        submission_time: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        self.process_payment(payment, submission_time).await?;
+        Ok(())
+    }
+
+    async fn process_requests(
+        &mut self,
+        payments: Vec<(FundsReceivedEvent, DateTime<Utc>)>,
+    ) -> Vec<anyhow::Result<()>> {
+        self.process_payments(payments)
+            .await
+            .into_iter()
+            .map(|result| result.map(|_| ()))
+            .collect()
+    }
+
+    async fn reconcile_unconfirmed(&mut self, timeout: Duration) -> anyhow::Result<usize> {
+        MempoolForcedExitSender::reconcile_unconfirmed(self, timeout).await
+    }
+
+    fn has_left_in_flight(&self) -> bool {
+        self.left_in_flight
+    }
+
+    async fn process_held_requests(&mut self, now: DateTime<Utc>) -> anyhow::Result<()> {
+        MempoolForcedExitSender::process_held_requests(self, now).await
+    }
+
+    async fn process_deferred_requests(&mut self) {
+        MempoolForcedExitSender::process_deferred_requests(self).await
+    }
+
+    async fn process_refunds(&mut self) -> anyhow::Result<()> {
+        MempoolForcedExitSender::process_refunds(self).await
+    }
+
+    async fn retry_request(
+        &mut self,
+        retry: &ForcedExitRetry,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<TxHash>> {
+        MempoolForcedExitSender::retry_request(self, retry, now).await
+    }
+
+    async fn drain(&mut self) {
+        // The payments are processed while taken, only the deferred ones are left
+        if !self.deferred.is_empty() {
+            vlog::warn!(
+                "{} deferred forced exit payments are left to be replayed after the restart",
+                self.deferred.len()
+            );
+            self.deferred.clear();
+        }
+    }
+}
+
+impl<T: CoreInteractionWrapper + Sync> MempoolForcedExitSender<T> {
+    pub fn new(
+        core_interaction_wrapper: T,
+        config: ForcedExitRequestsConfig,
+        forced_exit_sender_account_id: AccountId,
+        zksync_contract: Address,
+    ) -> Self {
+        let sender_accounts = Arc::new(SenderAccounts::new(
+            SenderAccount::main(&config, forced_exit_sender_account_id),
+            Vec::new(),
+        ));
+        let token_labels = TokenLabels::new(config.metrics_max_token_labels);
+        let pipeline_config_hash = config.pipeline_config().hash();
+        let pacer = Arc::new(SubmissionPacer::new(config.pacing()));
+        let refund_budget = RefundBudget::new(config.max_refunded_amount_per_hour.clone());
+
+        Self {
+            core_interaction_wrapper,
+            config,
+            sender_accounts,
+            zksync_contract,
+            last_known_tokens: LastKnownTokens::default(),
+            deferred: Vec::new(),
+            receipt_poller: None,
+            l1_transfer_check: None,
+            token_labels,
+            left_in_flight: false,
+            pipeline_config_hash,
+            pacer,
+            refund_budget,
+            shutdown: ShutdownSignal::never(),
+            clock: Utc::now,
+            fee_rejections: 0,
+        }
+    }
+
+    /// Sends the transactions from the accounts shared with the other senders, one batch
+    /// at a time from each of them, see the `sender_accounts` module.
+    pub fn with_sender_accounts(mut self, sender_accounts: Arc<SenderAccounts>) -> Self {
+        self.sender_accounts = sender_accounts;
+        self
+    }
+
+    /// Paces the batches together with the other senders.
+    pub fn with_pacer(mut self, pacer: Arc<SubmissionPacer>) -> Self {
+        self.pacer = pacer;
+        self
+    }
+
+    /// Awaits the transactions through the poller shared with the other senders.
+    pub fn with_receipt_poller(mut self, receipt_poller: ReceiptPoller) -> Self {
+        self.receipt_poller = Some(receipt_poller);
+        self
+    }
+
+    /// Stops taking the payments once the shutdown is requested, see the `shutdown` module.
+    pub fn with_shutdown(mut self, shutdown: ShutdownSignal) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Skips the tokens, the withdrawals of which to the target would be stuck on L1.
+    pub fn with_l1_transfer_check(mut self, l1_transfer_check: L1TransferCheck) -> Self {
+        self.l1_transfer_check = Some(l1_transfer_check);
+        self
+    }
+
+    /// The token cache for a new cycle, falling back to the tokens loaded by the previous ones.
+    fn token_cache(&self) -> TokenCache {
+        TokenCache::with_last_known(
+            self.last_known_tokens.clone(),
+            self.config.token_cache_max_staleness(),
+        )
+    }
+
+    /// The nonce of the next transaction of the sender account.
+    async fn next_nonce(&self, account: &SenderAccount) -> anyhow::Result<Nonce> {
+        let committed_nonce = self
+            .core_interaction_wrapper
+            .get_nonce(account.account_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Forced Exit sender account does not have nonce"))?;
+        // The transactions sent for the previous requests may still be in the mempool,
+        // e.g. the ones left in flight, the nonces must follow theirs
+        let pending_nonce = self
+            .core_interaction_wrapper
+            .get_pending_nonce(account.account_id)
+            .await?;
+
+        Ok(pending_nonce.map_or(committed_nonce, |pending| pending.max(committed_nonce)))
+    }
+
+    /// Processes the payment, the failed attempts are repeated with the growing delays.
+    /// The error is returned once all the attempts have failed, the failure is recorded
+    /// for the request then, so the payment is not processed again by itself.
+    ///
+    /// The batch rejected for its outdated fee is sent again with the fee quoted anew
+    /// and raised, see `quote_batch_fee`.
+    pub async fn process_payment(
+        &mut self,
+        payment: FundsReceivedEvent,
+        submission_time: DateTime<Utc>,
+    ) -> anyhow::Result<PaymentDecision> {
+        let processed = self
+            .process_payment_attempts(payment, submission_time)
+            .await;
+        self.fee_rejections = 0;
+        processed
+    }
+
+    async fn process_payment_attempts(
+        &mut self,
+        payment: FundsReceivedEvent,
+        submission_time: DateTime<Utc>,
+    ) -> anyhow::Result<PaymentDecision> {
+        let max_attempts = self.config.processing_attempts.max(1);
+        let mut attempts: u32 = 0;
+        // Typically this should not run any longer than 1 iteration
+        // In case something bad happens we do not want the server crush because
+        // of the forced_exit_requests component
+        loop {
+            // The payment is replayed after the restart, nothing is recorded for it
+            if self.shutdown.is_requested() {
+                return Err(ShuttingDown.into());
+            }
+            let processing_attempt = self
+                .try_process_request(payment.clone(), submission_time)
+                .await;
+
+            match processing_attempt {
+                Ok(decision) => {
+                    sender_metrics::report_decision(&decision, submission_time);
+                    return Ok(decision);
+                }
+                Err(err) => {
+                    if let Some(unavailable) = err.downcast_ref::<DependencyUnavailable>() {
+                        return Ok(self.defer_payment(payment, submission_time, unavailable));
+                    }
+                    // Every attempt would fail the same way, the request has been released
+                    if let Some(not_found) = err.downcast_ref::<TargetNotFound>() {
+                        let request_id = not_found.request_id;
+                        let decision = self
+                            .fail_missing_target(&payment, request_id, submission_time)
+                            .await?;
+                        sender_metrics::report_decision(&decision, submission_time);
+                        return Ok(decision);
+                    }
+                    // The request has been released without sending anything
+                    if err.is::<ShuttingDown>() {
+                        return Err(err);
+                    }
+                    attempts += 1;
+                    let (public_id, _, _) = self.matcher().payment_target(&payment);
+                    let request_id = self.stored_request_id(public_id).await.unwrap_or(public_id);
+                    // The transactions rejected for what they are would be rejected again,
+                    // unless it is only their fee, which is raised by the next attempt
+                    let fee_too_low = self.config.fee_token.is_some()
+                        && matches!(
+                            err.downcast_ref::<SubmissionError>(),
+                            Some(submission_error) if is_fee_too_low(submission_error)
+                        );
+                    if fee_too_low {
+                        self.fee_rejections += 1;
+                        metrics::increment_counter!("forced_exit_requests.outdated_fee_rejections");
+                    }
+                    let rejected = matches!(
+                        err.downcast_ref::<SubmissionError>(),
+                        Some(submission_error) if !submission_error.retryable && !fee_too_low
+                    ) || err.is::<FeeLimitExceeded>();
+
+                    if attempts >= max_attempts || rejected {
+                        // We should not get stuck processing requests that possibly could never be processed
+                        self.record_processing_failure(request_id, attempts, &err)
+                            .await;
+                        sender_metrics::report_failure(&err);
+                        return Err(err.context(format!(
+                            "Failed to process the payment for ForcedExit request {} in {} attempts",
+                            request_id, attempts
+                        )));
+                    }
+
+                    let delay = processing_retry_delay(&self.config, attempts);
+                    vlog::warn!(
+                        "Attempt {} to process the payment for ForcedExit request {} has failed, retrying in {}ms: {}",
+                        attempts,
+                        request_id,
+                        delay.as_millis(),
+                        err
+                    );
+                    let mut shutdown = self.shutdown.clone();
+                    tokio::select! {
+                        biased;
+                        _ = shutdown.requested() => {}
+                        _ = time::sleep(delay) => continue,
+                    }
+                    // The attempts left are made once the payment is replayed after the restart
+                    self.note_shutdown(
+                        request_id,
+                        format!(
+                            "The payment has not been processed before the shutdown \
+                             after {} failed attempts: {}",
+                            attempts, err
+                        ),
+                    )
+                    .await;
+                    return Err(ShuttingDown.into());
+                }
+            }
+        }
+    }
+
+    async fn record_processing_failure(
+        &self,
+        request_id: ForcedExitRequestId,
+        attempts: u32,
+        err: &anyhow::Error,
+    ) {
+        let failure = ForcedExitProcessingFailure {
+            request_id,
+            attempts,
+            error: err.to_string(),
+            failed_at: Utc::now(),
+            submission_error: err.downcast_ref::<SubmissionError>().cloned(),
+        };
+        // The payment is logged by the caller either way
+        if let Err(record_err) = self
+            .core_interaction_wrapper
+            .record_processing_failure(failure)
+            .await
+        {
+            vlog::warn!(
+                "Failed to record the processing failure of ForcedExit request {}: {}",
+                request_id,
+                record_err
+            );
+        }
+    }
+
+    fn defer_payment(
+        &mut self,
+        payment: FundsReceivedEvent,
+        submission_time: DateTime<Utc>,
+        unavailable: &DependencyUnavailable,
+    ) -> PaymentDecision {
+        let (request_id, _, _) = self.matcher().payment_target(&payment);
+        vlog::warn!(
+            "Processing of the payment for ForcedExit request {} is deferred: {}",
+            request_id,
+            unavailable
+        );
+        metrics::increment_counter!(
+            "forced_exit_requests.degraded_decisions",
+            "dependency" => unavailable.dependency,
+            "decision" => "deferred"
+        );
+        self.deferred.push((payment, submission_time));
+        PaymentDecision::Deferred {
+            request_id,
+            dependency: unavailable.dependency.to_string(),
+        }
+    }
+
+    /// Processes the deferred payments as if they were received at the original time,
+    /// the ones which still can not be processed are deferred again.
+    pub async fn process_deferred_requests(&mut self) {
+        for (payment, submission_time) in std::mem::take(&mut self.deferred) {
+            if let Err(err) = self.process_payment(payment, submission_time).await {
+                vlog::error!("Failed to process the deferred payment: {:#}", err);
+                metrics::increment_counter!("forced_exit_requests.failed_payments");
+            }
+        }
+    }
+
+    /// Processes the payments received together, the transactions of the requests ready
+    /// to be sent are sent in a single batch, so its commit is awaited once for all of them.
+    ///
+    /// The payments which fail to be prepared and the requests of the batch which has been
+    /// refused or has failed are processed one by one afterwards with the attempts of their own,
+    /// so neither holds the rest of the requests back.
+    pub async fn process_payments(
+        &mut self,
+        payments: Vec<(FundsReceivedEvent, DateTime<Utc>)>,
+    ) -> Vec<anyhow::Result<PaymentDecision>> {
+        let mut results: Vec<Option<anyhow::Result<PaymentDecision>>> =
+            payments.iter().map(|_| None).collect();
+        let mut one_by_one = Vec::new();
+        let mut claimed = Vec::new();
+        let mut claimed_txs = 0;
+        let mut on_their_own = Vec::new();
+        for (i, (payment, submission_time)) in payments.iter().enumerate() {
+            // There is nothing to send the single payment together with
+            if payments.len() == 1 || self.shutdown.is_requested() {
+                one_by_one.push(i);
+                continue;
+            }
+            match self
+                .prepare_payment(payment.clone(), *submission_time)
+                .await
+            {
+                // The requests not fitting into the batch with the rest are sent on their own,
+                // as are the ones sent in part, their transactions follow the committed ones
+                Ok(PreparedPayment::Claimed(request)) => {
+                    let txs = request.preflight.batch_size();
+                    if claimed_txs + txs > self.config.max_batch_size
+                        || !request.committed.is_empty()
+                    {
+                        on_their_own.push((i, *request));
+                    } else {
+                        claimed_txs += txs;
+                        claimed.push((i, *request));
+                    }
+                }
+                Ok(PreparedPayment::Settled(decision)) => {
+                    sender_metrics::report_decision(&decision, *submission_time);
+                    results[i] = Some(Ok(decision));
+                }
+                Err(err) => {
+                    let (request_id, _, _) = self.matcher().payment_target(payment);
+                    vlog::warn!(
+                        "The payment for ForcedExit request {} is processed on its own: {}",
+                        request_id,
+                        err
+                    );
+                    one_by_one.push(i);
+                }
+            }
+        }
+
+        if !claimed.is_empty() {
+            let (indices, requests): (Vec<usize>, Vec<ClaimedRequest>) =
+                claimed.into_iter().unzip();
+            let decisions = self.send_together(requests).await;
+            for (i, decision) in indices.into_iter().zip(decisions) {
+                match decision {
+                    Some(decision) => {
+                        sender_metrics::report_decision(&decision, payments[i].1);
+                        results[i] = Some(Ok(decision));
+                    }
+                    None => one_by_one.push(i),
+                }
+            }
+        }
+        for (i, request) in on_their_own {
+            let id = request.fe_request.id;
+            match self.send_claimed(request).await {
+                Ok(decision) => {
+                    sender_metrics::report_decision(&decision, payments[i].1);
+                    results[i] = Some(Ok(decision));
+                }
+                // Released by the failed attempt, the payment is processed again
+                Err(err) => {
+                    vlog::warn!(
+                        "The payment for ForcedExit request {} is processed on its own: {}",
+                        id,
+                        err
+                    );
+                    one_by_one.push(i);
+                }
+            }
+        }
+
+        one_by_one.sort_unstable();
+        for i in one_by_one {
+            let (payment, submission_time) = payments[i].clone();
+            results[i] = Some(self.process_payment(payment, submission_time).await);
+        }
+        results
+            .into_iter()
+            .map(|result| result.expect("Every payment is processed"))
+            .collect()
+    }
+
+    pub async fn try_process_request(
+        &mut self,
+        payment: FundsReceivedEvent,
+        submission_time: DateTime<Utc>,
+    ) -> anyhow::Result<PaymentDecision> {
+        match self.prepare_payment(payment, submission_time).await? {
+            PreparedPayment::Claimed(claimed) => self.send_claimed(*claimed).await,
+            PreparedPayment::Settled(decision) => Ok(decision),
+        }
+    }
+
+    /// Matches the payment and checks the request, claiming its fulfillment if the transactions
+    /// are to be sent, see `claim`, or resuming it if it has been sent in part, see `resume`.
+    async fn prepare_payment(
+        &mut self,
+        payment: FundsReceivedEvent,
+        submission_time: DateTime<Utc>,
+    ) -> anyhow::Result<PreparedPayment> {
+        let payment_tx_hash = payment.eth_tx_hash;
+        let payment_amount = payment.amount.clone();
+        let (mut fe_request, match_scheme) = match self
+            .match_payment(payment.clone(), submission_time)
+            .await?
+        {
+            Some(matched) => matched,
+            None => {
+                // The request was not valid, that's fine
+                let expected = self
+                    .matcher()
+                    .expected_payment(&self.match_source(), &payment)
+                    .await?;
+                let (request_id, match_scheme) = match expected {
+                    Some(expected) => (expected.request_id, PaymentMatchScheme::ExpectedPayment),
+                    None => {
+                        let (public_id, _, match_scheme) = self.matcher().payment_target(&payment);
+                        (self.stored_request_id(public_id).await?, match_scheme)
+                    }
+                };
+                self.refund_payment(&payment, request_id, submission_time)
+                    .await?;
+                return Ok(PreparedPayment::Settled(PaymentDecision::Unmatched {
+                    request_id,
+                    match_scheme,
+                }));
+            }
+        };
+        let id = fe_request.id;
+
+        // The transactions sent by an attempt, which has not lived to see them committed,
+        // are awaited instead, a new batch is only sent for the tokens they have not withdrawn
+        let mut committed = Vec::new();
+        if fe_request.fulfilled_by.is_some() {
+            match self.await_sent_batch(&fe_request, match_scheme).await? {
+                SentBatch::Settled(decision) => return Ok(PreparedPayment::Settled(decision)),
+                SentBatch::Unsent { committed: sent } => committed = sent,
+            }
+            fe_request = self
+                .core_interaction_wrapper
+                .get_request_by_id(id)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("ForcedExit request {} does not exist", id))?;
+        }
+
+        // Right before sending the transactions we must check if the request is possible at all
+        let preflight = self
+            .preflight(&unsent_part(&fe_request, committed.len()), submission_time)
+            .await?;
+        match preflight.blocker {
+            None => {}
+            // The request is fulfilled on L1 by the operators
+            Some(ForcedExitBlocker::Escalated) => {
+                return Ok(PreparedPayment::Settled(PaymentDecision::Escalated {
+                    request_id: id,
+                }))
+            }
+            // If not possible at all, return without sending any transactions
+            Some(ForcedExitBlocker::NotPossible) => {
+                return Ok(PreparedPayment::Settled(PaymentDecision::NotPossible {
+                    request_id: id,
+                }))
+            }
+            // The target has set the signing key since the request was created
+            Some(ForcedExitBlocker::TargetBecameActive) => {
+                let policy = self
+                    .handle_active_target(
+                        &fe_request,
+                        &preflight,
+                        match_scheme,
+                        payment_amount,
+                        payment_tx_hash,
+                        submission_time,
+                    )
+                    .await?;
+                return Ok(PreparedPayment::Settled(
+                    PaymentDecision::TargetBecameActive {
+                        request_id: id,
+                        policy,
+                    },
+                ));
+            }
+            // The matching has already checked that the request is payable
+            Some(ForcedExitBlocker::Fulfilled) | Some(ForcedExitBlocker::Cancelled) => {
+                return Ok(PreparedPayment::Settled(PaymentDecision::Unmatched {
+                    request_id: id,
+                    match_scheme,
+                }))
+            }
+            // The request has expired since it was matched, it is not processed anymore
+            Some(ForcedExitBlocker::Expired) => {
+                self.core_interaction_wrapper
+                    .cancel_request(id, ForcedExitCancellationKind::Expired)
+                    .await?;
+                self.refund_payment(&payment, id, submission_time).await?;
+                return Ok(PreparedPayment::Settled(PaymentDecision::Unmatched {
+                    request_id: id,
+                    match_scheme,
+                }));
+            }
+        }
+
+        if !committed.is_empty() {
+            return self
+                .resume(fe_request, &preflight, match_scheme, committed)
+                .await;
+        }
+        self.claim(
+            fe_request,
+            &preflight,
+            match_scheme,
+            &payment_amount,
+            payment_tx_hash,
+        )
+        .await
+    }
+
+    /// Tells the operators why the request has not been settled, the failure to do so
+    /// does not fail the shutdown.
+    async fn note_shutdown(&self, request_id: ForcedExitRequestId, text: String) {
+        let note = SaveForcedExitRequestNoteQuery {
+            request_id,
+            author: String::from("forced_exit_sender"),
+            text,
+            tags: vec![String::from("shutdown")],
+            created_at: Utc::now(),
+        };
+        if let Err(err) = self.core_interaction_wrapper.store_note(note).await {
+            vlog::warn!(
+                "Failed to note the shutdown for the ForcedExit request {}: {}",
+                request_id,
+                err
+            );
+        }
+    }
+
+    async fn fulfill(
+        &mut self,
+        fe_request: ForcedExitRequest,
+        preflight: &ForcedExitPreflight,
+        match_scheme: PaymentMatchScheme,
+        paid_amount: &BigUint,
+        payment_tx_hash: Option<H256>,
+        committed: Vec<TxHash>,
+    ) -> anyhow::Result<PaymentDecision> {
+        let prepared = if committed.is_empty() {
+            self.claim(
+                fe_request,
+                preflight,
+                match_scheme,
+                paid_amount,
+                payment_tx_hash,
+            )
+            .await?
+        } else {
+            self.resume(fe_request, preflight, match_scheme, committed)
+                .await?
+        };
+        match prepared {
+            PreparedPayment::Claimed(claimed) => self.send_claimed(*claimed).await,
+            PreparedPayment::Settled(decision) => Ok(decision),
+        }
+    }
+
+    /// Records the payment of the request and claims its fulfillment, so the transactions
+    /// are only sent once for the payment. The request is settled right away if the claim
+    /// has been taken already or there is nothing left to withdraw.
+    async fn claim(
+        &self,
+        mut fe_request: ForcedExitRequest,
+        preflight: &ForcedExitPreflight,
+        match_scheme: PaymentMatchScheme,
+        paid_amount: &BigUint,
+        payment_tx_hash: Option<H256>,
+    ) -> anyhow::Result<PreparedPayment> {
+        let id = fe_request.id;
+
+        self.core_interaction_wrapper
+            .set_match_scheme(id, match_scheme, paid_amount, payment_tx_hash)
+            .await?;
+        if !preflight.skipped.is_empty() {
+            self.skip_tokens(&mut fe_request, &preflight.skipped)
+                .await?;
+        }
+        // The same payment may be delivered again, e.g. by another instance or after
+        // a restart, the transactions are only sent for the first delivery
+        if !self
+            .core_interaction_wrapper
+            .begin_fulfillment(id, payment_tx_hash)
+            .await?
+        {
+            vlog::warn!(
+                "The fulfillment of the ForcedExit request {} for the payment {:?} \
+                 has already been started, the transactions are not sent again",
+                id,
+                payment_tx_hash
+            );
+            metrics::increment_counter!("forced_exit_requests.prevented_duplicates");
+            return Ok(PreparedPayment::Settled(PaymentDecision::Fulfilled {
+                request_id: id,
+                match_scheme,
+                tokens: fe_request.tokens,
+            }));
+        }
+        // There is nothing left to withdraw
+        if preflight.transactions.is_empty() {
+            vlog::warn!(
+                "{}, it is fulfilled without transactions",
+                NothingToExit { request_id: id }
+            );
+            metrics::increment_counter!("forced_exit_requests.nothing_to_exit");
+            self.set_fulfilled(id).await?;
+            return Ok(PreparedPayment::Settled(PaymentDecision::NothingToExit {
+                request_id: id,
+                match_scheme,
+            }));
+        }
+
+        Ok(PreparedPayment::Claimed(Box::new(ClaimedRequest {
+            fe_request,
+            preflight: preflight.clone(),
+            match_scheme,
+            committed: Vec::new(),
+        })))
+    }
+
+    /// Resumes sending the request, the transactions of which have been `committed` in part.
+    /// Its fulfillment has been claimed when the first of its batches was sent, the preflight
+    /// plans the transactions for the rest of its tokens.
+    async fn resume(
+        &self,
+        mut fe_request: ForcedExitRequest,
+        preflight: &ForcedExitPreflight,
+        match_scheme: PaymentMatchScheme,
+        committed: Vec<TxHash>,
+    ) -> anyhow::Result<PreparedPayment> {
+        let id = fe_request.id;
+        vlog::info!(
+            "{} ForcedExit transactions of the request {} have been committed, \
+             the rest of them are sent",
+            committed.len(),
+            id
+        );
+        if !preflight.skipped.is_empty() {
+            self.skip_tokens(&mut fe_request, &preflight.skipped)
+                .await?;
+        }
+        // The rest of the tokens have nothing left to withdraw
+        if preflight.transactions.is_empty() {
+            self.set_fulfilled(id).await?;
+            return Ok(PreparedPayment::Settled(PaymentDecision::Fulfilled {
+                request_id: id,
+                match_scheme,
+                tokens: fe_request.tokens,
+            }));
+        }
+
+        Ok(PreparedPayment::Claimed(Box::new(ClaimedRequest {
+            fe_request,
+            preflight: preflight.clone(),
+            match_scheme,
+            committed,
+        })))
+    }
+
+    /// Leaves the tokens, which can not be withdrawn, out of the request, so the tokens
+    /// of the request stay aligned with the transactions sent for it.
+    async fn skip_tokens(
+        &self,
+        fe_request: &mut ForcedExitRequest,
+        skipped: &[SkippedForcedExit],
+    ) -> anyhow::Result<()> {
+        for skipped in skipped {
+            vlog::warn!(
+                "The token {} of the ForcedExit request {} is skipped: {}",
+                skipped.token,
+                fe_request.id,
+                skipped.reason
+            );
+            metrics::increment_counter!(
+                "forced_exit_requests.skipped_tokens",
+                "reason" => skipped.reason.as_str()
+            );
+        }
+        self.core_interaction_wrapper
+            .skip_tokens(fe_request.id, skipped)
+            .await?;
+        fe_request
+            .tokens
+            .retain(|token| skipped.iter().all(|skipped| skipped.token != *token));
+        Ok(())
+    }
+
+    /// Sends the transactions of the paid request once again, as the operators have asked to,
+    /// and returns their hashes. The request is checked the way its payment was, as of the
+    /// time of the payment for the forced retries, so these are sent for the expired requests.
+    pub async fn retry_request(
+        &mut self,
+        retry: &ForcedExitRetry,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<TxHash>> {
+        let id = retry.request_id;
+        let request = self
+            .core_interaction_wrapper
+            .get_request_by_id(id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("ForcedExit request {} not found", id))?;
+        let (paid_amount, matched_at) = match (&request.paid_amount, request.matched_at) {
+            (Some(paid_amount), Some(matched_at)) => (BigUint::from_str(paid_amount)?, matched_at),
+            _ => anyhow::bail!("ForcedExit request {} has not been paid for", id),
+        };
+        let submission_time = if retry.force { matched_at } else { now };
+        if !self.matcher().check_request_with_explicit_id(
+            &paid_amount,
+            submission_time,
+            &request,
+            &mut (),
+        ) {
+            anyhow::bail!(
+                "ForcedExit request {} can not be paid for as of {}",
+                id,
+                submission_time
+            );
+        }
+
+        // The transactions committed by the batches sent before are kept
+        let committed = match &request.fulfilled_by {
+            Some(hashes) => {
+                let receipts = self.core_interaction_wrapper.get_receipts(hashes).await?;
+                committed_prefix(hashes, &receipts)
+            }
+            None => Vec::new(),
+        };
+        let preflight = self
+            .preflight(&unsent_part(&request, committed.len()), submission_time)
+            .await?;
+        if let Some(blocker) = preflight.blocker {
+            anyhow::bail!(
+                "ForcedExit request {} can not be fulfilled: {:?}",
+                id,
+                blocker
+            );
+        }
+        vlog::info!(
+            "ForcedExit request {} is retried by the operators (force: {})",
+            id,
+            retry.force
+        );
+        let match_scheme = request
+            .match_scheme
+            .unwrap_or(PaymentMatchScheme::ExplicitId);
+        self.fulfill(
+            request,
+            &preflight,
+            match_scheme,
+            &paid_amount,
+            None,
+            committed,
+        )
+        .await?;
+
+        let tx_hashes = self
+            .core_interaction_wrapper
+            .get_request_by_id(id)
+            .await?
+            .and_then(|request| request.fulfilled_by)
+            .unwrap_or_default();
+        Ok(tx_hashes)
+    }
+
+    /// Marks the request as fulfilled and records the version of the pipeline it was fulfilled with.
+    async fn set_fulfilled(&self, id: ForcedExitRequestId) -> anyhow::Result<()> {
+        self.core_interaction_wrapper.set_fulfilled_at(id).await?;
+
+        if self
+            .core_interaction_wrapper
+            .capabilities()
+            .pipeline_versions
+        {
+            let version = ForcedExitPipelineVersion {
+                request_id: id,
+                stage: ForcedExitPipelineStage::Fulfilled,
+                version: FORCED_EXIT_PIPELINE_VERSION,
+                config_hash: self.pipeline_config_hash.clone(),
+                recorded_at: Utc::now(),
+            };
+            // The request is fulfilled already, the missing version must not get it processed again
+            if let Err(err) = self
+                .core_interaction_wrapper
+                .record_pipeline_version(version)
+                .await
+            {
+                vlog::warn!(
+                    "Failed to record the pipeline version of the ForcedExit request {}: {}",
+                    id,
+                    err
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        ops::{Add, Mul},
+        time::Instant,
+    };
+
+    use zksync_api::api_server::forced_exit_preflight::{blocker_before_target, skip_tokens};
+    use zksync_types::{forced_exit_requests::ForcedExitTokenSkipReason, ZkSyncTx};
+
+    use crate::test::{add_request, MockCoreInteractionWrapper};
+
+    use super::test_utils::{
+        failed_receipt, get_stored_request, get_test_forced_exit_sender, get_test_request, payment,
+        sent_txs_count, TEST_ACCOUNT_FORCED_EXIT_SENDER_ID,
+    };
+    use super::*;
+
+    #[tokio::test]
+    async fn test_forced_exit_sender() {
+        let day = chrono::Duration::days(1);
+
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            // There must be 10 digits in id
+            digits_in_id: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+
+        // Not the right amount, because not enough zeroes
+        forced_exit_sender
+            .process_request(payment("1000000012", None), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+
+        // Not the right amount, because id is not correct
+        forced_exit_sender
+            .process_request(payment("10000000001", None), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+
+        // The tranasction is correct, buuut it is expired
+        forced_exit_sender
+            .process_request(payment("10000000001", None), Utc::now().add(day.mul(3)))
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+
+        // The transaction is correct
+        let eth_tx_hash = H256::repeat_byte(0x12);
+        forced_exit_sender
+            .process_request(
+                FundsReceivedEvent {
+                    eth_tx_hash: Some(eth_tx_hash),
+                    ..payment("10000000012", None)
+                },
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+        assert_eq!(
+            get_stored_request(&forced_exit_sender, 12).match_scheme,
+            Some(PaymentMatchScheme::AmountDigits)
+        );
+        // The request can be looked up by the payment
+        assert_eq!(
+            *forced_exit_sender
+                .core_interaction_wrapper
+                .payment_matches
+                .lock()
+                .unwrap(),
+            vec![(eth_tx_hash, 12)]
+        );
+    }
+
+    #[tokio::test]
+    async fn fulfilled_request_is_stamped_with_pipeline_version() {
+        let config = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let config_hash = config.pipeline_config().hash();
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(config));
+
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        forced_exit_sender
+            .process_request(payment("10000000012", None), Utc::now())
+            .await
+            .unwrap();
+
+        let versions = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_pipeline_versions()
+            .clone();
+        assert_eq!(versions.len(), 1);
+        assert_eq!(versions[0].request_id, 12);
+        assert_eq!(versions[0].stage, ForcedExitPipelineStage::Fulfilled);
+        assert_eq!(versions[0].version, FORCED_EXIT_PIPELINE_VERSION);
+        assert_eq!(versions[0].config_hash, config_hash);
+        // The transitions made by the pipeline are notified along with its version
+        assert!(forced_exit_sender
+            .core_interaction_wrapper
+            .lock_deliveries()
+            .iter()
+            .all(|delivery| delivery.pipeline_version == Some(FORCED_EXIT_PIPELINE_VERSION)));
+    }
+
+    #[tokio::test]
+    async fn test_forced_exit_sender_explicit_id() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+
+        // The amount is less than the price of the request
+        forced_exit_sender
+            .process_request(payment("9999999999", Some(12)), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+
+        // The request does not exist
+        forced_exit_sender
+            .process_request(payment("10000000000", Some(13)), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+
+        // The amount does not contain the id, but it is not needed here.
+        // Paying more than the price is fine as well
+        forced_exit_sender
+            .process_request(payment("10000000005", Some(12)), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+        assert_eq!(
+            get_stored_request(&forced_exit_sender, 12).match_scheme,
+            Some(PaymentMatchScheme::ExplicitId)
+        );
+    }
+
+    #[tokio::test]
+    async fn replayed_payment_is_fulfilled_once() {
+        let mut forced_exit_sender = get_test_forced_exit_sender(None);
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        let paid = FundsReceivedEvent {
+            eth_tx_hash: Some(H256::repeat_byte(0x12)),
+            ..payment("10000000000", Some(12))
+        };
+
+        let decision = forced_exit_sender
+            .try_process_request(paid.clone(), Utc::now())
+            .await
+            .unwrap();
+        assert!(matches!(decision, PaymentDecision::Fulfilled { .. }));
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+
+        // As if another instance has not seen the request fulfilled yet
+        forced_exit_sender
+            .core_interaction_wrapper
+            .requests
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .for_each(|request| request.fulfilled_at = None);
+        let decision = forced_exit_sender
+            .try_process_request(paid.clone(), Utc::now())
+            .await
+            .unwrap();
+        assert!(matches!(decision, PaymentDecision::Fulfilled { .. }));
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+
+        // Another payment for the same request is not a replay
+        let decision = forced_exit_sender
+            .try_process_request(
+                FundsReceivedEvent {
+                    eth_tx_hash: Some(H256::repeat_byte(0x13)),
+                    ..paid
+                },
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+        assert!(matches!(decision, PaymentDecision::Fulfilled { .. }));
+        assert_eq!(sent_txs_count(&forced_exit_sender), 2);
+    }
+
+    #[tokio::test]
+    async fn cancelled_requests_are_retried_by_kind() {
+        let mut forced_exit_sender = get_test_forced_exit_sender(None);
+        for id in [12, 13] {
+            add_request(
+                &forced_exit_sender.core_interaction_wrapper.requests,
+                get_test_request(id, "10000000000"),
+            );
+        }
+        // The payments were submitted before the requests were cancelled
+        let submitted_at = Utc::now();
+
+        forced_exit_sender
+            .core_interaction_wrapper
+            .cancel_request(12, ForcedExitCancellationKind::UserCancelled)
+            .await
+            .unwrap();
+        let decision = forced_exit_sender
+            .try_process_request(payment("10000000000", Some(12)), submitted_at)
+            .await
+            .unwrap();
+        assert!(matches!(decision, PaymentDecision::Unmatched { .. }));
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+        // Nor is it picked up while resuming the held requests
+        let request = get_stored_request(&forced_exit_sender, 12);
+        assert_eq!(
+            blocker_before_target(&request, false, submitted_at),
+            Some(ForcedExitBlocker::Cancelled)
+        );
+
+        // The request the transactions of which were reset by the system is sent again
+        forced_exit_sender
+            .core_interaction_wrapper
+            .cancel_request(13, ForcedExitCancellationKind::SystemRetry)
+            .await
+            .unwrap();
+        let decision = forced_exit_sender
+            .try_process_request(payment("10000000000", Some(13)), submitted_at)
+            .await
+            .unwrap();
+        assert!(matches!(decision, PaymentDecision::Fulfilled { .. }));
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+
+        let kinds: Vec<_> = forced_exit_sender
+            .core_interaction_wrapper
+            .cancellations
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|cancellation| (cancellation.request_id, cancellation.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (12, ForcedExitCancellationKind::UserCancelled),
+                (13, ForcedExitCancellationKind::SystemRetry)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn skipped_tokens_are_left_out_of_the_request() {
+        let mut forced_exit_sender = get_test_forced_exit_sender(None);
+        let request = ForcedExitRequest {
+            tokens: vec![TokenId(1), TokenId(2), TokenId(3)],
+            ..get_test_request(12, "10000000000")
+        };
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            request.clone(),
+        );
+        let restricted = |token| SkippedForcedExit {
+            token,
+            reason: ForcedExitTokenSkipReason::L1TransferRestricted,
+        };
+
+        let preflight = forced_exit_sender
+            .preflight(&request, Utc::now())
+            .await
+            .unwrap();
+        let preflight = skip_tokens(preflight, vec![restricted(TokenId(2))]);
+        let paid = request.price_in_wei.clone();
+        let decision = forced_exit_sender
+            .fulfill(
+                request,
+                &preflight,
+                PaymentMatchScheme::ExplicitId,
+                &paid,
+                None,
+                Vec::new(),
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            decision,
+            PaymentDecision::Fulfilled { tokens, .. } if tokens == vec![TokenId(1), TokenId(3)]
+        ));
+        let sent_tokens: Vec<_> = forced_exit_sender
+            .core_interaction_wrapper
+            .sent_txs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|tx| match &tx.tx {
+                ZkSyncTx::ForcedExit(tx) => tx.token,
+                _ => panic!("Only ForcedExit transactions are sent"),
+            })
+            .collect();
+        assert_eq!(sent_tokens, vec![TokenId(1), TokenId(3)]);
+        // The transactions stay aligned with the tokens of the request
+        let stored = get_stored_request(&forced_exit_sender, 12);
+        assert_eq!(stored.tokens, vec![TokenId(1), TokenId(3)]);
+        assert_eq!(stored.fulfilled_by.unwrap().len(), 2);
+        assert_eq!(
+            *forced_exit_sender
+                .core_interaction_wrapper
+                .skipped_tokens
+                .lock()
+                .unwrap(),
+            vec![(12, restricted(TokenId(2)))]
+        );
+
+        // Nothing is sent if all the tokens are skipped
+        let request = get_test_request(13, "10000000000");
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            request.clone(),
+        );
+        let preflight = forced_exit_sender
+            .preflight(&request, Utc::now())
+            .await
+            .unwrap();
+        let preflight = skip_tokens(preflight, vec![restricted(TokenId(1))]);
+        let paid = request.price_in_wei.clone();
+        let decision = forced_exit_sender
+            .fulfill(
+                request,
+                &preflight,
+                PaymentMatchScheme::ExplicitId,
+                &paid,
+                None,
+                Vec::new(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            decision,
+            PaymentDecision::NothingToExit {
+                request_id: 13,
+                match_scheme: PaymentMatchScheme::ExplicitId,
+            }
+        );
+        assert_eq!(sent_txs_count(&forced_exit_sender), 2);
+        assert!(get_stored_request(&forced_exit_sender, 13)
+            .fulfilled_at
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn requests_are_sent_from_the_least_loaded_account() {
+        let config = ForcedExitRequestsConfig::from_env();
+        let additional = SenderAccount::new(
+            Address::repeat_byte(0x5e),
+            AccountId(TEST_ACCOUNT_FORCED_EXIT_SENDER_ID + 1),
+            &config.sender_private_key,
+        );
+        let sender_accounts = Arc::new(SenderAccounts::new(
+            SenderAccount::main(&config, AccountId(TEST_ACCOUNT_FORCED_EXIT_SENDER_ID)),
+            vec![additional],
+        ));
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(config))
+            .with_sender_accounts(Arc::clone(&sender_accounts));
+        // The batch of another request is still in flight from the main account
+        let mut in_flight = sender_accounts.acquire();
+        in_flight.sent(Nonce(0), 2);
+
+        let request = ForcedExitRequest {
+            tokens: vec![TokenId(1), TokenId(2)],
+            ..get_test_request(12, "10000000000")
+        };
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            request.clone(),
+        );
+        let preflight = forced_exit_sender
+            .preflight(&request, Utc::now())
+            .await
+            .unwrap();
+        let paid = request.price_in_wei.clone();
+        forced_exit_sender
+            .fulfill(
+                request,
+                &preflight,
+                PaymentMatchScheme::ExplicitId,
+                &paid,
+                None,
+                Vec::new(),
+            )
+            .await
+            .unwrap();
+
+        // All the transactions of the request are sent from the same account
+        let initiators: Vec<_> = forced_exit_sender
+            .core_interaction_wrapper
+            .sent_txs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|tx| match &tx.tx {
+                ZkSyncTx::ForcedExit(tx) => tx.initiator_account_id,
+                _ => panic!("Only ForcedExit transactions are sent"),
+            })
+            .collect();
+        assert_eq!(
+            initiators,
+            vec![AccountId(TEST_ACCOUNT_FORCED_EXIT_SENDER_ID + 1); 2]
+        );
+        // The awaited batch is no longer in flight
+        assert_eq!(
+            sender_accounts.in_flight_txs(),
+            vec![
+                (forced_exit_sender.config.sender_account_address, 2),
+                (Address::repeat_byte(0x5e), 0),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn planned_nonces_taken_meanwhile_are_not_reused() {
+        let mut forced_exit_sender = get_test_forced_exit_sender(None);
+        let first = get_test_request(12, "10000000000");
+        let second = get_test_request(13, "10000000000");
+        for request in [&first, &second] {
+            add_request(
+                &forced_exit_sender.core_interaction_wrapper.requests,
+                request.clone(),
+            );
+        }
+
+        // Both requests are planned before either is sent, as the concurrent workers do
+        let first_preflight = forced_exit_sender
+            .preflight(&first, Utc::now())
+            .await
+            .unwrap();
+        let second_preflight = forced_exit_sender
+            .preflight(&second, Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(
+            first_preflight.transactions[0].nonce,
+            second_preflight.transactions[0].nonce
+        );
+
+        for (request, preflight) in [(first, first_preflight), (second, second_preflight)] {
+            let paid = request.price_in_wei.clone();
+            forced_exit_sender
+                .fulfill(
+                    request,
+                    &preflight,
+                    PaymentMatchScheme::ExplicitId,
+                    &paid,
+                    None,
+                    Vec::new(),
+                )
+                .await
+                .unwrap();
+        }
+        let nonces: Vec<_> = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_sent_txs()
+            .iter()
+            .map(|tx| tx.nonce())
+            .collect();
+        assert_eq!(nonces, vec![Nonce(0), Nonce(1)]);
+    }
+
+    #[tokio::test]
+    async fn empty_request_is_never_sent() {
+        let mut forced_exit_sender = get_test_forced_exit_sender(None);
+        // The requests without tokens are refused by the API, but may have been created before
+        let request = ForcedExitRequest {
+            tokens: Vec::new(),
+            ..get_test_request(12, "10000000000")
+        };
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            request.clone(),
+        );
+
+        // There is nothing to build the transactions for
+        let preflight = forced_exit_sender
+            .preflight(&request, Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(preflight.blocker, None);
+        let err = forced_exit_sender
+            .build_transactions(
+                forced_exit_sender.sender_accounts.main(),
+                &request,
+                &preflight,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<NothingToExit>(),
+            Some(NothingToExit { request_id: 12 })
+        ));
+        // The empty batch is refused without marking the request as sent
+        assert!(forced_exit_sender
+            .core_interaction_wrapper
+            .send_and_save_txs_batch(&request, Vec::new())
+            .await
+            .is_err());
+        assert_eq!(
+            get_stored_request(&forced_exit_sender, 12).fulfilled_by,
+            None
+        );
+
+        // The paid request is fulfilled without any transactions
+        let decision = forced_exit_sender
+            .try_process_request(payment("10000000000", Some(12)), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(
+            decision,
+            PaymentDecision::NothingToExit {
+                request_id: 12,
+                match_scheme: PaymentMatchScheme::ExplicitId,
+            }
+        );
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+        assert!(get_stored_request(&forced_exit_sender, 12)
+            .fulfilled_at
+            .is_some());
+
+        // The same request is not processed again
+        let decision = forced_exit_sender
+            .try_process_request(payment("10000000000", Some(12)), Utc::now())
+            .await
+            .unwrap();
+        assert!(matches!(decision, PaymentDecision::Unmatched { .. }));
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+    }
+
+    #[tokio::test]
+    async fn payment_failing_every_time_is_given_up_on() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            l1_escalation_enabled: false,
+            processing_attempts: 4,
+            processing_retry_base_delay: 10,
+            processing_retry_max_delay: 25,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        forced_exit_sender.core_interaction_wrapper.tx_receipt = Some(failed_receipt());
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+
+        let started_at = Instant::now();
+        let err = forced_exit_sender
+            .process_payment(payment("10000000012", None), Utc::now())
+            .await
+            .unwrap_err();
+        // The attempts are 10ms, 20ms and then 25ms apart, the delay is capped
+        assert!(started_at.elapsed() >= Duration::from_millis(55));
+        assert_eq!(sent_txs_count(&forced_exit_sender), 4);
+        assert!(err.downcast_ref::<CommitError>().is_some());
+
+        // The operators can see why the request has not been fulfilled
+        let failures = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_processing_failures()
+            .clone();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].request_id, 12);
+        assert_eq!(failures[0].attempts, 4);
+        assert!(failures[0].error.contains("has failed"));
+        assert!(get_stored_request(&forced_exit_sender, 12)
+            .fulfilled_at
+            .is_none());
+
+        // The failed payment is not retried by itself
+        assert!(forced_exit_sender.deferred.is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejected_batch_is_not_retried() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            processing_attempts: 3,
+            processing_retry_base_delay: 10,
+            processing_retry_max_delay: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        let last_failure = |sender: &MempoolForcedExitSender<MockCoreInteractionWrapper>| {
+            sender
+                .core_interaction_wrapper
+                .lock_processing_failures()
+                .last()
+                .cloned()
+                .unwrap()
+        };
+
+        // The server may be back on the next attempt
+        let unavailable = SubmissionError::http(503, Some(607), "Core server is unavailable");
+        *forced_exit_sender
+            .core_interaction_wrapper
+            .submission_error
+            .lock()
+            .unwrap() = Some(unavailable.clone());
+        let err = forced_exit_sender
+            .process_payment(payment("10000000012", None), Utc::now())
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast_ref::<SubmissionError>(), Some(&unavailable));
+        let failure = last_failure(&forced_exit_sender);
+        assert_eq!(failure.attempts, 3);
+        assert_eq!(failure.submission_error, Some(unavailable));
+
+        // The same transactions would be rejected again
+        let rejected = SubmissionError::rejected(Some(605), "Transaction fee is too low", false);
+        *forced_exit_sender
+            .core_interaction_wrapper
+            .submission_error
+            .lock()
+            .unwrap() = Some(rejected.clone());
+        let err = forced_exit_sender
+            .process_payment(payment("10000000012", None), Utc::now())
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast_ref::<SubmissionError>(), Some(&rejected));
+        let failure = last_failure(&forced_exit_sender);
+        assert_eq!(failure.attempts, 1);
+        assert_eq!(failure.submission_error, Some(rejected));
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+    }
+
+    // Checks that the failed request is sent again once retried, the expired one
+    // only if the retry is forced, and that the fulfilled one is never sent again
+    #[tokio::test]
+    async fn failed_requests_are_retried_by_operators() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            processing_attempts: 1,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        let now = Utc::now();
+        let retry = |request_id: ForcedExitRequestId, force: bool| ForcedExitRetry {
+            id: request_id,
+            request_id,
+            force,
+            created_at: now,
+            processed_at: None,
+            tx_hashes: vec![],
+            error: None,
+        };
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            ForcedExitRequest {
+                valid_until: now - chrono::Duration::days(1),
+                match_scheme: Some(PaymentMatchScheme::ExplicitId),
+                matched_at: Some(now - chrono::Duration::days(2)),
+                paid_amount: Some("10000000000".to_owned()),
+                ..get_test_request(13, "10000000000")
+            },
+        );
+
+        // The batch is rejected, so the request is left paid but not fulfilled
+        *forced_exit_sender
+            .core_interaction_wrapper
+            .submission_error
+            .lock()
+            .unwrap() = Some(SubmissionError::rejected(None, "Nonce mismatch", false));
+        forced_exit_sender
+            .process_payment(payment("10000000012", None), now)
+            .await
+            .unwrap_err();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+        *forced_exit_sender
+            .core_interaction_wrapper
+            .submission_error
+            .lock()
+            .unwrap() = None;
+
+        forced_exit_sender
+            .core_interaction_wrapper
+            .set_fulfilled_by(12, None)
+            .await
+            .unwrap();
+        let tx_hashes = forced_exit_sender
+            .retry_request(&retry(12, false), now)
+            .await
+            .unwrap();
+        assert_eq!(tx_hashes.len(), 1);
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+        let request = get_stored_request(&forced_exit_sender, 12);
+        assert!(request.fulfilled_at.is_some());
+        assert_eq!(request.fulfilled_by, Some(tx_hashes));
+        forced_exit_sender
+            .retry_request(&retry(12, true), now)
+            .await
+            .unwrap_err();
+
+        // The expired request was payable at the time of the payment
+        forced_exit_sender
+            .retry_request(&retry(13, false), now)
+            .await
+            .unwrap_err();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+        forced_exit_sender
+            .retry_request(&retry(13, true), now)
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 2);
+        assert!(get_stored_request(&forced_exit_sender, 13)
+            .fulfilled_at
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn batches_sent_back_to_back_do_not_share_nonces() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            tx_commit_timeout: 50,
+            receipt_poll_interval: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        forced_exit_sender.core_interaction_wrapper.nonce = Nonce(3);
+        for id in [12, 13] {
+            add_request(
+                &forced_exit_sender.core_interaction_wrapper.requests,
+                ForcedExitRequest {
+                    tokens: vec![TokenId(1), TokenId(2)],
+                    ..get_test_request(id, "10000000000")
+                },
+            );
+        }
+
+        // Neither batch is committed, so the committed nonce stays the same
+        forced_exit_sender.core_interaction_wrapper.tx_receipt = None;
+        for amount in ["10000000012", "10000000013"] {
+            let decision = forced_exit_sender
+                .process_payment(payment(amount, None), Utc::now())
+                .await
+                .unwrap();
+            assert!(matches!(decision, PaymentDecision::InFlight { .. }));
+        }
+
+        let nonces: Vec<_> = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_sent_txs()
+            .iter()
+            .map(|tx| tx.nonce())
+            .collect();
+        assert_eq!(nonces, vec![Nonce(3), Nonce(4), Nonce(5), Nonce(6)]);
+    }
+
+    #[tokio::test]
+    async fn commit_timeout_leaves_request_in_flight() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            tx_commit_timeout: 50,
+            receipt_poll_interval: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+
+        // The transaction is not committed in time, yet it may still be
+        let succeeded_receipt = forced_exit_sender
+            .core_interaction_wrapper
+            .tx_receipt
+            .take();
+        let decision = forced_exit_sender
+            .process_payment(payment("10000000012", None), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(
+            decision,
+            PaymentDecision::InFlight {
+                request_id: 12,
+                match_scheme: PaymentMatchScheme::AmountDigits,
+            }
+        );
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+        let in_flight = get_stored_request(&forced_exit_sender, 12);
+        assert!(in_flight.fulfilled_by.is_some());
+        assert_eq!(in_flight.fulfilled_at, None);
+        assert_eq!(in_flight.cancellation, None);
+        assert!(forced_exit_sender
+            .core_interaction_wrapper
+            .failures
+            .lock()
+            .unwrap()
+            .is_empty());
+        assert!(forced_exit_sender.has_left_in_flight());
+
+        // The reconciliation settles the request once the receipt appears
+        forced_exit_sender.core_interaction_wrapper.tx_receipt = succeeded_receipt;
+        let in_flight = forced_exit_sender
+            .reconcile_unconfirmed(Duration::from_millis(0))
+            .await
+            .unwrap();
+        assert_eq!(in_flight, 0);
+        assert!(get_stored_request(&forced_exit_sender, 12)
+            .fulfilled_at
+            .is_some());
+        assert!(!forced_exit_sender.has_left_in_flight());
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+    }
+
+    #[tokio::test]
+    async fn sent_batch_is_awaited_instead_of_sent_again() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            tx_commit_timeout: 50,
+            receipt_poll_interval: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        for id in [12, 13] {
+            add_request(
+                &forced_exit_sender.core_interaction_wrapper.requests,
+                get_test_request(id, "10000000000"),
+            );
+        }
+        let submission_time = Utc::now();
+
+        // The attempts do not live to see their batches committed
+        let succeeded_receipt = forced_exit_sender
+            .core_interaction_wrapper
+            .tx_receipt
+            .take();
+        for amount in ["10000000012", "10000000013"] {
+            let decision = forced_exit_sender
+                .process_payment(payment(amount, None), submission_time)
+                .await
+                .unwrap();
+            assert!(matches!(decision, PaymentDecision::InFlight { .. }));
+        }
+        assert_eq!(sent_txs_count(&forced_exit_sender), 2);
+
+        // The same payment observed again awaits the batch sent for it
+        forced_exit_sender.core_interaction_wrapper.tx_receipt = succeeded_receipt;
+        let decision = forced_exit_sender
+            .process_payment(payment("10000000012", None), submission_time)
+            .await
+            .unwrap();
+        assert_eq!(
+            decision,
+            PaymentDecision::Fulfilled {
+                request_id: 12,
+                match_scheme: PaymentMatchScheme::AmountDigits,
+                tokens: vec![TokenId(1)],
+            }
+        );
+        assert_eq!(sent_txs_count(&forced_exit_sender), 2);
+        assert!(get_stored_request(&forced_exit_sender, 12)
+            .fulfilled_at
+            .is_some());
+
+        // A new batch is only sent once the previous one has failed
+        let failed_hashes = get_stored_request(&forced_exit_sender, 13)
+            .fulfilled_by
+            .unwrap();
+        forced_exit_sender
+            .core_interaction_wrapper
+            .lock_receipts()
+            .insert(failed_hashes[0], failed_receipt());
+        let decision = forced_exit_sender
+            .process_payment(payment("10000000013", None), submission_time)
+            .await
+            .unwrap();
+        assert!(matches!(
+            decision,
+            PaymentDecision::Fulfilled { request_id: 13, .. }
+        ));
+        assert_eq!(sent_txs_count(&forced_exit_sender), 3);
+        let fulfilled = get_stored_request(&forced_exit_sender, 13);
+        assert!(fulfilled.fulfilled_at.is_some());
+        assert_ne!(fulfilled.fulfilled_by, Some(failed_hashes));
+    }
+
+    fn shutdown_notes(sender: &MempoolForcedExitSender<MockCoreInteractionWrapper>) -> Vec<String> {
+        sender
+            .core_interaction_wrapper
+            .notes
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|note| note.tags.contains(&String::from("shutdown")))
+            .map(|note| note.text.clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn sent_batch_is_drained_on_shutdown() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            receipt_poll_interval: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let (shutdown_handle, shutdown) = ShutdownSignal::new(Duration::from_secs(10));
+        let mut forced_exit_sender =
+            get_test_forced_exit_sender(Some(forced_exit_requests)).with_shutdown(shutdown);
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(13, "10000000000"),
+        );
+
+        // The shutdown is requested while the batch is awaited, it is still settled
+        *forced_exit_sender
+            .core_interaction_wrapper
+            .shutdown_on_send
+            .lock()
+            .unwrap() = Some(shutdown_handle);
+        let decision = forced_exit_sender
+            .process_payment(payment("10000000012", None), Utc::now())
+            .await
+            .unwrap();
+        assert!(matches!(
+            decision,
+            PaymentDecision::Fulfilled { request_id: 12, .. }
+        ));
+        assert!(get_stored_request(&forced_exit_sender, 12)
+            .fulfilled_at
+            .is_some());
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+
+        // No new batches are sent, the payment is processed once replayed after the restart
+        let err = forced_exit_sender
+            .process_payment(payment("10000000013", None), Utc::now())
+            .await
+            .unwrap_err();
+        assert!(err.is::<ShuttingDown>());
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+        let not_sent = get_stored_request(&forced_exit_sender, 13);
+        assert_eq!(not_sent.fulfilled_by, None);
+        assert!(forced_exit_sender
+            .core_interaction_wrapper
+            .lock_processing_failures()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn retries_are_stopped_on_shutdown() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            processing_attempts: 5,
+            processing_retry_base_delay: 60000,
+            processing_retry_max_delay: 60000,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let (shutdown_handle, shutdown) = ShutdownSignal::new(Duration::from_secs(10));
+        let mut forced_exit_sender =
+            get_test_forced_exit_sender(Some(forced_exit_requests)).with_shutdown(shutdown);
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        *forced_exit_sender
+            .core_interaction_wrapper
+            .submission_error
+            .lock()
+            .unwrap() = Some(SubmissionError::http(
+            503,
+            Some(607),
+            "Core server is unavailable",
+        ));
+
+        // The shutdown is requested while the retry waits
+        let started_at = Instant::now();
+        let request_shutdown = async {
+            time::sleep(Duration::from_millis(20)).await;
+            shutdown_handle.request();
+        };
+        let (result, ()) = tokio::join!(
+            forced_exit_sender.process_payment(payment("10000000012", None), Utc::now()),
+            request_shutdown
+        );
+        assert!(result.unwrap_err().is::<ShuttingDown>());
+        assert!(started_at.elapsed() < Duration::from_secs(10));
+
+        // The failure is not recorded, since the attempts left are made after the restart
+        assert!(forced_exit_sender
+            .core_interaction_wrapper
+            .lock_processing_failures()
+            .is_empty());
+        let notes = shutdown_notes(&forced_exit_sender);
+        assert_eq!(notes.len(), 1);
+        assert!(notes[0].contains("after 1 failed attempts"));
+    }
+
+    #[tokio::test]
+    async fn undrained_batch_is_left_in_flight() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            tx_commit_timeout: 60000,
+            receipt_poll_interval: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        // Nothing is awaited once the shutdown is requested
+        let (shutdown_handle, shutdown) = ShutdownSignal::new(Duration::from_millis(0));
+        let mut forced_exit_sender =
+            get_test_forced_exit_sender(Some(forced_exit_requests)).with_shutdown(shutdown);
+        forced_exit_sender.core_interaction_wrapper.tx_receipt = None;
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        *forced_exit_sender
+            .core_interaction_wrapper
+            .shutdown_on_send
+            .lock()
+            .unwrap() = Some(shutdown_handle);
+
+        let started_at = Instant::now();
+        let decision = forced_exit_sender
+            .process_payment(payment("10000000012", None), Utc::now())
+            .await
+            .unwrap();
+        assert!(started_at.elapsed() < Duration::from_secs(10));
+        assert_eq!(
+            decision,
+            PaymentDecision::InFlight {
+                request_id: 12,
+                match_scheme: PaymentMatchScheme::AmountDigits,
+            }
+        );
+        // The request is settled by the reconciliation after the restart
+        let in_flight = get_stored_request(&forced_exit_sender, 12);
+        assert!(in_flight.fulfilled_by.is_some());
+        assert_eq!(in_flight.fulfilled_at, None);
+        assert_eq!(in_flight.cancellation, None);
+        assert!(forced_exit_sender.has_left_in_flight());
+        let notes = shutdown_notes(&forced_exit_sender);
+        assert_eq!(notes.len(), 1);
+        assert!(notes[0].contains("have not been committed before the shutdown"));
+    }
+
+    #[tokio::test]
+    async fn commit_errors_are_told_apart() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            tx_commit_timeout: 50,
+            receipt_poll_interval: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        let tx_hash = TxHash::from_slice(&[1; 32]).unwrap();
+
+        forced_exit_sender.core_interaction_wrapper.tx_receipt = Some(failed_receipt());
+        let err = forced_exit_sender
+            .wait_until_comitted(tx_hash)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CommitError>(),
+            Some(CommitError::Failed { reason, .. }) if reason == "Pathological account state"
+        ));
+
+        forced_exit_sender.core_interaction_wrapper.tx_receipt = None;
+        let err = forced_exit_sender
+            .wait_until_comitted(tx_hash)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CommitError>(),
+            Some(CommitError::Timeout { timeout, .. }) if *timeout == Duration::from_millis(50)
+        ));
+    }
+}
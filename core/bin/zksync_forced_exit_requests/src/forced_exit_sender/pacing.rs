@@ -0,0 +1,116 @@
+//! Waiting until the batch is allowed to be sent by the `SubmissionPacer`.
+
+use std::time::{Duration, Instant};
+
+use tokio::time;
+
+use crate::{core_interaction_wrapper::CoreInteractionWrapper, pacing::PacingDecision};
+
+use super::MempoolForcedExitSender;
+
+// How long the paced batch waits at most before checking the pacing again, so the raised limits
+// are applied to the waiting batches as well
+const PACING_RECHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+impl<T: CoreInteractionWrapper + Sync> MempoolForcedExitSender<T> {
+    /// Waits until the next batch is allowed by the pacing, see the `pacing` module.
+    /// The nonces are not taken meanwhile, so the batch does not hold up the other transactions.
+    pub(super) async fn pace(&self) {
+        let started_at = Instant::now();
+        let mut paced = false;
+        self.pacer.begin_pacing();
+        loop {
+            match self.pacer.next_batch() {
+                PacingDecision::Send => break,
+                // The batch is not sent once the shutdown is requested
+                PacingDecision::Wait(_) if self.shutdown.is_requested() => break,
+                PacingDecision::Wait(delay) => {
+                    let mut shutdown = self.shutdown.clone();
+                    tokio::select! {
+                        _ = time::sleep(delay.min(PACING_RECHECK_INTERVAL)) => {}
+                        _ = shutdown.requested() => {}
+                    }
+                }
+                PacingDecision::AwaitBlock(tx_hash) => {
+                    // The failed or lost batch does not stop the sending
+                    if let Err(err) = self.wait_until_comitted(tx_hash).await {
+                        vlog::warn!(
+                            "The block with the ForcedExit batch {} was not awaited: {}",
+                            tx_hash,
+                            err
+                        );
+                    }
+                    self.pacer.block_sealed(tx_hash);
+                }
+            }
+            paced = true;
+        }
+        self.pacer.end_pacing();
+        if paced {
+            metrics::histogram!("forced_exit_requests.pacing_delay", started_at.elapsed());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use chrono::Utc;
+
+    use zksync_config::ForcedExitRequestsConfig;
+
+    use crate::{
+        forced_exit_sender::test_utils::{
+            get_stored_request, get_test_forced_exit_sender, get_test_request, payment,
+            sent_txs_count,
+        },
+        pacing::SubmissionPacer,
+        test::add_request,
+    };
+
+    use super::*;
+
+    // Checks that the paid requests above the pacing limits wait for their turn
+    // instead of being failed, and that the block is awaited after the batches per block
+    #[tokio::test]
+    async fn paced_requests_are_fulfilled() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            max_batches_per_minute: 600,
+            batches_per_block: 2,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let pacer = Arc::new(SubmissionPacer::new(forced_exit_requests.pacing()));
+        let mut forced_exit_sender =
+            get_test_forced_exit_sender(Some(forced_exit_requests)).with_pacer(pacer.clone());
+        for id in 12..15 {
+            add_request(
+                &forced_exit_sender.core_interaction_wrapper.requests,
+                get_test_request(id, "10000000000"),
+            );
+        }
+
+        // The whole minute worth of the batches is used up, so each next one waits for 100ms
+        while let PacingDecision::Send = pacer.next_batch() {}
+        let started_at = Instant::now();
+        for id in 12..15 {
+            forced_exit_sender
+                .process_payment(payment(&format!("100000000{}", id), None), Utc::now())
+                .await
+                .unwrap();
+        }
+        assert!(started_at.elapsed() >= Duration::from_millis(250));
+
+        assert_eq!(sent_txs_count(&forced_exit_sender), 3);
+        for id in 12..15 {
+            assert!(get_stored_request(&forced_exit_sender, id)
+                .fulfilled_at
+                .is_some());
+        }
+        let state = pacer.state(Utc::now());
+        assert_eq!(state.paced_batches, 0);
+        // The third batch is sent once the block with the second one is sealed
+        assert_eq!(state.batches_in_block, 1);
+    }
+}
@@ -0,0 +1,501 @@
+//! Settling the requests sent before the restart or left in flight by the sender.
+
+use std::time::{Duration, Instant};
+
+use tokio::time;
+
+use zksync_api::api_server::forced_exit_receipts::{match_receipts, tx_status};
+use zksync_storage::chain::operations_ext::records::TxReceiptResponse;
+use zksync_types::{
+    forced_exit_requests::{ForcedExitCancellationKind, ForcedExitTxStatus},
+    tx::TxHash,
+};
+
+use crate::{core_interaction_wrapper::CoreInteractionWrapper, metrics as sender_metrics};
+
+use super::{committed_prefix, CommitError, MempoolForcedExitSender};
+
+// How often the receipts of the transactions sent before the restart are checked
+const RECONCILIATION_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+impl<T: CoreInteractionWrapper + Sync> MempoolForcedExitSender<T> {
+    /// Settles the requests, the transactions of which were sent before the restart.
+    ///
+    /// The requests with all the transactions executed are fulfilled, the ones with a failed
+    /// transaction are released to be sent again. The receipts are polled until the `timeout`
+    /// passes, then the number of the requests still in flight is returned.
+    pub async fn reconcile_unconfirmed(&mut self, timeout: Duration) -> anyhow::Result<usize> {
+        let started_at = Instant::now();
+        if self
+            .core_interaction_wrapper
+            .capabilities()
+            .submission_records
+        {
+            self.settle_interrupted_submissions().await?;
+        }
+        let unconfirmed = self
+            .core_interaction_wrapper
+            .get_unconfirmed_requests()
+            .await?;
+        sender_metrics::report_unconfirmed(unconfirmed.len());
+        let mut requests: Vec<_> = unconfirmed
+            .into_iter()
+            .filter(|request| request.fulfilled_by.is_some())
+            .collect();
+        // The requests left in flight by the processing are among the loaded ones
+        self.left_in_flight = false;
+        let total = requests.len();
+        if total == 0 {
+            sender_metrics::report_reconciliation(0, 0);
+            return Ok(0);
+        }
+        vlog::info!("Reconciling {} ForcedExit requests sent before", total);
+        let mut cancelled = 0;
+
+        loop {
+            // Every poll is a cycle of its own, the tokens are looked up anew
+            let mut tokens = self.token_cache();
+            let hashes: Vec<TxHash> = requests
+                .iter()
+                .flat_map(|request| request.fulfilled_by.iter().flatten().copied())
+                .collect();
+            let receipts = self.core_interaction_wrapper.get_receipts(&hashes).await?;
+
+            let mut in_flight = Vec::new();
+            for request in requests {
+                let hashes = request.fulfilled_by.clone().unwrap_or_default();
+                let request_statuses: Vec<_> = match_receipts(&hashes, &receipts)
+                    .into_iter()
+                    .map(tx_status)
+                    .collect();
+
+                if request_statuses.contains(&ForcedExitTxStatus::Failed) {
+                    vlog::error!(
+                        "A previously sent forced exit transaction of the request {} has failed. \
+                         Canceling the txs.",
+                        request.id
+                    );
+                    self.handle_failed_batch(&request, &hashes, &mut tokens)
+                        .await?;
+                    self.release_unsent(request.id, &committed_prefix(&hashes, &receipts))
+                        .await?;
+                    cancelled += 1;
+                } else if request_statuses.iter().all(ForcedExitTxStatus::is_executed) {
+                    // The request sent in several batches may have been stopped in between,
+                    // the batches committed are kept and only the rest of them are sent
+                    if hashes.len() < request.tokens.len() {
+                        vlog::warn!(
+                            "Only {} of {} ForcedExit transactions of the request {} have been \
+                             sent, the rest of them are sent by the next attempt",
+                            hashes.len(),
+                            request.tokens.len(),
+                            request.id
+                        );
+                        metrics::increment_counter!("forced_exit_requests.partially_sent_requests");
+                    } else {
+                        self.set_fulfilled(request.id).await?;
+                    }
+                } else {
+                    in_flight.push(request);
+                }
+            }
+            requests = in_flight;
+
+            vlog::info!(
+                "{} of {} ForcedExit requests sent before are settled",
+                total - requests.len(),
+                total
+            );
+            if requests.is_empty() {
+                sender_metrics::report_reconciliation(total, cancelled);
+                return Ok(0);
+            }
+
+            let elapsed = started_at.elapsed();
+            if elapsed >= timeout || self.shutdown.is_drained() {
+                vlog::warn!(
+                    "{} ForcedExit requests are still in flight after {}s, proceeding anyway",
+                    requests.len(),
+                    elapsed.as_secs()
+                );
+                sender_metrics::report_reconciliation(total, cancelled);
+                return Ok(requests.len());
+            }
+            let mut shutdown = self.shutdown.clone();
+            tokio::select! {
+                _ = time::sleep(RECONCILIATION_POLL_INTERVAL.min(timeout - elapsed)) => {}
+                _ = shutdown.drained() => {}
+            }
+        }
+    }
+
+    /// Settles the batches, the submission of which has been interrupted by a crash: the ones
+    /// the mempool has accepted are recorded as sent and are awaited as the rest of them,
+    /// the others are released to be sent again. The reconciliation runs with no batches
+    /// being sent, so every batch not confirmed as submitted by then has been interrupted.
+    pub(super) async fn settle_interrupted_submissions(&self) -> anyhow::Result<()> {
+        let interrupted = self
+            .core_interaction_wrapper
+            .get_interrupted_submissions()
+            .await?;
+        for submission in interrupted {
+            let id = submission.request_id;
+            if submission.reached_mempool {
+                vlog::warn!(
+                    "The submission of the transactions {:?} of ForcedExit request {} has been \
+                     interrupted after the mempool had accepted them, they are recorded as sent",
+                    submission.tx_hashes,
+                    id
+                );
+                self.core_interaction_wrapper
+                    .set_fulfilled_by(id, Some(submission.tx_hashes))
+                    .await?;
+            } else {
+                vlog::warn!(
+                    "The transactions {:?} of ForcedExit request {} have not reached the mempool \
+                     before the submission was interrupted, the request is sent again",
+                    submission.tx_hashes,
+                    id
+                );
+                self.core_interaction_wrapper
+                    .cancel_request(id, ForcedExitCancellationKind::SystemRetry)
+                    .await?;
+            }
+            metrics::increment_counter!(
+                "forced_exit_requests.interrupted_submissions",
+                "outcome" => if submission.reached_mempool { "sent" } else { "released" }
+            );
+        }
+        Ok(())
+    }
+
+    /// Waits for the transaction to be committed, the error is `CommitError`
+    /// unless the receipt could not be queried at all.
+    pub async fn wait_until_comitted(&self, tx_hash: TxHash) -> anyhow::Result<()> {
+        let timeout = self.config.tx_commit_timeout();
+        let started_at = Instant::now();
+        let receipt = async {
+            match &self.receipt_poller {
+                Some(receipt_poller) => receipt_poller.wait_for_receipt(tx_hash, timeout).await,
+                None => self.poll_receipt(tx_hash, timeout).await,
+            }
+        };
+        // The batches sent already are awaited until the drain deadline of the shutdown
+        let mut shutdown = self.shutdown.clone();
+        let receipt = tokio::select! {
+            receipt = receipt => receipt?,
+            _ = shutdown.drained() => {
+                sender_metrics::report_commit_wait(started_at.elapsed(), "interrupted");
+                return Err(CommitError::Interrupted { tx_hash }.into());
+            }
+        };
+
+        let (outcome, result) = match receipt {
+            Some(tx_receipt) if tx_receipt.success => ("committed", Ok(())),
+            Some(tx_receipt) => (
+                "failed",
+                Err(CommitError::Failed {
+                    tx_hash,
+                    reason: tx_receipt.fail_reason.unwrap_or_default(),
+                }
+                .into()),
+            ),
+            None => (
+                "timeout",
+                Err(CommitError::Timeout { tx_hash, timeout }.into()),
+            ),
+        };
+        sender_metrics::report_commit_wait(started_at.elapsed(), outcome);
+        result
+    }
+
+    pub(super) async fn poll_receipt(
+        &self,
+        tx_hash: TxHash,
+        timeout: Duration,
+    ) -> anyhow::Result<Option<TxReceiptResponse>> {
+        let started_at = Instant::now();
+        let mut timer = time::interval(self.config.receipt_poll_interval());
+
+        while started_at.elapsed() < timeout {
+            let receipt = self.core_interaction_wrapper.get_receipt(tx_hash).await?;
+            if receipt.is_some() {
+                return Ok(receipt);
+            }
+            timer.tick().await;
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+
+    use zksync_config::ForcedExitRequestsConfig;
+    use zksync_types::{
+        forced_exit_requests::{ForcedExitRequest, InterruptedSubmission},
+        TokenId,
+    };
+
+    use crate::{
+        forced_exit_sender::{
+            test_utils::{
+                failed_receipt, get_stored_request, get_test_forced_exit_sender, get_test_request,
+                payment, sent_txs_count,
+            },
+            ForcedExitSender,
+        },
+        test::add_request,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_forced_exit_sender_reconciliation() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        let hash = |byte: u8| TxHash::from_slice(&[byte; 32]).unwrap();
+
+        // The batches sent before the restart: committed, failed and still pending ones
+        let committed = ForcedExitRequest {
+            fulfilled_by: Some(vec![hash(1)]),
+            ..get_test_request(12, "10000000000")
+        };
+        let failed = ForcedExitRequest {
+            fulfilled_by: Some(vec![hash(2)]),
+            ..get_test_request(13, "10000000000")
+        };
+        let pending = ForcedExitRequest {
+            fulfilled_by: Some(vec![hash(3)]),
+            ..get_test_request(14, "10000000000")
+        };
+        for request in [committed, failed, pending] {
+            add_request(
+                &forced_exit_sender.core_interaction_wrapper.requests,
+                request,
+            );
+        }
+
+        let succeeded_receipt = forced_exit_sender
+            .core_interaction_wrapper
+            .tx_receipt
+            .clone();
+        {
+            let mut receipts = forced_exit_sender.core_interaction_wrapper.lock_receipts();
+            receipts.insert(hash(1), succeeded_receipt.clone().unwrap());
+            receipts.insert(hash(2), failed_receipt());
+        }
+        // The transactions without a receipt are not processed yet
+        forced_exit_sender.core_interaction_wrapper.tx_receipt = None;
+
+        let in_flight = forced_exit_sender
+            .reconcile_unconfirmed(Duration::from_millis(0))
+            .await
+            .unwrap();
+        assert_eq!(in_flight, 1);
+
+        assert!(get_stored_request(&forced_exit_sender, 12)
+            .fulfilled_at
+            .is_some());
+        let failed = get_stored_request(&forced_exit_sender, 13);
+        assert_eq!(failed.fulfilled_at, None);
+        assert_eq!(failed.fulfilled_by, None);
+        assert_eq!(
+            failed.cancellation,
+            Some(ForcedExitCancellationKind::SystemRetry)
+        );
+        assert_eq!(
+            forced_exit_sender
+                .core_interaction_wrapper
+                .failures
+                .lock()
+                .unwrap()
+                .get(&(13, TokenId(1))),
+            Some(&1)
+        );
+        let pending = get_stored_request(&forced_exit_sender, 14);
+        assert_eq!(pending.fulfilled_at, None);
+        assert_eq!(pending.fulfilled_by, Some(vec![hash(3)]));
+
+        // The new payments are processed once the startup phase is over
+        forced_exit_sender.core_interaction_wrapper.tx_receipt = succeeded_receipt;
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(15, "10000000000"),
+        );
+        forced_exit_sender
+            .process_request(payment("10000000015", None), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+        assert!(get_stored_request(&forced_exit_sender, 15)
+            .fulfilled_at
+            .is_some());
+
+        // The rest is settled as soon as the receipt appears
+        let in_flight = forced_exit_sender
+            .reconcile_unconfirmed(Duration::from_millis(0))
+            .await
+            .unwrap();
+        assert_eq!(in_flight, 0);
+        assert!(get_stored_request(&forced_exit_sender, 14)
+            .fulfilled_at
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn partially_committed_batch_is_not_fulfilled() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        let hash = |byte: u8| TxHash::from_slice(&[byte; 32]).unwrap();
+
+        // The batches of two tokens, only the first transaction of which has a receipt
+        let pending = ForcedExitRequest {
+            tokens: vec![TokenId(1), TokenId(2)],
+            fulfilled_by: Some(vec![hash(1), hash(2)]),
+            ..get_test_request(12, "20000000000")
+        };
+        let failed = ForcedExitRequest {
+            tokens: vec![TokenId(1), TokenId(2)],
+            fulfilled_by: Some(vec![hash(3), hash(4)]),
+            ..get_test_request(13, "20000000000")
+        };
+        for request in [pending, failed] {
+            add_request(
+                &forced_exit_sender.core_interaction_wrapper.requests,
+                request,
+            );
+        }
+
+        let succeeded_receipt = forced_exit_sender
+            .core_interaction_wrapper
+            .tx_receipt
+            .clone()
+            .unwrap();
+        {
+            let mut receipts = forced_exit_sender.core_interaction_wrapper.lock_receipts();
+            receipts.insert(hash(1), succeeded_receipt.clone());
+            receipts.insert(hash(3), succeeded_receipt.clone());
+            receipts.insert(hash(4), failed_receipt());
+        }
+        forced_exit_sender.core_interaction_wrapper.tx_receipt = None;
+
+        let in_flight = forced_exit_sender
+            .reconcile_unconfirmed(Duration::from_millis(0))
+            .await
+            .unwrap();
+        assert_eq!(in_flight, 1);
+
+        // The request is not fulfilled until every transaction of the batch is executed
+        let pending = get_stored_request(&forced_exit_sender, 12);
+        assert_eq!(pending.fulfilled_at, None);
+        assert_eq!(pending.fulfilled_by, Some(vec![hash(1), hash(2)]));
+
+        // A single failed transaction releases the whole request, though only
+        // the token of the failed one is counted as failed
+        let failed = get_stored_request(&forced_exit_sender, 13);
+        assert_eq!(failed.fulfilled_at, None);
+        assert_eq!(failed.fulfilled_by, None);
+        assert_eq!(
+            failed.cancellation,
+            Some(ForcedExitCancellationKind::SystemRetry)
+        );
+        {
+            let failures = forced_exit_sender
+                .core_interaction_wrapper
+                .failures
+                .lock()
+                .unwrap();
+            assert_eq!(failures.get(&(13, TokenId(1))), None);
+            assert_eq!(failures.get(&(13, TokenId(2))), Some(&1));
+        }
+
+        forced_exit_sender
+            .core_interaction_wrapper
+            .lock_receipts()
+            .insert(hash(2), succeeded_receipt);
+        let in_flight = forced_exit_sender
+            .reconcile_unconfirmed(Duration::from_millis(0))
+            .await
+            .unwrap();
+        assert_eq!(in_flight, 0);
+        assert!(get_stored_request(&forced_exit_sender, 12)
+            .fulfilled_at
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn interrupted_submissions_are_settled() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        let hash = |byte: u8| TxHash::from_slice(&[byte; 32]).unwrap();
+
+        // The transactions were recorded, yet the server crashed before they were confirmed
+        // as submitted: the first batch had reached the mempool by then, the second one not
+        for (id, tx_hash, reached_mempool) in [(12, hash(1), true), (13, hash(2), false)] {
+            add_request(
+                &forced_exit_sender.core_interaction_wrapper.requests,
+                ForcedExitRequest {
+                    fulfilled_by: Some(vec![tx_hash]),
+                    ..get_test_request(id, "10000000000")
+                },
+            );
+            forced_exit_sender
+                .core_interaction_wrapper
+                .interrupted_submissions
+                .lock()
+                .unwrap()
+                .push(InterruptedSubmission {
+                    request_id: id,
+                    tx_hashes: vec![tx_hash],
+                    reached_mempool,
+                });
+        }
+
+        let in_flight = forced_exit_sender
+            .reconcile_unconfirmed(Duration::from_millis(0))
+            .await
+            .unwrap();
+        assert_eq!(in_flight, 0);
+        assert!(forced_exit_sender
+            .core_interaction_wrapper
+            .interrupted_submissions
+            .lock()
+            .unwrap()
+            .is_empty());
+
+        // The accepted batch is awaited as any other one sent before
+        let sent = get_stored_request(&forced_exit_sender, 12);
+        assert_eq!(sent.fulfilled_by, Some(vec![hash(1)]));
+        assert!(sent.fulfilled_at.is_some());
+
+        // The lost one is sent again, it is not counted as failed
+        let released = get_stored_request(&forced_exit_sender, 13);
+        assert_eq!(released.fulfilled_by, None);
+        assert_eq!(released.fulfilled_at, None);
+        assert_eq!(
+            released.cancellation,
+            Some(ForcedExitCancellationKind::SystemRetry)
+        );
+        assert!(forced_exit_sender
+            .core_interaction_wrapper
+            .failures
+            .lock()
+            .unwrap()
+            .is_empty());
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+    }
+}
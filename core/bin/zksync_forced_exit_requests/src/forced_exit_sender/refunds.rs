@@ -0,0 +1,655 @@
+//! Refunding the payments which can not be used to fulfill the requests.
+
+use chrono::{DateTime, Utc};
+use num::{BigUint, ToPrimitive};
+
+use zksync_api::api_server::forced_exit_receipts::tx_status;
+use zksync_types::{
+    forced_exit_requests::{
+        ForcedExitCancellationKind, ForcedExitRefund, ForcedExitRefundReason,
+        ForcedExitRefundStatus, ForcedExitRequestId, ForcedExitTxStatus, FundsReceivedEvent,
+        SaveForcedExitRefundQuery,
+    },
+    helpers::closest_packable_token_amount,
+    TokenId,
+};
+
+use crate::{
+    core_interaction_wrapper::CoreInteractionWrapper, refund_budget::RefundBudgetDecision,
+};
+
+use super::{CommitError, MempoolForcedExitSender};
+
+impl<T: CoreInteractionWrapper + Sync> MempoolForcedExitSender<T> {
+    /// Records the refund of the payment, which can not fulfill the request it was made for.
+    ///
+    /// The payments for the fulfilled requests are not refunded, since these are most
+    /// likely delivered once again. Neither are the ones not covering the processing fee.
+    pub(super) async fn refund_payment(
+        &self,
+        payment: &FundsReceivedEvent,
+        request_id: ForcedExitRequestId,
+        submission_time: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        if !self.config.refunds_enabled || !self.core_interaction_wrapper.capabilities().refunds {
+            return Ok(());
+        }
+        // There is nobody to return the payment to without the payer
+        let (payer, payment_tx_hash) = match (payment.payer, payment.eth_tx_hash) {
+            (Some(payer), Some(payment_tx_hash)) => (payer, payment_tx_hash),
+            _ => return Ok(()),
+        };
+        let request = match self
+            .core_interaction_wrapper
+            .get_request_by_id(request_id)
+            .await?
+        {
+            Some(request) if request.fulfilled_at.is_none() && request.fulfilled_by.is_none() => {
+                request
+            }
+            _ => return Ok(()),
+        };
+        let fee = BigUint::from(self.config.refund_processing_fee);
+        if payment.amount <= fee {
+            vlog::info!(
+                "The payment {:?} for ForcedExit request {} does not cover the refund fee",
+                payment_tx_hash,
+                request_id
+            );
+            return Ok(());
+        }
+
+        let reason = match request.cancellation {
+            Some(ForcedExitCancellationKind::Expired) => ForcedExitRefundReason::Expired,
+            Some(ForcedExitCancellationKind::TargetNotFound) => {
+                ForcedExitRefundReason::TargetNotFound
+            }
+            _ if request.valid_until <= submission_time => ForcedExitRefundReason::Expired,
+            Some(kind) if !kind.allows_reprocessing() => ForcedExitRefundReason::Cancelled,
+            _ => ForcedExitRefundReason::IncorrectAmount,
+        };
+        let amount = closest_packable_token_amount(&(&payment.amount - &fee));
+        let requires_approval = amount > self.config.refund_approval_threshold;
+        let refund = SaveForcedExitRefundQuery {
+            request_id,
+            payment_tx_hash,
+            recipient: payer,
+            amount,
+            fee,
+            reason,
+            requires_approval,
+            created_at: Utc::now(),
+        };
+        if let Some(refund) = self.core_interaction_wrapper.store_refund(refund).await? {
+            vlog::info!(
+                "The payment {:?} for ForcedExit request {} is refunded to {:?}: {}",
+                payment_tx_hash,
+                request_id,
+                payer,
+                reason
+            );
+            metrics::increment_counter!(
+                "forced_exit_requests.refunds",
+                "reason" => refund.reason.as_str()
+            );
+            if requires_approval {
+                vlog::warn!(
+                    "The refund {} of {} wei exceeds the approval threshold, it awaits the operators",
+                    refund.id,
+                    refund.amount
+                );
+                metrics::increment_counter!("forced_exit_requests.refunds_awaiting_approval");
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the transfers of the recorded refunds and settles the ones sent before.
+    ///
+    /// The failed transfers are sent again until `processing_attempts` of them have failed,
+    /// the ones not committed in time are checked again on the next call. The refunds
+    /// not fitting into the hourly limit are deferred to the next calls, see `refund_budget`,
+    /// and so are the ones not covered by the balance of the sender account.
+    pub async fn process_refunds(&mut self) -> anyhow::Result<()> {
+        if !self.config.refunds_enabled || !self.core_interaction_wrapper.capabilities().refunds {
+            return Ok(());
+        }
+        if self.refund_budget.is_limited() && !self.refund_budget.is_restored() {
+            let refunded = self
+                .core_interaction_wrapper
+                .get_refunded_amount_since(Utc::now() - chrono::Duration::hours(1))
+                .await?;
+            self.refund_budget.restore(&refunded);
+        }
+
+        let max_attempts = self.config.processing_attempts.max(1);
+        let refunds = self
+            .core_interaction_wrapper
+            .get_unsettled_refunds(max_attempts)
+            .await?;
+        let mut balance = self.check_sender_balance().await?;
+        let mut deferred = 0;
+        let mut awaiting_funds = 0;
+        for refund in refunds {
+            if refund.status != ForcedExitRefundStatus::Sent {
+                // The transfer would be rejected and use an attempt up, so it waits for the account
+                // to be topped up, along with the ones following it
+                if awaiting_funds > 0 || refund.amount > balance {
+                    awaiting_funds += 1;
+                    continue;
+                }
+                // The approved refunds go first and are sent regardless of the limit. The rest
+                // are sent in the order they were recorded, so the ones following the deferred
+                // refund wait as well instead of using the budget up before it
+                if refund.approved_at.is_some() {
+                    self.refund_budget.spend(&refund.amount);
+                } else if deferred > 0 {
+                    deferred += 1;
+                    continue;
+                } else if let RefundBudgetDecision::Defer(delay) =
+                    self.refund_budget.try_spend(&refund.amount)
+                {
+                    vlog::info!(
+                        "The refund {} of {} wei exceeds the hourly limit, it is deferred for {:?}",
+                        refund.id,
+                        refund.amount,
+                        delay
+                    );
+                    deferred += 1;
+                    continue;
+                }
+                balance -= &refund.amount;
+            }
+            if let Err(err) = self.process_refund(&refund).await {
+                vlog::warn!(
+                    "Failed to process the refund {} for ForcedExit request {}: {}",
+                    refund.id,
+                    refund.request_id,
+                    err
+                );
+            }
+        }
+        metrics::gauge!("forced_exit_requests.deferred_refunds", deferred as f64);
+        metrics::gauge!(
+            "forced_exit_requests.refunds_awaiting_funds",
+            awaiting_funds as f64
+        );
+        if awaiting_funds > 0 {
+            vlog::warn!(
+                "{} refunds are not covered by the balance of {} wei of the forced exit sender {:?}",
+                awaiting_funds,
+                balance,
+                self.sender_accounts.main().address
+            );
+        }
+        Ok(())
+    }
+
+    /// Reports the balance of the main sender account, which the refunds are sent from,
+    /// and alerts the operators once it falls below `min_sender_balance`.
+    pub(super) async fn check_sender_balance(&self) -> anyhow::Result<BigUint> {
+        let account = self.sender_accounts.main();
+        let balance = self
+            .core_interaction_wrapper
+            .get_balance(account.account_id, TokenId(0))
+            .await?;
+        metrics::gauge!(
+            "forced_exit_requests.sender_balance",
+            balance.to_f64().unwrap_or(f64::MAX)
+        );
+        if balance < self.config.min_sender_balance {
+            metrics::increment_counter!("forced_exit_requests.low_sender_balance");
+            vlog::error!(
+                "The balance of the forced exit sender {:?} is {} wei, below the minimum of {} wei, \
+                 it must be topped up to keep sending the refunds",
+                account.address,
+                balance,
+                self.config.min_sender_balance
+            );
+        }
+        Ok(balance)
+    }
+
+    pub(super) async fn process_refund(&mut self, refund: &ForcedExitRefund) -> anyhow::Result<()> {
+        if refund.status == ForcedExitRefundStatus::Sent {
+            let tx_hash = refund
+                .tx_hash
+                .ok_or_else(|| anyhow::anyhow!("Refund {} was sent without a hash", refund.id))?;
+            let receipt = self.core_interaction_wrapper.get_receipt(tx_hash).await?;
+            let status = match tx_status(receipt.as_ref()) {
+                ForcedExitTxStatus::Pending => return Ok(()),
+                ForcedExitTxStatus::Failed => ForcedExitRefundStatus::Failed,
+                ForcedExitTxStatus::Committed | ForcedExitTxStatus::Verified => {
+                    ForcedExitRefundStatus::Completed
+                }
+            };
+            return self
+                .core_interaction_wrapper
+                .set_refund_status(refund.id, status, None)
+                .await;
+        }
+
+        let send_lock = self.sender_accounts.main().send_lock.clone();
+        let send_guard = send_lock.lock().await;
+        let nonce = self.next_nonce(self.sender_accounts.main()).await?;
+        let tx = self.build_refund(refund, nonce).await?;
+        let sent = self
+            .core_interaction_wrapper
+            .send_refund(refund.id, tx)
+            .await;
+        drop(send_guard);
+        let tx_hash = match sent {
+            Ok(tx_hash) => tx_hash,
+            Err(err) => {
+                // The rejected transfers count as failed, so they are not sent forever
+                self.core_interaction_wrapper
+                    .set_refund_status(refund.id, ForcedExitRefundStatus::Failed, None)
+                    .await?;
+                return Err(err);
+            }
+        };
+        match self.wait_until_comitted(tx_hash).await {
+            Ok(()) => {
+                self.core_interaction_wrapper
+                    .set_refund_status(refund.id, ForcedExitRefundStatus::Completed, None)
+                    .await?;
+                metrics::increment_counter!("forced_exit_requests.completed_refunds");
+                Ok(())
+            }
+            Err(err) => {
+                if let Some(CommitError::Failed { .. }) = err.downcast_ref::<CommitError>() {
+                    self.core_interaction_wrapper
+                        .set_refund_status(refund.id, ForcedExitRefundStatus::Failed, None)
+                        .await?;
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use zksync_config::ForcedExitRequestsConfig;
+    use zksync_types::{forced_exit_requests::ForcedExitRequest, Address, Nonce, ZkSyncTx, H256};
+
+    use crate::{
+        forced_exit_sender::{
+            test_utils::{
+                failed_receipt, get_stored_request, get_test_forced_exit_sender, get_test_request,
+                payment, refundable_payment, refunds_config, sent_txs_count,
+            },
+            ForcedExitSender,
+        },
+        refund_budget::RefundBudget,
+        test::add_request,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn expired_request_payment_is_refunded() {
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(refunds_config()));
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            ForcedExitRequest {
+                valid_until: Utc::now() - chrono::Duration::days(1),
+                ..get_test_request(12, "10000000000")
+            },
+        );
+
+        // The payment delivered twice is refunded once
+        let paid = refundable_payment("10000000012", None, H256::repeat_byte(0x01));
+        for _ in 0..2 {
+            forced_exit_sender
+                .process_request(paid.clone(), Utc::now())
+                .await
+                .unwrap();
+        }
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+        let refunds = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_refunds()
+            .clone();
+        assert_eq!(refunds.len(), 1);
+        assert_eq!(refunds[0].request_id, 12);
+        assert_eq!(refunds[0].recipient, Address::repeat_byte(0x33));
+        assert_eq!(refunds[0].reason, ForcedExitRefundReason::Expired);
+        assert_eq!(refunds[0].amount, BigUint::from(9_999_000_012u64));
+        assert_eq!(refunds[0].fee, BigUint::from(1_000_000u64));
+        assert_eq!(refunds[0].status, ForcedExitRefundStatus::Pending);
+
+        // The payment is transferred back to the payer once
+        for _ in 0..2 {
+            forced_exit_sender.process_refunds().await.unwrap();
+        }
+        let sent_txs = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_sent_txs()
+            .clone();
+        assert_eq!(sent_txs.len(), 1);
+        match &sent_txs[0].tx {
+            ZkSyncTx::Transfer(transfer) => {
+                assert_eq!(transfer.to, Address::repeat_byte(0x33));
+                assert_eq!(transfer.token, TokenId(0));
+                assert_eq!(transfer.amount, BigUint::from(9_999_000_012u64));
+            }
+            _ => panic!("Only Transfer transactions are sent for the refunds"),
+        }
+        let refund = forced_exit_sender.core_interaction_wrapper.lock_refunds()[0].clone();
+        assert_eq!(refund.status, ForcedExitRefundStatus::Completed);
+        assert_eq!(refund.tx_hash, Some(sent_txs[0].hash()));
+    }
+
+    #[tokio::test]
+    async fn unpayable_requests_payments_are_refunded_by_reason() {
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(refunds_config()));
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            ForcedExitRequest {
+                cancellation: Some(ForcedExitCancellationKind::UserCancelled),
+                ..get_test_request(13, "10000000000")
+            },
+        );
+
+        // Less than the price of the request
+        forced_exit_sender
+            .process_request(
+                refundable_payment("9999999999", Some(12), H256::repeat_byte(0x01)),
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+        forced_exit_sender
+            .process_request(
+                refundable_payment("10000000000", Some(13), H256::repeat_byte(0x02)),
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+        // There is nobody to return the payments without the payer to,
+        // neither the ones not covering the fee
+        forced_exit_sender
+            .process_request(payment("9999999999", Some(12)), Utc::now())
+            .await
+            .unwrap();
+        forced_exit_sender
+            .process_request(
+                refundable_payment("1000000", Some(12), H256::repeat_byte(0x03)),
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+
+        let reasons: Vec<_> = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_refunds()
+            .iter()
+            .map(|refund| (refund.request_id, refund.reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                (12, ForcedExitRefundReason::IncorrectAmount),
+                (13, ForcedExitRefundReason::Cancelled)
+            ]
+        );
+
+        // The request paid for correctly afterwards is still fulfilled
+        forced_exit_sender
+            .process_request(
+                refundable_payment("10000000000", Some(12), H256::repeat_byte(0x04)),
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+        assert!(get_stored_request(&forced_exit_sender, 12)
+            .fulfilled_at
+            .is_some());
+        assert_eq!(
+            forced_exit_sender
+                .core_interaction_wrapper
+                .lock_refunds()
+                .len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_refunds_are_retried() {
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(refunds_config()));
+        forced_exit_sender.core_interaction_wrapper.tx_receipt = Some(failed_receipt());
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        forced_exit_sender
+            .process_request(
+                refundable_payment("9999999999", Some(12), H256::repeat_byte(0x01)),
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+
+        // The transfer is sent again until all the attempts have failed
+        for _ in 0..3 {
+            forced_exit_sender.process_refunds().await.unwrap();
+        }
+        assert_eq!(sent_txs_count(&forced_exit_sender), 2);
+        let refund = forced_exit_sender.core_interaction_wrapper.lock_refunds()[0].clone();
+        assert_eq!(refund.status, ForcedExitRefundStatus::Failed);
+        assert_eq!(refund.attempts, 2);
+        let nonces: Vec<_> = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_sent_txs()
+            .iter()
+            .map(|tx| tx.nonce())
+            .collect();
+        assert_eq!(nonces, vec![Nonce(0), Nonce(1)]);
+    }
+
+    #[tokio::test]
+    async fn payments_are_not_refunded_when_disabled() {
+        let config = ForcedExitRequestsConfig {
+            refunds_enabled: false,
+            ..refunds_config()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(config));
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        forced_exit_sender
+            .process_request(
+                refundable_payment("9999999999", Some(12), H256::repeat_byte(0x01)),
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+        forced_exit_sender.process_refunds().await.unwrap();
+
+        assert!(forced_exit_sender
+            .core_interaction_wrapper
+            .lock_refunds()
+            .is_empty());
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+    }
+
+    #[tokio::test]
+    async fn refunds_above_the_threshold_await_approval() {
+        let config = ForcedExitRequestsConfig {
+            refund_approval_threshold: BigUint::from(5_000_000_000u64),
+            ..refunds_config()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(config));
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        let payments = [
+            refundable_payment("2000000", Some(12), H256::repeat_byte(0x01)),
+            refundable_payment("9999999999", Some(12), H256::repeat_byte(0x02)),
+        ];
+        for payment in &payments {
+            forced_exit_sender
+                .process_request(payment.clone(), Utc::now())
+                .await
+                .unwrap();
+        }
+
+        // Only the refund below the threshold is sent on its own
+        forced_exit_sender.process_refunds().await.unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+        let refunds = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_refunds()
+            .clone();
+        assert_eq!(refunds[0].status, ForcedExitRefundStatus::Completed);
+        assert_eq!(refunds[1].status, ForcedExitRefundStatus::AwaitingApproval);
+        assert_eq!(refunds[1].amount, BigUint::from(9_998_999_999u64));
+
+        {
+            let mut refunds = forced_exit_sender.core_interaction_wrapper.lock_refunds();
+            refunds[1].status = ForcedExitRefundStatus::Pending;
+            refunds[1].approved_by = Some("operator".to_string());
+            refunds[1].approved_at = Some(Utc::now());
+        }
+        // The approved refund is sent even though the hourly limit is used up
+        forced_exit_sender.refund_budget = RefundBudget::new(BigUint::from(1_000_000u64));
+        forced_exit_sender.process_refunds().await.unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 2);
+        let refund = forced_exit_sender.core_interaction_wrapper.lock_refunds()[1].clone();
+        assert_eq!(refund.status, ForcedExitRefundStatus::Completed);
+
+        // The payments delivered again are not refunded twice
+        for payment in &payments {
+            forced_exit_sender
+                .process_request(payment.clone(), Utc::now())
+                .await
+                .unwrap();
+        }
+        forced_exit_sender.process_refunds().await.unwrap();
+        assert_eq!(
+            forced_exit_sender
+                .core_interaction_wrapper
+                .lock_refunds()
+                .len(),
+            2
+        );
+        assert_eq!(sent_txs_count(&forced_exit_sender), 2);
+    }
+
+    #[tokio::test]
+    async fn refunds_above_the_hourly_limit_are_deferred() {
+        let config = ForcedExitRequestsConfig {
+            refund_approval_threshold: BigUint::from(10_000_000_000u64),
+            max_refunded_amount_per_hour: BigUint::from(15_000_000_000u64),
+            ..refunds_config()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(config.clone()));
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        for byte in 1..=3 {
+            forced_exit_sender
+                .process_request(
+                    refundable_payment("9999999999", Some(12), H256::repeat_byte(byte)),
+                    Utc::now(),
+                )
+                .await
+                .unwrap();
+        }
+
+        // The bucket only fits one of the refunds, the rest wait for it to be refilled
+        for _ in 0..2 {
+            forced_exit_sender.process_refunds().await.unwrap();
+        }
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+        let statuses: Vec<_> = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_refunds()
+            .iter()
+            .map(|refund| refund.status)
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ForcedExitRefundStatus::Completed,
+                ForcedExitRefundStatus::Pending,
+                ForcedExitRefundStatus::Pending,
+            ]
+        );
+
+        // The restarted sender takes the refund sent within the hour into account
+        forced_exit_sender.refund_budget = RefundBudget::new(config.max_refunded_amount_per_hour);
+        forced_exit_sender.process_refunds().await.unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+    }
+
+    #[tokio::test]
+    async fn refunds_await_the_sender_to_be_funded() {
+        let config = ForcedExitRequestsConfig {
+            min_sender_balance: BigUint::from(100_000_000_000u64),
+            ..refunds_config()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(config));
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        for byte in 1..=3 {
+            forced_exit_sender
+                .process_request(
+                    refundable_payment("9999999999", Some(12), H256::repeat_byte(byte)),
+                    Utc::now(),
+                )
+                .await
+                .unwrap();
+        }
+
+        // The balance only covers one of the refunds, the rest are not sent at all,
+        // so they do not use their attempts up
+        *forced_exit_sender
+            .core_interaction_wrapper
+            .sender_balance
+            .lock()
+            .unwrap() = BigUint::from(15_000_000_000u64);
+        for _ in 0..3 {
+            forced_exit_sender.process_refunds().await.unwrap();
+        }
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+        let statuses: Vec<_> = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_refunds()
+            .iter()
+            .map(|refund| refund.status)
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ForcedExitRefundStatus::Completed,
+                ForcedExitRefundStatus::Pending,
+                ForcedExitRefundStatus::Pending,
+            ]
+        );
+
+        // Once the account is topped up the rest are sent
+        *forced_exit_sender
+            .core_interaction_wrapper
+            .sender_balance
+            .lock()
+            .unwrap() = BigUint::from(100_000_000_000u64);
+        forced_exit_sender.process_refunds().await.unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 3);
+        assert!(forced_exit_sender
+            .core_interaction_wrapper
+            .lock_refunds()
+            .iter()
+            .all(|refund| refund.status == ForcedExitRefundStatus::Completed));
+    }
+}
@@ -0,0 +1,639 @@
+//! Resolving the target accounts of the requests and handling the targets which have
+//! become active since the requests were created.
+
+use chrono::{DateTime, Utc};
+use num::BigUint;
+
+use zksync_types::{
+    forced_exit_requests::{
+        ActiveTargetPolicy, ForcedExitCancellationKind, ForcedExitPreflight, ForcedExitRequest,
+        ForcedExitRequestActiveTarget, ForcedExitRequestId, FundsReceivedEvent, PaymentMatchScheme,
+    },
+    AccountId, Nonce, H256,
+};
+
+use crate::core_interaction_wrapper::CoreInteractionWrapper;
+
+use super::{MempoolForcedExitSender, PaymentDecision, TargetNotFound};
+
+impl<T: CoreInteractionWrapper + Sync> MempoolForcedExitSender<T> {
+    /// Fails the request, the target address of which has no account, and refunds its payment.
+    pub(super) async fn fail_missing_target(
+        &self,
+        payment: &FundsReceivedEvent,
+        request_id: ForcedExitRequestId,
+        submission_time: DateTime<Utc>,
+    ) -> anyhow::Result<PaymentDecision> {
+        vlog::warn!(
+            "ForcedExit request {} has failed, its target has no account",
+            request_id
+        );
+        metrics::increment_counter!("forced_exit_requests.targets_not_found");
+        self.core_interaction_wrapper
+            .cancel_request(request_id, ForcedExitCancellationKind::TargetNotFound)
+            .await?;
+        self.refund_payment(payment, request_id, submission_time)
+            .await?;
+        Ok(PaymentDecision::NotPossible { request_id })
+    }
+
+    /// Resolves the id of the target account at the latest committed state.
+    pub(super) async fn resolve_target(
+        &self,
+        fe_request: &ForcedExitRequest,
+    ) -> anyhow::Result<AccountId> {
+        self.core_interaction_wrapper
+            .get_account_id(fe_request.target)
+            .await?
+            .ok_or_else(|| {
+                TargetNotFound {
+                    request_id: fe_request.id,
+                }
+                .into()
+            })
+    }
+
+    /// Records the id the target was resolved to along with the sent transactions.
+    pub(super) async fn record_target_account_id(
+        &self,
+        id: ForcedExitRequestId,
+        target_account_id: AccountId,
+    ) {
+        if !self
+            .core_interaction_wrapper
+            .capabilities()
+            .target_account_ids
+        {
+            return;
+        }
+        // The transactions are sent already, the missing id must not get them sent again
+        if let Err(err) = self
+            .core_interaction_wrapper
+            .record_target_account_id(id, target_account_id)
+            .await
+        {
+            vlog::warn!(
+                "Failed to record the target account id {} of the ForcedExit request {}: {}",
+                target_account_id,
+                id,
+                err
+            );
+        }
+    }
+
+    /// Applies the configured policy to the paid request, the target of which has
+    /// become active: the request either fails right away or is held for a while.
+    ///
+    /// The payment is recorded along with the policy, so the failed request can
+    /// be refunded and the held one fulfilled without the payment at hand.
+    pub(super) async fn handle_active_target(
+        &self,
+        request: &ForcedExitRequest,
+        preflight: &ForcedExitPreflight,
+        match_scheme: PaymentMatchScheme,
+        payment_amount: BigUint,
+        payment_tx_hash: Option<H256>,
+        paid_at: DateTime<Utc>,
+    ) -> anyhow::Result<ActiveTargetPolicy> {
+        let policy = self.config.active_target_policy;
+        let target_nonce = preflight
+            .target
+            .and_then(|target| target.nonce)
+            .unwrap_or(Nonce(0));
+        let detected_at = Utc::now();
+        let (hold_until, failed_at) = match policy {
+            ActiveTargetPolicy::Fail => (None, Some(detected_at)),
+            ActiveTargetPolicy::Hold => {
+                let hold_period =
+                    chrono::Duration::from_std(self.config.active_target_hold_period())?;
+                (Some(detected_at + hold_period), None)
+            }
+        };
+
+        // Only the failing policy is supported without the record, see `Capabilities`
+        if self.core_interaction_wrapper.capabilities().active_targets {
+            self.core_interaction_wrapper
+                .store_active_target(ForcedExitRequestActiveTarget {
+                    request_id: request.id,
+                    policy,
+                    target_nonce,
+                    payment_amount,
+                    payment_tx_hash,
+                    match_scheme,
+                    paid_at,
+                    detected_at,
+                    hold_until,
+                    resumed_at: None,
+                    failed_at,
+                })
+                .await?;
+        }
+
+        vlog::warn!(
+            "The target of the ForcedExit request {} has become active with the nonce {}, \
+             the request is handled with the `{}` policy",
+            request.id,
+            target_nonce,
+            policy
+        );
+        metrics::increment_counter!(
+            "forced_exit_requests.active_targets",
+            "policy" => policy.as_str()
+        );
+        Ok(policy)
+    }
+
+    /// Resumes the held requests, the targets of which can be forced to exit again,
+    /// and fails the ones held for longer than the hold period.
+    pub async fn process_held_requests(&mut self, now: DateTime<Utc>) -> anyhow::Result<()> {
+        if !self.core_interaction_wrapper.capabilities().active_targets {
+            return Ok(());
+        }
+
+        let held = self
+            .core_interaction_wrapper
+            .get_held_active_targets()
+            .await?;
+        for active_target in held {
+            let id = active_target.request_id;
+            let request = match self.core_interaction_wrapper.get_request_by_id(id).await? {
+                Some(request) => request,
+                None => continue,
+            };
+
+            // The request was payable when paid for, so it does not expire while held
+            let preflight = self.preflight(&request, active_target.paid_at).await?;
+            if preflight.blocker.is_none() {
+                self.core_interaction_wrapper
+                    .resume_active_target(id, now)
+                    .await?;
+                vlog::info!(
+                    "The target of the held ForcedExit request {} can be forced to exit again",
+                    id
+                );
+                metrics::increment_counter!("forced_exit_requests.active_targets_resumed");
+                self.fulfill(
+                    request,
+                    &preflight,
+                    active_target.match_scheme,
+                    &active_target.payment_amount,
+                    active_target.payment_tx_hash,
+                    Vec::new(),
+                )
+                .await?;
+            } else if active_target.is_hold_expired(now) {
+                self.core_interaction_wrapper
+                    .fail_active_target(id, now)
+                    .await?;
+                vlog::warn!(
+                    "The held ForcedExit request {} has failed, its payment is to be refunded",
+                    id
+                );
+                metrics::increment_counter!("forced_exit_requests.active_targets_failed");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+    use num::Zero;
+
+    use zksync_config::ForcedExitRequestsConfig;
+    use zksync_storage::chain::operations_ext::records::TxReceiptResponse;
+    use zksync_types::{
+        forced_exit_requests::{
+            ForcedExitBlocker, ForcedExitLifecycleStatus, ForcedExitRefundReason,
+            ForcedExitRequestEvent, ForcedExitTargetCheck, PlannedForcedExit,
+        },
+        TokenId,
+    };
+
+    use crate::{
+        forced_exit_sender::{
+            test_utils::{
+                failed_receipt, get_stored_request, get_test_forced_exit_sender, get_test_request,
+                payment, refundable_payment, refunds_config, sent_txs_count,
+            },
+            CommitError,
+        },
+        test::{add_request, MockCoreInteractionWrapper},
+    };
+
+    use super::*;
+
+    // The request is created for the target which can be forced to exit, so the target
+    // of the mock sets its signing key after the request is paid for and matched,
+    // but before the transactions are built
+    fn activate_target(sender: &mut MempoolForcedExitSender<MockCoreInteractionWrapper>) {
+        sender.core_interaction_wrapper.target_check = ForcedExitTargetCheck {
+            old_enough: true,
+            nonce: Some(Nonce(1)),
+        };
+    }
+
+    #[tokio::test]
+    async fn test_active_target_fails_request() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            active_target_policy: ActiveTargetPolicy::Fail,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        let request = get_test_request(12, "10000000000");
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            request.clone(),
+        );
+        activate_target(&mut forced_exit_sender);
+
+        let preflight = forced_exit_sender
+            .preflight(&request, Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(
+            preflight.blocker,
+            Some(ForcedExitBlocker::TargetBecameActive)
+        );
+
+        let paid_at = Utc::now();
+        let eth_tx_hash = H256::repeat_byte(0x12);
+        let decision = forced_exit_sender
+            .process_payment(
+                FundsReceivedEvent {
+                    eth_tx_hash: Some(eth_tx_hash),
+                    ..payment("10000000012", None)
+                },
+                paid_at,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            decision,
+            PaymentDecision::TargetBecameActive {
+                request_id: 12,
+                policy: ActiveTargetPolicy::Fail,
+            }
+        );
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+
+        // The payment is recorded to be refunded
+        let active_targets = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_active_targets()
+            .clone();
+        assert_eq!(active_targets.len(), 1);
+        let failed = &active_targets[0];
+        assert_eq!(failed.request_id, 12);
+        assert_eq!(failed.policy, ActiveTargetPolicy::Fail);
+        assert_eq!(failed.target_nonce, Nonce(1));
+        assert_eq!(failed.payment_amount, BigUint::from(10000000012u64));
+        assert_eq!(failed.payment_tx_hash, Some(eth_tx_hash));
+        assert_eq!(failed.match_scheme, PaymentMatchScheme::AmountDigits);
+        assert_eq!(failed.paid_at, paid_at);
+        assert!(failed.is_failed() && !failed.is_held());
+        let events: Vec<_> = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_deliveries()
+            .iter()
+            .map(|delivery| (delivery.request_id, delivery.event))
+            .collect();
+        assert_eq!(events, vec![(12, ForcedExitRequestEvent::Failed)]);
+
+        // The failed request is not resumed even if the target is reset
+        forced_exit_sender.core_interaction_wrapper.target_check = ForcedExitTargetCheck {
+            old_enough: true,
+            nonce: Some(Nonce(0)),
+        };
+        forced_exit_sender
+            .process_held_requests(Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+        assert!(get_stored_request(&forced_exit_sender, 12)
+            .fulfilled_at
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_active_target_holds_request() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            active_target_policy: ActiveTargetPolicy::Hold,
+            active_target_hold_period: 60 * 60 * 1000,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        for id in 12..14 {
+            add_request(
+                &forced_exit_sender.core_interaction_wrapper.requests,
+                get_test_request(id, "10000000000"),
+            );
+        }
+        activate_target(&mut forced_exit_sender);
+
+        let paid_at = Utc::now();
+        let eth_tx_hash = H256::repeat_byte(0x12);
+        let decision = forced_exit_sender
+            .process_payment(
+                FundsReceivedEvent {
+                    eth_tx_hash: Some(eth_tx_hash),
+                    ..payment("10000000012", None)
+                },
+                paid_at,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            decision,
+            PaymentDecision::TargetBecameActive {
+                request_id: 12,
+                policy: ActiveTargetPolicy::Hold,
+            }
+        );
+        // The second request is held for no time at all
+        forced_exit_sender.config.active_target_hold_period = 0;
+        forced_exit_sender
+            .process_payment(payment("10000000013", None), paid_at)
+            .await
+            .unwrap();
+
+        let held = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_active_targets()
+            .clone();
+        assert_eq!(held.len(), 2);
+        assert!(held.iter().all(|active_target| active_target.is_held()));
+        assert!(held[0].hold_until.unwrap() > Utc::now());
+
+        // The request which is held for too long fails, the other one is still held
+        forced_exit_sender
+            .process_held_requests(Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 0);
+        let active_targets = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_active_targets()
+            .clone();
+        assert!(active_targets[0].is_held());
+        assert!(active_targets[1].is_failed());
+
+        // The request is fulfilled once the target is reset, even though it expires in the meantime
+        forced_exit_sender
+            .core_interaction_wrapper
+            .requests
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .for_each(|request| request.valid_until = Utc::now());
+        forced_exit_sender.core_interaction_wrapper.target_check = ForcedExitTargetCheck {
+            old_enough: true,
+            nonce: Some(Nonce(0)),
+        };
+        forced_exit_sender
+            .process_held_requests(Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+
+        let resumed = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_active_targets()[0]
+            .clone();
+        assert!(resumed.resumed_at.is_some() && !resumed.is_held());
+        let request = get_stored_request(&forced_exit_sender, 12);
+        assert!(request.fulfilled_at.is_some());
+        assert_eq!(request.match_scheme, Some(PaymentMatchScheme::AmountDigits));
+        assert_eq!(
+            *forced_exit_sender
+                .core_interaction_wrapper
+                .payment_matches
+                .lock()
+                .unwrap(),
+            vec![(eth_tx_hash, 12)]
+        );
+        assert!(get_stored_request(&forced_exit_sender, 13)
+            .fulfilled_at
+            .is_none());
+
+        let events: Vec<_> = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_deliveries()
+            .iter()
+            .map(|delivery| (delivery.request_id, delivery.event))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (13, ForcedExitRequestEvent::Failed),
+                (12, ForcedExitRequestEvent::Submitted),
+                (12, ForcedExitRequestEvent::Fulfilled),
+            ]
+        );
+    }
+
+    fn target_mismatch_receipt() -> TxReceiptResponse {
+        TxReceiptResponse {
+            fail_reason: Some(String::from(
+                "Batch execution failed, since tx #0 of batch failed with a reason: \
+                 Target account does not exist",
+            )),
+            ..failed_receipt()
+        }
+    }
+
+    // The target is resolved to one account when the request is sent for the first time
+    // and to another one once the batch has failed
+    fn resolve_target_to(
+        sender: &MempoolForcedExitSender<MockCoreInteractionWrapper>,
+        account_ids: &[AccountId],
+    ) {
+        sender
+            .core_interaction_wrapper
+            .target_account_ids
+            .lock()
+            .unwrap()
+            .extend(account_ids.iter().copied().map(Some));
+    }
+
+    fn recorded_target_account_id(
+        sender: &MempoolForcedExitSender<MockCoreInteractionWrapper>,
+        id: ForcedExitRequestId,
+    ) -> Option<AccountId> {
+        sender
+            .core_interaction_wrapper
+            .recorded_target_account_ids
+            .lock()
+            .unwrap()
+            .get(&id)
+            .copied()
+    }
+
+    #[tokio::test]
+    async fn target_is_resolved_anew_after_mismatch() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        let request = get_test_request(12, "10000000000");
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            request.clone(),
+        );
+        // The target is resolved once again after the mismatch and by the next batch
+        resolve_target_to(
+            &forced_exit_sender,
+            &[AccountId(40), AccountId(41), AccountId(41)],
+        );
+
+        // Only the first batch fails, its target has been resolved to the stale account
+        forced_exit_sender.clock = || Utc.timestamp(1_600_000_000, 0);
+        let first_batch = forced_exit_sender
+            .build_forced_exit(
+                forced_exit_sender.sender_accounts.main(),
+                request.target,
+                &PlannedForcedExit {
+                    token: TokenId(1),
+                    nonce: Nonce(0),
+                    fee: BigUint::zero(),
+                },
+                forced_exit_sender
+                    .forced_exit_time_range(Utc.timestamp(1_600_000_000, 0))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        forced_exit_sender
+            .core_interaction_wrapper
+            .lock_receipts()
+            .insert(first_batch.hash(), target_mismatch_receipt());
+
+        let decision = forced_exit_sender
+            .process_payment(payment("10000000012", None), Utc::now())
+            .await
+            .unwrap();
+        assert!(matches!(
+            decision,
+            PaymentDecision::Fulfilled { request_id: 12, .. }
+        ));
+        assert_eq!(sent_txs_count(&forced_exit_sender), 2);
+        assert!(get_stored_request(&forced_exit_sender, 12)
+            .fulfilled_at
+            .is_some());
+        // The id of the batch which has been committed is kept
+        assert_eq!(
+            recorded_target_account_id(&forced_exit_sender, 12),
+            Some(AccountId(41))
+        );
+        // The retried failure does not count towards the escalation
+        assert!(forced_exit_sender
+            .core_interaction_wrapper
+            .failures
+            .lock()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn target_mismatch_is_retried_once() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        forced_exit_sender.core_interaction_wrapper.tx_receipt = Some(target_mismatch_receipt());
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        resolve_target_to(
+            &forced_exit_sender,
+            &[AccountId(40), AccountId(41), AccountId(41), AccountId(41)],
+        );
+
+        let err = forced_exit_sender
+            .try_process_request(payment("10000000012", None), Utc::now())
+            .await
+            .unwrap_err();
+        assert!(err
+            .downcast_ref::<CommitError>()
+            .map_or(false, CommitError::is_target_mismatch));
+        assert_eq!(sent_txs_count(&forced_exit_sender), 2);
+        assert_eq!(
+            recorded_target_account_id(&forced_exit_sender, 12),
+            Some(AccountId(41))
+        );
+        // Only the failure of the retried batch is counted
+        assert_eq!(
+            forced_exit_sender
+                .core_interaction_wrapper
+                .failures
+                .lock()
+                .unwrap()[&(12, TokenId(1))],
+            1
+        );
+        // The request is sent again by the next attempt
+        assert_eq!(
+            get_stored_request(&forced_exit_sender, 12).cancellation,
+            Some(ForcedExitCancellationKind::SystemRetry)
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_target_fails_the_request_and_is_refunded() {
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(refunds_config()));
+        forced_exit_sender.core_interaction_wrapper.tx_receipt = Some(target_mismatch_receipt());
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            get_test_request(12, "10000000000"),
+        );
+        // The target has no account anymore once the batch has failed
+        forced_exit_sender
+            .core_interaction_wrapper
+            .target_account_ids
+            .lock()
+            .unwrap()
+            .extend([Some(AccountId(40)), None]);
+
+        let decision = forced_exit_sender
+            .process_payment(
+                refundable_payment("10000000012", None, H256::repeat_byte(0x01)),
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(decision, PaymentDecision::NotPossible { request_id: 12 });
+        // Neither is the batch sent again nor the payment processed once more
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+        assert!(forced_exit_sender
+            .core_interaction_wrapper
+            .processing_failures
+            .lock()
+            .unwrap()
+            .is_empty());
+        assert!(forced_exit_sender
+            .core_interaction_wrapper
+            .failures
+            .lock()
+            .unwrap()
+            .is_empty());
+
+        let request = get_stored_request(&forced_exit_sender, 12);
+        assert_eq!(
+            request.cancellation,
+            Some(ForcedExitCancellationKind::TargetNotFound)
+        );
+        assert_eq!(request.status, ForcedExitLifecycleStatus::Failed);
+        let refunds = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_refunds()
+            .clone();
+        assert_eq!(refunds.len(), 1);
+        assert_eq!(refunds[0].request_id, 12);
+        assert_eq!(refunds[0].reason, ForcedExitRefundReason::TargetNotFound);
+    }
+}
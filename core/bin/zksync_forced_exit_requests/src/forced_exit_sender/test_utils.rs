@@ -0,0 +1,145 @@
+//! The helpers shared by the tests of the forced exit sender.
+
+use std::{ops::Add, str::FromStr};
+
+use chrono::Utc;
+use num::BigUint;
+
+use zksync_config::ForcedExitRequestsConfig;
+use zksync_storage::chain::operations_ext::records::TxReceiptResponse;
+use zksync_types::{
+    forced_exit_requests::{
+        legacy_pay_exactly, pay_exactly, ForcedExitRequest, ForcedExitRequestId, FundsReceivedEvent,
+    },
+    AccountId, Address, TokenId, H256,
+};
+
+use crate::test::MockCoreInteractionWrapper;
+
+use super::MempoolForcedExitSender;
+
+// Just a random number for tests
+pub(super) const TEST_ACCOUNT_FORCED_EXIT_SENDER_ID: u32 = 12;
+
+pub(super) const TEST_ZKSYNC_CONTRACT: Address = Address::repeat_byte(0x12);
+
+pub(super) fn get_test_forced_exit_sender(
+    config: Option<ForcedExitRequestsConfig>,
+) -> MempoolForcedExitSender<MockCoreInteractionWrapper> {
+    let core_interaction_wrapper = MockCoreInteractionWrapper::default();
+
+    let config = config.unwrap_or_else(ForcedExitRequestsConfig::from_env);
+
+    MempoolForcedExitSender::new(
+        core_interaction_wrapper,
+        config,
+        AccountId(TEST_ACCOUNT_FORCED_EXIT_SENDER_ID),
+        TEST_ZKSYNC_CONTRACT,
+    )
+}
+
+// Most of the tests pay with the legacy amounts, i.e. the price with the id added
+// to it, which are accepted as long as `legacy_amount_ids_enabled` is set
+pub(super) fn get_test_request(id: ForcedExitRequestId, price_in_wei: &str) -> ForcedExitRequest {
+    ForcedExitRequest {
+        pay_exactly: legacy_pay_exactly(&BigUint::from_str(price_in_wei).unwrap(), id),
+        ..get_checked_test_request(id, price_in_wei)
+    }
+}
+
+pub(super) fn get_checked_test_request(
+    id: ForcedExitRequestId,
+    price_in_wei: &str,
+) -> ForcedExitRequest {
+    ForcedExitRequest {
+        id,
+        public_id: id,
+        target: Address::random(),
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_str(price_in_wei).unwrap(),
+        pay_exactly: pay_exactly(&BigUint::from_str(price_in_wei).unwrap(), id),
+        valid_until: Utc::now().add(chrono::Duration::days(1)),
+        created_at: Utc::now(),
+        fulfilled_by: None,
+        fulfilled_at: None,
+        match_scheme: None,
+        matched_at: None,
+        cancellation: None,
+        paid_amount: None,
+        metadata: None,
+        status: Default::default(),
+        payment_terms: None,
+    }
+}
+
+pub(super) fn payment(amount: &str, request_id: Option<ForcedExitRequestId>) -> FundsReceivedEvent {
+    FundsReceivedEvent {
+        amount: BigUint::from_str(amount).unwrap(),
+        request_id,
+        block_number: 0,
+        eth_tx_hash: None,
+        payer: None,
+        recipient: None,
+    }
+}
+
+pub(super) fn sent_txs_count(
+    sender: &MempoolForcedExitSender<MockCoreInteractionWrapper>,
+) -> usize {
+    sender
+        .core_interaction_wrapper
+        .sent_txs
+        .lock()
+        .unwrap()
+        .len()
+}
+
+pub(super) fn get_stored_request(
+    sender: &MempoolForcedExitSender<MockCoreInteractionWrapper>,
+    id: ForcedExitRequestId,
+) -> ForcedExitRequest {
+    sender
+        .core_interaction_wrapper
+        .requests
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|request| request.id == id)
+        .cloned()
+        .unwrap()
+}
+
+pub(super) fn failed_receipt() -> TxReceiptResponse {
+    TxReceiptResponse {
+        tx_hash: String::from("1212"),
+        block_number: 120,
+        success: false,
+        verified: false,
+        fail_reason: Some(String::from("Pathological account state")),
+        prover_run: None,
+    }
+}
+
+pub(super) fn refunds_config() -> ForcedExitRequestsConfig {
+    ForcedExitRequestsConfig {
+        digits_in_id: 10,
+        overpayment_tolerance: 0,
+        overpayment_tolerance_percent: 0,
+        refunds_enabled: true,
+        refund_processing_fee: 1_000_000,
+        processing_attempts: 2,
+        ..ForcedExitRequestsConfig::from_env()
+    }
+}
+
+pub(super) fn refundable_payment(
+    amount: &str,
+    request_id: Option<ForcedExitRequestId>,
+    eth_tx_hash: H256,
+) -> FundsReceivedEvent {
+    FundsReceivedEvent {
+        eth_tx_hash: Some(eth_tx_hash),
+        payer: Some(Address::repeat_byte(0x33)),
+        ..payment(amount, request_id)
+    }
+}
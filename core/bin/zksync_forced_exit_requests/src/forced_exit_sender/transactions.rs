@@ -0,0 +1,569 @@
+//! Building and signing the `ForcedExit`, fee payment and refund transactions.
+
+use chrono::{DateTime, Utc};
+use num::{BigUint, Zero};
+
+use zksync_api::api_server::forced_exit_preflight::{
+    blocked_preflight, blocker_before_target, plan_forced_exits, skip_tokens, target_blocker,
+    with_fee_payment,
+};
+use zksync_crypto::params::max_fungible_token_id;
+use zksync_types::{
+    forced_exit_requests::{
+        ForcedExitPreflight, ForcedExitRefund, ForcedExitRequest, ForcedExitTokenSkipReason,
+        PlannedFeePayment, PlannedForcedExit, SkippedForcedExit,
+    },
+    tx::TimeRange,
+    Address, ForcedExit, Nonce, SignedZkSyncTx, TokenId, Transfer,
+};
+
+use crate::{
+    core_interaction_wrapper::CoreInteractionWrapper, sender_accounts::SenderAccount,
+    signer::eth_sign_data,
+};
+
+use super::{MempoolForcedExitSender, NothingToExit};
+
+impl<T: CoreInteractionWrapper + Sync> MempoolForcedExitSender<T> {
+    /// The `ForcedExit` signed at `now` is executed until the ttl passes, so the one stuck
+    /// in the mempool fails instead of being executed once the target may have become active.
+    /// The ttl is checked with the config, the time range out of range fails the batch
+    /// instead of the sender.
+    pub(super) fn forced_exit_time_range(&self, now: DateTime<Utc>) -> anyhow::Result<TimeRange> {
+        let valid_until = chrono::Duration::from_std(self.config.forced_exit_tx_ttl())
+            .ok()
+            .and_then(|ttl| now.checked_add_signed(ttl))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "The ttl of the ForcedExit transactions {}ms is out of range",
+                    self.config.forced_exit_tx_ttl
+                )
+            })?;
+        Ok(TimeRange::new(
+            now.timestamp() as u64,
+            valid_until.timestamp() as u64,
+        ))
+    }
+
+    /// Signs the `ForcedExit` with the signer of the account, see the `signer` module,
+    /// and with the Ethereum private key of the account if it has one.
+    pub async fn build_forced_exit(
+        &self,
+        account: &SenderAccount,
+        target: Address,
+        planned: &PlannedForcedExit,
+        time_range: TimeRange,
+    ) -> anyhow::Result<SignedZkSyncTx> {
+        let tx = ForcedExit::new(
+            account.account_id,
+            target,
+            planned.token,
+            planned.fee.clone(),
+            planned.nonce,
+            time_range,
+            None,
+        );
+        // The message is built before the transaction is moved into the signer
+        let eth_message = match account.eth_private_key {
+            Some(private_key) => {
+                let token = self
+                    .core_interaction_wrapper
+                    .get_token(planned.token)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("Unknown token {}", planned.token))?;
+                Some((
+                    private_key,
+                    tx.get_ethereum_sign_message(&token.symbol, token.decimals),
+                ))
+            }
+            None => None,
+        };
+        let mut signed = account.signer.sign_forced_exit(tx).await?;
+        if let Some((private_key, message)) = eth_message {
+            signed.eth_sign_data = Some(eth_sign_data(&private_key, message)?);
+        }
+        Ok(signed)
+    }
+
+    /// Signs the transfer returning the payment to the payer.
+    pub async fn build_refund(
+        &self,
+        refund: &ForcedExitRefund,
+        nonce: Nonce,
+    ) -> anyhow::Result<SignedZkSyncTx> {
+        // The transactions of the sender account are free, the fee is kept by deducting
+        // it from the refunded amount instead
+        let account = self.sender_accounts.main();
+        let tx = Transfer::new(
+            account.account_id,
+            account.address,
+            refund.recipient,
+            TokenId(0),
+            refund.amount.clone(),
+            BigUint::zero(),
+            nonce,
+            TimeRange::default(),
+            None,
+        );
+        account.signer.sign_transfer(tx).await
+    }
+
+    /// Signs the transfer of the account to itself paying the fee of the batch.
+    pub async fn build_fee_payment(
+        &self,
+        account: &SenderAccount,
+        planned: &PlannedFeePayment,
+    ) -> anyhow::Result<SignedZkSyncTx> {
+        let tx = Transfer::new(
+            account.account_id,
+            account.address,
+            account.address,
+            planned.token,
+            BigUint::zero(),
+            planned.fee.clone(),
+            planned.nonce,
+            TimeRange::default(),
+            None,
+        );
+        account.signer.sign_transfer(tx).await
+    }
+
+    /// Signs the transactions planned by the preflight of the request with the account
+    /// the request is sent from, so all of them are sent in one batch.
+    pub async fn build_transactions(
+        &self,
+        account: &SenderAccount,
+        fe_request: &ForcedExitRequest,
+        preflight: &ForcedExitPreflight,
+    ) -> anyhow::Result<Vec<SignedZkSyncTx>> {
+        if preflight.transactions.is_empty() {
+            return Err(NothingToExit {
+                request_id: fe_request.id,
+            }
+            .into());
+        }
+        let time_range = self.forced_exit_time_range((self.clock)())?;
+        let mut txs = Vec::with_capacity(preflight.transactions.len());
+        for planned in &preflight.transactions {
+            txs.push(
+                self.build_forced_exit(account, fe_request.target, planned, time_range)
+                    .await?,
+            );
+        }
+        if let Some(planned) = &preflight.fee_payment {
+            txs.push(self.build_fee_payment(account, planned).await?);
+        }
+        Ok(txs)
+    }
+
+    /// Evaluates what fulfilling the paid request would do at the given time.
+    ///
+    /// Nothing is written and no transactions are signed, so the evaluation can be
+    /// repeated freely. The nonces are not reserved either, they are only valid
+    /// until the sender sends another transaction.
+    pub async fn preflight(
+        &self,
+        request: &ForcedExitRequest,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<ForcedExitPreflight> {
+        // Nothing could have been escalated without the support of the backend
+        let escalated = self.core_interaction_wrapper.capabilities().escalations
+            && self
+                .core_interaction_wrapper
+                .get_escalation(request.id)
+                .await?
+                .is_some();
+        if let Some(blocker) = blocker_before_target(request, escalated, now) {
+            return Ok(blocked_preflight(request, blocker, None));
+        }
+
+        let target = self
+            .core_interaction_wrapper
+            .check_forced_exit_request(request)
+            .await?;
+        if let Some(blocker) = target_blocker(&target) {
+            return Ok(blocked_preflight(request, blocker, Some(target)));
+        }
+        // The `ForcedExit` of the NFT would be rejected along with the whole batch,
+        // while the fungible tokens of the request can still be withdrawn
+        let mut skipped: Vec<_> = request
+            .tokens
+            .iter()
+            .filter(|token| **token > max_fungible_token_id())
+            .map(|token| SkippedForcedExit {
+                token: *token,
+                reason: ForcedExitTokenSkipReason::Nft,
+            })
+            .collect();
+        // The transactions for the unknown tokens would be rejected by the server
+        if self.core_interaction_wrapper.capabilities().tokens {
+            let mut tokens = self.token_cache();
+            let mut addresses = Vec::with_capacity(request.tokens.len());
+            for token in request
+                .tokens
+                .iter()
+                .filter(|token| **token <= max_fungible_token_id())
+            {
+                let address = tokens
+                    .token_address(&self.core_interaction_wrapper, *token)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("Token {} not found", token))?;
+                addresses.push((*token, address));
+            }
+            if let Some(l1_transfer_check) = &self.l1_transfer_check {
+                skipped.extend(
+                    l1_transfer_check
+                        .restricted_tokens(&addresses, request.target)
+                        .await
+                        .into_iter()
+                        .map(|token| SkippedForcedExit {
+                            token,
+                            reason: ForcedExitTokenSkipReason::L1TransferRestricted,
+                        }),
+                );
+            }
+        }
+        // The transactions are planned for the main account, they are moved to the nonces
+        // of the account they are sent from once it is picked
+        let sender_nonce = self.next_nonce(self.sender_accounts.main()).await?;
+        let preflight = skip_tokens(plan_forced_exits(request, target, sender_nonce), skipped);
+
+        match self.config.fee_token {
+            Some(fee_token) if !preflight.transactions.is_empty() => {
+                let fee = self
+                    .quote_batch_fee(request, preflight.transactions.len(), fee_token)
+                    .await?;
+                Ok(with_fee_payment(preflight, fee_token, fee))
+            }
+            _ => Ok(preflight),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+
+    use zksync_config::ForcedExitRequestsConfig;
+    use zksync_types::{
+        forced_exit_requests::{
+            ForcedExitBlocker, ForcedExitRequestEscalation, ForcedExitTargetCheck,
+            PaymentMatchScheme,
+        },
+        tx::{PackedEthSignature, TxEthSignature},
+        ZkSyncTx, H256,
+    };
+
+    use crate::{
+        forced_exit_sender::{
+            test_utils::{
+                get_stored_request, get_test_forced_exit_sender, get_test_request, payment,
+                sent_txs_count,
+            },
+            PaymentDecision,
+        },
+        test::add_request,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn nfts_are_skipped_by_the_preflight() {
+        let mut forced_exit_sender = get_test_forced_exit_sender(None);
+        let nft = TokenId(*max_fungible_token_id() + 1);
+        let request = ForcedExitRequest {
+            tokens: vec![TokenId(1), nft],
+            ..get_test_request(12, "10000000000")
+        };
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            request.clone(),
+        );
+
+        let preflight = forced_exit_sender
+            .preflight(&request, Utc::now())
+            .await
+            .unwrap();
+        let skipped_nft = SkippedForcedExit {
+            token: nft,
+            reason: ForcedExitTokenSkipReason::Nft,
+        };
+        assert_eq!(preflight.skipped, vec![skipped_nft]);
+        assert_eq!(preflight.transactions.len(), 1);
+        assert_eq!(preflight.transactions[0].token, TokenId(1));
+
+        // The fungible token is still withdrawn
+        let paid = request.price_in_wei.clone();
+        let decision = forced_exit_sender
+            .fulfill(
+                request,
+                &preflight,
+                PaymentMatchScheme::ExplicitId,
+                &paid,
+                None,
+                Vec::new(),
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            decision,
+            PaymentDecision::Fulfilled { tokens, .. } if tokens == vec![TokenId(1)]
+        ));
+        assert_eq!(sent_txs_count(&forced_exit_sender), 1);
+        assert_eq!(
+            *forced_exit_sender
+                .core_interaction_wrapper
+                .skipped_tokens
+                .lock()
+                .unwrap(),
+            vec![(12, skipped_nft)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forced_exit_sender_preflight() {
+        let forced_exit_requests = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(forced_exit_requests));
+        forced_exit_sender.core_interaction_wrapper.nonce = Nonce(5);
+
+        let request = ForcedExitRequest {
+            tokens: vec![TokenId(1), TokenId(3)],
+            ..get_test_request(12, "10000000000")
+        };
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            request.clone(),
+        );
+
+        // The planned transactions are exactly the ones sent afterwards
+        let preflight = forced_exit_sender
+            .preflight(&request, Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(preflight.blocker, None);
+        let decision = forced_exit_sender
+            .process_payment(payment("10000000012", None), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(
+            decision,
+            PaymentDecision::Fulfilled {
+                request_id: 12,
+                match_scheme: PaymentMatchScheme::AmountDigits,
+                tokens: request.tokens.clone(),
+            }
+        );
+        let sent: Vec<_> = forced_exit_sender
+            .core_interaction_wrapper
+            .sent_txs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|tx| match &tx.tx {
+                ZkSyncTx::ForcedExit(tx) => PlannedForcedExit {
+                    token: tx.token,
+                    nonce: tx.nonce,
+                    fee: tx.fee.clone(),
+                },
+                _ => panic!("Only ForcedExit transactions are sent"),
+            })
+            .collect();
+        assert_eq!(sent, preflight.transactions);
+        assert_eq!(sent[0].nonce, Nonce(5));
+        assert_eq!(sent[1].nonce, Nonce(6));
+
+        let stored_request = get_stored_request(&forced_exit_sender, 12);
+        let preflight = forced_exit_sender
+            .preflight(&stored_request, Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(preflight.blocker, Some(ForcedExitBlocker::Fulfilled));
+        assert!(preflight.transactions.is_empty());
+
+        // The expired request is not matched with the payment at all
+        let request = get_test_request(13, "10000000000");
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            request.clone(),
+        );
+        let preflight = forced_exit_sender
+            .preflight(&request, request.valid_until)
+            .await
+            .unwrap();
+        assert_eq!(preflight.blocker, Some(ForcedExitBlocker::Expired));
+        let decision = forced_exit_sender
+            .process_payment(payment("10000000013", None), request.valid_until)
+            .await
+            .unwrap();
+        assert!(matches!(decision, PaymentDecision::Unmatched { .. }));
+
+        // The escalated request is left to the operators
+        let request = get_test_request(14, "10000000000");
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            request.clone(),
+        );
+        forced_exit_sender
+            .core_interaction_wrapper
+            .store_escalation(ForcedExitRequestEscalation {
+                request_id: 14,
+                full_exits: vec![],
+                created_at: Utc::now(),
+                l1_tx_hash: None,
+                finalized_at: None,
+            })
+            .await
+            .unwrap();
+        let preflight = forced_exit_sender
+            .preflight(&request, Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(preflight.blocker, Some(ForcedExitBlocker::Escalated));
+        let decision = forced_exit_sender
+            .process_payment(payment("10000000014", None), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(decision, PaymentDecision::Escalated { request_id: 14 });
+
+        // The target which is not old enough can not be forced to exit
+        forced_exit_sender.core_interaction_wrapper.target_check = ForcedExitTargetCheck {
+            old_enough: false,
+            nonce: Some(Nonce(0)),
+        };
+        let request = get_test_request(15, "10000000000");
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            request.clone(),
+        );
+        let preflight = forced_exit_sender
+            .preflight(&request, Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(preflight.blocker, Some(ForcedExitBlocker::NotPossible));
+        let decision = forced_exit_sender
+            .process_payment(payment("10000000015", None), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(decision, PaymentDecision::NotPossible { request_id: 15 });
+
+        assert_eq!(sent_txs_count(&forced_exit_sender), 2);
+    }
+
+    #[tokio::test]
+    async fn forced_exits_expire_after_ttl() {
+        let config = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            forced_exit_tx_ttl: 300_000,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mut forced_exit_sender = get_test_forced_exit_sender(Some(config));
+        add_request(
+            &forced_exit_sender.core_interaction_wrapper.requests,
+            ForcedExitRequest {
+                tokens: vec![TokenId(1), TokenId(3)],
+                ..get_test_request(12, "10000000000")
+            },
+        );
+
+        forced_exit_sender.clock = || Utc.timestamp(1_600_000_000, 0);
+        forced_exit_sender
+            .process_payment(payment("10000000012", None), Utc::now())
+            .await
+            .unwrap();
+
+        let sent_txs = forced_exit_sender
+            .core_interaction_wrapper
+            .lock_sent_txs()
+            .clone();
+        assert_eq!(sent_txs.len(), 2);
+        for tx in &sent_txs {
+            match &tx.tx {
+                ZkSyncTx::ForcedExit(forced_exit) => {
+                    let time_range = forced_exit.time_range.unwrap();
+                    assert_eq!(time_range.valid_from, 1_600_000_000);
+                    assert_eq!(time_range.valid_until, 1_600_000_300);
+                }
+                _ => panic!("Only the ForcedExit transactions are sent"),
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_range_ttl_is_an_error() {
+        let config = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            forced_exit_tx_ttl: u64::MAX,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        assert!(config.validate().is_err());
+
+        // The sender given the config anyway fails the batch instead of panicking
+        let forced_exit_sender = get_test_forced_exit_sender(Some(config));
+        assert!(forced_exit_sender
+            .forced_exit_time_range(Utc.timestamp(1_600_000_000, 0))
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn forced_exits_are_eth_signed_with_configured_key() {
+        let eth_private_key = H256::repeat_byte(0x11);
+        let sender_address =
+            PackedEthSignature::address_from_private_key(&eth_private_key).unwrap();
+        let config = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            sender_eth_private_key: eth_private_key,
+            sender_account_address: sender_address,
+            eth_sign_forced_exits: true,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        config.validate().unwrap();
+
+        for eth_signed in [true, false] {
+            let mut forced_exit_sender =
+                get_test_forced_exit_sender(Some(ForcedExitRequestsConfig {
+                    eth_sign_forced_exits: eth_signed,
+                    ..config.clone()
+                }));
+            add_request(
+                &forced_exit_sender.core_interaction_wrapper.requests,
+                get_test_request(12, "10000000000"),
+            );
+            forced_exit_sender
+                .process_payment(payment("10000000012", None), Utc::now())
+                .await
+                .unwrap();
+
+            let sent_txs = forced_exit_sender
+                .core_interaction_wrapper
+                .lock_sent_txs()
+                .clone();
+            assert_eq!(sent_txs.len(), 1);
+            if !eth_signed {
+                assert!(sent_txs[0].eth_sign_data.is_none());
+                continue;
+            }
+            let eth_sign_data = sent_txs[0].eth_sign_data.as_ref().unwrap();
+            let forced_exit = match &sent_txs[0].tx {
+                ZkSyncTx::ForcedExit(forced_exit) => forced_exit,
+                tx => panic!("Unexpected transaction {:?}", tx),
+            };
+            assert_eq!(
+                eth_sign_data.message,
+                forced_exit
+                    .get_ethereum_sign_message("TKN1", 18)
+                    .into_bytes()
+            );
+            let signer = match &eth_sign_data.signature {
+                TxEthSignature::EthereumSignature(signature) => signature
+                    .signature_recover_signer(&eth_sign_data.message)
+                    .unwrap(),
+                signature => panic!("Unexpected signature {:?}", signature),
+            };
+            assert_eq!(signer, sender_address);
+        }
+    }
+}
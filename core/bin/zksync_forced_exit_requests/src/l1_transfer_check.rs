@@ -0,0 +1,229 @@
+//! Some tokens can pause their transfers or blacklist the addresses on L1. The `ForcedExit`
+//! of such a token succeeds on L2, but the withdrawal to the target is stuck on L1 afterwards,
+//! so the token contracts are asked about the target before the transactions are sent.
+//!
+//! The check is best-effort: the contracts are asked through the view functions of the
+//! well-known tokens, and the ones which do not implement them or do not answer in time
+//! are considered unrestricted.
+
+use std::time::Duration;
+
+use ethabi::{ParamType, Token};
+use futures::future::join_all;
+use tokio::time;
+use web3::{
+    transports::Http,
+    types::{Bytes, CallRequest},
+    Web3,
+};
+
+use zksync_config::ForcedExitRequestsConfig;
+use zksync_types::{Address, TokenId};
+
+/// The view functions returning `true` if the transfers are restricted, the ones
+/// taking an address are asked about the target.
+const RESTRICTIONS: [(&str, bool); 3] = [
+    // Pausable tokens, e.g. USDT and USDC
+    ("paused", false),
+    // USDT
+    ("isBlackListed", true),
+    // USDC
+    ("isBlacklisted", true),
+];
+
+#[derive(Debug, Clone)]
+pub struct L1TransferCheck {
+    web3: Web3<Http>,
+    timeout: Duration,
+}
+
+impl L1TransferCheck {
+    pub fn new(web3_url: &str, timeout: Duration) -> Self {
+        let transport = Http::new(web3_url).expect("Invalid L1 transfer check web3 URL");
+        Self {
+            web3: Web3::new(transport),
+            timeout,
+        }
+    }
+
+    /// The tokens are only checked if the node to ask is configured.
+    pub fn from_config(config: &ForcedExitRequestsConfig) -> Option<Self> {
+        config
+            .l1_transfer_check_web3_url
+            .as_deref()
+            .map(|web3_url| Self::new(web3_url, config.l1_transfer_check_timeout()))
+    }
+
+    /// Returns the tokens, the transfers of which to the target are known to be restricted.
+    /// The tokens are given along with the addresses of their contracts on L1.
+    pub async fn restricted_tokens(
+        &self,
+        tokens: &[(TokenId, Address)],
+        target: Address,
+    ) -> Vec<TokenId> {
+        // The tokens are checked concurrently, so the slow one does not hold up the rest
+        let checks = tokens.iter().map(|(token, address)| async move {
+            match time::timeout(self.timeout, self.is_restricted(*address, target)).await {
+                Ok(restricted) => (*token, restricted),
+                Err(_) => {
+                    vlog::warn!(
+                        "The L1 transfer restrictions of the token {} were not checked in time",
+                        token
+                    );
+                    metrics::increment_counter!("forced_exit_requests.l1_transfer_check_timeouts");
+                    (*token, false)
+                }
+            }
+        });
+
+        join_all(checks)
+            .await
+            .into_iter()
+            .filter(|(_, restricted)| *restricted)
+            .map(|(token, _)| token)
+            .collect()
+    }
+
+    async fn is_restricted(&self, token_address: Address, target: Address) -> bool {
+        let queries = RESTRICTIONS.iter().map(|(function, takes_address)| {
+            self.query(token_address, function, *takes_address, target)
+        });
+        join_all(queries)
+            .await
+            .into_iter()
+            .any(|restricted| restricted)
+    }
+
+    // The function not implemented by the contract reverts, which is no restriction either
+    async fn query(
+        &self,
+        token_address: Address,
+        function: &str,
+        takes_address: bool,
+        target: Address,
+    ) -> bool {
+        let (params, args) = if takes_address {
+            (vec![ParamType::Address], vec![Token::Address(target)])
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        let mut data = ethabi::short_signature(function, &params).to_vec();
+        data.extend(ethabi::encode(&args));
+
+        let request = CallRequest {
+            to: Some(token_address),
+            data: Some(Bytes(data)),
+            ..Default::default()
+        };
+        match self.web3.eth().call(request, None).await {
+            Ok(output) => matches!(
+                ethabi::decode(&[ParamType::Bool], &output.0).as_deref(),
+                Ok([Token::Bool(true)])
+            ),
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{web, App};
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    const PAUSED_TOKEN: Address = Address::repeat_byte(0x01);
+    const BLACKLISTING_TOKEN: Address = Address::repeat_byte(0x02);
+    const HEALTHY_TOKEN: Address = Address::repeat_byte(0x03);
+    const UNRESPONSIVE_TOKEN: Address = Address::repeat_byte(0x04);
+    const BLACKLISTED_TARGET: Address = Address::repeat_byte(0x12);
+    const TIMEOUT: Duration = Duration::from_millis(500);
+
+    fn selector(function: &str, takes_address: bool) -> String {
+        let params = if takes_address {
+            vec![ParamType::Address]
+        } else {
+            Vec::new()
+        };
+        hex::encode(ethabi::short_signature(function, &params))
+    }
+
+    fn encoded_bool(value: bool) -> Value {
+        json!(format!(
+            "0x{}",
+            hex::encode(ethabi::encode(&[Token::Bool(value)]))
+        ))
+    }
+
+    /// Answers the calls the way the tokens would, the functions a token does not have revert.
+    async fn eth_call(request: web::Json<Value>) -> web::Json<Value> {
+        let call = &request["params"][0];
+        let token: Address = serde_json::from_value(call["to"].clone()).unwrap();
+        let data = call["data"].as_str().unwrap().trim_start_matches("0x");
+        let (function, args) = data.split_at(8);
+        let asks_about = |target: Address| args.ends_with(&hex::encode(target));
+
+        let result = if token == PAUSED_TOKEN && function == selector("paused", false) {
+            Some(true)
+        } else if token == BLACKLISTING_TOKEN && function == selector("paused", false) {
+            Some(false)
+        } else if token == BLACKLISTING_TOKEN && function == selector("isBlackListed", true) {
+            Some(asks_about(BLACKLISTED_TARGET))
+        } else if token == HEALTHY_TOKEN && function == selector("paused", false) {
+            Some(false)
+        } else if token == HEALTHY_TOKEN && function == selector("isBlacklisted", true) {
+            Some(false)
+        } else if token == UNRESPONSIVE_TOKEN {
+            time::sleep(TIMEOUT * 4).await;
+            Some(true)
+        } else {
+            None
+        };
+        let response = match result {
+            Some(value) => {
+                json!({ "jsonrpc": "2.0", "id": request["id"], "result": encoded_bool(value) })
+            }
+            None => json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "error": { "code": -32000, "message": "execution reverted" }
+            }),
+        };
+        web::Json(response)
+    }
+
+    #[actix_rt::test]
+    async fn restricted_tokens_are_reported() {
+        let server = actix_test::start(|| App::new().route("/", web::post().to(eth_call)));
+        let check = L1TransferCheck::new(&server.url("/"), TIMEOUT);
+        let tokens = [
+            (TokenId(1), PAUSED_TOKEN),
+            (TokenId(2), BLACKLISTING_TOKEN),
+            (TokenId(3), HEALTHY_TOKEN),
+            (TokenId(4), UNRESPONSIVE_TOKEN),
+            // Does not implement any of the functions
+            (TokenId(5), Address::repeat_byte(0x05)),
+        ];
+
+        // The token which has not answered in time is not known to be restricted
+        assert_eq!(
+            check.restricted_tokens(&tokens, BLACKLISTED_TARGET).await,
+            vec![TokenId(1), TokenId(2)]
+        );
+        assert_eq!(
+            check
+                .restricted_tokens(&tokens, Address::repeat_byte(0x13))
+                .await,
+            vec![TokenId(1)]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn unreachable_node_restricts_nothing() {
+        let check = L1TransferCheck::new("http://127.0.0.1:1", TIMEOUT);
+        assert!(check
+            .restricted_tokens(&[(TokenId(1), PAUSED_TOKEN)], BLACKLISTED_TARGET)
+            .await
+            .is_empty());
+    }
+}
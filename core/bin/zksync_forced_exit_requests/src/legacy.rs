@@ -0,0 +1,178 @@
+//! Upgrade of the requests stored by the servers preceding the schema changes.
+//!
+//! The storage reads such requests with the missing values computed on the fly, so they
+//! are processed the same way whether they are upgraded or not. The upgrade only makes
+//! the stored rows complete: it runs once the server starts, in small batches so that no
+//! request is locked for long, and the rows left by the interrupted upgrade are simply
+//! picked up by the next one.
+//!
+//! The transactions sent for the requests are moved from the `fulfilled_by` column to the
+//! fulfillments the same way. Until the column is deprecated, both are written and compared
+//! by the checker, which reports the requests they differ for.
+
+use std::time::Duration;
+
+use tokio::{task::JoinHandle, time};
+use zksync_storage::ConnectionPool;
+
+use crate::spawner::ForcedExitSpawner;
+
+const UPGRADE_BATCH_SIZE: u32 = 100;
+const CONSISTENCY_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// The mismatches are counted up to this number, the first few of them are logged.
+const MISMATCHES_LIMIT: u32 = 100;
+const LOGGED_MISMATCHES: usize = 5;
+
+/// Upgrades the legacy requests batch by batch, returns the number of the upgraded ones.
+pub async fn upgrade_legacy_requests(
+    connection_pool: &ConnectionPool,
+    batch_size: u32,
+) -> anyhow::Result<u64> {
+    let mut storage = connection_pool.access_storage().await?;
+    let mut fe_schema = storage.forced_exit_requests_schema();
+
+    let mut remaining = fe_schema.count_legacy_requests().await?;
+    metrics::gauge!("forced_exit_requests.legacy_requests", remaining as f64);
+    if remaining == 0 {
+        return Ok(0);
+    }
+    vlog::info!("Upgrading {} legacy ForcedExit requests", remaining);
+
+    let mut upgraded = 0;
+    loop {
+        let batch = u64::from(fe_schema.upgrade_legacy_requests(batch_size).await?);
+        if batch == 0 {
+            break;
+        }
+        upgraded += batch;
+        remaining = remaining.saturating_sub(batch);
+        metrics::gauge!("forced_exit_requests.legacy_requests", remaining as f64);
+        vlog::info!(
+            "{} legacy ForcedExit requests are upgraded, about {} left",
+            upgraded,
+            remaining
+        );
+    }
+
+    // The servers of the previous version may still be running and storing the legacy
+    // requests, as well as some of the rows may have been locked by the transitions
+    let remaining = fe_schema.count_legacy_requests().await?;
+    metrics::gauge!("forced_exit_requests.legacy_requests", remaining as f64);
+    if remaining > 0 {
+        vlog::warn!(
+            "{} legacy ForcedExit requests are left to be upgraded on the next start",
+            remaining
+        );
+    }
+    Ok(upgraded)
+}
+
+/// Stores the fulfillments of the requests sent by the servers preceding them batch by batch,
+/// returns the number of such requests.
+pub async fn backfill_fulfillments(
+    connection_pool: &ConnectionPool,
+    batch_size: u32,
+) -> anyhow::Result<u64> {
+    let mut storage = connection_pool.access_storage().await?;
+    let mut fe_schema = storage.forced_exit_requests_schema();
+
+    let remaining = fe_schema.count_unbackfilled_fulfillments().await?;
+    metrics::gauge!(
+        "forced_exit_requests.unbackfilled_fulfillments",
+        remaining as f64
+    );
+    if remaining == 0 {
+        return Ok(0);
+    }
+    vlog::info!(
+        "Backfilling the fulfillments of {} ForcedExit requests",
+        remaining
+    );
+
+    let mut backfilled = 0;
+    loop {
+        let batch = u64::from(fe_schema.backfill_fulfillments(batch_size).await?);
+        if batch == 0 {
+            break;
+        }
+        backfilled += batch;
+        metrics::gauge!(
+            "forced_exit_requests.unbackfilled_fulfillments",
+            remaining.saturating_sub(backfilled) as f64
+        );
+    }
+
+    // The requests sent by the servers of the previous version in the meantime
+    // are picked up on the next start
+    let remaining = fe_schema.count_unbackfilled_fulfillments().await?;
+    metrics::gauge!(
+        "forced_exit_requests.unbackfilled_fulfillments",
+        remaining as f64
+    );
+    vlog::info!(
+        "The fulfillments of {} ForcedExit requests are backfilled, {} left",
+        backfilled,
+        remaining
+    );
+    Ok(backfilled)
+}
+
+/// Starts the upgrade in the background. Unlike the actors of the component, the upgrade
+/// finishes once it is done, so it is not among the tasks expected to run forever.
+pub fn run_legacy_upgrade(spawner: &ForcedExitSpawner, connection_pool: ConnectionPool) {
+    spawner.spawn(async move {
+        if let Err(err) = upgrade_legacy_requests(&connection_pool, UPGRADE_BATCH_SIZE).await {
+            vlog::error!("Failed to upgrade the legacy ForcedExit requests: {}", err);
+        }
+        if let Err(err) = backfill_fulfillments(&connection_pool, UPGRADE_BATCH_SIZE).await {
+            vlog::error!(
+                "Failed to backfill the fulfillments of the ForcedExit requests: {}",
+                err
+            );
+        }
+    });
+}
+
+/// Compares the `fulfilled_by` column with the fulfillments, returns the number
+/// of the requests they differ for (up to `MISMATCHES_LIMIT`).
+pub async fn check_fulfillments(connection_pool: &ConnectionPool) -> anyhow::Result<usize> {
+    let mut storage = connection_pool.access_storage().await?;
+    let mismatches = storage
+        .forced_exit_requests_schema()
+        .load_fulfillment_mismatches(MISMATCHES_LIMIT)
+        .await?;
+
+    metrics::gauge!(
+        "forced_exit_requests.fulfillment_mismatches",
+        mismatches.len() as f64
+    );
+    for mismatch in mismatches.iter().take(LOGGED_MISMATCHES) {
+        vlog::warn!(
+            "The transactions of ForcedExit request {} differ: {:?} in `fulfilled_by`, {:?} in the fulfillments",
+            mismatch.request_id,
+            mismatch.legacy,
+            mismatch.fulfillments
+        );
+    }
+    Ok(mismatches.len())
+}
+
+/// Runs the checker while both the `fulfilled_by` column and the fulfillments are written.
+/// The requests sent by the servers preceding the fulfillments are reported until backfilled.
+pub fn run_fulfillments_checker(
+    spawner: &ForcedExitSpawner,
+    connection_pool: ConnectionPool,
+) -> JoinHandle<()> {
+    spawner.spawn(async move {
+        let mut timer = time::interval(CONSISTENCY_CHECK_INTERVAL);
+        loop {
+            timer.tick().await;
+            if let Err(err) = check_fulfillments(&connection_pool).await {
+                vlog::warn!(
+                    "Failed to compare the fulfillments of the ForcedExit requests: {}",
+                    err
+                );
+            }
+        }
+    })
+}
@@ -3,34 +3,123 @@ use tokio::task::JoinHandle;
 use zksync_config::{ContractsConfig, ForcedExitRequestsConfig};
 use zksync_storage::ConnectionPool;
 
+use core_interaction_wrapper::MempoolCoreInteractionWrapper;
 use forced_exit_sender::ForcedExitSender;
+use outbox::WebhookSink;
+use shutdown::ShutdownSignal;
+use singleton::SingletonLock;
+use spawner::ForcedExitSpawner;
 use zksync_config::configs::api::CommonApiConfig;
 use zksync_mempool::MempoolTransactionRequest;
 
+pub mod client;
+pub mod config_history;
+pub mod consistency;
 mod core_interaction_wrapper;
+mod db_pools;
 pub mod eth_watch;
+pub mod expiration;
 pub mod forced_exit_sender;
+pub mod l1_transfer_check;
+pub mod legacy;
+pub mod metrics;
+pub mod outbox;
+pub mod pacing;
+pub mod payment_events;
 pub mod prepare_forced_exit_sender;
+pub mod receipt_poller;
+pub mod refund_budget;
+pub mod remote;
+pub mod replay;
+pub mod sender_accounts;
+pub mod shutdown;
+pub mod signer;
+pub mod singleton;
+pub mod spawner;
+pub mod token_cache;
+pub mod token_labels;
 mod utils;
+pub mod worker_pool;
 
 #[cfg(test)]
 pub mod test;
 
 #[must_use]
 pub fn run_forced_exit_requests_actors(
+    spawner: &ForcedExitSpawner,
     pool: ConnectionPool,
     sender: mpsc::Sender<MempoolTransactionRequest>,
     config: ForcedExitRequestsConfig,
     common: CommonApiConfig,
     contracts: ContractsConfig,
     web3_url: String,
-) -> JoinHandle<()> {
-    eth_watch::run_forced_exit_contract_watcher(
+    shutdown: ShutdownSignal,
+) -> Vec<JoinHandle<()>> {
+    let mut tasks = Vec::new();
+    // The notifications are delivered even if the feature is disabled,
+    // since the transitions could have happened before that
+    if let Some(webhook_url) = config.webhook_url.clone() {
+        let core_interaction_wrapper = MempoolCoreInteractionWrapper::new(
+            common.forced_exit_minimum_account_age_secs,
+            pool.clone(),
+            sender.clone(),
+        );
+        tasks.push(outbox::run_outbox_dispatcher(
+            spawner,
+            core_interaction_wrapper,
+            WebhookSink::new(webhook_url),
+        ));
+    }
+    // So are the callbacks, the requests created before could have them
+    tasks.push(outbox::run_callback_dispatcher(
+        spawner,
+        MempoolCoreInteractionWrapper::new(
+            common.forced_exit_minimum_account_age_secs,
+            pool.clone(),
+            sender.clone(),
+        ),
+        config.callback_max_attempts,
+    ));
+
+    // The config is recorded by the server, it prices the requests and advertises the payment address
+    config_history::run_config_recorder(spawner, pool.clone(), &config, contracts.forced_exit_addr);
+    // The rows are upgraded by the server, wherever the requests are processed
+    legacy::run_legacy_upgrade(spawner, pool.clone());
+    // The transactions are compared by the server as well, the remote component writes them via the API
+    if config.legacy_fulfilled_by_enabled {
+        tasks.push(legacy::run_fulfillments_checker(spawner, pool.clone()));
+    }
+    tasks.push(consistency::run_consistency_checker(spawner, pool.clone()));
+    tasks.push(expiration::run_expiration_sweeper(
+        spawner,
+        pool.clone(),
+        &config,
+    ));
+
+    // The payments are watched by the remote component then, see the `remote` module
+    if config.remote_api_url.is_some() {
+        vlog::info!("ForcedExit requests are fulfilled by the remote component");
+        return tasks;
+    }
+    // The disabled instance does not process anything, so it leaves the lock to the enabled ones
+    let singleton = if config.enabled {
+        let (guard, singleton) = singleton::run_singleton_guard(spawner, config.singleton_mode);
+        tasks.push(guard);
+        singleton
+    } else {
+        SingletonLock::unguarded()
+    };
+    tasks.push(eth_watch::run_forced_exit_contract_watcher(
+        spawner,
+        singleton,
         sender,
         pool,
         config,
         common.forced_exit_minimum_account_age_secs,
         contracts.forced_exit_addr,
+        contracts.contract_addr,
         web3_url,
-    )
+        shutdown,
+    ));
+    tasks
 }
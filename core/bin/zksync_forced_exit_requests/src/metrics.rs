@@ -0,0 +1,184 @@
+//! The metrics of the processing of the forced exit requests.
+//!
+//! Every processed payment is counted once, along with the outcome it has led to. The
+//! outcomes and their reasons are derived here, so they can be checked without a recorder.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use zksync_types::forced_exit_requests::{SubmissionError, SubmissionErrorKind};
+
+use crate::forced_exit_sender::{CommitError, PaymentDecision};
+
+pub const PROCESSED_REQUESTS: &str = "forced_exit_requests.processed_requests";
+pub const FULFILLED_REQUESTS: &str = "forced_exit_requests.fulfilled_requests";
+pub const REJECTED_REQUESTS: &str = "forced_exit_requests.rejected_requests";
+pub const FAILED_REQUESTS: &str = "forced_exit_requests.failed_requests";
+/// From the submission of the payment until the request is fulfilled.
+pub const FULFILLMENT_LATENCY: &str = "forced_exit_requests.fulfillment_latency";
+pub const COMMIT_WAIT: &str = "forced_exit_requests.commit_wait";
+pub const UNCONFIRMED_REQUESTS: &str = "forced_exit_requests.unconfirmed_requests";
+/// The requests sent before, found by every reconciliation.
+pub const RECONCILED_REQUESTS: &str = "forced_exit_requests.reconciled_requests";
+/// The requests released by the reconciliation to be sent again.
+pub const RECONCILIATION_CANCELLED_REQUESTS: &str =
+    "forced_exit_requests.reconciliation_cancelled_requests";
+
+/// The counter of the outcome the decision has led to and the reason of the outcome.
+/// The decisions which leave the request to be settled later are not counted.
+pub fn decision_outcome(decision: &PaymentDecision) -> Option<(&'static str, &'static str)> {
+    match decision {
+        PaymentDecision::Fulfilled { .. } => Some((FULFILLED_REQUESTS, "sent")),
+        PaymentDecision::NothingToExit { .. } => Some((FULFILLED_REQUESTS, "nothing_to_exit")),
+        PaymentDecision::Unmatched { .. } => Some((REJECTED_REQUESTS, "unmatched")),
+        PaymentDecision::NotPossible { .. } => Some((REJECTED_REQUESTS, "not_possible")),
+        PaymentDecision::TargetBecameActive { .. } => {
+            Some((REJECTED_REQUESTS, "target_became_active"))
+        }
+        PaymentDecision::Failed { .. } => Some((FAILED_REQUESTS, "attempts_exhausted")),
+        PaymentDecision::Escalated { .. }
+        | PaymentDecision::Deferred { .. }
+        | PaymentDecision::InFlight { .. } => None,
+    }
+}
+
+/// The reason the processing of the payment has failed with.
+pub fn failure_reason(err: &anyhow::Error) -> &'static str {
+    if let Some(SubmissionError { kind, .. }) = err.downcast_ref::<SubmissionError>() {
+        return match kind {
+            SubmissionErrorKind::Transport => "submission_transport",
+            SubmissionErrorKind::Http => "submission_http",
+            SubmissionErrorKind::Rejected => "submission_rejected",
+        };
+    }
+    match err.downcast_ref::<CommitError>() {
+        Some(CommitError::Failed { .. }) => "tx_failed",
+        Some(CommitError::Timeout { .. }) => "commit_timeout",
+        Some(CommitError::Interrupted { .. }) => "interrupted",
+        None => "other",
+    }
+}
+
+pub fn report_decision(decision: &PaymentDecision, submission_time: DateTime<Utc>) {
+    ::metrics::increment_counter!(PROCESSED_REQUESTS);
+    let (counter, reason) = match decision_outcome(decision) {
+        Some(outcome) => outcome,
+        None => return,
+    };
+    ::metrics::increment_counter!(counter, "reason" => reason);
+
+    if counter == FULFILLED_REQUESTS {
+        // The clocks of the watcher and of the sender may disagree a little
+        let latency = Utc::now()
+            .signed_duration_since(submission_time)
+            .to_std()
+            .unwrap_or_default();
+        ::metrics::histogram!(FULFILLMENT_LATENCY, latency);
+    }
+}
+
+pub fn report_failure(err: &anyhow::Error) {
+    ::metrics::increment_counter!(PROCESSED_REQUESTS);
+    ::metrics::increment_counter!(FAILED_REQUESTS, "reason" => failure_reason(err));
+}
+
+pub fn report_commit_wait(duration: Duration, outcome: &'static str) {
+    ::metrics::histogram!(COMMIT_WAIT, duration, "outcome" => outcome);
+}
+
+pub fn report_unconfirmed(count: usize) {
+    ::metrics::gauge!(UNCONFIRMED_REQUESTS, count as f64);
+}
+
+pub fn report_reconciliation(found: usize, cancelled: usize) {
+    ::metrics::histogram!(RECONCILED_REQUESTS, found as f64);
+    ::metrics::counter!(RECONCILIATION_CANCELLED_REQUESTS, cancelled as u64);
+}
+
+#[cfg(test)]
+mod tests {
+    use zksync_types::{forced_exit_requests::PaymentMatchScheme, tx::TxHash};
+
+    use super::*;
+
+    // Checks that only the settled decisions are counted as the outcomes
+    #[test]
+    fn decision_outcomes() {
+        let match_scheme = PaymentMatchScheme::AmountDigits;
+        let cases = vec![
+            (
+                PaymentDecision::Fulfilled {
+                    request_id: 1,
+                    match_scheme,
+                    tokens: vec![],
+                },
+                Some((FULFILLED_REQUESTS, "sent")),
+            ),
+            (
+                PaymentDecision::NothingToExit {
+                    request_id: 1,
+                    match_scheme,
+                },
+                Some((FULFILLED_REQUESTS, "nothing_to_exit")),
+            ),
+            (
+                PaymentDecision::Unmatched {
+                    request_id: 1,
+                    match_scheme,
+                },
+                Some((REJECTED_REQUESTS, "unmatched")),
+            ),
+            (
+                PaymentDecision::NotPossible { request_id: 1 },
+                Some((REJECTED_REQUESTS, "not_possible")),
+            ),
+            (
+                PaymentDecision::Failed {
+                    error: "error".to_string(),
+                },
+                Some((FAILED_REQUESTS, "attempts_exhausted")),
+            ),
+            (PaymentDecision::Escalated { request_id: 1 }, None),
+            (
+                PaymentDecision::Deferred {
+                    request_id: 1,
+                    dependency: "tokens".to_string(),
+                },
+                None,
+            ),
+            (
+                PaymentDecision::InFlight {
+                    request_id: 1,
+                    match_scheme,
+                },
+                None,
+            ),
+        ];
+
+        for (decision, expected) in cases {
+            assert_eq!(decision_outcome(&decision), expected, "{:?}", decision);
+        }
+    }
+
+    // Checks that the failures keep the reason of the underlying error
+    #[test]
+    fn failure_reasons() {
+        let rejected = SubmissionError::rejected(Some(101), "Invalid signature", false);
+        assert_eq!(
+            failure_reason(&anyhow::Error::from(rejected)),
+            "submission_rejected"
+        );
+        assert_eq!(
+            failure_reason(&SubmissionError::transport("connection refused").into()),
+            "submission_transport"
+        );
+
+        let failed = CommitError::Failed {
+            tx_hash: TxHash::from_slice(&[1; 32]).unwrap(),
+            reason: "Pathological account state".to_string(),
+        };
+        assert_eq!(failure_reason(&failed.into()), "tx_failed");
+        assert_eq!(failure_reason(&anyhow::anyhow!("database")), "other");
+    }
+}
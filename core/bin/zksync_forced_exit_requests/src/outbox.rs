@@ -0,0 +1,468 @@
+//! Delivery of the notifications about the status transitions of the requests.
+//!
+//! The notifications are written to the outbox by the storage within the same transactions
+//! as the transitions themselves, so none of them is lost if the server is stopped in between.
+//! The dispatcher marks a notification as delivered only after the receiver has accepted it,
+//! thus the notifications are delivered at least once and the receivers are expected to drop
+//! the duplicates by the `Idempotency-Key` header.
+//!
+//! The failed notifications are retried later, so the notifications of the same request
+//! may arrive out of order. Their `sequence` numbers tell the order of the transitions.
+//!
+//! The callbacks to the URLs supplied with the requests go through the outbox as well,
+//! but are dispatched by their own task, so the slow receivers of the callbacks do not
+//! hold the webhook up. Unlike the webhook notifications, the callbacks are given up on
+//! after `callback_max_attempts` failed attempts, until redelivered by the operators.
+
+use std::time::Duration;
+
+use chrono::Utc;
+use serde::Serialize;
+use tokio::{task::JoinHandle, time};
+
+use zksync_types::forced_exit_requests::{
+    ForcedExitRequestDelivery, ForcedExitRequestEvent, ForcedExitRequestId,
+};
+
+use crate::{core_interaction_wrapper::CoreInteractionWrapper, spawner::ForcedExitSpawner};
+
+/// The maximum number of notifications loaded from the outbox at once.
+const DELIVERIES_BATCH_SIZE: u32 = 100;
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+/// The callbacks are posted to the servers of the clients, which are not trusted to respond in time.
+const CALLBACK_CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const CALLBACK_TIMEOUT: Duration = Duration::from_secs(5);
+/// The delay before the first retry, it is doubled with every failed attempt.
+const MIN_RETRY_DELAY_SECS: i64 = 10;
+const MAX_RETRY_DELAY_SECS: i64 = 60 * 60;
+
+#[async_trait::async_trait]
+pub trait DeliverySink {
+    async fn deliver(&self, delivery: &ForcedExitRequestDelivery) -> anyhow::Result<()>;
+}
+
+/// Body of the webhook request.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Notification {
+    idempotency_key: String,
+    request_id: ForcedExitRequestId,
+    sequence: i64,
+    event: ForcedExitRequestEvent,
+    created_at: chrono::DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pipeline_version: Option<u32>,
+}
+
+/// Posts the notifications as JSON to the configured URL, any non-successful
+/// response is considered a failed attempt.
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookSink {
+    pub fn new(url: String) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .expect("Failed to create the webhook client");
+
+        Self { client, url }
+    }
+}
+
+#[async_trait::async_trait]
+impl DeliverySink for WebhookSink {
+    async fn deliver(&self, delivery: &ForcedExitRequestDelivery) -> anyhow::Result<()> {
+        let notification = Notification {
+            idempotency_key: delivery.idempotency_key(),
+            request_id: delivery.request_id,
+            sequence: delivery.sequence,
+            event: delivery.event,
+            created_at: delivery.created_at,
+            pipeline_version: delivery.pipeline_version,
+        };
+
+        self.client
+            .post(&self.url)
+            .header("Idempotency-Key", &notification.idempotency_key)
+            .json(&notification)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+/// Posts the callbacks as JSON to the URLs supplied with the requests. The redirects are
+/// not followed, so the callback is only posted to the URL checked when the request was created.
+pub struct CallbackSink {
+    client: reqwest::Client,
+}
+
+impl CallbackSink {
+    pub fn new() -> Self {
+        let client = reqwest::Client::builder()
+            .connect_timeout(CALLBACK_CONNECT_TIMEOUT)
+            .timeout(CALLBACK_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("Failed to create the callback client");
+
+        Self { client }
+    }
+}
+
+impl Default for CallbackSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait::async_trait]
+impl DeliverySink for CallbackSink {
+    async fn deliver(&self, delivery: &ForcedExitRequestDelivery) -> anyhow::Result<()> {
+        let (url, callback) = match (&delivery.callback_url, &delivery.callback) {
+            (Some(url), Some(callback)) => (url, callback),
+            _ => anyhow::bail!("Delivery {} is not a callback", delivery.id),
+        };
+
+        self.client
+            .post(url)
+            .header("Idempotency-Key", delivery.idempotency_key())
+            .json(callback)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+/// Returns the delay after the failed attempt, `attempts` is the number of
+/// the attempts made before the failed one.
+pub fn retry_delay(attempts: u32) -> chrono::Duration {
+    let delay = MIN_RETRY_DELAY_SECS.saturating_mul(1 << attempts.min(16));
+    chrono::Duration::seconds(delay.min(MAX_RETRY_DELAY_SECS))
+}
+
+pub struct OutboxDispatcher<T: CoreInteractionWrapper, S: DeliverySink> {
+    core_interaction_wrapper: T,
+    sink: S,
+    /// Whether the callbacks are dispatched rather than the webhook notifications.
+    callbacks: bool,
+    /// The notifications are attempted until delivered if not set.
+    max_attempts: Option<u32>,
+}
+
+impl<T: CoreInteractionWrapper, S: DeliverySink> OutboxDispatcher<T, S> {
+    pub fn new(core_interaction_wrapper: T, sink: S) -> Self {
+        Self {
+            core_interaction_wrapper,
+            sink,
+            callbacks: false,
+            max_attempts: None,
+        }
+    }
+
+    pub fn for_callbacks(core_interaction_wrapper: T, sink: S, max_attempts: u32) -> Self {
+        Self {
+            core_interaction_wrapper,
+            sink,
+            callbacks: true,
+            max_attempts: Some(max_attempts.max(1)),
+        }
+    }
+
+    /// Makes a single attempt to deliver the notifications that are due,
+    /// returns the number of the delivered ones.
+    pub async fn dispatch_once(&self) -> anyhow::Result<usize> {
+        let deliveries = self
+            .core_interaction_wrapper
+            .get_pending_deliveries(DELIVERIES_BATCH_SIZE, self.callbacks)
+            .await?;
+
+        let mut delivered = 0;
+        for delivery in deliveries {
+            match self.sink.deliver(&delivery).await {
+                Ok(()) => {
+                    self.core_interaction_wrapper
+                        .mark_delivered(delivery.id)
+                        .await?;
+                    delivered += 1;
+                }
+                Err(err) if self.is_last_attempt(&delivery) => {
+                    vlog::error!(
+                        "The callback {} for the ForcedExit request {} has failed {} times, giving up: {}",
+                        delivery.id,
+                        delivery.request_id,
+                        delivery.attempts + 1,
+                        err
+                    );
+                    metrics::increment_counter!("forced_exit_requests.failed_callbacks");
+                    self.core_interaction_wrapper
+                        .mark_delivery_failed(delivery.id, err.to_string())
+                        .await?;
+                }
+                Err(err) => {
+                    let next_attempt_at = Utc::now() + retry_delay(delivery.attempts);
+                    vlog::warn!(
+                        "Failed to deliver the {} notification for the ForcedExit request {}, attempt {}: {}",
+                        delivery.event,
+                        delivery.request_id,
+                        delivery.attempts + 1,
+                        err
+                    );
+                    self.core_interaction_wrapper
+                        .record_delivery_failure(delivery.id, err.to_string(), next_attempt_at)
+                        .await?;
+                }
+            }
+        }
+
+        Ok(delivered)
+    }
+
+    fn is_last_attempt(&self, delivery: &ForcedExitRequestDelivery) -> bool {
+        matches!(self.max_attempts, Some(max_attempts) if delivery.attempts + 1 >= max_attempts)
+    }
+
+    pub async fn run(self) {
+        let mut timer = time::interval(POLL_INTERVAL);
+        loop {
+            timer.tick().await;
+
+            if let Err(err) = self.dispatch_once().await {
+                vlog::error!("Failed to dispatch the ForcedExit notifications: {}", err);
+            }
+        }
+    }
+}
+
+pub fn run_outbox_dispatcher<T, S>(
+    spawner: &ForcedExitSpawner,
+    core_interaction_wrapper: T,
+    sink: S,
+) -> JoinHandle<()>
+where
+    T: CoreInteractionWrapper + Send + Sync + 'static,
+    S: DeliverySink + Send + Sync + 'static,
+{
+    let dispatcher = OutboxDispatcher::new(core_interaction_wrapper, sink);
+    spawner.spawn(dispatcher.run())
+}
+
+pub fn run_callback_dispatcher<T>(
+    spawner: &ForcedExitSpawner,
+    core_interaction_wrapper: T,
+    max_attempts: u32,
+) -> JoinHandle<()>
+where
+    T: CoreInteractionWrapper + Send + Sync + 'static,
+{
+    let dispatcher = OutboxDispatcher::for_callbacks(
+        core_interaction_wrapper,
+        CallbackSink::new(),
+        max_attempts,
+    );
+    spawner.spawn(dispatcher.run())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use zksync_types::forced_exit_requests::{
+        ForcedExitFulfilledCallback, ForcedExitRequestEscalation,
+    };
+
+    use super::*;
+    use crate::test::MockCoreInteractionWrapper;
+
+    /// Records every notification it receives, but fails the requested number of times
+    /// as if the response was lost.
+    #[derive(Default, Clone)]
+    struct MockSink {
+        received: Arc<Mutex<Vec<(String, ForcedExitRequestEvent)>>>,
+        failures_left: Arc<Mutex<u32>>,
+    }
+
+    impl MockSink {
+        fn failing(failures: u32) -> Self {
+            Self {
+                failures_left: Arc::new(Mutex::new(failures)),
+                ..Self::default()
+            }
+        }
+
+        fn received(&self) -> Vec<(String, ForcedExitRequestEvent)> {
+            self.received.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl DeliverySink for MockSink {
+        async fn deliver(&self, delivery: &ForcedExitRequestDelivery) -> anyhow::Result<()> {
+            self.received
+                .lock()
+                .unwrap()
+                .push((delivery.idempotency_key(), delivery.event));
+
+            let mut failures_left = self.failures_left.lock().unwrap();
+            if *failures_left > 0 {
+                *failures_left -= 1;
+                anyhow::bail!("Connection reset by peer");
+            }
+            Ok(())
+        }
+    }
+
+    async fn escalate(wrapper: &MockCoreInteractionWrapper, request_id: ForcedExitRequestId) {
+        wrapper
+            .store_escalation(ForcedExitRequestEscalation {
+                request_id,
+                full_exits: vec![],
+                created_at: Utc::now(),
+                l1_tx_hash: None,
+                finalized_at: None,
+            })
+            .await
+            .unwrap();
+    }
+
+    // Makes the postponed deliveries due as if the retry delay has passed
+    fn skip_retry_delay(wrapper: &MockCoreInteractionWrapper) {
+        for delivery in wrapper.lock_deliveries().iter_mut() {
+            delivery.next_attempt_at = Utc::now();
+        }
+    }
+
+    #[test]
+    fn retry_delays() {
+        assert_eq!(retry_delay(0), chrono::Duration::seconds(10));
+        assert_eq!(retry_delay(1), chrono::Duration::seconds(20));
+        assert_eq!(retry_delay(5), chrono::Duration::seconds(320));
+        assert_eq!(retry_delay(10), chrono::Duration::hours(1));
+        assert_eq!(retry_delay(u32::MAX), chrono::Duration::hours(1));
+    }
+
+    #[tokio::test]
+    async fn delivery_after_restart() {
+        let wrapper = MockCoreInteractionWrapper::default();
+        let sink = MockSink::default();
+
+        // The server is stopped after the transition has been committed,
+        // but before the dispatcher has picked the notification up
+        escalate(&wrapper, 12).await;
+        escalate(&wrapper, 12).await;
+        assert!(sink.received().is_empty());
+
+        let dispatcher = OutboxDispatcher::new(wrapper, sink.clone());
+        assert_eq!(dispatcher.dispatch_once().await.unwrap(), 1);
+        assert_eq!(
+            sink.received(),
+            vec![(
+                "forced-exit-delivery-1".to_string(),
+                ForcedExitRequestEvent::Escalated
+            )]
+        );
+
+        // Nothing is delivered twice once the delivery is acknowledged
+        assert_eq!(dispatcher.dispatch_once().await.unwrap(), 0);
+        let deliveries = dispatcher.core_interaction_wrapper.lock_deliveries();
+        assert!(deliveries[0].is_delivered());
+        assert_eq!(deliveries[0].attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn redelivery_with_same_idempotency_key() {
+        let wrapper = MockCoreInteractionWrapper::default();
+        let sink = MockSink::failing(2);
+        escalate(&wrapper, 12).await;
+        escalate(&wrapper, 13).await;
+
+        // The receiver got the notifications, but the acknowledgements were lost
+        let dispatcher = OutboxDispatcher::new(wrapper, sink.clone());
+        assert_eq!(dispatcher.dispatch_once().await.unwrap(), 0);
+        assert_eq!(sink.received().len(), 2);
+
+        // The retries are postponed
+        assert_eq!(dispatcher.dispatch_once().await.unwrap(), 0);
+        {
+            let deliveries = dispatcher.core_interaction_wrapper.lock_deliveries();
+            assert!(deliveries.iter().all(|delivery| {
+                delivery.attempts == 1
+                    && !delivery.is_delivered()
+                    && delivery.last_error.as_deref() == Some("Connection reset by peer")
+                    && delivery.next_attempt_at > Utc::now()
+            }));
+        }
+
+        // The dispatcher is restarted and delivers the notifications once again
+        let wrapper = dispatcher.core_interaction_wrapper;
+        skip_retry_delay(&wrapper);
+        let dispatcher = OutboxDispatcher::new(wrapper, sink.clone());
+        assert_eq!(dispatcher.dispatch_once().await.unwrap(), 2);
+
+        let received = sink.received();
+        assert_eq!(received.len(), 4);
+        assert_eq!(received[0], received[2]);
+        assert_eq!(received[1], received[3]);
+        assert_ne!(received[0].0, received[1].0);
+
+        let deliveries = dispatcher.core_interaction_wrapper.lock_deliveries();
+        assert!(deliveries
+            .iter()
+            .all(|delivery| delivery.is_delivered() && delivery.attempts == 2));
+    }
+
+    #[tokio::test]
+    async fn callbacks_are_given_up_on() {
+        let wrapper = MockCoreInteractionWrapper::default();
+        escalate(&wrapper, 12).await;
+        // The request is fulfilled as if it was created with the callback URL
+        {
+            let mut deliveries = wrapper.lock_deliveries();
+            let mut callback = deliveries[0].clone();
+            callback.id = 2;
+            callback.sequence = 2;
+            callback.event = ForcedExitRequestEvent::Fulfilled;
+            callback.callback_url = Some("https://exchange.example/forced_exits".to_owned());
+            callback.callback = Some(ForcedExitFulfilledCallback {
+                request_id: 12,
+                target: Default::default(),
+                tokens: vec![],
+                tx_hashes: vec![],
+                fulfilled_at: Utc::now(),
+            });
+            deliveries.push(callback);
+        }
+
+        // The callbacks are dispatched apart from the webhook notifications
+        let sink = MockSink::failing(u32::MAX);
+        let dispatcher = OutboxDispatcher::for_callbacks(wrapper, sink.clone(), 3);
+        for _ in 0..5 {
+            assert_eq!(dispatcher.dispatch_once().await.unwrap(), 0);
+            skip_retry_delay(&dispatcher.core_interaction_wrapper);
+        }
+        let received = sink.received();
+        assert_eq!(received.len(), 3);
+        assert!(received.iter().all(|(key, event)| {
+            key == "forced-exit-delivery-2" && *event == ForcedExitRequestEvent::Fulfilled
+        }));
+
+        let deliveries = dispatcher.core_interaction_wrapper.lock_deliveries();
+        assert!(!deliveries[0].is_failed());
+        assert_eq!(deliveries[0].attempts, 0);
+        assert!(deliveries[1].is_failed());
+        assert!(!deliveries[1].is_delivered());
+        assert_eq!(deliveries[1].attempts, 3);
+        assert_eq!(
+            deliveries[1].last_error.as_deref(),
+            Some("Connection reset by peer")
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! The batches of the ForcedExit transactions are paced, so the bursts of the paid requests
+//! do not crowd the transactions of the users out of the blocks.
+//!
+//! The submissions are limited by a token bucket refilled at `max_batches_per_minute` per minute
+//! and holding at most a minute worth of the batches. Besides, after `batches_per_block` batches
+//! the sender waits for the block with the last of them to be sealed before sending the next ones.
+//! The batches above the limits wait for their turn, the requests are not dropped or failed.
+//!
+//! The pacing is shared by all the senders of the account, and can be changed at runtime,
+//! the batches sent under the previous limits are kept counting towards the new ones.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use zksync_types::{
+    forced_exit_requests::{ForcedExitPacing, ForcedExitPacingState},
+    tx::TxHash,
+};
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+/// What the sender has to do before sending the next batch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PacingDecision {
+    /// The batch can be sent right away, it is counted towards the limits.
+    Send,
+    /// The limit of the batches per minute is reached, the next one is allowed after the delay.
+    Wait(Duration),
+    /// The batches allowed per block are sent, the block with the given one is to be sealed first.
+    AwaitBlock(TxHash),
+}
+
+#[derive(Debug)]
+struct PacerState {
+    pacing: ForcedExitPacing,
+    overridden: bool,
+    // The batches allowed to be sent as of `refilled_at`, fractional between the refills
+    available: f64,
+    refilled_at: Instant,
+    // The batches sent since the block was last awaited, and the last of them
+    batches_in_block: u32,
+    last_batch: Option<TxHash>,
+    // The batches waiting for their turn
+    paced_batches: u32,
+}
+
+impl PacerState {
+    fn capacity(&self) -> f64 {
+        f64::from(self.pacing.max_batches_per_minute)
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+        self.available = (self.available
+            + elapsed.as_secs_f64() * self.capacity() / MINUTE.as_secs_f64())
+        .min(self.capacity());
+        self.refilled_at = now;
+    }
+}
+
+#[derive(Debug)]
+pub struct SubmissionPacer {
+    state: Mutex<PacerState>,
+}
+
+impl SubmissionPacer {
+    pub fn new(pacing: ForcedExitPacing) -> Self {
+        Self::new_at(pacing, Instant::now())
+    }
+
+    fn new_at(pacing: ForcedExitPacing, now: Instant) -> Self {
+        Self {
+            state: Mutex::new(PacerState {
+                pacing,
+                overridden: false,
+                available: f64::from(pacing.max_batches_per_minute),
+                refilled_at: now,
+                batches_in_block: 0,
+                last_batch: None,
+                paced_batches: 0,
+            }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PacerState> {
+        self.state.lock().expect("Failed to get the pacer lock")
+    }
+
+    /// Applies the pacing, either the configured one or the one set by the operators.
+    pub fn set_pacing(&self, pacing: ForcedExitPacing, overridden: bool) {
+        self.set_pacing_at(pacing, overridden, Instant::now())
+    }
+
+    fn set_pacing_at(&self, pacing: ForcedExitPacing, overridden: bool, now: Instant) {
+        let mut state = self.lock();
+        state.overridden = overridden;
+        if state.pacing == pacing {
+            return;
+        }
+
+        // The batches allowed under the previous limit are not taken away, unless they exceed the new one
+        let was_limited = state.pacing.max_batches_per_minute > 0;
+        state.refill(now);
+        state.pacing = pacing;
+        state.available = if was_limited {
+            state.available.min(state.capacity())
+        } else {
+            state.capacity()
+        };
+        if pacing.batches_per_block == 0 {
+            state.batches_in_block = 0;
+            state.last_batch = None;
+        }
+    }
+
+    /// Decides whether the next batch can be sent. The batch is counted towards the limits
+    /// if it can, otherwise the sender is expected to wait and ask again.
+    pub fn next_batch(&self) -> PacingDecision {
+        self.next_batch_at(Instant::now())
+    }
+
+    fn next_batch_at(&self, now: Instant) -> PacingDecision {
+        let mut state = self.lock();
+        let per_block = state.pacing.batches_per_block;
+        if per_block > 0 && state.batches_in_block >= per_block {
+            match state.last_batch {
+                Some(tx_hash) => return PacingDecision::AwaitBlock(tx_hash),
+                // The last batch of the block was not sent after all
+                None => state.batches_in_block = 0,
+            }
+        }
+
+        if state.pacing.max_batches_per_minute > 0 {
+            state.refill(now);
+            if state.available < 1.0 {
+                let missing = 1.0 - state.available;
+                return PacingDecision::Wait(Duration::from_secs_f64(
+                    missing * MINUTE.as_secs_f64() / state.capacity(),
+                ));
+            }
+            state.available -= 1.0;
+        }
+        state.batches_in_block += 1;
+        PacingDecision::Send
+    }
+
+    /// Records the last transaction of the sent batch, the block of which is awaited
+    /// once the batches allowed per block are sent.
+    pub fn batch_sent(&self, tx_hash: TxHash) {
+        self.lock().last_batch = Some(tx_hash);
+    }
+
+    /// Starts counting the batches for the next block, unless it is started already
+    /// by another sender awaiting the same batch.
+    pub fn block_sealed(&self, tx_hash: TxHash) {
+        let mut state = self.lock();
+        if state.last_batch == Some(tx_hash) {
+            state.batches_in_block = 0;
+            state.last_batch = None;
+        }
+    }
+
+    pub fn begin_pacing(&self) {
+        self.lock().paced_batches += 1;
+    }
+
+    pub fn end_pacing(&self) {
+        let mut state = self.lock();
+        state.paced_batches = state.paced_batches.saturating_sub(1);
+    }
+
+    /// The pacing state reported along with the status of the sender.
+    pub fn state(&self, reported_at: DateTime<Utc>) -> ForcedExitPacingState {
+        self.state_at(Instant::now(), reported_at)
+    }
+
+    fn state_at(&self, now: Instant, reported_at: DateTime<Utc>) -> ForcedExitPacingState {
+        let mut state = self.lock();
+        let available_batches = if state.pacing.max_batches_per_minute > 0 {
+            state.refill(now);
+            Some(state.available.floor() as u32)
+        } else {
+            None
+        };
+
+        ForcedExitPacingState {
+            pacing: state.pacing,
+            overridden: state.overridden,
+            available_batches,
+            batches_in_block: state.batches_in_block,
+            paced_batches: state.paced_batches,
+            reported_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pacing(max_batches_per_minute: u32, batches_per_block: u32) -> ForcedExitPacing {
+        ForcedExitPacing {
+            max_batches_per_minute,
+            batches_per_block,
+        }
+    }
+
+    #[test]
+    fn unlimited_batches_are_sent_right_away() {
+        let start = Instant::now();
+        let pacer = SubmissionPacer::new_at(ForcedExitPacing::default(), start);
+
+        for _ in 0..100 {
+            assert_eq!(pacer.next_batch_at(start), PacingDecision::Send);
+        }
+        assert_eq!(pacer.state_at(start, Utc::now()).available_batches, None);
+    }
+
+    #[test]
+    fn batches_above_the_limit_wait_for_the_refill() {
+        let start = Instant::now();
+        let pacer = SubmissionPacer::new_at(pacing(6, 0), start);
+
+        // A minute worth of the batches is allowed at once
+        for _ in 0..6 {
+            assert_eq!(pacer.next_batch_at(start), PacingDecision::Send);
+        }
+        assert_eq!(
+            pacer.next_batch_at(start),
+            PacingDecision::Wait(Duration::from_secs(10))
+        );
+        assert_eq!(
+            pacer.next_batch_at(start + Duration::from_secs(5)),
+            PacingDecision::Wait(Duration::from_secs(5))
+        );
+        assert_eq!(
+            pacer.next_batch_at(start + Duration::from_secs(10)),
+            PacingDecision::Send
+        );
+        assert_eq!(
+            pacer.next_batch_at(start + Duration::from_secs(10)),
+            PacingDecision::Wait(Duration::from_secs(10))
+        );
+
+        // The idle time does not allow more than a minute worth of the batches
+        let later = start + Duration::from_secs(600);
+        assert_eq!(pacer.state_at(later, Utc::now()).available_batches, Some(6));
+        for _ in 0..6 {
+            assert_eq!(pacer.next_batch_at(later), PacingDecision::Send);
+        }
+        assert!(matches!(
+            pacer.next_batch_at(later),
+            PacingDecision::Wait(_)
+        ));
+    }
+
+    #[test]
+    fn block_is_awaited_after_the_batches_per_block() {
+        let start = Instant::now();
+        let pacer = SubmissionPacer::new_at(pacing(0, 2), start);
+        let first = TxHash::default();
+        let second = TxHash::from_slice(&[1; 32]).unwrap();
+
+        assert_eq!(pacer.next_batch_at(start), PacingDecision::Send);
+        pacer.batch_sent(first);
+        assert_eq!(pacer.next_batch_at(start), PacingDecision::Send);
+        pacer.batch_sent(second);
+        assert_eq!(
+            pacer.next_batch_at(start),
+            PacingDecision::AwaitBlock(second)
+        );
+        assert_eq!(pacer.state_at(start, Utc::now()).batches_in_block, 2);
+
+        pacer.block_sealed(second);
+        assert_eq!(pacer.next_batch_at(start), PacingDecision::Send);
+        assert_eq!(pacer.state_at(start, Utc::now()).batches_in_block, 1);
+        // The block awaited by the late sender does not reset the counting once more
+        pacer.block_sealed(second);
+        assert_eq!(pacer.state_at(start, Utc::now()).batches_in_block, 1);
+    }
+
+    #[test]
+    fn changed_pacing_keeps_the_sent_batches() {
+        let start = Instant::now();
+        let pacer = SubmissionPacer::new_at(pacing(60, 0), start);
+        for _ in 0..58 {
+            assert_eq!(pacer.next_batch_at(start), PacingDecision::Send);
+        }
+
+        // The lower limit caps the allowed batches, the sent ones are not refunded by the higher one
+        pacer.set_pacing_at(pacing(1, 0), true, start);
+        let state = pacer.state_at(start, Utc::now());
+        assert_eq!(state.available_batches, Some(1));
+        assert!(state.overridden);
+        pacer.set_pacing_at(pacing(120, 0), true, start);
+        assert_eq!(pacer.state_at(start, Utc::now()).available_batches, Some(1));
+        assert_eq!(
+            pacer
+                .state_at(start + Duration::from_secs(1), Utc::now())
+                .available_batches,
+            Some(3)
+        );
+
+        // The limit set after the unlimited sending allows a minute worth of the batches
+        pacer.set_pacing_at(ForcedExitPacing::default(), false, start);
+        pacer.set_pacing_at(pacing(2, 0), false, start);
+        assert_eq!(pacer.state_at(start, Utc::now()).available_batches, Some(2));
+    }
+}
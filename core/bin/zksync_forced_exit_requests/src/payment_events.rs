@@ -0,0 +1,427 @@
+//! Decoding of the payment events emitted by the different versions
+//! of the forced exit contract.
+//!
+//! Upon an upgrade of the contract both the address and the set of emitted
+//! events may change, so the payments are recognized by the pair of the contract
+//! address and the event topic. Payments to the previous deployments are accepted
+//! only within their activation block ranges.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+use web3::types::Log;
+use zksync_config::configs::forced_exit_requests::ForcedExitContractDeployment;
+use zksync_types::{
+    forced_exit_requests::{
+        FundsReceivedEvent, FundsReceivedEventKind, FundsReceivedEventParseError,
+        PaymentAddressWindow,
+    },
+    Address, H256,
+};
+
+/// Version of the currently deployed forced exit contract.
+pub const CURRENT_CONTRACT_VERSION: u32 = 2;
+
+/// Returns the payment events emitted by the given version of the contract.
+fn contract_version_events(version: u32) -> Option<&'static [FundsReceivedEventKind]> {
+    let events: &'static [FundsReceivedEventKind] = match version {
+        1 => &[FundsReceivedEventKind::FundsReceived],
+        2 => &FundsReceivedEventKind::ALL,
+        _ => return None,
+    };
+    Some(events)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ActivationRange {
+    first_block: u64,
+    /// `None` for the current deployment, which has no end of life yet.
+    last_block: Option<u64>,
+}
+
+impl ActivationRange {
+    fn contains(&self, block: u64) -> bool {
+        block >= self.first_block && self.last_block.map_or(true, |last| block <= last)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PaymentLogError {
+    #[error("Log was emitted by the unwatched address {0:?}")]
+    UnwatchedAddress(Address),
+    #[error("Unknown event with topic {topic:?} was emitted by the contract {address:?}")]
+    UnknownTopic { address: Address, topic: H256 },
+    #[error(
+        "Payment to the contract {address:?} at block {block} is outside of its activation range"
+    )]
+    OutsideActivationRange { address: Address, block: u64 },
+    #[error("Failed to parse the payment event: {0}")]
+    Parse(#[from] FundsReceivedEventParseError),
+}
+
+/// Decoders for the payment events of all the watched deployments of the contract.
+#[derive(Debug, Clone)]
+pub struct PaymentEventDecoder {
+    contracts: HashMap<Address, ActivationRange>,
+    decoders: HashMap<(Address, H256), FundsReceivedEventKind>,
+}
+
+impl PaymentEventDecoder {
+    pub fn new(
+        current_contract: Address,
+        legacy_contracts: &[ForcedExitContractDeployment],
+    ) -> anyhow::Result<Self> {
+        let mut decoder = Self {
+            contracts: HashMap::new(),
+            decoders: HashMap::new(),
+        };
+
+        decoder.add_contract(
+            current_contract,
+            CURRENT_CONTRACT_VERSION,
+            ActivationRange {
+                first_block: 0,
+                last_block: None,
+            },
+        )?;
+        for deployment in legacy_contracts {
+            decoder.add_contract(
+                deployment.address,
+                deployment.version,
+                ActivationRange {
+                    first_block: deployment.first_block,
+                    last_block: Some(deployment.last_block),
+                },
+            )?;
+        }
+
+        Ok(decoder)
+    }
+
+    /// Adds the payment addresses of the rotation schedule, which are the deployments
+    /// of the current version of the contract. All their payments are decoded, since
+    /// the payments to the retired addresses are recorded by the watcher rather than skipped.
+    pub fn with_payment_addresses(
+        mut self,
+        current_contract: Address,
+        payment_addresses: &[PaymentAddressWindow],
+    ) -> anyhow::Result<Self> {
+        for window in payment_addresses {
+            if window.address == current_contract {
+                continue;
+            }
+            self.add_contract(
+                window.address,
+                CURRENT_CONTRACT_VERSION,
+                ActivationRange {
+                    first_block: 0,
+                    last_block: None,
+                },
+            )?;
+        }
+        Ok(self)
+    }
+
+    fn add_contract(
+        &mut self,
+        address: Address,
+        version: u32,
+        range: ActivationRange,
+    ) -> anyhow::Result<()> {
+        let events = contract_version_events(version).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown version {} of the forced exit contract {:?}",
+                version,
+                address
+            )
+        })?;
+        if self.contracts.insert(address, range).is_some() {
+            anyhow::bail!(
+                "The forced exit contract {:?} is configured more than once",
+                address
+            );
+        }
+
+        for kind in events {
+            self.decoders.insert((address, kind.topic()), *kind);
+        }
+        Ok(())
+    }
+
+    /// Addresses of all the deployments the payments are accepted to.
+    pub fn watched_addresses(&self) -> Vec<Address> {
+        self.contracts.keys().copied().collect()
+    }
+
+    pub fn decode(&self, log: Log) -> Result<FundsReceivedEvent, PaymentLogError> {
+        let address = log.address;
+        let range = self
+            .contracts
+            .get(&address)
+            .ok_or(PaymentLogError::UnwatchedAddress(address))?;
+
+        // Anonymous events have no topics at all
+        let topic = log.topics.first().copied().unwrap_or_default();
+        let kind = self
+            .decoders
+            .get(&(address, topic))
+            .copied()
+            .ok_or(PaymentLogError::UnknownTopic { address, topic })?;
+
+        let event = FundsReceivedEvent::from_log(kind, log)?;
+        if !range.contains(event.block_number) {
+            return Err(PaymentLogError::OutsideActivationRange {
+                address,
+                block: event.block_number,
+            });
+        }
+
+        Ok(event)
+    }
+
+    /// Decodes the payments from the logs of the watched contracts.
+    ///
+    /// Logs that can not be decoded are skipped, but unknown events
+    /// on the watched addresses are reported, since they most likely
+    /// mean that the contract was upgraded without updating the decoders.
+    pub fn decode_payments(&self, logs: Vec<Log>) -> Vec<FundsReceivedEvent> {
+        let mut payments = Vec::with_capacity(logs.len());
+        for log in logs {
+            match self.decode(log) {
+                Ok(payment) => payments.push(payment),
+                Err(err @ PaymentLogError::UnknownTopic { .. }) => {
+                    vlog::error!("Unable to recognize the forced exit payment: {}", err);
+                    metrics::increment_counter!("forced_exit_requests.eth_watcher.unknown_event");
+                }
+                Err(err) => {
+                    vlog::warn!("Skipping the forced exit contract log: {}", err);
+                }
+            }
+        }
+        payments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethabi::{encode, Token};
+    use web3::types::Bytes;
+    use zksync_types::U256;
+
+    fn log(address: Address, topic: H256, data: Vec<Token>, block: u64) -> Log {
+        Log {
+            address,
+            topics: vec![topic],
+            data: Bytes(encode(&data)),
+            block_hash: None,
+            block_number: Some(block.into()),
+            transaction_hash: None,
+            transaction_index: None,
+            log_index: None,
+            transaction_log_index: None,
+            log_type: None,
+            removed: Some(false),
+        }
+    }
+
+    fn get_test_decoder() -> (PaymentEventDecoder, Address, Address) {
+        let current_contract = Address::from_low_u64_be(2);
+        let legacy_contract = Address::from_low_u64_be(1);
+
+        let decoder = PaymentEventDecoder::new(
+            current_contract,
+            &[ForcedExitContractDeployment {
+                address: legacy_contract,
+                version: 1,
+                first_block: 100,
+                last_block: 200,
+            }],
+        )
+        .unwrap();
+
+        (decoder, current_contract, legacy_contract)
+    }
+
+    #[test]
+    fn decode_payments_of_both_versions() {
+        let (decoder, current_contract, legacy_contract) = get_test_decoder();
+        let amount = Token::Uint(U256::from(1212));
+
+        let event = decoder
+            .decode(log(
+                legacy_contract,
+                FundsReceivedEventKind::FundsReceived.topic(),
+                vec![amount.clone()],
+                150,
+            ))
+            .unwrap();
+        assert_eq!(event.amount, 1212u32.into());
+        assert_eq!(event.request_id, None);
+        assert_eq!(event.block_number, 150);
+
+        let event = decoder
+            .decode(log(
+                current_contract,
+                FundsReceivedEventKind::FundsReceivedWithRequestId.topic(),
+                vec![amount.clone(), Token::Uint(U256::from(34))],
+                250,
+            ))
+            .unwrap();
+        assert_eq!(event.amount, 1212u32.into());
+        assert_eq!(event.request_id, Some(34));
+
+        let event = decoder
+            .decode(log(
+                current_contract,
+                FundsReceivedEventKind::FundsReceived.topic(),
+                vec![amount.clone()],
+                250,
+            ))
+            .unwrap();
+        assert_eq!(event.request_id, None);
+
+        // The legacy contract is not accepted outside of the migration window
+        let result = decoder.decode(log(
+            legacy_contract,
+            FundsReceivedEventKind::FundsReceived.topic(),
+            vec![amount],
+            250,
+        ));
+        assert!(matches!(
+            result,
+            Err(PaymentLogError::OutsideActivationRange { block: 250, .. })
+        ));
+    }
+
+    #[test]
+    fn decode_unknown_topic() {
+        let (decoder, current_contract, legacy_contract) = get_test_decoder();
+        let amount = Token::Uint(U256::from(1212));
+
+        // The first version of the contract does not emit the request id
+        let topic = FundsReceivedEventKind::FundsReceivedWithRequestId.topic();
+        let result = decoder.decode(log(
+            legacy_contract,
+            topic,
+            vec![amount.clone(), Token::Uint(U256::from(34))],
+            150,
+        ));
+        assert!(matches!(
+            result,
+            Err(PaymentLogError::UnknownTopic { address, topic: t })
+                if address == legacy_contract && t == topic
+        ));
+
+        let topic = H256::repeat_byte(0x12);
+        let result = decoder.decode(log(current_contract, topic, vec![amount.clone()], 250));
+        assert!(matches!(
+            result,
+            Err(PaymentLogError::UnknownTopic { address, topic: t })
+                if address == current_contract && t == topic
+        ));
+
+        // Only the recognized payments are returned
+        let payments = decoder.decode_payments(vec![
+            log(current_contract, topic, vec![amount.clone()], 250),
+            log(
+                current_contract,
+                FundsReceivedEventKind::FundsReceived.topic(),
+                vec![amount],
+                250,
+            ),
+        ]);
+        assert_eq!(payments.len(), 1);
+    }
+
+    #[test]
+    fn decode_large_amounts() {
+        let (decoder, current_contract, _) = get_test_decoder();
+
+        let amounts = [
+            U256::from(u128::MAX),
+            U256::from(u128::MAX) + 1,
+            U256::max_value(),
+        ];
+        for amount in amounts.iter() {
+            let event = decoder
+                .decode(log(
+                    current_contract,
+                    FundsReceivedEventKind::FundsReceived.topic(),
+                    vec![Token::Uint(*amount)],
+                    250,
+                ))
+                .unwrap();
+            assert_eq!(event.amount.to_str_radix(10), amount.to_string());
+        }
+    }
+
+    #[test]
+    fn decode_rotated_payment_addresses() {
+        let (decoder, current_contract, legacy_contract) = get_test_decoder();
+        let retired_address = Address::from_low_u64_be(3);
+        let decoder = decoder
+            .with_payment_addresses(
+                current_contract,
+                &[
+                    PaymentAddressWindow {
+                        address: current_contract,
+                        first_block: 0,
+                        last_block: None,
+                    },
+                    PaymentAddressWindow {
+                        address: retired_address,
+                        first_block: 0,
+                        last_block: Some(200),
+                    },
+                ],
+            )
+            .unwrap();
+
+        // The windows of the payment addresses are checked later, so that the
+        // payments to the retired addresses are recorded
+        let event = decoder
+            .decode(log(
+                retired_address,
+                FundsReceivedEventKind::FundsReceivedWithRequestId.topic(),
+                vec![Token::Uint(U256::from(1212)), Token::Uint(U256::from(34))],
+                250,
+            ))
+            .unwrap();
+        assert_eq!(event.request_id, Some(34));
+        assert_eq!(event.recipient, Some(retired_address));
+
+        // The payment address may not be a legacy deployment at the same time
+        let (decoder, current_contract, _) = get_test_decoder();
+        let result = decoder.with_payment_addresses(
+            current_contract,
+            &[PaymentAddressWindow {
+                address: legacy_contract,
+                first_block: 0,
+                last_block: None,
+            }],
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn invalid_deployments() {
+        let contract = Address::from_low_u64_be(1);
+
+        let unknown_version = ForcedExitContractDeployment {
+            address: Address::from_low_u64_be(2),
+            version: 12,
+            first_block: 100,
+            last_block: 200,
+        };
+        assert!(PaymentEventDecoder::new(contract, &[unknown_version]).is_err());
+
+        let duplicate = ForcedExitContractDeployment {
+            address: contract,
+            version: 1,
+            first_block: 100,
+            last_block: 200,
+        };
+        assert!(PaymentEventDecoder::new(contract, &[duplicate]).is_err());
+    }
+}
@@ -1,17 +1,20 @@
+use chrono::Utc;
 use num::BigUint;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use zksync_api::api_server::forced_exit_receipts::load_receipt;
 use zksync_config::ForcedExitRequestsConfig;
-use zksync_storage::{
-    chain::operations_ext::records::TxReceiptResponse, ConnectionPool, StorageProcessor,
-};
+use zksync_storage::{ConnectionPool, StorageProcessor};
 
 use zksync_types::{
+    forced_exit_requests::ForcedExitSenderState,
     tx::{ChangePubKeyType, TimeRange, TxHash},
     AccountId, Address, PubKeyHash, ZkSyncTx, H256,
 };
 
 use zksync_types::{Nonce, TokenId};
 
+use std::future::Future;
+
 use zksync_crypto::franklin_crypto::eddsa::PrivateKey;
 
 use futures::channel::{mpsc, oneshot};
@@ -21,40 +24,91 @@ use tokio::time;
 use zksync_mempool::MempoolTransactionRequest;
 use zksync_test_account::{ZkSyncAccount, ZkSyncETHAccountData};
 
-use super::utils::{read_signing_key, Engine};
+use super::{
+    shutdown::ShutdownSignal,
+    signer::signer_from_config,
+    utils::{read_signing_key, Engine},
+};
+
+/// How long to wait before preparing the sender account again after the preparation failed.
+pub const SENDER_ACCOUNT_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Prepares the sender account until it succeeds. Until then nothing is sent, but the server
+/// keeps running, so funding the account is enough to make it operational.
+/// Returns `None` if the shutdown is requested first.
+pub async fn retry_sender_account_preparation<F, Fut>(
+    sender_address: Address,
+    retry_interval: Duration,
+    shutdown: &mut ShutdownSignal,
+    mut prepare: F,
+) -> Option<AccountId>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<AccountId>>,
+{
+    loop {
+        let err = tokio::select! {
+            prepared = prepare() => match prepared {
+                Ok(id) => return Some(id),
+                Err(err) => err,
+            },
+            _ = shutdown.requested() => return None,
+        };
+        metrics::increment_counter!("forced_exit_requests.sender_account_preparation_failures");
+        vlog::error!(
+            "Waiting for the forced exit sender account {:?}, no forced exits are sent \
+             until it is funded and prepared. Retrying in {:?}: {:#}",
+            sender_address,
+            retry_interval,
+            err
+        );
+        tokio::select! {
+            _ = time::sleep(retry_interval) => {}
+            _ = shutdown.requested() => return None,
+        }
+    }
+}
 
 pub async fn prepare_forced_exit_sender_account(
     connection_pool: ConnectionPool,
     config: &ForcedExitRequestsConfig,
     mempool_tx_sender: mpsc::Sender<MempoolTransactionRequest>,
 ) -> anyhow::Result<AccountId> {
-    let mut storage = connection_pool
-        .access_storage()
-        .await
-        .expect("forced_exit_requests: Failed to get the connection to storage");
+    let mut storage = connection_pool.access_storage().await?;
 
-    let sender_sk = hex::decode(&config.sender_private_key[2..])
-        .expect("Failed to decode forced_exit_sender sk");
-    let sender_sk = read_signing_key(&sender_sk).expect("Failed to read forced exit sender sk");
     let sender_address = config.sender_account_address;
     let sender_eth_private_key = config.sender_eth_private_key;
+    let pub_key_hash = signer_from_config(config, sender_address, Some(&config.sender_private_key))
+        .pub_key_hash()
+        .await?;
 
     let is_sender_prepared =
-        check_forced_exit_sender_prepared(&mut storage, &sender_sk, sender_address)
-            .await
-            .expect("Failed to check if the sender is prepared");
+        check_forced_exit_sender_prepared(&mut storage, pub_key_hash, sender_address).await?;
 
     if let Some(id) = is_sender_prepared {
+        storage
+            .forced_exit_requests_schema()
+            .store_sender_state(sender_address, ForcedExitSenderState::Ready, Utc::now())
+            .await?;
         return Ok(id);
     }
 
     // The sender is not prepared. This should not ever happen in production, but handling
     // such step is vital for testing locally.
 
+    // The key held by the external signer can not be set from here
+    if config.external_signer_url.is_some() {
+        anyhow::bail!(
+            "The signing key of the ForcedExit sender {:?} is not set to the one of the external signer",
+            sender_address
+        );
+    }
+    let sender_sk = hex::decode(&config.sender_private_key[2..])
+        .expect("Failed to decode forced_exit_sender sk");
+    let sender_sk = read_signing_key(&sender_sk).expect("Failed to read forced exit sender sk");
+
     // Waiting until the sender has an id (sending funds to the account should be done by an external script)
-    let id = wait_for_account_id(&mut storage, sender_address)
-        .await
-        .expect("Failed to get account id for forced exit sender");
+    let id = wait_for_account_id(&mut storage, config).await?;
 
     register_signing_key(
         &mut storage,
@@ -71,7 +125,7 @@ pub async fn prepare_forced_exit_sender_account(
 
 pub async fn check_forced_exit_sender_prepared(
     storage: &mut StorageProcessor<'_>,
-    sender_sk: &PrivateKey<Engine>,
+    pub_key_hash: PubKeyHash,
     sender_address: Address,
 ) -> anyhow::Result<Option<AccountId>> {
     let mut accounts_schema = storage.chain().account_schema();
@@ -83,11 +137,7 @@ pub async fn check_forced_exit_sender_prepared(
 
     match state {
         Some(account_state) => {
-            let pk_hash = account_state.1.pub_key_hash;
-
-            let sk_pub_key_hash = PubKeyHash::from_privkey(sender_sk);
-
-            if pk_hash == sk_pub_key_hash {
+            if account_state.1.pub_key_hash == pub_key_hash {
                 Ok(Some(account_state.0))
             } else {
                 Ok(None)
@@ -97,23 +147,126 @@ pub async fn check_forced_exit_sender_prepared(
     }
 }
 
-pub async fn wait_for_account_id(
+/// Tells the sender account, the creation of which is pending, apart from the address
+/// nothing is known to create, so the misconfigured address is given up on sooner.
+#[derive(Debug)]
+struct SenderAccountWait {
+    creation_pending_timeout: Duration,
+    address_unknown_timeout: Duration,
+    // The state the account has been observed in since the instant
+    state: Option<(ForcedExitSenderState, Instant)>,
+}
+
+impl SenderAccountWait {
+    fn new(creation_pending_timeout: Duration, address_unknown_timeout: Duration) -> Self {
+        Self {
+            creation_pending_timeout,
+            address_unknown_timeout,
+            state: None,
+        }
+    }
+
+    /// Returns whether the state of the account has changed, fails once the account
+    /// has stayed in the state for longer than the timeout of the state.
+    fn observe(&mut self, state: ForcedExitSenderState, now: Instant) -> anyhow::Result<bool> {
+        let since = match self.state {
+            Some((observed, since)) if observed == state => since,
+            _ => {
+                self.state = Some((state, now));
+                return Ok(true);
+            }
+        };
+        let waited = now.saturating_duration_since(since);
+        match state {
+            ForcedExitSenderState::Ready => {}
+            ForcedExitSenderState::CreationPending if waited > self.creation_pending_timeout => {
+                anyhow::bail!(
+                    "The creation of the forced exit sender account is still pending after {:?}",
+                    waited
+                );
+            }
+            ForcedExitSenderState::AddressUnknown if waited > self.address_unknown_timeout => {
+                anyhow::bail!(
+                    "Nothing has created the forced exit sender account in {:?}, \
+                     the address is likely misconfigured",
+                    waited
+                );
+            }
+            _ => {}
+        }
+        Ok(false)
+    }
+}
+
+async fn sender_account_state(
     storage: &mut StorageProcessor<'_>,
     sender_address: Address,
+) -> anyhow::Result<(ForcedExitSenderState, Option<AccountId>)> {
+    let account_id = storage
+        .chain()
+        .account_schema()
+        .account_id_by_address(sender_address)
+        .await?;
+    if account_id.is_some() {
+        return Ok((ForcedExitSenderState::Ready, account_id));
+    }
+
+    let state = if storage
+        .forced_exit_requests_schema()
+        .is_account_creation_pending(sender_address)
+        .await?
+    {
+        ForcedExitSenderState::CreationPending
+    } else {
+        ForcedExitSenderState::AddressUnknown
+    };
+    Ok((state, None))
+}
+
+pub async fn wait_for_account_id(
+    storage: &mut StorageProcessor<'_>,
+    config: &ForcedExitRequestsConfig,
 ) -> anyhow::Result<AccountId> {
     vlog::info!("Forced exit sender account is not yet prepared. Waiting for account id...");
 
-    let mut account_schema = storage.chain().account_schema();
+    let sender_address = config.sender_account_address;
+    let mut wait = SenderAccountWait::new(
+        config.sender_creation_pending_timeout(),
+        config.sender_address_unknown_timeout(),
+    );
     let mut timer = time::interval(Duration::from_secs(1));
 
     loop {
-        let account_id = account_schema.account_id_by_address(sender_address).await?;
+        let (state, account_id) = sender_account_state(storage, sender_address).await?;
+        // The state is reported by the health check of the core
+        if wait.observe(state, Instant::now())? {
+            storage
+                .forced_exit_requests_schema()
+                .store_sender_state(sender_address, state, Utc::now())
+                .await?;
+            match (state, account_id) {
+                (_, Some(id)) => {
+                    vlog::info!("Forced exit sender account has account id = {}", id);
+                }
+                (ForcedExitSenderState::CreationPending, None) => {
+                    vlog::info!(
+                        "The creation of the forced exit sender account {:?} is pending, \
+                         waiting for it to be committed",
+                        sender_address
+                    );
+                }
+                (_, None) => {
+                    vlog::warn!(
+                        "Nothing is known to create the forced exit sender account {:?}, \
+                         the address is likely misconfigured",
+                        sender_address
+                    );
+                }
+            }
+        }
 
         match account_id {
-            Some(id) => {
-                vlog::info!("Forced exit sender account has account id = {}", 1);
-                return Ok(id);
-            }
+            Some(id) => return Ok(id),
             None => {
                 timer.tick().await;
             }
@@ -121,17 +274,6 @@ pub async fn wait_for_account_id(
     }
 }
 
-async fn get_receipt(
-    storage: &mut StorageProcessor<'_>,
-    tx_hash: TxHash,
-) -> anyhow::Result<Option<TxReceiptResponse>> {
-    storage
-        .chain()
-        .operations_ext_schema()
-        .tx_receipt(tx_hash.as_ref())
-        .await
-}
-
 pub async fn wait_for_change_pub_key_tx(
     storage: &mut StorageProcessor<'_>,
     tx_hash: TxHash,
@@ -143,9 +285,12 @@ pub async fn wait_for_change_pub_key_tx(
     let mut timer = time::interval(Duration::from_secs(1));
 
     loop {
-        let tx_receipt = get_receipt(storage, tx_hash)
-            .await
-            .expect("Faield t oget the traecipt pf ChangePubKey transaction");
+        let tx_receipt = load_receipt(storage, tx_hash).await.map_err(|err| {
+            anyhow::anyhow!(
+                "Failed to get the receipt of the ChangePubKey transaction: {}",
+                err
+            )
+        })?;
 
         match tx_receipt {
             Some(receipt) => {
@@ -156,8 +301,8 @@ pub async fn wait_for_change_pub_key_tx(
                     let fail_reason = receipt
                         .fail_reason
                         .unwrap_or_else(|| String::from("unknown"));
-                    panic!(
-                        "Failed to set public key for forced exit sedner. Reason: {}",
+                    anyhow::bail!(
+                        "Failed to set the public key of the forced exit sender. Reason: {}",
                         fail_reason
                     );
                 }
@@ -205,18 +350,138 @@ pub async fn register_signing_key(
     let (sender, receiver) = oneshot::channel();
     let item = MempoolTransactionRequest::NewTx(Box::new(tx.into()), sender);
 
+    // An unfunded sender is rejected by the mempool, the preparation is retried then
     mempool_tx_sender
         .send(item)
         .await
-        .expect("Failed to send CPK transaction");
+        .map_err(|err| anyhow::anyhow!("Failed to send the ChangePubKey transaction: {}", err))?;
     receiver
         .await
-        .expect("Failed to receive result")
-        .expect("Failed to change pub key");
+        .map_err(|_| anyhow::anyhow!("The mempool has dropped the ChangePubKey transaction"))?
+        .map_err(|err| anyhow::anyhow!("Failed to change the public key: {}", err))?;
 
-    wait_for_change_pub_key_tx(storage, tx_hash)
-        .await
-        .expect("Failed to wait for ChangePubKey tx");
+    wait_for_change_pub_key_tx(storage, tx_hash).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CREATION_PENDING_TIMEOUT: Duration = Duration::from_secs(3600);
+    const ADDRESS_UNKNOWN_TIMEOUT: Duration = Duration::from_secs(300);
+
+    fn sender_account_wait() -> SenderAccountWait {
+        SenderAccountWait::new(CREATION_PENDING_TIMEOUT, ADDRESS_UNKNOWN_TIMEOUT)
+    }
 
-    Ok(())
+    #[tokio::test]
+    async fn sender_account_is_waited_for_until_funded() {
+        let sender_address = Address::repeat_byte(0x42);
+        let polls = std::sync::atomic::AtomicU32::new(0);
+        let mut shutdown = ShutdownSignal::never();
+
+        // The account appears on the third poll only, e.g. once the deposit is committed
+        let id = retry_sender_account_preparation(
+            sender_address,
+            Duration::from_millis(10),
+            &mut shutdown,
+            || {
+                let poll = polls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                async move {
+                    match poll {
+                        0 | 1 => Err(anyhow::anyhow!("The sender account has no id yet")),
+                        _ => Ok(AccountId(7)),
+                    }
+                }
+            },
+        )
+        .await;
+        assert_eq!(id, Some(AccountId(7)));
+        assert_eq!(polls.into_inner(), 3);
+
+        // The wait does not hold up the shutdown
+        let (shutdown_handle, mut shutdown) = ShutdownSignal::new(Duration::from_secs(1));
+        let wait = tokio::spawn(async move {
+            retry_sender_account_preparation(
+                sender_address,
+                Duration::from_secs(3600),
+                &mut shutdown,
+                || async { Err(anyhow::anyhow!("The sender account has no id yet")) },
+            )
+            .await
+        });
+        shutdown_handle.request();
+        assert_eq!(wait.await.unwrap(), None);
+    }
+
+    #[test]
+    fn committed_account_is_not_waited_for() {
+        let mut wait = sender_account_wait();
+        let start = Instant::now();
+
+        assert!(wait.observe(ForcedExitSenderState::Ready, start).unwrap());
+        assert!(!wait
+            .observe(
+                ForcedExitSenderState::Ready,
+                start + CREATION_PENDING_TIMEOUT * 2
+            )
+            .unwrap());
+    }
+
+    #[test]
+    fn pending_creation_is_waited_for_longer() {
+        let mut wait = sender_account_wait();
+        let start = Instant::now();
+
+        assert!(wait
+            .observe(ForcedExitSenderState::CreationPending, start)
+            .unwrap());
+        // The unknown address would have been given up on by now
+        assert!(!wait
+            .observe(
+                ForcedExitSenderState::CreationPending,
+                start + ADDRESS_UNKNOWN_TIMEOUT * 2
+            )
+            .unwrap());
+        let err = wait
+            .observe(
+                ForcedExitSenderState::CreationPending,
+                start + CREATION_PENDING_TIMEOUT + Duration::from_secs(1),
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("still pending"));
+    }
+
+    #[test]
+    fn unknown_address_is_given_up_on() {
+        let mut wait = sender_account_wait();
+        let start = Instant::now();
+
+        assert!(wait
+            .observe(ForcedExitSenderState::AddressUnknown, start)
+            .unwrap());
+        let err = wait
+            .observe(
+                ForcedExitSenderState::AddressUnknown,
+                start + ADDRESS_UNKNOWN_TIMEOUT + Duration::from_secs(1),
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("likely misconfigured"));
+
+        // The deposit creating the account appears a while after the start,
+        // the timeout of the new state counts from then on
+        let mut wait = sender_account_wait();
+        let deposited = start + ADDRESS_UNKNOWN_TIMEOUT / 2;
+        wait.observe(ForcedExitSenderState::AddressUnknown, start)
+            .unwrap();
+        assert!(wait
+            .observe(ForcedExitSenderState::CreationPending, deposited)
+            .unwrap());
+        assert!(!wait
+            .observe(
+                ForcedExitSenderState::CreationPending,
+                deposited + ADDRESS_UNKNOWN_TIMEOUT * 2
+            )
+            .unwrap());
+    }
 }
@@ -0,0 +1,265 @@
+//! The receipts of the sent transactions are polled by a single task for all the requests
+//! in flight. The senders register the hashes they wait for and are notified once the
+//! receipts appear, while the poller queries the receipts of all the registered hashes
+//! at once on every tick. This way the number of the queries does not grow with the number
+//! of the requests in flight.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::{self, MissedTickBehavior},
+};
+
+use zksync_config::ForcedExitRequestsConfig;
+use zksync_storage::chain::operations_ext::records::TxReceiptResponse;
+use zksync_types::tx::TxHash;
+
+use crate::core_interaction_wrapper::CoreInteractionWrapper;
+
+type Registration = (TxHash, oneshot::Sender<TxReceiptResponse>);
+
+/// The handle the senders register the awaited transactions through.
+#[derive(Debug, Clone)]
+pub struct ReceiptPoller {
+    registrations: mpsc::UnboundedSender<Registration>,
+}
+
+impl ReceiptPoller {
+    pub fn new(tick_interval: Duration, batch_size: usize) -> (Self, ReceiptPollerTask) {
+        let (registrations, receiver) = mpsc::unbounded_channel();
+        let task = ReceiptPollerTask {
+            registrations: receiver,
+            tick_interval,
+            batch_size: batch_size.max(1),
+            awaited: HashMap::new(),
+            queue: VecDeque::new(),
+        };
+        (Self { registrations }, task)
+    }
+
+    pub fn from_config(config: &ForcedExitRequestsConfig) -> (Self, ReceiptPollerTask) {
+        Self::new(
+            config.receipt_poll_interval(),
+            config.receipt_poll_batch_size,
+        )
+    }
+
+    /// Waits for the receipt of the transaction, `None` is returned if it has not
+    /// appeared within the `timeout`.
+    pub async fn wait_for_receipt(
+        &self,
+        tx_hash: TxHash,
+        timeout: Duration,
+    ) -> anyhow::Result<Option<TxReceiptResponse>> {
+        let (sender, receiver) = oneshot::channel();
+        self.registrations
+            .send((tx_hash, sender))
+            .map_err(|_| anyhow::Error::msg("The receipt poller is stopped"))?;
+
+        match time::timeout(timeout, receiver).await {
+            Ok(Ok(receipt)) => Ok(Some(receipt)),
+            Ok(Err(_)) => Err(anyhow::Error::msg("The receipt poller is stopped")),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+/// The task querying the receipts of the registered transactions.
+#[derive(Debug)]
+pub struct ReceiptPollerTask {
+    registrations: mpsc::UnboundedReceiver<Registration>,
+    tick_interval: Duration,
+    batch_size: usize,
+    awaited: HashMap<TxHash, Vec<oneshot::Sender<TxReceiptResponse>>>,
+    // The order the hashes are queried in, the queried ones are moved to the back
+    // so the rest are not starved if there are more of them than fit into a batch
+    queue: VecDeque<TxHash>,
+}
+
+impl ReceiptPollerTask {
+    /// Polls the receipts until all the handles are dropped and nothing is awaited anymore.
+    pub async fn run<T: CoreInteractionWrapper>(mut self, core_interaction_wrapper: &T) {
+        let mut timer = time::interval(self.tick_interval);
+        // The ticks missed while idle would otherwise be made up for by a burst of queries
+        timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut handles_dropped = false;
+
+        loop {
+            if self.awaited.is_empty() {
+                if handles_dropped {
+                    return;
+                }
+                // There is nothing to poll until the first registration
+                match self.registrations.recv().await {
+                    Some(registration) => self.register(registration),
+                    None => return,
+                }
+            }
+            timer.tick().await;
+
+            handles_dropped |= self.accept_registrations();
+            self.forget_abandoned();
+            if let Err(err) = self.poll(core_interaction_wrapper).await {
+                vlog::warn!(
+                    "Failed to query the ForcedExit transaction receipts: {}",
+                    err
+                );
+            }
+        }
+    }
+
+    fn register(&mut self, (tx_hash, sender): Registration) {
+        let senders = self.awaited.entry(tx_hash).or_default();
+        if senders.is_empty() {
+            self.queue.push_back(tx_hash);
+        }
+        senders.push(sender);
+    }
+
+    /// Takes all the pending registrations, returns whether all the handles are dropped.
+    fn accept_registrations(&mut self) -> bool {
+        loop {
+            match self.registrations.try_recv() {
+                Ok(registration) => self.register(registration),
+                Err(mpsc::error::TryRecvError::Empty) => return false,
+                Err(mpsc::error::TryRecvError::Disconnected) => return true,
+            }
+        }
+    }
+
+    /// Stops polling the transactions nobody waits for anymore, e.g. after the timeout.
+    fn forget_abandoned(&mut self) {
+        self.awaited.retain(|_, senders| {
+            senders.retain(|sender| !sender.is_closed());
+            !senders.is_empty()
+        });
+        let awaited = &self.awaited;
+        self.queue.retain(|tx_hash| awaited.contains_key(tx_hash));
+    }
+
+    async fn poll<T: CoreInteractionWrapper>(
+        &mut self,
+        core_interaction_wrapper: &T,
+    ) -> anyhow::Result<()> {
+        let batch_size = self.batch_size.min(self.queue.len());
+        if batch_size == 0 {
+            return Ok(());
+        }
+        let batch: Vec<TxHash> = self.queue.drain(..batch_size).collect();
+        self.queue.extend(batch.iter().copied());
+
+        metrics::histogram!(
+            "forced_exit_requests.receipt_poller.batch_size",
+            batch.len() as f64
+        );
+        let receipts = core_interaction_wrapper.get_receipts(&batch).await?;
+
+        let batch: HashMap<String, TxHash> = batch
+            .into_iter()
+            .map(|tx_hash| (hex::encode(tx_hash.as_ref()), tx_hash))
+            .collect();
+        for receipt in receipts {
+            let senders = batch
+                .get(&receipt.tx_hash)
+                .and_then(|tx_hash| self.awaited.remove(tx_hash));
+            for sender in senders.into_iter().flatten() {
+                // The waiter may have given up in the meantime
+                let _ = sender.send(receipt.clone());
+            }
+        }
+        let awaited = &self.awaited;
+        self.queue.retain(|tx_hash| awaited.contains_key(tx_hash));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future::join_all;
+
+    use super::*;
+    use crate::test::MockCoreInteractionWrapper;
+
+    const TICK: Duration = Duration::from_millis(10);
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn hashes(count: u8) -> Vec<TxHash> {
+        (0..count)
+            .map(|i| TxHash::from_slice(&[i; 32]).unwrap())
+            .collect()
+    }
+
+    async fn wait_all(poller: ReceiptPoller, hashes: &[TxHash]) -> Vec<Option<TxReceiptResponse>> {
+        join_all(
+            hashes
+                .iter()
+                .map(|tx_hash| poller.wait_for_receipt(*tx_hash, TIMEOUT)),
+        )
+        .await
+        .into_iter()
+        .map(Result::unwrap)
+        .collect()
+    }
+
+    #[tokio::test]
+    async fn single_query_resolves_all_the_waiters() {
+        let core_interaction_wrapper = MockCoreInteractionWrapper::default();
+        let (poller, task) = ReceiptPoller::new(TICK, 500);
+        let hashes = hashes(200);
+
+        let (receipts, ()) = futures::join!(
+            wait_all(poller, &hashes),
+            task.run(&core_interaction_wrapper)
+        );
+
+        for (tx_hash, receipt) in hashes.iter().zip(receipts) {
+            assert_eq!(receipt.unwrap().tx_hash, hex::encode(tx_hash.as_ref()));
+        }
+        let queries = core_interaction_wrapper.receipt_queries.lock().unwrap();
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[0].len(), 200);
+    }
+
+    #[tokio::test]
+    async fn hashes_are_queried_in_batches() {
+        let core_interaction_wrapper = MockCoreInteractionWrapper {
+            tx_receipt: None,
+            ..Default::default()
+        };
+        let (poller, task) = ReceiptPoller::new(TICK, 40);
+        let hashes = hashes(100);
+        // Only the last transaction is not executed yet
+        let receipt = MockCoreInteractionWrapper::default().tx_receipt.unwrap();
+        for tx_hash in &hashes[..99] {
+            core_interaction_wrapper
+                .lock_receipts()
+                .insert(*tx_hash, receipt.clone());
+        }
+
+        let waiters = async {
+            let first = poller.wait_for_receipt(hashes[99], TICK * 5);
+            let rest = wait_all(poller.clone(), &hashes[..99]);
+            let (first, rest) = futures::join!(first, rest);
+            drop(poller);
+            (first.unwrap(), rest)
+        };
+        let ((first, rest), ()) = futures::join!(waiters, task.run(&core_interaction_wrapper));
+
+        assert!(first.is_none());
+        assert!(rest.iter().all(Option::is_some));
+        let queries = core_interaction_wrapper.receipt_queries.lock().unwrap();
+        // A query per tick, none of them exceeding the batch size
+        assert!(queries.iter().all(|query| query.len() <= 40));
+        assert_eq!(queries[0].len(), 40);
+        assert_eq!(queries[1].len(), 40);
+        // Every transaction is queried on the first 3 ticks
+        let queried: std::collections::HashSet<_> = queries[..3].iter().flatten().collect();
+        assert_eq!(queried.len(), 100);
+        // The unexecuted transaction is not polled after its waiter gives up
+        assert_eq!(queries.last().unwrap(), &vec![hashes[99]]);
+    }
+}
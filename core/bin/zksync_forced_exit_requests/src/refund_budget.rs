@@ -0,0 +1,227 @@
+//! The refunds sent without the approval of the operators are limited by the amount
+//! returned per hour, so a bug in the matching can not drain the sender account.
+//!
+//! The limit is a token bucket of `max_refunded_amount_per_hour` wei refilled continuously
+//! over the hour. The refunds which do not fit into it stay pending until it is refilled.
+//! The refunds approved by the operators are sent regardless, though they use the bucket up as well.
+//!
+//! The bucket is not persisted. On startup it is reduced by the amount refunded within
+//! the last hour, so restarting the sender does not refill it.
+
+use std::time::{Duration, Instant};
+
+use num::{BigUint, ToPrimitive, Zero};
+
+const HOUR_MILLIS: u64 = 60 * 60 * 1000;
+
+/// Whether the refund can be sent within the limit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RefundBudgetDecision {
+    /// The refund is sent right away, its amount is taken from the bucket.
+    Send,
+    /// The bucket has less than the amount, it has the whole amount after the delay.
+    Defer(Duration),
+}
+
+#[derive(Debug)]
+pub struct RefundBudget {
+    // Zero if the refunds are not limited
+    hourly_limit: BigUint,
+    available: BigUint,
+    refilled_at: Instant,
+    // Whether the refunds sent before the start are taken into account
+    restored: bool,
+}
+
+impl RefundBudget {
+    pub fn new(hourly_limit: BigUint) -> Self {
+        Self::new_at(hourly_limit, Instant::now())
+    }
+
+    fn new_at(hourly_limit: BigUint, now: Instant) -> Self {
+        Self {
+            available: hourly_limit.clone(),
+            hourly_limit,
+            refilled_at: now,
+            restored: false,
+        }
+    }
+
+    pub fn is_limited(&self) -> bool {
+        !self.hourly_limit.is_zero()
+    }
+
+    pub fn is_restored(&self) -> bool {
+        self.restored
+    }
+
+    /// Takes the amount refunded before the start from the bucket, once.
+    pub fn restore(&mut self, refunded: &BigUint) {
+        if !self.restored {
+            self.spend(refunded);
+            self.restored = true;
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_millis() as u64;
+        let refilled = &self.hourly_limit * elapsed / HOUR_MILLIS;
+        // The time is only accounted for once it has refilled anything, so the short
+        // intervals add up instead of being rounded down to nothing
+        if !refilled.is_zero() {
+            self.available = (&self.available + refilled).min(self.hourly_limit.clone());
+            self.refilled_at = now;
+        }
+    }
+
+    /// Decides whether the refund of the amount can be sent. The amount is taken
+    /// from the bucket if it can, otherwise the refund is expected to wait.
+    pub fn try_spend(&mut self, amount: &BigUint) -> RefundBudgetDecision {
+        self.try_spend_at(amount, Instant::now())
+    }
+
+    fn try_spend_at(&mut self, amount: &BigUint, now: Instant) -> RefundBudgetDecision {
+        if !self.is_limited() {
+            return RefundBudgetDecision::Send;
+        }
+
+        self.refill(now);
+        if self.available < *amount {
+            let missing = amount - &self.available;
+            let millis = missing * HOUR_MILLIS / &self.hourly_limit + 1u32;
+            return RefundBudgetDecision::Defer(Duration::from_millis(
+                millis.to_u64().unwrap_or(u64::MAX),
+            ));
+        }
+        self.available -= amount;
+        RefundBudgetDecision::Send
+    }
+
+    /// Takes the amount of the refund sent regardless of the limit, leaving nothing
+    /// in the bucket if it exceeds the available amount.
+    pub fn spend(&mut self, amount: &BigUint) {
+        self.spend_at(amount, Instant::now())
+    }
+
+    fn spend_at(&mut self, amount: &BigUint, now: Instant) {
+        if !self.is_limited() {
+            return;
+        }
+
+        self.refill(now);
+        self.available = if self.available > *amount {
+            &self.available - amount
+        } else {
+            BigUint::zero()
+        };
+    }
+
+    /// The amount which can be refunded right now, `None` if the refunds are not limited.
+    pub fn available(&mut self) -> Option<BigUint> {
+        self.available_at(Instant::now())
+    }
+
+    fn available_at(&mut self, now: Instant) -> Option<BigUint> {
+        if !self.is_limited() {
+            return None;
+        }
+
+        self.refill(now);
+        Some(self.available.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amount(amount: u64) -> BigUint {
+        BigUint::from(amount)
+    }
+
+    #[test]
+    fn unlimited_refunds_are_sent_right_away() {
+        let start = Instant::now();
+        let mut budget = RefundBudget::new_at(BigUint::zero(), start);
+
+        for _ in 0..100 {
+            assert_eq!(
+                budget.try_spend_at(&amount(u64::MAX), start),
+                RefundBudgetDecision::Send
+            );
+        }
+        assert_eq!(budget.available_at(start), None);
+    }
+
+    #[test]
+    fn refunds_above_the_limit_are_deferred_until_the_refill() {
+        let start = Instant::now();
+        let mut budget = RefundBudget::new_at(amount(3600), start);
+
+        assert_eq!(
+            budget.try_spend_at(&amount(3000), start),
+            RefundBudgetDecision::Send
+        );
+        // 600 are left, the bucket is refilled by one per second
+        assert_eq!(
+            budget.try_spend_at(&amount(1000), start),
+            RefundBudgetDecision::Defer(Duration::from_millis(400_001))
+        );
+        assert_eq!(
+            budget.try_spend_at(&amount(600), start),
+            RefundBudgetDecision::Send
+        );
+        assert_eq!(budget.available_at(start), Some(BigUint::zero()));
+
+        let later = start + Duration::from_secs(100);
+        assert_eq!(budget.available_at(later), Some(amount(100)));
+        assert!(matches!(
+            budget.try_spend_at(&amount(1000), later),
+            RefundBudgetDecision::Defer(_)
+        ));
+        assert_eq!(
+            budget.try_spend_at(&amount(1000), later + Duration::from_secs(900)),
+            RefundBudgetDecision::Send
+        );
+
+        // The idle time does not allow more than the hourly limit
+        let much_later = start + Duration::from_secs(24 * 60 * 60);
+        assert_eq!(budget.available_at(much_later), Some(amount(3600)));
+    }
+
+    #[test]
+    fn short_intervals_add_up() {
+        let start = Instant::now();
+        // Less than one wei per millisecond
+        let mut budget = RefundBudget::new_at(amount(36), start);
+        budget.spend_at(&amount(36), start);
+
+        for second in 1..=100 {
+            budget.available_at(start + Duration::from_secs(second));
+        }
+        assert_eq!(
+            budget.available_at(start + Duration::from_secs(100)),
+            Some(amount(1))
+        );
+    }
+
+    #[test]
+    fn approved_and_restored_refunds_use_the_budget_up() {
+        let start = Instant::now();
+        let mut budget = RefundBudget::new_at(amount(3600), start);
+
+        budget.restore(&amount(3000));
+        assert!(budget.is_restored());
+        // The amount refunded before the start is only taken once
+        budget.restore(&amount(3000));
+        assert_eq!(budget.available_at(start), Some(amount(600)));
+
+        // The approved refund is sent in full, leaving nothing for the rest
+        budget.spend_at(&amount(5000), start);
+        assert_eq!(budget.available_at(start), Some(BigUint::zero()));
+        assert!(matches!(
+            budget.try_spend_at(&amount(1), start),
+            RefundBudgetDecision::Defer(_)
+        ));
+    }
+}
@@ -0,0 +1,1400 @@
+//! Running the component separately from the server, without the direct access
+//! to its database.
+//!
+//! The requests stay in the database of the server, the remote component only
+//! watches the payments, matches them against the requests, sends the `ForcedExit`
+//! transactions and waits for them to be executed. The data it needs is loaded
+//! through the operators part of the server API, and the transactions are
+//! submitted through the public API the same way as any other ones.
+//!
+//! The features listed in `Capabilities` are not available in this mode: the
+//! requests are not escalated to L1, the payments are not recorded for the replay,
+//! the payments injected by the operators are not processed, the requests
+//! are never held when their targets become active and the payments registered
+//! by the partners in advance are matched by their amounts, the payments are
+//! not refunded, the failed requests are not retried and the pacing of the sender
+//! is only set by the config. The notifications are still delivered by the server,
+//! since they are produced by its database.
+
+use std::time;
+
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{encode, EncodingKey, Header};
+use num::BigUint;
+use serde::{Deserialize, Serialize};
+
+use zksync_api_client::rest::{
+    client::{Client, ClientError},
+    error::ErrorBody,
+    forced_exit_requests::remote::{
+        BeginFulfillmentRequest, CancelRequestRequest, DeleteOldRequestsRequest,
+        SetFulfilledByRequest, SetMatchSchemeRequest, TxReceiptsRequest,
+    },
+};
+use zksync_api_types::{
+    v02::{
+        fee::{ApiFee, ApiTxFeeTypes, TxInBatchFeeRequest},
+        token::ApiToken,
+        ResultStatus,
+    },
+    TxWithSignature,
+};
+use zksync_config::ForcedExitRequestsConfig;
+use zksync_storage::chain::operations_ext::records::TxReceiptResponse;
+use zksync_types::{
+    forced_exit_requests::{
+        ExpectedForcedExitPayment, ForcedExitBacklogReport, ForcedExitCancellationKind,
+        ForcedExitPacing, ForcedExitPacingState, ForcedExitPayment, ForcedExitPipelineVersion,
+        ForcedExitProcessingFailure, ForcedExitRefund, ForcedExitRefundId, ForcedExitRefundStatus,
+        ForcedExitRequest, ForcedExitRequestActiveTarget, ForcedExitRequestDelivery,
+        ForcedExitRequestDeliveryId, ForcedExitRequestEscalation, ForcedExitRequestId,
+        ForcedExitRetry, ForcedExitRetryId, ForcedExitTargetCheck, InjectedForcedExitPayment,
+        InjectedForcedExitPaymentId, InterruptedSubmission, PaymentMatchScheme, PaymentSourceState,
+        SaveForcedExitRefundQuery, SaveForcedExitRequestNoteQuery, SkippedForcedExit,
+        SubmissionError, UnmatchedPaymentReason,
+    },
+    tx::{TxEthSignatureVariant, TxHash},
+    AccountId, Address, Nonce, SignedZkSyncTx, Token, TokenId, TokenKind, TokenLike, H256,
+};
+
+use crate::{
+    core_interaction_wrapper::{
+        append_chunk, combine_batch, ensure_batch_not_empty, Capabilities, CoreInteractionWrapper,
+    },
+    eth_watch::{run_watcher, EthHttpClient},
+    shutdown::ShutdownSignal,
+    singleton::SingletonLock,
+};
+
+/// The auth tokens are issued for every request, so they may be short-lived.
+const AUTH_TOKEN_LIFETIME: time::Duration = time::Duration::from_secs(60);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PayloadAuthToken {
+    /// Subject (whom auth token refers to).
+    sub: String,
+    /// Expiration time (as UTC timestamp).
+    exp: usize,
+}
+
+// The codes of the API errors caused by the state of the server rather than by the transactions:
+// the storage, the internal and the core server communication errors
+const RETRYABLE_API_CODES: &[u32] = &[300, 600, 607];
+
+fn unsupported(method: &str) -> anyhow::Error {
+    anyhow::anyhow!("`{}` is not supported by the remote backend", method)
+}
+
+/// Tells the connection failures apart from the error statuses returned by the server.
+fn client_submission_error(err: ClientError) -> SubmissionError {
+    match err {
+        ClientError::BadRequest { http_code, body } => SubmissionError::http(
+            http_code.as_u16(),
+            body.code.map(|code| code as u32),
+            error_body_message(&body),
+        ),
+        ClientError::NotFound(url) => {
+            SubmissionError::http(404, None, format!("Method {} not found", url))
+        }
+        ClientError::Parse(err) => {
+            SubmissionError::transport(format!("The response could not be read: {}", err))
+        }
+        ClientError::Other(err) => match err.status() {
+            Some(status) => SubmissionError::http(status.as_u16(), None, err.to_string()),
+            None => SubmissionError::transport(err.to_string()),
+        },
+    }
+}
+
+fn error_body_message(body: &ErrorBody) -> String {
+    if body.detail.is_empty() {
+        body.title.clone()
+    } else {
+        format!("{}: {}", body.title, body.detail)
+    }
+}
+
+// The Ethereum signatures are only attached if the sender is configured to make them
+fn with_eth_signature(tx: SignedZkSyncTx) -> TxWithSignature {
+    TxWithSignature {
+        tx: tx.tx,
+        signature: TxEthSignatureVariant::Single(tx.eth_sign_data.map(|data| data.signature)),
+    }
+}
+
+/// The transactions rejected by the server along with the error returned by the API.
+fn api_rejection(error: Option<serde_json::Value>) -> SubmissionError {
+    let error = error.unwrap_or_default();
+    let api_code = error
+        .get("code")
+        .and_then(serde_json::Value::as_u64)
+        .map(|code| code as u32);
+    let message = error
+        .get("message")
+        .and_then(serde_json::Value::as_str)
+        .map_or_else(|| error.to_string(), str::to_owned);
+    let retryable = matches!(api_code, Some(code) if RETRYABLE_API_CODES.contains(&code));
+
+    SubmissionError::rejected(api_code, message, retryable)
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiCoreInteractionWrapper {
+    client: Client,
+    secret_auth: String,
+}
+
+impl ApiCoreInteractionWrapper {
+    pub fn new(api_url: String, secret_auth: String) -> Self {
+        Self {
+            client: Client::new(api_url),
+            secret_auth,
+        }
+    }
+
+    /// Encodes the JWT auth token with the admin secret shared with the server.
+    fn auth_token(&self) -> anyhow::Result<String> {
+        let exp = time::UNIX_EPOCH.elapsed()? + AUTH_TOKEN_LIFETIME;
+        let payload = PayloadAuthToken {
+            sub: "forced_exit_requests".to_string(),
+            exp: exp.as_secs() as usize,
+        };
+        let token = encode(
+            &Header::default(),
+            &payload,
+            &EncodingKey::from_secret(self.secret_auth.as_ref()),
+        )?;
+        Ok(token)
+    }
+
+    /// Estimates the outcome of fulfilling the paid requests, which have not been sent yet.
+    pub async fn simulate_backlog(&self) -> anyhow::Result<ForcedExitBacklogReport> {
+        let report = self
+            .client
+            .simulate_forced_exit_backlog(&self.auth_token()?)
+            .await?;
+        Ok(report)
+    }
+}
+
+#[async_trait::async_trait]
+impl CoreInteractionWrapper for ApiCoreInteractionWrapper {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            escalations: false,
+            payment_log: false,
+            injected_payments: false,
+            active_targets: false,
+            tokens: false,
+            expected_payments: false,
+            pipeline_versions: false,
+            overpayments: false,
+            refunds: false,
+            retries: false,
+            pacing_overrides: false,
+            target_account_ids: false,
+            submission_records: false,
+        }
+    }
+
+    async fn get_nonce(&self, account_id: AccountId) -> anyhow::Result<Option<Nonce>> {
+        let nonce = self
+            .client
+            .remote_account_nonce(account_id, &self.auth_token()?)
+            .await?;
+        Ok(nonce)
+    }
+
+    async fn get_pending_nonce(&self, account_id: AccountId) -> anyhow::Result<Option<Nonce>> {
+        let nonce = self
+            .client
+            .remote_account_pending_nonce(account_id, &self.auth_token()?)
+            .await?;
+        Ok(nonce)
+    }
+
+    async fn get_unconfirmed_requests(&self) -> anyhow::Result<Vec<ForcedExitRequest>> {
+        let requests = self
+            .client
+            .unconfirmed_forced_exit_requests(&self.auth_token()?)
+            .await?;
+        Ok(requests)
+    }
+
+    async fn set_fulfilled_at(&self, id: i64) -> anyhow::Result<()> {
+        self.client
+            .set_forced_exit_request_fulfilled(id, &self.auth_token()?)
+            .await?;
+        vlog::info!("ForcedExit request with id {} was fulfilled", id);
+        Ok(())
+    }
+
+    async fn set_fulfilled_by(
+        &self,
+        id: ForcedExitRequestId,
+        value: Option<Vec<TxHash>>,
+    ) -> anyhow::Result<()> {
+        self.client
+            .set_forced_exit_request_fulfilled_by(
+                id,
+                &SetFulfilledByRequest {
+                    fulfilled_by: value,
+                },
+                &self.auth_token()?,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn cancel_request(
+        &self,
+        id: ForcedExitRequestId,
+        kind: ForcedExitCancellationKind,
+    ) -> anyhow::Result<()> {
+        let cancelled = self
+            .client
+            .cancel_forced_exit_request(id, &CancelRequestRequest { kind }, &self.auth_token()?)
+            .await?;
+        if cancelled.is_some() {
+            vlog::info!("ForcedExit request with id {} was cancelled: {}", id, kind);
+        }
+        Ok(())
+    }
+
+    async fn set_match_scheme(
+        &self,
+        id: ForcedExitRequestId,
+        match_scheme: PaymentMatchScheme,
+        paid_amount: &BigUint,
+        payment_tx_hash: Option<H256>,
+    ) -> anyhow::Result<()> {
+        self.client
+            .set_forced_exit_request_match_scheme(
+                id,
+                &SetMatchSchemeRequest {
+                    match_scheme,
+                    payment_tx_hash,
+                    paid_amount: Some(paid_amount.to_string()),
+                },
+                &self.auth_token()?,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn begin_fulfillment(
+        &self,
+        id: ForcedExitRequestId,
+        payment_tx_hash: Option<H256>,
+    ) -> anyhow::Result<bool> {
+        let started = self
+            .client
+            .begin_forced_exit_request_fulfillment(
+                id,
+                &BeginFulfillmentRequest { payment_tx_hash },
+                &self.auth_token()?,
+            )
+            .await?;
+        Ok(started)
+    }
+
+    async fn get_request_by_id(&self, id: i64) -> anyhow::Result<Option<ForcedExitRequest>> {
+        let request = self
+            .client
+            .remote_forced_exit_request(id, &self.auth_token()?)
+            .await?;
+        Ok(request)
+    }
+
+    async fn get_request_by_public_id(
+        &self,
+        public_id: ForcedExitRequestId,
+    ) -> anyhow::Result<Option<ForcedExitRequest>> {
+        let request = self
+            .client
+            .remote_forced_exit_request_by_public_id(public_id, &self.auth_token()?)
+            .await?;
+        Ok(request)
+    }
+
+    async fn get_requests_by_closest_amount(
+        &self,
+        _max_amount: &BigUint,
+        _submission_time: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<ForcedExitRequest>> {
+        Err(unsupported("get_requests_by_closest_amount"))
+    }
+
+    async fn get_receipt(&self, tx_hash: TxHash) -> anyhow::Result<Option<TxReceiptResponse>> {
+        let mut receipts = self.get_receipts(&[tx_hash]).await?;
+        Ok(receipts.pop())
+    }
+
+    async fn get_receipts(&self, tx_hashes: &[TxHash]) -> anyhow::Result<Vec<TxReceiptResponse>> {
+        let receipts = self
+            .client
+            .forced_exit_tx_receipts(
+                &TxReceiptsRequest {
+                    tx_hashes: tx_hashes.to_vec(),
+                },
+                &self.auth_token()?,
+            )
+            .await?;
+
+        // The receipts are shaped the same way as the ones loaded from the database
+        Ok(receipts
+            .into_iter()
+            .map(|receipt| TxReceiptResponse {
+                tx_hash: hex::encode(receipt.tx_hash.as_ref()),
+                block_number: receipt.block_number,
+                success: receipt.success,
+                verified: receipt.verified,
+                fail_reason: receipt.fail_reason,
+                prover_run: None,
+            })
+            .collect())
+    }
+
+    async fn send_and_save_txs_batch(
+        &mut self,
+        request: &ForcedExitRequest,
+        txs: Vec<SignedZkSyncTx>,
+    ) -> anyhow::Result<Vec<TxHash>> {
+        ensure_batch_not_empty(request, &txs)?;
+        let hashes: Vec<TxHash> = txs.iter().map(|tx| tx.hash()).collect();
+
+        let txs = txs.into_iter().map(with_eth_signature).collect();
+        let response = self
+            .client
+            .submit_batch(txs, None)
+            .await
+            .map_err(client_submission_error)?;
+        if let ResultStatus::Error = response.status {
+            return Err(api_rejection(response.error).into());
+        }
+        self.set_fulfilled_by(request.id, Some(hashes.clone()))
+            .await?;
+
+        Ok(hashes)
+    }
+
+    async fn send_and_save_combined_batch(
+        &mut self,
+        parts: Vec<(ForcedExitRequestId, Vec<SignedZkSyncTx>)>,
+    ) -> anyhow::Result<Vec<Vec<TxHash>>> {
+        let ids: Vec<ForcedExitRequestId> = parts.iter().map(|(id, _)| *id).collect();
+        let (txs, hashes) = combine_batch(parts)?;
+
+        let txs = txs.into_iter().map(with_eth_signature).collect();
+        let response = self
+            .client
+            .submit_batch(txs, None)
+            .await
+            .map_err(client_submission_error)?;
+        if let ResultStatus::Error = response.status {
+            return Err(api_rejection(response.error).into());
+        }
+        for (id, request_hashes) in ids.into_iter().zip(&hashes) {
+            self.set_fulfilled_by(id, Some(request_hashes.clone()))
+                .await?;
+        }
+
+        Ok(hashes)
+    }
+
+    async fn send_and_save_txs_chunk(
+        &mut self,
+        request: &ForcedExitRequest,
+        sent: &[TxHash],
+        txs: Vec<SignedZkSyncTx>,
+    ) -> anyhow::Result<Vec<TxHash>> {
+        ensure_batch_not_empty(request, &txs)?;
+        let hashes: Vec<TxHash> = txs.iter().map(|tx| tx.hash()).collect();
+        let fulfilled_by = append_chunk(sent, &txs);
+
+        let txs = txs.into_iter().map(with_eth_signature).collect();
+        let response = self
+            .client
+            .submit_batch(txs, None)
+            .await
+            .map_err(client_submission_error)?;
+        if let ResultStatus::Error = response.status {
+            return Err(api_rejection(response.error).into());
+        }
+        self.set_fulfilled_by(request.id, Some(fulfilled_by))
+            .await?;
+
+        Ok(hashes)
+    }
+
+    async fn get_oldest_unfulfilled_request(&self) -> anyhow::Result<Option<ForcedExitRequest>> {
+        let request = self
+            .client
+            .oldest_unfulfilled_forced_exit_request(&self.auth_token()?)
+            .await?;
+        Ok(request)
+    }
+
+    async fn delete_old_unfulfilled_requests(
+        &self,
+        deleting_threshold: chrono::Duration,
+    ) -> anyhow::Result<()> {
+        self.client
+            .delete_old_forced_exit_requests(
+                &DeleteOldRequestsRequest {
+                    deleting_threshold_millis: deleting_threshold.num_milliseconds(),
+                },
+                &self.auth_token()?,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn check_forced_exit_request(
+        &self,
+        request: &ForcedExitRequest,
+    ) -> anyhow::Result<ForcedExitTargetCheck> {
+        let target_check = self
+            .client
+            .forced_exit_target_check(request.target, &self.auth_token()?)
+            .await?;
+        Ok(target_check)
+    }
+
+    async fn record_failure(
+        &self,
+        _id: ForcedExitRequestId,
+        _token: TokenId,
+    ) -> anyhow::Result<u32> {
+        Err(unsupported("record_failure"))
+    }
+
+    async fn record_processing_failure(
+        &self,
+        _failure: ForcedExitProcessingFailure,
+    ) -> anyhow::Result<()> {
+        Err(unsupported("record_processing_failure"))
+    }
+
+    async fn record_pipeline_version(
+        &self,
+        _version: ForcedExitPipelineVersion,
+    ) -> anyhow::Result<()> {
+        Err(unsupported("record_pipeline_version"))
+    }
+
+    async fn get_account_id(&self, address: Address) -> anyhow::Result<Option<AccountId>> {
+        let account_id = self
+            .client
+            .remote_account_id(address, &self.auth_token()?)
+            .await?;
+        Ok(account_id)
+    }
+
+    async fn record_target_account_id(
+        &self,
+        _id: ForcedExitRequestId,
+        _target_account_id: AccountId,
+    ) -> anyhow::Result<()> {
+        Err(unsupported("record_target_account_id"))
+    }
+
+    async fn get_interrupted_submissions(&self) -> anyhow::Result<Vec<InterruptedSubmission>> {
+        Err(unsupported("get_interrupted_submissions"))
+    }
+
+    async fn store_note(&self, _note: SaveForcedExitRequestNoteQuery) -> anyhow::Result<()> {
+        Err(unsupported("store_note"))
+    }
+
+    async fn get_token_address(&self, _token: TokenId) -> anyhow::Result<Option<Address>> {
+        Err(unsupported("get_token_address"))
+    }
+
+    async fn get_token(&self, token: TokenId) -> anyhow::Result<Option<Token>> {
+        let response = self.client.token_by_id(&TokenLike::Id(token)).await?;
+        if let ResultStatus::Error = response.status {
+            return Err(api_rejection(response.error).into());
+        }
+        let token: Option<ApiToken> = serde_json::from_value(response.result.unwrap_or_default())?;
+
+        Ok(token.map(|token| {
+            Token::new(
+                token.id,
+                token.address,
+                &token.symbol,
+                token.decimals,
+                TokenKind::ERC20,
+            )
+        }))
+    }
+
+    async fn store_escalation(
+        &self,
+        _escalation: ForcedExitRequestEscalation,
+    ) -> anyhow::Result<()> {
+        Err(unsupported("store_escalation"))
+    }
+
+    async fn get_escalation(
+        &self,
+        _id: ForcedExitRequestId,
+    ) -> anyhow::Result<Option<ForcedExitRequestEscalation>> {
+        Err(unsupported("get_escalation"))
+    }
+
+    async fn store_active_target(
+        &self,
+        _active_target: ForcedExitRequestActiveTarget,
+    ) -> anyhow::Result<()> {
+        Err(unsupported("store_active_target"))
+    }
+
+    async fn get_held_active_targets(&self) -> anyhow::Result<Vec<ForcedExitRequestActiveTarget>> {
+        Err(unsupported("get_held_active_targets"))
+    }
+
+    async fn resume_active_target(
+        &self,
+        _id: ForcedExitRequestId,
+        _resumed_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        Err(unsupported("resume_active_target"))
+    }
+
+    async fn fail_active_target(
+        &self,
+        _id: ForcedExitRequestId,
+        _failed_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        Err(unsupported("fail_active_target"))
+    }
+
+    async fn skip_tokens(
+        &self,
+        _id: ForcedExitRequestId,
+        _skipped: &[SkippedForcedExit],
+    ) -> anyhow::Result<()> {
+        Err(unsupported("skip_tokens"))
+    }
+
+    async fn store_payment(&self, _payment: &ForcedExitPayment) -> anyhow::Result<()> {
+        Err(unsupported("store_payment"))
+    }
+
+    async fn store_unmatched_payment(
+        &self,
+        _payment: &ForcedExitPayment,
+        _reason: UnmatchedPaymentReason,
+    ) -> anyhow::Result<()> {
+        Err(unsupported("store_unmatched_payment"))
+    }
+
+    async fn get_payment_source_states(&self) -> anyhow::Result<Vec<PaymentSourceState>> {
+        let states = self
+            .client
+            .forced_exit_payment_sources(&self.auth_token()?)
+            .await?;
+        Ok(states)
+    }
+
+    async fn get_expected_payment(
+        &self,
+        _eth_tx_hash: H256,
+    ) -> anyhow::Result<Option<ExpectedForcedExitPayment>> {
+        Err(unsupported("get_expected_payment"))
+    }
+
+    async fn flag_expected_payment(
+        &self,
+        _eth_tx_hash: H256,
+        _paid: &BigUint,
+    ) -> anyhow::Result<()> {
+        Err(unsupported("flag_expected_payment"))
+    }
+
+    async fn get_injected_payments(
+        &self,
+        _limit: u32,
+    ) -> anyhow::Result<Vec<InjectedForcedExitPayment>> {
+        Err(unsupported("get_injected_payments"))
+    }
+
+    async fn delete_injected_payment(
+        &self,
+        _id: InjectedForcedExitPaymentId,
+    ) -> anyhow::Result<()> {
+        Err(unsupported("delete_injected_payment"))
+    }
+
+    async fn get_pending_retries(&self, _limit: u32) -> anyhow::Result<Vec<ForcedExitRetry>> {
+        Err(unsupported("get_pending_retries"))
+    }
+
+    async fn complete_retry(
+        &self,
+        _id: ForcedExitRetryId,
+        _tx_hashes: &[TxHash],
+        _error: Option<&str>,
+    ) -> anyhow::Result<()> {
+        Err(unsupported("complete_retry"))
+    }
+
+    async fn get_pacing_override(&self) -> anyhow::Result<Option<ForcedExitPacing>> {
+        Err(unsupported("get_pacing_override"))
+    }
+
+    async fn report_pacing(
+        &self,
+        _sender: Address,
+        _state: &ForcedExitPacingState,
+    ) -> anyhow::Result<()> {
+        Err(unsupported("report_pacing"))
+    }
+
+    // The outbox is dispatched by the server
+
+    async fn get_pending_deliveries(
+        &self,
+        _limit: u32,
+        _callbacks: bool,
+    ) -> anyhow::Result<Vec<ForcedExitRequestDelivery>> {
+        Err(unsupported("get_pending_deliveries"))
+    }
+
+    async fn mark_delivered(&self, _id: ForcedExitRequestDeliveryId) -> anyhow::Result<()> {
+        Err(unsupported("mark_delivered"))
+    }
+
+    async fn record_delivery_failure(
+        &self,
+        _id: ForcedExitRequestDeliveryId,
+        _error: String,
+        _next_attempt_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        Err(unsupported("record_delivery_failure"))
+    }
+
+    async fn mark_delivery_failed(
+        &self,
+        _id: ForcedExitRequestDeliveryId,
+        _error: String,
+    ) -> anyhow::Result<()> {
+        Err(unsupported("mark_delivery_failed"))
+    }
+
+    async fn store_refund(
+        &self,
+        _refund: SaveForcedExitRefundQuery,
+    ) -> anyhow::Result<Option<ForcedExitRefund>> {
+        Err(unsupported("store_refund"))
+    }
+
+    async fn get_unsettled_refunds(
+        &self,
+        _max_attempts: u32,
+    ) -> anyhow::Result<Vec<ForcedExitRefund>> {
+        Err(unsupported("get_unsettled_refunds"))
+    }
+
+    async fn get_refunded_amount_since(&self, _since: DateTime<Utc>) -> anyhow::Result<BigUint> {
+        Err(unsupported("get_refunded_amount_since"))
+    }
+
+    async fn get_balance(
+        &self,
+        _account_id: AccountId,
+        _token: TokenId,
+    ) -> anyhow::Result<BigUint> {
+        Err(unsupported("get_balance"))
+    }
+
+    // The batch is submitted through the public API, so the fee is quoted by it as well
+    async fn get_forced_exit_batch_fee(
+        &self,
+        target: Address,
+        forced_exits: usize,
+        fee_payer: Address,
+        token: TokenId,
+    ) -> anyhow::Result<BigUint> {
+        let mut txs = vec![
+            TxInBatchFeeRequest {
+                tx_type: ApiTxFeeTypes::ForcedExit,
+                address: target,
+            };
+            forced_exits
+        ];
+        txs.push(TxInBatchFeeRequest {
+            tx_type: ApiTxFeeTypes::Transfer,
+            address: fee_payer,
+        });
+        let response = self.client.get_batch_fee(txs, TokenLike::Id(token)).await?;
+        if let ResultStatus::Error = response.status {
+            return Err(api_rejection(response.error).into());
+        }
+        let fee: ApiFee = serde_json::from_value(response.result.unwrap_or_default())?;
+
+        Ok(fee.total_fee)
+    }
+
+    async fn send_refund(
+        &mut self,
+        _id: ForcedExitRefundId,
+        _tx: SignedZkSyncTx,
+    ) -> anyhow::Result<TxHash> {
+        Err(unsupported("send_refund"))
+    }
+
+    async fn set_refund_status(
+        &self,
+        _id: ForcedExitRefundId,
+        _status: ForcedExitRefundStatus,
+        _tx_hash: Option<TxHash>,
+    ) -> anyhow::Result<()> {
+        Err(unsupported("set_refund_status"))
+    }
+}
+
+/// Runs the watcher talking to the server at `api_url`.
+///
+/// The sender account must already be registered on the server, it is not prepared
+/// by the remote component, since that requires the access to the mempool.
+/// Returns once the shutdown is requested and the sent requests are drained.
+pub async fn run_remote_forced_exit_contract_watcher(
+    config: ForcedExitRequestsConfig,
+    api_url: String,
+    secret_auth: String,
+    contract: Address,
+    zksync_contract: Address,
+    web3_url: String,
+    mut shutdown: ShutdownSignal,
+) -> anyhow::Result<()> {
+    // We should not proceed if the feature is disabled
+    if !config.enabled {
+        shutdown.requested().await;
+        return Ok(());
+    }
+
+    let core_interaction_wrapper = ApiCoreInteractionWrapper::new(api_url, secret_auth);
+    core_interaction_wrapper
+        .capabilities()
+        .ensure_supported(&config)?;
+
+    let sender_account_id = core_interaction_wrapper
+        .get_account_id(config.sender_account_address)
+        .await?
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "The ForcedExit sender account {:?} is not registered on the server",
+                config.sender_account_address
+            )
+        })?;
+
+    let eth_client = EthHttpClient::from_config(&web3_url, contract, &config);
+    run_watcher(
+        core_interaction_wrapper,
+        config,
+        eth_client,
+        sender_account_id,
+        zksync_contract,
+        // The remote component has no access to the database the lock is taken in
+        SingletonLock::unguarded(),
+        shutdown,
+    )
+    .await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{HashMap, HashSet},
+        ops::Add,
+        str::FromStr,
+        sync::Mutex,
+    };
+
+    use actix_web::{web, App, HttpRequest};
+    use jsonwebtoken::{decode, DecodingKey, Validation};
+    use num::BigUint;
+
+    use zksync_api_client::rest::forced_exit_requests::remote::ForcedExitTxReceipt;
+    use zksync_api_types::v02::{transaction::IncomingTxBatch, ApiVersion, Request, Response};
+    use zksync_types::{
+        forced_exit_requests::{
+            pay_exactly, ActiveTargetPolicy, FundsReceivedEvent, SubmissionErrorKind,
+        },
+        network::Network,
+        ZkSyncTx,
+    };
+
+    use super::*;
+    use crate::forced_exit_sender::{ForcedExitSender, MempoolForcedExitSender};
+
+    const TEST_SECRET_AUTH: &str = "sample";
+    const TEST_ZKSYNC_CONTRACT: Address = Address::repeat_byte(0x12);
+
+    /// The server side of the remote backend, the submitted transactions are executed right away.
+    #[derive(Default)]
+    struct MockApiState {
+        requests: Mutex<Vec<ForcedExitRequest>>,
+        batches: Mutex<Vec<Vec<TxWithSignature>>>,
+        executed: Mutex<Vec<TxHash>>,
+        fulfillment_keys: Mutex<HashSet<(ForcedExitRequestId, Option<H256>)>>,
+        // The error the batches are rejected with while it is set
+        rejection: Mutex<Option<serde_json::Value>>,
+    }
+
+    impl MockApiState {
+        fn update_request(&self, id: ForcedExitRequestId, f: impl FnOnce(&mut ForcedExitRequest)) {
+            let mut requests = self.requests.lock().unwrap();
+            let request = requests
+                .iter_mut()
+                .find(|request| request.id == id)
+                .expect("The request does not exist");
+            f(request);
+        }
+    }
+
+    // The mock is only reachable with the token signed with the shared secret
+    fn authorize(req: &HttpRequest) {
+        let token = req
+            .headers()
+            .get("Authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .expect("The request is not authorized");
+        decode::<PayloadAuthToken>(
+            token,
+            &DecodingKey::from_secret(TEST_SECRET_AUTH.as_ref()),
+            &Validation::default(),
+        )
+        .expect("Invalid auth token");
+    }
+
+    async fn unconfirmed_requests(
+        req: HttpRequest,
+        state: web::Data<MockApiState>,
+    ) -> web::Json<Vec<ForcedExitRequest>> {
+        authorize(&req);
+        let requests = state.requests.lock().unwrap();
+        web::Json(
+            requests
+                .iter()
+                .filter(|request| request.fulfilled_at.is_none())
+                .cloned()
+                .collect(),
+        )
+    }
+
+    async fn request_by_id(
+        req: HttpRequest,
+        state: web::Data<MockApiState>,
+        id: web::Path<ForcedExitRequestId>,
+    ) -> web::Json<Option<ForcedExitRequest>> {
+        authorize(&req);
+        let requests = state.requests.lock().unwrap();
+        web::Json(requests.iter().find(|request| request.id == *id).cloned())
+    }
+
+    async fn request_by_public_id(
+        req: HttpRequest,
+        state: web::Data<MockApiState>,
+        public_id: web::Path<ForcedExitRequestId>,
+    ) -> web::Json<Option<ForcedExitRequest>> {
+        authorize(&req);
+        let requests = state.requests.lock().unwrap();
+        web::Json(
+            requests
+                .iter()
+                .find(|request| request.public_id == *public_id)
+                .cloned(),
+        )
+    }
+
+    async fn set_fulfilled(
+        req: HttpRequest,
+        state: web::Data<MockApiState>,
+        id: web::Path<ForcedExitRequestId>,
+    ) -> web::Json<()> {
+        authorize(&req);
+        state.update_request(*id, |request| request.fulfilled_at = Some(Utc::now()));
+        web::Json(())
+    }
+
+    async fn set_fulfilled_by(
+        req: HttpRequest,
+        state: web::Data<MockApiState>,
+        id: web::Path<ForcedExitRequestId>,
+        params: web::Json<SetFulfilledByRequest>,
+    ) -> web::Json<()> {
+        authorize(&req);
+        let fulfilled_by = params.into_inner().fulfilled_by;
+        if fulfilled_by.is_none() {
+            state
+                .fulfillment_keys
+                .lock()
+                .unwrap()
+                .retain(|(request_id, _)| *request_id != *id);
+        }
+        state.update_request(*id, |request| request.fulfilled_by = fulfilled_by);
+        web::Json(())
+    }
+
+    async fn cancel_request(
+        req: HttpRequest,
+        state: web::Data<MockApiState>,
+        id: web::Path<ForcedExitRequestId>,
+        params: web::Json<CancelRequestRequest>,
+    ) -> web::Json<Option<ForcedExitRequest>> {
+        authorize(&req);
+        let kind = params.kind;
+        if kind.allows_reprocessing() {
+            state
+                .fulfillment_keys
+                .lock()
+                .unwrap()
+                .retain(|(request_id, _)| *request_id != *id);
+        }
+        let mut cancelled = None;
+        state.update_request(*id, |request| {
+            if kind.allows_reprocessing() {
+                request.fulfilled_by = None;
+            } else {
+                request.valid_until = request.valid_until.min(Utc::now());
+            }
+            request.cancellation = Some(kind);
+            cancelled = Some(request.clone());
+        });
+        web::Json(cancelled)
+    }
+
+    async fn begin_fulfillment(
+        req: HttpRequest,
+        state: web::Data<MockApiState>,
+        id: web::Path<ForcedExitRequestId>,
+        params: web::Json<BeginFulfillmentRequest>,
+    ) -> web::Json<bool> {
+        authorize(&req);
+        let mut keys = state.fulfillment_keys.lock().unwrap();
+        web::Json(keys.insert((*id, params.payment_tx_hash)))
+    }
+
+    async fn set_match_scheme(
+        req: HttpRequest,
+        state: web::Data<MockApiState>,
+        id: web::Path<ForcedExitRequestId>,
+        params: web::Json<SetMatchSchemeRequest>,
+    ) -> web::Json<()> {
+        authorize(&req);
+        state.update_request(*id, |request| {
+            request.match_scheme = Some(params.match_scheme);
+            request.matched_at = Some(Utc::now());
+            request.paid_amount = params.paid_amount.clone();
+        });
+        web::Json(())
+    }
+
+    async fn receipts(
+        req: HttpRequest,
+        state: web::Data<MockApiState>,
+        params: web::Json<TxReceiptsRequest>,
+    ) -> web::Json<Vec<ForcedExitTxReceipt>> {
+        authorize(&req);
+        let executed = state.executed.lock().unwrap();
+        web::Json(
+            params
+                .tx_hashes
+                .iter()
+                .filter(|hash| executed.contains(hash))
+                .map(|hash| ForcedExitTxReceipt {
+                    tx_hash: *hash,
+                    block_number: 1,
+                    success: true,
+                    verified: false,
+                    fail_reason: None,
+                })
+                .collect(),
+        )
+    }
+
+    async fn target_check(req: HttpRequest) -> web::Json<ForcedExitTargetCheck> {
+        authorize(&req);
+        web::Json(ForcedExitTargetCheck {
+            old_enough: true,
+            nonce: Some(Nonce(0)),
+        })
+    }
+
+    async fn account_nonce(req: HttpRequest) -> web::Json<Option<Nonce>> {
+        authorize(&req);
+        web::Json(Some(Nonce(0)))
+    }
+
+    // The submitted batches are executed right away, nothing is pending
+    async fn account_pending_nonce(req: HttpRequest) -> web::Json<Option<Nonce>> {
+        authorize(&req);
+        web::Json(None)
+    }
+
+    async fn submit_batch(
+        state: web::Data<MockApiState>,
+        body: web::Json<IncomingTxBatch>,
+    ) -> web::Json<Response> {
+        let batch = body.into_inner().txs;
+        let rejection = state.rejection.lock().unwrap().clone();
+        if rejection.is_none() {
+            state
+                .executed
+                .lock()
+                .unwrap()
+                .extend(batch.iter().map(|tx| tx.tx.hash()));
+        }
+        state.batches.lock().unwrap().push(batch);
+
+        web::Json(Response {
+            request: Request {
+                network: Network::Localhost,
+                api_version: ApiVersion::V02,
+                resource: "/api/v0.2/transactions/batches".to_string(),
+                args: HashMap::new(),
+                timestamp: Utc::now(),
+            },
+            status: if rejection.is_some() {
+                ResultStatus::Error
+            } else {
+                ResultStatus::Success
+            },
+            error: rejection,
+            result: None,
+        })
+    }
+
+    fn start_mock_api(state: web::Data<MockApiState>) -> actix_test::TestServer {
+        actix_test::start(move || {
+            App::new()
+                .app_data(state.clone())
+                .service(
+                    web::scope("/admin/forced_exit_requests/remote")
+                        .route("/requests/unconfirmed", web::get().to(unconfirmed_requests))
+                        .route(
+                            "/requests/by_public_id/{id}",
+                            web::get().to(request_by_public_id),
+                        )
+                        .route("/requests/{id}", web::get().to(request_by_id))
+                        .route("/requests/{id}/fulfilled", web::post().to(set_fulfilled))
+                        .route(
+                            "/requests/{id}/fulfilled_by",
+                            web::post().to(set_fulfilled_by),
+                        )
+                        .route("/requests/{id}/cancel", web::post().to(cancel_request))
+                        .route(
+                            "/requests/{id}/match_scheme",
+                            web::post().to(set_match_scheme),
+                        )
+                        .route(
+                            "/requests/{id}/fulfillment",
+                            web::post().to(begin_fulfillment),
+                        )
+                        .route("/receipts", web::post().to(receipts))
+                        .route(
+                            "/accounts/{address}/target_check",
+                            web::get().to(target_check),
+                        )
+                        .route("/accounts/{id}/nonce", web::get().to(account_nonce))
+                        .route(
+                            "/accounts/{id}/pending_nonce",
+                            web::get().to(account_pending_nonce),
+                        ),
+                )
+                .route(
+                    "/api/v0.2/transactions/batches",
+                    web::post().to(submit_batch),
+                )
+        })
+    }
+
+    fn test_request(id: ForcedExitRequestId) -> ForcedExitRequest {
+        ForcedExitRequest {
+            id,
+            public_id: id,
+            target: Address::random(),
+            tokens: vec![TokenId(1)],
+            price_in_wei: BigUint::from_str("10000000000").unwrap(),
+            pay_exactly: pay_exactly(&BigUint::from_str("10000000000").unwrap(), id),
+            valid_until: Utc::now().add(chrono::Duration::days(1)),
+            created_at: Utc::now(),
+            fulfilled_by: None,
+            fulfilled_at: None,
+            match_scheme: None,
+            matched_at: None,
+            cancellation: None,
+            paid_amount: None,
+            metadata: None,
+            status: Default::default(),
+            payment_terms: None,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn remote_backend_fulfills_requests() {
+        let state = web::Data::new(MockApiState::default());
+        let server = start_mock_api(state.clone());
+
+        let request = test_request(12);
+        // The batch of this one was sent and executed while the component was down
+        let sent_before = ForcedExitRequest {
+            fulfilled_by: Some(vec![TxHash::from_slice(&[1; 32]).unwrap()]),
+            ..test_request(13)
+        };
+        state
+            .executed
+            .lock()
+            .unwrap()
+            .push(TxHash::from_slice(&[1; 32]).unwrap());
+        state
+            .requests
+            .lock()
+            .unwrap()
+            .extend(vec![request.clone(), sent_before]);
+
+        let config = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            l1_escalation_enabled: false,
+            admin_payments_enabled: false,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let core_interaction_wrapper = ApiCoreInteractionWrapper::new(
+            server.url("").trim_end_matches('/').to_owned(),
+            TEST_SECRET_AUTH.to_owned(),
+        );
+        core_interaction_wrapper
+            .capabilities()
+            .ensure_supported(&config)
+            .unwrap();
+        let mut sender = MempoolForcedExitSender::new(
+            core_interaction_wrapper,
+            config,
+            AccountId(12),
+            TEST_ZKSYNC_CONTRACT,
+        );
+
+        let in_flight = sender
+            .reconcile_unconfirmed(time::Duration::from_secs(0))
+            .await
+            .unwrap();
+        assert_eq!(in_flight, 0);
+
+        sender
+            .process_request(
+                FundsReceivedEvent {
+                    amount: BigUint::from_str("10000000012").unwrap(),
+                    request_id: None,
+                    block_number: 0,
+                    eth_tx_hash: None,
+                    payer: None,
+                    recipient: None,
+                },
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+
+        let batches = state.batches.lock().unwrap();
+        assert_eq!(batches.len(), 1);
+        let hashes: Vec<_> = batches[0].iter().map(|tx| tx.tx.hash()).collect();
+        match &batches[0][0].tx {
+            ZkSyncTx::ForcedExit(tx) => {
+                assert_eq!(tx.target, request.target);
+                assert_eq!(tx.token, TokenId(1));
+            }
+            _ => panic!("Only ForcedExit transactions are sent"),
+        }
+
+        let requests = state.requests.lock().unwrap();
+        let fulfilled = requests.iter().find(|r| r.id == 12).unwrap();
+        assert!(fulfilled.fulfilled_at.is_some());
+        assert_eq!(fulfilled.fulfilled_by, Some(hashes));
+        assert_eq!(
+            fulfilled.match_scheme,
+            Some(PaymentMatchScheme::AmountDigits)
+        );
+        let settled = requests.iter().find(|r| r.id == 13).unwrap();
+        assert!(settled.fulfilled_at.is_some());
+    }
+
+    #[actix_rt::test]
+    async fn rejected_batch_keeps_api_error() {
+        let state = web::Data::new(MockApiState::default());
+        let server = start_mock_api(state.clone());
+        state.requests.lock().unwrap().push(test_request(12));
+        *state.rejection.lock().unwrap() = Some(serde_json::json!({
+            "errorType": "submitError",
+            "code": 605,
+            "message": "Transaction fee is too low",
+        }));
+
+        let config = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            processing_attempts: 3,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let core_interaction_wrapper = ApiCoreInteractionWrapper::new(
+            server.url("").trim_end_matches('/').to_owned(),
+            TEST_SECRET_AUTH.to_owned(),
+        );
+        let mut sender = MempoolForcedExitSender::new(
+            core_interaction_wrapper,
+            config,
+            AccountId(12),
+            TEST_ZKSYNC_CONTRACT,
+        );
+
+        let err = sender
+            .process_payment(
+                FundsReceivedEvent {
+                    amount: BigUint::from_str("10000000012").unwrap(),
+                    request_id: None,
+                    block_number: 0,
+                    eth_tx_hash: None,
+                    payer: None,
+                    recipient: None,
+                },
+                Utc::now(),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SubmissionError>(),
+            Some(&SubmissionError::rejected(
+                Some(605),
+                "Transaction fee is too low",
+                false
+            ))
+        );
+        // The rejection is not retried
+        assert_eq!(state.batches.lock().unwrap().len(), 1);
+        let requests = state.requests.lock().unwrap();
+        assert_eq!(requests[0].fulfilled_by, None);
+
+        // The errors caused by the server may pass
+        let unavailable = api_rejection(Some(serde_json::json!({
+            "errorType": "submitError",
+            "code": 607,
+            "message": "Core server communication error",
+        })));
+        assert_eq!(unavailable.kind, SubmissionErrorKind::Rejected);
+        assert!(unavailable.retryable);
+        // The unexpected errors are kept as they are
+        let unknown = api_rejection(Some(serde_json::json!("Rejected")));
+        assert_eq!(unknown.api_code, None);
+        assert_eq!(unknown.message, "\"Rejected\"");
+        assert!(!unknown.retryable);
+    }
+
+    #[test]
+    fn error_statuses_keep_http_details() {
+        let error = client_submission_error(ClientError::BadRequest {
+            http_code: reqwest::StatusCode::SERVICE_UNAVAILABLE,
+            body: ErrorBody {
+                title: "Service unavailable".to_owned(),
+                detail: "The mempool is overloaded".to_owned(),
+                code: Some(607),
+                ..ErrorBody::default()
+            },
+        });
+        assert_eq!(
+            error,
+            SubmissionError {
+                kind: SubmissionErrorKind::Http,
+                http_status: Some(503),
+                api_code: Some(607),
+                message: "Service unavailable: The mempool is overloaded".to_owned(),
+                retryable: true,
+            }
+        );
+
+        let error = client_submission_error(ClientError::BadRequest {
+            http_code: reqwest::StatusCode::BAD_REQUEST,
+            body: ErrorBody {
+                title: "Invalid batch".to_owned(),
+                ..ErrorBody::default()
+            },
+        });
+        assert_eq!(error.kind, SubmissionErrorKind::Http);
+        assert_eq!(error.http_status, Some(400));
+        assert_eq!(error.api_code, None);
+        assert_eq!(error.message, "Invalid batch");
+        assert!(!error.retryable);
+
+        let error = client_submission_error(ClientError::NotFound("/batches".to_owned()));
+        assert_eq!(error.http_status, Some(404));
+        assert!(!error.retryable);
+    }
+
+    #[actix_rt::test]
+    async fn unreachable_server_is_transport_error() {
+        // Nothing listens on the port
+        let client = Client::new("http://127.0.0.1:1".to_owned());
+        let err = client.submit_batch(Vec::new(), None).await.unwrap_err();
+
+        let error = client_submission_error(err);
+        assert_eq!(error.kind, SubmissionErrorKind::Transport);
+        assert_eq!(error.http_status, None);
+        assert_eq!(error.api_code, None);
+        assert!(!error.message.is_empty());
+        assert!(error.retryable);
+    }
+
+    #[test]
+    fn remote_backend_capabilities() {
+        let wrapper = ApiCoreInteractionWrapper::new(String::new(), TEST_SECRET_AUTH.to_owned());
+        let config = ForcedExitRequestsConfig {
+            l1_escalation_enabled: false,
+            admin_payments_enabled: false,
+            refunds_enabled: false,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        wrapper.capabilities().ensure_supported(&config).unwrap();
+
+        // The features depending on the database are refused rather than ignored
+        let escalations = ForcedExitRequestsConfig {
+            l1_escalation_enabled: true,
+            ..config.clone()
+        };
+        assert!(wrapper
+            .capabilities()
+            .ensure_supported(&escalations)
+            .is_err());
+        let injected_payments = ForcedExitRequestsConfig {
+            admin_payments_enabled: true,
+            ..config.clone()
+        };
+        assert!(wrapper
+            .capabilities()
+            .ensure_supported(&injected_payments)
+            .is_err());
+        let held_requests = ForcedExitRequestsConfig {
+            active_target_policy: ActiveTargetPolicy::Hold,
+            ..config.clone()
+        };
+        assert!(wrapper
+            .capabilities()
+            .ensure_supported(&held_requests)
+            .is_err());
+        let l1_transfer_check = ForcedExitRequestsConfig {
+            l1_transfer_check_web3_url: Some("http://127.0.0.1:8545".to_owned()),
+            ..config.clone()
+        };
+        assert!(wrapper
+            .capabilities()
+            .ensure_supported(&l1_transfer_check)
+            .is_err());
+        let refunds = ForcedExitRequestsConfig {
+            refunds_enabled: true,
+            ..config.clone()
+        };
+        assert!(wrapper.capabilities().ensure_supported(&refunds).is_err());
+        Capabilities::ALL.ensure_supported(&escalations).unwrap();
+    }
+}
@@ -0,0 +1,931 @@
+//! Deterministic replay of the recorded payments to the forced exit contract.
+//!
+//! The payments processed by the watcher are stored in the database, so they can
+//! be exported to a JSONL file and replayed through the matching pipeline against
+//! a copy of the database. The `ForcedExit` transactions are never sent during the
+//! replay: the submissions are stubbed out and are considered to be committed
+//! right away. The outcome of processing each payment is written to the decision log.
+
+use std::{
+    collections::HashSet,
+    convert::TryInto,
+    future::Future,
+    io::{BufRead, Write},
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Utc};
+use futures::channel::mpsc;
+use num::BigUint;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use zksync_config::{ContractsConfig, ForcedExitRequestsConfig};
+use zksync_storage::{chain::operations_ext::records::TxReceiptResponse, ConnectionPool};
+use zksync_types::{
+    forced_exit_requests::{
+        ExpectedForcedExitPayment, ForcedExitCancellationKind, ForcedExitPacing,
+        ForcedExitPacingState, ForcedExitPayment, ForcedExitPipelineVersion,
+        ForcedExitProcessingFailure, ForcedExitRefund, ForcedExitRefundId, ForcedExitRefundStatus,
+        ForcedExitRequest, ForcedExitRequestActiveTarget, ForcedExitRequestDelivery,
+        ForcedExitRequestDeliveryId, ForcedExitRequestEscalation, ForcedExitRequestId,
+        ForcedExitRetry, ForcedExitRetryId, ForcedExitTargetCheck, InjectedForcedExitPayment,
+        InjectedForcedExitPaymentId, InterruptedSubmission, PaymentMatchScheme, PaymentSourceState,
+        SaveForcedExitRefundQuery, SaveForcedExitRequestNoteQuery, SkippedForcedExit,
+        UnmatchedPaymentReason,
+    },
+    tx::TxHash,
+    AccountId, Address, Nonce, SignedZkSyncTx, Token, TokenId, H256,
+};
+
+use crate::{
+    core_interaction_wrapper::{
+        append_chunk, combine_batch, ensure_batch_not_empty, CoreInteractionWrapper,
+        MempoolCoreInteractionWrapper,
+    },
+    forced_exit_sender::{MempoolForcedExitSender, PaymentDecision},
+};
+
+/// The outcome of processing the recorded payment.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecisionLogEntry {
+    pub payment: ForcedExitPayment,
+    pub decision: PaymentDecision,
+}
+
+/// Writes the items to the JSONL file, one item per line.
+pub fn write_jsonl<T: Serialize>(mut writer: impl Write, items: &[T]) -> anyhow::Result<()> {
+    for item in items {
+        write_jsonl_line(&mut writer, item)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_jsonl_line<T: Serialize>(mut writer: impl Write, item: &T) -> anyhow::Result<()> {
+    serde_json::to_writer(&mut writer, item)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// Reads the items of the JSONL file, the empty lines are skipped.
+pub fn read_jsonl<T: DeserializeOwned>(reader: impl BufRead) -> anyhow::Result<Vec<T>> {
+    let mut items = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let item = serde_json::from_str(&line)
+            .map_err(|err| anyhow::anyhow!("Invalid entry at line {}: {}", index + 1, err))?;
+        items.push(item);
+    }
+    Ok(items)
+}
+
+/// The number of the payments read from the database at once by the export.
+pub const EXPORT_PAGE_SIZE: u32 = 1000;
+
+/// How far the export has got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaymentsExport {
+    /// The number of the payments written.
+    pub exported: usize,
+    /// The id of the last payment written, if the row cap was reached before the end
+    /// of the range. The export is continued after it.
+    pub continue_after: Option<i64>,
+}
+
+/// Writes the payments made within the block range (inclusive) to the JSONL file in the order
+/// they were processed, starting after the payment with `after_id`. The payments are read and
+/// written page by page, so at most `EXPORT_PAGE_SIZE` of them are held in memory at once.
+/// At most `max_rows` payments are written, the rest is left for the continued export.
+pub async fn export_payments(
+    connection_pool: &ConnectionPool,
+    from_block: u64,
+    to_block: u64,
+    after_id: Option<i64>,
+    max_rows: usize,
+    writer: impl Write,
+) -> anyhow::Result<PaymentsExport> {
+    export_pages(after_id, max_rows, writer, |after_id, limit| async move {
+        let mut storage = connection_pool.access_storage().await?;
+        let page = storage
+            .forced_exit_requests_schema()
+            .load_payments_page(from_block, to_block, after_id, limit)
+            .await?;
+        Ok(page)
+    })
+    .await
+}
+
+async fn export_pages<F, Fut>(
+    mut after_id: Option<i64>,
+    max_rows: usize,
+    mut writer: impl Write,
+    mut load_page: F,
+) -> anyhow::Result<PaymentsExport>
+where
+    F: FnMut(Option<i64>, u32) -> Fut,
+    Fut: Future<Output = anyhow::Result<Vec<(i64, ForcedExitPayment)>>>,
+{
+    anyhow::ensure!(max_rows > 0, "At least one payment has to be exported");
+
+    let mut exported = 0;
+    let continue_after = loop {
+        let remaining = max_rows - exported;
+        if remaining == 0 {
+            // The export is only continued if there is anything left
+            let more = !load_page(after_id, 1).await?.is_empty();
+            break after_id.filter(|_| more);
+        }
+
+        let limit = EXPORT_PAGE_SIZE.min(remaining.try_into().unwrap_or(u32::MAX));
+        let page = load_page(after_id, limit).await?;
+        let is_last = page.len() < limit as usize;
+        for (id, payment) in page {
+            write_jsonl_line(&mut writer, &payment)?;
+            after_id = Some(id);
+            exported += 1;
+        }
+        if is_last {
+            break None;
+        }
+    };
+    writer.flush()?;
+
+    Ok(PaymentsExport {
+        exported,
+        continue_after,
+    })
+}
+
+/// Wrapper, which stubs out the submission of the transactions and delegates
+/// everything else to the inner one.
+///
+/// The stubbed transactions are considered to be successfully committed.
+pub struct ReplayCoreInteractionWrapper<T: CoreInteractionWrapper> {
+    inner: T,
+    submitted_txs: Mutex<HashSet<TxHash>>,
+}
+
+impl<T: CoreInteractionWrapper> ReplayCoreInteractionWrapper<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            submitted_txs: Mutex::new(HashSet::new()),
+        }
+    }
+
+    fn lock_submitted_txs(&self) -> std::sync::MutexGuard<'_, HashSet<TxHash>> {
+        self.submitted_txs
+            .lock()
+            .expect("Failed to get the submitted txs lock")
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: CoreInteractionWrapper + Send + Sync> CoreInteractionWrapper
+    for ReplayCoreInteractionWrapper<T>
+{
+    async fn get_nonce(&self, account_id: AccountId) -> anyhow::Result<Option<Nonce>> {
+        self.inner.get_nonce(account_id).await
+    }
+
+    async fn get_pending_nonce(&self, account_id: AccountId) -> anyhow::Result<Option<Nonce>> {
+        self.inner.get_pending_nonce(account_id).await
+    }
+
+    async fn get_unconfirmed_requests(&self) -> anyhow::Result<Vec<ForcedExitRequest>> {
+        self.inner.get_unconfirmed_requests().await
+    }
+
+    async fn set_fulfilled_at(&self, id: i64) -> anyhow::Result<()> {
+        self.inner.set_fulfilled_at(id).await
+    }
+
+    async fn set_fulfilled_by(
+        &self,
+        id: ForcedExitRequestId,
+        value: Option<Vec<TxHash>>,
+    ) -> anyhow::Result<()> {
+        self.inner.set_fulfilled_by(id, value).await
+    }
+
+    async fn cancel_request(
+        &self,
+        id: ForcedExitRequestId,
+        kind: ForcedExitCancellationKind,
+    ) -> anyhow::Result<()> {
+        self.inner.cancel_request(id, kind).await
+    }
+
+    async fn set_match_scheme(
+        &self,
+        id: ForcedExitRequestId,
+        match_scheme: PaymentMatchScheme,
+        paid_amount: &BigUint,
+        payment_tx_hash: Option<H256>,
+    ) -> anyhow::Result<()> {
+        self.inner
+            .set_match_scheme(id, match_scheme, paid_amount, payment_tx_hash)
+            .await
+    }
+
+    async fn begin_fulfillment(
+        &self,
+        id: ForcedExitRequestId,
+        payment_tx_hash: Option<H256>,
+    ) -> anyhow::Result<bool> {
+        self.inner.begin_fulfillment(id, payment_tx_hash).await
+    }
+
+    async fn get_request_by_id(&self, id: i64) -> anyhow::Result<Option<ForcedExitRequest>> {
+        self.inner.get_request_by_id(id).await
+    }
+
+    async fn get_request_by_public_id(
+        &self,
+        public_id: ForcedExitRequestId,
+    ) -> anyhow::Result<Option<ForcedExitRequest>> {
+        self.inner.get_request_by_public_id(public_id).await
+    }
+
+    async fn get_requests_by_closest_amount(
+        &self,
+        max_amount: &BigUint,
+        submission_time: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<ForcedExitRequest>> {
+        self.inner
+            .get_requests_by_closest_amount(max_amount, submission_time)
+            .await
+    }
+
+    async fn get_receipt(&self, tx_hash: TxHash) -> anyhow::Result<Option<TxReceiptResponse>> {
+        if !self.lock_submitted_txs().contains(&tx_hash) {
+            return self.inner.get_receipt(tx_hash).await;
+        }
+
+        Ok(Some(TxReceiptResponse {
+            tx_hash: hex::encode(tx_hash.as_ref()),
+            block_number: 0,
+            success: true,
+            verified: false,
+            fail_reason: None,
+            prover_run: None,
+        }))
+    }
+
+    async fn get_receipts(&self, tx_hashes: &[TxHash]) -> anyhow::Result<Vec<TxReceiptResponse>> {
+        let (submitted, other): (Vec<TxHash>, Vec<TxHash>) = {
+            let submitted_txs = self.lock_submitted_txs();
+            tx_hashes
+                .iter()
+                .copied()
+                .partition(|tx_hash| submitted_txs.contains(tx_hash))
+        };
+
+        let mut receipts = self.inner.get_receipts(&other).await?;
+        for tx_hash in submitted {
+            receipts.extend(self.get_receipt(tx_hash).await?);
+        }
+        Ok(receipts)
+    }
+
+    async fn send_and_save_txs_batch(
+        &mut self,
+        request: &ForcedExitRequest,
+        txs: Vec<SignedZkSyncTx>,
+    ) -> anyhow::Result<Vec<TxHash>> {
+        ensure_batch_not_empty(request, &txs)?;
+        // The transactions are not sent anywhere, only the state of the request is updated
+        let hashes: Vec<TxHash> = txs.iter().map(|tx| tx.hash()).collect();
+        self.lock_submitted_txs().extend(hashes.iter().copied());
+        self.inner
+            .set_fulfilled_by(request.id, Some(hashes.clone()))
+            .await?;
+
+        Ok(hashes)
+    }
+
+    async fn send_and_save_combined_batch(
+        &mut self,
+        parts: Vec<(ForcedExitRequestId, Vec<SignedZkSyncTx>)>,
+    ) -> anyhow::Result<Vec<Vec<TxHash>>> {
+        let ids: Vec<ForcedExitRequestId> = parts.iter().map(|(id, _)| *id).collect();
+        let (_, hashes) = combine_batch(parts)?;
+        self.lock_submitted_txs()
+            .extend(hashes.iter().flatten().copied());
+        for (id, request_hashes) in ids.into_iter().zip(&hashes) {
+            self.inner
+                .set_fulfilled_by(id, Some(request_hashes.clone()))
+                .await?;
+        }
+
+        Ok(hashes)
+    }
+
+    async fn send_and_save_txs_chunk(
+        &mut self,
+        request: &ForcedExitRequest,
+        sent: &[TxHash],
+        txs: Vec<SignedZkSyncTx>,
+    ) -> anyhow::Result<Vec<TxHash>> {
+        ensure_batch_not_empty(request, &txs)?;
+        let hashes: Vec<TxHash> = txs.iter().map(|tx| tx.hash()).collect();
+        self.lock_submitted_txs().extend(hashes.iter().copied());
+        self.inner
+            .set_fulfilled_by(request.id, Some(append_chunk(sent, &txs)))
+            .await?;
+
+        Ok(hashes)
+    }
+
+    async fn get_oldest_unfulfilled_request(&self) -> anyhow::Result<Option<ForcedExitRequest>> {
+        self.inner.get_oldest_unfulfilled_request().await
+    }
+
+    async fn delete_old_unfulfilled_requests(
+        &self,
+        deleting_threshold: chrono::Duration,
+    ) -> anyhow::Result<()> {
+        self.inner
+            .delete_old_unfulfilled_requests(deleting_threshold)
+            .await
+    }
+
+    async fn check_forced_exit_request(
+        &self,
+        request: &ForcedExitRequest,
+    ) -> anyhow::Result<ForcedExitTargetCheck> {
+        self.inner.check_forced_exit_request(request).await
+    }
+
+    async fn record_failure(&self, id: ForcedExitRequestId, token: TokenId) -> anyhow::Result<u32> {
+        self.inner.record_failure(id, token).await
+    }
+
+    async fn record_processing_failure(
+        &self,
+        failure: ForcedExitProcessingFailure,
+    ) -> anyhow::Result<()> {
+        self.inner.record_processing_failure(failure).await
+    }
+
+    async fn record_pipeline_version(
+        &self,
+        version: ForcedExitPipelineVersion,
+    ) -> anyhow::Result<()> {
+        self.inner.record_pipeline_version(version).await
+    }
+
+    async fn get_account_id(&self, address: Address) -> anyhow::Result<Option<AccountId>> {
+        self.inner.get_account_id(address).await
+    }
+
+    async fn record_target_account_id(
+        &self,
+        id: ForcedExitRequestId,
+        target_account_id: AccountId,
+    ) -> anyhow::Result<()> {
+        self.inner
+            .record_target_account_id(id, target_account_id)
+            .await
+    }
+
+    async fn get_interrupted_submissions(&self) -> anyhow::Result<Vec<InterruptedSubmission>> {
+        self.inner.get_interrupted_submissions().await
+    }
+
+    async fn store_note(&self, note: SaveForcedExitRequestNoteQuery) -> anyhow::Result<()> {
+        self.inner.store_note(note).await
+    }
+
+    async fn get_token_address(&self, token: TokenId) -> anyhow::Result<Option<Address>> {
+        self.inner.get_token_address(token).await
+    }
+
+    async fn get_token(&self, token: TokenId) -> anyhow::Result<Option<Token>> {
+        self.inner.get_token(token).await
+    }
+
+    async fn store_escalation(
+        &self,
+        escalation: ForcedExitRequestEscalation,
+    ) -> anyhow::Result<()> {
+        self.inner.store_escalation(escalation).await
+    }
+
+    async fn get_escalation(
+        &self,
+        id: ForcedExitRequestId,
+    ) -> anyhow::Result<Option<ForcedExitRequestEscalation>> {
+        self.inner.get_escalation(id).await
+    }
+
+    async fn store_active_target(
+        &self,
+        active_target: ForcedExitRequestActiveTarget,
+    ) -> anyhow::Result<()> {
+        self.inner.store_active_target(active_target).await
+    }
+
+    async fn get_held_active_targets(&self) -> anyhow::Result<Vec<ForcedExitRequestActiveTarget>> {
+        self.inner.get_held_active_targets().await
+    }
+
+    async fn resume_active_target(
+        &self,
+        id: ForcedExitRequestId,
+        resumed_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        self.inner.resume_active_target(id, resumed_at).await
+    }
+
+    async fn fail_active_target(
+        &self,
+        id: ForcedExitRequestId,
+        failed_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        self.inner.fail_active_target(id, failed_at).await
+    }
+
+    async fn skip_tokens(
+        &self,
+        id: ForcedExitRequestId,
+        skipped: &[SkippedForcedExit],
+    ) -> anyhow::Result<()> {
+        self.inner.skip_tokens(id, skipped).await
+    }
+
+    async fn store_payment(&self, _payment: &ForcedExitPayment) -> anyhow::Result<()> {
+        // The replayed payments are already recorded
+        Ok(())
+    }
+
+    async fn store_unmatched_payment(
+        &self,
+        _payment: &ForcedExitPayment,
+        _reason: UnmatchedPaymentReason,
+    ) -> anyhow::Result<()> {
+        // Only the recorded payments are replayed, so none of them is set aside
+        Ok(())
+    }
+
+    async fn get_payment_source_states(&self) -> anyhow::Result<Vec<PaymentSourceState>> {
+        self.inner.get_payment_source_states().await
+    }
+
+    async fn get_expected_payment(
+        &self,
+        eth_tx_hash: H256,
+    ) -> anyhow::Result<Option<ExpectedForcedExitPayment>> {
+        self.inner.get_expected_payment(eth_tx_hash).await
+    }
+
+    async fn flag_expected_payment(&self, eth_tx_hash: H256, paid: &BigUint) -> anyhow::Result<()> {
+        self.inner.flag_expected_payment(eth_tx_hash, paid).await
+    }
+
+    async fn get_injected_payments(
+        &self,
+        limit: u32,
+    ) -> anyhow::Result<Vec<InjectedForcedExitPayment>> {
+        self.inner.get_injected_payments(limit).await
+    }
+
+    async fn delete_injected_payment(&self, id: InjectedForcedExitPaymentId) -> anyhow::Result<()> {
+        self.inner.delete_injected_payment(id).await
+    }
+
+    async fn get_pending_retries(&self, limit: u32) -> anyhow::Result<Vec<ForcedExitRetry>> {
+        self.inner.get_pending_retries(limit).await
+    }
+
+    async fn complete_retry(
+        &self,
+        id: ForcedExitRetryId,
+        tx_hashes: &[TxHash],
+        error: Option<&str>,
+    ) -> anyhow::Result<()> {
+        self.inner.complete_retry(id, tx_hashes, error).await
+    }
+
+    async fn get_pacing_override(&self) -> anyhow::Result<Option<ForcedExitPacing>> {
+        self.inner.get_pacing_override().await
+    }
+
+    async fn report_pacing(
+        &self,
+        sender: Address,
+        state: &ForcedExitPacingState,
+    ) -> anyhow::Result<()> {
+        self.inner.report_pacing(sender, state).await
+    }
+
+    async fn get_pending_deliveries(
+        &self,
+        limit: u32,
+        callbacks: bool,
+    ) -> anyhow::Result<Vec<ForcedExitRequestDelivery>> {
+        self.inner.get_pending_deliveries(limit, callbacks).await
+    }
+
+    async fn mark_delivered(&self, id: ForcedExitRequestDeliveryId) -> anyhow::Result<()> {
+        self.inner.mark_delivered(id).await
+    }
+
+    async fn record_delivery_failure(
+        &self,
+        id: ForcedExitRequestDeliveryId,
+        error: String,
+        next_attempt_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        self.inner
+            .record_delivery_failure(id, error, next_attempt_at)
+            .await
+    }
+
+    async fn mark_delivery_failed(
+        &self,
+        id: ForcedExitRequestDeliveryId,
+        error: String,
+    ) -> anyhow::Result<()> {
+        self.inner.mark_delivery_failed(id, error).await
+    }
+
+    async fn store_refund(
+        &self,
+        refund: SaveForcedExitRefundQuery,
+    ) -> anyhow::Result<Option<ForcedExitRefund>> {
+        self.inner.store_refund(refund).await
+    }
+
+    async fn get_unsettled_refunds(
+        &self,
+        max_attempts: u32,
+    ) -> anyhow::Result<Vec<ForcedExitRefund>> {
+        self.inner.get_unsettled_refunds(max_attempts).await
+    }
+
+    async fn get_refunded_amount_since(&self, since: DateTime<Utc>) -> anyhow::Result<BigUint> {
+        self.inner.get_refunded_amount_since(since).await
+    }
+
+    async fn get_balance(&self, account_id: AccountId, token: TokenId) -> anyhow::Result<BigUint> {
+        self.inner.get_balance(account_id, token).await
+    }
+
+    async fn get_forced_exit_batch_fee(
+        &self,
+        target: Address,
+        forced_exits: usize,
+        fee_payer: Address,
+        token: TokenId,
+    ) -> anyhow::Result<BigUint> {
+        self.inner
+            .get_forced_exit_batch_fee(target, forced_exits, fee_payer, token)
+            .await
+    }
+
+    async fn send_refund(
+        &mut self,
+        id: ForcedExitRefundId,
+        tx: SignedZkSyncTx,
+    ) -> anyhow::Result<TxHash> {
+        // The same as for the `ForcedExit` transactions, the transfer is not sent anywhere
+        let tx_hash = tx.hash();
+        self.lock_submitted_txs().insert(tx_hash);
+        self.inner
+            .set_refund_status(id, ForcedExitRefundStatus::Sent, Some(tx_hash))
+            .await?;
+
+        Ok(tx_hash)
+    }
+
+    async fn set_refund_status(
+        &self,
+        id: ForcedExitRefundId,
+        status: ForcedExitRefundStatus,
+        tx_hash: Option<TxHash>,
+    ) -> anyhow::Result<()> {
+        self.inner.set_refund_status(id, status, tx_hash).await
+    }
+}
+
+/// Processes the payments one by one in the recorded order.
+pub(crate) async fn replay_with_sender<T: CoreInteractionWrapper + Send + Sync>(
+    forced_exit_sender: &mut MempoolForcedExitSender<ReplayCoreInteractionWrapper<T>>,
+    payments: Vec<ForcedExitPayment>,
+) -> Vec<DecisionLogEntry> {
+    let mut decision_log = Vec::with_capacity(payments.len());
+    for payment in payments {
+        let decision = forced_exit_sender
+            .process_payment(payment.event(), payment.received_at)
+            .await
+            .unwrap_or_else(|err| PaymentDecision::Failed {
+                error: format!("{:#}", err),
+            });
+        decision_log.push(DecisionLogEntry { payment, decision });
+    }
+    decision_log
+}
+
+/// Replays the payments against the database the pool is connected to.
+///
+/// The database is modified the same way the real processing would do it,
+/// so it must be a copy of the production one.
+pub async fn replay_payments(
+    connection_pool: ConnectionPool,
+    config: ForcedExitRequestsConfig,
+    contracts: ContractsConfig,
+    forced_exit_minimum_account_age_secs: u64,
+    payments: Vec<ForcedExitPayment>,
+) -> anyhow::Result<Vec<DecisionLogEntry>> {
+    let sender_account_id = connection_pool
+        .access_storage()
+        .await?
+        .chain()
+        .account_schema()
+        .account_id_by_address(config.sender_account_address)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("ForcedExit sender account does not exist"))?;
+
+    // The receiver is dropped right away, so nothing can ever reach the mempool,
+    // even if the submissions were not stubbed out
+    let (mempool_tx_sender, _) = mpsc::channel(1);
+    let core_interaction_wrapper =
+        ReplayCoreInteractionWrapper::new(MempoolCoreInteractionWrapper::new(
+            forced_exit_minimum_account_age_secs,
+            connection_pool,
+            mempool_tx_sender,
+        ));
+    let mut forced_exit_sender = MempoolForcedExitSender::new(
+        core_interaction_wrapper,
+        config,
+        sender_account_id,
+        contracts.contract_addr,
+    );
+
+    Ok(replay_with_sender(&mut forced_exit_sender, payments).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, ops::Add, rc::Rc};
+
+    use chrono::{DateTime, Utc};
+    use num::BigUint;
+    use zksync_types::forced_exit_requests::{pay_exactly, PaymentSource};
+
+    use super::*;
+    use crate::test::{add_request, MockCoreInteractionWrapper};
+
+    const FIXTURE: &str = include_str!("replay_payments.jsonl");
+
+    fn fixture_time() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2022-08-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn request(id: ForcedExitRequestId, tokens: Vec<TokenId>) -> ForcedExitRequest {
+        ForcedExitRequest {
+            id,
+            public_id: id,
+            target: Address::from_low_u64_be(id as u64),
+            tokens,
+            price_in_wei: BigUint::from(10_000_000_000u64),
+            pay_exactly: pay_exactly(&BigUint::from(10_000_000_000u64), id),
+            valid_until: fixture_time().add(chrono::Duration::hours(1)),
+            created_at: fixture_time(),
+            fulfilled_by: None,
+            fulfilled_at: None,
+            match_scheme: None,
+            matched_at: None,
+            cancellation: None,
+            paid_amount: None,
+            metadata: None,
+            status: Default::default(),
+            payment_terms: None,
+        }
+    }
+
+    fn get_test_sender(
+    ) -> MempoolForcedExitSender<ReplayCoreInteractionWrapper<MockCoreInteractionWrapper>> {
+        let config = ForcedExitRequestsConfig {
+            digits_in_id: 10,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let mock = MockCoreInteractionWrapper::default();
+        add_request(&mock.requests, request(12, vec![TokenId(1), TokenId(2)]));
+        add_request(&mock.requests, request(13, vec![TokenId(1)]));
+        // The stubbed submissions must be used instead of the receipts of the inner wrapper
+        let mock = MockCoreInteractionWrapper {
+            tx_receipt: None,
+            ..mock
+        };
+
+        MempoolForcedExitSender::new(
+            ReplayCoreInteractionWrapper::new(mock),
+            config,
+            AccountId(12),
+            Address::repeat_byte(0x12),
+        )
+    }
+
+    #[test]
+    fn jsonl_roundtrip() {
+        let payments: Vec<ForcedExitPayment> = read_jsonl(FIXTURE.as_bytes()).unwrap();
+        assert_eq!(payments.len(), 5);
+
+        let mut file = Vec::new();
+        write_jsonl(&mut file, &payments).unwrap();
+        assert_eq!(
+            read_jsonl::<ForcedExitPayment>(&file[..]).unwrap(),
+            payments
+        );
+
+        assert!(read_jsonl::<ForcedExitPayment>("{\"amount\": 12}".as_bytes()).is_err());
+    }
+
+    /// The payments stored in the database, generated once requested.
+    fn stored_payment(id: i64) -> ForcedExitPayment {
+        ForcedExitPayment {
+            amount: BigUint::from(10_000_000_000u64 + id as u64),
+            request_id: Some(id),
+            block_number: id as u64 / 10,
+            eth_tx_hash: Some(H256::from_low_u64_be(id as u64)),
+            payer: Some(Address::repeat_byte(0x12)),
+            received_at: fixture_time(),
+            source: PaymentSource::L1Event,
+        }
+    }
+
+    /// Counts the lines written instead of keeping them.
+    struct LineCounter(Rc<Cell<usize>>);
+
+    impl Write for LineCounter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let lines = buf.iter().filter(|byte| **byte == b'\n').count();
+            self.0.set(self.0.get() + lines);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Exports the `total` payments, checking that every page is written out
+    /// before the next one is loaded. Returns the outcome and the largest page loaded.
+    async fn export_stored(
+        total: i64,
+        after_id: Option<i64>,
+        max_rows: usize,
+    ) -> (PaymentsExport, usize) {
+        let written = Rc::new(Cell::new(0));
+        let mut loaded = 0;
+        let mut largest_page = 0;
+        let export = export_pages(
+            after_id,
+            max_rows,
+            LineCounter(written.clone()),
+            |after_id, limit| {
+                // Nothing loaded before is held back
+                assert_eq!(written.get(), loaded);
+                let first = after_id.unwrap_or(0) + 1;
+                let last = (first + i64::from(limit) - 1).min(total);
+                let page: Vec<_> = (first..=last).map(|id| (id, stored_payment(id))).collect();
+                largest_page = largest_page.max(page.len());
+                loaded += page.len();
+                async move { Ok(page) }
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(written.get(), export.exported);
+        (export, largest_page)
+    }
+
+    #[tokio::test]
+    async fn export_in_pages() {
+        // A year worth of payments is written with a single page in memory at a time
+        let (export, largest_page) = export_stored(100_000, None, usize::MAX).await;
+        assert_eq!(
+            export,
+            PaymentsExport {
+                exported: 100_000,
+                continue_after: None,
+            }
+        );
+        assert_eq!(largest_page, EXPORT_PAGE_SIZE as usize);
+
+        let mut file = Vec::new();
+        export_pages(None, 2, &mut file, |after_id, limit| {
+            let page: Vec<_> = (after_id.unwrap_or(0) + 1..=3)
+                .take(limit as usize)
+                .map(|id| (id, stored_payment(id)))
+                .collect();
+            async move { Ok(page) }
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            read_jsonl::<ForcedExitPayment>(&file[..]).unwrap(),
+            vec![stored_payment(1), stored_payment(2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn export_row_cap() {
+        // The rows above the cap are left for the continued export
+        let (export, _) = export_stored(100_000, None, 2_500).await;
+        assert_eq!(
+            export,
+            PaymentsExport {
+                exported: 2_500,
+                continue_after: Some(2_500),
+            }
+        );
+        let (export, largest_page) = export_stored(100_000, Some(2_500), usize::MAX).await;
+        assert_eq!(export.exported, 97_500);
+        assert_eq!(export.continue_after, None);
+        assert_eq!(largest_page, EXPORT_PAGE_SIZE as usize);
+
+        // Nothing is left to continue with once the cap is exactly the size of the range
+        let (export, _) = export_stored(3_000, None, 3_000).await;
+        assert_eq!(
+            export,
+            PaymentsExport {
+                exported: 3_000,
+                continue_after: None,
+            }
+        );
+
+        assert!(
+            export_pages(None, 0, std::io::sink(), |_, _| async { Ok(Vec::new()) })
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn replay_fixture() {
+        let payments: Vec<ForcedExitPayment> = read_jsonl(FIXTURE.as_bytes()).unwrap();
+        let mut forced_exit_sender = get_test_sender();
+
+        let decision_log = replay_with_sender(&mut forced_exit_sender, payments.clone()).await;
+        let decisions: Vec<_> = decision_log
+            .iter()
+            .map(|entry| entry.decision.clone())
+            .collect();
+        assert_eq!(
+            decisions,
+            vec![
+                // The amount differs from the price of the request 12
+                PaymentDecision::Unmatched {
+                    request_id: 12,
+                    match_scheme: PaymentMatchScheme::AmountDigits,
+                },
+                PaymentDecision::Fulfilled {
+                    request_id: 12,
+                    match_scheme: PaymentMatchScheme::AmountDigits,
+                    tokens: vec![TokenId(1), TokenId(2)],
+                },
+                // The same payment rescanned after the reorg is not processed twice
+                PaymentDecision::Unmatched {
+                    request_id: 12,
+                    match_scheme: PaymentMatchScheme::AmountDigits,
+                },
+                // The request has already expired at the time of the payment
+                PaymentDecision::Unmatched {
+                    request_id: 13,
+                    match_scheme: PaymentMatchScheme::ExplicitId,
+                },
+                PaymentDecision::Unmatched {
+                    request_id: 14,
+                    match_scheme: PaymentMatchScheme::ExplicitId,
+                },
+            ]
+        );
+        let logged_payments: Vec<_> = decision_log
+            .into_iter()
+            .map(|entry| entry.payment)
+            .collect();
+        assert_eq!(logged_payments, payments);
+
+        // Nothing has reached the mempool of the inner wrapper
+        let inner = &forced_exit_sender.core_interaction_wrapper.inner;
+        assert!(inner.sent_txs.lock().unwrap().is_empty());
+
+        // The state of the requests is updated the same way the real processing does
+        let requests = inner.requests.lock().unwrap();
+        assert!(requests[0].fulfilled_at.is_some());
+        assert_eq!(
+            requests[0].match_scheme,
+            Some(PaymentMatchScheme::AmountDigits)
+        );
+        assert!(requests[1].fulfilled_by.is_none());
+    }
+}
@@ -0,0 +1,332 @@
+//! The accounts the `ForcedExit` transactions are sent from.
+//!
+//! The transactions of an account are sent one batch at a time, since each batch takes
+//! the nonces following the previous one, and the batch is awaited until committed. With
+//! several accounts the batches of the different requests are sent alongside each other.
+//!
+//! The batch is sent from the account with the fewest transactions in flight, all the
+//! transactions of a request are sent from the same account, so they stay in one batch.
+//! The account the batches of which keep failing, e.g. the one without the balance for
+//! the fees, is taken out of the rotation, unless it is the last one left in it.
+
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::Mutex as AsyncMutex;
+
+use zksync_config::ForcedExitRequestsConfig;
+use zksync_types::{AccountId, Address, Nonce, H256};
+
+use crate::{
+    core_interaction_wrapper::CoreInteractionWrapper,
+    signer::{signer_from_config, FeSigner, LocalSigner},
+};
+
+/// The number of the failed batches in a row after which the account is taken out of the rotation.
+pub const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+
+pub struct SenderAccount {
+    pub address: Address,
+    pub account_id: AccountId,
+    pub signer: Arc<dyn FeSigner>,
+    /// The Ethereum private key the `ForcedExit` transactions are additionally signed with,
+    /// see `ForcedExitRequestsConfig::eth_sign_forced_exits`.
+    pub eth_private_key: Option<H256>,
+    /// Held while the nonces are taken and the transactions are sent, so the senders
+    /// of the same account do not send the transactions with the same nonces.
+    pub(crate) send_lock: Arc<AsyncMutex<()>>,
+}
+
+impl SenderAccount {
+    pub fn new(address: Address, account_id: AccountId, private_key: &str) -> Self {
+        Self::with_signer(
+            address,
+            account_id,
+            Arc::new(LocalSigner::from_hex(private_key)),
+        )
+    }
+
+    pub fn with_signer(address: Address, account_id: AccountId, signer: Arc<dyn FeSigner>) -> Self {
+        Self {
+            address,
+            account_id,
+            signer,
+            eth_private_key: None,
+            send_lock: Arc::default(),
+        }
+    }
+
+    /// The main account of the config, the refunds are sent from it.
+    pub fn main(config: &ForcedExitRequestsConfig, account_id: AccountId) -> Self {
+        let address = config.sender_account_address;
+        let signer = signer_from_config(config, address, Some(&config.sender_private_key));
+        Self {
+            eth_private_key: config
+                .eth_sign_forced_exits
+                .then(|| config.sender_eth_private_key),
+            ..Self::with_signer(address, account_id, signer)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct AccountLoad {
+    /// The nonces of the batches sent from the account, which are not committed yet.
+    in_flight: Vec<Range<u32>>,
+    consecutive_failures: u32,
+    in_rotation: bool,
+}
+
+impl AccountLoad {
+    fn in_flight_txs(&self) -> u32 {
+        self.in_flight
+            .iter()
+            .map(|nonces| nonces.end - nonces.start)
+            .sum()
+    }
+}
+
+/// The accounts shared by all the senders, the first of them is the main one.
+pub struct SenderAccounts {
+    accounts: Vec<SenderAccount>,
+    loads: Mutex<Vec<AccountLoad>>,
+}
+
+impl SenderAccounts {
+    pub fn new(main: SenderAccount, additional: Vec<SenderAccount>) -> Self {
+        let accounts: Vec<_> = std::iter::once(main).chain(additional).collect();
+        let loads = accounts
+            .iter()
+            .map(|_| AccountLoad {
+                in_rotation: true,
+                ..AccountLoad::default()
+            })
+            .collect();
+        Self {
+            accounts,
+            loads: Mutex::new(loads),
+        }
+    }
+
+    /// Resolves the ids of the additional accounts of the config. The accounts which
+    /// can not be resolved are left out, so they do not keep the rest from being used.
+    pub async fn resolve<T: CoreInteractionWrapper>(
+        core_interaction_wrapper: &T,
+        config: &ForcedExitRequestsConfig,
+        main_account_id: AccountId,
+    ) -> Self {
+        let mut additional = Vec::with_capacity(config.additional_senders.len());
+        for sender in &config.additional_senders {
+            match core_interaction_wrapper
+                .get_account_id(sender.address)
+                .await
+            {
+                Ok(Some(account_id)) => additional.push(SenderAccount::with_signer(
+                    sender.address,
+                    account_id,
+                    signer_from_config(config, sender.address, sender.private_key.as_deref()),
+                )),
+                Ok(None) => vlog::warn!(
+                    "The ForcedExit sender account {:?} does not exist, it is not used",
+                    sender.address
+                ),
+                Err(err) => vlog::warn!(
+                    "Failed to resolve the ForcedExit sender account {:?}, it is not used: {}",
+                    sender.address,
+                    err
+                ),
+            }
+        }
+        Self::new(SenderAccount::main(config, main_account_id), additional)
+    }
+
+    pub fn main(&self) -> &SenderAccount {
+        &self.accounts[0]
+    }
+
+    fn lock_loads(&self) -> std::sync::MutexGuard<'_, Vec<AccountLoad>> {
+        self.loads
+            .lock()
+            .expect("Failed to get the sender accounts lock")
+    }
+
+    /// Picks the account in the rotation with the fewest transactions in flight,
+    /// the main one on a tie.
+    pub fn acquire(self: &Arc<Self>) -> SenderLease {
+        let loads = self.lock_loads();
+        let index = loads
+            .iter()
+            .enumerate()
+            .filter(|(_, load)| load.in_rotation)
+            .min_by_key(|(index, load)| (load.in_flight_txs(), *index))
+            .map(|(index, _)| index)
+            .expect("The last account is never taken out of the rotation");
+        SenderLease {
+            accounts: Arc::clone(self),
+            index,
+            nonces: None,
+        }
+    }
+
+    /// The addresses of the accounts in the rotation.
+    pub fn in_rotation(&self) -> Vec<Address> {
+        let loads = self.lock_loads();
+        self.accounts
+            .iter()
+            .zip(loads.iter())
+            .filter(|(_, load)| load.in_rotation)
+            .map(|(account, _)| account.address)
+            .collect()
+    }
+
+    /// The number of the transactions in flight sent from each of the accounts.
+    pub fn in_flight_txs(&self) -> Vec<(Address, u32)> {
+        let loads = self.lock_loads();
+        self.accounts
+            .iter()
+            .zip(loads.iter())
+            .map(|(account, load)| (account.address, load.in_flight_txs()))
+            .collect()
+    }
+}
+
+/// The account picked to send the batch of a request. The nonces of the batch count as
+/// in flight until the lease is dropped, i.e. once the batch is awaited.
+pub struct SenderLease {
+    accounts: Arc<SenderAccounts>,
+    index: usize,
+    nonces: Option<Range<u32>>,
+}
+
+impl SenderLease {
+    pub fn account(&self) -> &SenderAccount {
+        &self.accounts.accounts[self.index]
+    }
+
+    /// Records the nonces of the batch sent from the account.
+    pub fn sent(&mut self, first_nonce: Nonce, txs_count: usize) {
+        let nonces = *first_nonce..*first_nonce + txs_count as u32;
+        let mut loads = self.accounts.lock_loads();
+        let in_flight = &mut loads[self.index].in_flight;
+        if let Some(previous) = self.nonces.take() {
+            in_flight.retain(|nonces| *nonces != previous);
+        }
+        in_flight.push(nonces.clone());
+        self.nonces = Some(nonces);
+    }
+
+    /// The batch has been committed, the account keeps its place in the rotation.
+    pub fn succeeded(&self) {
+        self.accounts.lock_loads()[self.index].consecutive_failures = 0;
+    }
+
+    /// The batch has been rejected or has failed, which may be the fault of the account.
+    pub fn failed(&self) {
+        let mut loads = self.accounts.lock_loads();
+        loads[self.index].consecutive_failures += 1;
+        if loads[self.index].consecutive_failures < MAX_CONSECUTIVE_FAILURES
+            || !loads[self.index].in_rotation
+        {
+            return;
+        }
+        let account = self.account();
+        if loads.iter().filter(|load| load.in_rotation).count() == 1 {
+            vlog::error!(
+                "The batches of the ForcedExit sender account {:?} keep failing, \
+                 it is the last one left in the rotation",
+                account.address
+            );
+            return;
+        }
+        loads[self.index].in_rotation = false;
+        vlog::warn!(
+            "The ForcedExit sender account {:?} is taken out of the rotation \
+             after {} failed batches in a row",
+            account.address,
+            MAX_CONSECUTIVE_FAILURES
+        );
+        metrics::increment_counter!("forced_exit_requests.senders_out_of_rotation");
+    }
+}
+
+impl Drop for SenderLease {
+    fn drop(&mut self) {
+        if let Some(nonces) = self.nonces.take() {
+            self.accounts.lock_loads()[self.index]
+                .in_flight
+                .retain(|in_flight| *in_flight != nonces);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "0x0092788f3890ed50dcab7f72fb574a0a9d30b1bc778ba076c609c311a8555352";
+
+    fn accounts(count: u8) -> Arc<SenderAccounts> {
+        let account = |i: u8| SenderAccount::new(Address::repeat_byte(i), AccountId(i.into()), KEY);
+        Arc::new(SenderAccounts::new(
+            account(0),
+            (1..count).map(account).collect(),
+        ))
+    }
+
+    #[test]
+    fn least_loaded_account_is_picked() {
+        let accounts = accounts(3);
+
+        let mut first = accounts.acquire();
+        assert_eq!(first.account().account_id, AccountId(0));
+        first.sent(Nonce(10), 3);
+        let mut second = accounts.acquire();
+        assert_eq!(second.account().account_id, AccountId(1));
+        second.sent(Nonce(4), 1);
+        let mut third = accounts.acquire();
+        assert_eq!(third.account().account_id, AccountId(2));
+        third.sent(Nonce(7), 2);
+        // The account with the single transaction in flight is the least loaded one
+        let fourth = accounts.acquire();
+        assert_eq!(fourth.account().account_id, AccountId(1));
+        assert_eq!(
+            accounts.in_flight_txs(),
+            vec![
+                (Address::repeat_byte(0), 3),
+                (Address::repeat_byte(1), 1),
+                (Address::repeat_byte(2), 2),
+            ]
+        );
+
+        // The awaited batches are no longer in flight
+        drop(first);
+        assert_eq!(accounts.in_flight_txs()[0], (Address::repeat_byte(0), 0));
+        assert_eq!(accounts.acquire().account().account_id, AccountId(0));
+    }
+
+    #[test]
+    fn failing_account_leaves_the_rotation() {
+        let accounts = accounts(2);
+
+        for _ in 0..MAX_CONSECUTIVE_FAILURES - 1 {
+            accounts.acquire().failed();
+        }
+        // The success resets the failures
+        accounts.acquire().succeeded();
+        for _ in 0..MAX_CONSECUTIVE_FAILURES - 1 {
+            accounts.acquire().failed();
+        }
+        assert_eq!(accounts.in_rotation().len(), 2);
+        accounts.acquire().failed();
+        assert_eq!(accounts.in_rotation(), vec![Address::repeat_byte(1)]);
+        assert_eq!(accounts.acquire().account().account_id, AccountId(1));
+
+        // The last account is kept in the rotation, nothing would be sent otherwise
+        for _ in 0..MAX_CONSECUTIVE_FAILURES {
+            accounts.acquire().failed();
+        }
+        assert_eq!(accounts.in_rotation(), vec![Address::repeat_byte(1)]);
+    }
+}
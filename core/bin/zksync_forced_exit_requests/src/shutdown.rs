@@ -0,0 +1,143 @@
+//! The graceful shutdown of the component.
+//!
+//! Once the shutdown is requested, the watcher stops polling and the senders take no new
+//! payments, neither do they retry the failed ones. The batches sent already are still
+//! awaited, though only until the drain deadline. The requests, the batches of which are
+//! not committed by then, are left in flight with a note for the operators, so they are
+//! settled by the reconciliation on the next start.
+
+use std::time::{Duration, Instant};
+
+use tokio::{sync::watch, time};
+
+// How long the tasks may take to stop after the drain deadline before they are given up on
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Requests the shutdown of the tasks holding its `ShutdownSignal`.
+#[derive(Debug)]
+pub struct ShutdownHandle {
+    deadline: watch::Sender<Option<Instant>>,
+    drain_timeout: Duration,
+}
+
+impl ShutdownHandle {
+    /// Requests the shutdown, the first request sets the drain deadline.
+    pub fn request(&self) {
+        if self.deadline.borrow().is_none() {
+            // Nobody is left to observe the request if the tasks are gone already
+            let _ = self
+                .deadline
+                .send(Some(Instant::now() + self.drain_timeout));
+        }
+    }
+
+    /// Requests the shutdown and waits until the tasks holding the signal are done,
+    /// though not much longer than the drain timeout.
+    pub async fn shutdown(self) {
+        self.request();
+        let stopped = time::timeout(
+            self.drain_timeout + SHUTDOWN_GRACE_PERIOD,
+            self.deadline.closed(),
+        )
+        .await;
+        match stopped {
+            Ok(()) => vlog::info!("The forced exit requests component has stopped"),
+            Err(_) => vlog::warn!("The forced exit requests component has not stopped in time"),
+        }
+    }
+}
+
+/// Tells the tasks whether the shutdown has been requested and until when the batches
+/// sent already are awaited.
+#[derive(Debug, Clone)]
+pub struct ShutdownSignal {
+    deadline: watch::Receiver<Option<Instant>>,
+}
+
+impl ShutdownSignal {
+    pub fn new(drain_timeout: Duration) -> (ShutdownHandle, Self) {
+        let (deadline_sender, deadline) = watch::channel(None);
+        let handle = ShutdownHandle {
+            deadline: deadline_sender,
+            drain_timeout,
+        };
+        (handle, Self { deadline })
+    }
+
+    /// The signal of the tasks which are never shut down gracefully.
+    pub fn never() -> Self {
+        let (_, deadline) = watch::channel(None);
+        Self { deadline }
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.deadline.borrow().is_some()
+    }
+
+    /// Whether the drain deadline has passed, so nothing is awaited anymore.
+    pub fn is_drained(&self) -> bool {
+        self.deadline
+            .borrow()
+            .map_or(false, |deadline| Instant::now() >= deadline)
+    }
+
+    /// Waits until the shutdown is requested, which may never happen.
+    pub async fn requested(&mut self) {
+        while !self.is_requested() {
+            if self.deadline.changed().await.is_err() {
+                // The handle is gone, nobody is going to request the shutdown
+                futures::future::pending::<()>().await;
+            }
+        }
+    }
+
+    /// Waits until the shutdown is requested and the drain deadline passes.
+    pub async fn drained(&mut self) {
+        self.requested().await;
+        let deadline = *self.deadline.borrow();
+        if let Some(deadline) = deadline {
+            time::sleep_until(time::Instant::from_std(deadline)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn sent_batches_are_drained_until_the_deadline() {
+        let (handle, mut signal) = ShutdownSignal::new(Duration::from_millis(50));
+        assert!(!signal.is_requested());
+        assert!(!signal.is_drained());
+        assert!(time::timeout(Duration::from_millis(20), signal.requested())
+            .await
+            .is_err());
+
+        handle.request();
+        signal.requested().await;
+        assert!(signal.is_requested());
+        assert!(!signal.is_drained());
+        // The repeated request does not move the deadline
+        let deadline = *signal.deadline.borrow();
+        handle.request();
+        assert_eq!(*signal.deadline.borrow(), deadline);
+
+        signal.drained().await;
+        assert!(signal.is_drained());
+
+        // The handle waits for the tasks holding the signal
+        let task = tokio::spawn(async move { signal.drained().await });
+        handle.shutdown().await;
+        assert!(task.await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn signal_without_handle_is_never_requested() {
+        let mut signal = ShutdownSignal::never();
+        assert!(time::timeout(Duration::from_millis(20), signal.requested())
+            .await
+            .is_err());
+        assert!(!signal.is_drained());
+    }
+}
@@ -0,0 +1,446 @@
+//! Signing of the transactions sent from the sender accounts.
+//!
+//! The transactions are either signed with the private keys of the config, or by the external
+//! signing service, so that the keys do not have to be kept in the config at all. The service
+//! is given the transaction along with the message to sign on behalf of the account, and the
+//! signature it returns is verified before the transaction is sent.
+
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::time;
+
+use zksync_config::ForcedExitRequestsConfig;
+use zksync_crypto::franklin_crypto::eddsa::PrivateKey;
+use zksync_types::{
+    tx::{EthSignData, PackedEthSignature, TxEthSignature, TxSignature},
+    Address, ForcedExit, PubKeyHash, SignedZkSyncTx, Transfer, ZkSyncTx, H256,
+};
+
+use super::utils::{read_signing_key, Engine};
+
+/// The delay before the second attempt to sign, doubled after every next failed one.
+const EXTERNAL_SIGNER_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+#[async_trait::async_trait]
+pub trait FeSigner: Send + Sync {
+    /// The hash of the public key the transactions are signed with, which the signing key
+    /// of the account is expected to be set to.
+    async fn pub_key_hash(&self) -> anyhow::Result<PubKeyHash>;
+
+    async fn sign_forced_exit(&self, tx: ForcedExit) -> anyhow::Result<SignedZkSyncTx>;
+
+    async fn sign_transfer(&self, tx: Transfer) -> anyhow::Result<SignedZkSyncTx>;
+}
+
+/// Selects the signer of the account: the external one if configured,
+/// the private key of the config otherwise.
+pub fn signer_from_config(
+    config: &ForcedExitRequestsConfig,
+    address: Address,
+    private_key: Option<&str>,
+) -> Arc<dyn FeSigner> {
+    match &config.external_signer_url {
+        Some(url) => Arc::new(ExternalSigner::new(
+            url.clone(),
+            config.external_signer_auth_token.clone(),
+            config.external_signer_timeout(),
+            config.external_signer_attempts,
+            address,
+        )),
+        None => {
+            let private_key = private_key.unwrap_or_else(|| {
+                panic!("The private key of the sender {:?} is not set", address)
+            });
+            Arc::new(LocalSigner::from_hex(private_key))
+        }
+    }
+}
+
+/// Signs the message of the transaction with the Ethereum private key of the account,
+/// for the servers which require the Ethereum signatures of the transactions.
+pub fn eth_sign_data(private_key: &H256, message: String) -> anyhow::Result<EthSignData> {
+    let message = message.into_bytes();
+    let signature = PackedEthSignature::sign(private_key, &message)?;
+    Ok(EthSignData {
+        signature: TxEthSignature::EthereumSignature(signature),
+        message,
+    })
+}
+
+fn signed(tx: ZkSyncTx) -> SignedZkSyncTx {
+    SignedZkSyncTx {
+        tx,
+        eth_sign_data: None,
+        created_at: Utc::now(),
+    }
+}
+
+/// Signs the transactions with the private key of the account.
+pub struct LocalSigner {
+    private_key: PrivateKey<Engine>,
+}
+
+impl LocalSigner {
+    pub fn new(private_key: PrivateKey<Engine>) -> Self {
+        Self { private_key }
+    }
+
+    /// Reads the `0x`-prefixed hex key.
+    pub fn from_hex(private_key: &str) -> Self {
+        let private_key = hex::decode(&private_key[2..]).expect("Decoding private key failed");
+        Self::new(read_signing_key(&private_key).expect("Reading private key failed"))
+    }
+}
+
+#[async_trait::async_trait]
+impl FeSigner for LocalSigner {
+    async fn pub_key_hash(&self) -> anyhow::Result<PubKeyHash> {
+        Ok(PubKeyHash::from_privkey(&self.private_key))
+    }
+
+    async fn sign_forced_exit(&self, mut tx: ForcedExit) -> anyhow::Result<SignedZkSyncTx> {
+        tx.signature = TxSignature::sign_musig(&self.private_key, &tx.get_bytes());
+        tx.check_correctness()?;
+        Ok(signed(ZkSyncTx::ForcedExit(Box::new(tx))))
+    }
+
+    async fn sign_transfer(&self, mut tx: Transfer) -> anyhow::Result<SignedZkSyncTx> {
+        tx.signature = TxSignature::sign_musig(&self.private_key, &tx.get_bytes());
+        tx.check_correctness()?;
+        Ok(signed(ZkSyncTx::Transfer(Box::new(tx))))
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SignRequest<'a, T> {
+    address: Address,
+    tx_type: &'static str,
+    tx: &'a T,
+    /// The bytes of the transaction to be signed, `0x`-prefixed hex.
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SignResponse {
+    signature: TxSignature,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PubKeyHashResponse {
+    pub_key_hash: PubKeyHash,
+}
+
+/// Asks the signing service to sign the transactions of the account. The service is expected
+/// to serve `POST /sign` and `GET /pub_key_hash/{address}`, authorized with the bearer token.
+pub struct ExternalSigner {
+    client: reqwest::Client,
+    url: String,
+    auth_token: Option<String>,
+    attempts: u32,
+    address: Address,
+}
+
+impl ExternalSigner {
+    pub fn new(
+        url: String,
+        auth_token: Option<String>,
+        timeout: Duration,
+        attempts: u32,
+        address: Address,
+    ) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .expect("Failed to create the external signer client");
+        Self {
+            client,
+            url: url.trim_end_matches('/').to_owned(),
+            auth_token,
+            attempts,
+            address,
+        }
+    }
+
+    /// Sends the request built anew for every attempt, retrying the transient failures:
+    /// the timeouts, the connection errors and the unavailability of the service.
+    async fn send<R>(
+        &self,
+        request: impl Fn() -> reqwest::RequestBuilder + Send,
+    ) -> anyhow::Result<R>
+    where
+        R: serde::de::DeserializeOwned,
+    {
+        let mut delay = EXTERNAL_SIGNER_RETRY_DELAY;
+        let mut attempt = 1;
+        loop {
+            let mut builder = request();
+            if let Some(auth_token) = &self.auth_token {
+                builder = builder.bearer_auth(auth_token);
+            }
+            let result = builder
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            let err = match result {
+                Ok(response) => return Ok(response.json().await?),
+                Err(err) => err,
+            };
+            let transient = err.is_timeout()
+                || err.is_connect()
+                || err.status().map_or(false, |status| {
+                    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                });
+            metrics::increment_counter!("forced_exit_requests.external_signer_failures");
+            if !transient || attempt >= self.attempts {
+                anyhow::bail!(
+                    "The request to the external signer for {:?} has failed after {} attempts: {}",
+                    self.address,
+                    attempt,
+                    err
+                );
+            }
+            vlog::warn!(
+                "The request to the external signer for {:?} has failed, retrying: {}",
+                self.address,
+                err
+            );
+            time::sleep(delay).await;
+            delay *= 2;
+            attempt += 1;
+        }
+    }
+
+    async fn sign<T: Serialize + Sync>(
+        &self,
+        tx_type: &'static str,
+        tx: &T,
+        message: &[u8],
+    ) -> anyhow::Result<TxSignature> {
+        let request = SignRequest {
+            address: self.address,
+            tx_type,
+            tx,
+            message: format!("0x{}", hex::encode(message)),
+        };
+        let url = format!("{}/sign", self.url);
+        let response: SignResponse = self.send(|| self.client.post(&url).json(&request)).await?;
+        Ok(response.signature)
+    }
+}
+
+#[async_trait::async_trait]
+impl FeSigner for ExternalSigner {
+    async fn pub_key_hash(&self) -> anyhow::Result<PubKeyHash> {
+        let url = format!("{}/pub_key_hash/{:?}", self.url, self.address);
+        let response: PubKeyHashResponse = self.send(|| self.client.get(&url)).await?;
+        Ok(response.pub_key_hash)
+    }
+
+    async fn sign_forced_exit(&self, mut tx: ForcedExit) -> anyhow::Result<SignedZkSyncTx> {
+        tx.signature = self.sign("ForcedExit", &tx, &tx.get_bytes()).await?;
+        // The signature returned by the service is not trusted blindly
+        tx.check_correctness().map_err(|err| {
+            anyhow::anyhow!(
+                "The external signer has returned an invalid ForcedExit: {}",
+                err
+            )
+        })?;
+        Ok(signed(ZkSyncTx::ForcedExit(Box::new(tx))))
+    }
+
+    async fn sign_transfer(&self, mut tx: Transfer) -> anyhow::Result<SignedZkSyncTx> {
+        tx.signature = self.sign("Transfer", &tx, &tx.get_bytes()).await?;
+        tx.check_correctness().map_err(|err| {
+            anyhow::anyhow!(
+                "The external signer has returned an invalid Transfer: {}",
+                err
+            )
+        })?;
+        Ok(signed(ZkSyncTx::Transfer(Box::new(tx))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use actix_web::{web, App, HttpResponse};
+    use num::BigUint;
+    use serde_json::{json, Value};
+    use zksync_types::{tx::TimeRange, AccountId, Nonce, TokenId};
+
+    use super::*;
+
+    const KEY: &str = "0x0092788f3890ed50dcab7f72fb574a0a9d30b1bc778ba076c609c311a8555352";
+    const AUTH_TOKEN: &str = "signer-token";
+    const TIMEOUT: Duration = Duration::from_millis(500);
+
+    struct MockSigner {
+        key: PrivateKey<Engine>,
+        // The number of the requests failed with 503 before the signing ones
+        unavailable: AtomicU32,
+        // Signs the message other than the one given
+        tampered: bool,
+    }
+
+    async fn sign(
+        state: web::Data<MockSigner>,
+        request: web::HttpRequest,
+        body: web::Json<Value>,
+    ) -> HttpResponse {
+        let authorized = request
+            .headers()
+            .get("Authorization")
+            .and_then(|header| header.to_str().ok())
+            == Some(format!("Bearer {}", AUTH_TOKEN).as_str());
+        if !authorized {
+            return HttpResponse::Unauthorized().finish();
+        }
+        if state
+            .unavailable
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                left.checked_sub(1)
+            })
+            .is_ok()
+        {
+            return HttpResponse::ServiceUnavailable().finish();
+        }
+        let message = body["message"].as_str().unwrap();
+        let mut message = hex::decode(&message[2..]).unwrap();
+        if state.tampered {
+            message.push(0);
+        }
+        HttpResponse::Ok()
+            .json(json!({ "signature": TxSignature::sign_musig(&state.key, &message) }))
+    }
+
+    async fn pub_key_hash(state: web::Data<MockSigner>) -> HttpResponse {
+        HttpResponse::Ok().json(json!({ "pubKeyHash": PubKeyHash::from_privkey(&state.key) }))
+    }
+
+    fn start_mock_signer(unavailable: u32, tampered: bool) -> actix_test::TestServer {
+        let state = web::Data::new(MockSigner {
+            key: LocalSigner::from_hex(KEY).private_key,
+            unavailable: AtomicU32::new(unavailable),
+            tampered,
+        });
+        actix_test::start(move || {
+            App::new()
+                .app_data(state.clone())
+                .route("/sign", web::post().to(sign))
+                .route("/pub_key_hash/{address}", web::get().to(pub_key_hash))
+        })
+    }
+
+    fn forced_exit() -> ForcedExit {
+        ForcedExit::new(
+            AccountId(7),
+            Address::repeat_byte(0x12),
+            TokenId(1),
+            BigUint::from(0u32),
+            Nonce(3),
+            TimeRange::default(),
+            None,
+        )
+    }
+
+    fn external_signer(server: &actix_test::TestServer, attempts: u32) -> ExternalSigner {
+        ExternalSigner::new(
+            server.url(""),
+            Some(AUTH_TOKEN.to_owned()),
+            TIMEOUT,
+            attempts,
+            Address::repeat_byte(0x5e),
+        )
+    }
+
+    fn signer_of(tx: &SignedZkSyncTx) -> PubKeyHash {
+        match &tx.tx {
+            ZkSyncTx::ForcedExit(tx) => tx.verify_signature().unwrap().0,
+            tx => panic!("Unexpected transaction {:?}", tx),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn external_signer_signs_forced_exit() {
+        let expected = LocalSigner::from_hex(KEY);
+        // The transient failures are retried
+        let server = start_mock_signer(2, false);
+        let signer = external_signer(&server, 3);
+
+        let tx = signer.sign_forced_exit(forced_exit()).await.unwrap();
+        assert_eq!(signer_of(&tx), expected.pub_key_hash().await.unwrap());
+        assert_eq!(
+            signer.pub_key_hash().await.unwrap(),
+            expected.pub_key_hash().await.unwrap()
+        );
+        // The same transaction is signed the same way by the local signer
+        let local = expected.sign_forced_exit(forced_exit()).await.unwrap();
+        assert_eq!(tx.tx.hash(), local.tx.hash());
+        assert_eq!(signer_of(&local), signer_of(&tx));
+    }
+
+    #[actix_rt::test]
+    async fn config_selects_the_signer() {
+        let server = start_mock_signer(0, false);
+        let local = ForcedExitRequestsConfig {
+            sender_private_key: KEY.to_owned(),
+            external_signer_url: None,
+            ..ForcedExitRequestsConfig::from_env()
+        };
+        let external = ForcedExitRequestsConfig {
+            sender_private_key: String::new(),
+            external_signer_url: Some(server.url("")),
+            external_signer_auth_token: Some(AUTH_TOKEN.to_owned()),
+            ..local.clone()
+        };
+
+        // Both sign with the same key, which is only known to the mock in the second case
+        for config in &[local, external] {
+            let signer = signer_from_config(
+                config,
+                config.sender_account_address,
+                Some(&config.sender_private_key),
+            );
+            let tx = signer.sign_forced_exit(forced_exit()).await.unwrap();
+            assert_eq!(signer_of(&tx), signer.pub_key_hash().await.unwrap());
+        }
+    }
+
+    #[actix_rt::test]
+    async fn external_signer_failures() {
+        // The attempts are exhausted
+        let server = start_mock_signer(3, false);
+        assert!(external_signer(&server, 3)
+            .sign_forced_exit(forced_exit())
+            .await
+            .is_err());
+
+        // The unauthorized requests are not retried
+        let server = start_mock_signer(0, false);
+        let unauthorized =
+            ExternalSigner::new(server.url(""), None, TIMEOUT, 3, Address::repeat_byte(0x5e));
+        assert!(unauthorized.sign_forced_exit(forced_exit()).await.is_err());
+
+        // The signature of another message is refused
+        let server = start_mock_signer(0, true);
+        assert!(external_signer(&server, 1)
+            .sign_forced_exit(forced_exit())
+            .await
+            .is_err());
+
+        // The unreachable service fails after the attempts
+        let unreachable = ExternalSigner::new(
+            "http://127.0.0.1:1".to_owned(),
+            None,
+            TIMEOUT,
+            2,
+            Address::repeat_byte(0x5e),
+        );
+        assert!(unreachable.sign_forced_exit(forced_exit()).await.is_err());
+    }
+}
@@ -0,0 +1,191 @@
+//! Guard against several server instances processing the requests at once.
+//!
+//! The requests are not leased row by row, so two instances watching the payments would
+//! both send the `ForcedExit` transactions for them. The instance processing the requests
+//! holds the advisory lock of the database instead, taken with a session of its own: the
+//! lock is released as soon as the session is closed, e.g. once the instance crashes.
+//!
+//! The guard checks the lock periodically. If the session is lost, the processing is
+//! paused and the guard tries to take the lock again with a new session, while another
+//! instance may take it over in the meantime. The API is served by every instance.
+
+use std::{env, process, time::Duration};
+
+use tokio::{sync::watch, task::JoinHandle, time};
+
+use zksync_config::configs::forced_exit_requests::SingletonMode;
+use zksync_storage::StorageProcessor;
+
+use crate::spawner::ForcedExitSpawner;
+
+const LOCK_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Tells whether the instance is the one processing the requests.
+#[derive(Debug, Clone)]
+pub struct SingletonLock {
+    held: watch::Receiver<bool>,
+}
+
+impl SingletonLock {
+    /// The lock of the instance which is known to be the only one, e.g. the remote component.
+    pub fn unguarded() -> Self {
+        let (_, held) = watch::channel(true);
+        Self { held }
+    }
+
+    /// The lock taken and lost by the test itself.
+    #[cfg(test)]
+    pub(crate) fn manual(held: bool) -> (watch::Sender<bool>, Self) {
+        let (held_sender, held) = watch::channel(held);
+        (held_sender, Self { held })
+    }
+
+    pub fn is_held(&self) -> bool {
+        *self.held.borrow()
+    }
+
+    /// Waits until the lock is taken, which may never happen.
+    pub async fn wait_held(&mut self) {
+        while !*self.held.borrow() {
+            if self.held.changed().await.is_err() {
+                // The guard is gone, nobody is going to take the lock
+                futures::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+struct SingletonGuard {
+    instance: String,
+    mode: SingletonMode,
+    held: watch::Sender<bool>,
+    /// The session holding the lock.
+    session: Option<StorageProcessor<'static>>,
+    started: bool,
+    /// Whether the instance has refused to process the requests for good.
+    refused: bool,
+}
+
+impl SingletonGuard {
+    fn new(instance: String, mode: SingletonMode) -> (Self, SingletonLock) {
+        let (held_sender, held) = watch::channel(false);
+        let guard = Self {
+            instance,
+            mode,
+            held: held_sender,
+            session: None,
+            started: false,
+            refused: false,
+        };
+        (guard, SingletonLock { held })
+    }
+
+    async fn try_acquire(&mut self) -> anyhow::Result<bool> {
+        let mut session = StorageProcessor::establish_connection().await?;
+        let mut fe_schema = session.forced_exit_requests_schema();
+        if fe_schema.try_acquire_singleton_lock(&self.instance).await? {
+            self.session = Some(session);
+            return Ok(true);
+        }
+
+        match fe_schema.load_singleton_holder().await? {
+            Some(holder) => vlog::warn!(
+                "ForcedExit requests are processed by the instance {} (session {})",
+                holder.instance,
+                holder.session_pid
+            ),
+            None => vlog::warn!("ForcedExit requests are processed by another instance"),
+        }
+        Ok(false)
+    }
+
+    /// Whether the session taken the lock with is still alive and holds it.
+    async fn check_held(&mut self) -> bool {
+        let session = match self.session.as_mut() {
+            Some(session) => session,
+            None => return false,
+        };
+        match session
+            .forced_exit_requests_schema()
+            .singleton_lock_held()
+            .await
+        {
+            Ok(held) => held,
+            Err(err) => {
+                vlog::warn!("Failed to check the ForcedExit requests lock: {}", err);
+                false
+            }
+        }
+    }
+
+    fn set_held(&mut self, held: bool) {
+        metrics::gauge!(
+            "forced_exit_requests.singleton_held",
+            if held { 1.0 } else { 0.0 }
+        );
+        // The processing actors may be gone, e.g. if the feature is disabled
+        let _ = self.held.send(held);
+    }
+
+    async fn step(&mut self) {
+        if self.session.is_some() {
+            if self.check_held().await {
+                return;
+            }
+            vlog::error!("ForcedExit requests lock is lost, the processing is paused");
+            self.session = None;
+            self.set_held(false);
+        }
+        if self.refused {
+            return;
+        }
+
+        match self.try_acquire().await {
+            Ok(true) => {
+                vlog::info!(
+                    "ForcedExit requests are processed by this instance ({})",
+                    self.instance
+                );
+                self.set_held(true);
+            }
+            Ok(false) => {
+                self.set_held(false);
+                if !self.started && self.mode == SingletonMode::Strict {
+                    vlog::error!(
+                        "ForcedExit requests are not processed by this instance, \
+                         stop the other one and restart this instance to process them"
+                    );
+                    self.refused = true;
+                }
+            }
+            // The database being unavailable on startup does not mean another instance holds the lock
+            Err(err) => {
+                vlog::warn!("Failed to take the ForcedExit requests lock: {}", err);
+                return;
+            }
+        }
+        self.started = true;
+    }
+
+    async fn run(mut self) {
+        let mut timer = time::interval(LOCK_CHECK_INTERVAL);
+        loop {
+            timer.tick().await;
+            self.step().await;
+        }
+    }
+}
+
+/// The name the instance holds the lock with.
+fn instance_name() -> String {
+    let host = env::var("HOSTNAME").unwrap_or_else(|_| "unknown".to_string());
+    format!("{}:{}", host, process::id())
+}
+
+pub fn run_singleton_guard(
+    spawner: &ForcedExitSpawner,
+    mode: SingletonMode,
+) -> (JoinHandle<()>, SingletonLock) {
+    let (guard, lock) = SingletonGuard::new(instance_name(), mode);
+    (spawner.spawn(guard.run()), lock)
+}
@@ -0,0 +1,186 @@
+//! All the tasks of the component are spawned through `ForcedExitSpawner`, so they
+//! can be moved off the runtime of the API server. The component polls the receipts
+//! of its transactions in tight loops, which otherwise delays the handling of the
+//! API requests sharing the worker threads with it.
+
+use std::{future::Future, sync::mpsc, thread};
+
+use futures::future;
+use tokio::{
+    runtime::{self, Handle},
+    task::JoinHandle,
+};
+
+use zksync_config::ForcedExitRequestsConfig;
+
+#[derive(Debug, Clone)]
+pub struct ForcedExitSpawner {
+    handle: Handle,
+    dedicated: bool,
+}
+
+impl ForcedExitSpawner {
+    /// Spawns the tasks on the runtime of the caller.
+    pub fn shared() -> Self {
+        Self {
+            handle: Handle::current(),
+            dedicated: false,
+        }
+    }
+
+    /// Starts the multi-thread runtime used only by this component. The runtime
+    /// is kept alive by its own thread until the process exits.
+    pub fn dedicated(worker_threads: usize) -> Self {
+        let (handle_sender, handle_receiver) = mpsc::channel();
+        thread::Builder::new()
+            .name("forced-exit-runtime".to_string())
+            .spawn(move || {
+                let runtime = runtime::Builder::new_multi_thread()
+                    .worker_threads(worker_threads)
+                    .thread_name("forced-exit-worker")
+                    .enable_all()
+                    .build()
+                    .expect("Unable to build runtime for the ForcedExit requests");
+                handle_sender
+                    .send(runtime.handle().clone())
+                    .expect("The spawner is dropped before the runtime is started");
+
+                runtime.block_on(future::pending::<()>());
+            })
+            .expect("Failed to start the ForcedExit requests runtime");
+
+        Self {
+            handle: handle_receiver
+                .recv()
+                .expect("Failed to start the ForcedExit requests runtime"),
+            dedicated: true,
+        }
+    }
+
+    pub fn from_config(config: &ForcedExitRequestsConfig) -> Self {
+        match config.runtime_threads {
+            Some(worker_threads) => Self::dedicated(worker_threads),
+            None => Self::shared(),
+        }
+    }
+
+    /// Whether the tasks are isolated from the rest of the server.
+    pub fn is_dedicated(&self) -> bool {
+        self.dedicated
+    }
+
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.handle.spawn(future)
+    }
+
+    /// Runs the function within the runtime context, so the tasks it spawns
+    /// with `tokio::spawn` end up on the runtime of the component as well.
+    pub fn enter<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _guard = self.handle.enter();
+        f()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    };
+
+    use tokio::time;
+
+    use super::*;
+
+    const API_WORKER_THREADS: usize = 2;
+    const POLLING_TASKS: usize = 8;
+    const REQUESTS: usize = 10;
+
+    // The receipt polling tasks spend most of the time doing the synchronous work
+    // between the short awaits, so they never let the worker threads park
+    fn start_polling_storm(spawner: &ForcedExitSpawner) -> Arc<AtomicBool> {
+        let stopped = Arc::new(AtomicBool::new(false));
+        for _ in 0..POLLING_TASKS {
+            let stopped = stopped.clone();
+            spawner.spawn(async move {
+                while !stopped.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(5));
+                    tokio::task::yield_now().await;
+                }
+            });
+        }
+        stopped
+    }
+
+    // Returns the longest delay of the API request handling, each request only waits for a timer
+    async fn max_api_latency() -> Duration {
+        let mut max_latency = Duration::default();
+        for _ in 0..REQUESTS {
+            let started_at = Instant::now();
+            tokio::spawn(time::sleep(Duration::from_millis(1)))
+                .await
+                .unwrap();
+            max_latency = max_latency.max(started_at.elapsed());
+        }
+        max_latency
+    }
+
+    fn api_runtime() -> runtime::Runtime {
+        runtime::Builder::new_multi_thread()
+            .worker_threads(API_WORKER_THREADS)
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn api_latency_under_polling_storm() {
+        let api_runtime = api_runtime();
+        let idle_latency = api_runtime.block_on(max_api_latency());
+
+        let spawner = ForcedExitSpawner::dedicated(API_WORKER_THREADS);
+        assert!(spawner.is_dedicated());
+        let stopped = start_polling_storm(&spawner);
+        let isolated_latency = api_runtime.block_on(max_api_latency());
+        stopped.store(true, Ordering::Relaxed);
+
+        let shared_latency = api_runtime.block_on(async {
+            let spawner = ForcedExitSpawner::shared();
+            assert!(!spawner.is_dedicated());
+            let stopped = start_polling_storm(&spawner);
+            let latency = max_api_latency().await;
+            stopped.store(true, Ordering::Relaxed);
+            latency
+        });
+
+        assert!(
+            isolated_latency < idle_latency + Duration::from_millis(20),
+            "API latency is affected by the isolated component: {:?} while idle, {:?} under load",
+            idle_latency,
+            isolated_latency
+        );
+        assert!(
+            shared_latency > isolated_latency,
+            "The polling storm has not affected the shared runtime: {:?}, isolated {:?}",
+            shared_latency,
+            isolated_latency
+        );
+    }
+
+    #[test]
+    fn enter_dedicated_runtime() {
+        let spawner = ForcedExitSpawner::dedicated(1);
+        let name =
+            spawner.enter(|| tokio::spawn(async { thread::current().name().map(String::from) }));
+
+        let name = api_runtime().block_on(name).unwrap();
+        assert_eq!(name.as_deref(), Some("forced-exit-worker"));
+    }
+}
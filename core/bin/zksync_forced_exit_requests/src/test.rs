@@ -1,29 +1,112 @@
-use std::{ops::Sub, sync::Mutex};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    ops::Sub,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use num::BigUint;
+use zksync_api::api_server::forced_exit_matcher::payment_rejection;
 use zksync_storage::chain::operations_ext::records::TxReceiptResponse;
 use zksync_types::Nonce;
 use zksync_types::{
-    forced_exit_requests::{ForcedExitRequest, ForcedExitRequestId},
+    forced_exit_requests::{
+        ExpectedForcedExitPayment, ForcedExitCancellation, ForcedExitCancellationKind,
+        ForcedExitLifecycleStatus, ForcedExitPacing, ForcedExitPacingState, ForcedExitPayment,
+        ForcedExitPipelineVersion, ForcedExitProcessingFailure, ForcedExitRefund,
+        ForcedExitRefundId, ForcedExitRefundStatus, ForcedExitRequest,
+        ForcedExitRequestActiveTarget, ForcedExitRequestDelivery, ForcedExitRequestDeliveryId,
+        ForcedExitRequestEscalation, ForcedExitRequestEvent, ForcedExitRequestId, ForcedExitRetry,
+        ForcedExitRetryId, ForcedExitTargetCheck, InjectedForcedExitPayment,
+        InjectedForcedExitPaymentId, InterruptedSubmission, PaymentMatchScheme, PaymentSourceState,
+        SaveForcedExitRefundQuery, SaveForcedExitRequestNoteQuery, SkippedForcedExit,
+        SubmissionError, UnmatchedPaymentReason, FORCED_EXIT_PIPELINE_VERSION,
+    },
     tx::TxHash,
-    AccountId, SignedZkSyncTx,
+    AccountId, Address, SignedZkSyncTx, Token, TokenId, TokenKind, ZkSyncTx, H256,
+};
+
+use super::{
+    core_interaction_wrapper::{
+        append_chunk, combine_batch, ensure_batch_not_empty, CoreInteractionWrapper,
+    },
+    shutdown::ShutdownHandle,
 };
 
-use super::core_interaction_wrapper::CoreInteractionWrapper;
+// The account id every target of the requests has
+pub const TEST_TARGET_ACCOUNT_ID: AccountId = AccountId(34);
 
 pub struct MockCoreInteractionWrapper {
     pub nonce: Nonce,
+    pub target_check: ForcedExitTargetCheck,
     pub requests: Mutex<Vec<ForcedExitRequest>>,
     pub tx_receipt: Option<TxReceiptResponse>,
+    // The receipts of the particular transactions, `tx_receipt` is used for the rest
+    pub receipts: Mutex<HashMap<TxHash, TxReceiptResponse>>,
+    // The transactions the receipts were queried for, per query
+    pub receipt_queries: Mutex<Vec<Vec<TxHash>>>,
     pub sent_txs: Mutex<Vec<SignedZkSyncTx>>,
+    // The requests the transactions of which have been sent, per batch
+    pub sent_batches: Mutex<Vec<Vec<ForcedExitRequestId>>>,
     // It is easier when keeping track of the deleted txs
     pub deleted_requests: Mutex<Vec<ForcedExitRequest>>,
+    pub failures: Mutex<HashMap<(ForcedExitRequestId, TokenId), u32>>,
+    pub processing_failures: Mutex<Vec<ForcedExitProcessingFailure>>,
+    pub pipeline_versions: Mutex<Vec<ForcedExitPipelineVersion>>,
+    pub escalations: Mutex<Vec<ForcedExitRequestEscalation>>,
+    // The tokens the metadata was queried for, in the order of the queries
+    pub token_lookups: Mutex<Vec<TokenId>>,
+    // The tokens can not be queried while it is unset, as if the database was unavailable
+    pub tokens_available: AtomicBool,
+    pub active_targets: Mutex<Vec<ForcedExitRequestActiveTarget>>,
+    pub payments: Mutex<Vec<ForcedExitPayment>>,
+    pub unmatched_payments: Mutex<Vec<(ForcedExitPayment, UnmatchedPaymentReason)>>,
+    pub payment_matches: Mutex<Vec<(H256, ForcedExitRequestId)>>,
+    // The claimed fulfillments, released the same way the storage does it
+    pub fulfillment_keys: Mutex<HashSet<(ForcedExitRequestId, Option<H256>)>>,
+    pub skipped_tokens: Mutex<Vec<(ForcedExitRequestId, SkippedForcedExit)>>,
+    pub cancellations: Mutex<Vec<ForcedExitCancellation>>,
+    pub payment_source_states: Mutex<Vec<PaymentSourceState>>,
+    pub expected_payments: Mutex<Vec<ExpectedForcedExitPayment>>,
+    pub injected_payments: Mutex<Vec<InjectedForcedExitPayment>>,
+    pub retries: Mutex<Vec<ForcedExitRetry>>,
+    pub pacing_override: Mutex<Option<ForcedExitPacing>>,
+    pub reported_pacing: Mutex<Option<ForcedExitPacingState>>,
+    // The outbox is filled by the status transitions the same way the storage does it
+    pub deliveries: Mutex<Vec<ForcedExitRequestDelivery>>,
+    pub refunds: Mutex<Vec<ForcedExitRefund>>,
+    // The batches are refused with the error while it is set
+    pub submission_error: Mutex<Option<SubmissionError>>,
+    // The ids the targets are resolved to one by one, `TEST_TARGET_ACCOUNT_ID` once used up,
+    // `None` for the target which has no account
+    pub target_account_ids: Mutex<VecDeque<Option<AccountId>>>,
+    pub recorded_target_account_ids: Mutex<HashMap<ForcedExitRequestId, AccountId>>,
+    pub notes: Mutex<Vec<SaveForcedExitRequestNoteQuery>>,
+    // The shutdown is requested right after the next batch is sent
+    pub shutdown_on_send: Mutex<Option<ShutdownHandle>>,
+    // The batches left unconfirmed by a crash, settled once the transactions are set or reset
+    pub interrupted_submissions: Mutex<Vec<InterruptedSubmission>>,
+    // The balance of the sender accounts in any token, spent by the refunds
+    pub sender_balance: Mutex<BigUint>,
+    // The fee of a single transaction in the token, the tokens without one are not quoted
+    pub tx_fees: Mutex<HashMap<TokenId, BigUint>>,
+    // The batches paying less than the fee are rejected for the outdated fee
+    pub required_batch_fee: Mutex<Option<BigUint>>,
 }
 
 impl Default for MockCoreInteractionWrapper {
     fn default() -> Self {
         Self {
             nonce: Nonce(0),
+            // For tests it is better to have all the targets eligible by default
+            target_check: ForcedExitTargetCheck {
+                old_enough: true,
+                nonce: Some(Nonce(0)),
+            },
             requests: Mutex::new(vec![]),
             tx_receipt: Some(TxReceiptResponse {
                 // All the values here don't matter except for success = true
@@ -34,8 +117,41 @@ impl Default for MockCoreInteractionWrapper {
                 fail_reason: None,
                 prover_run: None,
             }),
+            receipts: Mutex::new(HashMap::new()),
+            receipt_queries: Mutex::new(vec![]),
             sent_txs: Mutex::new(vec![]),
+            sent_batches: Mutex::new(vec![]),
             deleted_requests: Mutex::new(vec![]),
+            failures: Mutex::new(HashMap::new()),
+            processing_failures: Mutex::new(vec![]),
+            pipeline_versions: Mutex::new(vec![]),
+            escalations: Mutex::new(vec![]),
+            token_lookups: Mutex::new(vec![]),
+            tokens_available: AtomicBool::new(true),
+            active_targets: Mutex::new(vec![]),
+            payments: Mutex::new(vec![]),
+            unmatched_payments: Mutex::new(vec![]),
+            payment_matches: Mutex::new(vec![]),
+            fulfillment_keys: Mutex::new(HashSet::new()),
+            skipped_tokens: Mutex::new(vec![]),
+            cancellations: Mutex::new(vec![]),
+            payment_source_states: Mutex::new(vec![]),
+            expected_payments: Mutex::new(vec![]),
+            injected_payments: Mutex::new(vec![]),
+            retries: Mutex::new(vec![]),
+            pacing_override: Mutex::new(None),
+            reported_pacing: Mutex::new(None),
+            deliveries: Mutex::new(vec![]),
+            refunds: Mutex::new(vec![]),
+            submission_error: Mutex::new(None),
+            target_account_ids: Mutex::new(VecDeque::new()),
+            recorded_target_account_ids: Mutex::new(HashMap::new()),
+            notes: Mutex::new(vec![]),
+            shutdown_on_send: Mutex::new(None),
+            interrupted_submissions: Mutex::new(vec![]),
+            sender_balance: Mutex::new(BigUint::from(u128::MAX)),
+            tx_fees: Mutex::new(HashMap::new()),
+            required_batch_fee: Mutex::new(None),
         }
     }
 }
@@ -55,10 +171,162 @@ impl MockCoreInteractionWrapper {
         index_option.ok_or_else(|| anyhow::Error::msg("Element not found"))
     }
 
-    fn lock_sent_txs(&self) -> std::sync::MutexGuard<'_, Vec<SignedZkSyncTx>> {
+    pub fn lock_sent_txs(&self) -> std::sync::MutexGuard<'_, Vec<SignedZkSyncTx>> {
         self.sent_txs.lock().expect("Failed to get the write lock")
     }
 
+    pub fn lock_receipts(&self) -> std::sync::MutexGuard<'_, HashMap<TxHash, TxReceiptResponse>> {
+        self.receipts
+            .lock()
+            .expect("Failed to get the receipts lock")
+    }
+
+    pub fn lock_escalations(&self) -> std::sync::MutexGuard<'_, Vec<ForcedExitRequestEscalation>> {
+        self.escalations
+            .lock()
+            .expect("Failed to get the escalations lock")
+    }
+
+    pub fn token_lookups(&self) -> Vec<TokenId> {
+        self.token_lookups
+            .lock()
+            .expect("Failed to get the token lookups lock")
+            .clone()
+    }
+
+    // The fee of the batch is paid by the transfer closing it
+    fn ensure_batch_fee(&self, txs: &[SignedZkSyncTx]) -> Result<(), SubmissionError> {
+        let required_fee = match &*self.required_batch_fee.lock().unwrap() {
+            Some(required_fee) => required_fee.clone(),
+            None => return Ok(()),
+        };
+        let fee = match txs.last().map(|tx| &tx.tx) {
+            Some(ZkSyncTx::Transfer(transfer)) => transfer.fee.clone(),
+            _ => BigUint::from(0u32),
+        };
+        if fee < required_fee {
+            return Err(SubmissionError::rejected(
+                None,
+                "Transactions batch summary fee is too low",
+                false,
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn set_tokens_available(&self, available: bool) {
+        self.tokens_available.store(available, Ordering::SeqCst);
+    }
+
+    pub fn lock_active_targets(
+        &self,
+    ) -> std::sync::MutexGuard<'_, Vec<ForcedExitRequestActiveTarget>> {
+        self.active_targets
+            .lock()
+            .expect("Failed to get the active targets lock")
+    }
+
+    pub fn lock_deliveries(&self) -> std::sync::MutexGuard<'_, Vec<ForcedExitRequestDelivery>> {
+        self.deliveries
+            .lock()
+            .expect("Failed to get the deliveries lock")
+    }
+
+    fn set_status(&self, request_id: ForcedExitRequestId, status: ForcedExitLifecycleStatus) {
+        if let Some(request) = self
+            .lock_requests()
+            .iter_mut()
+            .find(|request| request.id == request_id)
+        {
+            request.status = status;
+        }
+    }
+
+    fn enqueue_delivery(&self, request_id: ForcedExitRequestId, event: ForcedExitRequestEvent) {
+        let mut deliveries = self.lock_deliveries();
+        let now = Utc::now();
+        let id = deliveries.len() as ForcedExitRequestDeliveryId + 1;
+        let sequence = deliveries
+            .iter()
+            .filter(|delivery| delivery.request_id == request_id)
+            .count() as i64
+            + 1;
+        deliveries.push(ForcedExitRequestDelivery {
+            id,
+            request_id,
+            sequence,
+            event,
+            created_at: now,
+            attempts: 0,
+            next_attempt_at: now,
+            delivered_at: None,
+            last_error: None,
+            pipeline_version: Some(FORCED_EXIT_PIPELINE_VERSION),
+            callback_url: None,
+            callback: None,
+            failed_at: None,
+        });
+    }
+
+    fn get_delivery_index_by_id(&self, id: ForcedExitRequestDeliveryId) -> anyhow::Result<usize> {
+        self.lock_deliveries()
+            .iter()
+            .position(|delivery| delivery.id == id)
+            .ok_or_else(|| anyhow::Error::msg("Delivery not found"))
+    }
+
+    pub fn lock_injected_payments(
+        &self,
+    ) -> std::sync::MutexGuard<'_, Vec<InjectedForcedExitPayment>> {
+        self.injected_payments
+            .lock()
+            .expect("Failed to get the injected payments lock")
+    }
+
+    pub fn lock_retries(&self) -> std::sync::MutexGuard<'_, Vec<ForcedExitRetry>> {
+        self.retries.lock().expect("Failed to get the retries lock")
+    }
+
+    pub fn lock_pacing_override(&self) -> std::sync::MutexGuard<'_, Option<ForcedExitPacing>> {
+        self.pacing_override
+            .lock()
+            .expect("Failed to get the pacing override lock")
+    }
+
+    pub fn lock_reported_pacing(&self) -> std::sync::MutexGuard<'_, Option<ForcedExitPacingState>> {
+        self.reported_pacing
+            .lock()
+            .expect("Failed to get the reported pacing lock")
+    }
+
+    pub fn lock_expected_payments(
+        &self,
+    ) -> std::sync::MutexGuard<'_, Vec<ExpectedForcedExitPayment>> {
+        self.expected_payments
+            .lock()
+            .expect("Failed to get the expected payments lock")
+    }
+
+    pub fn lock_processing_failures(
+        &self,
+    ) -> std::sync::MutexGuard<'_, Vec<ForcedExitProcessingFailure>> {
+        self.processing_failures
+            .lock()
+            .expect("Failed to get the processing failures lock")
+    }
+
+    pub fn lock_pipeline_versions(
+        &self,
+    ) -> std::sync::MutexGuard<'_, Vec<ForcedExitPipelineVersion>> {
+        self.pipeline_versions
+            .lock()
+            .expect("Failed to get the pipeline versions lock")
+    }
+
+    pub fn lock_refunds(&self) -> std::sync::MutexGuard<'_, Vec<ForcedExitRefund>> {
+        self.refunds.lock().expect("Failed to get the refunds lock")
+    }
+
     fn lock_deleted_requests(&self) -> std::sync::MutexGuard<'_, Vec<ForcedExitRequest>> {
         self.deleted_requests
             .lock()
@@ -71,6 +339,17 @@ impl CoreInteractionWrapper for MockCoreInteractionWrapper {
     async fn get_nonce(&self, _account_id: AccountId) -> anyhow::Result<Option<Nonce>> {
         Ok(Some(self.nonce))
     }
+
+    // The committed nonce never changes, so the sent transactions are all pending
+    async fn get_pending_nonce(&self, _account_id: AccountId) -> anyhow::Result<Option<Nonce>> {
+        let pending = self
+            .lock_sent_txs()
+            .iter()
+            .map(|tx| tx.nonce())
+            .max()
+            .map(|nonce| nonce + 1);
+        Ok(pending)
+    }
     async fn get_unconfirmed_requests(&self) -> anyhow::Result<Vec<ForcedExitRequest>> {
         let requests = self.lock_requests();
 
@@ -87,6 +366,8 @@ impl CoreInteractionWrapper for MockCoreInteractionWrapper {
         let mut requests = self.lock_requests();
 
         requests[index].fulfilled_at = Some(Utc::now());
+        requests[index].status = ForcedExitLifecycleStatus::Fulfilled;
+        self.enqueue_delivery(id, ForcedExitRequestEvent::Fulfilled);
 
         Ok(())
     }
@@ -96,12 +377,104 @@ impl CoreInteractionWrapper for MockCoreInteractionWrapper {
         value: Option<Vec<TxHash>>,
     ) -> anyhow::Result<()> {
         let index = self.get_request_index_by_id(id)?;
+        self.interrupted_submissions
+            .lock()
+            .expect("Failed to get the interrupted submissions lock")
+            .retain(|submission| submission.request_id != id);
         let mut requests = self.lock_requests();
 
+        if value.is_some() {
+            requests[index].status = ForcedExitLifecycleStatus::TxsSent;
+            self.enqueue_delivery(id, ForcedExitRequestEvent::Submitted);
+        } else {
+            if requests[index].status == ForcedExitLifecycleStatus::TxsSent {
+                requests[index].status = ForcedExitLifecycleStatus::PaymentReceived;
+            }
+            self.fulfillment_keys
+                .lock()
+                .expect("Failed to get the fulfillment keys lock")
+                .retain(|(request_id, _)| *request_id != id);
+        }
         requests[index].fulfilled_by = value;
 
         Ok(())
     }
+
+    async fn cancel_request(
+        &self,
+        id: ForcedExitRequestId,
+        kind: ForcedExitCancellationKind,
+    ) -> anyhow::Result<()> {
+        let index = self.get_request_index_by_id(id)?;
+        let cancelled_at = Utc::now();
+
+        if kind.allows_reprocessing() {
+            self.set_fulfilled_by(id, None).await?;
+        } else {
+            // Only the requests which have not been processed yet are cancelled this way
+            let mut requests = self.lock_requests();
+            let request = &mut requests[index];
+            if request.fulfilled_by.is_some() || request.fulfilled_at.is_some() {
+                return Ok(());
+            }
+            request.valid_until = request.valid_until.min(cancelled_at);
+            request.status = match kind {
+                ForcedExitCancellationKind::Expired => ForcedExitLifecycleStatus::Expired,
+                ForcedExitCancellationKind::TargetNotFound => ForcedExitLifecycleStatus::Failed,
+                _ => ForcedExitLifecycleStatus::Cancelled,
+            };
+        }
+        self.lock_requests()[index].cancellation = Some(kind);
+        self.cancellations
+            .lock()
+            .expect("Failed to get the cancellations lock")
+            .push(ForcedExitCancellation {
+                request_id: id,
+                kind,
+                cancelled_at,
+            });
+
+        Ok(())
+    }
+    async fn set_match_scheme(
+        &self,
+        id: ForcedExitRequestId,
+        match_scheme: PaymentMatchScheme,
+        paid_amount: &BigUint,
+        payment_tx_hash: Option<H256>,
+    ) -> anyhow::Result<()> {
+        let index = self.get_request_index_by_id(id)?;
+        let mut requests = self.lock_requests();
+
+        requests[index].match_scheme = Some(match_scheme);
+        requests[index].paid_amount = Some(paid_amount.to_string());
+        requests[index].matched_at.get_or_insert_with(Utc::now);
+        if !matches!(
+            requests[index].status,
+            ForcedExitLifecycleStatus::TxsSent | ForcedExitLifecycleStatus::Fulfilled
+        ) {
+            requests[index].status = ForcedExitLifecycleStatus::PaymentReceived;
+        }
+        if let Some(payment_tx_hash) = payment_tx_hash {
+            self.payment_matches
+                .lock()
+                .expect("Failed to get the payment matches lock")
+                .push((payment_tx_hash, id));
+        }
+
+        Ok(())
+    }
+    async fn begin_fulfillment(
+        &self,
+        id: ForcedExitRequestId,
+        payment_tx_hash: Option<H256>,
+    ) -> anyhow::Result<bool> {
+        Ok(self
+            .fulfillment_keys
+            .lock()
+            .expect("Failed to get the fulfillment keys lock")
+            .insert((id, payment_tx_hash)))
+    }
     async fn get_request_by_id(&self, id: i64) -> anyhow::Result<Option<ForcedExitRequest>> {
         let index = self.get_request_index_by_id(id);
 
@@ -114,8 +487,55 @@ impl CoreInteractionWrapper for MockCoreInteractionWrapper {
         }
     }
 
-    async fn get_receipt(&self, _tx_hash: TxHash) -> anyhow::Result<Option<TxReceiptResponse>> {
-        Ok(self.tx_receipt.clone())
+    async fn get_request_by_public_id(
+        &self,
+        public_id: ForcedExitRequestId,
+    ) -> anyhow::Result<Option<ForcedExitRequest>> {
+        Ok(self
+            .lock_requests()
+            .iter()
+            .find(|request| request.public_id == public_id)
+            .cloned())
+    }
+
+    async fn get_requests_by_closest_amount(
+        &self,
+        max_amount: &BigUint,
+        submission_time: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<ForcedExitRequest>> {
+        let amount = |request: &ForcedExitRequest| BigUint::from_str(&request.pay_exactly).unwrap();
+        let mut requests: Vec<_> = self
+            .lock_requests()
+            .iter()
+            .filter(|request| amount(request) <= *max_amount)
+            .filter(|request| payment_rejection(request, submission_time).is_none())
+            .cloned()
+            .collect();
+        requests.sort_by_key(|request| std::cmp::Reverse(amount(request)));
+        requests.truncate(2);
+        Ok(requests)
+    }
+
+    async fn get_receipt(&self, tx_hash: TxHash) -> anyhow::Result<Option<TxReceiptResponse>> {
+        let receipt = self.lock_receipts().get(&tx_hash).cloned();
+        Ok(receipt.or_else(|| self.tx_receipt.clone()))
+    }
+
+    async fn get_receipts(&self, tx_hashes: &[TxHash]) -> anyhow::Result<Vec<TxReceiptResponse>> {
+        self.receipt_queries
+            .lock()
+            .unwrap()
+            .push(tx_hashes.to_vec());
+        let mut receipts = Vec::new();
+        for tx_hash in tx_hashes {
+            if let Some(receipt) = self.get_receipt(*tx_hash).await? {
+                receipts.push(TxReceiptResponse {
+                    tx_hash: hex::encode(tx_hash.as_ref()),
+                    ..receipt
+                });
+            }
+        }
+        Ok(receipts)
     }
 
     async fn send_and_save_txs_batch(
@@ -123,12 +543,71 @@ impl CoreInteractionWrapper for MockCoreInteractionWrapper {
         request: &ForcedExitRequest,
         mut txs: Vec<SignedZkSyncTx>,
     ) -> anyhow::Result<Vec<TxHash>> {
+        ensure_batch_not_empty(request, &txs)?;
+        if let Some(submission_error) = self.submission_error.lock().unwrap().clone() {
+            return Err(submission_error.into());
+        }
+        self.ensure_batch_fee(&txs)?;
         let hashes: Vec<TxHash> = txs.iter().map(|tx| tx.hash()).collect();
 
         self.lock_sent_txs().append(&mut txs);
+        self.sent_batches.lock().unwrap().push(vec![request.id]);
 
         self.set_fulfilled_by(request.id, Some(hashes.clone()))
             .await?;
+        if let Some(shutdown) = self.shutdown_on_send.lock().unwrap().take() {
+            shutdown.request();
+        }
+
+        Ok(hashes)
+    }
+
+    async fn send_and_save_combined_batch(
+        &mut self,
+        parts: Vec<(ForcedExitRequestId, Vec<SignedZkSyncTx>)>,
+    ) -> anyhow::Result<Vec<Vec<TxHash>>> {
+        let ids: Vec<ForcedExitRequestId> = parts.iter().map(|(id, _)| *id).collect();
+        let (mut txs, hashes) = combine_batch(parts)?;
+        if let Some(submission_error) = self.submission_error.lock().unwrap().clone() {
+            return Err(submission_error.into());
+        }
+
+        self.lock_sent_txs().append(&mut txs);
+        self.sent_batches.lock().unwrap().push(ids.clone());
+
+        for (id, request_hashes) in ids.into_iter().zip(&hashes) {
+            self.set_fulfilled_by(id, Some(request_hashes.clone()))
+                .await?;
+        }
+        if let Some(shutdown) = self.shutdown_on_send.lock().unwrap().take() {
+            shutdown.request();
+        }
+
+        Ok(hashes)
+    }
+
+    async fn send_and_save_txs_chunk(
+        &mut self,
+        request: &ForcedExitRequest,
+        sent: &[TxHash],
+        mut txs: Vec<SignedZkSyncTx>,
+    ) -> anyhow::Result<Vec<TxHash>> {
+        ensure_batch_not_empty(request, &txs)?;
+        if let Some(submission_error) = self.submission_error.lock().unwrap().clone() {
+            return Err(submission_error.into());
+        }
+        self.ensure_batch_fee(&txs)?;
+        let hashes: Vec<TxHash> = txs.iter().map(|tx| tx.hash()).collect();
+        let fulfilled_by = append_chunk(sent, &txs);
+
+        self.lock_sent_txs().append(&mut txs);
+        self.sent_batches.lock().unwrap().push(vec![request.id]);
+
+        self.set_fulfilled_by(request.id, Some(fulfilled_by))
+            .await?;
+        if let Some(shutdown) = self.shutdown_on_send.lock().unwrap().take() {
+            shutdown.request();
+        }
 
         Ok(hashes)
     }
@@ -164,9 +643,594 @@ impl CoreInteractionWrapper for MockCoreInteractionWrapper {
     async fn check_forced_exit_request(
         &self,
         _request: &ForcedExitRequest,
-    ) -> anyhow::Result<bool> {
-        // For tests it is better to just return true all the time
-        Ok(true)
+    ) -> anyhow::Result<ForcedExitTargetCheck> {
+        Ok(self.target_check)
+    }
+
+    async fn record_failure(&self, id: ForcedExitRequestId, token: TokenId) -> anyhow::Result<u32> {
+        let mut failures = self
+            .failures
+            .lock()
+            .expect("Failed to get the failures lock");
+        let count = failures.entry((id, token)).or_default();
+        *count += 1;
+
+        Ok(*count)
+    }
+
+    async fn record_processing_failure(
+        &self,
+        failure: ForcedExitProcessingFailure,
+    ) -> anyhow::Result<()> {
+        // The same way the storage does it, only the last failure of the known request is kept
+        match self
+            .lock_requests()
+            .iter_mut()
+            .find(|request| request.id == failure.request_id)
+        {
+            Some(request) => {
+                if !matches!(
+                    request.status,
+                    ForcedExitLifecycleStatus::TxsSent | ForcedExitLifecycleStatus::Fulfilled
+                ) {
+                    request.status = ForcedExitLifecycleStatus::Failed;
+                }
+            }
+            None => return Ok(()),
+        }
+        let mut processing_failures = self.lock_processing_failures();
+        processing_failures.retain(|recorded| recorded.request_id != failure.request_id);
+        processing_failures.push(failure);
+
+        Ok(())
+    }
+
+    async fn record_pipeline_version(
+        &self,
+        version: ForcedExitPipelineVersion,
+    ) -> anyhow::Result<()> {
+        // The same way the storage does it, only the last version of the stage is kept
+        if !self
+            .lock_requests()
+            .iter()
+            .any(|request| request.id == version.request_id)
+        {
+            return Ok(());
+        }
+        let mut pipeline_versions = self.lock_pipeline_versions();
+        pipeline_versions.retain(|recorded| {
+            (recorded.request_id, recorded.stage) != (version.request_id, version.stage)
+        });
+        pipeline_versions.push(version);
+
+        Ok(())
+    }
+
+    async fn get_account_id(&self, _address: Address) -> anyhow::Result<Option<AccountId>> {
+        let account_id = self
+            .target_account_ids
+            .lock()
+            .expect("Failed to get the target account ids lock")
+            .pop_front();
+        Ok(account_id.unwrap_or(Some(TEST_TARGET_ACCOUNT_ID)))
+    }
+
+    async fn record_target_account_id(
+        &self,
+        id: ForcedExitRequestId,
+        target_account_id: AccountId,
+    ) -> anyhow::Result<()> {
+        self.recorded_target_account_ids
+            .lock()
+            .expect("Failed to get the recorded target account ids lock")
+            .insert(id, target_account_id);
+        Ok(())
+    }
+
+    async fn get_interrupted_submissions(&self) -> anyhow::Result<Vec<InterruptedSubmission>> {
+        Ok(self
+            .interrupted_submissions
+            .lock()
+            .expect("Failed to get the interrupted submissions lock")
+            .clone())
+    }
+
+    async fn store_note(&self, note: SaveForcedExitRequestNoteQuery) -> anyhow::Result<()> {
+        self.notes
+            .lock()
+            .expect("Failed to get the notes lock")
+            .push(note);
+        Ok(())
+    }
+
+    async fn get_token_address(&self, token: TokenId) -> anyhow::Result<Option<Address>> {
+        self.token_lookups
+            .lock()
+            .expect("Failed to get the token lookups lock")
+            .push(token);
+        if !self.tokens_available.load(Ordering::SeqCst) {
+            anyhow::bail!("The tokens can not be queried");
+        }
+        Ok(Some(Address::from_low_u64_be(token.0 as u64)))
+    }
+
+    async fn get_token(&self, token: TokenId) -> anyhow::Result<Option<Token>> {
+        Ok(Some(Token::new(
+            token,
+            Address::from_low_u64_be(token.0 as u64),
+            &format!("TKN{}", token.0),
+            18,
+            TokenKind::ERC20,
+        )))
+    }
+
+    async fn store_escalation(
+        &self,
+        escalation: ForcedExitRequestEscalation,
+    ) -> anyhow::Result<()> {
+        let mut escalations = self.lock_escalations();
+        if escalations
+            .iter()
+            .all(|stored| stored.request_id != escalation.request_id)
+        {
+            self.enqueue_delivery(escalation.request_id, ForcedExitRequestEvent::Escalated);
+            escalations.push(escalation);
+        }
+
+        Ok(())
+    }
+
+    async fn get_escalation(
+        &self,
+        id: ForcedExitRequestId,
+    ) -> anyhow::Result<Option<ForcedExitRequestEscalation>> {
+        let escalation = self
+            .lock_escalations()
+            .iter()
+            .find(|escalation| escalation.request_id == id)
+            .cloned();
+
+        Ok(escalation)
+    }
+
+    async fn store_active_target(
+        &self,
+        active_target: ForcedExitRequestActiveTarget,
+    ) -> anyhow::Result<()> {
+        let request_id = active_target.request_id;
+        let failed = active_target.is_failed();
+        let inserted = {
+            let mut active_targets = self.lock_active_targets();
+            let inserted = active_targets
+                .iter()
+                .all(|stored| stored.request_id != request_id);
+            if inserted {
+                active_targets.push(active_target);
+            }
+            inserted
+        };
+        // The requests are locked before the active targets elsewhere
+        if inserted && failed {
+            self.set_status(request_id, ForcedExitLifecycleStatus::Failed);
+            self.enqueue_delivery(request_id, ForcedExitRequestEvent::Failed);
+        }
+
+        Ok(())
+    }
+
+    async fn get_held_active_targets(&self) -> anyhow::Result<Vec<ForcedExitRequestActiveTarget>> {
+        let requests = self.lock_requests();
+        let escalations = self.lock_escalations();
+        let held = self
+            .lock_active_targets()
+            .iter()
+            .filter(|active_target| {
+                active_target.is_held()
+                    && requests.iter().any(|request| {
+                        request.id == active_target.request_id
+                            && request.fulfilled_at.is_none()
+                            && request.fulfilled_by.is_none()
+                    })
+                    && escalations
+                        .iter()
+                        .all(|escalation| escalation.request_id != active_target.request_id)
+            })
+            .cloned()
+            .collect();
+
+        Ok(held)
+    }
+
+    async fn resume_active_target(
+        &self,
+        id: ForcedExitRequestId,
+        resumed_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        let mut active_targets = self.lock_active_targets();
+        if let Some(active_target) = active_targets
+            .iter_mut()
+            .find(|active_target| active_target.request_id == id && active_target.is_held())
+        {
+            active_target.resumed_at = Some(resumed_at);
+        }
+
+        Ok(())
+    }
+
+    async fn fail_active_target(
+        &self,
+        id: ForcedExitRequestId,
+        failed_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        let failed = match self
+            .lock_active_targets()
+            .iter_mut()
+            .find(|active_target| active_target.request_id == id && active_target.is_held())
+        {
+            Some(active_target) => {
+                active_target.failed_at = Some(failed_at);
+                true
+            }
+            None => false,
+        };
+        if failed {
+            self.set_status(id, ForcedExitLifecycleStatus::Failed);
+            self.enqueue_delivery(id, ForcedExitRequestEvent::Failed);
+        }
+
+        Ok(())
+    }
+
+    async fn skip_tokens(
+        &self,
+        id: ForcedExitRequestId,
+        skipped: &[SkippedForcedExit],
+    ) -> anyhow::Result<()> {
+        let mut skipped_tokens = self
+            .skipped_tokens
+            .lock()
+            .expect("Failed to get the skipped tokens lock");
+        for skipped in skipped {
+            skipped_tokens
+                .retain(|(request_id, stored)| *request_id != id || stored.token != skipped.token);
+            skipped_tokens.push((id, *skipped));
+        }
+        drop(skipped_tokens);
+
+        let index = self.get_request_index_by_id(id)?;
+        self.lock_requests()[index]
+            .tokens
+            .retain(|token| skipped.iter().all(|skipped| skipped.token != *token));
+
+        Ok(())
+    }
+
+    async fn store_payment(&self, payment: &ForcedExitPayment) -> anyhow::Result<()> {
+        self.payments
+            .lock()
+            .expect("Failed to get the payments lock")
+            .push(payment.clone());
+
+        Ok(())
+    }
+
+    async fn store_unmatched_payment(
+        &self,
+        payment: &ForcedExitPayment,
+        reason: UnmatchedPaymentReason,
+    ) -> anyhow::Result<()> {
+        self.unmatched_payments
+            .lock()
+            .expect("Failed to get the unmatched payments lock")
+            .push((payment.clone(), reason));
+
+        Ok(())
+    }
+
+    async fn get_payment_source_states(&self) -> anyhow::Result<Vec<PaymentSourceState>> {
+        let states = self
+            .payment_source_states
+            .lock()
+            .expect("Failed to get the payment source states lock")
+            .clone();
+
+        Ok(states)
+    }
+
+    async fn get_expected_payment(
+        &self,
+        eth_tx_hash: H256,
+    ) -> anyhow::Result<Option<ExpectedForcedExitPayment>> {
+        let expected = self
+            .lock_expected_payments()
+            .iter()
+            .find(|expected| expected.eth_tx_hash == eth_tx_hash)
+            .cloned();
+
+        Ok(expected)
+    }
+
+    async fn flag_expected_payment(&self, eth_tx_hash: H256, paid: &BigUint) -> anyhow::Result<()> {
+        if let Some(expected) = self
+            .lock_expected_payments()
+            .iter_mut()
+            .find(|expected| expected.eth_tx_hash == eth_tx_hash)
+        {
+            expected.mismatched_amount = Some(paid.to_string());
+        }
+
+        Ok(())
+    }
+
+    async fn get_injected_payments(
+        &self,
+        limit: u32,
+    ) -> anyhow::Result<Vec<InjectedForcedExitPayment>> {
+        let payments = self
+            .lock_injected_payments()
+            .iter()
+            .take(limit as usize)
+            .cloned()
+            .collect();
+
+        Ok(payments)
+    }
+
+    async fn delete_injected_payment(&self, id: InjectedForcedExitPaymentId) -> anyhow::Result<()> {
+        self.lock_injected_payments()
+            .retain(|payment| payment.id != id);
+
+        Ok(())
+    }
+
+    async fn get_pending_retries(&self, limit: u32) -> anyhow::Result<Vec<ForcedExitRetry>> {
+        let retries = self
+            .lock_retries()
+            .iter()
+            .filter(|retry| !retry.is_processed())
+            .take(limit as usize)
+            .cloned()
+            .collect();
+
+        Ok(retries)
+    }
+
+    async fn complete_retry(
+        &self,
+        id: ForcedExitRetryId,
+        tx_hashes: &[TxHash],
+        error: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let mut retries = self.lock_retries();
+        let retry = retries
+            .iter_mut()
+            .find(|retry| retry.id == id)
+            .ok_or_else(|| anyhow::Error::msg("Retry not found"))?;
+        retry.processed_at = Some(Utc::now());
+        retry.tx_hashes = tx_hashes.to_vec();
+        retry.error = error.map(str::to_owned);
+
+        Ok(())
+    }
+
+    async fn get_pacing_override(&self) -> anyhow::Result<Option<ForcedExitPacing>> {
+        Ok(*self.lock_pacing_override())
+    }
+
+    async fn report_pacing(
+        &self,
+        _sender: Address,
+        state: &ForcedExitPacingState,
+    ) -> anyhow::Result<()> {
+        *self.lock_reported_pacing() = Some(state.clone());
+        Ok(())
+    }
+
+    async fn get_pending_deliveries(
+        &self,
+        limit: u32,
+        callbacks: bool,
+    ) -> anyhow::Result<Vec<ForcedExitRequestDelivery>> {
+        let now = Utc::now();
+        let deliveries = self
+            .lock_deliveries()
+            .iter()
+            .filter(|delivery| {
+                !delivery.is_delivered()
+                    && !delivery.is_failed()
+                    && delivery.is_callback() == callbacks
+                    && delivery.next_attempt_at <= now
+            })
+            .take(limit as usize)
+            .cloned()
+            .collect();
+
+        Ok(deliveries)
+    }
+
+    async fn mark_delivered(&self, id: ForcedExitRequestDeliveryId) -> anyhow::Result<()> {
+        let index = self.get_delivery_index_by_id(id)?;
+        let mut deliveries = self.lock_deliveries();
+
+        deliveries[index].attempts += 1;
+        deliveries[index].delivered_at = Some(Utc::now());
+        deliveries[index].last_error = None;
+
+        Ok(())
+    }
+
+    async fn record_delivery_failure(
+        &self,
+        id: ForcedExitRequestDeliveryId,
+        error: String,
+        next_attempt_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        let index = self.get_delivery_index_by_id(id)?;
+        let mut deliveries = self.lock_deliveries();
+
+        deliveries[index].attempts += 1;
+        deliveries[index].last_error = Some(error);
+        deliveries[index].next_attempt_at = next_attempt_at;
+
+        Ok(())
+    }
+
+    async fn mark_delivery_failed(
+        &self,
+        id: ForcedExitRequestDeliveryId,
+        error: String,
+    ) -> anyhow::Result<()> {
+        let index = self.get_delivery_index_by_id(id)?;
+        let mut deliveries = self.lock_deliveries();
+
+        deliveries[index].attempts += 1;
+        deliveries[index].last_error = Some(error);
+        deliveries[index].failed_at = Some(Utc::now());
+
+        Ok(())
+    }
+
+    async fn store_refund(
+        &self,
+        refund: SaveForcedExitRefundQuery,
+    ) -> anyhow::Result<Option<ForcedExitRefund>> {
+        let mut refunds = self.lock_refunds();
+        if refunds
+            .iter()
+            .any(|stored| stored.payment_tx_hash == refund.payment_tx_hash)
+        {
+            return Ok(None);
+        }
+
+        let refund = ForcedExitRefund {
+            id: refunds.len() as ForcedExitRefundId + 1,
+            request_id: refund.request_id,
+            payment_tx_hash: refund.payment_tx_hash,
+            recipient: refund.recipient,
+            amount: refund.amount,
+            fee: refund.fee,
+            reason: refund.reason,
+            status: if refund.requires_approval {
+                ForcedExitRefundStatus::AwaitingApproval
+            } else {
+                ForcedExitRefundStatus::Pending
+            },
+            tx_hash: None,
+            attempts: 0,
+            approved_by: None,
+            approved_at: None,
+            sent_at: None,
+            created_at: refund.created_at,
+            updated_at: refund.created_at,
+        };
+        refunds.push(refund.clone());
+
+        Ok(Some(refund))
+    }
+
+    async fn get_unsettled_refunds(
+        &self,
+        max_attempts: u32,
+    ) -> anyhow::Result<Vec<ForcedExitRefund>> {
+        let mut refunds: Vec<_> =
+            self.lock_refunds()
+                .iter()
+                .filter(|refund| match refund.status {
+                    ForcedExitRefundStatus::Pending | ForcedExitRefundStatus::Sent => true,
+                    ForcedExitRefundStatus::Failed => refund.attempts < max_attempts,
+                    ForcedExitRefundStatus::AwaitingApproval
+                    | ForcedExitRefundStatus::Completed => false,
+                })
+                .cloned()
+                .collect();
+        // The approved ones go first
+        refunds.sort_by_key(|refund| refund.approved_at.is_none());
+
+        Ok(refunds)
+    }
+
+    async fn get_refunded_amount_since(&self, since: DateTime<Utc>) -> anyhow::Result<BigUint> {
+        Ok(self
+            .lock_refunds()
+            .iter()
+            .filter(|refund| refund.status != ForcedExitRefundStatus::Failed)
+            .filter(|refund| matches!(refund.sent_at, Some(sent_at) if sent_at >= since))
+            .map(|refund| refund.amount.clone())
+            .sum())
+    }
+
+    async fn get_balance(
+        &self,
+        _account_id: AccountId,
+        _token: TokenId,
+    ) -> anyhow::Result<BigUint> {
+        Ok(self
+            .sender_balance
+            .lock()
+            .expect("Failed to get the sender balance lock")
+            .clone())
+    }
+
+    async fn get_forced_exit_batch_fee(
+        &self,
+        _target: Address,
+        forced_exits: usize,
+        _fee_payer: Address,
+        token: TokenId,
+    ) -> anyhow::Result<BigUint> {
+        let tx_fees = self.tx_fees.lock().expect("Failed to get the fees lock");
+        let tx_fee = tx_fees
+            .get(&token)
+            .ok_or_else(|| anyhow::anyhow!("The fee in token {} is not quoted", token))?;
+        Ok(tx_fee * (forced_exits + 1))
+    }
+
+    async fn send_refund(
+        &mut self,
+        id: ForcedExitRefundId,
+        tx: SignedZkSyncTx,
+    ) -> anyhow::Result<TxHash> {
+        let tx_hash = tx.hash();
+        if let ZkSyncTx::Transfer(transfer) = &tx.tx {
+            let mut balance = self
+                .sender_balance
+                .lock()
+                .expect("Failed to get the sender balance lock");
+            if *balance < transfer.amount {
+                anyhow::bail!("Not enough balance");
+            }
+            *balance -= &transfer.amount;
+        }
+        self.lock_sent_txs().push(tx);
+        self.set_refund_status(id, ForcedExitRefundStatus::Sent, Some(tx_hash))
+            .await?;
+
+        Ok(tx_hash)
+    }
+
+    async fn set_refund_status(
+        &self,
+        id: ForcedExitRefundId,
+        status: ForcedExitRefundStatus,
+        tx_hash: Option<TxHash>,
+    ) -> anyhow::Result<()> {
+        let mut refunds = self.lock_refunds();
+        let refund = refunds
+            .iter_mut()
+            .find(|refund| refund.id == id)
+            .ok_or_else(|| anyhow::Error::msg("Refund not found"))?;
+
+        if status == ForcedExitRefundStatus::Failed {
+            refund.attempts += 1;
+        }
+        refund.status = status;
+        refund.tx_hash = tx_hash.or(refund.tx_hash);
+        refund.updated_at = Utc::now();
+        if status == ForcedExitRefundStatus::Sent {
+            refund.sent_at = Some(refund.updated_at);
+        }
+
+        Ok(())
     }
 }
 
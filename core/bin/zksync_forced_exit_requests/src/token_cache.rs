@@ -0,0 +1,200 @@
+//! Token metadata shared by the requests processed in one cycle.
+//!
+//! The requests of the backlog mostly share the same few tokens, so the metadata is
+//! loaded once per token instead of once per request. The cache is created at the
+//! start of a cycle and dropped at its end, nothing is carried over to the next one.
+//!
+//! A token changed in the middle of the cycle (e.g. disabled) may thus still be used
+//! with the old metadata until the cycle is over. This is acceptable: the change is
+//! picked up by the next cycle, and the transactions built for such a token are
+//! rejected by the server the same way as if they were sent just before the change.
+//!
+//! The only thing outliving the cycles are the last known addresses, which stand in
+//! for the tokens while they can not be queried at all, as long as they are recent enough.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use zksync_types::{Address, TokenId};
+
+use crate::core_interaction_wrapper::CoreInteractionWrapper;
+
+/// The data the processing depends on is briefly unavailable, e.g. the database is
+/// restarted. Unlike the other errors, it says nothing about the request itself,
+/// so the request is deferred until the data is available again rather than failed.
+#[derive(Debug, thiserror::Error)]
+#[error("The {dependency} are unavailable: {reason}")]
+pub struct DependencyUnavailable {
+    pub dependency: &'static str,
+    reason: String,
+}
+
+impl DependencyUnavailable {
+    pub fn new(dependency: &'static str, reason: impl ToString) -> Self {
+        Self {
+            dependency,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// The token addresses loaded by the previous cycles along with the time they were loaded at.
+#[derive(Debug, Clone, Default)]
+pub struct LastKnownTokens(Arc<Mutex<HashMap<TokenId, (Option<Address>, Instant)>>>);
+
+impl LastKnownTokens {
+    fn record(&self, token: TokenId, address: Option<Address>) {
+        self.0
+            .lock()
+            .expect("Failed to get the last known tokens lock")
+            .insert(token, (address, Instant::now()));
+    }
+
+    fn loaded_within(&self, token: TokenId, max_staleness: Duration) -> Option<Option<Address>> {
+        self.0
+            .lock()
+            .expect("Failed to get the last known tokens lock")
+            .get(&token)
+            .filter(|(_, loaded_at)| loaded_at.elapsed() <= max_staleness)
+            .map(|(address, _)| *address)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct TokenCache {
+    addresses: HashMap<TokenId, Option<Address>>,
+    last_known: LastKnownTokens,
+    /// Zero if the last known addresses are never used.
+    max_staleness: Duration,
+}
+
+impl TokenCache {
+    /// The cache falling back to the last known addresses loaded within `max_staleness`.
+    pub fn with_last_known(last_known: LastKnownTokens, max_staleness: Duration) -> Self {
+        Self {
+            addresses: HashMap::new(),
+            last_known,
+            max_staleness,
+        }
+    }
+
+    /// Returns the address of the token, it is only queried the first time in the cycle.
+    /// The errors are not cached, the failed query is repeated on the next call.
+    ///
+    /// If the query fails, the last known address is used. Without the recent enough one
+    /// the error is `DependencyUnavailable`.
+    pub async fn token_address<T: CoreInteractionWrapper>(
+        &mut self,
+        core_interaction_wrapper: &T,
+        token: TokenId,
+    ) -> anyhow::Result<Option<Address>> {
+        if let Some(address) = self.addresses.get(&token) {
+            return Ok(*address);
+        }
+        let address = match core_interaction_wrapper.get_token_address(token).await {
+            Ok(address) => {
+                self.last_known.record(token, address);
+                address
+            }
+            Err(err) => match self.last_known.loaded_within(token, self.max_staleness) {
+                Some(address) => {
+                    vlog::warn!(
+                        "Failed to load the token {}, the last known address is used: {}",
+                        token,
+                        err
+                    );
+                    metrics::increment_counter!(
+                        "forced_exit_requests.degraded_decisions",
+                        "dependency" => "tokens",
+                        "decision" => "cached"
+                    );
+                    address
+                }
+                None => {
+                    return Err(DependencyUnavailable {
+                        dependency: "tokens",
+                        reason: err.to_string(),
+                    }
+                    .into())
+                }
+            },
+        };
+        self.addresses.insert(token, address);
+        Ok(address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::MockCoreInteractionWrapper;
+
+    #[tokio::test]
+    async fn token_is_queried_once_per_cycle() {
+        let core_interaction_wrapper = MockCoreInteractionWrapper::default();
+
+        let mut tokens = TokenCache::default();
+        for _ in 0..3 {
+            for token in [TokenId(1), TokenId(2)] {
+                let address = tokens
+                    .token_address(&core_interaction_wrapper, token)
+                    .await
+                    .unwrap();
+                assert_eq!(address, Some(Address::from_low_u64_be(token.0 as u64)));
+            }
+        }
+        assert_eq!(
+            core_interaction_wrapper.token_lookups(),
+            vec![TokenId(1), TokenId(2)]
+        );
+
+        // The next cycle queries the tokens again
+        let mut tokens = TokenCache::default();
+        tokens
+            .token_address(&core_interaction_wrapper, TokenId(1))
+            .await
+            .unwrap();
+        assert_eq!(
+            core_interaction_wrapper.token_lookups(),
+            vec![TokenId(1), TokenId(2), TokenId(1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn last_known_tokens_are_used_while_unavailable() {
+        let core_interaction_wrapper = MockCoreInteractionWrapper::default();
+        let last_known = LastKnownTokens::default();
+
+        let mut tokens = TokenCache::with_last_known(last_known.clone(), Duration::from_secs(60));
+        tokens
+            .token_address(&core_interaction_wrapper, TokenId(1))
+            .await
+            .unwrap();
+
+        core_interaction_wrapper.set_tokens_available(false);
+        // The next cycle falls back to the token loaded by the previous one
+        let mut tokens = TokenCache::with_last_known(last_known.clone(), Duration::from_secs(60));
+        let address = tokens
+            .token_address(&core_interaction_wrapper, TokenId(1))
+            .await
+            .unwrap();
+        assert_eq!(address, Some(Address::from_low_u64_be(1)));
+        // The token never loaded is unavailable
+        let err = tokens
+            .token_address(&core_interaction_wrapper, TokenId(2))
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<DependencyUnavailable>().is_some());
+
+        // The address loaded too long ago is unavailable as well
+        let mut tokens = TokenCache::with_last_known(last_known, Duration::from_secs(0));
+        let err = tokens
+            .token_address(&core_interaction_wrapper, TokenId(1))
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<DependencyUnavailable>().is_some());
+    }
+}
@@ -0,0 +1,94 @@
+//! The metrics of the fulfillments are labelled by the token, so the failures of a single
+//! token can be alerted on. Every label value is a separate time series though, and any
+//! token can be requested, so only the tokens requested recently get labels of their own.
+//!
+//! The labels are kept for the tokens seen within the last day, up to the configured number
+//! of them. The tokens seen while all the labels are taken share the `other` label, the label
+//! of the token not seen for a day is given to the next new token.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use zksync_types::TokenId;
+
+/// The label of the tokens, which have not got a label of their own.
+pub const OTHER_TOKENS_LABEL: &str = "other";
+// How long the token keeps its label since it was last seen
+const TOKEN_LABEL_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug)]
+pub struct TokenLabels {
+    max_labels: usize,
+    // The tokens having the labels along with the time they were last seen at
+    active: Mutex<HashMap<TokenId, Instant>>,
+}
+
+impl TokenLabels {
+    pub fn new(max_labels: usize) -> Self {
+        Self {
+            max_labels,
+            active: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the label the metrics of the token are reported under.
+    pub fn label(&self, token: TokenId) -> String {
+        self.label_at(token, Instant::now())
+    }
+
+    fn label_at(&self, token: TokenId, now: Instant) -> String {
+        let mut active = self
+            .active
+            .lock()
+            .expect("Failed to get the token labels lock");
+
+        if !active.contains_key(&token) && active.len() >= self.max_labels {
+            // The least recently seen tokens are the first to expire
+            active.retain(|_, seen_at| now.saturating_duration_since(*seen_at) < TOKEN_LABEL_TTL);
+            if active.len() >= self.max_labels {
+                return OTHER_TOKENS_LABEL.to_owned();
+            }
+        }
+        active.insert(token, now);
+        token.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overflowing_tokens_share_the_label() {
+        let labels = TokenLabels::new(3);
+        let start = Instant::now();
+
+        let assigned: Vec<_> = (1..=5)
+            .map(|token| labels.label_at(TokenId(token), start))
+            .collect();
+        assert_eq!(assigned, vec!["1", "2", "3", "other", "other"]);
+        // The tokens having the labels keep them
+        assert_eq!(labels.label_at(TokenId(2), start), "2");
+    }
+
+    #[test]
+    fn labels_of_inactive_tokens_are_reused() {
+        let labels = TokenLabels::new(2);
+        let start = Instant::now();
+
+        labels.label_at(TokenId(1), start);
+        labels.label_at(TokenId(2), start);
+        // Only the second token is seen again within the day
+        let later = start + TOKEN_LABEL_TTL / 2;
+        assert_eq!(labels.label_at(TokenId(2), later), "2");
+
+        let next_day = start + TOKEN_LABEL_TTL;
+        assert_eq!(labels.label_at(TokenId(3), next_day), "3");
+        assert_eq!(labels.label_at(TokenId(4), next_day), "other");
+        assert_eq!(labels.label_at(TokenId(1), next_day), "other");
+        assert_eq!(labels.label_at(TokenId(2), next_day), "2");
+    }
+}
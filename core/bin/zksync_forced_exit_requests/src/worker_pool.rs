@@ -0,0 +1,412 @@
+//! The payments processed by several senders at once.
+//!
+//! Fulfilling a request mostly means waiting for its transactions to be committed, so
+//! the payments are queued and taken by the workers, each with a sender of its own. The
+//! senders share the sender accounts (see `with_sender_accounts`), each of which sends
+//! one batch at a time, which keeps the nonces of every account following each other.
+
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use tokio::{
+    sync::{mpsc, Mutex, MutexGuard, OwnedSemaphorePermit, Semaphore},
+    task::JoinHandle,
+    time::{self, Instant},
+};
+
+use zksync_types::{
+    forced_exit_requests::{ForcedExitRetry, FundsReceivedEvent},
+    tx::TxHash,
+};
+
+use crate::forced_exit_sender::{ForcedExitSender, ShuttingDown};
+
+// How many payments may wait for each of the workers before the watcher waits for them
+const QUEUED_PAYMENTS_PER_WORKER: usize = 4;
+
+// The permit is released once the payment is processed
+type QueuedPayment = (FundsReceivedEvent, DateTime<Utc>, OwnedSemaphorePermit);
+
+/// How the payments are collected to be processed together, see `ForcedExitSender::process_requests`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaymentAggregation {
+    /// How long the payments are collected after the first one.
+    pub window: Duration,
+    /// The maximum number of the payments processed together.
+    pub max_payments: usize,
+}
+
+impl PaymentAggregation {
+    /// Every payment is processed on its own.
+    pub const NONE: Self = Self {
+        window: Duration::from_secs(0),
+        max_payments: 1,
+    };
+}
+
+/// Processes the payments with the given senders concurrently.
+///
+/// The rest of the sender operations, such as the reconciliation or the refunds, load
+/// the requests shared by all the senders, so they wait for the queued payments to be
+/// processed and keep the workers idle until they are done.
+pub struct ForcedExitWorkerPool<S> {
+    queue: mpsc::UnboundedSender<QueuedPayment>,
+    /// A permit is held for each of the payments until it is processed.
+    permits: Arc<Semaphore>,
+    capacity: usize,
+    senders: Vec<Arc<Mutex<S>>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<S> ForcedExitWorkerPool<S>
+where
+    S: ForcedExitSender + Send + 'static,
+{
+    /// Spawns a worker for each of the senders.
+    pub fn new(senders: Vec<S>) -> Self {
+        Self::aggregating(senders, PaymentAggregation::NONE)
+    }
+
+    /// Spawns a worker for each of the senders, the payments received within the window
+    /// are processed together by one of them.
+    pub fn aggregating(senders: Vec<S>, aggregation: PaymentAggregation) -> Self {
+        assert!(
+            !senders.is_empty(),
+            "At least one forced exit sender is required"
+        );
+        let capacity = senders.len() * QUEUED_PAYMENTS_PER_WORKER;
+        let (queue, receiver) = mpsc::unbounded_channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let senders: Vec<_> = senders
+            .into_iter()
+            .map(|sender| Arc::new(Mutex::new(sender)))
+            .collect();
+        let workers = senders
+            .iter()
+            .map(|sender| tokio::spawn(run_worker(sender.clone(), receiver.clone(), aggregation)))
+            .collect();
+
+        Self {
+            queue,
+            permits: Arc::new(Semaphore::new(capacity)),
+            capacity,
+            senders,
+            workers,
+        }
+    }
+
+    /// Waits for all the queued payments to be processed. The workers stay idle
+    /// until the returned guards are dropped.
+    async fn idle_senders(&self) -> anyhow::Result<Vec<MutexGuard<'_, S>>> {
+        let _queue_drained = self.permits.acquire_many(self.capacity as u32).await?;
+
+        let mut senders = Vec::with_capacity(self.senders.len());
+        for sender in &self.senders {
+            senders.push(sender.lock().await);
+        }
+        Ok(senders)
+    }
+}
+
+impl<S> Drop for ForcedExitWorkerPool<S> {
+    fn drop(&mut self) {
+        for worker in &self.workers {
+            worker.abort();
+        }
+    }
+}
+
+async fn run_worker<S: ForcedExitSender>(
+    sender: Arc<Mutex<S>>,
+    receiver: Arc<Mutex<mpsc::UnboundedReceiver<QueuedPayment>>>,
+    aggregation: PaymentAggregation,
+) {
+    loop {
+        // The receiver is only locked while waiting, not while the payments are processed
+        let queued = match receive_aggregated(&receiver, aggregation).await {
+            Some(queued) => queued,
+            None => return,
+        };
+        // The permits are released once all the payments are processed
+        let (payments, _permits): (Vec<_>, Vec<_>) = queued
+            .into_iter()
+            .map(|(payment, submission_time, permit)| ((payment, submission_time), permit))
+            .unzip();
+
+        let mut sender = sender.lock().await;
+        // The failure has been recorded for the request by the sender, it is up to the operators now
+        for result in sender.process_requests(payments).await {
+            match result {
+                Err(err) if err.is::<ShuttingDown>() => vlog::info!("{}", err),
+                Err(err) => {
+                    vlog::error!("Failed to process the forced exit payment: {:#}", err);
+                    metrics::increment_counter!("forced_exit_requests.failed_payments");
+                }
+                Ok(()) => {}
+            }
+        }
+    }
+}
+
+/// Waits for the next payment and takes the ones queued within the aggregation window after it,
+/// `None` once the queue is closed.
+async fn receive_aggregated(
+    receiver: &Mutex<mpsc::UnboundedReceiver<QueuedPayment>>,
+    aggregation: PaymentAggregation,
+) -> Option<Vec<QueuedPayment>> {
+    let mut receiver = receiver.lock().await;
+    let mut queued = vec![receiver.recv().await?];
+    let deadline = Instant::now() + aggregation.window;
+    while queued.len() < aggregation.max_payments {
+        match time::timeout_at(deadline, receiver.recv()).await {
+            Ok(Some(payment)) => queued.push(payment),
+            // The payments taken so far are processed before the worker stops
+            Ok(None) | Err(_) => break,
+        }
+    }
+    Some(queued)
+}
+
+#[async_trait::async_trait]
+impl<S> ForcedExitSender for ForcedExitWorkerPool<S>
+where
+    S: ForcedExitSender + Send + 'static,
+{
+    /// Queues the payment, the failures of the processing are reported by the worker.
+    async fn process_request(
+        &mut self,
+        payment: FundsReceivedEvent,
+        submission_time: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        let permit = self.permits.clone().acquire_owned().await?;
+        self.queue
+            .send((payment, submission_time, permit))
+            .map_err(|_| anyhow::anyhow!("The forced exit workers have stopped"))
+    }
+
+    /// Queues the payments, the workers collect them again within the aggregation window.
+    async fn process_requests(
+        &mut self,
+        payments: Vec<(FundsReceivedEvent, DateTime<Utc>)>,
+    ) -> Vec<anyhow::Result<()>> {
+        let mut results = Vec::with_capacity(payments.len());
+        for (payment, submission_time) in payments {
+            results.push(self.process_request(payment, submission_time).await);
+        }
+        results
+    }
+
+    async fn reconcile_unconfirmed(&mut self, timeout: Duration) -> anyhow::Result<usize> {
+        let mut senders = self.idle_senders().await?;
+        // The first reconciliation settles the requests of all the senders,
+        // the rest only reset the flags of their senders without waiting
+        let mut in_flight = 0;
+        for (i, sender) in senders.iter_mut().enumerate() {
+            let timeout = if i == 0 {
+                timeout
+            } else {
+                Duration::from_secs(0)
+            };
+            in_flight = sender.reconcile_unconfirmed(timeout).await?;
+        }
+        Ok(in_flight)
+    }
+
+    fn has_left_in_flight(&self) -> bool {
+        // The busy senders are checked on the next poll
+        self.senders.iter().any(|sender| {
+            sender
+                .try_lock()
+                .map_or(false, |sender| sender.has_left_in_flight())
+        })
+    }
+
+    async fn process_held_requests(&mut self, now: DateTime<Utc>) -> anyhow::Result<()> {
+        let mut senders = self.idle_senders().await?;
+        senders[0].process_held_requests(now).await
+    }
+
+    async fn process_deferred_requests(&mut self) {
+        match self.idle_senders().await {
+            Ok(mut senders) => {
+                for sender in senders.iter_mut() {
+                    sender.process_deferred_requests().await;
+                }
+            }
+            Err(err) => vlog::warn!("Failed to wait for the forced exit workers: {}", err),
+        }
+    }
+
+    async fn process_refunds(&mut self) -> anyhow::Result<()> {
+        let mut senders = self.idle_senders().await?;
+        senders[0].process_refunds().await
+    }
+
+    async fn retry_request(
+        &mut self,
+        retry: &ForcedExitRetry,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<TxHash>> {
+        let mut senders = self.idle_senders().await?;
+        senders[0].retry_request(retry, now).await
+    }
+
+    /// Waits for the queued payments, which are not processed once the shutdown is requested,
+    /// and for the ones being processed.
+    async fn drain(&mut self) {
+        match self.idle_senders().await {
+            Ok(mut senders) => {
+                for sender in senders.iter_mut() {
+                    sender.drain().await;
+                }
+            }
+            Err(err) => vlog::warn!("Failed to wait for the forced exit workers: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Instant,
+    };
+
+    use num::BigUint;
+
+    use super::*;
+
+    const COMMIT_TIME: Duration = Duration::from_millis(100);
+
+    // Every request takes a single commit round, the way the mocked core commits the batches
+    #[derive(Default)]
+    struct CommitRounds {
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+        processed: AtomicUsize,
+        // The number of the payments processed together, per call
+        batch_sizes: std::sync::Mutex<Vec<usize>>,
+    }
+
+    struct CommittingSender(Arc<CommitRounds>);
+
+    #[async_trait::async_trait]
+    impl ForcedExitSender for CommittingSender {
+        async fn process_request(
+            &mut self,
+            _payment: FundsReceivedEvent,
+            _submission_time: DateTime<Utc>,
+        ) -> anyhow::Result<()> {
+            let in_flight = self.0.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.0.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            tokio::time::sleep(COMMIT_TIME).await;
+            self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+            self.0.processed.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn process_requests(
+            &mut self,
+            payments: Vec<(FundsReceivedEvent, DateTime<Utc>)>,
+        ) -> Vec<anyhow::Result<()>> {
+            self.0.batch_sizes.lock().unwrap().push(payments.len());
+            let mut results = Vec::with_capacity(payments.len());
+            for (payment, submission_time) in payments {
+                results.push(self.process_request(payment, submission_time).await);
+            }
+            results
+        }
+
+        async fn reconcile_unconfirmed(&mut self, _timeout: Duration) -> anyhow::Result<usize> {
+            Ok(0)
+        }
+
+        fn has_left_in_flight(&self) -> bool {
+            false
+        }
+
+        async fn process_held_requests(&mut self, _now: DateTime<Utc>) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn process_deferred_requests(&mut self) {}
+
+        async fn process_refunds(&mut self) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn retry_request(
+            &mut self,
+            _retry: &ForcedExitRetry,
+            _now: DateTime<Utc>,
+        ) -> anyhow::Result<Vec<TxHash>> {
+            Ok(vec![])
+        }
+
+        async fn drain(&mut self) {}
+    }
+
+    fn payment(request_id: i64) -> FundsReceivedEvent {
+        FundsReceivedEvent {
+            amount: BigUint::from(10000000000u64),
+            request_id: Some(request_id),
+            block_number: 0,
+            eth_tx_hash: None,
+            payer: None,
+            recipient: None,
+        }
+    }
+
+    // Checks that the payments are processed concurrently by all the workers
+    #[tokio::test]
+    async fn payments_are_processed_concurrently() {
+        const WORKERS: usize = 4;
+        const PAYMENTS: usize = 20;
+
+        let rounds = Arc::new(CommitRounds::default());
+        let senders = (0..WORKERS)
+            .map(|_| CommittingSender(rounds.clone()))
+            .collect();
+        let mut pool = ForcedExitWorkerPool::new(senders);
+
+        let started_at = Instant::now();
+        for request_id in 0..PAYMENTS {
+            pool.process_request(payment(request_id as i64), Utc::now())
+                .await
+                .unwrap();
+        }
+        // The rest of the operations wait for the queued payments
+        pool.process_refunds().await.unwrap();
+        let elapsed = started_at.elapsed();
+
+        assert_eq!(rounds.processed.load(Ordering::SeqCst), PAYMENTS);
+        assert_eq!(rounds.max_in_flight.load(Ordering::SeqCst), WORKERS);
+        // It would take 20 rounds to process the payments one by one
+        let expected_rounds = ((PAYMENTS + WORKERS - 1) / WORKERS) as u32;
+        assert!(elapsed >= COMMIT_TIME * expected_rounds);
+        assert!(elapsed < COMMIT_TIME * expected_rounds * 2);
+    }
+
+    // Checks that the payments queued within the window are taken by the same worker
+    #[tokio::test]
+    async fn payments_within_window_are_processed_together() {
+        let rounds = Arc::new(CommitRounds::default());
+        let aggregation = PaymentAggregation {
+            window: COMMIT_TIME,
+            max_payments: 3,
+        };
+        let mut pool =
+            ForcedExitWorkerPool::aggregating(vec![CommittingSender(rounds.clone())], aggregation);
+
+        for request_id in 0..5 {
+            pool.process_request(payment(request_id), Utc::now())
+                .await
+                .unwrap();
+        }
+        pool.process_refunds().await.unwrap();
+
+        assert_eq!(rounds.processed.load(Ordering::SeqCst), 5);
+        assert_eq!(*rounds.batch_sizes.lock().unwrap(), vec![3, 2]);
+    }
+}
@@ -109,6 +109,18 @@ impl ClientRequestBuilder {
         }
     }
 
+    /// Add a header to the request.
+    ///
+    /// See [reqwest] documentation for details
+    ///
+    /// [reqwest]: https://docs.rs/reqwest/latest/reqwest/struct.RequestBuilder.html#method.header
+    pub fn header(self, name: &str, value: &str) -> Self {
+        Self {
+            inner: self.inner.header(name, value),
+            url: self.url,
+        }
+    }
+
     /// Constructs the Request and sends it to the target URL, returning a future Response.
     ///
     /// This method takes account of the responses structure and the error handling specific.
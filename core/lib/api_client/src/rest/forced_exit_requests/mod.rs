@@ -3,10 +3,25 @@
 // Built-in uses
 
 // External uses
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 // Workspace uses
-use zksync_types::{forced_exit_requests::ForcedExitRequest, Address, TokenId};
+use zksync_api_types::v02::{
+    pagination::{ApiEither, PaginationQuery},
+    Response,
+};
+use zksync_types::{
+    forced_exit_requests::{
+        ActiveTargetPolicy, CreationLimits, ExpectedForcedExitPayment, ForcedExitCancellation,
+        ForcedExitFeature, ForcedExitLifecycleStatus, ForcedExitMaintenance, ForcedExitPacing,
+        ForcedExitProcessingFailure, ForcedExitRequest, ForcedExitRequestActiveTarget,
+        ForcedExitRequestId, ForcedExitRequestNote, ForcedExitRequestsApiKey, ForcedExitTxStatus,
+        PaymentAddressWindow, SkippedForcedExit, SubmissionError, UnmatchedPaymentReason,
+    },
+    tx::TxHash,
+    Address, TokenId, H256,
+};
 use zksync_utils::BigUintSerdeAsRadix10Str;
 
 use num::BigUint;
@@ -14,6 +29,8 @@ use num::BigUint;
 // Local uses
 use crate::rest::client::{Client, Result as ClientResult};
 
+pub mod remote;
+
 // Data transfer objects.
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -22,8 +39,35 @@ pub struct ConfigInfo {
     pub request_fee: BigUint,
     pub max_tokens_per_request: u8,
     pub recomended_tx_interval_millis: i64,
+    /// The newest of the payment addresses, the payments are expected to be sent there.
     pub forced_exit_contract_address: Address,
     pub wait_confirmations: u64,
+    pub id_space: IdSpaceUsage,
+    /// The schedule of the rotation of the payment address, including the retired ones.
+    pub payment_addresses: Vec<PaymentAddressWindow>,
+    /// What happens to the paid request if its target sets the signing key before
+    /// the request is fulfilled.
+    pub active_target_policy: ActiveTargetPolicy,
+    /// The scheduled maintenance in progress, the requests are not fulfilled until it is over.
+    #[serde(default)]
+    pub maintenance: Option<ForcedExitMaintenance>,
+    /// The optional parts of the component enabled by the operators.
+    #[serde(default)]
+    pub enabled_features: Vec<ForcedExitFeature>,
+    /// The limits the new requests are checked against, not reported by the servers preceding them.
+    #[serde(default)]
+    pub creation_limits: Option<CreationLimits>,
+}
+
+/// The number of the requests awaiting the payment compared to the number of the ids
+/// which can be encoded in the paid amounts.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct IdSpaceUsage {
+    pub active_requests: u32,
+    pub id_space_size: u64,
+    /// Whether the active requests occupy enough of the space for the ids to collide.
+    pub alert: bool,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
@@ -41,9 +85,241 @@ pub struct ForcedExitRegisterRequest {
     // since the price might change (with config)
     #[serde(with = "BigUintSerdeAsRadix10Str")]
     pub price_in_wei: BigUint,
+    /// The text attached to the request, e.g. the reference in the system of the partner.
+    #[serde(default)]
+    pub metadata: Option<String>,
+    /// The HTTPS URL the request is posted to once fulfilled, if the callbacks are enabled.
+    #[serde(default)]
+    pub callback_url: Option<String>,
+}
+
+/// Price of the request to withdraw the given number of tokens.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitRequestQuote {
+    pub tokens_count: usize,
+    #[serde(with = "BigUintSerdeAsRadix10Str")]
+    pub price_in_wei: BigUint,
+    pub forced_exit_contract_address: Address,
+    /// The chain the payment is sent on, not known for the test networks. The exact
+    /// amount to pay is only known once the request is created, see `ForcedExitCreatedRequest`.
+    #[serde(default)]
+    pub chain_id: Option<u64>,
+}
+
+/// What the wallets need to render the payment screen of the request. The values are
+/// the ones the payment is matched by, so paying by them is always accepted.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitPaymentInstructions {
+    /// EIP-681 URI of the payment, to be shown as a QR code.
+    pub uri: String,
+    pub address: Address,
+    /// The exact amount to pay in wei, see `ForcedExitRequest::pay_exactly`.
+    pub amount: String,
+    /// The chain the payment is sent on, not known for the test networks.
+    pub chain_id: Option<u64>,
+    /// The request can not be paid for after this time.
+    pub expires_at: DateTime<Utc>,
+}
+
+/// The created request along with the instructions to pay for it.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitCreatedRequest {
+    #[serde(flatten)]
+    pub request: ForcedExitRequest,
+    pub payment: ForcedExitPaymentInstructions,
+}
+
+/// Place of the paid request among the ones awaiting the `ForcedExit` transactions.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitRequestQueueInfo {
+    /// The number of the requests to be processed before this one.
+    pub position: u32,
+    /// Expected time until the request is fulfilled based on the recent throughput,
+    /// not known if no requests have been fulfilled lately.
+    pub eta_secs: Option<u64>,
+}
+
+/// The request along with its place in the queue, if it is waiting to be processed.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitRequestDetails {
+    #[serde(flatten)]
+    pub request: ForcedExitRequest,
+    pub queue: Option<ForcedExitRequestQueueInfo>,
+    /// Set if the target has become active after the request was paid for,
+    /// tells whether the request is held or has failed.
+    #[serde(default)]
+    pub active_target: Option<ForcedExitRequestActiveTarget>,
+    /// The tokens of the request, which are not withdrawn, e.g. since the withdrawal
+    /// would be stuck on L1.
+    #[serde(default)]
+    pub skipped_tokens: Vec<SkippedForcedExit>,
+    /// Every cancellation of the request, the last one is the `cancellation` of the request.
+    #[serde(default)]
+    pub cancellations: Vec<ForcedExitCancellation>,
+    /// The L1 transactions registered by the partner to pay for the request.
+    #[serde(default)]
+    pub expected_payments: Vec<ExpectedForcedExitPayment>,
+    /// Set while the request awaits the end of the scheduled maintenance.
+    #[serde(default)]
+    pub maintenance: Option<ForcedExitMaintenance>,
+    /// The last payment for the request, which could not be processed in any of the attempts.
+    #[serde(default)]
+    pub processing_failure: Option<ForcedExitProcessingFailure>,
+}
+
+/// The transactions sent for the request, only the public parts of the request are reported.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitRequestProgress {
+    /// The public id of the request.
+    pub id: ForcedExitRequestId,
+    pub target: Address,
+    pub tokens: Vec<TokenId>,
+    pub status: ForcedExitLifecycleStatus,
+    pub fulfilled_at: Option<DateTime<Utc>>,
+    /// The transaction of every withdrawn token, empty until the transactions are sent.
+    pub transactions: Vec<ForcedExitTokenProgress>,
+}
+
+/// The `ForcedExit` transaction withdrawing the token of the request.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitTokenProgress {
+    pub token: TokenId,
+    pub tx_hash: TxHash,
+    pub status: ForcedExitTxStatus,
+    /// The block the transaction is included in, once it is executed.
+    pub block_number: Option<i64>,
+}
+
+/// The request as seen by the operators, along with the notes they have left on it.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitRequestAdminDetails {
+    #[serde(flatten)]
+    pub details: ForcedExitRequestDetails,
+    pub notes: Vec<ForcedExitRequestNote>,
+    /// Why the transactions of the last failed processing could not be submitted, if so.
+    #[serde(default)]
+    pub submission_error: Option<SubmissionError>,
+}
+
+/// What is known about the payment made by the L1 transaction.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum ForcedExitPaymentLookup {
+    /// The requests paid for by the transaction, it may pay for several requests at once.
+    Matched {
+        requests: Vec<ForcedExitRequestDetails>,
+    },
+    /// The payment has been seen, but no request has been paid for with it. The reasons
+    /// are only known for the payments set aside before the matching.
+    Unmatched {
+        reasons: Vec<UnmatchedPaymentReason>,
+    },
+}
+
+/// The L1 transaction the partner is going to pay for the request with,
+/// registered before it is mined.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitExpectedPaymentRequest {
+    pub eth_tx_hash: H256,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitQuoteQuery {
+    pub tokens_count: usize,
+}
+
+/// Confirmation of the `FullExit` operations of the escalated request sent on L1.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FinalizeEscalationRequest {
+    pub l1_tx_hash: H256,
+}
+
+/// Parameters of the API key issued to the trusted partner, the limits which are not
+/// specified are taken from the server config.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiKeyRequest {
+    pub label: String,
+    pub max_tokens_per_request: Option<u8>,
+    pub max_requests_per_hour: Option<u32>,
+}
+
+/// The issued API key, its value is only returned once and is never stored by the server.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedApiKey {
+    pub key: String,
+    pub api_key: ForcedExitRequestsApiKey,
+}
+
+/// Payment injected by the operators, it is processed by the watcher the same way
+/// as the payments to the forced exit contract.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct InjectPaymentRequest {
+    #[serde(with = "BigUintSerdeAsRadix10Str")]
+    pub amount: BigUint,
+    pub request_id: Option<ForcedExitRequestId>,
+    /// The L1 block the funds were received at.
+    pub block_number: u64,
+    pub eth_tx_hash: Option<H256>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SetPaymentSourceRequest {
+    pub enabled: bool,
+}
+
+/// The payment to explain the matching of, as if it was received by the sender.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ExplainPaymentRequest {
+    #[serde(with = "BigUintSerdeAsRadix10Str")]
+    pub amount: BigUint,
+    /// The submission time of the payment, the validity of the requests is checked against it.
+    pub timestamp: DateTime<Utc>,
+    /// The hash of the payment transaction, the ones registered in advance are matched by it.
+    #[serde(default)]
+    pub eth_tx_hash: Option<H256>,
+    /// The public id supplied by the payer in the calldata, if any.
+    #[serde(default)]
+    pub request_id: Option<ForcedExitRequestId>,
+}
+
+/// The pacing of the sender set instead of the configured one, `None` restores the configured one.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SetPacingRequest {
+    pub pacing: Option<ForcedExitPacing>,
+}
+
+/// The note appended to the request by the operator, authored by the label of the API key
+/// supplied along with the admin token.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AddRequestNoteRequest {
+    pub text: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
+/// Header the API key of the trusted partner is supplied with.
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
 const FORCED_EXIT_REQUESTS_SCOPE: &str = "/api/forced_exit_requests/v0.1/";
+const FORCED_EXIT_REQUESTS_V02_SCOPE: &str = "/api/forced_exit_requests/v0.2/";
 
 impl Client {
     pub async fn get_forced_exit_requests_status(&self) -> ClientResult<ForcedExitRequestStatus> {
@@ -61,4 +337,136 @@ impl Client {
             .send()
             .await
     }
+
+    pub async fn forced_exit_requests_status_v02(&self) -> ClientResult<Response> {
+        self.get_with_scope(FORCED_EXIT_REQUESTS_V02_SCOPE, "status")
+            .send()
+            .await
+    }
+
+    pub async fn forced_exit_request_quote(&self, tokens_count: usize) -> ClientResult<Response> {
+        self.get_with_scope(FORCED_EXIT_REQUESTS_V02_SCOPE, "quote")
+            .query(&ForcedExitQuoteQuery { tokens_count })
+            .send()
+            .await
+    }
+
+    pub async fn create_forced_exit_request(
+        &self,
+        register_request: &ForcedExitRegisterRequest,
+    ) -> ClientResult<Response> {
+        self.post_with_scope(FORCED_EXIT_REQUESTS_V02_SCOPE, "requests")
+            .body(register_request)
+            .send()
+            .await
+    }
+
+    /// Creates the request with the limits of the trusted partner's API key.
+    pub async fn create_forced_exit_request_with_api_key(
+        &self,
+        register_request: &ForcedExitRegisterRequest,
+        api_key: &str,
+    ) -> ClientResult<Response> {
+        self.post_with_scope(FORCED_EXIT_REQUESTS_V02_SCOPE, "requests")
+            .header(API_KEY_HEADER, api_key)
+            .body(register_request)
+            .send()
+            .await
+    }
+
+    pub async fn forced_exit_request_by_id(
+        &self,
+        public_id: ForcedExitRequestId,
+    ) -> ClientResult<Response> {
+        self.get_with_scope(
+            FORCED_EXIT_REQUESTS_V02_SCOPE,
+            &format!("requests/{}", public_id),
+        )
+        .send()
+        .await
+    }
+
+    /// The transactions sent for the request and their statuses.
+    pub async fn forced_exit_request_progress(
+        &self,
+        public_id: ForcedExitRequestId,
+    ) -> ClientResult<Response> {
+        self.get_with_scope(
+            FORCED_EXIT_REQUESTS_V02_SCOPE,
+            &format!("requests/{}/status", public_id),
+        )
+        .send()
+        .await
+    }
+
+    /// The payments received for the request and what has become of them.
+    pub async fn forced_exit_request_ledger(
+        &self,
+        public_id: ForcedExitRequestId,
+    ) -> ClientResult<Response> {
+        self.get_with_scope(
+            FORCED_EXIT_REQUESTS_V02_SCOPE,
+            &format!("requests/{}/ledger", public_id),
+        )
+        .send()
+        .await
+    }
+
+    /// Looks up the requests paid for by the given L1 transaction.
+    pub async fn forced_exit_requests_by_payment(
+        &self,
+        eth_tx_hash: H256,
+    ) -> ClientResult<Response> {
+        self.get_with_scope(
+            FORCED_EXIT_REQUESTS_V02_SCOPE,
+            &format!("by_payment/{:?}", eth_tx_hash),
+        )
+        .send()
+        .await
+    }
+
+    /// Registers the L1 transaction paying for the request created with the API key,
+    /// so the payment is matched as soon as the transaction is mined.
+    pub async fn register_expected_forced_exit_payment(
+        &self,
+        public_id: ForcedExitRequestId,
+        eth_tx_hash: H256,
+        api_key: &str,
+    ) -> ClientResult<Response> {
+        self.post_with_scope(
+            FORCED_EXIT_REQUESTS_V02_SCOPE,
+            &format!("requests/{}/expected_payment", public_id),
+        )
+        .header(API_KEY_HEADER, api_key)
+        .body(&ForcedExitExpectedPaymentRequest { eth_tx_hash })
+        .send()
+        .await
+    }
+
+    /// Extends the validity period of the request that has not been paid yet.
+    pub async fn extend_forced_exit_request(
+        &self,
+        public_id: ForcedExitRequestId,
+    ) -> ClientResult<Response> {
+        self.post_with_scope(
+            FORCED_EXIT_REQUESTS_V02_SCOPE,
+            &format!("requests/{}/extend", public_id),
+        )
+        .send()
+        .await
+    }
+
+    pub async fn forced_exit_requests_pagination(
+        &self,
+        target: Address,
+        pagination_query: &PaginationQuery<ApiEither<ForcedExitRequestId>>,
+    ) -> ClientResult<Response> {
+        self.get_with_scope(
+            FORCED_EXIT_REQUESTS_V02_SCOPE,
+            &format!("accounts/{:?}/requests", target),
+        )
+        .query(pagination_query)
+        .send()
+        .await
+    }
 }
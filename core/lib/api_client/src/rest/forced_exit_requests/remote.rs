@@ -0,0 +1,373 @@
+//! Part of the admin API used by the forced exit requests component running
+//! separately from the server.
+//!
+//! The requests are stored by the server, the component only reads the ones it
+//! needs and reports the progress of fulfilling them. All the endpoints require
+//! the JWT auth token signed with the admin secret.
+
+// Built-in uses
+
+// External uses
+use serde::{Deserialize, Serialize};
+
+// Workspace uses
+use zksync_types::{
+    forced_exit_requests::{
+        ForcedExitBacklogReport, ForcedExitCancellationKind, ForcedExitRequest,
+        ForcedExitRequestId, ForcedExitSingletonHolder, ForcedExitTargetCheck, PaymentMatchScheme,
+        PaymentSourceState,
+    },
+    tx::TxHash,
+    AccountId, Address, Nonce, H256,
+};
+
+// Local uses
+use crate::rest::client::{Client, ClientRequestBuilder, Result as ClientResult};
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SetFulfilledByRequest {
+    pub fulfilled_by: Option<Vec<TxHash>>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelRequestRequest {
+    pub kind: ForcedExitCancellationKind,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SetMatchSchemeRequest {
+    pub match_scheme: PaymentMatchScheme,
+    /// The L1 transaction the payment was made with, if known.
+    #[serde(default)]
+    pub payment_tx_hash: Option<H256>,
+    /// The amount the request was paid for with as a decimal string,
+    /// not sent by the components preceding the field.
+    #[serde(default)]
+    pub paid_amount: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BeginFulfillmentRequest {
+    /// The L1 transaction of the payment the request is fulfilled for, if known.
+    #[serde(default)]
+    pub payment_tx_hash: Option<H256>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteOldRequestsRequest {
+    /// The unfulfilled requests created earlier than this many milliseconds ago are deleted.
+    pub deleting_threshold_millis: i64,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TxReceiptsRequest {
+    pub tx_hashes: Vec<TxHash>,
+}
+
+/// Receipt of the executed transaction, the ones not executed yet are not returned.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitTxReceipt {
+    pub tx_hash: TxHash,
+    pub block_number: i64,
+    pub success: bool,
+    pub verified: bool,
+    pub fail_reason: Option<String>,
+}
+
+const FORCED_EXIT_REQUESTS_REMOTE_SCOPE: &str = "/admin/forced_exit_requests/remote/";
+const FORCED_EXIT_REQUESTS_ADMIN_SCOPE: &str = "/admin/forced_exit_requests/";
+
+fn with_auth(builder: ClientRequestBuilder, auth_token: &str) -> ClientRequestBuilder {
+    builder.header("Authorization", &format!("Bearer {}", auth_token))
+}
+
+impl Client {
+    /// Loads the requests which have been paid for, but are not fulfilled yet.
+    pub async fn unconfirmed_forced_exit_requests(
+        &self,
+        auth_token: &str,
+    ) -> ClientResult<Vec<ForcedExitRequest>> {
+        with_auth(
+            self.get_with_scope(FORCED_EXIT_REQUESTS_REMOTE_SCOPE, "requests/unconfirmed"),
+            auth_token,
+        )
+        .send()
+        .await
+    }
+
+    pub async fn oldest_unfulfilled_forced_exit_request(
+        &self,
+        auth_token: &str,
+    ) -> ClientResult<Option<ForcedExitRequest>> {
+        with_auth(
+            self.get_with_scope(
+                FORCED_EXIT_REQUESTS_REMOTE_SCOPE,
+                "requests/oldest_unfulfilled",
+            ),
+            auth_token,
+        )
+        .send()
+        .await
+    }
+
+    pub async fn remote_forced_exit_request(
+        &self,
+        request_id: ForcedExitRequestId,
+        auth_token: &str,
+    ) -> ClientResult<Option<ForcedExitRequest>> {
+        with_auth(
+            self.get_with_scope(
+                FORCED_EXIT_REQUESTS_REMOTE_SCOPE,
+                &format!("requests/{}", request_id),
+            ),
+            auth_token,
+        )
+        .send()
+        .await
+    }
+
+    pub async fn remote_forced_exit_request_by_public_id(
+        &self,
+        public_id: ForcedExitRequestId,
+        auth_token: &str,
+    ) -> ClientResult<Option<ForcedExitRequest>> {
+        with_auth(
+            self.get_with_scope(
+                FORCED_EXIT_REQUESTS_REMOTE_SCOPE,
+                &format!("requests/by_public_id/{}", public_id),
+            ),
+            auth_token,
+        )
+        .send()
+        .await
+    }
+
+    pub async fn set_forced_exit_request_fulfilled(
+        &self,
+        request_id: ForcedExitRequestId,
+        auth_token: &str,
+    ) -> ClientResult<()> {
+        with_auth(
+            self.post_with_scope(
+                FORCED_EXIT_REQUESTS_REMOTE_SCOPE,
+                &format!("requests/{}/fulfilled", request_id),
+            ),
+            auth_token,
+        )
+        .send()
+        .await
+    }
+
+    pub async fn set_forced_exit_request_fulfilled_by(
+        &self,
+        request_id: ForcedExitRequestId,
+        params: &SetFulfilledByRequest,
+        auth_token: &str,
+    ) -> ClientResult<()> {
+        with_auth(
+            self.post_with_scope(
+                FORCED_EXIT_REQUESTS_REMOTE_SCOPE,
+                &format!("requests/{}/fulfilled_by", request_id),
+            ),
+            auth_token,
+        )
+        .body(params)
+        .send()
+        .await
+    }
+
+    /// Returns the cancelled request, or `None` if it can not be cancelled in this way.
+    pub async fn cancel_forced_exit_request(
+        &self,
+        request_id: ForcedExitRequestId,
+        params: &CancelRequestRequest,
+        auth_token: &str,
+    ) -> ClientResult<Option<ForcedExitRequest>> {
+        with_auth(
+            self.post_with_scope(
+                FORCED_EXIT_REQUESTS_REMOTE_SCOPE,
+                &format!("requests/{}/cancel", request_id),
+            ),
+            auth_token,
+        )
+        .body(params)
+        .send()
+        .await
+    }
+
+    pub async fn set_forced_exit_request_match_scheme(
+        &self,
+        request_id: ForcedExitRequestId,
+        params: &SetMatchSchemeRequest,
+        auth_token: &str,
+    ) -> ClientResult<()> {
+        with_auth(
+            self.post_with_scope(
+                FORCED_EXIT_REQUESTS_REMOTE_SCOPE,
+                &format!("requests/{}/match_scheme", request_id),
+            ),
+            auth_token,
+        )
+        .body(params)
+        .send()
+        .await
+    }
+
+    /// Returns `false` if the fulfillment for the same payment has already been started.
+    pub async fn begin_forced_exit_request_fulfillment(
+        &self,
+        request_id: ForcedExitRequestId,
+        params: &BeginFulfillmentRequest,
+        auth_token: &str,
+    ) -> ClientResult<bool> {
+        with_auth(
+            self.post_with_scope(
+                FORCED_EXIT_REQUESTS_REMOTE_SCOPE,
+                &format!("requests/{}/fulfillment", request_id),
+            ),
+            auth_token,
+        )
+        .body(params)
+        .send()
+        .await
+    }
+
+    pub async fn delete_old_forced_exit_requests(
+        &self,
+        params: &DeleteOldRequestsRequest,
+        auth_token: &str,
+    ) -> ClientResult<()> {
+        with_auth(
+            self.post_with_scope(FORCED_EXIT_REQUESTS_REMOTE_SCOPE, "requests/delete_old"),
+            auth_token,
+        )
+        .body(params)
+        .send()
+        .await
+    }
+
+    pub async fn forced_exit_tx_receipts(
+        &self,
+        params: &TxReceiptsRequest,
+        auth_token: &str,
+    ) -> ClientResult<Vec<ForcedExitTxReceipt>> {
+        with_auth(
+            self.post_with_scope(FORCED_EXIT_REQUESTS_REMOTE_SCOPE, "receipts"),
+            auth_token,
+        )
+        .body(params)
+        .send()
+        .await
+    }
+
+    pub async fn forced_exit_target_check(
+        &self,
+        target: Address,
+        auth_token: &str,
+    ) -> ClientResult<ForcedExitTargetCheck> {
+        with_auth(
+            self.get_with_scope(
+                FORCED_EXIT_REQUESTS_REMOTE_SCOPE,
+                &format!("accounts/{:?}/target_check", target),
+            ),
+            auth_token,
+        )
+        .send()
+        .await
+    }
+
+    pub async fn remote_account_id(
+        &self,
+        address: Address,
+        auth_token: &str,
+    ) -> ClientResult<Option<AccountId>> {
+        with_auth(
+            self.get_with_scope(
+                FORCED_EXIT_REQUESTS_REMOTE_SCOPE,
+                &format!("accounts/{:?}/id", address),
+            ),
+            auth_token,
+        )
+        .send()
+        .await
+    }
+
+    /// Loads the nonce of the account in the last committed state.
+    pub async fn remote_account_nonce(
+        &self,
+        account_id: AccountId,
+        auth_token: &str,
+    ) -> ClientResult<Option<Nonce>> {
+        with_auth(
+            self.get_with_scope(
+                FORCED_EXIT_REQUESTS_REMOTE_SCOPE,
+                &format!("accounts/{}/nonce", account_id),
+            ),
+            auth_token,
+        )
+        .send()
+        .await
+    }
+
+    /// Loads the nonce following the transactions of the account still in the mempool.
+    pub async fn remote_account_pending_nonce(
+        &self,
+        account_id: AccountId,
+        auth_token: &str,
+    ) -> ClientResult<Option<Nonce>> {
+        with_auth(
+            self.get_with_scope(
+                FORCED_EXIT_REQUESTS_REMOTE_SCOPE,
+                &format!("accounts/{}/pending_nonce", account_id),
+            ),
+            auth_token,
+        )
+        .send()
+        .await
+    }
+
+    pub async fn forced_exit_payment_sources(
+        &self,
+        auth_token: &str,
+    ) -> ClientResult<Vec<PaymentSourceState>> {
+        with_auth(
+            self.get_with_scope(FORCED_EXIT_REQUESTS_ADMIN_SCOPE, "payment_sources"),
+            auth_token,
+        )
+        .send()
+        .await
+    }
+
+    /// Estimates the outcome of fulfilling the paid requests, which have not been sent yet.
+    pub async fn simulate_forced_exit_backlog(
+        &self,
+        auth_token: &str,
+    ) -> ClientResult<ForcedExitBacklogReport> {
+        with_auth(
+            self.post_with_scope(FORCED_EXIT_REQUESTS_ADMIN_SCOPE, "backlog/simulate"),
+            auth_token,
+        )
+        .send()
+        .await
+    }
+
+    /// Loads the server instance processing the requests, if any.
+    pub async fn forced_exit_singleton_holder(
+        &self,
+        auth_token: &str,
+    ) -> ClientResult<Option<ForcedExitSingletonHolder>> {
+        with_auth(
+            self.get_with_scope(FORCED_EXIT_REQUESTS_ADMIN_SCOPE, "singleton"),
+            auth_token,
+        )
+        .send()
+        .await
+    }
+}
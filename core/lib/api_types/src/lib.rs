@@ -1,6 +1,7 @@
 pub use either::Either;
 use serde::{Deserialize, Serialize};
 use zksync_types::{
+    forced_exit_requests::ForcedExitSenderStatus,
     tx::{TxEthSignatureVariant, TxHash},
     ZkSyncTx, H256,
 };
@@ -34,4 +35,8 @@ pub struct CoreStatus {
     pub main_database_available: bool,
     pub replica_database_available: bool,
     pub web3_available: bool,
+    /// The state of the `ForcedExit` sender account, which the service waits to be
+    /// committed on startup. Not set if the service has never started.
+    #[serde(default)]
+    pub forced_exit_sender: Option<ForcedExitSenderStatus>,
 }
@@ -2,7 +2,10 @@ use either::Either;
 use serde::{Deserialize, Serialize, Serializer};
 use std::str::FromStr;
 use thiserror::Error;
-use zksync_types::{tx::TxHash, AccountId, Address, BlockNumber, SerialId, TokenId};
+use zksync_types::{
+    forced_exit_requests::ForcedExitRequestId, tx::TxHash, AccountId, Address, BlockNumber,
+    SerialId, TokenId,
+};
 
 pub const MAX_LIMIT: u32 = 100;
 
@@ -139,3 +142,9 @@ pub struct AccountTxsRequest {
     pub token: Option<TokenId>,
     pub second_address: Option<Address>,
 }
+
+#[derive(Debug, Serialize)]
+pub struct ForcedExitRequestsQuery {
+    pub target: Address,
+    pub request_id: ApiEither<ForcedExitRequestId>,
+}
@@ -18,6 +18,11 @@ pub struct DBConfig {
     pub rejected_transactions_max_age: u64,
     /// Sleep time (in hours) of the actor responsible for deleting failed transactions from the database.
     pub rejected_transactions_cleaner_interval: u64,
+    /// Key (32 bytes in hex) for the encryption of the sensitive columns, they are stored in plaintext if not set.
+    pub encryption_key: Option<String>,
+    /// Whether the plaintext values of the sensitive columns are accepted while the existing rows are being encrypted.
+    #[serde(default)]
+    pub encryption_mixed_mode: bool,
 }
 
 impl DBConfig {
@@ -47,6 +52,10 @@ mod tests {
             url: "postgres://postgres@localhost/plasma".into(),
             rejected_transactions_max_age: 336,
             rejected_transactions_cleaner_interval: 24,
+            encryption_key: Some(
+                "0x27593fea79697e947890ecbecce7901b0008345e5d7259710d0dd5e500d040be".into(),
+            ),
+            encryption_mixed_mode: true,
         }
     }
 
@@ -57,6 +66,8 @@ DATABASE_POOL_SIZE="10"
 DATABASE_URL="postgres://postgres@localhost/plasma"
 DATABASE_REJECTED_TRANSACTIONS_MAX_AGE="336"
 DATABASE_REJECTED_TRANSACTIONS_CLEANER_INTERVAL="24"
+DATABASE_ENCRYPTION_KEY="0x27593fea79697e947890ecbecce7901b0008345e5d7259710d0dd5e500d040be"
+DATABASE_ENCRYPTION_MIXED_MODE="true"
         "#;
         set_env(config);
 
@@ -1,9 +1,20 @@
-use std::time::Duration;
+use std::{collections::HashSet, str::FromStr, time::Duration};
 
 use crate::envy_load;
 /// External uses
+use chrono::{DateTime, Utc};
+use num::{BigUint, Zero};
 use serde::Deserialize;
-use zksync_types::{Address, H256};
+use zksync_types::{
+    forced_exit_requests::{
+        amount_id_digits, ActiveTargetPolicy, CreationLimits, ForcedExitConfigCandidate,
+        ForcedExitFeature, ForcedExitMoneyConfig, ForcedExitPacing, ForcedExitPipelineConfig,
+        MaintenanceRecurrence, MaintenanceWindow, PaymentAddressWindow, PaymentSource,
+        MAX_DIGITS_IN_ID,
+    },
+    tx::PackedEthSignature,
+    Address, TokenId, H256,
+};
 
 // There are two types of configs:
 // The original one (with tx_interval_scaling_factor)
@@ -17,50 +28,720 @@ use zksync_types::{Address, H256};
 struct ForcedExitRequestsInternalConfig {
     pub enabled: bool,
     pub max_tokens_per_request: u8,
+    pub max_metadata_length: usize,
+    pub max_active_requests_per_target: u32,
+    #[serde(default)]
+    pub allowed_tokens: String,
+    pub max_requests_per_hour: u32,
     pub recomended_tx_interval: i64,
     pub tx_interval_scaling_factor: f64,
     pub price_per_token: i64,
     pub digits_in_id: u8,
     pub wait_confirmations: u64,
+    #[serde(default)]
     pub sender_private_key: String,
     pub sender_eth_private_key: H256,
     pub sender_account_address: Address,
+    #[serde(default)]
+    pub eth_sign_forced_exits: bool,
+    #[serde(default)]
+    pub additional_senders: String,
+    pub external_signer_url: Option<String>,
+    pub external_signer_auth_token: Option<String>,
+    pub external_signer_timeout: u64,
+    pub external_signer_attempts: u32,
     pub expiration_period: u64,
+    pub expiration_grace_period: u64,
+    pub expiration_sweep_interval: u64,
+    pub expiration_sweep_batch_size: u32,
     pub blocks_check_amount: u64,
     pub eth_node_poll_interval: u64,
+    #[serde(default)]
+    pub legacy_contracts: String,
+    #[serde(default)]
+    pub payment_addresses: String,
+    pub l1_escalation_enabled: bool,
+    pub l1_escalation_failures_threshold: u32,
+    pub webhook_url: Option<String>,
+    pub callbacks_enabled: bool,
+    pub callback_max_attempts: u32,
+    pub runtime_threads: Option<usize>,
+    pub remote_api_url: Option<String>,
+    pub read_replica_pool_size: Option<u32>,
+    pub id_space_alert_utilization: u8,
+    pub id_space_max_utilization: u8,
+    pub l1_payments_enabled: bool,
+    pub admin_payments_enabled: bool,
+    pub max_payment_amount: String,
+    pub startup_reconciliation_timeout: u64,
+    pub reconciliation_interval: u64,
+    pub reconciliation_jitter: u64,
+    pub active_target_policy: String,
+    pub active_target_hold_period: u64,
+    pub singleton_mode: String,
+    pub token_cache_max_staleness: u64,
+    pub legacy_fulfilled_by_enabled: bool,
+    pub receipt_poll_interval: u64,
+    pub receipt_poll_batch_size: usize,
+    pub processing_workers: usize,
+    pub aggregation_window: u64,
+    pub max_aggregated_requests: usize,
+    pub max_batch_size: usize,
+    pub l1_transfer_check_web3_url: Option<String>,
+    pub l1_transfer_check_timeout: u64,
+    pub sender_creation_pending_timeout: u64,
+    pub sender_address_unknown_timeout: u64,
+    pub metrics_max_token_labels: usize,
+    pub expected_payment_wait_confirmations: u64,
+    pub tx_commit_timeout: u64,
+    pub forced_exit_tx_ttl: u64,
+    pub shutdown_drain_timeout: u64,
+    #[serde(default)]
+    pub maintenance_windows: String,
+    pub maintenance_lead_time: u64,
+    pub processing_attempts: u32,
+    pub processing_retry_base_delay: u64,
+    pub processing_retry_max_delay: u64,
+    pub overpayment_tolerance: u64,
+    pub overpayment_tolerance_percent: u8,
+    pub refunds_enabled: bool,
+    pub refund_processing_fee: u64,
+    pub refund_approval_threshold: String,
+    pub max_refunded_amount_per_hour: String,
+    pub min_sender_balance: String,
+    pub fee_token: Option<u32>,
+    pub fee_multiplier_percent: u32,
+    pub fee_bump_percent: u32,
+    pub max_batch_fee: String,
+    pub legacy_amount_ids_enabled: bool,
+    pub max_batches_per_minute: u32,
+    pub batches_per_block: u32,
 }
 
 #[derive(Debug, Deserialize, Clone, PartialEq)]
 pub struct ForcedExitRequestsConfig {
     pub enabled: bool,
     pub max_tokens_per_request: u8,
+    /// The longest metadata (in bytes) the clients may attach to the request.
+    pub max_metadata_length: usize,
+    /// How many requests awaiting the payment may be created for the same target account.
+    pub max_active_requests_per_target: u32,
+    /// The tokens the requests may be created for, any token if empty.
+    pub allowed_tokens: Vec<TokenId>,
+    /// The maximum number of requests created per hour without an API key,
+    /// the trusted partners have their own limits assigned to the keys.
+    pub max_requests_per_hour: u32,
     pub recomended_tx_interval: i64,
     pub max_tx_interval: i64,
     pub price_per_token: i64,
     pub digits_in_id: u8,
     pub wait_confirmations: u64,
+    /// L2 private key of the sender account, empty if the transactions are signed
+    /// by the external signer.
     pub sender_private_key: String,
     pub sender_eth_private_key: H256,
     pub sender_account_address: Address,
+    /// Whether the `ForcedExit` transactions of the main sender account carry the Ethereum
+    /// signature made with `sender_eth_private_key`, for the servers which require it.
+    pub eth_sign_forced_exits: bool,
+    /// The accounts the `ForcedExit` transactions are sent from besides the main one,
+    /// so the requests do not wait for the nonces of the same account. The refunds are
+    /// only sent from the main account.
+    pub additional_senders: Vec<ForcedExitSenderKey>,
+    /// The service the transactions of the sender accounts are signed by, so their private
+    /// keys are not kept in the config. The keys of the config are used if it is not set.
+    pub external_signer_url: Option<String>,
+    /// The bearer token the external signer is authorized with.
+    pub external_signer_auth_token: Option<String>,
+    /// How long (in milliseconds) a single request to the external signer may take.
+    pub external_signer_timeout: u64,
+    /// How many times the transaction is sent to the external signer before it is given up on,
+    /// the transient failures (timeouts, unavailability) are retried.
+    pub external_signer_attempts: u32,
     pub expiration_period: u64,
+    /// How long (in milliseconds) after the end of its validity period the unpaid request
+    /// keeps its id. The request is expired then and its id is allocated to the new requests,
+    /// so the payments made for it later are no longer matched with it.
+    pub expiration_grace_period: u64,
+    /// How often (in milliseconds) the unpaid requests are expired.
+    pub expiration_sweep_interval: u64,
+    /// The maximum number of the requests expired in a single transaction.
+    pub expiration_sweep_batch_size: u32,
     pub blocks_check_amount: u64,
     pub eth_node_poll_interval: u64,
+    /// Previous deployments of the forced exit contract, the payments to which
+    /// are still recognized within their activation block ranges.
+    pub legacy_contracts: Vec<ForcedExitContractDeployment>,
+    /// The schedule of the rotation of the payment address. The payments to each
+    /// address are accepted within its window and the newest address is advertised
+    /// to the payers. If empty, the forced exit contract is the only payment address.
+    pub payment_addresses: Vec<PaymentAddressWindow>,
+    /// Whether the requests, which keep failing on L2, are escalated to the `FullExit`
+    /// priority operations to be sent on L1.
+    pub l1_escalation_enabled: bool,
+    /// The number of failed `ForcedExit` transactions for a token after which
+    /// the request is escalated to L1.
+    pub l1_escalation_failures_threshold: u32,
+    /// The URL the notifications about the status transitions of the requests are posted to.
+    /// The notifications are kept in the outbox until delivered, if the URL is not set
+    /// they are not delivered at all.
+    pub webhook_url: Option<String>,
+    /// Whether the requests may be created with the URL they are posted to once fulfilled.
+    /// The callbacks of the requests created before are delivered regardless.
+    pub callbacks_enabled: bool,
+    /// The number of the failed attempts to deliver the callback, after which it is
+    /// given up on until redelivered by the operators.
+    pub callback_max_attempts: u32,
+    /// The number of the worker threads of the runtime dedicated to the component,
+    /// if not set the component shares the runtime with the rest of the server.
+    pub runtime_threads: Option<usize>,
+    /// The URL of the server API the component talks to when it runs separately from
+    /// the server without access to the database. If set, the server does not watch
+    /// the payments itself, leaving it to the remote component.
+    pub remote_api_url: Option<String>,
+    /// The size of the pool of the connections to the read replica of the database
+    /// (`DATABASE_REPLICA_URL`). If set, the reads which tolerate the replication lag,
+    /// such as the receipt polling, go to the replica instead of the primary database.
+    pub read_replica_pool_size: Option<u32>,
+    /// The share (in percents) of the id space occupied by the requests awaiting
+    /// the payment, after which the operators are alerted.
+    pub id_space_alert_utilization: u8,
+    /// The share (in percents) of the id space occupied by the requests awaiting
+    /// the payment, after which the new requests are refused.
+    pub id_space_max_utilization: u8,
+    /// Whether the payments emitted as the events of the forced exit contract are processed.
+    /// The operators may override it at runtime, see `PaymentSourceState`.
+    pub l1_payments_enabled: bool,
+    /// Whether the payments injected by the operators are processed.
+    pub admin_payments_enabled: bool,
+    /// The payments larger than this amount (in wei) are set aside without being matched,
+    /// no request could cost that much.
+    pub max_payment_amount: BigUint,
+    /// How long (in milliseconds) the transactions sent before the restart are awaited
+    /// on startup before the new payments are processed.
+    pub startup_reconciliation_timeout: u64,
+    /// How often (in milliseconds) the requests sent before are reconciled besides the startup,
+    /// so the ones left unconfirmed are settled even if the sender has not noticed them.
+    /// Zero disables the periodic reconciliation.
+    pub reconciliation_interval: u64,
+    /// Up to how long (in milliseconds) every periodic reconciliation is delayed at random,
+    /// so the replicas started together do not query the database at the same time.
+    pub reconciliation_jitter: u64,
+    /// What happens to the paid request if its target sets the signing key before
+    /// the request is fulfilled.
+    pub active_target_policy: ActiveTargetPolicy,
+    /// How long (in milliseconds) such a request is held with the `hold` policy,
+    /// before it fails and its payment is to be refunded.
+    pub active_target_hold_period: u64,
+    /// What the instance does if another one is already processing the requests.
+    pub singleton_mode: SingletonMode,
+    /// How old (in milliseconds) the token metadata may be to be used while the tokens
+    /// can not be queried. The payments, which need the tokens older than that, are
+    /// deferred until the tokens are available again.
+    pub token_cache_max_staleness: u64,
+    /// Whether the hashes of the sent transactions are still written to the deprecated
+    /// `fulfilled_by` column besides the fulfillments. The column is read by the servers
+    /// preceding the fulfillments, so it may only be disabled once none of them is running.
+    pub legacy_fulfilled_by_enabled: bool,
+    /// How often (in milliseconds) the receipts of the sent transactions are queried.
+    /// The transactions awaited by all the requests in flight are queried together.
+    pub receipt_poll_interval: u64,
+    /// The maximum number of the transactions the receipts of which are queried at once,
+    /// the rest are queried on the following ticks.
+    pub receipt_poll_batch_size: usize,
+    /// The number of the payments processed at once. The transactions of the requests
+    /// are still sent one batch at a time, but their commits are awaited concurrently.
+    pub processing_workers: usize,
+    /// How long (in milliseconds) the payments are collected after the first one, so the transactions
+    /// of their requests are sent in a single batch and its commit is awaited once.
+    pub aggregation_window: u64,
+    /// The maximum number of the requests the transactions of which are sent in a single batch.
+    /// The payments are processed one by one if it is 1.
+    pub max_aggregated_requests: usize,
+    /// The maximum number of the transactions sent in a single batch, the fee payment included.
+    /// The transactions of the request with more tokens are sent in several batches one after another.
+    pub max_batch_size: usize,
+    /// The Ethereum node the token contracts are asked whether the transfers to the target
+    /// are paused or blacklisted on L1. Such tokens are not withdrawn, if not set the tokens
+    /// are not checked at all.
+    pub l1_transfer_check_web3_url: Option<String>,
+    /// How long (in milliseconds) the check of the tokens of a single request may take,
+    /// the tokens not checked by then are withdrawn anyway.
+    pub l1_transfer_check_timeout: u64,
+    /// How long (in milliseconds) the startup waits for the sender account, the creation
+    /// of which is pending in the priority queue or the mempool, to be committed.
+    pub sender_creation_pending_timeout: u64,
+    /// How long (in milliseconds) the startup waits for the sender account, nothing creating
+    /// which is known. The address is likely misconfigured, so it is given up on sooner.
+    pub sender_address_unknown_timeout: u64,
+    /// The maximum number of the tokens the metrics of the fulfillments are labelled with,
+    /// the rest of the tokens share the `other` label.
+    pub metrics_max_token_labels: usize,
+    /// The number of the confirmations the payments pre-registered for the requests wait for.
+    /// The payer has declared the transaction in advance, so it is processed sooner than
+    /// the rest, though never later than after `wait_confirmations`.
+    pub expected_payment_wait_confirmations: u64,
+    /// How long (in milliseconds) the sent transactions are awaited to be committed. The request
+    /// the transactions of which are not committed by then is settled by the reconciliation.
+    pub tx_commit_timeout: u64,
+    /// How long (in milliseconds) the signed `ForcedExit` transactions stay valid. The transaction
+    /// stuck in the mempool is not executed once the target may have become active again.
+    pub forced_exit_tx_ttl: u64,
+    /// How long (in milliseconds) the transactions sent before the shutdown are still awaited
+    /// to be committed. The requests, the transactions of which are not committed by then,
+    /// are left in flight for the reconciliation on the next start.
+    pub shutdown_drain_timeout: u64,
+    /// The scheduled maintenance, during which no transactions are sent for the requests.
+    /// The payments are still recorded and are processed once the maintenance is over.
+    pub maintenance_windows: Vec<MaintenanceWindow>,
+    /// How long (in milliseconds) before the start of the maintenance the transactions
+    /// stop being sent, so the ones sent last are committed before it starts.
+    pub maintenance_lead_time: u64,
+    /// How many times the payment is processed before its failure is recorded and it is given up on.
+    pub processing_attempts: u32,
+    /// How long (in milliseconds) the processing waits before the second attempt,
+    /// the delay doubles with every next one up to `processing_retry_max_delay`.
+    pub processing_retry_base_delay: u64,
+    pub processing_retry_max_delay: u64,
+    /// How much (in wei) the amount matched by its lowest digits may exceed the amount to be
+    /// paid for the request. The overpayment changes the digits the id is extracted from, so
+    /// the payment is only matched this way if no request is paid for with the exact amount.
+    pub overpayment_tolerance: u64,
+    /// The same tolerance in percents of the price of the request, the larger one applies.
+    pub overpayment_tolerance_percent: u8,
+    /// Whether the payments for the requests, which could not be fulfilled because of the expiration,
+    /// the cancellation or the incorrect amount, are transferred back to the payers on L2.
+    pub refunds_enabled: bool,
+    /// The fee (in wei) deducted from the refunded payment.
+    pub refund_processing_fee: u64,
+    /// The refunds larger than this amount (in wei) are only sent once approved by the operators.
+    pub refund_approval_threshold: BigUint,
+    /// How much (in wei) may be refunded automatically per hour, the refunds above the limit
+    /// wait for it to be replenished. Zero disables the limit.
+    pub max_refunded_amount_per_hour: BigUint,
+    /// The balance (in wei) of the sender account, which the refunds are sent from, below which
+    /// the operators are alerted to top it up. Zero disables the alert.
+    pub min_sender_balance: BigUint,
+    /// The token the sender account pays the fees of the batches of the `ForcedExit` transactions in.
+    /// If not set, the transactions are sent without a fee, which is only accepted if the server
+    /// exempts the sender from the fees.
+    pub fee_token: Option<TokenId>,
+    /// The fee quoted for the batch is scaled up by this percentage, so it still covers the batch
+    /// if the prices move before the batch is submitted.
+    pub fee_multiplier_percent: u32,
+    /// Once the batch is rejected since its fee has become too low, the fee it is sent with
+    /// the next time is at least the rejected one raised by this percentage.
+    pub fee_bump_percent: u32,
+    /// The largest fee (in the units of `fee_token`) paid for a single batch, the request fails
+    /// rather than paying more once the fee has been raised above it. Zero disables the limit.
+    pub max_batch_fee: BigUint,
+    /// Whether the payments are still matched by the id alone in the lowest `digits_in_id` digits
+    /// of the amount, without the check digit, the way the requests created before it was added
+    /// are paid for.
+    pub legacy_amount_ids_enabled: bool,
+    /// How many batches of the ForcedExit transactions the sender submits per minute at most,
+    /// the batches above the limit wait for their turn. Zero disables the limit.
+    pub max_batches_per_minute: u32,
+    /// How many batches are sent before the sender waits for the block with the last of them
+    /// to be sealed. Zero disables the waiting.
+    pub batches_per_block: u32,
+}
+
+/// What the instance does on startup if the requests are already processed by another
+/// instance, i.e. the lock allowing to process them is held by it. Either way, the lock
+/// lost at runtime pauses the processing until it is taken again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SingletonMode {
+    /// The instance never processes the requests, only serves the API.
+    Strict,
+    /// The instance serves the API, processing the requests once the lock is released.
+    Degraded,
+}
+
+impl FromStr for SingletonMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(Self::Strict),
+            "degraded" => Ok(Self::Degraded),
+            another => Err(another.to_owned()),
+        }
+    }
+}
+
+/// The account sending the `ForcedExit` transactions, which is written as
+/// `<address>:<private key>`, or as `<address>` if it is signed by the external signer.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ForcedExitSenderKey {
+    pub address: Address,
+    /// L2 private key of the account as a `0x`-prefixed hex string.
+    pub private_key: Option<String>,
+}
+
+impl FromStr for ForcedExitSenderKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, private_key) = match s.trim().split_once(':') {
+            Some((address, private_key)) => (address, Some(private_key)),
+            None => (s.trim(), None),
+        };
+        let address = address
+            .trim_start_matches("0x")
+            .parse()
+            .map_err(|err| format!("Invalid sender address `{}`: {}", address, err))?;
+        let private_key = match private_key {
+            Some(private_key) => private_key,
+            None => {
+                return Ok(Self {
+                    address,
+                    private_key: None,
+                })
+            }
+        };
+        // The key itself is not written to the error, it is a secret
+        let is_key = private_key.len() == 66
+            && private_key.starts_with("0x")
+            && private_key[2..].chars().all(|c| c.is_ascii_hexdigit());
+        if !is_key {
+            return Err(format!(
+                "Invalid private key of the sender {:?}, expected 32 bytes in hex",
+                address
+            ));
+        }
+        Ok(Self {
+            address,
+            private_key: Some(private_key.to_owned()),
+        })
+    }
+}
+
+/// Deployment of the forced exit contract, which is written as
+/// `<address>:<version>:<first_block>:<last_block>`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ForcedExitContractDeployment {
+    pub address: Address,
+    /// Version of the contract, which determines the events it emits.
+    pub version: u32,
+    /// The first block (inclusive) the payments to the contract are accepted at.
+    pub first_block: u64,
+    /// The last block (inclusive) the payments to the contract are accepted at.
+    pub last_block: u64,
+}
+
+impl FromStr for ForcedExitContractDeployment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<_> = s.trim().split(':').collect();
+        if parts.len() != 4 {
+            return Err(format!(
+                "Expected `<address>:<version>:<first_block>:<last_block>`, got `{}`",
+                s
+            ));
+        }
+
+        let address = parts[0]
+            .trim_start_matches("0x")
+            .parse()
+            .map_err(|err| format!("Invalid contract address `{}`: {}", parts[0], err))?;
+        let version = parts[1]
+            .parse()
+            .map_err(|err| format!("Invalid contract version `{}`: {}", parts[1], err))?;
+        let first_block: u64 = parts[2]
+            .parse()
+            .map_err(|err| format!("Invalid first block `{}`: {}", parts[2], err))?;
+        let last_block: u64 = parts[3]
+            .parse()
+            .map_err(|err| format!("Invalid last block `{}`: {}", parts[3], err))?;
+        if first_block > last_block {
+            return Err(format!(
+                "The first block {} is greater than the last block {}",
+                first_block, last_block
+            ));
+        }
+
+        Ok(Self {
+            address,
+            version,
+            first_block,
+            last_block,
+        })
+    }
+}
+
+/// The id followed by its check digit has to be decoded from the amounts, see `MAX_DIGITS_IN_ID`.
+fn validate_digits_in_id(digits_in_id: u8) -> Result<(), String> {
+    if !(1..=MAX_DIGITS_IN_ID).contains(&u32::from(digits_in_id)) {
+        return Err(format!(
+            "Invalid number of digits in id {}, at most {} are supported",
+            digits_in_id, MAX_DIGITS_IN_ID
+        ));
+    }
+    Ok(())
+}
+
+/// The longest ttl (in milliseconds) of the `ForcedExit` transactions, a week. The transaction
+/// stuck in the mempool for longer would only keep the target from being exited again.
+const MAX_FORCED_EXIT_TX_TTL: u64 = 7 * 24 * 60 * 60 * 1000;
+
+/// The validity of the `ForcedExit` transactions is counted from the time they are signed,
+/// the longer ttl would overflow the time range of the transactions.
+fn validate_forced_exit_tx_ttl(ttl: u64) -> Result<(), String> {
+    if ttl == 0 || ttl > MAX_FORCED_EXIT_TX_TTL {
+        return Err(format!(
+            "Invalid ttl of the ForcedExit transactions {}ms, it must be positive and at most {}ms",
+            ttl, MAX_FORCED_EXIT_TX_TTL
+        ));
+    }
+    Ok(())
 }
 
 // Checks that in no way the price will overlap with the requests id space
 //
 // The amount that the users have to send to pay for the ForcedExit request
-// = (number of tokens) * (price_per_token) + id
+// = (number of tokens) * (price_per_token) + id * 10 + check digit
 //
-// Thus we need to check that at least digits_in_id first digits
+// Thus we need to check that at least id_digits first digits
 // are equal to zeroes in price_per_token
-fn validate_price_with_id_space(price: i64, digits_in_id: u8) {
-    let id_space = (10_i64).saturating_pow(digits_in_id.into());
+fn validate_price_with_id_space(price: i64, id_digits: u8) -> Result<(), String> {
+    let id_space = (10_i64).saturating_pow(id_digits.into());
+    if price % id_space != 0 {
+        return Err(format!(
+            "The price per token {} may overlap with request id, it must be a multiple of {}",
+            price, id_space
+        ));
+    }
+    Ok(())
+}
+
+/// The refunds sent without the approval have to fit into the hourly limit,
+/// otherwise the largest of them would never be sent.
+fn validate_refund_limits(
+    approval_threshold: &BigUint,
+    max_refunded_amount_per_hour: &BigUint,
+) -> Result<(), String> {
+    if !max_refunded_amount_per_hour.is_zero() && approval_threshold > max_refunded_amount_per_hour
+    {
+        return Err(format!(
+            "The refund approval threshold {} exceeds the max refunded amount per hour {}",
+            approval_threshold, max_refunded_amount_per_hour
+        ));
+    }
+    Ok(())
+}
 
-    assert!(
-        price % id_space == 0,
-        "The price per token may overlap with request id"
-    )
+// The payment for the most expensive request must not be set aside as the out of range one
+fn validate_max_payment_amount(
+    max_payment_amount: &BigUint,
+    price: i64,
+    max_tokens_per_request: u8,
+    id_digits: u8,
+) -> Result<(), String> {
+    let id_space = BigUint::from(10u32).pow(id_digits.into());
+    let max_price = BigUint::from(price as u64) * max_tokens_per_request;
+    if *max_payment_amount < max_price.clone() + id_space {
+        return Err(format!(
+            "The max payment amount {} is less than the price of the largest request {} with the id added",
+            max_payment_amount, max_price
+        ));
+    }
+    Ok(())
+}
+
+// Parses `<address>:<first_block>` or `<address>:<first_block>:<last_block>`,
+// the address without the last block is not retired yet
+fn parse_payment_address(s: &str) -> Result<PaymentAddressWindow, String> {
+    let parts: Vec<_> = s.trim().split(':').collect();
+    if parts.len() != 2 && parts.len() != 3 {
+        return Err(format!(
+            "Expected `<address>:<first_block>[:<last_block>]`, got `{}`",
+            s
+        ));
+    }
+
+    let address = parts[0]
+        .trim_start_matches("0x")
+        .parse()
+        .map_err(|err| format!("Invalid payment address `{}`: {}", parts[0], err))?;
+    let first_block: u64 = parts[1]
+        .parse()
+        .map_err(|err| format!("Invalid first block `{}`: {}", parts[1], err))?;
+    let last_block = match parts.get(2) {
+        Some(last_block) => {
+            let last_block: u64 = last_block
+                .parse()
+                .map_err(|err| format!("Invalid last block `{}`: {}", last_block, err))?;
+            if first_block > last_block {
+                return Err(format!(
+                    "The first block {} is greater than the last block {}",
+                    first_block, last_block
+                ));
+            }
+            Some(last_block)
+        }
+        None => None,
+    };
+
+    Ok(PaymentAddressWindow {
+        address,
+        first_block,
+        last_block,
+    })
+}
+
+// The payments have to be accepted to some address all the time,
+// so the newest address, which is advertised to the payers, may not be retired
+fn validate_payment_addresses(windows: &[PaymentAddressWindow]) -> Result<(), String> {
+    for (i, window) in windows.iter().enumerate() {
+        if windows[..i].iter().any(|w| w.address == window.address) {
+            return Err(format!(
+                "The payment address {:?} is configured more than once",
+                window.address
+            ));
+        }
+    }
+    if let Some(newest) = newest_payment_address(windows) {
+        if let Some(last_block) = newest.last_block {
+            return Err(format!(
+                "The newest payment address {:?} is retired at the block {}",
+                newest.address, last_block
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn newest_payment_address(windows: &[PaymentAddressWindow]) -> Option<&PaymentAddressWindow> {
+    windows.iter().max_by_key(|window| window.first_block)
+}
+
+fn parse_payment_addresses(value: &str) -> Vec<PaymentAddressWindow> {
+    let windows = value
+        .split(',')
+        .filter(|window| !window.trim().is_empty())
+        .map(|window| {
+            parse_payment_address(window)
+                .unwrap_or_else(|err| panic!("Invalid forced exit payment address: {}", err))
+        })
+        .collect::<Vec<_>>();
+    validate_payment_addresses(&windows)
+        .unwrap_or_else(|err| panic!("Invalid forced exit payment addresses: {}", err));
+    windows
+}
+
+fn parse_allowed_tokens(value: &str) -> Vec<TokenId> {
+    value
+        .split(',')
+        .filter(|token| !token.trim().is_empty())
+        .map(|token| {
+            token
+                .trim()
+                .parse()
+                .map(TokenId)
+                .unwrap_or_else(|err| panic!("Invalid forced exit token `{}`: {}", token, err))
+        })
+        .collect()
+}
+
+// Parses `<start>|<end>|<reason>` or `<start>|<end>|<reason>|<recurrence>`,
+// the times are in RFC 3339 and the recurrence is either `daily` or `weekly`
+fn parse_maintenance_window(s: &str) -> Result<MaintenanceWindow, String> {
+    let parts: Vec<_> = s.trim().split('|').collect();
+    if parts.len() != 3 && parts.len() != 4 {
+        return Err(format!(
+            "Expected `<start>|<end>|<reason>[|<recurrence>]`, got `{}`",
+            s
+        ));
+    }
+
+    let parse_time = |time: &str| {
+        DateTime::parse_from_rfc3339(time)
+            .map(|time| time.with_timezone(&Utc))
+            .map_err(|err| format!("Invalid maintenance time `{}`: {}", time, err))
+    };
+    let start = parse_time(parts[0])?;
+    let end = parse_time(parts[1])?;
+    if start >= end {
+        return Err(format!(
+            "The maintenance starting at {} ends at {}",
+            start, end
+        ));
+    }
+    let recurrence = match parts.get(3) {
+        Some(recurrence) => {
+            let recurrence: MaintenanceRecurrence = recurrence
+                .parse()
+                .map_err(|recurrence| format!("Invalid recurrence `{}`", recurrence))?;
+            Some(recurrence)
+        }
+        None => None,
+    };
+    // Otherwise the next occurrence starts before the previous one is over
+    if let Some(recurrence) = recurrence {
+        if end - start >= recurrence.period() {
+            return Err(format!(
+                "The {:?} maintenance starting at {} lasts longer than its period",
+                recurrence, start
+            ));
+        }
+    }
+
+    Ok(MaintenanceWindow {
+        start,
+        end,
+        reason: parts[2].to_owned(),
+        recurrence,
+    })
+}
+
+fn parse_maintenance_windows(value: &str) -> Vec<MaintenanceWindow> {
+    value
+        .split(',')
+        .filter(|window| !window.trim().is_empty())
+        .map(|window| {
+            parse_maintenance_window(window)
+                .unwrap_or_else(|err| panic!("Invalid forced exit maintenance window: {}", err))
+        })
+        .collect()
+}
+
+// The alert is supposed to precede the refusals
+fn validate_id_space_utilization(alert: u8, max: u8) -> Result<(), String> {
+    if alert > max || max > 100 {
+        return Err(format!(
+            "Invalid id space utilization limits: the alert at {}%, the refusals at {}%",
+            alert, max
+        ));
+    }
+    Ok(())
+}
+
+// The list is stored as comma-separated keys, an empty list is an empty string
+fn parse_additional_senders(value: &str) -> Vec<ForcedExitSenderKey> {
+    value
+        .split(',')
+        .filter(|sender| !sender.trim().is_empty())
+        .map(|sender| {
+            sender
+                .parse()
+                .unwrap_or_else(|err| panic!("Invalid additional forced exit sender: {}", err))
+        })
+        .collect()
+}
+
+// The list is stored as comma-separated deployments, an empty list is an empty string
+fn parse_legacy_contracts(value: &str) -> Vec<ForcedExitContractDeployment> {
+    value
+        .split(',')
+        .filter(|deployment| !deployment.trim().is_empty())
+        .map(|deployment| {
+            deployment
+                .parse()
+                .unwrap_or_else(|err| panic!("Invalid legacy forced exit contract: {}", err))
+        })
+        .collect()
 }
 
 impl ForcedExitRequestsConfig {
@@ -71,11 +752,41 @@ impl ForcedExitRequestsConfig {
         let max_tx_interval: f64 =
             (config.recomended_tx_interval as f64) * config.tx_interval_scaling_factor;
 
-        validate_price_with_id_space(config.price_per_token, config.digits_in_id);
-
-        ForcedExitRequestsConfig {
+        let max_payment_amount = config
+            .max_payment_amount
+            .parse()
+            .unwrap_or_else(|err| panic!("Invalid max payment amount: {}", err));
+        let refund_approval_threshold = config
+            .refund_approval_threshold
+            .parse()
+            .unwrap_or_else(|err| panic!("Invalid refund approval threshold: {}", err));
+        let max_refunded_amount_per_hour = config
+            .max_refunded_amount_per_hour
+            .parse()
+            .unwrap_or_else(|err| panic!("Invalid max refunded amount per hour: {}", err));
+        let min_sender_balance = config
+            .min_sender_balance
+            .parse()
+            .unwrap_or_else(|err| panic!("Invalid min sender balance: {}", err));
+        let max_batch_fee = config
+            .max_batch_fee
+            .parse()
+            .unwrap_or_else(|err| panic!("Invalid max batch fee: {}", err));
+        let active_target_policy = config
+            .active_target_policy
+            .parse()
+            .unwrap_or_else(|policy| panic!("Invalid active target policy `{}`", policy));
+        let singleton_mode = config
+            .singleton_mode
+            .parse()
+            .unwrap_or_else(|mode| panic!("Invalid singleton mode `{}`", mode));
+        let config = ForcedExitRequestsConfig {
             enabled: config.enabled,
             max_tokens_per_request: config.max_tokens_per_request,
+            max_metadata_length: config.max_metadata_length,
+            max_active_requests_per_target: config.max_active_requests_per_target,
+            allowed_tokens: parse_allowed_tokens(&config.allowed_tokens),
+            max_requests_per_hour: config.max_requests_per_hour,
             recomended_tx_interval: config.recomended_tx_interval,
             max_tx_interval: max_tx_interval.round() as i64,
             digits_in_id: config.digits_in_id,
@@ -84,13 +795,717 @@ impl ForcedExitRequestsConfig {
             sender_private_key: config.sender_private_key,
             sender_eth_private_key: config.sender_eth_private_key,
             sender_account_address: config.sender_account_address,
+            eth_sign_forced_exits: config.eth_sign_forced_exits,
+            additional_senders: parse_additional_senders(&config.additional_senders),
+            external_signer_url: config.external_signer_url,
+            external_signer_auth_token: config.external_signer_auth_token,
+            external_signer_timeout: config.external_signer_timeout,
+            external_signer_attempts: config.external_signer_attempts,
             expiration_period: config.expiration_period,
+            expiration_grace_period: config.expiration_grace_period,
+            expiration_sweep_interval: config.expiration_sweep_interval,
+            expiration_sweep_batch_size: config.expiration_sweep_batch_size,
             blocks_check_amount: config.blocks_check_amount,
             eth_node_poll_interval: config.eth_node_poll_interval,
+            legacy_contracts: parse_legacy_contracts(&config.legacy_contracts),
+            payment_addresses: parse_payment_addresses(&config.payment_addresses),
+            l1_escalation_enabled: config.l1_escalation_enabled,
+            l1_escalation_failures_threshold: config.l1_escalation_failures_threshold,
+            webhook_url: config.webhook_url,
+            callbacks_enabled: config.callbacks_enabled,
+            callback_max_attempts: config.callback_max_attempts,
+            runtime_threads: config.runtime_threads,
+            remote_api_url: config.remote_api_url,
+            read_replica_pool_size: config.read_replica_pool_size,
+            id_space_alert_utilization: config.id_space_alert_utilization,
+            id_space_max_utilization: config.id_space_max_utilization,
+            l1_payments_enabled: config.l1_payments_enabled,
+            admin_payments_enabled: config.admin_payments_enabled,
+            max_payment_amount,
+            startup_reconciliation_timeout: config.startup_reconciliation_timeout,
+            reconciliation_interval: config.reconciliation_interval,
+            reconciliation_jitter: config.reconciliation_jitter,
+            active_target_policy,
+            active_target_hold_period: config.active_target_hold_period,
+            singleton_mode,
+            token_cache_max_staleness: config.token_cache_max_staleness,
+            legacy_fulfilled_by_enabled: config.legacy_fulfilled_by_enabled,
+            receipt_poll_interval: config.receipt_poll_interval,
+            receipt_poll_batch_size: config.receipt_poll_batch_size,
+            processing_workers: config.processing_workers,
+            aggregation_window: config.aggregation_window,
+            max_aggregated_requests: config.max_aggregated_requests,
+            max_batch_size: config.max_batch_size,
+            l1_transfer_check_web3_url: config.l1_transfer_check_web3_url,
+            l1_transfer_check_timeout: config.l1_transfer_check_timeout,
+            sender_creation_pending_timeout: config.sender_creation_pending_timeout,
+            sender_address_unknown_timeout: config.sender_address_unknown_timeout,
+            metrics_max_token_labels: config.metrics_max_token_labels,
+            expected_payment_wait_confirmations: config.expected_payment_wait_confirmations,
+            tx_commit_timeout: config.tx_commit_timeout,
+            forced_exit_tx_ttl: config.forced_exit_tx_ttl,
+            shutdown_drain_timeout: config.shutdown_drain_timeout,
+            maintenance_windows: parse_maintenance_windows(&config.maintenance_windows),
+            maintenance_lead_time: config.maintenance_lead_time,
+            processing_attempts: config.processing_attempts,
+            processing_retry_base_delay: config.processing_retry_base_delay,
+            processing_retry_max_delay: config.processing_retry_max_delay,
+            overpayment_tolerance: config.overpayment_tolerance,
+            overpayment_tolerance_percent: config.overpayment_tolerance_percent,
+            refunds_enabled: config.refunds_enabled,
+            refund_processing_fee: config.refund_processing_fee,
+            refund_approval_threshold,
+            max_refunded_amount_per_hour,
+            min_sender_balance,
+            fee_token: config.fee_token.map(TokenId),
+            fee_multiplier_percent: config.fee_multiplier_percent,
+            fee_bump_percent: config.fee_bump_percent,
+            max_batch_fee,
+            legacy_amount_ids_enabled: config.legacy_amount_ids_enabled,
+            max_batches_per_minute: config.max_batches_per_minute,
+            batches_per_block: config.batches_per_block,
+        };
+        config
+            .validate()
+            .unwrap_or_else(|err| panic!("Invalid forced exit requests config: {}", err));
+        config
+    }
+
+    /// Checks that the values of the config can be used together.
+    pub fn validate(&self) -> Result<(), String> {
+        validate_digits_in_id(self.digits_in_id)?;
+        validate_price_with_id_space(self.price_per_token, amount_id_digits(self.digits_in_id))?;
+        validate_id_space_utilization(
+            self.id_space_alert_utilization,
+            self.id_space_max_utilization,
+        )?;
+        if self.overpayment_tolerance_percent > 100 {
+            return Err(format!(
+                "Invalid overpayment tolerance: {}%",
+                self.overpayment_tolerance_percent
+            ));
+        }
+        validate_max_payment_amount(
+            &self.max_payment_amount,
+            self.price_per_token,
+            self.max_tokens_per_request,
+            amount_id_digits(self.digits_in_id),
+        )?;
+        validate_refund_limits(
+            &self.refund_approval_threshold,
+            &self.max_refunded_amount_per_hour,
+        )?;
+        if self.fee_multiplier_percent < 100 {
+            return Err(format!(
+                "Invalid fee multiplier: {}%, the quoted fee can not be reduced",
+                self.fee_multiplier_percent
+            ));
+        }
+        if self.fee_bump_percent <= 100 {
+            return Err(format!(
+                "Invalid fee bump: {}%, the rejected fee must be raised",
+                self.fee_bump_percent
+            ));
+        }
+        if self.processing_workers == 0 {
+            return Err("At least one processing worker is required".to_owned());
+        }
+        if self.max_aggregated_requests == 0 {
+            return Err("At least one request must be allowed in a batch".to_owned());
+        }
+        // The batch paying its fee with a transfer has to fit a `ForcedExit` besides it
+        let min_batch_size = if self.fee_token.is_some() { 2 } else { 1 };
+        if self.max_batch_size < min_batch_size {
+            return Err(format!(
+                "Invalid max batch size {}, at least {} transactions are required",
+                self.max_batch_size, min_batch_size
+            ));
+        }
+        validate_forced_exit_tx_ttl(self.forced_exit_tx_ttl)?;
+        // The transactions which can not be executed anymore are not awaited
+        if self.tx_commit_timeout > self.forced_exit_tx_ttl {
+            return Err(format!(
+                "The commit timeout {}ms exceeds the ttl of the ForcedExit transactions {}ms",
+                self.tx_commit_timeout, self.forced_exit_tx_ttl
+            ));
+        }
+        if self.eth_sign_forced_exits {
+            // The Ethereum keys of the additional senders are not known
+            if !self.additional_senders.is_empty() {
+                return Err(
+                    "The Ethereum signatures are only supported for the main sender account"
+                        .to_owned(),
+                );
+            }
+            let address =
+                PackedEthSignature::address_from_private_key(&self.sender_eth_private_key)
+                    .map_err(|err| format!("Invalid sender Ethereum private key: {}", err))?;
+            if address != self.sender_account_address {
+                return Err(format!(
+                    "The sender Ethereum private key belongs to {:?}, not to the sender account {:?}",
+                    address, self.sender_account_address
+                ));
+            }
+        }
+        let mut senders = HashSet::new();
+        senders.insert(self.sender_account_address);
+        for sender in &self.additional_senders {
+            if !senders.insert(sender.address) {
+                return Err(format!(
+                    "The sender account {:?} is listed twice",
+                    sender.address
+                ));
+            }
+        }
+        // The keys are not used at all with the external signer
+        if self.external_signer_url.is_none() {
+            if self.sender_private_key.is_empty() {
+                return Err(
+                    "Either the sender private key or the external signer is required".to_owned(),
+                );
+            }
+            if let Some(sender) = self
+                .additional_senders
+                .iter()
+                .find(|sender| sender.private_key.is_none())
+            {
+                return Err(format!(
+                    "The private key of the sender {:?} is required without the external signer",
+                    sender.address
+                ));
+            }
+        }
+        if self.external_signer_attempts == 0 {
+            return Err(
+                "At least one attempt to sign with the external signer is required".to_owned(),
+            );
+        }
+        Ok(())
+    }
+
+    /// The current values of all the keys the candidate may change, see `with_candidate`.
+    pub fn as_candidate(&self) -> ForcedExitConfigCandidate {
+        ForcedExitConfigCandidate {
+            price_per_token: Some(self.price_per_token),
+            digits_in_id: Some(self.digits_in_id),
+            max_tokens_per_request: Some(self.max_tokens_per_request),
+            max_payment_amount: Some(self.max_payment_amount.clone().into()),
+            max_metadata_length: Some(self.max_metadata_length),
+            max_active_requests_per_target: Some(self.max_active_requests_per_target),
+            allowed_tokens: Some(self.allowed_tokens.clone()),
+            overpayment_tolerance: Some(self.overpayment_tolerance),
+            overpayment_tolerance_percent: Some(self.overpayment_tolerance_percent),
+            expiration_grace_period: Some(self.expiration_grace_period),
+            legacy_amount_ids_enabled: Some(self.legacy_amount_ids_enabled),
+        }
+    }
+
+    /// The config with the keys set by the candidate replaced, validated the same way
+    /// as the one loaded from the environment. The config itself is not changed.
+    pub fn with_candidate(&self, candidate: &ForcedExitConfigCandidate) -> Result<Self, String> {
+        let mut config = self.clone();
+        if let Some(price_per_token) = candidate.price_per_token {
+            config.price_per_token = price_per_token;
+        }
+        if let Some(digits_in_id) = candidate.digits_in_id {
+            config.digits_in_id = digits_in_id;
+        }
+        if let Some(max_tokens_per_request) = candidate.max_tokens_per_request {
+            config.max_tokens_per_request = max_tokens_per_request;
+        }
+        if let Some(max_payment_amount) = &candidate.max_payment_amount {
+            config.max_payment_amount = max_payment_amount.0.clone();
+        }
+        if let Some(max_metadata_length) = candidate.max_metadata_length {
+            config.max_metadata_length = max_metadata_length;
+        }
+        if let Some(max_active_requests_per_target) = candidate.max_active_requests_per_target {
+            config.max_active_requests_per_target = max_active_requests_per_target;
+        }
+        if let Some(allowed_tokens) = &candidate.allowed_tokens {
+            config.allowed_tokens = allowed_tokens.clone();
+        }
+        if let Some(overpayment_tolerance) = candidate.overpayment_tolerance {
+            config.overpayment_tolerance = overpayment_tolerance;
+        }
+        if let Some(overpayment_tolerance_percent) = candidate.overpayment_tolerance_percent {
+            config.overpayment_tolerance_percent = overpayment_tolerance_percent;
+        }
+        if let Some(expiration_grace_period) = candidate.expiration_grace_period {
+            config.expiration_grace_period = expiration_grace_period;
+        }
+        if let Some(legacy_amount_ids_enabled) = candidate.legacy_amount_ids_enabled {
+            config.legacy_amount_ids_enabled = legacy_amount_ids_enabled;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Whether the payments of the source are processed unless the operators have said otherwise.
+    pub fn payment_source_enabled(&self, source: PaymentSource) -> bool {
+        match source {
+            PaymentSource::L1Event => self.l1_payments_enabled,
+            PaymentSource::Admin => self.admin_payments_enabled,
+        }
+    }
+
+    /// Whether the optional part of the component is enabled.
+    pub fn feature_enabled(&self, feature: ForcedExitFeature) -> bool {
+        match feature {
+            ForcedExitFeature::L1Escalation => self.l1_escalation_enabled,
+            ForcedExitFeature::Webhooks => self.webhook_url.is_some(),
+            ForcedExitFeature::Overpayments => {
+                self.overpayment_tolerance > 0 || self.overpayment_tolerance_percent > 0
+            }
+            ForcedExitFeature::Refunds => self.refunds_enabled,
+            ForcedExitFeature::Callbacks => self.callbacks_enabled,
+        }
+    }
+
+    /// The optional parts of the component which are enabled, reported along with the status.
+    pub fn enabled_features(&self) -> Vec<ForcedExitFeature> {
+        ForcedExitFeature::ALL
+            .iter()
+            .copied()
+            .filter(|feature| self.feature_enabled(*feature))
+            .collect()
+    }
+
+    /// The address the payers are asked to send the payments to: the newest
+    /// of the payment addresses, or the forced exit contract if there are none.
+    pub fn active_payment_address(&self, contract: Address) -> Address {
+        newest_payment_address(&self.payment_addresses)
+            .map(|window| window.address)
+            .unwrap_or(contract)
+    }
+
+    /// The rotation schedule of the payment address, the forced exit contract
+    /// is accepted all the time if the address was never rotated.
+    pub fn payment_address_schedule(&self, contract: Address) -> Vec<PaymentAddressWindow> {
+        if self.payment_addresses.is_empty() {
+            vec![PaymentAddressWindow {
+                address: contract,
+                first_block: 0,
+                last_block: None,
+            }]
+        } else {
+            self.payment_addresses.clone()
+        }
+    }
+
+    /// Whether the payment to the address at the given block is outside of the validity
+    /// window of the address. The addresses not listed in the schedule are never retired.
+    pub fn is_payment_address_retired(&self, address: Address, block: u64) -> bool {
+        self.payment_addresses
+            .iter()
+            .find(|window| window.address == address)
+            .map_or(false, |window| !window.contains(block))
+    }
+
+    /// The pacing of the sender unless the operators have overridden it.
+    pub fn pacing(&self) -> ForcedExitPacing {
+        ForcedExitPacing {
+            max_batches_per_minute: self.max_batches_per_minute,
+            batches_per_block: self.batches_per_block,
         }
     }
 
     pub fn poll_interval(&self) -> Duration {
         Duration::from_millis(self.eth_node_poll_interval)
     }
+
+    pub fn startup_reconciliation_timeout(&self) -> Duration {
+        Duration::from_millis(self.startup_reconciliation_timeout)
+    }
+
+    /// `None` if the requests are only reconciled on startup and when left in flight.
+    pub fn reconciliation_interval(&self) -> Option<Duration> {
+        (self.reconciliation_interval > 0)
+            .then(|| Duration::from_millis(self.reconciliation_interval))
+    }
+
+    pub fn active_target_hold_period(&self) -> Duration {
+        Duration::from_millis(self.active_target_hold_period)
+    }
+
+    pub fn token_cache_max_staleness(&self) -> Duration {
+        Duration::from_millis(self.token_cache_max_staleness)
+    }
+
+    pub fn receipt_poll_interval(&self) -> Duration {
+        Duration::from_millis(self.receipt_poll_interval)
+    }
+
+    pub fn tx_commit_timeout(&self) -> Duration {
+        Duration::from_millis(self.tx_commit_timeout)
+    }
+
+    pub fn forced_exit_tx_ttl(&self) -> Duration {
+        Duration::from_millis(self.forced_exit_tx_ttl)
+    }
+
+    pub fn aggregation_window(&self) -> Duration {
+        Duration::from_millis(self.aggregation_window)
+    }
+
+    pub fn shutdown_drain_timeout(&self) -> Duration {
+        Duration::from_millis(self.shutdown_drain_timeout)
+    }
+
+    pub fn l1_transfer_check_timeout(&self) -> Duration {
+        Duration::from_millis(self.l1_transfer_check_timeout)
+    }
+
+    pub fn external_signer_timeout(&self) -> Duration {
+        Duration::from_millis(self.external_signer_timeout)
+    }
+
+    pub fn sender_creation_pending_timeout(&self) -> Duration {
+        Duration::from_millis(self.sender_creation_pending_timeout)
+    }
+
+    pub fn sender_address_unknown_timeout(&self) -> Duration {
+        Duration::from_millis(self.sender_address_unknown_timeout)
+    }
+
+    /// The confirmations the pre-registered payments wait for, at most the usual ones.
+    pub fn expected_payment_confirmations(&self) -> u64 {
+        self.expected_payment_wait_confirmations
+            .min(self.wait_confirmations)
+    }
+
+    pub fn expiration_grace_period(&self) -> chrono::Duration {
+        chrono::Duration::milliseconds(self.expiration_grace_period as i64)
+    }
+
+    pub fn expiration_sweep_interval(&self) -> Duration {
+        Duration::from_millis(self.expiration_sweep_interval)
+    }
+
+    pub fn maintenance_lead_time(&self) -> chrono::Duration {
+        chrono::Duration::milliseconds(self.maintenance_lead_time as i64)
+    }
+
+    /// The limits the new requests are checked against, reported to the clients as well.
+    pub fn creation_limits(&self) -> CreationLimits {
+        CreationLimits {
+            max_tokens_per_request: self.max_tokens_per_request,
+            max_validity: self.max_tx_interval,
+            max_metadata_length: self.max_metadata_length,
+            max_active_per_target: self.max_active_requests_per_target,
+            allowed_tokens: self.allowed_tokens.clone(),
+        }
+    }
+
+    /// The part of the config the pricing and the matching of the requests depend on.
+    pub fn pipeline_config(&self) -> ForcedExitPipelineConfig {
+        ForcedExitPipelineConfig {
+            price_per_token: self.price_per_token,
+            max_tokens_per_request: self.max_tokens_per_request,
+            digits_in_id: self.digits_in_id,
+            max_payment_amount: self.max_payment_amount.clone(),
+            wait_confirmations: self.wait_confirmations,
+            expected_payment_wait_confirmations: self.expected_payment_wait_confirmations,
+            active_target_policy: self.active_target_policy,
+            active_target_hold_period: self.active_target_hold_period,
+            l1_escalation_enabled: self.l1_escalation_enabled,
+            l1_escalation_failures_threshold: self.l1_escalation_failures_threshold,
+            overpayment_tolerance: self.overpayment_tolerance,
+            overpayment_tolerance_percent: self.overpayment_tolerance_percent,
+            legacy_amount_ids_enabled: self.legacy_amount_ids_enabled,
+        }
+    }
+
+    /// The part of the config the amounts paid by the users depend on, see `ForcedExitMoneyConfig`.
+    pub fn money_config(&self, contract: Address) -> ForcedExitMoneyConfig {
+        ForcedExitMoneyConfig {
+            price_per_token: self.price_per_token,
+            digits_in_id: self.digits_in_id,
+            validity_period: self.creation_limits().max_validity,
+            payment_address: self.active_payment_address(contract),
+            overpayment_tolerance: self.overpayment_tolerance,
+            overpayment_tolerance_percent: self.overpayment_tolerance_percent,
+            refund_processing_fee: self.refund_processing_fee,
+            legacy_amount_ids_enabled: self.legacy_amount_ids_enabled,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configs::test_utils::addr;
+
+    #[test]
+    fn parse_legacy_contracts_list() {
+        assert_eq!(parse_legacy_contracts(""), vec![]);
+
+        let contracts = parse_legacy_contracts(
+            "0x9c7AeE886D6FcFc14e37784f143a6dAccEf50Db7:1:100:200,\
+             0x1963917ba0b44A879cf6248387C1d51A0F11669d:2:150:300",
+        );
+        assert_eq!(
+            contracts,
+            vec![
+                ForcedExitContractDeployment {
+                    address: addr("9c7AeE886D6FcFc14e37784f143a6dAccEf50Db7"),
+                    version: 1,
+                    first_block: 100,
+                    last_block: 200,
+                },
+                ForcedExitContractDeployment {
+                    address: addr("1963917ba0b44A879cf6248387C1d51A0F11669d"),
+                    version: 2,
+                    first_block: 150,
+                    last_block: 300,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_allowed_tokens_list() {
+        assert_eq!(parse_allowed_tokens(""), vec![]);
+        assert_eq!(
+            parse_allowed_tokens("0, 2,15"),
+            vec![TokenId(0), TokenId(2), TokenId(15)]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid forced exit token")]
+    fn parse_invalid_allowed_tokens() {
+        parse_allowed_tokens("0,ETH");
+    }
+
+    #[test]
+    fn parse_payment_addresses_schedule() {
+        assert_eq!(parse_payment_addresses(""), vec![]);
+
+        let windows = parse_payment_addresses(
+            "0x9c7AeE886D6FcFc14e37784f143a6dAccEf50Db7:0:200,\
+             0x1963917ba0b44A879cf6248387C1d51A0F11669d:150",
+        );
+        assert_eq!(
+            windows,
+            vec![
+                PaymentAddressWindow {
+                    address: addr("9c7AeE886D6FcFc14e37784f143a6dAccEf50Db7"),
+                    first_block: 0,
+                    last_block: Some(200),
+                },
+                PaymentAddressWindow {
+                    address: addr("1963917ba0b44A879cf6248387C1d51A0F11669d"),
+                    first_block: 150,
+                    last_block: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_payment_addresses() {
+        let old = "0x9c7AeE886D6FcFc14e37784f143a6dAccEf50Db7";
+        let new = "0x1963917ba0b44A879cf6248387C1d51A0F11669d";
+        for window in &[
+            old.to_string(),
+            format!("{}:200:100", old),
+            format!("{}:one", old),
+            format!("{}:1:2:3", old),
+        ] {
+            assert!(parse_payment_address(window).is_err());
+        }
+
+        let windows = |schedule: &str| {
+            schedule
+                .split(',')
+                .map(|window| parse_payment_address(window).unwrap())
+                .collect::<Vec<_>>()
+        };
+        // The newest address is retired
+        assert!(
+            validate_payment_addresses(&windows(&format!("{}:0,{}:150:300", old, new))).is_err()
+        );
+        // The address is listed twice
+        assert!(
+            validate_payment_addresses(&windows(&format!("{}:0:200,{}:150", old, old))).is_err()
+        );
+        validate_payment_addresses(&windows(&format!("{}:0:200,{}:150", old, new))).unwrap();
+    }
+
+    #[test]
+    fn parse_maintenance_schedule() {
+        assert_eq!(parse_maintenance_windows(""), vec![]);
+
+        let time = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        let windows = parse_maintenance_windows(
+            "2022-09-01T03:00:00Z|2022-09-01T04:00:00Z|Database migration,\
+             2022-09-04T01:00:00+02:00|2022-09-04T01:30:00+02:00|Backups|weekly",
+        );
+        assert_eq!(
+            windows,
+            vec![
+                MaintenanceWindow {
+                    start: time("2022-09-01T03:00:00Z"),
+                    end: time("2022-09-01T04:00:00Z"),
+                    reason: "Database migration".to_owned(),
+                    recurrence: None,
+                },
+                MaintenanceWindow {
+                    start: time("2022-09-03T23:00:00Z"),
+                    end: time("2022-09-03T23:30:00Z"),
+                    reason: "Backups".to_owned(),
+                    recurrence: Some(MaintenanceRecurrence::Weekly),
+                },
+            ]
+        );
+
+        for window in &[
+            "2022-09-01T03:00:00Z|2022-09-01T04:00:00Z",
+            "2022-09-01T04:00:00Z|2022-09-01T03:00:00Z|Migration",
+            "2022-09-01|2022-09-02|Migration",
+            "2022-09-01T03:00:00Z|2022-09-01T04:00:00Z|Migration|monthly",
+            "2022-09-01T03:00:00Z|2022-09-02T04:00:00Z|Migration|daily",
+        ] {
+            assert!(parse_maintenance_window(window).is_err());
+        }
+    }
+
+    #[test]
+    fn digits_in_id_limits() {
+        validate_digits_in_id(1).unwrap();
+        validate_digits_in_id(MAX_DIGITS_IN_ID as u8).unwrap();
+        assert!(validate_digits_in_id(0).is_err());
+        assert!(validate_digits_in_id(MAX_DIGITS_IN_ID as u8 + 1).is_err());
+    }
+
+    #[test]
+    fn forced_exit_tx_ttl_limits() {
+        validate_forced_exit_tx_ttl(600_000).unwrap();
+        validate_forced_exit_tx_ttl(MAX_FORCED_EXIT_TX_TTL).unwrap();
+        assert!(validate_forced_exit_tx_ttl(0).is_err());
+        assert!(validate_forced_exit_tx_ttl(MAX_FORCED_EXIT_TX_TTL + 1).is_err());
+        // Would overflow the timestamps of the time range
+        assert!(validate_forced_exit_tx_ttl(u64::MAX).is_err());
+    }
+
+    #[test]
+    fn aligned_price() {
+        validate_price_with_id_space(30_000_000_000_000_000, 13).unwrap();
+        validate_price_with_id_space(0, 13).unwrap();
+        validate_price_with_id_space(12_000, 3).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "may overlap with request id")]
+    fn misaligned_price() {
+        // The price of the default config with the lowest digit changed
+        validate_price_with_id_space(30_000_000_000_000_001, 13).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "may overlap with request id")]
+    fn price_below_id_space() {
+        validate_price_with_id_space(1_000_000_000, 13).unwrap();
+    }
+
+    #[test]
+    fn id_space_utilization_limits() {
+        validate_id_space_utilization(50, 80).unwrap();
+        validate_id_space_utilization(100, 100).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid id space utilization limits")]
+    fn alert_after_refusals() {
+        validate_id_space_utilization(90, 80).unwrap();
+    }
+
+    #[test]
+    fn max_payment_amount_limits() {
+        // The default config
+        let max_payment_amount = BigUint::from(10u32).pow(24);
+        validate_max_payment_amount(&max_payment_amount, 30_000_000_000_000_000, 10, 13).unwrap();
+        // The largest request with the largest id is still in range
+        let max_payment_amount = BigUint::from(300_000_000_000_000_000u64 + 10_000_000_000_000);
+        validate_max_payment_amount(&max_payment_amount, 30_000_000_000_000_000, 10, 13).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "is less than the price of the largest request")]
+    fn max_payment_amount_below_price() {
+        let max_payment_amount = BigUint::from(300_000_000_000_000_000u64);
+        validate_max_payment_amount(&max_payment_amount, 30_000_000_000_000_000, 10, 13).unwrap();
+    }
+
+    #[test]
+    fn refund_limits() {
+        let ether = BigUint::from(10u32).pow(18);
+        validate_refund_limits(&(&ether / 10u32), &ether).unwrap();
+        validate_refund_limits(&ether, &ether).unwrap();
+        // The refunds are not limited at all
+        validate_refund_limits(&ether, &BigUint::zero()).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds the max refunded amount per hour")]
+    fn refund_approval_threshold_above_hourly_limit() {
+        let ether = BigUint::from(10u32).pow(18);
+        validate_refund_limits(&(&ether * 2u32), &ether).unwrap();
+    }
+
+    #[test]
+    fn parse_additional_senders_list() {
+        assert_eq!(parse_additional_senders(""), vec![]);
+
+        let key = format!("0x{}", "ab".repeat(32));
+        let senders = parse_additional_senders(&format!(
+            "0x9c7AeE886D6FcFc14e37784f143a6dAccEf50Db7:{key}, \
+             0x1963917ba0b44A879cf6248387C1d51A0F11669d:{key}",
+            key = key
+        ));
+        assert_eq!(
+            senders,
+            vec![
+                ForcedExitSenderKey {
+                    address: addr("9c7AeE886D6FcFc14e37784f143a6dAccEf50Db7"),
+                    private_key: Some(key.clone()),
+                },
+                ForcedExitSenderKey {
+                    address: addr("1963917ba0b44A879cf6248387C1d51A0F11669d"),
+                    private_key: Some(key.clone()),
+                },
+            ]
+        );
+        // The accounts signed by the external signer are listed without the keys
+        assert_eq!(
+            parse_additional_senders("0x9c7AeE886D6FcFc14e37784f143a6dAccEf50Db7"),
+            vec![ForcedExitSenderKey {
+                address: addr("9c7AeE886D6FcFc14e37784f143a6dAccEf50Db7"),
+                private_key: None,
+            }]
+        );
+
+        for sender in &[
+            "0x9c7AeE886D6FcFc14e37784f143a6dAccEf50Db7:".to_string(),
+            format!("0x9c7AeE886D6FcFc14e37784f143a6dAccEf50Db7:{}", &key[2..]),
+            format!("0x9c7AeE886D6FcFc14e37784f143a6dAccEf50Db7:{}", &key[..64]),
+            format!("0x9c7A:{}", key),
+        ] {
+            assert!(sender.parse::<ForcedExitSenderKey>().is_err());
+        }
+    }
+
+    #[test]
+    fn parse_invalid_deployment() {
+        let address = "0x9c7AeE886D6FcFc14e37784f143a6dAccEf50Db7";
+        for deployment in &[
+            format!("{}:1:100", address),
+            format!("{}:one:100:200", address),
+            format!("{}:1:200:100", address),
+            "0x9c7A:1:100:200".to_string(),
+        ] {
+            assert!(deployment.parse::<ForcedExitContractDeployment>().is_err());
+        }
+    }
 }
@@ -0,0 +1,383 @@
+//! Deduplication of the alerts sent through the notifier.
+//!
+//! A single outage makes every affected operation fail the same way, and an alert per
+//! failure would flood the channel until it is muted. So the alerts of the same kind are
+//! coalesced: the first one goes out right away, the ones following it within the window
+//! are counted and sent as a single message once the window is over, and the messages of
+//! a kind are never sent more often than its minimum interval. The critical alerts are
+//! neither coalesced nor throttled, they always go out immediately.
+//!
+//! The state is kept in memory for a bounded number of kinds, the pending alerts of
+//! the kind evicted to make room for a new one are sent right away.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AlertSeverity {
+    Info,
+    Warning,
+    /// Sent immediately, regardless of the alerts of the same kind sent before.
+    Critical,
+}
+
+impl fmt::Display for AlertSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Critical => "critical",
+        };
+        write!(f, "{}", severity)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alert {
+    /// The alerts of the same kind are coalesced, e.g. `forced_exit_commit_timeout`.
+    pub kind: String,
+    pub severity: AlertSeverity,
+    pub text: String,
+}
+
+impl Alert {
+    pub fn new(kind: impl Into<String>, severity: AlertSeverity, text: impl Into<String>) -> Self {
+        Self {
+            kind: kind.into(),
+            severity,
+            text: text.into(),
+        }
+    }
+}
+
+/// The message sent for one or several alerts of the same kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlertMessage {
+    pub kind: String,
+    /// The highest severity of the coalesced alerts.
+    pub severity: AlertSeverity,
+    /// The number of the alerts coalesced into the message.
+    pub count: u64,
+    /// The text of the last of the coalesced alerts.
+    pub text: String,
+}
+
+impl AlertMessage {
+    fn single(alert: Alert) -> Self {
+        Self {
+            kind: alert.kind,
+            severity: alert.severity,
+            count: 1,
+            text: alert.text,
+        }
+    }
+}
+
+impl fmt::Display for AlertMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.count == 1 {
+            write!(f, "[{}] {}", self.severity, self.text)
+        } else {
+            write!(
+                f,
+                "[{}] {} `{}` alerts, the last one: {}",
+                self.severity, self.count, self.kind, self.text
+            )
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AlertPolicy {
+    /// How long the alerts following the sent one are coalesced before they are sent.
+    pub window: Duration,
+    /// The minimum interval between the messages of the same kind.
+    pub min_interval: Duration,
+    /// The minimum intervals of the kinds which are throttled differently.
+    pub kind_min_intervals: HashMap<String, Duration>,
+    /// The maximum number of the kinds the state is kept for.
+    pub max_kinds: usize,
+}
+
+impl Default for AlertPolicy {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(60),
+            min_interval: Duration::from_secs(5 * 60),
+            kind_min_intervals: HashMap::new(),
+            max_kinds: 256,
+        }
+    }
+}
+
+impl AlertPolicy {
+    fn min_interval(&self, kind: &str) -> Duration {
+        self.kind_min_intervals
+            .get(kind)
+            .copied()
+            .unwrap_or(self.min_interval)
+    }
+}
+
+#[derive(Debug)]
+struct PendingAlerts {
+    count: u64,
+    first_at: Instant,
+    severity: AlertSeverity,
+    last: Alert,
+}
+
+impl PendingAlerts {
+    fn into_message(self) -> AlertMessage {
+        AlertMessage {
+            kind: self.last.kind,
+            severity: self.severity,
+            count: self.count,
+            text: self.last.text,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct KindState {
+    last_sent: Option<Instant>,
+    pending: Option<PendingAlerts>,
+}
+
+#[derive(Debug)]
+pub struct AlertDeduplicator {
+    policy: AlertPolicy,
+    kinds: HashMap<String, KindState>,
+}
+
+impl AlertDeduplicator {
+    pub fn new(policy: AlertPolicy) -> Self {
+        Self {
+            policy,
+            kinds: HashMap::new(),
+        }
+    }
+
+    /// Returns the messages to be sent right away for the alert, none if it is coalesced.
+    pub fn push(&mut self, alert: Alert, now: Instant) -> Vec<AlertMessage> {
+        if alert.severity == AlertSeverity::Critical {
+            metrics::increment_counter!("notifier.alerts_sent", "kind" => alert.kind.clone());
+            return vec![AlertMessage::single(alert)];
+        }
+
+        let mut messages = Vec::new();
+        if !self.kinds.contains_key(&alert.kind) && self.kinds.len() >= self.policy.max_kinds {
+            messages.extend(self.evict());
+        }
+        let min_interval = self.policy.min_interval(&alert.kind);
+        let state = self.kinds.entry(alert.kind.clone()).or_default();
+        let throttled = state.last_sent.map_or(false, |last_sent| {
+            now.duration_since(last_sent) < min_interval
+        });
+
+        if state.pending.is_none() && !throttled {
+            state.last_sent = Some(now);
+            metrics::increment_counter!("notifier.alerts_sent", "kind" => alert.kind.clone());
+            messages.push(AlertMessage::single(alert));
+            return messages;
+        }
+
+        metrics::increment_counter!("notifier.alerts_suppressed", "kind" => alert.kind.clone());
+        match &mut state.pending {
+            Some(pending) => {
+                pending.count += 1;
+                pending.severity = pending.severity.max(alert.severity);
+                pending.last = alert;
+            }
+            None => {
+                state.pending = Some(PendingAlerts {
+                    count: 1,
+                    first_at: now,
+                    severity: alert.severity,
+                    last: alert,
+                })
+            }
+        }
+        messages
+    }
+
+    /// Returns the messages of the coalesced alerts, the window and the minimum interval
+    /// of which are over. Expected to be called periodically, at least once per window.
+    pub fn flush(&mut self, now: Instant) -> Vec<AlertMessage> {
+        let policy = &self.policy;
+        let mut messages = Vec::new();
+        for (kind, state) in self.kinds.iter_mut() {
+            let due = match (&state.pending, state.last_sent) {
+                (Some(pending), last_sent) => {
+                    now.duration_since(pending.first_at) >= policy.window
+                        && last_sent.map_or(true, |last_sent| {
+                            now.duration_since(last_sent) >= policy.min_interval(kind)
+                        })
+                }
+                (None, _) => false,
+            };
+            if due {
+                state.last_sent = Some(now);
+                let message = state.pending.take().unwrap().into_message();
+                metrics::increment_counter!("notifier.alerts_sent", "kind" => kind.clone());
+                messages.push(message);
+            }
+        }
+        // The kinds which are not throttled anymore are no different from the ones never seen
+        self.kinds.retain(|kind, state| {
+            state.pending.is_some()
+                || state.last_sent.map_or(false, |last_sent| {
+                    now.duration_since(last_sent) < policy.min_interval(kind)
+                })
+        });
+        messages.sort_by_key(|message| std::cmp::Reverse(message.severity));
+        messages
+    }
+
+    /// Drops the state of the kind sent the longest ago, returns its pending alerts.
+    fn evict(&mut self) -> Option<AlertMessage> {
+        let kind = self
+            .kinds
+            .iter()
+            .min_by_key(|(_, state)| state.last_sent)
+            .map(|(kind, _)| kind.clone())?;
+        let state = self.kinds.remove(&kind)?;
+        metrics::increment_counter!("notifier.alert_kinds_evicted");
+        state.pending.map(PendingAlerts::into_message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_secs(60);
+    const MIN_INTERVAL: Duration = Duration::from_secs(300);
+
+    fn deduplicator(max_kinds: usize) -> AlertDeduplicator {
+        AlertDeduplicator::new(AlertPolicy {
+            window: WINDOW,
+            min_interval: MIN_INTERVAL,
+            kind_min_intervals: vec![("slow".to_string(), Duration::from_secs(600))]
+                .into_iter()
+                .collect(),
+            max_kinds,
+        })
+    }
+
+    fn timeout(request_id: u64) -> Alert {
+        Alert::new(
+            "commit_timeout",
+            AlertSeverity::Warning,
+            format!("Request {} is not committed in time", request_id),
+        )
+    }
+
+    fn sent(messages: &[AlertMessage]) -> Vec<(&str, u64, AlertSeverity)> {
+        messages
+            .iter()
+            .map(|message| (message.kind.as_str(), message.count, message.severity))
+            .collect()
+    }
+
+    #[test]
+    fn bursts_are_coalesced() {
+        let mut alerts = deduplicator(10);
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        // The first alert of the burst goes out, the rest are counted
+        assert_eq!(
+            sent(&alerts.push(timeout(1), at(0))),
+            vec![("commit_timeout", 1, AlertSeverity::Warning)]
+        );
+        for request_id in 2..=100 {
+            assert!(alerts.push(timeout(request_id), at(1)).is_empty());
+        }
+        // The critical alert is not held back by the burst
+        let mismatch = Alert::new("key_mismatch", AlertSeverity::Critical, "Key mismatch");
+        assert_eq!(
+            sent(&alerts.push(mismatch.clone(), at(2))),
+            vec![("key_mismatch", 1, AlertSeverity::Critical)]
+        );
+        assert_eq!(alerts.push(mismatch, at(3)).len(), 1);
+
+        // The window is over, but the kind is still throttled
+        assert!(alerts.flush(at(61)).is_empty());
+        let messages = alerts.flush(at(300));
+        assert_eq!(
+            sent(&messages),
+            vec![("commit_timeout", 99, AlertSeverity::Warning)]
+        );
+        assert_eq!(
+            messages[0].to_string(),
+            "[warning] 99 `commit_timeout` alerts, the last one: Request 100 is not committed in time"
+        );
+        assert!(alerts.flush(at(400)).is_empty());
+
+        // The next burst is coalesced since the message has just been sent
+        assert!(alerts.push(timeout(101), at(500)).is_empty());
+        assert!(alerts.flush(at(560)).is_empty());
+        assert_eq!(
+            sent(&alerts.flush(at(600))),
+            vec![("commit_timeout", 1, AlertSeverity::Warning)]
+        );
+
+        // Once the kind is not throttled anymore, the alert goes out right away
+        assert_eq!(alerts.push(timeout(102), at(900)).len(), 1);
+    }
+
+    #[test]
+    fn kinds_are_throttled_separately() {
+        let mut alerts = deduplicator(10);
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let slow = |severity| Alert::new("slow", severity, "Slow fulfillment");
+
+        assert_eq!(alerts.push(timeout(1), at(0)).len(), 1);
+        assert_eq!(alerts.push(slow(AlertSeverity::Info), at(0)).len(), 1);
+        assert!(alerts.push(timeout(2), at(10)).is_empty());
+        assert!(alerts.push(slow(AlertSeverity::Info), at(10)).is_empty());
+        assert!(alerts.push(slow(AlertSeverity::Warning), at(20)).is_empty());
+
+        // The slow kind has the longer interval, the coalesced severity is the highest one
+        assert_eq!(
+            sent(&alerts.flush(at(300))),
+            vec![("commit_timeout", 1, AlertSeverity::Warning)]
+        );
+        assert_eq!(
+            sent(&alerts.flush(at(600))),
+            vec![("slow", 2, AlertSeverity::Warning)]
+        );
+    }
+
+    #[test]
+    fn state_is_bounded() {
+        let mut alerts = deduplicator(2);
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let alert = |kind: &str| Alert::new(kind, AlertSeverity::Warning, kind);
+
+        assert_eq!(alerts.push(alert("first"), at(0)).len(), 1);
+        assert!(alerts.push(alert("first"), at(1)).is_empty());
+        assert_eq!(alerts.push(alert("second"), at(2)).len(), 1);
+
+        // The kind sent the longest ago makes room for the new one, its pending alerts go out
+        assert_eq!(
+            sent(&alerts.push(alert("third"), at(3))),
+            vec![
+                ("first", 1, AlertSeverity::Warning),
+                ("third", 1, AlertSeverity::Warning)
+            ]
+        );
+        assert_eq!(alerts.kinds.len(), 2);
+        // The evicted kind is not throttled anymore, the next one to be evicted has nothing pending
+        assert_eq!(
+            sent(&alerts.push(alert("first"), at(4))),
+            vec![("first", 1, AlertSeverity::Warning)]
+        );
+    }
+}
@@ -1,20 +1,31 @@
+use std::{sync::Mutex, time::Instant};
+
+use alerts::{Alert, AlertDeduplicator, AlertMessage, AlertPolicy};
 use matter_most_notifier::MatterMostNotifier;
 use reqwest::Url;
 use zksync_types::tokens::Token;
 
+pub mod alerts;
 mod matter_most_notifier;
 
 pub struct Notifier {
     matter_most_notifier: MatterMostNotifier,
+    alerts: Mutex<AlertDeduplicator>,
 }
 
 impl Notifier {
     pub fn with_mattermost(webhook_url: Url) -> Self {
         Self {
             matter_most_notifier: MatterMostNotifier::new(webhook_url),
+            alerts: Mutex::new(AlertDeduplicator::new(AlertPolicy::default())),
         }
     }
 
+    pub fn with_alert_policy(mut self, policy: AlertPolicy) -> Self {
+        self.alerts = Mutex::new(AlertDeduplicator::new(policy));
+        self
+    }
+
     pub async fn send_new_token_notify(&self, token: Token) -> anyhow::Result<()> {
         let token_info_msg = format!(
             "New token: id = {}, address = {}, symbol = {}, decimals = {}",
@@ -26,4 +37,29 @@ impl Notifier {
 
         Ok(())
     }
+
+    /// Sends the alert, unless it is coalesced with the alerts of the same kind sent before.
+    pub async fn send_alert(&self, alert: Alert) -> anyhow::Result<()> {
+        let messages = self.lock_alerts().push(alert, Instant::now());
+        self.send_alert_messages(messages).await
+    }
+
+    /// Sends the coalesced alerts which are due, expected to be called periodically.
+    pub async fn flush_alerts(&self) -> anyhow::Result<()> {
+        let messages = self.lock_alerts().flush(Instant::now());
+        self.send_alert_messages(messages).await
+    }
+
+    fn lock_alerts(&self) -> std::sync::MutexGuard<'_, AlertDeduplicator> {
+        self.alerts.lock().expect("Failed to get the alerts lock")
+    }
+
+    async fn send_alert_messages(&self, messages: Vec<AlertMessage>) -> anyhow::Result<()> {
+        for message in messages {
+            self.matter_most_notifier
+                .send_notify("alerts_bot", &message.to_string())
+                .await?;
+        }
+        Ok(())
+    }
 }
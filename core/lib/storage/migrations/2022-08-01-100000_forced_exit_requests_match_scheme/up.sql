@@ -0,0 +1,2 @@
+-- The way the payment was matched against the request, see `PaymentMatchScheme`
+ALTER TABLE forced_exit_requests ADD COLUMN match_scheme TEXT;
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS forced_exit_requests_escalations;
+DROP TABLE IF EXISTS forced_exit_requests_failures;
@@ -0,0 +1,16 @@
+-- Permanent failures of the L2 ForcedExit transactions per token of the request
+CREATE TABLE forced_exit_requests_failures (
+    request_id BIGINT NOT NULL REFERENCES forced_exit_requests(id) ON DELETE CASCADE,
+    token_id INTEGER NOT NULL,
+    failures_count INTEGER NOT NULL,
+    PRIMARY KEY (request_id, token_id)
+);
+
+-- Requests that have to be fulfilled with the FullExit priority operations on L1
+CREATE TABLE forced_exit_requests_escalations (
+    request_id BIGINT PRIMARY KEY REFERENCES forced_exit_requests(id) ON DELETE CASCADE,
+    full_exits JSONB NOT NULL,
+    created_at TIMESTAMP with time zone NOT NULL,
+    l1_tx_hash TEXT,
+    finalized_at TIMESTAMP with time zone
+);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS forced_exit_requests_api_key_usages;
+DROP TABLE IF EXISTS forced_exit_requests_api_keys;
@@ -0,0 +1,18 @@
+-- Keys of the trusted partners, which override the limits of the forced exit requests
+CREATE TABLE forced_exit_requests_api_keys (
+    id BIGSERIAL PRIMARY KEY,
+    label TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    max_tokens_per_request INTEGER,
+    max_requests_per_hour INTEGER,
+    created_at TIMESTAMP with time zone NOT NULL,
+    revoked_at TIMESTAMP with time zone
+);
+
+-- Requests created with the API keys
+CREATE TABLE forced_exit_requests_api_key_usages (
+    request_id BIGINT PRIMARY KEY REFERENCES forced_exit_requests(id) ON DELETE CASCADE,
+    api_key_id BIGINT NOT NULL REFERENCES forced_exit_requests_api_keys(id)
+);
+CREATE INDEX forced_exit_requests_api_key_usages_api_key_id_idx
+    ON forced_exit_requests_api_key_usages (api_key_id);
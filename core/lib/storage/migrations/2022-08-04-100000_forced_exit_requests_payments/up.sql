@@ -0,0 +1,14 @@
+-- Payments to the forced exit contract in the order they were processed,
+-- the payments rescanned after the reorgs are recorded once per processing
+CREATE TABLE forced_exit_requests_payments (
+    id BIGSERIAL PRIMARY KEY,
+    amount NUMERIC NOT NULL,
+    request_id BIGINT,
+    block_number BIGINT NOT NULL,
+    eth_tx_hash TEXT,
+    payer TEXT,
+    received_at TIMESTAMP with time zone NOT NULL
+);
+
+CREATE INDEX forced_exit_requests_payments_block_number_idx
+    ON forced_exit_requests_payments (block_number);
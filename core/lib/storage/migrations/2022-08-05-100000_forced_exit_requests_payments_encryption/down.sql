@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS forced_exit_requests_payments_payer_hash_idx;
+ALTER TABLE forced_exit_requests_payments DROP COLUMN IF EXISTS payer_hash;
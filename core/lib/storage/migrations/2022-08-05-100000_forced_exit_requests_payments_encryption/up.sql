@@ -0,0 +1,5 @@
+-- The payers may be encrypted, the payments are looked up by the HMAC of the address instead
+ALTER TABLE forced_exit_requests_payments ADD COLUMN payer_hash TEXT;
+
+CREATE INDEX forced_exit_requests_payments_payer_hash_idx
+    ON forced_exit_requests_payments (payer_hash);
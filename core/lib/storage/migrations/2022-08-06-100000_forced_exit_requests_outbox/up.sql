@@ -0,0 +1,15 @@
+-- Notifications about the status transitions of the requests, which are inserted
+-- within the same transaction as the transitions and delivered by the dispatcher
+CREATE TABLE forced_exit_requests_outbox (
+    id BIGSERIAL PRIMARY KEY,
+    request_id BIGINT NOT NULL,
+    event TEXT NOT NULL,
+    created_at TIMESTAMP with time zone NOT NULL,
+    attempts INT NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP with time zone NOT NULL,
+    delivered_at TIMESTAMP with time zone,
+    last_error TEXT
+);
+
+CREATE INDEX forced_exit_requests_outbox_pending_idx
+    ON forced_exit_requests_outbox (next_attempt_at) WHERE delivered_at IS NULL;
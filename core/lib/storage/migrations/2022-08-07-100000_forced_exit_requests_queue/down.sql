@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS forced_exit_requests_fulfilled_at_idx;
+DROP INDEX IF EXISTS forced_exit_requests_queue_idx;
+ALTER TABLE forced_exit_requests DROP COLUMN IF EXISTS matched_at;
@@ -0,0 +1,7 @@
+-- The paid requests are processed in the order they were matched with the payments
+ALTER TABLE forced_exit_requests ADD COLUMN matched_at TIMESTAMP with time zone;
+
+CREATE INDEX forced_exit_requests_queue_idx
+    ON forced_exit_requests (matched_at, id) WHERE fulfilled_at IS NULL AND matched_at IS NOT NULL;
+CREATE INDEX forced_exit_requests_fulfilled_at_idx
+    ON forced_exit_requests (fulfilled_at);
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS forced_exit_requests_injected_payments;
+DROP TABLE IF EXISTS forced_exit_requests_payment_sources;
+ALTER TABLE forced_exit_requests_payments DROP COLUMN IF EXISTS source;
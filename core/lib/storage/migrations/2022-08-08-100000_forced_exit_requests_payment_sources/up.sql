@@ -0,0 +1,20 @@
+-- All the payments recorded before are the events of the forced exit contract
+ALTER TABLE forced_exit_requests_payments ADD COLUMN source TEXT NOT NULL DEFAULT 'l1_event';
+
+-- The payment sources enabled or disabled by the operators at runtime,
+-- the sources without a row follow the config
+CREATE TABLE forced_exit_requests_payment_sources (
+    source TEXT PRIMARY KEY,
+    enabled BOOLEAN NOT NULL,
+    updated_at TIMESTAMP with time zone NOT NULL
+);
+
+-- Payments injected by the operators, which are yet to be processed by the watcher
+CREATE TABLE forced_exit_requests_injected_payments (
+    id BIGSERIAL PRIMARY KEY,
+    amount NUMERIC NOT NULL,
+    request_id BIGINT,
+    block_number BIGINT NOT NULL,
+    eth_tx_hash TEXT,
+    created_at TIMESTAMP with time zone NOT NULL
+);
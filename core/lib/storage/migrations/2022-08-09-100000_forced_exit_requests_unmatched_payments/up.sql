@@ -0,0 +1,12 @@
+-- Payments set aside before the matching, e.g. the ones with the amounts no request could cost.
+-- The amount is stored as text, since it may not fit into the numeric column
+CREATE TABLE forced_exit_requests_unmatched_payments (
+    id BIGSERIAL PRIMARY KEY,
+    amount TEXT NOT NULL,
+    request_id BIGINT,
+    block_number BIGINT NOT NULL,
+    eth_tx_hash TEXT,
+    source TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    received_at TIMESTAMP with time zone NOT NULL
+);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS forced_exit_requests_unmatched_payments_eth_tx_hash_idx;
+DROP INDEX IF EXISTS forced_exit_requests_payments_eth_tx_hash_idx;
+DROP TABLE IF EXISTS forced_exit_requests_payment_matches;
@@ -0,0 +1,13 @@
+-- The payment transactions the requests were matched with. A single transaction may pay
+-- for several requests, while a request may be matched once again after a failure
+CREATE TABLE forced_exit_requests_payment_matches (
+    eth_tx_hash TEXT NOT NULL,
+    request_id BIGINT NOT NULL REFERENCES forced_exit_requests(id) ON DELETE CASCADE,
+    matched_at TIMESTAMP with time zone NOT NULL,
+    PRIMARY KEY (eth_tx_hash, request_id)
+);
+
+CREATE INDEX forced_exit_requests_payments_eth_tx_hash_idx
+    ON forced_exit_requests_payments (eth_tx_hash);
+CREATE INDEX forced_exit_requests_unmatched_payments_eth_tx_hash_idx
+    ON forced_exit_requests_unmatched_payments (eth_tx_hash);
@@ -0,0 +1,15 @@
+-- The paid requests, the targets of which have set the signing key before the requests
+-- were fulfilled. The failed ones keep the payment to be refunded
+CREATE TABLE forced_exit_requests_active_targets (
+    request_id BIGINT PRIMARY KEY REFERENCES forced_exit_requests(id) ON DELETE CASCADE,
+    policy TEXT NOT NULL,
+    target_nonce BIGINT NOT NULL,
+    payment_amount NUMERIC NOT NULL,
+    payment_tx_hash TEXT,
+    match_scheme TEXT NOT NULL,
+    paid_at TIMESTAMP with time zone NOT NULL,
+    detected_at TIMESTAMP with time zone NOT NULL,
+    hold_until TIMESTAMP with time zone,
+    resumed_at TIMESTAMP with time zone,
+    failed_at TIMESTAMP with time zone
+);
@@ -0,0 +1 @@
+ALTER TABLE forced_exit_requests DROP COLUMN IF EXISTS pay_exactly;
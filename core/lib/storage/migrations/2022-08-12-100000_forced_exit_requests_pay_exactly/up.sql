@@ -0,0 +1,4 @@
+-- The exact amount to be paid for the request as a decimal string: the price with the id added
+ALTER TABLE forced_exit_requests ADD COLUMN pay_exactly TEXT;
+UPDATE forced_exit_requests SET pay_exactly = TRUNC(price_in_wei + id)::TEXT;
+ALTER TABLE forced_exit_requests ALTER COLUMN pay_exactly SET NOT NULL;
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS forced_exit_requests_outbox_sequence_idx;
+ALTER TABLE forced_exit_requests_outbox DROP COLUMN IF EXISTS sequence;
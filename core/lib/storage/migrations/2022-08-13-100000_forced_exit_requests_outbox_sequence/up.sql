@@ -0,0 +1,14 @@
+-- The number of the notification among the ones of the same request. It is assigned
+-- on insert under the lock of the request, so the notifications recorded concurrently
+-- are strictly ordered even if they have the same timestamps
+ALTER TABLE forced_exit_requests_outbox ADD COLUMN sequence BIGINT;
+UPDATE forced_exit_requests_outbox SET sequence = numbered.sequence
+FROM (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY request_id ORDER BY id) AS sequence
+    FROM forced_exit_requests_outbox
+) AS numbered
+WHERE forced_exit_requests_outbox.id = numbered.id;
+ALTER TABLE forced_exit_requests_outbox ALTER COLUMN sequence SET NOT NULL;
+
+CREATE UNIQUE INDEX forced_exit_requests_outbox_sequence_idx
+    ON forced_exit_requests_outbox (request_id, sequence);
@@ -0,0 +1,3 @@
+UPDATE forced_exit_requests SET pay_exactly = TRUNC(price_in_wei + id)::TEXT
+    WHERE pay_exactly IS NULL;
+ALTER TABLE forced_exit_requests ALTER COLUMN pay_exactly SET NOT NULL;
@@ -0,0 +1,4 @@
+-- The servers preceding the `pay_exactly` column keep storing the requests without it
+-- until they are replaced. Such rows are read with the amount computed on the fly and
+-- are upgraded in batches by the server once it starts
+ALTER TABLE forced_exit_requests ALTER COLUMN pay_exactly DROP NOT NULL;
@@ -0,0 +1,7 @@
+-- The estimations of the outcome of fulfilling the backlog of the paid requests,
+-- kept for the operators to compare them with what has actually happened
+CREATE TABLE forced_exit_requests_backlog_reports (
+    id BIGSERIAL PRIMARY KEY,
+    created_at TIMESTAMP with time zone NOT NULL,
+    report JSONB NOT NULL
+);
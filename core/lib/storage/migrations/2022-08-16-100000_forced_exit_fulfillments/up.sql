@@ -0,0 +1,13 @@
+-- The ForcedExit transactions sent to fulfill the requests, one per token of the request.
+-- Replaces the comma-separated `forced_exit_requests.fulfilled_by` column, which is still
+-- written for the servers preceding the table unless disabled. The requests sent before
+-- the table was created are backfilled by the server.
+CREATE TABLE forced_exit_fulfillments (
+    request_id BIGINT NOT NULL REFERENCES forced_exit_requests(id) ON DELETE CASCADE,
+    -- The position of the token in the request
+    position INT NOT NULL,
+    token INT NOT NULL,
+    tx_hash TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (request_id, position)
+);
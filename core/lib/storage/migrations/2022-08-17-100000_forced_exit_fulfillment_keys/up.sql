@@ -0,0 +1,13 @@
+-- The fulfillments started for the paid requests, recorded before the ForcedExit transactions
+-- are sent. A paid request is fulfilled at most once at a time: the key is unique, so a second
+-- fulfillment for the same payment is refused by the database whatever code path starts it.
+-- The key is removed once the transactions fail, so the request can be sent again.
+CREATE TABLE forced_exit_fulfillment_keys (
+    request_id BIGINT NOT NULL REFERENCES forced_exit_requests(id) ON DELETE CASCADE,
+    -- The L1 transaction the request was paid with, NULL if not known
+    payment_eth_tx_hash TEXT,
+    started_at TIMESTAMPTZ NOT NULL
+);
+-- The payments of unknown transactions share the key of the request
+CREATE UNIQUE INDEX forced_exit_fulfillment_keys_key
+    ON forced_exit_fulfillment_keys (request_id, COALESCE(payment_eth_tx_hash, ''));
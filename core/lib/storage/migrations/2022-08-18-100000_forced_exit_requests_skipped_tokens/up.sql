@@ -0,0 +1,9 @@
+-- The tokens of the paid requests, which are not withdrawn while the rest of their tokens are.
+-- A token skipped again, e.g. when the request is resumed, keeps a single row
+CREATE TABLE forced_exit_requests_skipped_tokens (
+    request_id BIGINT NOT NULL REFERENCES forced_exit_requests(id) ON DELETE CASCADE,
+    token INTEGER NOT NULL,
+    reason TEXT NOT NULL,
+    skipped_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (request_id, token)
+);
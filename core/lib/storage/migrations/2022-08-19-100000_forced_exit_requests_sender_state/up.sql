@@ -0,0 +1,8 @@
+-- The state of the ForcedExit sender account as last observed by the starting service,
+-- reported by the health check while the service waits for the account to be committed
+CREATE TABLE forced_exit_requests_sender_state (
+    address TEXT PRIMARY KEY,
+    state TEXT NOT NULL,
+    -- When the account has entered the state
+    since TIMESTAMPTZ NOT NULL
+);
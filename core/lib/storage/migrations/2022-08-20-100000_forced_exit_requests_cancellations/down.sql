@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS forced_exit_requests_cancellations;
+ALTER TABLE forced_exit_requests DROP COLUMN IF EXISTS cancellation;
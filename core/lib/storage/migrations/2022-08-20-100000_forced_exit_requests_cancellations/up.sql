@@ -0,0 +1,13 @@
+-- The kind of the last cancellation of the request, the requests cancelled by the users,
+-- the operators or upon expiry are not processed again until extended
+ALTER TABLE forced_exit_requests ADD COLUMN cancellation TEXT;
+
+-- Every cancellation of the request, in the order they happened
+CREATE TABLE forced_exit_requests_cancellations (
+    id BIGSERIAL PRIMARY KEY,
+    request_id BIGINT NOT NULL REFERENCES forced_exit_requests(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    cancelled_at TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX forced_exit_requests_cancellations_request_id_idx
+    ON forced_exit_requests_cancellations (request_id);
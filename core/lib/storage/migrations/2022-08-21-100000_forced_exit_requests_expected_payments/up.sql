@@ -0,0 +1,11 @@
+-- The L1 transactions declared by the payers before sending them, matched with the requests by their hashes.
+-- A transaction pays for a single request, so the hash is the key
+CREATE TABLE forced_exit_requests_expected_payments (
+    eth_tx_hash TEXT PRIMARY KEY,
+    request_id BIGINT NOT NULL REFERENCES forced_exit_requests(id) ON DELETE CASCADE,
+    registered_at TIMESTAMPTZ NOT NULL,
+    -- The amount actually paid, if it differs from the one the request asks for
+    mismatched_amount TEXT
+);
+CREATE INDEX forced_exit_requests_expected_payments_request_id_idx
+    ON forced_exit_requests_expected_payments (request_id);
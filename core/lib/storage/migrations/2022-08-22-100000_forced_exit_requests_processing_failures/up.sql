@@ -0,0 +1,7 @@
+-- The last payment for the request which could not be processed in any of the attempts
+CREATE TABLE forced_exit_requests_processing_failures (
+    request_id BIGINT PRIMARY KEY REFERENCES forced_exit_requests(id) ON DELETE CASCADE,
+    attempts INTEGER NOT NULL,
+    error TEXT NOT NULL,
+    failed_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,2 @@
+ALTER TABLE forced_exit_requests_outbox DROP COLUMN IF EXISTS pipeline_version;
+DROP TABLE IF EXISTS forced_exit_requests_pipeline_versions;
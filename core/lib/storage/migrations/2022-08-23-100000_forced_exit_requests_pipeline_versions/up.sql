@@ -0,0 +1,13 @@
+-- The version of the pipeline and the hash of its config the request was handled with at the stage,
+-- so it is known which rules were applied to the request
+CREATE TABLE forced_exit_requests_pipeline_versions (
+    request_id BIGINT NOT NULL REFERENCES forced_exit_requests(id) ON DELETE CASCADE,
+    stage TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    config_hash TEXT NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (request_id, stage)
+);
+
+-- The version of the pipeline the transition was made by, unknown for the older notifications
+ALTER TABLE forced_exit_requests_outbox ADD COLUMN pipeline_version INTEGER;
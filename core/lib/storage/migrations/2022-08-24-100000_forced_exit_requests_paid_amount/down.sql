@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS forced_exit_requests_pay_exactly_amount;
+ALTER TABLE forced_exit_requests DROP COLUMN IF EXISTS paid_amount;
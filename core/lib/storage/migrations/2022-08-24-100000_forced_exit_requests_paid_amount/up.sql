@@ -0,0 +1,7 @@
+-- The amount the request was actually paid for with, which exceeds `pay_exactly`
+-- for the requests overpaid within the tolerance. Not known for the requests paid before
+ALTER TABLE forced_exit_requests ADD COLUMN paid_amount NUMERIC;
+
+-- The overpaid requests are looked up by the closest amount to be paid below the paid one
+CREATE INDEX forced_exit_requests_pay_exactly_amount
+    ON forced_exit_requests ((COALESCE(pay_exactly::NUMERIC, price_in_wei + id)));
@@ -0,0 +1,19 @@
+-- The payments returned to the payers, at most one refund per payment.
+-- The requests are not referenced, the expired ones are deleted while their refunds are kept
+CREATE TABLE forced_exit_refunds (
+    id BIGSERIAL PRIMARY KEY,
+    request_id BIGINT NOT NULL,
+    payment_tx_hash TEXT NOT NULL UNIQUE,
+    recipient TEXT NOT NULL,
+    amount NUMERIC NOT NULL,
+    fee NUMERIC NOT NULL,
+    reason TEXT NOT NULL,
+    status TEXT NOT NULL,
+    -- The last transfer sent for the refund
+    tx_hash TEXT,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX forced_exit_refunds_request_id_idx ON forced_exit_refunds (request_id);
+CREATE INDEX forced_exit_refunds_status_idx ON forced_exit_refunds (status);
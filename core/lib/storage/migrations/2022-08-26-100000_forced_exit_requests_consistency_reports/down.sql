@@ -0,0 +1 @@
+DROP TABLE IF EXISTS forced_exit_requests_consistency_reports;
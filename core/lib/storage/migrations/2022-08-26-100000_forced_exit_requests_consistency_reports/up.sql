@@ -0,0 +1,7 @@
+-- The outcomes of checking the statuses of the requests against their records,
+-- kept for the operators to investigate the violations
+CREATE TABLE forced_exit_requests_consistency_reports (
+    id BIGSERIAL PRIMARY KEY,
+    created_at TIMESTAMP with time zone NOT NULL,
+    report JSONB NOT NULL
+);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS forced_exit_requests_public_id_counter;
+ALTER TABLE forced_exit_requests DROP COLUMN IF EXISTS public_id;
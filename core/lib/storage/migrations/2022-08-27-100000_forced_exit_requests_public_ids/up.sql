@@ -0,0 +1,20 @@
+-- The ids the clients refer to the requests with and the ones encoded in the payment amounts.
+-- They are allocated by the server from the counter rather than taken from the sequence of the
+-- primary key, so restoring the sequence or moving the table does not reuse the paid ids
+ALTER TABLE forced_exit_requests ADD COLUMN public_id BIGINT;
+-- The pending payments are made for the ids the requests have been created with
+UPDATE forced_exit_requests SET public_id = id;
+CREATE UNIQUE INDEX forced_exit_requests_public_id_idx ON forced_exit_requests (public_id);
+
+-- The single row with the next public id to be allocated
+CREATE TABLE forced_exit_requests_public_id_counter (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    next_public_id BIGINT NOT NULL
+);
+-- The ids taken from the sequence by the failed insertions may have been shown to the clients
+-- as well, so the counter continues after the sequence rather than after the stored requests
+INSERT INTO forced_exit_requests_public_id_counter ( next_public_id )
+    SELECT GREATEST(
+        (SELECT COALESCE(MAX(id), 0) FROM forced_exit_requests),
+        (SELECT CASE WHEN is_called THEN last_value ELSE last_value - 1 END FROM forced_exit_requests_id_seq)
+    ) + 1;
@@ -0,0 +1,12 @@
+-- The annotations left on the requests by the operators, never read by the processing.
+-- The requests are not referenced, the notes outlive the deleted ones
+CREATE TABLE forced_exit_requests_notes (
+    id BIGSERIAL PRIMARY KEY,
+    request_id BIGINT NOT NULL,
+    author TEXT NOT NULL,
+    text TEXT NOT NULL,
+    tags TEXT[] NOT NULL,
+    created_at TIMESTAMP with time zone NOT NULL
+);
+CREATE INDEX forced_exit_requests_notes_request_id_idx ON forced_exit_requests_notes (request_id);
+CREATE INDEX forced_exit_requests_notes_tags_idx ON forced_exit_requests_notes USING GIN (tags);
@@ -0,0 +1 @@
+ALTER TABLE forced_exit_requests_processing_failures DROP COLUMN IF EXISTS submission_error;
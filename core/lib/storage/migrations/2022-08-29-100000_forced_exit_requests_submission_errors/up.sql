@@ -0,0 +1,2 @@
+-- The details of the failed submission of the transactions, kept apart from the error message
+ALTER TABLE forced_exit_requests_processing_failures ADD COLUMN submission_error JSONB;
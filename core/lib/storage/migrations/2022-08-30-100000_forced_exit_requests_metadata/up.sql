@@ -0,0 +1,2 @@
+-- The text attached to the request by the client, its length is limited by the server config
+ALTER TABLE forced_exit_requests ADD COLUMN metadata TEXT;
@@ -0,0 +1,19 @@
+-- The stage of the lifecycle of the request, changed together with the records backing it
+ALTER TABLE forced_exit_requests ADD COLUMN status TEXT NOT NULL DEFAULT 'created';
+-- The existing requests get the stage their records point to, the later stages first
+UPDATE forced_exit_requests SET status = CASE
+    WHEN fulfilled_at IS NOT NULL THEN 'fulfilled'
+    WHEN id IN (
+        SELECT request_id FROM forced_exit_requests_active_targets WHERE failed_at IS NOT NULL
+    ) THEN 'failed'
+    WHEN fulfilled_by IS NOT NULL OR EXISTS (
+        SELECT 1 FROM forced_exit_fulfillments WHERE request_id = forced_exit_requests.id
+    ) OR id IN (
+        SELECT request_id FROM forced_exit_requests_escalations
+    ) THEN 'txs_sent'
+    WHEN cancellation = 'expired' THEN 'expired'
+    WHEN cancellation IN ('user_cancelled', 'operator_cancelled') THEN 'cancelled'
+    WHEN matched_at IS NOT NULL THEN 'payment_received'
+    ELSE 'created'
+END;
+CREATE INDEX forced_exit_requests_status_idx ON forced_exit_requests (status);
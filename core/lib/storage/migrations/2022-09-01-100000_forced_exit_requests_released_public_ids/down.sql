@@ -0,0 +1,13 @@
+-- The released ids may have been allocated again since then, so the public ids could not be
+-- unique anymore. The requests are kept, the rollback has to be resolved by hand instead
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM forced_exit_requests WHERE public_id_released_at IS NOT NULL) THEN
+        RAISE EXCEPTION 'Some forced exit requests have released public ids, resolve them before the rollback';
+    END IF;
+END $$;
+
+DROP TABLE IF EXISTS forced_exit_requests_released_public_ids;
+DROP INDEX IF EXISTS forced_exit_requests_public_id_idx;
+CREATE UNIQUE INDEX forced_exit_requests_public_id_idx ON forced_exit_requests (public_id);
+ALTER TABLE forced_exit_requests DROP COLUMN IF EXISTS public_id_released_at;
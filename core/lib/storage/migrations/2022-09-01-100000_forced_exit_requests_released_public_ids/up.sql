@@ -0,0 +1,14 @@
+-- The public id of the request which has not been paid for in time is released to be allocated
+-- again, the request keeps it for the history but is no longer found by it
+ALTER TABLE forced_exit_requests ADD COLUMN public_id_released_at TIMESTAMPTZ;
+DROP INDEX forced_exit_requests_public_id_idx;
+CREATE UNIQUE INDEX forced_exit_requests_public_id_idx ON forced_exit_requests (public_id)
+    WHERE public_id_released_at IS NULL;
+
+-- The released public ids, the ones released first are allocated first
+CREATE TABLE forced_exit_requests_released_public_ids (
+    public_id BIGINT PRIMARY KEY,
+    released_at TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX forced_exit_requests_released_public_ids_released_at_idx
+    ON forced_exit_requests_released_public_ids (released_at);
@@ -0,0 +1,4 @@
+-- The terms the payments for the request are matched by, fixed once the request is created
+-- so that the changes of the config do not affect the requests being paid. The requests
+-- created before are matched by the terms derived from the current config.
+ALTER TABLE forced_exit_requests ADD COLUMN payment_terms JSONB;
@@ -0,0 +1,13 @@
+-- Retries of the failed requests requested by the operators, which are picked up by the watcher
+CREATE TABLE forced_exit_requests_retries (
+    id BIGSERIAL PRIMARY KEY,
+    request_id BIGINT NOT NULL REFERENCES forced_exit_requests(id) ON DELETE CASCADE,
+    force BOOLEAN NOT NULL,
+    created_at TIMESTAMP with time zone NOT NULL,
+    processed_at TIMESTAMP with time zone,
+    tx_hashes TEXT,
+    error TEXT
+);
+
+CREATE INDEX forced_exit_requests_retries_pending_idx
+    ON forced_exit_requests_retries (id) WHERE processed_at IS NULL;
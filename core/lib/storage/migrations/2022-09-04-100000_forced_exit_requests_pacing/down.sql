@@ -0,0 +1,2 @@
+ALTER TABLE forced_exit_requests_sender_state DROP COLUMN IF EXISTS pacing;
+DROP TABLE IF EXISTS forced_exit_requests_pacing;
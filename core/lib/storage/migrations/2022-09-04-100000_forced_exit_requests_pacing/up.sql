@@ -0,0 +1,11 @@
+-- The pacing of the ForcedExit batches set by the operators at runtime,
+-- the config applies while there is no row
+CREATE TABLE forced_exit_requests_pacing (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    max_batches_per_minute BIGINT NOT NULL,
+    batches_per_block BIGINT NOT NULL,
+    updated_at TIMESTAMP with time zone NOT NULL
+);
+
+-- The pacing last reported by the service sending the transactions of the account
+ALTER TABLE forced_exit_requests_sender_state ADD COLUMN pacing JSONB;
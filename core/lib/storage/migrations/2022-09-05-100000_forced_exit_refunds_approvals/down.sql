@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS forced_exit_refunds_sent_at_idx;
+ALTER TABLE forced_exit_refunds DROP COLUMN IF EXISTS sent_at;
+ALTER TABLE forced_exit_refunds DROP COLUMN IF EXISTS approved_at;
+ALTER TABLE forced_exit_refunds DROP COLUMN IF EXISTS approved_by;
@@ -0,0 +1,6 @@
+-- The refunds above the automatic approval threshold await the operators, who they were approved by is kept
+ALTER TABLE forced_exit_refunds ADD COLUMN approved_by TEXT;
+ALTER TABLE forced_exit_refunds ADD COLUMN approved_at TIMESTAMP with time zone;
+-- The time the last transfer was sent, the hourly limit of the refunds is restored from it on restart
+ALTER TABLE forced_exit_refunds ADD COLUMN sent_at TIMESTAMP with time zone;
+CREATE INDEX forced_exit_refunds_sent_at_idx ON forced_exit_refunds (sent_at);
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS forced_exit_requests_outbox_failed_idx;
+ALTER TABLE forced_exit_requests_outbox DROP COLUMN IF EXISTS failed_at;
+ALTER TABLE forced_exit_requests_outbox DROP COLUMN IF EXISTS callback;
+ALTER TABLE forced_exit_requests_outbox DROP COLUMN IF EXISTS callback_url;
+ALTER TABLE forced_exit_requests DROP COLUMN IF EXISTS callback_url;
@@ -0,0 +1,9 @@
+-- The URL the request is posted to once fulfilled, it is not returned with the request
+ALTER TABLE forced_exit_requests ADD COLUMN callback_url TEXT;
+-- The callbacks are delivered through the outbox as well, with the body taken once the request is fulfilled.
+-- They are given up on after a number of the failed attempts, until redelivered by the operators
+ALTER TABLE forced_exit_requests_outbox ADD COLUMN callback_url TEXT;
+ALTER TABLE forced_exit_requests_outbox ADD COLUMN callback JSONB;
+ALTER TABLE forced_exit_requests_outbox ADD COLUMN failed_at TIMESTAMP with time zone;
+CREATE INDEX forced_exit_requests_outbox_failed_idx
+    ON forced_exit_requests_outbox (failed_at) WHERE failed_at IS NOT NULL;
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS forced_exit_config_history_effective_from_idx;
+DROP TABLE IF EXISTS forced_exit_config_history;
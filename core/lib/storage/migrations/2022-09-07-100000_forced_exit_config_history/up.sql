@@ -0,0 +1,9 @@
+-- The snapshots of the config the amounts paid by the users depend on, each one in effect
+-- from the time it was recorded until the next one
+CREATE TABLE forced_exit_config_history (
+    id BIGSERIAL PRIMARY KEY,
+    effective_from TIMESTAMP with time zone NOT NULL,
+    config JSONB NOT NULL
+);
+CREATE INDEX forced_exit_config_history_effective_from_idx
+    ON forced_exit_config_history (effective_from);
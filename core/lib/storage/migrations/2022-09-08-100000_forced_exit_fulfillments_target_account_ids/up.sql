@@ -0,0 +1,3 @@
+-- The id the target of the request was resolved to when the transactions were signed,
+-- unknown for the fulfillments sent or backfilled before the column was added
+ALTER TABLE forced_exit_fulfillments ADD COLUMN target_account_id BIGINT;
@@ -0,0 +1 @@
+ALTER TABLE forced_exit_fulfillments DROP COLUMN IF EXISTS submitted_at;
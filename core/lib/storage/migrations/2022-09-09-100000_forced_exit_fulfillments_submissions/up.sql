@@ -0,0 +1,6 @@
+-- The fulfillments are recorded before their transactions are submitted to the mempool and
+-- are marked as submitted once it has accepted them. The ones left unmarked by a crash are
+-- settled on the next start by whether the transactions have reached the mempool.
+ALTER TABLE forced_exit_fulfillments ADD COLUMN submitted_at TIMESTAMPTZ;
+-- The fulfillments were only recorded once submitted before
+UPDATE forced_exit_fulfillments SET submitted_at = created_at;
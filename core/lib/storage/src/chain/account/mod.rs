@@ -1,5 +1,5 @@
 // Built-in deps
-use std::time::Instant;
+use std::{collections::HashMap, time::Instant};
 // External imports
 use num::{BigUint, Zero};
 use sqlx::{types::BigDecimal, Acquire};
@@ -216,6 +216,141 @@ impl<'a, 'c> AccountSchema<'a, 'c> {
         account_state
     }
 
+    /// Loads the committed nonce of the account by its address, `None` if the account
+    /// does not exist. Unlike `account_state_by_address`, neither the balances nor the
+    /// NFTs of the account are loaded.
+    pub async fn committed_nonce_by_address(
+        &mut self,
+        address: Address,
+    ) -> QueryResult<Option<Nonce>> {
+        let start = Instant::now();
+        let mut transaction = self.0.start_transaction().await?;
+
+        let account_id = match AccountSchema(&mut transaction)
+            .account_id_by_address(address)
+            .await?
+        {
+            Some(account_id) => account_id,
+            None => return Ok(None),
+        };
+        let last_verified_block = BlockSchema(&mut transaction)
+            .get_last_verified_confirmed_block()
+            .await?
+            .0 as i64;
+
+        // The last committed update of the account sets its nonce, the deletion removes it
+        let last_update = sqlx::query!(
+            r#"
+            SELECT nonce as "nonce!", is_create as "is_create!" FROM (
+                SELECT block_number, update_order_id, new_nonce as nonce, true as is_create
+                FROM account_balance_updates
+                WHERE account_id = $1 AND block_number > $2
+                UNION ALL
+                SELECT block_number, update_order_id, new_nonce as nonce, true as is_create
+                FROM account_pubkey_updates
+                WHERE account_id = $1 AND block_number > $2
+                UNION ALL
+                SELECT block_number, update_order_id, nonce, is_create
+                FROM account_creates
+                WHERE account_id = $1 AND block_number > $2
+            ) updates
+            ORDER BY block_number DESC, update_order_id DESC
+            LIMIT 1
+            "#,
+            i64::from(*account_id),
+            last_verified_block
+        )
+        .fetch_optional(transaction.conn())
+        .await?;
+
+        let nonce = match last_update {
+            Some(update) => Some(update.nonce).filter(|_| update.is_create),
+            None => sqlx::query!(
+                "SELECT nonce FROM accounts WHERE id = $1",
+                i64::from(*account_id)
+            )
+            .fetch_optional(transaction.conn())
+            .await?
+            .map(|account| account.nonce),
+        };
+
+        transaction.commit().await?;
+        metrics::histogram!(
+            "sql.chain.account.committed_nonce_by_address",
+            start.elapsed()
+        );
+        Ok(nonce.map(|nonce| Nonce(nonce as u32)))
+    }
+
+    /// Loads the committed balances of the given tokens of the account by its address.
+    /// The tokens the account has never had the balance of are omitted, and so are all
+    /// the tokens if the account does not exist. The balances of the other tokens of
+    /// the account are not loaded, unlike with `account_state_by_address`.
+    pub async fn committed_balances_by_address(
+        &mut self,
+        address: Address,
+        tokens: &[TokenId],
+    ) -> QueryResult<HashMap<TokenId, BigUint>> {
+        let start = Instant::now();
+        let mut transaction = self.0.start_transaction().await?;
+
+        let account_id = match AccountSchema(&mut transaction)
+            .account_id_by_address(address)
+            .await?
+        {
+            Some(account_id) => account_id,
+            None => return Ok(HashMap::new()),
+        };
+        let last_verified_block = BlockSchema(&mut transaction)
+            .get_last_verified_confirmed_block()
+            .await?
+            .0 as i64;
+        let coin_ids: Vec<i32> = tokens.iter().map(|token| token.0 as i32).collect();
+
+        let verified_balances = sqlx::query!(
+            r#"
+            SELECT coin_id, balance FROM balances
+            WHERE account_id = $1 AND coin_id = ANY($2)
+            "#,
+            i64::from(*account_id),
+            &coin_ids
+        )
+        .fetch_all(transaction.conn())
+        .await?;
+        // The last update of each token committed after the verified block overrides its balance
+        let committed_balances = sqlx::query!(
+            r#"
+            SELECT DISTINCT ON (coin_id) coin_id, new_balance FROM account_balance_updates
+            WHERE account_id = $1 AND block_number > $2 AND coin_id = ANY($3)
+            ORDER BY coin_id, block_number DESC, update_order_id DESC
+            "#,
+            i64::from(*account_id),
+            last_verified_block,
+            &coin_ids
+        )
+        .fetch_all(transaction.conn())
+        .await?;
+
+        let to_biguint = |balance: BigDecimal| balance.to_bigint().unwrap().to_biguint().unwrap();
+        let balances = verified_balances
+            .into_iter()
+            .map(|record| (record.coin_id, record.balance))
+            .chain(
+                committed_balances
+                    .into_iter()
+                    .map(|record| (record.coin_id, record.new_balance)),
+            )
+            .map(|(coin_id, balance)| (TokenId(coin_id as u32), to_biguint(balance)))
+            .collect();
+
+        transaction.commit().await?;
+        metrics::histogram!(
+            "sql.chain.account.committed_balances_by_address",
+            start.elapsed()
+        );
+        Ok(balances)
+    }
+
     /// Loads the last committed (e.g. just added but no necessarily verified) state for
     /// account given its ID.
     /// Returns both verified and committed states.
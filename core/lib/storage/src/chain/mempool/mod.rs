@@ -11,7 +11,7 @@ use zksync_types::{
     block::IncompleteBlock,
     mempool::SignedTxVariant,
     tx::{TxEthSignature, TxHash},
-    AccountId, Address, BlockNumber, ExecutedOperations, ExecutedPriorityOp, ExecutedTx,
+    AccountId, Address, BlockNumber, ExecutedOperations, ExecutedPriorityOp, ExecutedTx, Nonce,
     PriorityOp, SerialId, SignedZkSyncTx, ZkSyncPriorityOp, H256,
 };
 // Local imports
@@ -588,6 +588,28 @@ impl<'a, 'c> MempoolSchema<'a, 'c> {
         Ok(size.unwrap_or(0) as u32)
     }
 
+    /// Returns the nonce following the transactions initiated by the account which are still
+    /// awaiting the execution, `None` if there are no such transactions.
+    pub async fn get_pending_nonce(&mut self, account_id: AccountId) -> QueryResult<Option<Nonce>> {
+        let start = Instant::now();
+
+        // The initiator is stored as `initiatorAccountId` for the `ForcedExit` transactions
+        let max_nonce = sqlx::query!(
+            r#"
+            SELECT MAX((tx->>'nonce')::bigint) AS "max_nonce"
+            FROM mempool_txs
+            WHERE COALESCE(tx->>'accountId', tx->>'initiatorAccountId')::bigint = $1
+            "#,
+            i64::from(*account_id)
+        )
+        .fetch_one(self.0.conn())
+        .await?
+        .max_nonce;
+
+        metrics::histogram!("sql.chain", start.elapsed(), "mempool" => "get_pending_nonce");
+        Ok(max_nonce.map(|nonce| Nonce(nonce as u32 + 1)))
+    }
+
     /// Get info about batch in mempool.
     pub async fn get_queued_batch_info(
         &mut self,
@@ -89,6 +89,45 @@ impl<'a, 'c> OperationsExtSchema<'a, 'c> {
         result
     }
 
+    /// Same as `tx_receipt`, but loads the receipts for all the hashes at once.
+    /// The transactions which have not been executed yet have no receipts.
+    pub async fn tx_receipts(&mut self, hashes: &[TxHash]) -> QueryResult<Vec<TxReceiptResponse>> {
+        let start = Instant::now();
+        let mut transaction = self.0.start_transaction().await?;
+
+        let hashes: Vec<Vec<u8>> = hashes.iter().map(|hash| hash.as_ref().to_vec()).collect();
+        let txs = sqlx::query!(
+            r#"
+            SELECT tx_hash, block_number, success, fail_reason FROM executed_transactions
+            WHERE tx_hash = ANY($1)
+            "#,
+            &hashes
+        )
+        .fetch_all(transaction.conn())
+        .await?;
+        let last_verified_block = transaction
+            .chain()
+            .block_schema()
+            .get_last_verified_confirmed_block()
+            .await?;
+        transaction.commit().await?;
+
+        let receipts = txs
+            .into_iter()
+            .map(|tx| TxReceiptResponse {
+                tx_hash: hex::encode(&tx.tx_hash),
+                block_number: tx.block_number,
+                success: tx.success,
+                verified: tx.block_number <= i64::from(*last_verified_block),
+                fail_reason: tx.fail_reason,
+                prover_run: None,
+            })
+            .collect();
+
+        metrics::histogram!("sql.chain.operations_ext.tx_receipts", start.elapsed());
+        Ok(receipts)
+    }
+
     pub async fn tx_receipt_api_v02(&mut self, hash: &[u8]) -> QueryResult<Option<Receipt>> {
         let start = Instant::now();
         let mut transaction = self.0.start_transaction().await?;
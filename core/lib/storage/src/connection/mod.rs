@@ -8,7 +8,7 @@ use sqlx::{Connection, Error as SqlxError, PgConnection};
 use tokio::time;
 // Local imports
 // use self::recoverable_connection::RecoverableConnection;
-use crate::{get_database_replica_url, get_database_url, StorageProcessor};
+use crate::{get_database_replica_url, get_database_url, QueryResult, StorageProcessor};
 use zksync_utils::parse_env;
 
 pub mod holder;
@@ -104,6 +104,21 @@ impl ConnectionPool {
         Ok(StorageProcessor::from_pool(connection))
     }
 
+    /// Same as `access_storage`, but the connection is not retried and the failure
+    /// to get it is returned as an error, e.g. for the callers which may fall back
+    /// to another database.
+    pub async fn try_access_storage(&self) -> QueryResult<StorageProcessor<'_>> {
+        let start = Instant::now();
+        let connection = self
+            .pool
+            .get()
+            .await
+            .map_err(|err| anyhow::anyhow!("Failed to get connection to db: {}", err))?;
+        metrics::histogram!("sql.connection_acquire", start.elapsed());
+
+        Ok(StorageProcessor::from_pool(connection))
+    }
+
     async fn get_pooled_connection(&self) -> PooledConnection {
         let mut retry_count = 0;
 
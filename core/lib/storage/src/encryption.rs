@@ -0,0 +1,230 @@
+//! Application-level encryption of the sensitive columns.
+//!
+//! The values are encrypted with AES-256-GCM using a random nonce per value and
+//! stored as `enc1:<hex(nonce || ciphertext || tag)>`. Since the encrypted values
+//! can not be searched, the columns that are used in lookups are accompanied by
+//! the index columns holding the HMAC-SHA256 of the plaintext.
+//!
+//! The master key is configured via the `DATABASE_ENCRYPTION_KEY` environment variable
+//! (32 bytes in hex). While the existing rows are being encrypted, the
+//! `DATABASE_ENCRYPTION_MIXED_MODE` flag allows reading the plaintext values as well.
+
+// Built-in deps
+use std::env;
+// External imports
+use anyhow::{ensure, format_err};
+use once_cell::sync::Lazy;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+// Local imports
+use crate::QueryResult;
+
+const ENCRYPTED_PREFIX: &str = "enc1:";
+
+static COLUMN_CIPHER: Lazy<Option<ColumnCipher>> = Lazy::new(ColumnCipher::from_env);
+
+/// Returns the cipher configured for the process, if the encryption is enabled.
+pub fn column_cipher() -> Option<&'static ColumnCipher> {
+    COLUMN_CIPHER.as_ref()
+}
+
+/// Encrypts the values of the sensitive columns and computes their lookup indices.
+pub struct ColumnCipher {
+    key: LessSafeKey,
+    index_key: hmac::Key,
+    mixed_mode: bool,
+    rng: SystemRandom,
+}
+
+impl std::fmt::Debug for ColumnCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ColumnCipher")
+            .field("mixed_mode", &self.mixed_mode)
+            .finish()
+    }
+}
+
+impl ColumnCipher {
+    /// Creates the cipher, the keys for the encryption and for the indices
+    /// are derived from the master key.
+    pub fn new(master_key: [u8; 32], mixed_mode: bool) -> Self {
+        let master_key = hmac::Key::new(hmac::HMAC_SHA256, &master_key);
+        let derive = |purpose: &[u8]| hmac::sign(&master_key, purpose);
+
+        let key = UnboundKey::new(&AES_256_GCM, derive(b"encryption").as_ref())
+            .expect("SHA256 output is a valid AES-256 key");
+        Self {
+            key: LessSafeKey::new(key),
+            index_key: hmac::Key::new(hmac::HMAC_SHA256, derive(b"index").as_ref()),
+            mixed_mode,
+            rng: SystemRandom::new(),
+        }
+    }
+
+    /// Loads the cipher from the environment, returns `None` if the encryption key is not set.
+    pub fn from_env() -> Option<Self> {
+        let key = env::var("DATABASE_ENCRYPTION_KEY").ok()?;
+        let key = hex::decode(key.trim_start_matches("0x"))
+            .expect("DATABASE_ENCRYPTION_KEY must be a hex string");
+        let mut master_key = [0u8; 32];
+        assert_eq!(
+            key.len(),
+            master_key.len(),
+            "DATABASE_ENCRYPTION_KEY must be 32 bytes long"
+        );
+        master_key.copy_from_slice(&key);
+
+        let mixed_mode = env::var("DATABASE_ENCRYPTION_MIXED_MODE")
+            .map(|flag| {
+                flag.parse()
+                    .expect("DATABASE_ENCRYPTION_MIXED_MODE must be a bool")
+            })
+            .unwrap_or(false);
+        Some(Self::new(master_key, mixed_mode))
+    }
+
+    /// Whether the plaintext values that have not been encrypted yet are accepted.
+    pub fn is_mixed_mode(&self) -> bool {
+        self.mixed_mode
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> String {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .expect("Failed to generate the nonce");
+
+        let mut in_out = plaintext.as_bytes().to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut in_out,
+            )
+            .expect("Failed to encrypt the value");
+
+        let mut stored = nonce.to_vec();
+        stored.extend(in_out);
+        format!("{}{}", ENCRYPTED_PREFIX, hex::encode(stored))
+    }
+
+    /// Decrypts the stored value, the plaintext values are only accepted in the mixed mode.
+    pub fn decrypt(&self, stored: &str) -> QueryResult<String> {
+        let encrypted = match stored.strip_prefix(ENCRYPTED_PREFIX) {
+            Some(encrypted) => hex::decode(encrypted)?,
+            None => {
+                ensure!(
+                    self.mixed_mode,
+                    "Unencrypted value is stored while the mixed mode is disabled"
+                );
+                return Ok(stored.to_owned());
+            }
+        };
+        ensure!(encrypted.len() > NONCE_LEN, "Encrypted value is too short");
+
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+        let mut nonce_bytes = [0u8; NONCE_LEN];
+        nonce_bytes.copy_from_slice(nonce);
+        let mut in_out = ciphertext.to_vec();
+        let plaintext = self
+            .key
+            .open_in_place(
+                Nonce::assume_unique_for_key(nonce_bytes),
+                Aad::empty(),
+                &mut in_out,
+            )
+            .map_err(|_| format_err!("Failed to decrypt the stored value"))?;
+
+        Ok(String::from_utf8(plaintext.to_vec())?)
+    }
+
+    /// Deterministic index of the value, which allows to look the rows up
+    /// without decrypting them.
+    pub fn index(&self, plaintext: &str) -> String {
+        hex::encode(hmac::sign(&self.index_key, plaintext.as_bytes()))
+    }
+}
+
+/// Checks whether the stored value has been encrypted.
+pub fn is_encrypted(stored: &str) -> bool {
+    stored.starts_with(ENCRYPTED_PREFIX)
+}
+
+/// Restores the value of the sensitive column, the encrypted values can only
+/// be read when the encryption is configured.
+pub fn decrypt_column(cipher: Option<&ColumnCipher>, stored: String) -> QueryResult<String> {
+    match cipher {
+        Some(cipher) => cipher.decrypt(&stored),
+        None => {
+            ensure!(
+                !is_encrypted(&stored),
+                "Encrypted value is stored while the encryption key is not set"
+            );
+            Ok(stored)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() -> QueryResult<()> {
+        let cipher = ColumnCipher::new([7u8; 32], false);
+
+        let plaintext = "0xc0f97cc918c9d6fa4e9fc6be61a6a06589d199b2";
+        let encrypted = cipher.encrypt(plaintext);
+        assert!(is_encrypted(&encrypted));
+        assert_eq!(cipher.decrypt(&encrypted)?, plaintext);
+
+        // The nonce is unique per value
+        assert_ne!(cipher.encrypt(plaintext), encrypted);
+
+        // The values encrypted with another key can not be read
+        let another_cipher = ColumnCipher::new([8u8; 32], true);
+        assert!(another_cipher.decrypt(&encrypted).is_err());
+        // As well as the tampered ones
+        let mut tampered = hex::decode(encrypted.trim_start_matches(ENCRYPTED_PREFIX))?;
+        *tampered.last_mut().unwrap() ^= 0x01;
+        let tampered = format!("{}{}", ENCRYPTED_PREFIX, hex::encode(tampered));
+        assert!(cipher.decrypt(&tampered).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn mixed_mode() -> QueryResult<()> {
+        let plaintext = "0xc0f97cc918c9d6fa4e9fc6be61a6a06589d199b2";
+
+        let cipher = ColumnCipher::new([7u8; 32], false);
+        assert!(cipher.decrypt(plaintext).is_err());
+
+        let cipher = ColumnCipher::new([7u8; 32], true);
+        assert_eq!(cipher.decrypt(plaintext)?, plaintext);
+        let encrypted = cipher.encrypt(plaintext);
+        assert_eq!(cipher.decrypt(&encrypted)?, plaintext);
+
+        // Without the key the encrypted values are rejected
+        assert_eq!(decrypt_column(None, plaintext.to_owned())?, plaintext);
+        assert!(decrypt_column(None, encrypted).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn deterministic_index() {
+        let cipher = ColumnCipher::new([7u8; 32], false);
+        let value = "0xc0f97cc918c9d6fa4e9fc6be61a6a06589d199b2";
+
+        assert_eq!(cipher.index(value), cipher.index(value));
+        assert_ne!(cipher.index(value), cipher.index("another value"));
+        assert_ne!(
+            cipher.index(value),
+            ColumnCipher::new([8u8; 32], false).index(value)
+        );
+    }
+}
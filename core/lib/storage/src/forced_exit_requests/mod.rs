@@ -1,25 +1,80 @@
 use chrono::{DateTime, Utc};
 // Built-in deps
-use num::BigInt;
-use sqlx::types::BigDecimal;
-use std::{ops::Sub, time::Instant};
+use std::{
+    collections::HashMap,
+    ops::{Range, Sub},
+    str::FromStr,
+    time::Instant,
+};
 // External imports
+use num::BigUint;
 // Workspace imports
 // Local imports
 use crate::{QueryResult, StorageProcessor};
+use zksync_api_types::v02::pagination::{PaginationDirection, PaginationQuery};
 use zksync_types::forced_exit_requests::{
-    ForcedExitRequest, ForcedExitRequestId, SaveForcedExitRequestQuery,
+    pay_exactly, ExpectedForcedExitPayment, ForcedExitBacklogReport, ForcedExitCancellation,
+    ForcedExitCancellationKind, ForcedExitConfigSnapshot, ForcedExitConsistencyReport,
+    ForcedExitFulfilledCallback, ForcedExitFulfillment, ForcedExitFulfillmentMismatch,
+    ForcedExitLifecycleStatus, ForcedExitMoneyConfig, ForcedExitPacing, ForcedExitPacingState,
+    ForcedExitPayment, ForcedExitPipelineVersion, ForcedExitProcessingFailure, ForcedExitRefund,
+    ForcedExitRefundEvidence, ForcedExitRefundId, ForcedExitRefundStatus, ForcedExitRequest,
+    ForcedExitRequestActiveTarget, ForcedExitRequestDelivery, ForcedExitRequestDeliveryId,
+    ForcedExitRequestEscalation, ForcedExitRequestEvent, ForcedExitRequestEvidence,
+    ForcedExitRequestId, ForcedExitRequestNote, ForcedExitRequestsApiKey,
+    ForcedExitRequestsApiKeyId, ForcedExitRetry, ForcedExitRetryId, ForcedExitSenderState,
+    ForcedExitSenderStatus, ForcedExitSingletonHolder, InjectedForcedExitPayment,
+    InjectedForcedExitPaymentId, PaymentMatchScheme, PaymentSource, PaymentSourceState,
+    SaveForcedExitRefundQuery, SaveForcedExitRequestNoteQuery, SaveForcedExitRequestQuery,
+    SaveForcedExitRequestsApiKeyQuery, SaveInjectedForcedExitPaymentQuery, SkippedForcedExit,
+    UnmatchedForcedExitPayment, UnmatchedPaymentReason, FORCED_EXIT_PIPELINE_VERSION,
 };
 
-use zksync_types::tx::TxHash;
+use zksync_types::{tx::TxHash, AccountId, Address, TokenId, H256};
+use zksync_utils::{amount_to_big_decimal, big_decimal_to_amount};
 
 pub mod records;
 
 mod utils;
 
-use records::DbForcedExitRequest;
+use records::{
+    DbExpectedForcedExitPayment, DbForcedExitCancellation, DbForcedExitFulfillment,
+    DbForcedExitPayment, DbForcedExitPipelineVersion, DbForcedExitProcessingFailure,
+    DbForcedExitRefund, DbForcedExitRequest, DbForcedExitRequestActiveTarget,
+    DbForcedExitRequestDelivery, DbForcedExitRequestEscalation, DbForcedExitRequestNote,
+    DbForcedExitRequestsApiKey, DbForcedExitRetry, DbInjectedForcedExitPayment,
+    DbPaymentSourceState, DbSkippedForcedExit, DbUnmatchedForcedExitPayment,
+};
+
+use crate::{
+    encryption::{column_cipher, decrypt_column, is_encrypted, ColumnCipher},
+    utils::{address_to_stored_string, stored_str_address_to_address},
+};
+
+/// Restores the payment, decrypting its payer if needed.
+fn decrypt_payment(
+    cipher: Option<&ColumnCipher>,
+    mut payment: DbForcedExitPayment,
+) -> QueryResult<ForcedExitPayment> {
+    payment.payer = payment
+        .payer
+        .map(|payer| decrypt_column(cipher, payer))
+        .transpose()?;
+    Ok(payment.into())
+}
+
+/// Restores the refund, decrypting its recipient if needed.
+fn decrypt_refund(
+    cipher: Option<&ColumnCipher>,
+    mut refund: DbForcedExitRefund,
+) -> QueryResult<ForcedExitRefund> {
+    refund.recipient = decrypt_column(cipher, refund.recipient)?;
+    Ok(refund.into())
+}
 
-use crate::utils::address_to_stored_string;
+/// Key of the advisory lock taken by the instance processing the requests. The key is
+/// below 2^32, so the lock is listed in `pg_locks` with the key as `objid`.
+const SINGLETON_LOCK_KEY: i64 = 0x0fe5_1e70;
 
 /// ForcedExitRequests schema handles the `forced_exit_requests` table, providing methods to
 #[derive(Debug)]
@@ -31,31 +86,53 @@ impl<'a, 'c> ForcedExitRequestsSchema<'a, 'c> {
         request: SaveForcedExitRequestQuery,
     ) -> QueryResult<ForcedExitRequest> {
         let start = Instant::now();
-        let price_in_wei = BigDecimal::from(BigInt::from(request.price_in_wei.clone()));
+        let price_in_wei = amount_to_big_decimal(&request.price_in_wei);
 
         let target_str = address_to_stored_string(&request.target);
 
         let tokens = utils::vec_to_comma_list(request.tokens.clone());
 
+        let mut transaction = self.0.start_transaction().await?;
+
+        // The public id is needed in advance to store the exact amount to be paid for the request
+        let public_id = transaction
+            .forced_exit_requests_schema()
+            .allocate_public_id()
+            .await?;
+        let pay_exactly = match &request.payment_terms {
+            Some(terms) => terms.pay_exactly(&request.price_in_wei, public_id),
+            None => pay_exactly(&request.price_in_wei, public_id),
+        };
+        let payment_terms = request.payment_terms.as_ref().map(|terms| {
+            serde_json::to_value(terms).expect("Failed to serialize the payment terms")
+        });
+
         let stored_request: DbForcedExitRequest = sqlx::query_as!(
             DbForcedExitRequest,
             r#"
-            INSERT INTO forced_exit_requests ( target, tokens, price_in_wei, created_at, valid_until )
-            VALUES ( $1, $2, $3, $4, $5 )
+            INSERT INTO forced_exit_requests ( public_id, target, tokens, price_in_wei, pay_exactly, created_at, valid_until, metadata, payment_terms, callback_url )
+            VALUES ( $1, $2, $3, $4, $5, $6, $7, $8, $9, $10 )
             RETURNING *
             "#,
+            public_id,
             target_str,
             &tokens,
             price_in_wei,
+            pay_exactly,
             // It is possible to generate created_at inside the db
             // However, since the valid_until is generated outside the db (using config params)
             // it was decided to set both values in the server for consistency
             request.created_at,
-            request.valid_until
+            request.valid_until,
+            request.metadata,
+            payment_terms,
+            request.callback_url
         )
-        .fetch_one(self.0.conn())
+        .fetch_one(transaction.conn())
         .await?;
 
+        transaction.commit().await?;
+
         metrics::histogram!("sql.forced_exit_requests.store_request", start.elapsed());
         Ok(stored_request.into())
     }
@@ -77,6 +154,10 @@ impl<'a, 'c> ForcedExitRequestsSchema<'a, 'c> {
         .fetch_optional(self.0.conn())
         .await?
         .map(|r| r.into());
+        let request = self
+            .attach_fulfillments(request.into_iter().collect())
+            .await?
+            .pop();
 
         metrics::histogram!(
             "sql.forced_exit_requests.get_request_by_id",
@@ -86,135 +167,4395 @@ impl<'a, 'c> ForcedExitRequestsSchema<'a, 'c> {
         Ok(request)
     }
 
+    /// Loads the request by the id the clients refer to it with.
+    pub async fn get_request_by_public_id(
+        &mut self,
+        public_id: ForcedExitRequestId,
+    ) -> QueryResult<Option<ForcedExitRequest>> {
+        let start = Instant::now();
+        // The legacy requests stored without the public id are referred to by their ids
+        let request: Option<ForcedExitRequest> = sqlx::query_as!(
+            DbForcedExitRequest,
+            r#"
+            SELECT * FROM forced_exit_requests
+            WHERE (public_id = $1 AND public_id_released_at IS NULL) OR (public_id IS NULL AND id = $1)
+            ORDER BY public_id NULLS LAST
+            LIMIT 1
+            "#,
+            public_id
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(|r| r.into());
+        let request = self
+            .attach_fulfillments(request.into_iter().collect())
+            .await?
+            .pop();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.get_request_by_public_id",
+            start.elapsed()
+        );
+
+        Ok(request)
+    }
+
+    /// Allocates the block of `count` consecutive public ids.
+    ///
+    /// The counter is updated in a single statement, so the concurrent allocations, including
+    /// the ones made by the other servers, wait for each other and get the blocks of their own.
+    /// Only the ids allocated within a transaction which is rolled back are allocated again,
+    /// the requests they were allocated for have not been stored either.
+    pub async fn reserve_public_ids(
+        &mut self,
+        count: u32,
+    ) -> QueryResult<Range<ForcedExitRequestId>> {
+        let start = Instant::now();
+        let count = i64::from(count);
+
+        let next_public_id = sqlx::query!(
+            r#"
+            UPDATE forced_exit_requests_public_id_counter
+                SET next_public_id = next_public_id + $1
+                RETURNING next_public_id
+            "#,
+            count
+        )
+        .fetch_one(self.0.conn())
+        .await?
+        .next_public_id;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.reserve_public_ids",
+            start.elapsed()
+        );
+        Ok(next_public_id - count..next_public_id)
+    }
+
+    /// Allocates the public id for a single request, the released ids are allocated first.
+    ///
+    /// The released id is taken within the transaction storing the request, so it is released
+    /// again if the request is not stored.
+    async fn allocate_public_id(&mut self) -> QueryResult<ForcedExitRequestId> {
+        let released = sqlx::query!(
+            r#"
+            DELETE FROM forced_exit_requests_released_public_ids
+            WHERE public_id = (
+                SELECT public_id FROM forced_exit_requests_released_public_ids
+                ORDER BY released_at, public_id
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING public_id
+            "#
+        )
+        .fetch_optional(self.0.conn())
+        .await?;
+
+        match released {
+            Some(released) => Ok(released.public_id),
+            None => Ok(self.reserve_public_ids(1).await?.start),
+        }
+    }
+
+    /// Expires the requests the grace period after the validity period of which is over by `now`
+    /// and releases their public ids, returns the number of the requests expired. The grace period
+    /// is taken from the payment terms of the request, `default_grace_period` is used for the requests
+    /// stored without them. At most `limit` requests are expired at once, so the rest are left
+    /// to the next calls.
+    ///
+    /// Only the requests nothing has been paid, sent or registered for are expired this way,
+    /// the payments made for the rest are still matched with them.
+    pub async fn expire_unpaid_requests(
+        &mut self,
+        now: DateTime<Utc>,
+        default_grace_period: chrono::Duration,
+        limit: u32,
+    ) -> QueryResult<u32> {
+        let start = Instant::now();
+        let released_at = Utc::now();
+
+        let expired = sqlx::query!(
+            r#"
+            WITH unpaid AS (
+                SELECT id FROM forced_exit_requests
+                WHERE valid_until + COALESCE(
+                        ( payment_terms->>'expirationGracePeriod' )::BIGINT, $7
+                    ) * INTERVAL '1 millisecond' < $1
+                    AND status IN ( $2, $3, $4 )
+                    AND public_id IS NOT NULL AND public_id_released_at IS NULL
+                    AND matched_at IS NULL AND fulfilled_by IS NULL AND NOT EXISTS (
+                        SELECT 1 FROM forced_exit_fulfillments WHERE request_id = forced_exit_requests.id
+                    ) AND NOT EXISTS (
+                        SELECT 1 FROM forced_exit_requests_expected_payments
+                        WHERE request_id = forced_exit_requests.id
+                    )
+                ORDER BY valid_until
+                LIMIT $5
+                FOR UPDATE SKIP LOCKED
+            ), released AS (
+                UPDATE forced_exit_requests
+                    SET status = CASE WHEN status = $4 THEN status ELSE $3 END,
+                        public_id_released_at = $6
+                    FROM unpaid
+                    WHERE forced_exit_requests.id = unpaid.id
+                RETURNING public_id
+            )
+            INSERT INTO forced_exit_requests_released_public_ids ( public_id, released_at )
+            SELECT public_id, $6 FROM released
+            ON CONFLICT ( public_id ) DO NOTHING
+            "#,
+            now,
+            ForcedExitLifecycleStatus::Created.as_str(),
+            ForcedExitLifecycleStatus::Expired.as_str(),
+            ForcedExitLifecycleStatus::Cancelled.as_str(),
+            i64::from(limit),
+            released_at,
+            default_grace_period.num_milliseconds()
+        )
+        .execute(self.0.conn())
+        .await?
+        .rows_affected();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.expire_unpaid_requests",
+            start.elapsed()
+        );
+        Ok(expired as u32)
+    }
+
+    /// Loads the page of the requests created for the given target account.
+    pub async fn load_requests_page(
+        &mut self,
+        target: Address,
+        query: &PaginationQuery<ForcedExitRequestId>,
+    ) -> QueryResult<Vec<ForcedExitRequest>> {
+        let start = Instant::now();
+        let target_str = address_to_stored_string(&target);
+        let limit = i64::from(query.limit);
+
+        let requests = match query.direction {
+            PaginationDirection::Newer => {
+                sqlx::query_as!(
+                    DbForcedExitRequest,
+                    r#"
+                    SELECT * FROM forced_exit_requests
+                    WHERE target = $1 AND id >= $2
+                    ORDER BY id ASC
+                    LIMIT $3
+                    "#,
+                    target_str,
+                    query.from,
+                    limit
+                )
+                .fetch_all(self.0.conn())
+                .await?
+            }
+            PaginationDirection::Older => {
+                sqlx::query_as!(
+                    DbForcedExitRequest,
+                    r#"
+                    SELECT * FROM forced_exit_requests
+                    WHERE target = $1 AND id <= $2
+                    ORDER BY id DESC
+                    LIMIT $3
+                    "#,
+                    target_str,
+                    query.from,
+                    limit
+                )
+                .fetch_all(self.0.conn())
+                .await?
+            }
+        };
+
+        let requests = self
+            .attach_fulfillments(requests.into_iter().map(|r| r.into()).collect())
+            .await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_requests_page",
+            start.elapsed()
+        );
+        Ok(requests)
+    }
+
+    /// Returns the id of the latest request created for the given target account.
+    pub async fn get_last_request_id(
+        &mut self,
+        target: Address,
+    ) -> QueryResult<Option<ForcedExitRequestId>> {
+        let start = Instant::now();
+        let target_str = address_to_stored_string(&target);
+
+        let id = sqlx::query!(
+            r#"
+            SELECT MAX(id) as "id" FROM forced_exit_requests
+            WHERE target = $1
+            "#,
+            target_str
+        )
+        .fetch_one(self.0.conn())
+        .await?
+        .id;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.get_last_request_id",
+            start.elapsed()
+        );
+        Ok(id)
+    }
+
+    pub async fn get_requests_count(&mut self, target: Address) -> QueryResult<u32> {
+        let start = Instant::now();
+        let target_str = address_to_stored_string(&target);
+
+        let count = sqlx::query!(
+            r#"
+            SELECT COUNT(*) as "count!" FROM forced_exit_requests
+            WHERE target = $1
+            "#,
+            target_str
+        )
+        .fetch_one(self.0.conn())
+        .await?
+        .count;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.get_requests_count",
+            start.elapsed()
+        );
+        Ok(count as u32)
+    }
+
     pub async fn set_fulfilled_at(
         &mut self,
         id: ForcedExitRequestId,
         fulfilled_at: DateTime<Utc>,
     ) -> QueryResult<()> {
         let start = Instant::now();
+        let mut transaction = self.0.start_transaction().await?;
 
         sqlx::query!(
             r#"
             UPDATE forced_exit_requests
-                SET fulfilled_at = $1
-                WHERE id = $2
+                SET fulfilled_at = $1, status = $2
+                WHERE id = $3
             "#,
             fulfilled_at,
+            ForcedExitLifecycleStatus::Fulfilled.as_str(),
             id
         )
-        .execute(self.0.conn())
+        .execute(transaction.conn())
         .await?;
+        transaction
+            .forced_exit_requests_schema()
+            .enqueue_delivery(id, ForcedExitRequestEvent::Fulfilled, fulfilled_at)
+            .await?;
+        transaction
+            .forced_exit_requests_schema()
+            .enqueue_callback(id, fulfilled_at)
+            .await?;
+
+        transaction.commit().await?;
 
         metrics::histogram!("sql.forced_exit_requests.set_fulfilled_at", start.elapsed());
 
         Ok(())
     }
 
-    pub async fn get_oldest_unfulfilled_request(
+    /// Records the way the request was matched with the payment. The request that
+    /// is matched once again (e.g. after the failure) keeps its place in the queue,
+    /// the one already being fulfilled keeps its status.
+    pub async fn set_match_scheme(
         &mut self,
-    ) -> QueryResult<Option<ForcedExitRequest>> {
+        id: ForcedExitRequestId,
+        match_scheme: PaymentMatchScheme,
+        matched_at: DateTime<Utc>,
+    ) -> QueryResult<()> {
         let start = Instant::now();
 
-        let request: Option<ForcedExitRequest> = sqlx::query_as!(
-            DbForcedExitRequest,
+        sqlx::query!(
             r#"
-            SELECT * FROM forced_exit_requests
-            WHERE fulfilled_at IS NULL AND created_at = (
-                SELECT MIN(created_at) FROM forced_exit_requests
-                WHERE fulfilled_at IS NULL
-            )
-            LIMIT 1
-            "#
+            UPDATE forced_exit_requests
+                SET match_scheme = $1, matched_at = COALESCE(matched_at, $2),
+                    status = CASE WHEN status IN ( $3, $4 ) THEN status ELSE $5 END
+                WHERE id = $6
+            "#,
+            match_scheme.as_str(),
+            matched_at,
+            ForcedExitLifecycleStatus::TxsSent.as_str(),
+            ForcedExitLifecycleStatus::Fulfilled.as_str(),
+            ForcedExitLifecycleStatus::PaymentReceived.as_str(),
+            id
         )
-        .fetch_optional(self.0.conn())
-        .await?
-        .map(|r| r.into());
+        .execute(self.0.conn())
+        .await?;
 
-        metrics::histogram!(
-            "sql.forced_exit_requests.get_oldest_unfulfilled_request",
-            start.elapsed()
-        );
+        metrics::histogram!("sql.forced_exit_requests.set_match_scheme", start.elapsed());
 
-        Ok(request)
+        Ok(())
     }
 
-    pub async fn set_fulfilled_by(
+    /// Records the amount the request was paid for with.
+    pub async fn set_paid_amount(
         &mut self,
         id: ForcedExitRequestId,
-        tx_hashes: Option<Vec<TxHash>>,
+        paid_amount: &BigUint,
     ) -> QueryResult<()> {
         let start = Instant::now();
 
-        let hash_str = tx_hashes.map(utils::vec_to_comma_list);
-
         sqlx::query!(
-            r#"
-            UPDATE forced_exit_requests
-                SET fulfilled_by = $1
-                WHERE id = $2
-            "#,
-            hash_str,
+            "UPDATE forced_exit_requests SET paid_amount = $1 WHERE id = $2",
+            amount_to_big_decimal(paid_amount),
             id
         )
         .execute(self.0.conn())
         .await?;
 
-        metrics::histogram!("sql.forced_exit_requests.set_fulfilled_by", start.elapsed());
+        metrics::histogram!("sql.forced_exit_requests.set_paid_amount", start.elapsed());
         Ok(())
     }
 
-    // Normally this function should not return any more
-    // than one request, but it was decided to make to more
-    // general from the start
-    pub async fn get_unconfirmed_requests(&mut self) -> QueryResult<Vec<ForcedExitRequest>> {
+    /// Loads the requests payable at `submission_time`, the exact amounts of which are the closest
+    /// ones not exceeding `max_amount`, the closest one first. At most two requests are loaded,
+    /// which is enough to tell whether the closest one is the only candidate for the overpayment.
+    pub async fn get_requests_by_closest_amount(
+        &mut self,
+        max_amount: &BigUint,
+        submission_time: DateTime<Utc>,
+    ) -> QueryResult<Vec<ForcedExitRequest>> {
         let start = Instant::now();
 
+        // The legacy requests are compared by the amount they would have been stored with.
+        // The requests which can not be paid for anymore must not hide the payable ones
         let requests: Vec<ForcedExitRequest> = sqlx::query_as!(
             DbForcedExitRequest,
             r#"
             SELECT * FROM forced_exit_requests
-            WHERE fulfilled_at IS NULL AND fulfilled_by IS NOT NULL
+            WHERE COALESCE(pay_exactly::NUMERIC, price_in_wei + id) <= $1
+                AND public_id_released_at IS NULL
+                AND fulfilled_at IS NULL
+                AND (cancellation IS NULL OR cancellation = 'system_retry')
+                AND valid_until > $2
+            ORDER BY COALESCE(pay_exactly::NUMERIC, price_in_wei + id) DESC
+            LIMIT 2
+            "#,
+            amount_to_big_decimal(max_amount),
+            submission_time
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(ForcedExitRequest::from)
+        .collect();
+        let requests = self.attach_fulfillments(requests).await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.get_requests_by_closest_amount",
+            start.elapsed()
+        );
+        Ok(requests)
+    }
+
+    /// Returns the number of the paid requests to be processed before the given one,
+    /// `None` if the request is not waiting to be processed.
+    pub async fn get_queue_position(
+        &mut self,
+        id: ForcedExitRequestId,
+    ) -> QueryResult<Option<u32>> {
+        let start = Instant::now();
+        let mut transaction = self.0.start_transaction().await?;
+
+        let matched_at = sqlx::query!(
+            r#"
+            SELECT matched_at FROM forced_exit_requests
+            WHERE id = $1 AND fulfilled_at IS NULL AND id NOT IN (
+                SELECT request_id FROM forced_exit_requests_escalations
+            )
+            "#,
+            id
+        )
+        .fetch_optional(transaction.conn())
+        .await?
+        .and_then(|request| request.matched_at);
+        let matched_at = match matched_at {
+            Some(matched_at) => matched_at,
+            None => return Ok(None),
+        };
+
+        // The escalated requests are fulfilled by the operators on L1 and do not hold the queue
+        let position = sqlx::query!(
+            r#"
+            SELECT COUNT(*) as "count!" FROM forced_exit_requests
+            WHERE fulfilled_at IS NULL AND matched_at IS NOT NULL
+                AND (matched_at, id) < ($1, $2)
+                AND id NOT IN (
+                    SELECT request_id FROM forced_exit_requests_escalations
+                )
+            "#,
+            matched_at,
+            id
+        )
+        .fetch_one(transaction.conn())
+        .await?
+        .count;
+        transaction.commit().await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.get_queue_position",
+            start.elapsed()
+        );
+        Ok(Some(position as u32))
+    }
+
+    /// Loads the ids of the paid requests the transactions of which have not been sent yet,
+    /// in the order they are processed in.
+    pub async fn get_backlog_request_ids(&mut self) -> QueryResult<Vec<ForcedExitRequestId>> {
+        let start = Instant::now();
+
+        let ids = sqlx::query!(
+            r#"
+            SELECT id FROM forced_exit_requests
+            WHERE matched_at IS NOT NULL AND fulfilled_at IS NULL AND fulfilled_by IS NULL
+                AND NOT EXISTS (
+                    SELECT 1 FROM forced_exit_fulfillments WHERE request_id = forced_exit_requests.id
+                )
+                AND id NOT IN (
+                    SELECT request_id FROM forced_exit_requests_escalations
+                )
+            ORDER BY matched_at, id
             "#
         )
         .fetch_all(self.0.conn())
         .await?
         .into_iter()
-        .map(|rec| rec.into())
+        .map(|request| request.id)
         .collect();
 
         metrics::histogram!(
-            "sql.forced_exit_requests.get_unconfirmed_requests",
+            "sql.forced_exit_requests.get_backlog_request_ids",
             start.elapsed()
         );
+        Ok(ids)
+    }
 
-        Ok(requests)
+    /// Returns the number of the requests fulfilled since the given moment.
+    pub async fn count_fulfilled_since(&mut self, since: DateTime<Utc>) -> QueryResult<u32> {
+        let start = Instant::now();
+
+        let count = sqlx::query!(
+            r#"
+            SELECT COUNT(*) as "count!" FROM forced_exit_requests
+            WHERE fulfilled_at >= $1
+            "#,
+            since
+        )
+        .fetch_one(self.0.conn())
+        .await?
+        .count;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.count_fulfilled_since",
+            start.elapsed()
+        );
+        Ok(count as u32)
     }
 
-    pub async fn delete_old_unfulfilled_requests(
+    /// Returns the number of the requests still awaiting the payment at the given moment,
+    /// i.e. the ones the ids of which can be encoded in the paid amounts.
+    pub async fn count_awaiting_payment(&mut self, now: DateTime<Utc>) -> QueryResult<u32> {
+        let start = Instant::now();
+
+        let count = sqlx::query!(
+            r#"
+            SELECT COUNT(*) as "count!" FROM forced_exit_requests
+            WHERE fulfilled_at IS NULL AND fulfilled_by IS NULL
+                AND NOT EXISTS (
+                    SELECT 1 FROM forced_exit_fulfillments WHERE request_id = forced_exit_requests.id
+                )
+                AND matched_at IS NULL AND valid_until > $1
+            "#,
+            now
+        )
+        .fetch_one(self.0.conn())
+        .await?
+        .count;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.count_awaiting_payment",
+            start.elapsed()
+        );
+        Ok(count as u32)
+    }
+
+    /// Counts the requests awaiting the payment, which are created for the given target account.
+    pub async fn count_awaiting_payment_for_target(
         &mut self,
-        // The time that has to be passed since the
-        // request has been considered invalid to delete it
-        deleting_threshold: chrono::Duration,
-    ) -> QueryResult<()> {
+        target: Address,
+        now: DateTime<Utc>,
+    ) -> QueryResult<u32> {
         let start = Instant::now();
+        let target_str = address_to_stored_string(&target);
 
-        let oldest_allowed = Utc::now().sub(deleting_threshold);
+        let count = sqlx::query!(
+            r#"
+            SELECT COUNT(*) as "count!" FROM forced_exit_requests
+            WHERE target = $1 AND fulfilled_at IS NULL AND fulfilled_by IS NULL
+                AND NOT EXISTS (
+                    SELECT 1 FROM forced_exit_fulfillments WHERE request_id = forced_exit_requests.id
+                )
+                AND matched_at IS NULL AND valid_until > $2
+            "#,
+            target_str,
+            now
+        )
+        .fetch_one(self.0.conn())
+        .await?
+        .count;
 
-        sqlx::query!(
+        metrics::histogram!(
+            "sql.forced_exit_requests.count_awaiting_payment_for_target",
+            start.elapsed()
+        );
+        Ok(count as u32)
+    }
+
+    /// Loads the requests awaiting the payment at the given moment, the oldest first.
+    pub async fn load_awaiting_payment(
+        &mut self,
+        now: DateTime<Utc>,
+        limit: u32,
+    ) -> QueryResult<Vec<ForcedExitRequest>> {
+        let start = Instant::now();
+
+        let requests = sqlx::query_as!(
+            DbForcedExitRequest,
             r#"
-            DELETE FROM forced_exit_requests
-            WHERE fulfilled_by IS NULL AND valid_until < $1
+            SELECT * FROM forced_exit_requests
+            WHERE fulfilled_at IS NULL AND fulfilled_by IS NULL
+                AND NOT EXISTS (
+                    SELECT 1 FROM forced_exit_fulfillments WHERE request_id = forced_exit_requests.id
+                )
+                AND matched_at IS NULL AND valid_until > $1
+            ORDER BY id
+            LIMIT $2
             "#,
-            oldest_allowed
+            now,
+            i64::from(limit)
         )
-        .execute(self.0.conn())
-        .await?;
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(|request| request.into())
+        .collect();
 
         metrics::histogram!(
-            "sql.forced_exit_requests.delete_old_unfulfilled_requests",
+            "sql.forced_exit_requests.load_awaiting_payment",
             start.elapsed()
         );
+        Ok(requests)
+    }
 
-        Ok(())
+    /// Counts the requests stored by the servers preceding the columns added since then.
+    pub async fn count_legacy_requests(&mut self) -> QueryResult<u64> {
+        let start = Instant::now();
+
+        let count = sqlx::query!(
+            r#"
+            SELECT COUNT(*) as "count!" FROM forced_exit_requests
+            WHERE pay_exactly IS NULL
+            "#
+        )
+        .fetch_one(self.0.conn())
+        .await?
+        .count;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.count_legacy_requests",
+            start.elapsed()
+        );
+        Ok(count as u64)
+    }
+
+    /// Fills the columns missing in the next batch of the legacy requests with the values
+    /// they are read with anyway, returns the number of the upgraded requests.
+    ///
+    /// The rows locked by the concurrent transactions are skipped, they are upgraded
+    /// by one of the next batches.
+    pub async fn upgrade_legacy_requests(&mut self, batch_size: u32) -> QueryResult<u32> {
+        let start = Instant::now();
+        let mut transaction = self.0.start_transaction().await?;
+
+        let legacy_requests: Vec<ForcedExitRequest> = sqlx::query_as!(
+            DbForcedExitRequest,
+            r#"
+            SELECT * FROM forced_exit_requests
+            WHERE pay_exactly IS NULL
+            ORDER BY id
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+            "#,
+            i64::from(batch_size)
+        )
+        .fetch_all(transaction.conn())
+        .await?
+        .into_iter()
+        .map(|request| request.into())
+        .collect();
+
+        for request in &legacy_requests {
+            sqlx::query!(
+                r#"
+                UPDATE forced_exit_requests
+                    SET pay_exactly = $1, public_id = COALESCE(public_id, $2)
+                    WHERE id = $3 AND pay_exactly IS NULL
+                "#,
+                request.pay_exactly,
+                request.public_id,
+                request.id
+            )
+            .execute(transaction.conn())
+            .await?;
+        }
+        transaction.commit().await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.upgrade_legacy_requests",
+            start.elapsed()
+        );
+        Ok(legacy_requests.len() as u32)
+    }
+
+    pub async fn get_oldest_unfulfilled_request(
+        &mut self,
+    ) -> QueryResult<Option<ForcedExitRequest>> {
+        let start = Instant::now();
+
+        let request: Option<ForcedExitRequest> = sqlx::query_as!(
+            DbForcedExitRequest,
+            r#"
+            SELECT * FROM forced_exit_requests
+            WHERE fulfilled_at IS NULL AND created_at = (
+                SELECT MIN(created_at) FROM forced_exit_requests
+                WHERE fulfilled_at IS NULL AND id NOT IN (
+                    SELECT request_id FROM forced_exit_requests_escalations
+                )
+            )
+            LIMIT 1
+            "#
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(|r| r.into());
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.get_oldest_unfulfilled_request",
+            start.elapsed()
+        );
+
+        Ok(request)
+    }
+
+    /// Records the start of the fulfillment of the request paid with the given L1 transaction,
+    /// before its transactions are sent. Returns `false` if the fulfillment for the same payment
+    /// has already been started, it is refused by the unique key then.
+    ///
+    /// The key is removed once the transactions are reset, see `set_fulfilled_by`.
+    pub async fn begin_fulfillment(
+        &mut self,
+        id: ForcedExitRequestId,
+        payment_eth_tx_hash: Option<H256>,
+        started_at: DateTime<Utc>,
+    ) -> QueryResult<bool> {
+        let start = Instant::now();
+
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO forced_exit_fulfillment_keys ( request_id, payment_eth_tx_hash, started_at )
+            VALUES ( $1, $2, $3 )
+            ON CONFLICT DO NOTHING
+            "#,
+            id,
+            payment_eth_tx_hash.map(|hash| hex::encode(hash.as_bytes())),
+            started_at
+        )
+        .execute(self.0.conn())
+        .await?
+        .rows_affected();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.begin_fulfillment",
+            start.elapsed()
+        );
+        Ok(inserted > 0)
+    }
+
+    /// Records the transactions sent for the tokens of the request (in the same order),
+    /// or resets them if the transactions have failed.
+    ///
+    /// The transactions are stored as the fulfillments of the request. Unless `legacy_column`
+    /// is unset, they are written to the deprecated `fulfilled_by` column as well, which is
+    /// still read by the servers preceding the fulfillments. Once reset, the fulfillment
+    /// of the request may be started again, the request gets back to the paid status.
+    pub async fn set_fulfilled_by(
+        &mut self,
+        id: ForcedExitRequestId,
+        tx_hashes: Option<Vec<TxHash>>,
+        legacy_column: bool,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+
+        let mut transaction = self.0.start_transaction().await?;
+
+        // Resetting the transactions is not a transition the subscribers are notified about
+        let submitted = tx_hashes.is_some();
+        let hashes: Vec<String> = tx_hashes
+            .iter()
+            .flatten()
+            .map(|hash| hash.to_string())
+            .collect();
+        let hash_str = tx_hashes
+            .filter(|_| legacy_column)
+            .map(utils::vec_to_comma_list);
+
+        sqlx::query!(
+            r#"
+            UPDATE forced_exit_requests
+                SET fulfilled_by = $1, status = CASE
+                    WHEN $2 THEN $3
+                    WHEN status = $3 THEN $4
+                    ELSE status
+                END
+                WHERE id = $5
+            "#,
+            hash_str,
+            submitted,
+            ForcedExitLifecycleStatus::TxsSent.as_str(),
+            ForcedExitLifecycleStatus::PaymentReceived.as_str(),
+            id
+        )
+        .execute(transaction.conn())
+        .await?;
+        sqlx::query!(
+            "DELETE FROM forced_exit_fulfillments WHERE request_id = $1",
+            id
+        )
+        .execute(transaction.conn())
+        .await?;
+        if !submitted {
+            sqlx::query!(
+                "DELETE FROM forced_exit_fulfillment_keys WHERE request_id = $1",
+                id
+            )
+            .execute(transaction.conn())
+            .await?;
+        }
+        sqlx::query!(
+            r#"
+            INSERT INTO forced_exit_fulfillments (
+                request_id, position, token, tx_hash, created_at, submitted_at
+            )
+            SELECT id, fulfillment.position - 1, fulfillment.token::INT, fulfillment.tx_hash, $3, $3
+            FROM forced_exit_requests,
+                unnest(string_to_array(tokens, ','), $2::TEXT[])
+                    WITH ORDINALITY AS fulfillment(token, tx_hash, position)
+            WHERE id = $1 AND fulfillment.token IS NOT NULL AND fulfillment.tx_hash IS NOT NULL
+            "#,
+            id,
+            &hashes,
+            Utc::now()
+        )
+        .execute(transaction.conn())
+        .await?;
+        if submitted {
+            transaction
+                .forced_exit_requests_schema()
+                .enqueue_delivery(id, ForcedExitRequestEvent::Submitted, Utc::now())
+                .await?;
+        }
+
+        transaction.commit().await?;
+
+        metrics::histogram!("sql.forced_exit_requests.set_fulfilled_by", start.elapsed());
+        Ok(())
+    }
+
+    /// Records the transactions of the request before they are submitted to the mempool,
+    /// they are marked as submitted by `set_fulfilled_by` once the mempool has accepted them.
+    /// Until then the request keeps its status and the legacy `fulfilled_by` column is not written.
+    pub async fn record_submission(
+        &mut self,
+        id: ForcedExitRequestId,
+        tx_hashes: &[TxHash],
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+
+        let mut transaction = self.0.start_transaction().await?;
+
+        let hashes: Vec<String> = tx_hashes.iter().map(|hash| hash.to_string()).collect();
+        sqlx::query!(
+            "DELETE FROM forced_exit_fulfillments WHERE request_id = $1",
+            id
+        )
+        .execute(transaction.conn())
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO forced_exit_fulfillments (request_id, position, token, tx_hash, created_at)
+            SELECT id, submission.position - 1, submission.token::INT, submission.tx_hash, $3
+            FROM forced_exit_requests,
+                unnest(string_to_array(tokens, ','), $2::TEXT[])
+                    WITH ORDINALITY AS submission(token, tx_hash, position)
+            WHERE id = $1 AND submission.token IS NOT NULL AND submission.tx_hash IS NOT NULL
+            "#,
+            id,
+            &hashes,
+            Utc::now()
+        )
+        .execute(transaction.conn())
+        .await?;
+
+        transaction.commit().await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.record_submission",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    /// Records the transactions of the next chunk of the request, which does not fit into
+    /// a single batch, for the tokens starting with `first_position`. Unlike `record_submission`
+    /// the transactions of the previous chunks are kept, they are marked as submitted already.
+    pub async fn record_chunk_submission(
+        &mut self,
+        id: ForcedExitRequestId,
+        first_position: u32,
+        tx_hashes: &[TxHash],
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+
+        let mut transaction = self.0.start_transaction().await?;
+
+        let first_position = first_position as i32;
+        let hashes: Vec<String> = tx_hashes.iter().map(|hash| hash.to_string()).collect();
+        sqlx::query!(
+            "DELETE FROM forced_exit_fulfillments WHERE request_id = $1 AND position >= $2",
+            id,
+            first_position
+        )
+        .execute(transaction.conn())
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO forced_exit_fulfillments (request_id, position, token, tx_hash, created_at)
+            SELECT id, $2 + submission.position - 1, submission.token::INT, submission.tx_hash, $4
+            FROM forced_exit_requests,
+                unnest((string_to_array(tokens, ','))[$2 + 1:], $3::TEXT[])
+                    WITH ORDINALITY AS submission(token, tx_hash, position)
+            WHERE id = $1 AND submission.token IS NOT NULL AND submission.tx_hash IS NOT NULL
+            "#,
+            id,
+            first_position,
+            &hashes,
+            Utc::now()
+        )
+        .execute(transaction.conn())
+        .await?;
+
+        transaction.commit().await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.record_chunk_submission",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    /// Removes the transactions recorded by `record_submission`, which the mempool has refused.
+    /// The transactions marked as submitted are kept.
+    pub async fn abort_submission(&mut self, id: ForcedExitRequestId) -> QueryResult<()> {
+        let start = Instant::now();
+
+        sqlx::query!(
+            "DELETE FROM forced_exit_fulfillments WHERE request_id = $1 AND submitted_at IS NULL",
+            id
+        )
+        .execute(self.0.conn())
+        .await?;
+
+        metrics::histogram!("sql.forced_exit_requests.abort_submission", start.elapsed());
+        Ok(())
+    }
+
+    /// Loads the transactions recorded by `record_submission`, which have not been marked
+    /// as submitted, ordered by the request and the position of the token.
+    pub async fn load_unsubmitted_fulfillments(
+        &mut self,
+    ) -> QueryResult<Vec<ForcedExitFulfillment>> {
+        let start = Instant::now();
+
+        let fulfillments = sqlx::query_as!(
+            DbForcedExitFulfillment,
+            r#"
+            SELECT * FROM forced_exit_fulfillments
+            WHERE submitted_at IS NULL
+            ORDER BY request_id, position
+            "#
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(ForcedExitFulfillment::from)
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_unsubmitted_fulfillments",
+            start.elapsed()
+        );
+        Ok(fulfillments)
+    }
+
+    /// Records the id the target of the request was resolved to when the transactions
+    /// stored by `set_fulfilled_by` were signed.
+    pub async fn set_target_account_id(
+        &mut self,
+        id: ForcedExitRequestId,
+        target_account_id: AccountId,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+
+        sqlx::query!(
+            "UPDATE forced_exit_fulfillments SET target_account_id = $2 WHERE request_id = $1",
+            id,
+            i64::from(*target_account_id)
+        )
+        .execute(self.0.conn())
+        .await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.set_target_account_id",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    /// Loads the transactions sent for the tokens of the request, ordered by the position
+    /// of the token. The requests not backfilled yet have none.
+    pub async fn load_fulfillments(
+        &mut self,
+        request_id: ForcedExitRequestId,
+    ) -> QueryResult<Vec<ForcedExitFulfillment>> {
+        let start = Instant::now();
+
+        let fulfillments = sqlx::query_as!(
+            DbForcedExitFulfillment,
+            r#"
+            SELECT * FROM forced_exit_fulfillments
+            WHERE request_id = $1
+            ORDER BY position
+            "#,
+            request_id
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(ForcedExitFulfillment::from)
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_fulfillments",
+            start.elapsed()
+        );
+        Ok(fulfillments)
+    }
+
+    /// Replaces the hashes read from the deprecated `fulfilled_by` column with the fulfillments
+    /// of the requests. The column is only relied on for the requests not backfilled yet.
+    async fn attach_fulfillments(
+        &mut self,
+        mut requests: Vec<ForcedExitRequest>,
+    ) -> QueryResult<Vec<ForcedExitRequest>> {
+        if requests.is_empty() {
+            return Ok(requests);
+        }
+        let ids: Vec<_> = requests.iter().map(|request| request.id).collect();
+
+        let mut hashes: HashMap<ForcedExitRequestId, Vec<TxHash>> = HashMap::new();
+        let fulfillments = sqlx::query_as!(
+            DbForcedExitFulfillment,
+            r#"
+            SELECT * FROM forced_exit_fulfillments
+            WHERE request_id = ANY($1)
+            ORDER BY request_id, position
+            "#,
+            &ids
+        )
+        .fetch_all(self.0.conn())
+        .await?;
+        for fulfillment in fulfillments {
+            let fulfillment = ForcedExitFulfillment::from(fulfillment);
+            hashes
+                .entry(fulfillment.request_id)
+                .or_default()
+                .push(fulfillment.tx_hash);
+        }
+
+        for request in &mut requests {
+            if let Some(hashes) = hashes.remove(&request.id) {
+                request.fulfilled_by = Some(hashes);
+            }
+        }
+        Ok(requests)
+    }
+
+    /// Returns the number of the requests, the transactions of which are only recorded
+    /// in the deprecated `fulfilled_by` column.
+    pub async fn count_unbackfilled_fulfillments(&mut self) -> QueryResult<u64> {
+        let start = Instant::now();
+
+        let count = sqlx::query!(
+            r#"
+            SELECT COUNT(*) as "count!" FROM forced_exit_requests
+            WHERE fulfilled_by IS NOT NULL AND NOT EXISTS (
+                SELECT 1 FROM forced_exit_fulfillments WHERE request_id = forced_exit_requests.id
+            )
+            "#
+        )
+        .fetch_one(self.0.conn())
+        .await?
+        .count;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.count_unbackfilled_fulfillments",
+            start.elapsed()
+        );
+        Ok(count as u64)
+    }
+
+    /// Stores the fulfillments of the next batch of the requests, the transactions of which
+    /// are only recorded in the deprecated `fulfilled_by` column, returns the number of
+    /// such requests. The time of the transactions is not known, so the fulfillments get
+    /// the time the request was matched with the payment.
+    ///
+    /// The rows locked by the concurrent transactions are skipped, they are backfilled
+    /// by one of the next batches.
+    pub async fn backfill_fulfillments(&mut self, batch_size: u32) -> QueryResult<u32> {
+        let start = Instant::now();
+
+        let count = sqlx::query!(
+            r#"
+            WITH batch AS (
+                SELECT id, tokens, fulfilled_by, COALESCE(matched_at, created_at) AS sent_at
+                FROM forced_exit_requests
+                WHERE fulfilled_by IS NOT NULL AND NOT EXISTS (
+                    SELECT 1 FROM forced_exit_fulfillments
+                    WHERE request_id = forced_exit_requests.id
+                )
+                ORDER BY id
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            ), inserted AS (
+                INSERT INTO forced_exit_fulfillments (
+                    request_id, position, token, tx_hash, created_at, submitted_at
+                )
+                SELECT batch.id, fulfillment.position - 1, fulfillment.token::INT,
+                    fulfillment.tx_hash, batch.sent_at, batch.sent_at
+                FROM batch,
+                    unnest(string_to_array(batch.tokens, ','), string_to_array(batch.fulfilled_by, ','))
+                        WITH ORDINALITY AS fulfillment(token, tx_hash, position)
+                WHERE fulfillment.token IS NOT NULL AND fulfillment.tx_hash IS NOT NULL
+                RETURNING request_id
+            )
+            SELECT COUNT(DISTINCT request_id) as "count!" FROM inserted
+            "#,
+            i64::from(batch_size)
+        )
+        .fetch_one(self.0.conn())
+        .await?
+        .count;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.backfill_fulfillments",
+            start.elapsed()
+        );
+        Ok(count as u32)
+    }
+
+    /// Loads the requests, the hashes of which differ between the deprecated `fulfilled_by`
+    /// column and the fulfillments. Only meaningful while the column is still written.
+    /// The transactions being submitted are not written to the column yet, so they are skipped.
+    pub async fn load_fulfillment_mismatches(
+        &mut self,
+        limit: u32,
+    ) -> QueryResult<Vec<ForcedExitFulfillmentMismatch>> {
+        let start = Instant::now();
+
+        let mismatches = sqlx::query!(
+            r#"
+            SELECT forced_exit_requests.id, forced_exit_requests.fulfilled_by,
+                fulfillments.tx_hashes
+            FROM forced_exit_requests
+            LEFT JOIN (
+                SELECT request_id, string_agg(tx_hash, ',' ORDER BY position) AS tx_hashes
+                FROM forced_exit_fulfillments
+                WHERE submitted_at IS NOT NULL
+                GROUP BY request_id
+            ) fulfillments ON fulfillments.request_id = forced_exit_requests.id
+            WHERE forced_exit_requests.fulfilled_by IS DISTINCT FROM fulfillments.tx_hashes
+            ORDER BY forced_exit_requests.id
+            LIMIT $1
+            "#,
+            i64::from(limit)
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(|mismatch| ForcedExitFulfillmentMismatch {
+            request_id: mismatch.id,
+            legacy: mismatch.fulfilled_by.map(utils::comma_list_to_vec),
+            fulfillments: mismatch.tx_hashes.map(utils::comma_list_to_vec),
+        })
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_fulfillment_mismatches",
+            start.elapsed()
+        );
+        Ok(mismatches)
+    }
+
+    /// Changes the validity period of the request that has not been processed yet,
+    /// returns `None` if there is no such request or it is already being fulfilled.
+    /// The request cancelled before may be processed again once extended, it gets back
+    /// to the status preceding the transactions.
+    pub async fn set_valid_until(
+        &mut self,
+        id: ForcedExitRequestId,
+        valid_until: DateTime<Utc>,
+    ) -> QueryResult<Option<ForcedExitRequest>> {
+        let start = Instant::now();
+
+        let request = sqlx::query_as!(
+            DbForcedExitRequest,
+            r#"
+            UPDATE forced_exit_requests
+                SET valid_until = $1, cancellation = NULL,
+                    status = CASE WHEN matched_at IS NULL THEN $2 ELSE $3 END
+                WHERE id = $4 AND public_id_released_at IS NULL AND fulfilled_by IS NULL AND fulfilled_at IS NULL AND NOT EXISTS (
+                    SELECT 1 FROM forced_exit_fulfillments WHERE request_id = forced_exit_requests.id
+                ) AND id NOT IN (
+                    SELECT request_id FROM forced_exit_requests_escalations
+                )
+            RETURNING *
+            "#,
+            valid_until,
+            ForcedExitLifecycleStatus::Created.as_str(),
+            ForcedExitLifecycleStatus::PaymentReceived.as_str(),
+            id
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(|request| request.into());
+
+        metrics::histogram!("sql.forced_exit_requests.set_valid_until", start.elapsed());
+        Ok(request)
+    }
+
+    /// Cancels the request and records the cancellation, returns `None` if there is no such
+    /// request or it can not be cancelled in this way.
+    ///
+    /// The system cancels the transactions which have failed or were not sent, so they are
+    /// sent again; the sent transactions are reset the way `set_fulfilled_by` does it. The rest
+    /// of the kinds only cancel the request which has not been processed yet, and end its
+    /// validity period.
+    pub async fn cancel_request(
+        &mut self,
+        id: ForcedExitRequestId,
+        kind: ForcedExitCancellationKind,
+        cancelled_at: DateTime<Utc>,
+        legacy_column: bool,
+    ) -> QueryResult<Option<ForcedExitRequest>> {
+        let start = Instant::now();
+        let mut transaction = self.0.start_transaction().await?;
+
+        let request: Option<DbForcedExitRequest> = if kind.allows_reprocessing() {
+            transaction
+                .forced_exit_requests_schema()
+                .set_fulfilled_by(id, None, legacy_column)
+                .await?;
+            sqlx::query_as!(
+                DbForcedExitRequest,
+                r#"
+                UPDATE forced_exit_requests
+                    SET cancellation = $1
+                    WHERE id = $2
+                RETURNING *
+                "#,
+                kind.as_str(),
+                id
+            )
+            .fetch_optional(transaction.conn())
+            .await?
+        } else {
+            let status = match kind {
+                ForcedExitCancellationKind::Expired => ForcedExitLifecycleStatus::Expired,
+                ForcedExitCancellationKind::TargetNotFound => ForcedExitLifecycleStatus::Failed,
+                _ => ForcedExitLifecycleStatus::Cancelled,
+            };
+            sqlx::query_as!(
+                DbForcedExitRequest,
+                r#"
+                UPDATE forced_exit_requests
+                    SET valid_until = LEAST(valid_until, $1), cancellation = $2, status = $3
+                    WHERE id = $4 AND fulfilled_by IS NULL AND fulfilled_at IS NULL AND NOT EXISTS (
+                        SELECT 1 FROM forced_exit_fulfillments WHERE request_id = forced_exit_requests.id
+                    ) AND id NOT IN (
+                        SELECT request_id FROM forced_exit_requests_escalations
+                    )
+                RETURNING *
+                "#,
+                cancelled_at,
+                kind.as_str(),
+                status.as_str(),
+                id
+            )
+            .fetch_optional(transaction.conn())
+            .await?
+        };
+        if request.is_some() {
+            sqlx::query!(
+                r#"
+                INSERT INTO forced_exit_requests_cancellations ( request_id, kind, cancelled_at )
+                VALUES ( $1, $2, $3 )
+                "#,
+                id,
+                kind.as_str(),
+                cancelled_at
+            )
+            .execute(transaction.conn())
+            .await?;
+        }
+
+        transaction.commit().await?;
+
+        metrics::histogram!("sql.forced_exit_requests.cancel_request", start.elapsed());
+        Ok(request.map(|request| request.into()))
+    }
+
+    /// Loads the cancellations of the request in the order they happened.
+    pub async fn load_cancellations(
+        &mut self,
+        id: ForcedExitRequestId,
+    ) -> QueryResult<Vec<ForcedExitCancellation>> {
+        let start = Instant::now();
+
+        let cancellations = sqlx::query_as!(
+            DbForcedExitCancellation,
+            r#"
+            SELECT request_id, kind, cancelled_at FROM forced_exit_requests_cancellations
+            WHERE request_id = $1
+            ORDER BY id
+            "#,
+            id
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(ForcedExitCancellation::from)
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_cancellations",
+            start.elapsed()
+        );
+        Ok(cancellations)
+    }
+
+    // Normally this function should not return any more
+    // than one request, but it was decided to make to more
+    // general from the start.
+    // The requests escalated to L1 are not awaited, their transactions have failed
+    pub async fn get_unconfirmed_requests(&mut self) -> QueryResult<Vec<ForcedExitRequest>> {
+        let start = Instant::now();
+
+        let requests: Vec<ForcedExitRequest> = sqlx::query_as!(
+            DbForcedExitRequest,
+            r#"
+            SELECT * FROM forced_exit_requests
+            WHERE status = $1 AND id NOT IN (
+                SELECT request_id FROM forced_exit_requests_escalations
+            )
+            "#,
+            ForcedExitLifecycleStatus::TxsSent.as_str()
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(|rec| rec.into())
+        .collect();
+        let requests = self.attach_fulfillments(requests).await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.get_unconfirmed_requests",
+            start.elapsed()
+        );
+
+        Ok(requests)
+    }
+
+    pub async fn delete_old_unfulfilled_requests(
+        &mut self,
+        // The time that has to be passed since the
+        // request has been considered invalid to delete it
+        deleting_threshold: chrono::Duration,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+
+        let now = Utc::now();
+        let oldest_allowed = now.sub(deleting_threshold);
+        let mut transaction = self.0.start_transaction().await?;
+
+        // The requests not paid for in time are kept expired until the threshold passes
+        sqlx::query!(
+            r#"
+            UPDATE forced_exit_requests
+                SET status = $1
+                WHERE status = $2 AND valid_until < $3
+            "#,
+            ForcedExitLifecycleStatus::Expired.as_str(),
+            ForcedExitLifecycleStatus::Created.as_str(),
+            now
+        )
+        .execute(transaction.conn())
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM forced_exit_requests
+            WHERE fulfilled_by IS NULL AND valid_until < $1 AND NOT EXISTS (
+                SELECT 1 FROM forced_exit_fulfillments WHERE request_id = forced_exit_requests.id
+            ) AND id NOT IN (
+                SELECT request_id FROM forced_exit_requests_escalations
+            ) AND id NOT IN (
+                SELECT request_id FROM forced_exit_requests_active_targets
+            ) AND (public_id IS NULL OR public_id_released_at IS NOT NULL)
+            "#,
+            oldest_allowed
+        )
+        .execute(transaction.conn())
+        .await?;
+
+        transaction.commit().await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.delete_old_unfulfilled_requests",
+            start.elapsed()
+        );
+
+        Ok(())
+    }
+
+    /// Records the permanent failure of the `ForcedExit` transaction for the token
+    /// of the request, returns the number of failures recorded so far.
+    pub async fn record_failure(
+        &mut self,
+        id: ForcedExitRequestId,
+        token: TokenId,
+    ) -> QueryResult<u32> {
+        let start = Instant::now();
+
+        let record = sqlx::query!(
+            r#"
+            INSERT INTO forced_exit_requests_failures ( request_id, token_id, failures_count )
+            VALUES ( $1, $2, 1 )
+            ON CONFLICT ( request_id, token_id )
+            DO UPDATE SET failures_count = forced_exit_requests_failures.failures_count + 1
+            RETURNING failures_count
+            "#,
+            id,
+            token.0 as i32
+        )
+        .fetch_one(self.0.conn())
+        .await?;
+
+        metrics::histogram!("sql.forced_exit_requests.record_failure", start.elapsed());
+        Ok(record.failures_count as u32)
+    }
+
+    /// Records the payment for the request which could not be processed in any of the attempts,
+    /// replacing the failure recorded before. Nothing is recorded for the unknown requests.
+    pub async fn store_processing_failure(
+        &mut self,
+        failure: &ForcedExitProcessingFailure,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+        let submission_error = failure.submission_error.as_ref().map(|submission_error| {
+            serde_json::to_value(submission_error)
+                .expect("Failed to serialize the submission error")
+        });
+        let mut transaction = self.0.start_transaction().await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO forced_exit_requests_processing_failures ( request_id, attempts, error, failed_at, submission_error )
+            SELECT $1, $2, $3, $4, $5
+            WHERE EXISTS (SELECT 1 FROM forced_exit_requests WHERE id = $1)
+            ON CONFLICT (request_id) DO UPDATE
+                SET attempts = EXCLUDED.attempts, error = EXCLUDED.error, failed_at = EXCLUDED.failed_at,
+                    submission_error = EXCLUDED.submission_error
+            "#,
+            failure.request_id,
+            failure.attempts as i32,
+            failure.error,
+            failure.failed_at,
+            submission_error
+        )
+        .execute(transaction.conn())
+        .await?;
+        // The sent transactions are still awaited, they may fulfill the request anyway
+        sqlx::query!(
+            r#"
+            UPDATE forced_exit_requests
+                SET status = $1
+                WHERE id = $2 AND status NOT IN ( $3, $4 )
+            "#,
+            ForcedExitLifecycleStatus::Failed.as_str(),
+            failure.request_id,
+            ForcedExitLifecycleStatus::TxsSent.as_str(),
+            ForcedExitLifecycleStatus::Fulfilled.as_str()
+        )
+        .execute(transaction.conn())
+        .await?;
+
+        transaction.commit().await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.store_processing_failure",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    /// Loads the last processing failure of the request, if any.
+    pub async fn get_processing_failure(
+        &mut self,
+        id: ForcedExitRequestId,
+    ) -> QueryResult<Option<ForcedExitProcessingFailure>> {
+        let start = Instant::now();
+
+        let failure = sqlx::query_as!(
+            DbForcedExitProcessingFailure,
+            r#"
+            SELECT * FROM forced_exit_requests_processing_failures
+            WHERE request_id = $1
+            "#,
+            id
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(ForcedExitProcessingFailure::from);
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.get_processing_failure",
+            start.elapsed()
+        );
+        Ok(failure)
+    }
+
+    /// Records the version of the pipeline the request has been handled with at the stage,
+    /// replacing the one recorded for the stage before, e.g. by the fulfillment of the request
+    /// processed again. Nothing is recorded for the unknown requests.
+    pub async fn store_pipeline_version(
+        &mut self,
+        version: &ForcedExitPipelineVersion,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO forced_exit_requests_pipeline_versions ( request_id, stage, version, config_hash, recorded_at )
+            SELECT $1, $2, $3, $4, $5
+            WHERE EXISTS (SELECT 1 FROM forced_exit_requests WHERE id = $1)
+            ON CONFLICT (request_id, stage) DO UPDATE
+                SET version = EXCLUDED.version, config_hash = EXCLUDED.config_hash, recorded_at = EXCLUDED.recorded_at
+            "#,
+            version.request_id,
+            version.stage.as_str(),
+            version.version as i32,
+            version.config_hash,
+            version.recorded_at
+        )
+        .execute(self.0.conn())
+        .await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.store_pipeline_version",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    /// Loads the versions of the pipeline the request has been handled with, in the order of the stages.
+    pub async fn load_pipeline_versions(
+        &mut self,
+        id: ForcedExitRequestId,
+    ) -> QueryResult<Vec<ForcedExitPipelineVersion>> {
+        let start = Instant::now();
+
+        let versions = sqlx::query_as!(
+            DbForcedExitPipelineVersion,
+            r#"
+            SELECT * FROM forced_exit_requests_pipeline_versions
+            WHERE request_id = $1
+            ORDER BY recorded_at, stage
+            "#,
+            id
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(ForcedExitPipelineVersion::from)
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_pipeline_versions",
+            start.elapsed()
+        );
+        Ok(versions)
+    }
+
+    /// Stores the request escalated to L1. Repeated escalations of the same request are ignored.
+    pub async fn store_escalation(
+        &mut self,
+        escalation: ForcedExitRequestEscalation,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+        let escalation = DbForcedExitRequestEscalation::from(escalation);
+        let mut transaction = self.0.start_transaction().await?;
+
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO forced_exit_requests_escalations ( request_id, full_exits, created_at )
+            VALUES ( $1, $2, $3 )
+            ON CONFLICT ( request_id ) DO NOTHING
+            "#,
+            escalation.request_id,
+            escalation.full_exits,
+            escalation.created_at
+        )
+        .execute(transaction.conn())
+        .await?
+        .rows_affected();
+        if inserted > 0 {
+            transaction
+                .forced_exit_requests_schema()
+                .enqueue_delivery(
+                    escalation.request_id,
+                    ForcedExitRequestEvent::Escalated,
+                    escalation.created_at,
+                )
+                .await?;
+        }
+
+        transaction.commit().await?;
+
+        metrics::histogram!("sql.forced_exit_requests.store_escalation", start.elapsed());
+        Ok(())
+    }
+
+    pub async fn get_escalation(
+        &mut self,
+        id: ForcedExitRequestId,
+    ) -> QueryResult<Option<ForcedExitRequestEscalation>> {
+        let start = Instant::now();
+
+        let escalation = sqlx::query_as!(
+            DbForcedExitRequestEscalation,
+            r#"
+            SELECT * FROM forced_exit_requests_escalations
+            WHERE request_id = $1
+            "#,
+            id
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(|escalation| escalation.into());
+
+        metrics::histogram!("sql.forced_exit_requests.get_escalation", start.elapsed());
+        Ok(escalation)
+    }
+
+    /// Loads the escalated requests which still await for the `FullExit` operations to be sent.
+    pub async fn get_pending_escalations(
+        &mut self,
+    ) -> QueryResult<Vec<ForcedExitRequestEscalation>> {
+        let start = Instant::now();
+
+        let escalations = sqlx::query_as!(
+            DbForcedExitRequestEscalation,
+            r#"
+            SELECT * FROM forced_exit_requests_escalations
+            WHERE finalized_at IS NULL
+            ORDER BY created_at
+            "#
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(|escalation| escalation.into())
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.get_pending_escalations",
+            start.elapsed()
+        );
+        Ok(escalations)
+    }
+
+    /// Records the L1 transaction that has executed the `FullExit` operations
+    /// of the escalated request and marks the request as fulfilled.
+    pub async fn finalize_escalation(
+        &mut self,
+        id: ForcedExitRequestId,
+        l1_tx_hash: H256,
+        finalized_at: DateTime<Utc>,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+        let mut transaction = self.0.start_transaction().await?;
+
+        let l1_tx_hash = hex::encode(l1_tx_hash.as_bytes());
+        sqlx::query!(
+            r#"
+            UPDATE forced_exit_requests_escalations
+                SET l1_tx_hash = $1, finalized_at = $2
+                WHERE request_id = $3
+            "#,
+            l1_tx_hash,
+            finalized_at,
+            id
+        )
+        .execute(transaction.conn())
+        .await?;
+        transaction
+            .forced_exit_requests_schema()
+            .set_fulfilled_at(id, finalized_at)
+            .await?;
+
+        transaction.commit().await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.finalize_escalation",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    pub async fn store_api_key(
+        &mut self,
+        api_key: SaveForcedExitRequestsApiKeyQuery,
+    ) -> QueryResult<ForcedExitRequestsApiKey> {
+        let start = Instant::now();
+
+        let key_hash = hex::encode(api_key.key_hash.as_bytes());
+        let stored: DbForcedExitRequestsApiKey = sqlx::query_as!(
+            DbForcedExitRequestsApiKey,
+            r#"
+            INSERT INTO forced_exit_requests_api_keys
+                ( label, key_hash, max_tokens_per_request, max_requests_per_hour, created_at )
+            VALUES ( $1, $2, $3, $4, $5 )
+            RETURNING *
+            "#,
+            api_key.label,
+            key_hash,
+            api_key.max_tokens_per_request.map(i32::from),
+            api_key.max_requests_per_hour.map(|limit| limit as i32),
+            api_key.created_at
+        )
+        .fetch_one(self.0.conn())
+        .await?;
+
+        metrics::histogram!("sql.forced_exit_requests.store_api_key", start.elapsed());
+        Ok(stored.into())
+    }
+
+    pub async fn get_api_key(
+        &mut self,
+        id: ForcedExitRequestsApiKeyId,
+    ) -> QueryResult<Option<ForcedExitRequestsApiKey>> {
+        let start = Instant::now();
+
+        let api_key = sqlx::query_as!(
+            DbForcedExitRequestsApiKey,
+            r#"
+            SELECT * FROM forced_exit_requests_api_keys
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(|api_key| api_key.into());
+
+        metrics::histogram!("sql.forced_exit_requests.get_api_key", start.elapsed());
+        Ok(api_key)
+    }
+
+    /// Looks up the key by the hash of its value, the revoked keys are returned as well.
+    pub async fn get_api_key_by_hash(
+        &mut self,
+        key_hash: H256,
+    ) -> QueryResult<Option<ForcedExitRequestsApiKey>> {
+        let start = Instant::now();
+
+        let key_hash = hex::encode(key_hash.as_bytes());
+        let api_key = sqlx::query_as!(
+            DbForcedExitRequestsApiKey,
+            r#"
+            SELECT * FROM forced_exit_requests_api_keys
+            WHERE key_hash = $1
+            "#,
+            key_hash
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(|api_key| api_key.into());
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.get_api_key_by_hash",
+            start.elapsed()
+        );
+        Ok(api_key)
+    }
+
+    pub async fn load_api_keys(&mut self) -> QueryResult<Vec<ForcedExitRequestsApiKey>> {
+        let start = Instant::now();
+
+        let api_keys = sqlx::query_as!(
+            DbForcedExitRequestsApiKey,
+            r#"
+            SELECT * FROM forced_exit_requests_api_keys
+            ORDER BY id
+            "#
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(|api_key| api_key.into())
+        .collect();
+
+        metrics::histogram!("sql.forced_exit_requests.load_api_keys", start.elapsed());
+        Ok(api_keys)
+    }
+
+    /// Revokes the active key, returns `None` if there is no such key or it is already revoked.
+    pub async fn revoke_api_key(
+        &mut self,
+        id: ForcedExitRequestsApiKeyId,
+        revoked_at: DateTime<Utc>,
+    ) -> QueryResult<Option<ForcedExitRequestsApiKey>> {
+        let start = Instant::now();
+
+        let api_key = sqlx::query_as!(
+            DbForcedExitRequestsApiKey,
+            r#"
+            UPDATE forced_exit_requests_api_keys
+                SET revoked_at = $1
+                WHERE id = $2 AND revoked_at IS NULL
+            RETURNING *
+            "#,
+            revoked_at,
+            id
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(|api_key| api_key.into());
+
+        metrics::histogram!("sql.forced_exit_requests.revoke_api_key", start.elapsed());
+        Ok(api_key)
+    }
+
+    /// Stores the request and attributes it to the API key it was created with.
+    pub async fn store_request_with_api_key(
+        &mut self,
+        request: SaveForcedExitRequestQuery,
+        api_key_id: ForcedExitRequestsApiKeyId,
+    ) -> QueryResult<ForcedExitRequest> {
+        let start = Instant::now();
+        let mut transaction = self.0.start_transaction().await?;
+
+        let stored_request = transaction
+            .forced_exit_requests_schema()
+            .store_request(request)
+            .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO forced_exit_requests_api_key_usages ( request_id, api_key_id )
+            VALUES ( $1, $2 )
+            "#,
+            stored_request.id,
+            api_key_id
+        )
+        .execute(transaction.conn())
+        .await?;
+
+        transaction.commit().await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.store_request_with_api_key",
+            start.elapsed()
+        );
+        Ok(stored_request)
+    }
+
+    /// Returns the id of the API key the request was created with, if any.
+    pub async fn get_request_api_key_id(
+        &mut self,
+        id: ForcedExitRequestId,
+    ) -> QueryResult<Option<ForcedExitRequestsApiKeyId>> {
+        let start = Instant::now();
+
+        let api_key_id = sqlx::query!(
+            r#"
+            SELECT api_key_id FROM forced_exit_requests_api_key_usages
+            WHERE request_id = $1
+            "#,
+            id
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(|record| record.api_key_id);
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.get_request_api_key_id",
+            start.elapsed()
+        );
+        Ok(api_key_id)
+    }
+
+    /// Counts the requests created since the given moment with the API key
+    /// or, if the key is not specified, anonymously.
+    pub async fn count_requests_created_since(
+        &mut self,
+        api_key_id: Option<ForcedExitRequestsApiKeyId>,
+        since: DateTime<Utc>,
+    ) -> QueryResult<u32> {
+        let start = Instant::now();
+
+        let count = match api_key_id {
+            Some(api_key_id) => {
+                sqlx::query!(
+                    r#"
+                    SELECT COUNT(*) as "count!" FROM forced_exit_requests
+                    INNER JOIN forced_exit_requests_api_key_usages
+                        ON forced_exit_requests_api_key_usages.request_id = forced_exit_requests.id
+                    WHERE api_key_id = $1 AND created_at >= $2
+                    "#,
+                    api_key_id,
+                    since
+                )
+                .fetch_one(self.0.conn())
+                .await?
+                .count
+            }
+            None => {
+                sqlx::query!(
+                    r#"
+                    SELECT COUNT(*) as "count!" FROM forced_exit_requests
+                    WHERE created_at >= $1 AND id NOT IN (
+                        SELECT request_id FROM forced_exit_requests_api_key_usages
+                    )
+                    "#,
+                    since
+                )
+                .fetch_one(self.0.conn())
+                .await?
+                .count
+            }
+        };
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.count_requests_created_since",
+            start.elapsed()
+        );
+        Ok(count as u32)
+    }
+
+    /// Records the payment processed by the watcher, the payer is encrypted
+    /// if the encryption of the sensitive columns is configured.
+    pub async fn store_payment(&mut self, payment: &ForcedExitPayment) -> QueryResult<()> {
+        self.store_payment_with_cipher(payment, column_cipher())
+            .await
+    }
+
+    pub(crate) async fn store_payment_with_cipher(
+        &mut self,
+        payment: &ForcedExitPayment,
+        cipher: Option<&ColumnCipher>,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+
+        let amount = amount_to_big_decimal(&payment.amount);
+        let eth_tx_hash = payment.eth_tx_hash.map(|hash| hex::encode(hash.as_bytes()));
+        let payer = payment.payer.as_ref().map(address_to_stored_string);
+        let (payer, payer_hash) = match (payer, cipher) {
+            (Some(payer), Some(cipher)) => {
+                (Some(cipher.encrypt(&payer)), Some(cipher.index(&payer)))
+            }
+            (payer, _) => (payer, None),
+        };
+        sqlx::query!(
+            r#"
+            INSERT INTO forced_exit_requests_payments
+                ( amount, request_id, block_number, eth_tx_hash, payer, payer_hash, received_at, source )
+            VALUES ( $1, $2, $3, $4, $5, $6, $7, $8 )
+            "#,
+            amount,
+            payment.request_id,
+            payment.block_number as i64,
+            eth_tx_hash,
+            payer,
+            payer_hash,
+            payment.received_at,
+            payment.source.as_str()
+        )
+        .execute(self.0.conn())
+        .await?;
+
+        metrics::histogram!("sql.forced_exit_requests.store_payment", start.elapsed());
+        Ok(())
+    }
+
+    /// Loads the payments made within the block range (inclusive) in the order
+    /// they were processed.
+    pub async fn load_payments(
+        &mut self,
+        from_block: u64,
+        to_block: u64,
+    ) -> QueryResult<Vec<ForcedExitPayment>> {
+        self.load_payments_with_cipher(from_block, to_block, column_cipher())
+            .await
+    }
+
+    pub(crate) async fn load_payments_with_cipher(
+        &mut self,
+        from_block: u64,
+        to_block: u64,
+        cipher: Option<&ColumnCipher>,
+    ) -> QueryResult<Vec<ForcedExitPayment>> {
+        let start = Instant::now();
+
+        let payments = sqlx::query_as!(
+            DbForcedExitPayment,
+            r#"
+            SELECT * FROM forced_exit_requests_payments
+            WHERE block_number BETWEEN $1 AND $2
+            ORDER BY id
+            "#,
+            from_block as i64,
+            to_block as i64
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(|payment| decrypt_payment(cipher, payment))
+        .collect::<QueryResult<_>>()?;
+
+        metrics::histogram!("sql.forced_exit_requests.load_payments", start.elapsed());
+        Ok(payments)
+    }
+
+    /// Loads at most `limit` payments made within the block range (inclusive) along with
+    /// their ids, in the order they were processed. Only the payments processed after
+    /// the one with `after_id` are loaded, so the range is read page by page.
+    pub async fn load_payments_page(
+        &mut self,
+        from_block: u64,
+        to_block: u64,
+        after_id: Option<i64>,
+        limit: u32,
+    ) -> QueryResult<Vec<(i64, ForcedExitPayment)>> {
+        let start = Instant::now();
+        let cipher = column_cipher();
+
+        let payments = sqlx::query_as!(
+            DbForcedExitPayment,
+            r#"
+            SELECT * FROM forced_exit_requests_payments
+            WHERE block_number BETWEEN $1 AND $2 AND ($3::BIGINT IS NULL OR id > $3)
+            ORDER BY id
+            LIMIT $4
+            "#,
+            from_block as i64,
+            to_block as i64,
+            after_id,
+            i64::from(limit)
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(|payment| Ok((payment.id, decrypt_payment(cipher, payment)?)))
+        .collect::<QueryResult<_>>()?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_payments_page",
+            start.elapsed()
+        );
+        Ok(payments)
+    }
+
+    /// Loads the payments sent by the given address in the order they were processed.
+    pub async fn load_payments_by_payer(
+        &mut self,
+        payer: Address,
+    ) -> QueryResult<Vec<ForcedExitPayment>> {
+        self.load_payments_by_payer_with_cipher(payer, column_cipher())
+            .await
+    }
+
+    pub(crate) async fn load_payments_by_payer_with_cipher(
+        &mut self,
+        payer: Address,
+        cipher: Option<&ColumnCipher>,
+    ) -> QueryResult<Vec<ForcedExitPayment>> {
+        let start = Instant::now();
+
+        let payer = address_to_stored_string(&payer);
+        let payer_hash = cipher.map(|cipher| cipher.index(&payer));
+        // The plaintext values are only stored before the encryption is enabled
+        // and while the existing rows are being encrypted
+        let plaintext_payer = match cipher {
+            Some(cipher) if !cipher.is_mixed_mode() => None,
+            _ => Some(payer),
+        };
+        let payments = sqlx::query_as!(
+            DbForcedExitPayment,
+            r#"
+            SELECT * FROM forced_exit_requests_payments
+            WHERE payer_hash = $1 OR payer = $2
+            ORDER BY id
+            "#,
+            payer_hash,
+            plaintext_payer
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(|payment| decrypt_payment(cipher, payment))
+        .collect::<QueryResult<_>>()?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_payments_by_payer",
+            start.elapsed()
+        );
+        Ok(payments)
+    }
+
+    /// Loads the payments the request has been matched with or which have been refunded
+    /// for it, in the order they were processed. Out of the payments made by the same
+    /// transaction only the ones without the id or with the public id of the request are loaded.
+    pub async fn load_request_payments(
+        &mut self,
+        id: ForcedExitRequestId,
+        public_id: ForcedExitRequestId,
+    ) -> QueryResult<Vec<ForcedExitPayment>> {
+        let start = Instant::now();
+        let cipher = column_cipher();
+
+        let payments = sqlx::query_as!(
+            DbForcedExitPayment,
+            r#"
+            SELECT * FROM forced_exit_requests_payments
+            WHERE eth_tx_hash IN (
+                SELECT eth_tx_hash FROM forced_exit_requests_payment_matches WHERE request_id = $1
+                UNION
+                SELECT payment_tx_hash FROM forced_exit_refunds WHERE request_id = $1
+            )
+            AND (request_id IS NULL OR request_id = $2)
+            ORDER BY id
+            "#,
+            id,
+            public_id
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(|payment| decrypt_payment(cipher, payment))
+        .collect::<QueryResult<_>>()?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_request_payments",
+            start.elapsed()
+        );
+        Ok(payments)
+    }
+
+    /// Encrypts the payers of the payments stored before the encryption was enabled,
+    /// returns the number of the encrypted rows. Should be called until no rows are left.
+    pub async fn encrypt_payments_batch(
+        &mut self,
+        cipher: &ColumnCipher,
+        batch_size: u32,
+    ) -> QueryResult<usize> {
+        let start = Instant::now();
+        let mut transaction = self.0.start_transaction().await?;
+
+        let payments = sqlx::query!(
+            r#"
+            SELECT id, payer as "payer!" FROM forced_exit_requests_payments
+            WHERE payer IS NOT NULL AND payer_hash IS NULL
+            ORDER BY id
+            LIMIT $1
+            FOR UPDATE
+            "#,
+            i64::from(batch_size)
+        )
+        .fetch_all(transaction.conn())
+        .await?;
+
+        for payment in &payments {
+            // The rows without the index have been stored unencrypted
+            let payer = if is_encrypted(&payment.payer) {
+                cipher.decrypt(&payment.payer)?
+            } else {
+                payment.payer.clone()
+            };
+            sqlx::query!(
+                r#"
+                UPDATE forced_exit_requests_payments
+                    SET payer = $1, payer_hash = $2
+                    WHERE id = $3
+                "#,
+                cipher.encrypt(&payer),
+                cipher.index(&payer),
+                payment.id
+            )
+            .execute(transaction.conn())
+            .await?;
+        }
+        transaction.commit().await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.encrypt_payments_batch",
+            start.elapsed()
+        );
+        Ok(payments.len())
+    }
+
+    /// Enables or disables the processing of the payments of the source regardless of the config.
+    pub async fn set_payment_source_enabled(
+        &mut self,
+        source: PaymentSource,
+        enabled: bool,
+        updated_at: DateTime<Utc>,
+    ) -> QueryResult<PaymentSourceState> {
+        let start = Instant::now();
+
+        let state = sqlx::query_as!(
+            DbPaymentSourceState,
+            r#"
+            INSERT INTO forced_exit_requests_payment_sources ( source, enabled, updated_at )
+            VALUES ( $1, $2, $3 )
+            ON CONFLICT ( source ) DO UPDATE
+                SET enabled = $2, updated_at = $3
+            RETURNING *
+            "#,
+            source.as_str(),
+            enabled,
+            updated_at
+        )
+        .fetch_one(self.0.conn())
+        .await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.set_payment_source_enabled",
+            start.elapsed()
+        );
+        Ok(state.into())
+    }
+
+    /// Loads the states of the payment sources set by the operators.
+    pub async fn load_payment_source_states(&mut self) -> QueryResult<Vec<PaymentSourceState>> {
+        let start = Instant::now();
+
+        let states = sqlx::query_as!(
+            DbPaymentSourceState,
+            r#"
+            SELECT * FROM forced_exit_requests_payment_sources
+            ORDER BY source
+            "#
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(PaymentSourceState::from)
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_payment_source_states",
+            start.elapsed()
+        );
+        Ok(states)
+    }
+
+    pub async fn store_injected_payment(
+        &mut self,
+        payment: SaveInjectedForcedExitPaymentQuery,
+    ) -> QueryResult<InjectedForcedExitPayment> {
+        let start = Instant::now();
+
+        let amount = amount_to_big_decimal(&payment.amount);
+        let eth_tx_hash = payment.eth_tx_hash.map(|hash| hex::encode(hash.as_bytes()));
+        let payment = sqlx::query_as!(
+            DbInjectedForcedExitPayment,
+            r#"
+            INSERT INTO forced_exit_requests_injected_payments
+                ( amount, request_id, block_number, eth_tx_hash, created_at )
+            VALUES ( $1, $2, $3, $4, $5 )
+            RETURNING *
+            "#,
+            amount,
+            payment.request_id,
+            payment.block_number as i64,
+            eth_tx_hash,
+            payment.created_at
+        )
+        .fetch_one(self.0.conn())
+        .await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.store_injected_payment",
+            start.elapsed()
+        );
+        Ok(payment.into())
+    }
+
+    /// Loads the injected payments which are yet to be processed in the order they were injected.
+    pub async fn load_injected_payments(
+        &mut self,
+        limit: u32,
+    ) -> QueryResult<Vec<InjectedForcedExitPayment>> {
+        let start = Instant::now();
+
+        let payments = sqlx::query_as!(
+            DbInjectedForcedExitPayment,
+            r#"
+            SELECT * FROM forced_exit_requests_injected_payments
+            ORDER BY id
+            LIMIT $1
+            "#,
+            i64::from(limit)
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(InjectedForcedExitPayment::from)
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_injected_payments",
+            start.elapsed()
+        );
+        Ok(payments)
+    }
+
+    /// Removes the injected payment once it has been processed.
+    pub async fn delete_injected_payment(
+        &mut self,
+        id: InjectedForcedExitPaymentId,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+
+        sqlx::query!(
+            r#"
+            DELETE FROM forced_exit_requests_injected_payments
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(self.0.conn())
+        .await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.delete_injected_payment",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    /// Records the retry of the request to be picked up by the watcher.
+    pub async fn store_retry(
+        &mut self,
+        request_id: ForcedExitRequestId,
+        force: bool,
+        created_at: DateTime<Utc>,
+    ) -> QueryResult<ForcedExitRetry> {
+        let start = Instant::now();
+
+        let retry = sqlx::query_as!(
+            DbForcedExitRetry,
+            r#"
+            INSERT INTO forced_exit_requests_retries ( request_id, force, created_at )
+            VALUES ( $1, $2, $3 )
+            RETURNING *
+            "#,
+            request_id,
+            force,
+            created_at
+        )
+        .fetch_one(self.0.conn())
+        .await?;
+
+        metrics::histogram!("sql.forced_exit_requests.store_retry", start.elapsed());
+        Ok(retry.into())
+    }
+
+    pub async fn get_retry(
+        &mut self,
+        id: ForcedExitRetryId,
+    ) -> QueryResult<Option<ForcedExitRetry>> {
+        let start = Instant::now();
+
+        let retry = sqlx::query_as!(
+            DbForcedExitRetry,
+            r#"
+            SELECT * FROM forced_exit_requests_retries
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(ForcedExitRetry::from);
+
+        metrics::histogram!("sql.forced_exit_requests.get_retry", start.elapsed());
+        Ok(retry)
+    }
+
+    /// Loads the retries which are yet to be processed in the order they were requested.
+    pub async fn load_pending_retries(&mut self, limit: u32) -> QueryResult<Vec<ForcedExitRetry>> {
+        let start = Instant::now();
+
+        let retries = sqlx::query_as!(
+            DbForcedExitRetry,
+            r#"
+            SELECT * FROM forced_exit_requests_retries
+            WHERE processed_at IS NULL
+            ORDER BY id
+            LIMIT $1
+            "#,
+            i64::from(limit)
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(ForcedExitRetry::from)
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_pending_retries",
+            start.elapsed()
+        );
+        Ok(retries)
+    }
+
+    /// Records the outcome of the retry: the transactions sent for the request or the
+    /// reason it could not be sent.
+    pub async fn complete_retry(
+        &mut self,
+        id: ForcedExitRetryId,
+        tx_hashes: &[TxHash],
+        error: Option<&str>,
+        processed_at: DateTime<Utc>,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+
+        let tx_hashes = if tx_hashes.is_empty() {
+            None
+        } else {
+            Some(utils::vec_to_comma_list(tx_hashes.to_vec()))
+        };
+        sqlx::query!(
+            r#"
+            UPDATE forced_exit_requests_retries
+            SET processed_at = $2, tx_hashes = $3, error = $4
+            WHERE id = $1
+            "#,
+            id,
+            processed_at,
+            tx_hashes,
+            error
+        )
+        .execute(self.0.conn())
+        .await?;
+
+        metrics::histogram!("sql.forced_exit_requests.complete_retry", start.elapsed());
+        Ok(())
+    }
+
+    /// Records the payment set aside before the matching. The amount is stored as text,
+    /// so the payment is recorded whatever the payer has sent.
+    pub async fn store_unmatched_payment(
+        &mut self,
+        payment: &ForcedExitPayment,
+        reason: UnmatchedPaymentReason,
+    ) -> QueryResult<UnmatchedForcedExitPayment> {
+        let start = Instant::now();
+
+        let eth_tx_hash = payment.eth_tx_hash.map(|hash| hex::encode(hash.as_bytes()));
+        let payment = sqlx::query_as!(
+            DbUnmatchedForcedExitPayment,
+            r#"
+            INSERT INTO forced_exit_requests_unmatched_payments
+                ( amount, request_id, block_number, eth_tx_hash, source, reason, received_at )
+            VALUES ( $1, $2, $3, $4, $5, $6, $7 )
+            RETURNING *
+            "#,
+            payment.amount.to_string(),
+            payment.request_id,
+            payment.block_number as i64,
+            eth_tx_hash,
+            payment.source.as_str(),
+            reason.as_str(),
+            payment.received_at
+        )
+        .fetch_one(self.0.conn())
+        .await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.store_unmatched_payment",
+            start.elapsed()
+        );
+        Ok(payment.into())
+    }
+
+    /// Loads the latest payments set aside before the matching.
+    pub async fn load_unmatched_payments(
+        &mut self,
+        limit: u32,
+    ) -> QueryResult<Vec<UnmatchedForcedExitPayment>> {
+        let start = Instant::now();
+
+        let payments = sqlx::query_as!(
+            DbUnmatchedForcedExitPayment,
+            r#"
+            SELECT * FROM forced_exit_requests_unmatched_payments
+            ORDER BY id DESC
+            LIMIT $1
+            "#,
+            i64::from(limit)
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(UnmatchedForcedExitPayment::from)
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_unmatched_payments",
+            start.elapsed()
+        );
+        Ok(payments)
+    }
+
+    /// Records that the request has been matched with the payment made by the given
+    /// L1 transaction. The same match recorded once again is ignored.
+    pub async fn store_payment_match(
+        &mut self,
+        id: ForcedExitRequestId,
+        eth_tx_hash: H256,
+        matched_at: DateTime<Utc>,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO forced_exit_requests_payment_matches ( eth_tx_hash, request_id, matched_at )
+            VALUES ( $1, $2, $3 )
+            ON CONFLICT DO NOTHING
+            "#,
+            hex::encode(eth_tx_hash.as_bytes()),
+            id,
+            matched_at
+        )
+        .execute(self.0.conn())
+        .await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.store_payment_match",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    /// Registers the L1 transaction the payer is about to pay for the request with.
+    /// Returns `false` if the transaction has already been registered for another request.
+    pub async fn store_expected_payment(
+        &mut self,
+        id: ForcedExitRequestId,
+        eth_tx_hash: H256,
+        registered_at: DateTime<Utc>,
+    ) -> QueryResult<bool> {
+        let start = Instant::now();
+
+        // Registering the transaction for the same request once again changes nothing
+        let registered = sqlx::query!(
+            r#"
+            INSERT INTO forced_exit_requests_expected_payments ( eth_tx_hash, request_id, registered_at )
+            VALUES ( $1, $2, $3 )
+            ON CONFLICT (eth_tx_hash) DO UPDATE
+                SET registered_at = forced_exit_requests_expected_payments.registered_at
+                WHERE forced_exit_requests_expected_payments.request_id = EXCLUDED.request_id
+            RETURNING request_id
+            "#,
+            hex::encode(eth_tx_hash.as_bytes()),
+            id,
+            registered_at
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .is_some();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.store_expected_payment",
+            start.elapsed()
+        );
+        Ok(registered)
+    }
+
+    /// Loads the payment registered in advance with the given L1 transaction, if any.
+    pub async fn get_expected_payment(
+        &mut self,
+        eth_tx_hash: H256,
+    ) -> QueryResult<Option<ExpectedForcedExitPayment>> {
+        let start = Instant::now();
+
+        let expected = sqlx::query_as!(
+            DbExpectedForcedExitPayment,
+            r#"
+            SELECT * FROM forced_exit_requests_expected_payments
+            WHERE eth_tx_hash = $1
+            "#,
+            hex::encode(eth_tx_hash.as_bytes())
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(ExpectedForcedExitPayment::from);
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.get_expected_payment",
+            start.elapsed()
+        );
+        Ok(expected)
+    }
+
+    /// Loads the payments registered in advance for the request, in the order of registration.
+    pub async fn load_expected_payments(
+        &mut self,
+        id: ForcedExitRequestId,
+    ) -> QueryResult<Vec<ExpectedForcedExitPayment>> {
+        let start = Instant::now();
+
+        let expected = sqlx::query_as!(
+            DbExpectedForcedExitPayment,
+            r#"
+            SELECT * FROM forced_exit_requests_expected_payments
+            WHERE request_id = $1
+            ORDER BY registered_at, eth_tx_hash
+            "#,
+            id
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(ExpectedForcedExitPayment::from)
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_expected_payments",
+            start.elapsed()
+        );
+        Ok(expected)
+    }
+
+    /// Records the amount paid by the registered transaction, which differs from the one
+    /// the request asks for.
+    pub async fn flag_expected_payment(
+        &mut self,
+        eth_tx_hash: H256,
+        paid: &BigUint,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+
+        sqlx::query!(
+            r#"
+            UPDATE forced_exit_requests_expected_payments
+                SET mismatched_amount = $1
+                WHERE eth_tx_hash = $2
+            "#,
+            paid.to_string(),
+            hex::encode(eth_tx_hash.as_bytes())
+        )
+        .execute(self.0.conn())
+        .await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.flag_expected_payment",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    /// Loads the requests matched with the payments made by the given L1 transaction,
+    /// a single transaction may pay for several requests.
+    pub async fn load_requests_by_payment(
+        &mut self,
+        eth_tx_hash: H256,
+    ) -> QueryResult<Vec<ForcedExitRequest>> {
+        let start = Instant::now();
+
+        let requests = sqlx::query_as!(
+            DbForcedExitRequest,
+            r#"
+            SELECT forced_exit_requests.* FROM forced_exit_requests
+            INNER JOIN forced_exit_requests_payment_matches
+                ON forced_exit_requests_payment_matches.request_id = forced_exit_requests.id
+            WHERE forced_exit_requests_payment_matches.eth_tx_hash = $1
+            ORDER BY forced_exit_requests.id
+            "#,
+            hex::encode(eth_tx_hash.as_bytes())
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(ForcedExitRequest::from)
+        .collect();
+        let requests = self.attach_fulfillments(requests).await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_requests_by_payment",
+            start.elapsed()
+        );
+        Ok(requests)
+    }
+
+    /// Loads the payments made by the given L1 transaction, which were set aside
+    /// before the matching.
+    pub async fn load_unmatched_payments_by_tx_hash(
+        &mut self,
+        eth_tx_hash: H256,
+    ) -> QueryResult<Vec<UnmatchedForcedExitPayment>> {
+        let start = Instant::now();
+
+        let payments = sqlx::query_as!(
+            DbUnmatchedForcedExitPayment,
+            r#"
+            SELECT * FROM forced_exit_requests_unmatched_payments
+            WHERE eth_tx_hash = $1
+            ORDER BY id
+            "#,
+            hex::encode(eth_tx_hash.as_bytes())
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(UnmatchedForcedExitPayment::from)
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_unmatched_payments_by_tx_hash",
+            start.elapsed()
+        );
+        Ok(payments)
+    }
+
+    /// Checks whether any payment made by the given L1 transaction has been processed.
+    pub async fn is_payment_recorded(&mut self, eth_tx_hash: H256) -> QueryResult<bool> {
+        let start = Instant::now();
+
+        let recorded = sqlx::query!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM forced_exit_requests_payments WHERE eth_tx_hash = $1
+            ) as "recorded!"
+            "#,
+            hex::encode(eth_tx_hash.as_bytes())
+        )
+        .fetch_one(self.0.conn())
+        .await?
+        .recorded;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.is_payment_recorded",
+            start.elapsed()
+        );
+        Ok(recorded)
+    }
+
+    /// Records the request, the target of which has become active. The request recorded
+    /// once again is ignored, so the policy applied first stays in effect.
+    ///
+    /// The payment is matched with the request, so it is found by its transaction hash.
+    pub async fn store_active_target(
+        &mut self,
+        active_target: ForcedExitRequestActiveTarget,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+        let payment_tx_hash = active_target.payment_tx_hash;
+        let active_target = DbForcedExitRequestActiveTarget::from(active_target);
+        let mut transaction = self.0.start_transaction().await?;
+
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO forced_exit_requests_active_targets (
+                request_id, policy, target_nonce, payment_amount, payment_tx_hash,
+                match_scheme, paid_at, detected_at, hold_until, failed_at
+            )
+            VALUES ( $1, $2, $3, $4, $5, $6, $7, $8, $9, $10 )
+            ON CONFLICT ( request_id ) DO NOTHING
+            "#,
+            active_target.request_id,
+            active_target.policy,
+            active_target.target_nonce,
+            active_target.payment_amount,
+            active_target.payment_tx_hash,
+            active_target.match_scheme,
+            active_target.paid_at,
+            active_target.detected_at,
+            active_target.hold_until,
+            active_target.failed_at
+        )
+        .execute(transaction.conn())
+        .await?
+        .rows_affected();
+        // The payment has been matched, even though no transactions are sent for it yet
+        if let Some(payment_tx_hash) = payment_tx_hash {
+            transaction
+                .forced_exit_requests_schema()
+                .store_payment_match(
+                    active_target.request_id,
+                    payment_tx_hash,
+                    active_target.detected_at,
+                )
+                .await?;
+        }
+        match active_target.failed_at {
+            // The request failed right away, otherwise it is held and may still be fulfilled
+            Some(failed_at) if inserted > 0 => {
+                transaction
+                    .forced_exit_requests_schema()
+                    .set_status(active_target.request_id, ForcedExitLifecycleStatus::Failed)
+                    .await?;
+                transaction
+                    .forced_exit_requests_schema()
+                    .enqueue_delivery(
+                        active_target.request_id,
+                        ForcedExitRequestEvent::Failed,
+                        failed_at,
+                    )
+                    .await?;
+            }
+            _ => {}
+        }
+
+        transaction.commit().await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.store_active_target",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    pub async fn get_active_target(
+        &mut self,
+        id: ForcedExitRequestId,
+    ) -> QueryResult<Option<ForcedExitRequestActiveTarget>> {
+        let start = Instant::now();
+
+        let active_target = sqlx::query_as!(
+            DbForcedExitRequestActiveTarget,
+            r#"
+            SELECT * FROM forced_exit_requests_active_targets
+            WHERE request_id = $1
+            "#,
+            id
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(ForcedExitRequestActiveTarget::from);
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.get_active_target",
+            start.elapsed()
+        );
+        Ok(active_target)
+    }
+
+    /// Loads the held requests, which have been neither resumed nor failed yet.
+    /// The requests fulfilled some other way in the meantime are not held anymore.
+    pub async fn load_held_active_targets(
+        &mut self,
+    ) -> QueryResult<Vec<ForcedExitRequestActiveTarget>> {
+        let start = Instant::now();
+
+        let active_targets = sqlx::query_as!(
+            DbForcedExitRequestActiveTarget,
+            r#"
+            SELECT forced_exit_requests_active_targets.* FROM forced_exit_requests_active_targets
+            INNER JOIN forced_exit_requests
+                ON forced_exit_requests.id = forced_exit_requests_active_targets.request_id
+            WHERE hold_until IS NOT NULL AND resumed_at IS NULL AND failed_at IS NULL
+                AND forced_exit_requests.fulfilled_at IS NULL
+                AND forced_exit_requests.fulfilled_by IS NULL
+                AND NOT EXISTS (
+                    SELECT 1 FROM forced_exit_fulfillments
+                    WHERE request_id = forced_exit_requests.id
+                )
+                AND forced_exit_requests.id NOT IN (
+                    SELECT request_id FROM forced_exit_requests_escalations
+                )
+            ORDER BY detected_at
+            "#
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(ForcedExitRequestActiveTarget::from)
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_held_active_targets",
+            start.elapsed()
+        );
+        Ok(active_targets)
+    }
+
+    /// Marks the held request as resumed, it is fulfilled as usual afterwards.
+    pub async fn resume_active_target(
+        &mut self,
+        id: ForcedExitRequestId,
+        resumed_at: DateTime<Utc>,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+
+        sqlx::query!(
+            r#"
+            UPDATE forced_exit_requests_active_targets
+                SET resumed_at = $1
+                WHERE request_id = $2 AND resumed_at IS NULL AND failed_at IS NULL
+            "#,
+            resumed_at,
+            id
+        )
+        .execute(self.0.conn())
+        .await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.resume_active_target",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    /// Fails the held request, its payment is to be refunded.
+    pub async fn fail_active_target(
+        &mut self,
+        id: ForcedExitRequestId,
+        failed_at: DateTime<Utc>,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+        let mut transaction = self.0.start_transaction().await?;
+
+        let updated = sqlx::query!(
+            r#"
+            UPDATE forced_exit_requests_active_targets
+                SET failed_at = $1
+                WHERE request_id = $2 AND resumed_at IS NULL AND failed_at IS NULL
+            "#,
+            failed_at,
+            id
+        )
+        .execute(transaction.conn())
+        .await?
+        .rows_affected();
+        if updated > 0 {
+            transaction
+                .forced_exit_requests_schema()
+                .set_status(id, ForcedExitLifecycleStatus::Failed)
+                .await?;
+            transaction
+                .forced_exit_requests_schema()
+                .enqueue_delivery(id, ForcedExitRequestEvent::Failed, failed_at)
+                .await?;
+        }
+
+        transaction.commit().await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.fail_active_target",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    /// Leaves the tokens out of the request and records why they are not withdrawn.
+    /// The rest of the tokens keep their order, so the transactions sent for the request
+    /// stay aligned with its tokens.
+    pub async fn skip_tokens(
+        &mut self,
+        id: ForcedExitRequestId,
+        skipped: &[SkippedForcedExit],
+        skipped_at: DateTime<Utc>,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+        let mut transaction = self.0.start_transaction().await?;
+
+        let tokens: Vec<i32> = skipped
+            .iter()
+            .map(|skipped| *skipped.token as i32)
+            .collect();
+        let reasons: Vec<String> = skipped
+            .iter()
+            .map(|skipped| skipped.reason.as_str().to_owned())
+            .collect();
+        sqlx::query!(
+            r#"
+            INSERT INTO forced_exit_requests_skipped_tokens ( request_id, token, reason, skipped_at )
+            SELECT $1, skipped.token, skipped.reason, $4
+                FROM UNNEST ($2::integer[], $3::text[]) AS skipped(token, reason)
+            ON CONFLICT (request_id, token)
+            DO UPDATE SET reason = EXCLUDED.reason, skipped_at = EXCLUDED.skipped_at
+            "#,
+            id,
+            &tokens,
+            &reasons,
+            skipped_at
+        )
+        .execute(transaction.conn())
+        .await?;
+        sqlx::query!(
+            r#"
+            UPDATE forced_exit_requests
+                SET tokens = array_to_string(
+                    ARRAY(
+                        SELECT token FROM unnest(string_to_array(tokens, ',')) WITH ORDINALITY AS requested(token, position)
+                        WHERE requested.token::INT <> ALL($2::integer[])
+                        ORDER BY position
+                    ),
+                    ','
+                )
+                WHERE id = $1
+            "#,
+            id,
+            &tokens
+        )
+        .execute(transaction.conn())
+        .await?;
+
+        transaction.commit().await?;
+
+        metrics::histogram!("sql.forced_exit_requests.skip_tokens", start.elapsed());
+        Ok(())
+    }
+
+    /// Loads the tokens of the request, which are not withdrawn, ordered by the token id.
+    pub async fn load_skipped_tokens(
+        &mut self,
+        id: ForcedExitRequestId,
+    ) -> QueryResult<Vec<SkippedForcedExit>> {
+        let start = Instant::now();
+
+        let skipped = sqlx::query_as!(
+            DbSkippedForcedExit,
+            r#"
+            SELECT token, reason FROM forced_exit_requests_skipped_tokens
+            WHERE request_id = $1
+            ORDER BY token
+            "#,
+            id
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(SkippedForcedExit::from)
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_skipped_tokens",
+            start.elapsed()
+        );
+        Ok(skipped)
+    }
+
+    /// Moves the request to the status, has to be called within the transaction
+    /// performing the transition.
+    async fn set_status(
+        &mut self,
+        id: ForcedExitRequestId,
+        status: ForcedExitLifecycleStatus,
+    ) -> QueryResult<()> {
+        sqlx::query!(
+            "UPDATE forced_exit_requests SET status = $1 WHERE id = $2",
+            status.as_str(),
+            id
+        )
+        .execute(self.0.conn())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Stores the notification about the status transition of the request,
+    /// has to be called within the transaction performing the transition.
+    async fn enqueue_delivery(
+        &mut self,
+        id: ForcedExitRequestId,
+        event: ForcedExitRequestEvent,
+        created_at: DateTime<Utc>,
+    ) -> QueryResult<()> {
+        // The request stays locked until the transition is committed, so the transitions
+        // of the same request made concurrently get the consecutive sequence numbers.
+        // Should the lock be missed, the unique index fails the insert instead
+        sqlx::query!(
+            "SELECT id FROM forced_exit_requests WHERE id = $1 FOR UPDATE",
+            id
+        )
+        .fetch_optional(self.0.conn())
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO forced_exit_requests_outbox ( request_id, sequence, event, created_at, next_attempt_at, pipeline_version )
+            SELECT $1, COALESCE(MAX(sequence), 0) + 1, $2, $3, $3, $4
+            FROM forced_exit_requests_outbox
+            WHERE request_id = $1
+            "#,
+            id,
+            event.as_str(),
+            created_at,
+            FORCED_EXIT_PIPELINE_VERSION as i32
+        )
+        .execute(self.0.conn())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Stores the callback of the fulfilled request, if it was created with the URL.
+    /// The body is taken right away, so all the attempts post the same one.
+    /// Has to be called within the transaction fulfilling the request.
+    async fn enqueue_callback(
+        &mut self,
+        id: ForcedExitRequestId,
+        fulfilled_at: DateTime<Utc>,
+    ) -> QueryResult<()> {
+        let callback_url = sqlx::query!(
+            "SELECT callback_url FROM forced_exit_requests WHERE id = $1",
+            id
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .and_then(|row| row.callback_url);
+        let callback_url = match callback_url {
+            Some(callback_url) => callback_url,
+            None => return Ok(()),
+        };
+        let request = match self.get_request_by_id(id).await? {
+            Some(request) => request,
+            None => return Ok(()),
+        };
+        let callback = ForcedExitFulfilledCallback {
+            request_id: request.public_id,
+            target: request.target,
+            tokens: request.tokens,
+            tx_hashes: request.fulfilled_by.unwrap_or_default(),
+            fulfilled_at,
+        };
+
+        // The callback takes the next number among the notifications of the request,
+        // which is locked by the notification of the same transition
+        sqlx::query!(
+            r#"
+            INSERT INTO forced_exit_requests_outbox ( request_id, sequence, event, created_at, next_attempt_at, pipeline_version, callback_url, callback )
+            SELECT $1, COALESCE(MAX(sequence), 0) + 1, $2, $3, $3, $4, $5, $6
+            FROM forced_exit_requests_outbox
+            WHERE request_id = $1
+            "#,
+            id,
+            ForcedExitRequestEvent::Fulfilled.as_str(),
+            fulfilled_at,
+            FORCED_EXIT_PIPELINE_VERSION as i32,
+            callback_url,
+            serde_json::to_value(callback).expect("Failed to serialize the callback")
+        )
+        .execute(self.0.conn())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Loads the undelivered notifications, the next attempt of which is due by `now`:
+    /// either the callbacks or the notifications posted to the webhook. The callbacks
+    /// which have run out of the attempts are not loaded.
+    pub async fn load_pending_deliveries(
+        &mut self,
+        now: DateTime<Utc>,
+        limit: u32,
+        callbacks: bool,
+    ) -> QueryResult<Vec<ForcedExitRequestDelivery>> {
+        let start = Instant::now();
+
+        let deliveries = sqlx::query_as!(
+            DbForcedExitRequestDelivery,
+            r#"
+            SELECT * FROM forced_exit_requests_outbox
+            WHERE delivered_at IS NULL AND failed_at IS NULL AND next_attempt_at <= $1
+                AND (callback_url IS NOT NULL) = $3
+            ORDER BY id
+            LIMIT $2
+            "#,
+            now,
+            i64::from(limit),
+            callbacks
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(|delivery| delivery.into())
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_pending_deliveries",
+            start.elapsed()
+        );
+        Ok(deliveries)
+    }
+
+    /// Loads all the notifications about the transitions of the request
+    /// in the order the transitions were made.
+    pub async fn load_request_deliveries(
+        &mut self,
+        id: ForcedExitRequestId,
+    ) -> QueryResult<Vec<ForcedExitRequestDelivery>> {
+        let start = Instant::now();
+
+        let deliveries = sqlx::query_as!(
+            DbForcedExitRequestDelivery,
+            r#"
+            SELECT * FROM forced_exit_requests_outbox
+            WHERE request_id = $1
+            ORDER BY sequence
+            "#,
+            id
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(|delivery| delivery.into())
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_request_deliveries",
+            start.elapsed()
+        );
+        Ok(deliveries)
+    }
+
+    pub async fn mark_delivered(
+        &mut self,
+        id: ForcedExitRequestDeliveryId,
+        delivered_at: DateTime<Utc>,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+
+        sqlx::query!(
+            r#"
+            UPDATE forced_exit_requests_outbox
+                SET attempts = attempts + 1, delivered_at = $1, last_error = NULL
+                WHERE id = $2
+            "#,
+            delivered_at,
+            id
+        )
+        .execute(self.0.conn())
+        .await?;
+
+        metrics::histogram!("sql.forced_exit_requests.mark_delivered", start.elapsed());
+        Ok(())
+    }
+
+    /// Records the failed delivery attempt and postpones the next one.
+    pub async fn record_delivery_failure(
+        &mut self,
+        id: ForcedExitRequestDeliveryId,
+        error: &str,
+        next_attempt_at: DateTime<Utc>,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+
+        sqlx::query!(
+            r#"
+            UPDATE forced_exit_requests_outbox
+                SET attempts = attempts + 1, last_error = $1, next_attempt_at = $2
+                WHERE id = $3
+            "#,
+            error,
+            next_attempt_at,
+            id
+        )
+        .execute(self.0.conn())
+        .await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.record_delivery_failure",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    /// Records the last failed attempt of the callback, it is not attempted again until redelivered.
+    pub async fn mark_delivery_failed(
+        &mut self,
+        id: ForcedExitRequestDeliveryId,
+        error: &str,
+        failed_at: DateTime<Utc>,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+
+        sqlx::query!(
+            r#"
+            UPDATE forced_exit_requests_outbox
+                SET attempts = attempts + 1, last_error = $1, failed_at = $2
+                WHERE id = $3
+            "#,
+            error,
+            failed_at,
+            id
+        )
+        .execute(self.0.conn())
+        .await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.mark_delivery_failed",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    pub async fn get_delivery(
+        &mut self,
+        id: ForcedExitRequestDeliveryId,
+    ) -> QueryResult<Option<ForcedExitRequestDelivery>> {
+        let start = Instant::now();
+
+        let delivery = sqlx::query_as!(
+            DbForcedExitRequestDelivery,
+            "SELECT * FROM forced_exit_requests_outbox WHERE id = $1",
+            id
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(|delivery| delivery.into());
+
+        metrics::histogram!("sql.forced_exit_requests.get_delivery", start.elapsed());
+        Ok(delivery)
+    }
+
+    /// Loads the callbacks which have run out of the attempts, the latest ones first.
+    pub async fn load_failed_deliveries(
+        &mut self,
+        limit: u32,
+    ) -> QueryResult<Vec<ForcedExitRequestDelivery>> {
+        let start = Instant::now();
+
+        let deliveries = sqlx::query_as!(
+            DbForcedExitRequestDelivery,
+            r#"
+            SELECT * FROM forced_exit_requests_outbox
+            WHERE failed_at IS NOT NULL
+            ORDER BY failed_at DESC, id DESC
+            LIMIT $1
+            "#,
+            i64::from(limit)
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(|delivery| delivery.into())
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_failed_deliveries",
+            start.elapsed()
+        );
+        Ok(deliveries)
+    }
+
+    /// Makes the failed callback due right away with all the attempts available again.
+    /// Returns `None` if there is no such failed callback.
+    pub async fn redeliver(
+        &mut self,
+        id: ForcedExitRequestDeliveryId,
+        now: DateTime<Utc>,
+    ) -> QueryResult<Option<ForcedExitRequestDelivery>> {
+        let start = Instant::now();
+
+        let delivery = sqlx::query_as!(
+            DbForcedExitRequestDelivery,
+            r#"
+            UPDATE forced_exit_requests_outbox
+                SET attempts = 0, failed_at = NULL, next_attempt_at = $1
+                WHERE id = $2 AND failed_at IS NOT NULL
+                RETURNING *
+            "#,
+            now,
+            id
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(|delivery| delivery.into());
+
+        metrics::histogram!("sql.forced_exit_requests.redeliver", start.elapsed());
+        Ok(delivery)
+    }
+
+    /// Tries to take the lock allowing the instance to process the requests, the instance
+    /// name is set for the session so the rest of the instances can see who holds the lock.
+    ///
+    /// The lock belongs to the database session and is released once it is closed, so it is
+    /// only to be taken with the connection established for that: the pooled connections
+    /// are shared and would keep the lock when returned to the pool.
+    pub async fn try_acquire_singleton_lock(&mut self, instance: &str) -> QueryResult<bool> {
+        let start = Instant::now();
+
+        sqlx::query!("SELECT set_config('application_name', $1, false)", instance)
+            .fetch_one(self.0.conn())
+            .await?;
+        let acquired = sqlx::query!(
+            r#"SELECT pg_try_advisory_lock($1) AS "acquired!""#,
+            SINGLETON_LOCK_KEY
+        )
+        .fetch_one(self.0.conn())
+        .await?
+        .acquired;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.try_acquire_singleton_lock",
+            start.elapsed()
+        );
+        Ok(acquired)
+    }
+
+    /// Whether the lock is still held by the session, e.g. it may have been
+    /// released by the operators terminating the session.
+    pub async fn singleton_lock_held(&mut self) -> QueryResult<bool> {
+        let start = Instant::now();
+
+        let held = sqlx::query!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM pg_locks
+                WHERE locktype = 'advisory' AND classid = 0 AND objid::BIGINT = $1 AND objsubid = 1
+                    AND granted AND pid = pg_backend_pid()
+            ) AS "held!"
+            "#,
+            SINGLETON_LOCK_KEY
+        )
+        .fetch_one(self.0.conn())
+        .await?
+        .held;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.singleton_lock_held",
+            start.elapsed()
+        );
+        Ok(held)
+    }
+
+    /// Releases the lock held by the session, returns `false` if it was not held.
+    pub async fn release_singleton_lock(&mut self) -> QueryResult<bool> {
+        let start = Instant::now();
+
+        let released = sqlx::query!(
+            r#"SELECT pg_advisory_unlock($1) AS "released!""#,
+            SINGLETON_LOCK_KEY
+        )
+        .fetch_one(self.0.conn())
+        .await?
+        .released;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.release_singleton_lock",
+            start.elapsed()
+        );
+        Ok(released)
+    }
+
+    /// Loads the instance holding the lock, if any.
+    pub async fn load_singleton_holder(
+        &mut self,
+    ) -> QueryResult<Option<ForcedExitSingletonHolder>> {
+        let start = Instant::now();
+
+        let holder = sqlx::query!(
+            r#"
+            SELECT activity.application_name AS "instance!", activity.pid AS "session_pid!"
+            FROM pg_locks locks
+            INNER JOIN pg_stat_activity activity ON activity.pid = locks.pid
+            WHERE locks.locktype = 'advisory' AND locks.classid = 0
+                AND locks.objid::BIGINT = $1 AND locks.objsubid = 1 AND locks.granted
+            LIMIT 1
+            "#,
+            SINGLETON_LOCK_KEY
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(|holder| ForcedExitSingletonHolder {
+            instance: holder.instance,
+            session_pid: holder.session_pid,
+        });
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_singleton_holder",
+            start.elapsed()
+        );
+        Ok(holder)
+    }
+
+    pub async fn store_backlog_report(
+        &mut self,
+        report: &ForcedExitBacklogReport,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+        let serialized =
+            serde_json::to_value(report).expect("Failed to serialize the backlog report");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO forced_exit_requests_backlog_reports ( created_at, report )
+            VALUES ( $1, $2 )
+            "#,
+            report.created_at,
+            serialized
+        )
+        .execute(self.0.conn())
+        .await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.store_backlog_report",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    /// Loads the latest backlog reports, the newest ones first.
+    pub async fn load_backlog_reports(
+        &mut self,
+        limit: u32,
+    ) -> QueryResult<Vec<ForcedExitBacklogReport>> {
+        let start = Instant::now();
+
+        let reports = sqlx::query!(
+            r#"
+            SELECT report FROM forced_exit_requests_backlog_reports
+            ORDER BY id DESC
+            LIMIT $1
+            "#,
+            i64::from(limit)
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(|record| {
+            serde_json::from_value(record.report).expect("Invalid backlog report has been stored")
+        })
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_backlog_reports",
+            start.elapsed()
+        );
+        Ok(reports)
+    }
+
+    /// Loads the requests changed since the given time (up to `limit` of them, the oldest ones
+    /// first) along with the records their statuses are checked against.
+    ///
+    /// The statuses are loaded before the records backing them, so the request transitioned
+    /// in the meantime is seen with the records at least as recent as its status.
+    pub async fn load_consistency_evidence(
+        &mut self,
+        since: DateTime<Utc>,
+        limit: u32,
+    ) -> QueryResult<Vec<ForcedExitRequestEvidence>> {
+        let start = Instant::now();
+
+        let ids: Vec<ForcedExitRequestId> = sqlx::query!(
+            r#"
+            SELECT id as "id!" FROM (
+                SELECT id FROM forced_exit_requests
+                    WHERE created_at >= $1 OR matched_at >= $1 OR fulfilled_at >= $1
+                UNION SELECT request_id FROM forced_exit_fulfillments WHERE created_at >= $1
+                UNION SELECT request_id FROM forced_exit_requests_outbox WHERE created_at >= $1
+                UNION SELECT request_id FROM forced_exit_requests_active_targets
+                    WHERE failed_at >= $1
+                UNION SELECT request_id FROM forced_exit_refunds WHERE updated_at >= $1
+            ) changed
+            ORDER BY id
+            LIMIT $2
+            "#,
+            since,
+            i64::from(limit)
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(|record| record.id)
+        .collect();
+
+        let requests: Vec<ForcedExitRequest> = sqlx::query_as!(
+            DbForcedExitRequest,
+            "SELECT * FROM forced_exit_requests WHERE id = ANY($1) ORDER BY id",
+            &ids
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(ForcedExitRequest::from)
+        .collect();
+        let failed: HashMap<_, _> = sqlx::query!(
+            r#"
+            SELECT request_id, failed_at as "failed_at!" FROM forced_exit_requests_active_targets
+            WHERE request_id = ANY($1) AND failed_at IS NOT NULL
+            "#,
+            &ids
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(|record| (record.request_id, record.failed_at))
+        .collect();
+
+        let mut evidence: Vec<_> = requests
+            .into_iter()
+            .map(|request| ForcedExitRequestEvidence {
+                failed_at: failed.get(&request.id).copied(),
+                request,
+                fulfilled_tokens: Vec::new(),
+                escalated: false,
+                events: Vec::new(),
+                matched_payments: Vec::new(),
+                refunds: Vec::new(),
+            })
+            .collect();
+        let positions: HashMap<_, _> = evidence
+            .iter()
+            .enumerate()
+            .map(|(position, evidence)| (evidence.request.id, position))
+            .collect();
+
+        let fulfillments = sqlx::query!(
+            r#"
+            SELECT request_id, token FROM forced_exit_fulfillments
+            WHERE request_id = ANY($1)
+            ORDER BY request_id, position
+            "#,
+            &ids
+        )
+        .fetch_all(self.0.conn())
+        .await?;
+        for fulfillment in fulfillments {
+            if let Some(position) = positions.get(&fulfillment.request_id) {
+                evidence[*position]
+                    .fulfilled_tokens
+                    .push(TokenId(fulfillment.token as u32));
+            }
+        }
+
+        let escalations = sqlx::query!(
+            "SELECT request_id FROM forced_exit_requests_escalations WHERE request_id = ANY($1)",
+            &ids
+        )
+        .fetch_all(self.0.conn())
+        .await?;
+        for escalation in escalations {
+            if let Some(position) = positions.get(&escalation.request_id) {
+                evidence[*position].escalated = true;
+            }
+        }
+
+        let events = sqlx::query!(
+            r#"
+            SELECT request_id, event FROM forced_exit_requests_outbox
+            WHERE request_id = ANY($1)
+            ORDER BY request_id, sequence
+            "#,
+            &ids
+        )
+        .fetch_all(self.0.conn())
+        .await?;
+        for event in events {
+            if let (Some(position), Ok(parsed)) = (
+                positions.get(&event.request_id),
+                ForcedExitRequestEvent::from_str(&event.event),
+            ) {
+                evidence[*position].events.push(parsed);
+            }
+        }
+
+        let matches = sqlx::query!(
+            r#"
+            SELECT request_id, eth_tx_hash FROM forced_exit_requests_payment_matches
+            WHERE request_id = ANY($1)
+            "#,
+            &ids
+        )
+        .fetch_all(self.0.conn())
+        .await?;
+        for payment_match in matches {
+            if let Some(position) = positions.get(&payment_match.request_id) {
+                evidence[*position].matched_payments.push(H256::from_slice(
+                    &hex::decode(payment_match.eth_tx_hash)
+                        .expect("Invalid payment tx hash has been stored"),
+                ));
+            }
+        }
+
+        // Only the columns which are never encrypted are read
+        let refunds = sqlx::query!(
+            r#"
+            SELECT id, request_id, payment_tx_hash, amount, fee, status,
+                EXISTS (
+                    SELECT 1 FROM forced_exit_requests_payments
+                        WHERE eth_tx_hash = forced_exit_refunds.payment_tx_hash
+                    UNION ALL SELECT 1 FROM forced_exit_requests_unmatched_payments
+                        WHERE eth_tx_hash = forced_exit_refunds.payment_tx_hash
+                    UNION ALL SELECT 1 FROM forced_exit_requests_payment_matches
+                        WHERE eth_tx_hash = forced_exit_refunds.payment_tx_hash
+                ) as "payment_recorded!",
+                (
+                    SELECT amount FROM forced_exit_requests_payments
+                    WHERE eth_tx_hash = forced_exit_refunds.payment_tx_hash
+                    ORDER BY id
+                    LIMIT 1
+                ) as payment_amount
+            FROM forced_exit_refunds
+            WHERE request_id = ANY($1)
+            ORDER BY id
+            "#,
+            &ids
+        )
+        .fetch_all(self.0.conn())
+        .await?;
+        for refund in refunds {
+            if let Some(position) = positions.get(&refund.request_id) {
+                evidence[*position].refunds.push(ForcedExitRefundEvidence {
+                    refund_id: refund.id,
+                    payment_tx_hash: H256::from_slice(
+                        &hex::decode(refund.payment_tx_hash)
+                            .expect("Invalid payment tx hash has been stored"),
+                    ),
+                    amount: big_decimal_to_amount(&refund.amount)
+                        .expect("Invalid refund amount has been stored"),
+                    fee: big_decimal_to_amount(&refund.fee)
+                        .expect("Invalid refund fee has been stored"),
+                    status: ForcedExitRefundStatus::from_str(&refund.status)
+                        .expect("Invalid refund status has been stored"),
+                    payment_recorded: refund.payment_recorded,
+                    payment_amount: refund
+                        .payment_amount
+                        .map(|amount| big_decimal_to_amount(&amount))
+                        .transpose()
+                        .expect("Invalid payment amount has been stored"),
+                });
+            }
+        }
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_consistency_evidence",
+            start.elapsed()
+        );
+        Ok(evidence)
+    }
+
+    pub async fn store_consistency_report(
+        &mut self,
+        report: &ForcedExitConsistencyReport,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+        let serialized =
+            serde_json::to_value(report).expect("Failed to serialize the consistency report");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO forced_exit_requests_consistency_reports ( created_at, report )
+            VALUES ( $1, $2 )
+            "#,
+            report.created_at,
+            serialized
+        )
+        .execute(self.0.conn())
+        .await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.store_consistency_report",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    /// Loads the latest consistency reports, the newest ones first.
+    pub async fn load_consistency_reports(
+        &mut self,
+        limit: u32,
+    ) -> QueryResult<Vec<ForcedExitConsistencyReport>> {
+        let start = Instant::now();
+
+        let reports = sqlx::query!(
+            r#"
+            SELECT report FROM forced_exit_requests_consistency_reports
+            ORDER BY id DESC
+            LIMIT $1
+            "#,
+            i64::from(limit)
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(|record| {
+            serde_json::from_value(record.report)
+                .expect("Invalid consistency report has been stored")
+        })
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_consistency_reports",
+            start.elapsed()
+        );
+        Ok(reports)
+    }
+
+    /// Records the snapshot of the money config effective from the given time, unless
+    /// it is the same as the one already in effect then. Returns whether it was recorded.
+    pub async fn record_config(
+        &mut self,
+        config: &ForcedExitMoneyConfig,
+        effective_from: DateTime<Utc>,
+    ) -> QueryResult<bool> {
+        let start = Instant::now();
+        let mut transaction = self.0.start_transaction().await?;
+
+        let latest = sqlx::query!(
+            r#"
+            SELECT config FROM forced_exit_config_history
+            WHERE effective_from <= $1
+            ORDER BY effective_from DESC, id DESC
+            LIMIT 1
+            FOR UPDATE
+            "#,
+            effective_from
+        )
+        .fetch_optional(transaction.conn())
+        .await?
+        .map(|record| {
+            serde_json::from_value::<ForcedExitMoneyConfig>(record.config)
+                .expect("Invalid money config has been stored")
+        });
+        let changed = latest.as_ref() != Some(config);
+        if changed {
+            let serialized =
+                serde_json::to_value(config).expect("Failed to serialize the money config");
+            sqlx::query!(
+                r#"
+                INSERT INTO forced_exit_config_history ( effective_from, config )
+                VALUES ( $1, $2 )
+                "#,
+                effective_from,
+                serialized
+            )
+            .execute(transaction.conn())
+            .await?;
+        }
+        transaction.commit().await?;
+
+        metrics::histogram!("sql.forced_exit_requests.record_config", start.elapsed());
+        Ok(changed)
+    }
+
+    /// Loads the snapshot of the money config in effect at the given time.
+    pub async fn config_at(
+        &mut self,
+        time: DateTime<Utc>,
+    ) -> QueryResult<Option<ForcedExitConfigSnapshot>> {
+        let start = Instant::now();
+
+        let snapshot = sqlx::query!(
+            r#"
+            SELECT effective_from, config FROM forced_exit_config_history
+            WHERE effective_from <= $1
+            ORDER BY effective_from DESC, id DESC
+            LIMIT 1
+            "#,
+            time
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(|record| ForcedExitConfigSnapshot {
+            effective_from: record.effective_from,
+            config: serde_json::from_value(record.config)
+                .expect("Invalid money config has been stored"),
+        });
+
+        metrics::histogram!("sql.forced_exit_requests.config_at", start.elapsed());
+        Ok(snapshot)
+    }
+
+    /// Whether the account of the address is being created, i.e. the deposit or the transfer
+    /// to the address awaits in the priority queue or the mempool.
+    pub async fn is_account_creation_pending(&mut self, address: Address) -> QueryResult<bool> {
+        let start = Instant::now();
+
+        let pending = sqlx::query!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM mempool_priority_operations
+                WHERE type = 'Deposit' AND l2_address = $1 AND NOT reverted
+            ) OR EXISTS (
+                SELECT 1 FROM mempool_txs
+                WHERE tx->>'type' = 'Transfer' AND tx->>'to' = $2 AND NOT reverted
+            ) AS "pending!"
+            "#,
+            address.as_bytes().to_vec(),
+            address_to_stored_string(&address)
+        )
+        .fetch_one(self.0.conn())
+        .await?
+        .pending;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.is_account_creation_pending",
+            start.elapsed()
+        );
+        Ok(pending)
+    }
+
+    /// Records the observed state of the sender account, the time the account has entered
+    /// the state is kept while the state stays the same.
+    pub async fn store_sender_state(
+        &mut self,
+        address: Address,
+        state: ForcedExitSenderState,
+        observed_at: DateTime<Utc>,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO forced_exit_requests_sender_state ( address, state, since )
+            VALUES ( $1, $2, $3 )
+            ON CONFLICT (address) DO UPDATE SET state = EXCLUDED.state, since = EXCLUDED.since
+                WHERE forced_exit_requests_sender_state.state <> EXCLUDED.state
+            "#,
+            address_to_stored_string(&address),
+            state.as_str(),
+            observed_at
+        )
+        .execute(self.0.conn())
+        .await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.store_sender_state",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    /// Loads the state of the sender account, which has changed the last.
+    pub async fn load_sender_status(&mut self) -> QueryResult<Option<ForcedExitSenderStatus>> {
+        let start = Instant::now();
+
+        let status = sqlx::query!(
+            r#"
+            SELECT address, state, since, pacing FROM forced_exit_requests_sender_state
+            ORDER BY since DESC
+            LIMIT 1
+            "#
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(|status| ForcedExitSenderStatus {
+            address: stored_str_address_to_address(&status.address),
+            state: status
+                .state
+                .parse()
+                .expect("Invalid sender state has been stored"),
+            since: status.since,
+            pacing: status.pacing.map(|pacing| {
+                serde_json::from_value(pacing).expect("Invalid pacing state has been stored")
+            }),
+        });
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_sender_status",
+            start.elapsed()
+        );
+        Ok(status)
+    }
+
+    /// Records the pacing of the sender account reported by the running service.
+    pub async fn store_pacing_state(
+        &mut self,
+        address: Address,
+        pacing: &ForcedExitPacingState,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+
+        sqlx::query!(
+            r#"
+            UPDATE forced_exit_requests_sender_state
+            SET pacing = $2
+            WHERE address = $1
+            "#,
+            address_to_stored_string(&address),
+            serde_json::to_value(pacing).expect("Failed to serialize the pacing state")
+        )
+        .execute(self.0.conn())
+        .await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.store_pacing_state",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    /// Loads the pacing set by the operators, if any.
+    pub async fn load_pacing_override(&mut self) -> QueryResult<Option<ForcedExitPacing>> {
+        let start = Instant::now();
+
+        let pacing = sqlx::query!(
+            r#"
+            SELECT max_batches_per_minute, batches_per_block FROM forced_exit_requests_pacing
+            "#
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(|pacing| ForcedExitPacing {
+            max_batches_per_minute: pacing.max_batches_per_minute as u32,
+            batches_per_block: pacing.batches_per_block as u32,
+        });
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_pacing_override",
+            start.elapsed()
+        );
+        Ok(pacing)
+    }
+
+    /// Overrides the pacing of the config, the config applies again once the override is removed.
+    pub async fn set_pacing_override(
+        &mut self,
+        pacing: Option<ForcedExitPacing>,
+        updated_at: DateTime<Utc>,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+
+        match pacing {
+            Some(pacing) => {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO forced_exit_requests_pacing
+                        ( max_batches_per_minute, batches_per_block, updated_at )
+                    VALUES ( $1, $2, $3 )
+                    ON CONFLICT (id) DO UPDATE SET
+                        max_batches_per_minute = EXCLUDED.max_batches_per_minute,
+                        batches_per_block = EXCLUDED.batches_per_block,
+                        updated_at = EXCLUDED.updated_at
+                    "#,
+                    i64::from(pacing.max_batches_per_minute),
+                    i64::from(pacing.batches_per_block),
+                    updated_at
+                )
+                .execute(self.0.conn())
+                .await?;
+            }
+            None => {
+                sqlx::query!("DELETE FROM forced_exit_requests_pacing")
+                    .execute(self.0.conn())
+                    .await?;
+            }
+        }
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.set_pacing_override",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    /// Records the refund of the payment, the recipient is encrypted if the encryption
+    /// of the sensitive columns is configured. Returns `None` if the payment has already
+    /// been refunded, so the same payment delivered once again is not refunded twice.
+    /// The refund requiring the approval awaits it before it is loaded as an unsettled one.
+    pub async fn store_refund(
+        &mut self,
+        refund: SaveForcedExitRefundQuery,
+    ) -> QueryResult<Option<ForcedExitRefund>> {
+        self.store_refund_with_cipher(refund, column_cipher()).await
+    }
+
+    pub(crate) async fn store_refund_with_cipher(
+        &mut self,
+        refund: SaveForcedExitRefundQuery,
+        cipher: Option<&ColumnCipher>,
+    ) -> QueryResult<Option<ForcedExitRefund>> {
+        let start = Instant::now();
+
+        let recipient = address_to_stored_string(&refund.recipient);
+        let recipient = match cipher {
+            Some(cipher) => cipher.encrypt(&recipient),
+            None => recipient,
+        };
+        let stored = sqlx::query_as!(
+            DbForcedExitRefund,
+            r#"
+            INSERT INTO forced_exit_refunds (
+                request_id, payment_tx_hash, recipient, amount, fee, reason, status,
+                created_at, updated_at
+            )
+            VALUES ( $1, $2, $3, $4, $5, $6, $7, $8, $8 )
+            ON CONFLICT ( payment_tx_hash ) DO NOTHING
+            RETURNING *
+            "#,
+            refund.request_id,
+            hex::encode(refund.payment_tx_hash.as_bytes()),
+            recipient,
+            amount_to_big_decimal(&refund.amount),
+            amount_to_big_decimal(&refund.fee),
+            refund.reason.as_str(),
+            if refund.requires_approval {
+                ForcedExitRefundStatus::AwaitingApproval
+            } else {
+                ForcedExitRefundStatus::Pending
+            }
+            .as_str(),
+            refund.created_at
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(|refund| decrypt_refund(cipher, refund))
+        .transpose()?;
+
+        metrics::histogram!("sql.forced_exit_requests.store_refund", start.elapsed());
+        Ok(stored)
+    }
+
+    /// Loads the refunds, which are not settled yet: the pending and the sent ones, as well as
+    /// the failed ones with less than `max_attempts` failed transfers. The refunds approved
+    /// by the operators go first, then the rest in the order they were recorded.
+    pub async fn load_unsettled_refunds(
+        &mut self,
+        max_attempts: u32,
+    ) -> QueryResult<Vec<ForcedExitRefund>> {
+        self.load_unsettled_refunds_with_cipher(max_attempts, column_cipher())
+            .await
+    }
+
+    pub(crate) async fn load_unsettled_refunds_with_cipher(
+        &mut self,
+        max_attempts: u32,
+        cipher: Option<&ColumnCipher>,
+    ) -> QueryResult<Vec<ForcedExitRefund>> {
+        let start = Instant::now();
+
+        let refunds = sqlx::query_as!(
+            DbForcedExitRefund,
+            r#"
+            SELECT * FROM forced_exit_refunds
+            WHERE status IN ( $1, $2 ) OR ( status = $3 AND attempts < $4 )
+            ORDER BY approved_at IS NULL, created_at, id
+            "#,
+            ForcedExitRefundStatus::Pending.as_str(),
+            ForcedExitRefundStatus::Sent.as_str(),
+            ForcedExitRefundStatus::Failed.as_str(),
+            max_attempts as i32
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(|refund| decrypt_refund(cipher, refund))
+        .collect::<QueryResult<_>>()?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_unsettled_refunds",
+            start.elapsed()
+        );
+        Ok(refunds)
+    }
+
+    /// Loads the refunds of the payments for the request, in the order they were recorded.
+    pub async fn load_request_refunds(
+        &mut self,
+        request_id: ForcedExitRequestId,
+    ) -> QueryResult<Vec<ForcedExitRefund>> {
+        let start = Instant::now();
+        let cipher = column_cipher();
+
+        let refunds = sqlx::query_as!(
+            DbForcedExitRefund,
+            r#"
+            SELECT * FROM forced_exit_refunds
+            WHERE request_id = $1
+            ORDER BY created_at, id
+            "#,
+            request_id
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(|refund| decrypt_refund(cipher, refund))
+        .collect::<QueryResult<_>>()?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_request_refunds",
+            start.elapsed()
+        );
+        Ok(refunds)
+    }
+
+    /// Loads the refund, `None` if there is no refund with such id.
+    pub async fn get_refund(
+        &mut self,
+        id: ForcedExitRefundId,
+    ) -> QueryResult<Option<ForcedExitRefund>> {
+        let start = Instant::now();
+        let cipher = column_cipher();
+
+        let refund = sqlx::query_as!(
+            DbForcedExitRefund,
+            r#"
+            SELECT * FROM forced_exit_refunds
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(|refund| decrypt_refund(cipher, refund))
+        .transpose()?;
+
+        metrics::histogram!("sql.forced_exit_requests.get_refund", start.elapsed());
+        Ok(refund)
+    }
+
+    /// Loads the refunds awaiting the approval of the operators, in the order they were recorded.
+    pub async fn load_refunds_awaiting_approval(&mut self) -> QueryResult<Vec<ForcedExitRefund>> {
+        let start = Instant::now();
+        let cipher = column_cipher();
+
+        let refunds = sqlx::query_as!(
+            DbForcedExitRefund,
+            r#"
+            SELECT * FROM forced_exit_refunds
+            WHERE status = $1
+            ORDER BY created_at, id
+            "#,
+            ForcedExitRefundStatus::AwaitingApproval.as_str()
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(|refund| decrypt_refund(cipher, refund))
+        .collect::<QueryResult<_>>()?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_refunds_awaiting_approval",
+            start.elapsed()
+        );
+        Ok(refunds)
+    }
+
+    /// Approves the refund awaiting the approval, so it is sent along with the rest.
+    /// Returns `None` if the refund does not await the approval (anymore).
+    pub async fn approve_refund(
+        &mut self,
+        id: ForcedExitRefundId,
+        approved_by: &str,
+        approved_at: DateTime<Utc>,
+    ) -> QueryResult<Option<ForcedExitRefund>> {
+        let start = Instant::now();
+        let cipher = column_cipher();
+
+        let refund = sqlx::query_as!(
+            DbForcedExitRefund,
+            r#"
+            UPDATE forced_exit_refunds
+                SET status = $3, approved_by = $4, approved_at = $5, updated_at = $5
+                WHERE id = $1 AND status = $2
+            RETURNING *
+            "#,
+            id,
+            ForcedExitRefundStatus::AwaitingApproval.as_str(),
+            ForcedExitRefundStatus::Pending.as_str(),
+            approved_by,
+            approved_at
+        )
+        .fetch_optional(self.0.conn())
+        .await?
+        .map(|refund| decrypt_refund(cipher, refund))
+        .transpose()?;
+
+        metrics::histogram!("sql.forced_exit_requests.approve_refund", start.elapsed());
+        Ok(refund)
+    }
+
+    /// The amount returned by the transfers sent since the time, the failed ones returned nothing.
+    pub async fn refunded_amount_since(&mut self, since: DateTime<Utc>) -> QueryResult<BigUint> {
+        let start = Instant::now();
+
+        let amount = sqlx::query!(
+            r#"
+            SELECT COALESCE(SUM(amount), 0) as "amount!" FROM forced_exit_refunds
+            WHERE sent_at >= $1 AND status <> $2
+            "#,
+            since,
+            ForcedExitRefundStatus::Failed.as_str()
+        )
+        .fetch_one(self.0.conn())
+        .await?
+        .amount;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.refunded_amount_since",
+            start.elapsed()
+        );
+        Ok(big_decimal_to_amount(&amount).expect("Invalid refunded amount has been summed up"))
+    }
+
+    /// Moves the refund to the status, the transfer is kept unless another one is sent.
+    /// The failed transfers are counted as the attempts.
+    pub async fn set_refund_status(
+        &mut self,
+        id: ForcedExitRefundId,
+        status: ForcedExitRefundStatus,
+        tx_hash: Option<TxHash>,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+
+        let failed = i32::from(status == ForcedExitRefundStatus::Failed);
+        sqlx::query!(
+            r#"
+            UPDATE forced_exit_refunds
+                SET status = $2,
+                    tx_hash = COALESCE($3, tx_hash),
+                    attempts = attempts + $4,
+                    sent_at = CASE WHEN $2 = $6 THEN $5 ELSE sent_at END,
+                    updated_at = $5
+                WHERE id = $1
+            "#,
+            id,
+            status.as_str(),
+            tx_hash.map(|hash| hash.to_string()),
+            failed,
+            Utc::now(),
+            ForcedExitRefundStatus::Sent.as_str()
+        )
+        .execute(self.0.conn())
+        .await?;
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.set_refund_status",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    /// Appends the note to the request. The request is not checked to exist, nor is it
+    /// changed in any way.
+    pub async fn store_note(
+        &mut self,
+        note: SaveForcedExitRequestNoteQuery,
+    ) -> QueryResult<ForcedExitRequestNote> {
+        let start = Instant::now();
+
+        let stored = sqlx::query_as!(
+            DbForcedExitRequestNote,
+            r#"
+            INSERT INTO forced_exit_requests_notes ( request_id, author, text, tags, created_at )
+            VALUES ( $1, $2, $3, $4, $5 )
+            RETURNING *
+            "#,
+            note.request_id,
+            note.author,
+            note.text,
+            &note.tags,
+            note.created_at
+        )
+        .fetch_one(self.0.conn())
+        .await?;
+
+        metrics::histogram!("sql.forced_exit_requests.store_note", start.elapsed());
+        Ok(stored.into())
+    }
+
+    /// Loads the notes of the request in the order they were added.
+    pub async fn load_request_notes(
+        &mut self,
+        request_id: ForcedExitRequestId,
+    ) -> QueryResult<Vec<ForcedExitRequestNote>> {
+        let start = Instant::now();
+
+        let notes = sqlx::query_as!(
+            DbForcedExitRequestNote,
+            r#"
+            SELECT * FROM forced_exit_requests_notes
+            WHERE request_id = $1
+            ORDER BY id
+            "#,
+            request_id
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_request_notes",
+            start.elapsed()
+        );
+        Ok(notes)
+    }
+
+    /// Loads at most `limit` of the latest notes with the tag, the newest ones first.
+    pub async fn load_notes_by_tag(
+        &mut self,
+        tag: &str,
+        limit: u32,
+    ) -> QueryResult<Vec<ForcedExitRequestNote>> {
+        let start = Instant::now();
+
+        let notes = sqlx::query_as!(
+            DbForcedExitRequestNote,
+            r#"
+            SELECT * FROM forced_exit_requests_notes
+            WHERE tags @> ARRAY[$1::TEXT]
+            ORDER BY id DESC
+            LIMIT $2
+            "#,
+            tag,
+            i64::from(limit)
+        )
+        .fetch_all(self.0.conn())
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+        metrics::histogram!(
+            "sql.forced_exit_requests.load_notes_by_tag",
+            start.elapsed()
+        );
+        Ok(notes)
     }
 }
@@ -1,8 +1,23 @@
 use crate::utils::{address_to_stored_string, stored_str_address_to_address};
 use chrono::{DateTime, Utc};
-use num::{bigint::ToBigInt, BigInt};
 use sqlx::types::BigDecimal;
-use zksync_types::{forced_exit_requests::ForcedExitRequest, tx::TxHash, TokenId};
+use std::str::FromStr;
+use zksync_types::{
+    forced_exit_requests::{
+        legacy_pay_exactly, ActiveTargetPolicy, ExpectedForcedExitPayment, ForcedExitCancellation,
+        ForcedExitCancellationKind, ForcedExitFulfillment, ForcedExitLifecycleStatus,
+        ForcedExitPayment, ForcedExitPipelineStage, ForcedExitPipelineVersion,
+        ForcedExitProcessingFailure, ForcedExitRefund, ForcedExitRefundReason,
+        ForcedExitRefundStatus, ForcedExitRequest, ForcedExitRequestActiveTarget,
+        ForcedExitRequestDelivery, ForcedExitRequestEscalation, ForcedExitRequestEvent,
+        ForcedExitRequestNote, ForcedExitRequestsApiKey, ForcedExitRetry,
+        ForcedExitTokenSkipReason, InjectedForcedExitPayment, PaymentMatchScheme, PaymentSource,
+        PaymentSourceState, SkippedForcedExit, UnmatchedForcedExitPayment, UnmatchedPaymentReason,
+    },
+    tx::TxHash,
+    AccountId, Nonce, TokenId, H256,
+};
+use zksync_utils::{amount_to_big_decimal, big_decimal_to_amount};
 
 use super::utils;
 
@@ -16,50 +31,627 @@ pub struct DbForcedExitRequest {
     pub created_at: DateTime<Utc>,
     pub fulfilled_by: Option<String>,
     pub fulfilled_at: Option<DateTime<Utc>>,
+    pub match_scheme: Option<String>,
+    pub matched_at: Option<DateTime<Utc>>,
+    /// Not set for the legacy requests stored by the servers preceding the column.
+    pub pay_exactly: Option<String>,
+    pub cancellation: Option<String>,
+    pub paid_amount: Option<BigDecimal>,
+    /// Not set for the legacy requests, their ids are the public ones.
+    pub public_id: Option<i64>,
+    pub metadata: Option<String>,
+    pub status: String,
+    /// Set once the request has expired unpaid and its public id may be allocated again.
+    pub public_id_released_at: Option<DateTime<Utc>>,
+    /// Not set for the requests stored by the servers preceding the column.
+    pub payment_terms: Option<serde_json::Value>,
+    /// Only read when the request is fulfilled, it is not returned with the request.
+    pub callback_url: Option<String>,
 }
 
 impl From<ForcedExitRequest> for DbForcedExitRequest {
     fn from(request: ForcedExitRequest) -> Self {
-        let price_in_wei = BigDecimal::from(BigInt::from(request.price_in_wei.clone()));
+        let price_in_wei = amount_to_big_decimal(&request.price_in_wei);
 
         let tokens = utils::vec_to_comma_list(request.tokens);
         let fulfilled_by = request.fulfilled_by.map(utils::vec_to_comma_list);
+        let match_scheme = request.match_scheme.map(|scheme| scheme.to_string());
+        let cancellation = request.cancellation.map(|kind| kind.to_string());
+        let paid_amount = request.paid_amount.map(|amount| {
+            BigDecimal::from_str(&amount).expect("Invalid paid amount of the forced exit request")
+        });
+        let payment_terms = request.payment_terms.map(|terms| {
+            serde_json::to_value(terms).expect("Failed to serialize the payment terms")
+        });
         Self {
             id: request.id,
             target: address_to_stored_string(&request.target),
             tokens,
             price_in_wei,
+            pay_exactly: Some(request.pay_exactly),
             valid_until: request.valid_until,
             created_at: request.created_at,
             fulfilled_at: request.fulfilled_at,
             fulfilled_by,
+            match_scheme,
+            matched_at: request.matched_at,
+            cancellation,
+            paid_amount,
+            public_id: Some(request.public_id),
+            metadata: request.metadata,
+            status: request.status.to_string(),
+            public_id_released_at: None,
+            payment_terms,
+            callback_url: None,
         }
     }
 }
 
 impl From<DbForcedExitRequest> for ForcedExitRequest {
     fn from(val: DbForcedExitRequest) -> Self {
-        let price_in_wei = val
-            .price_in_wei
-            .to_bigint()
-            .map(|int| int.to_biguint())
-            .flatten()
-            // The fact that the request was found, but could not be convert into the ForcedExitRequest
-            // means that invalid data is stored in the DB
+        // The fact that the request was found, but could not be convert into the ForcedExitRequest
+        // means that invalid data is stored in the DB
+        let price_in_wei = big_decimal_to_amount(&val.price_in_wei)
             .expect("Invalid forced exit request has been stored");
 
+        // The legacy request gets the amount it would have been stored with
+        let public_id = val.public_id.unwrap_or(val.id);
+        let pay_exactly = match val.pay_exactly {
+            Some(amount) => amount,
+            None => legacy_pay_exactly(&price_in_wei, public_id),
+        };
+
         let tokens: Vec<TokenId> = utils::comma_list_to_vec(val.tokens);
         let fulfilled_by: Option<Vec<TxHash>> = val.fulfilled_by.map(utils::comma_list_to_vec);
+        let match_scheme = val.match_scheme.map(|scheme| {
+            PaymentMatchScheme::from_str(&scheme)
+                .expect("Invalid payment match scheme has been stored")
+        });
+        let cancellation = val.cancellation.map(|kind| {
+            ForcedExitCancellationKind::from_str(&kind)
+                .expect("Invalid cancellation kind has been stored")
+        });
+        let paid_amount = val.paid_amount.map(|amount| {
+            big_decimal_to_amount(&amount)
+                .expect("Invalid forced exit request has been stored")
+                .to_string()
+        });
+        let status = ForcedExitLifecycleStatus::from_str(&val.status)
+            .expect("Invalid status of the forced exit request has been stored");
+        let payment_terms = val.payment_terms.map(|terms| {
+            serde_json::from_value(terms).expect("Invalid payment terms have been stored")
+        });
 
         ForcedExitRequest {
             id: val.id,
+            public_id,
             target: stored_str_address_to_address(&val.target),
             tokens,
             price_in_wei,
+            pay_exactly,
             created_at: val.created_at,
             valid_until: val.valid_until,
             fulfilled_at: val.fulfilled_at,
             fulfilled_by,
+            match_scheme,
+            matched_at: val.matched_at,
+            cancellation,
+            paid_amount,
+            metadata: val.metadata,
+            status,
+            payment_terms,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbForcedExitFulfillment {
+    pub request_id: i64,
+    pub position: i32,
+    pub token: i32,
+    pub tx_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub target_account_id: Option<i64>,
+    pub submitted_at: Option<DateTime<Utc>>,
+}
+
+impl From<DbForcedExitFulfillment> for ForcedExitFulfillment {
+    fn from(val: DbForcedExitFulfillment) -> Self {
+        ForcedExitFulfillment {
+            request_id: val.request_id,
+            position: val.position as u32,
+            token: TokenId(val.token as u32),
+            tx_hash: TxHash::from_str(&val.tx_hash).expect("Invalid tx hash has been stored"),
+            created_at: val.created_at,
+            target_account_id: val.target_account_id.map(|id| AccountId(id as u32)),
+            submitted_at: val.submitted_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbForcedExitRequestEscalation {
+    pub request_id: i64,
+    pub full_exits: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+    pub l1_tx_hash: Option<String>,
+    pub finalized_at: Option<DateTime<Utc>>,
+}
+
+impl From<ForcedExitRequestEscalation> for DbForcedExitRequestEscalation {
+    fn from(escalation: ForcedExitRequestEscalation) -> Self {
+        let full_exits = serde_json::to_value(escalation.full_exits)
+            .expect("Failed to serialize prepared FullExit operations");
+
+        Self {
+            request_id: escalation.request_id,
+            full_exits,
+            created_at: escalation.created_at,
+            l1_tx_hash: escalation
+                .l1_tx_hash
+                .map(|hash| hex::encode(hash.as_bytes())),
+            finalized_at: escalation.finalized_at,
+        }
+    }
+}
+
+impl From<DbForcedExitRequestEscalation> for ForcedExitRequestEscalation {
+    fn from(val: DbForcedExitRequestEscalation) -> Self {
+        let full_exits = serde_json::from_value(val.full_exits)
+            .expect("Invalid prepared FullExit operations have been stored");
+        let l1_tx_hash = val.l1_tx_hash.map(|hash| {
+            H256::from_slice(&hex::decode(hash).expect("Invalid L1 tx hash has been stored"))
+        });
+
+        ForcedExitRequestEscalation {
+            request_id: val.request_id,
+            full_exits,
+            created_at: val.created_at,
+            l1_tx_hash,
+            finalized_at: val.finalized_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbForcedExitRequestsApiKey {
+    pub id: i64,
+    pub label: String,
+    pub key_hash: String,
+    pub max_tokens_per_request: Option<i32>,
+    pub max_requests_per_hour: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl From<DbForcedExitRequestsApiKey> for ForcedExitRequestsApiKey {
+    fn from(val: DbForcedExitRequestsApiKey) -> Self {
+        ForcedExitRequestsApiKey {
+            id: val.id,
+            label: val.label,
+            max_tokens_per_request: val.max_tokens_per_request.map(|limit| limit as u8),
+            max_requests_per_hour: val.max_requests_per_hour.map(|limit| limit as u32),
+            created_at: val.created_at,
+            revoked_at: val.revoked_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbForcedExitPayment {
+    pub id: i64,
+    pub amount: BigDecimal,
+    pub request_id: Option<i64>,
+    pub block_number: i64,
+    pub eth_tx_hash: Option<String>,
+    pub payer: Option<String>,
+    pub received_at: DateTime<Utc>,
+    pub payer_hash: Option<String>,
+    pub source: String,
+}
+
+impl From<DbForcedExitPayment> for ForcedExitPayment {
+    fn from(val: DbForcedExitPayment) -> Self {
+        let amount = big_decimal_to_amount(&val.amount)
+            .expect("Invalid forced exit payment amount has been stored");
+        let eth_tx_hash = val.eth_tx_hash.map(|hash| {
+            H256::from_slice(&hex::decode(hash).expect("Invalid payment tx hash has been stored"))
+        });
+
+        ForcedExitPayment {
+            amount,
+            request_id: val.request_id,
+            block_number: val.block_number as u64,
+            eth_tx_hash,
+            payer: val.payer.map(|payer| stored_str_address_to_address(&payer)),
+            received_at: val.received_at,
+            source: PaymentSource::from_str(&val.source)
+                .expect("Invalid payment source has been stored"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbPaymentSourceState {
+    pub source: String,
+    pub enabled: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<DbPaymentSourceState> for PaymentSourceState {
+    fn from(val: DbPaymentSourceState) -> Self {
+        PaymentSourceState {
+            source: PaymentSource::from_str(&val.source)
+                .expect("Invalid payment source has been stored"),
+            enabled: val.enabled,
+            updated_at: val.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbInjectedForcedExitPayment {
+    pub id: i64,
+    pub amount: BigDecimal,
+    pub request_id: Option<i64>,
+    pub block_number: i64,
+    pub eth_tx_hash: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<DbInjectedForcedExitPayment> for InjectedForcedExitPayment {
+    fn from(val: DbInjectedForcedExitPayment) -> Self {
+        let amount = big_decimal_to_amount(&val.amount)
+            .expect("Invalid injected payment amount has been stored");
+        let eth_tx_hash = val.eth_tx_hash.map(|hash| {
+            H256::from_slice(&hex::decode(hash).expect("Invalid payment tx hash has been stored"))
+        });
+
+        InjectedForcedExitPayment {
+            id: val.id,
+            amount,
+            request_id: val.request_id,
+            block_number: val.block_number as u64,
+            eth_tx_hash,
+            created_at: val.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbForcedExitRetry {
+    pub id: i64,
+    pub request_id: i64,
+    pub force: bool,
+    pub created_at: DateTime<Utc>,
+    pub processed_at: Option<DateTime<Utc>>,
+    pub tx_hashes: Option<String>,
+    pub error: Option<String>,
+}
+
+impl From<DbForcedExitRetry> for ForcedExitRetry {
+    fn from(val: DbForcedExitRetry) -> Self {
+        ForcedExitRetry {
+            id: val.id,
+            request_id: val.request_id,
+            force: val.force,
+            created_at: val.created_at,
+            processed_at: val.processed_at,
+            tx_hashes: val
+                .tx_hashes
+                .map(utils::comma_list_to_vec)
+                .unwrap_or_default(),
+            error: val.error,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbUnmatchedForcedExitPayment {
+    pub id: i64,
+    pub amount: String,
+    pub request_id: Option<i64>,
+    pub block_number: i64,
+    pub eth_tx_hash: Option<String>,
+    pub source: String,
+    pub reason: String,
+    pub received_at: DateTime<Utc>,
+}
+
+impl From<DbUnmatchedForcedExitPayment> for UnmatchedForcedExitPayment {
+    fn from(val: DbUnmatchedForcedExitPayment) -> Self {
+        let eth_tx_hash = val.eth_tx_hash.map(|hash| {
+            H256::from_slice(&hex::decode(hash).expect("Invalid payment tx hash has been stored"))
+        });
+
+        UnmatchedForcedExitPayment {
+            id: val.id,
+            amount: val.amount,
+            request_id: val.request_id,
+            block_number: val.block_number as u64,
+            eth_tx_hash,
+            source: PaymentSource::from_str(&val.source)
+                .expect("Invalid payment source has been stored"),
+            reason: UnmatchedPaymentReason::from_str(&val.reason)
+                .expect("Invalid unmatched payment reason has been stored"),
+            received_at: val.received_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbSkippedForcedExit {
+    pub token: i32,
+    pub reason: String,
+}
+
+impl From<DbSkippedForcedExit> for SkippedForcedExit {
+    fn from(val: DbSkippedForcedExit) -> Self {
+        SkippedForcedExit {
+            token: TokenId(val.token as u32),
+            reason: ForcedExitTokenSkipReason::from_str(&val.reason)
+                .expect("Invalid token skip reason has been stored"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbExpectedForcedExitPayment {
+    pub eth_tx_hash: String,
+    pub request_id: i64,
+    pub registered_at: DateTime<Utc>,
+    pub mismatched_amount: Option<String>,
+}
+
+impl From<DbExpectedForcedExitPayment> for ExpectedForcedExitPayment {
+    fn from(val: DbExpectedForcedExitPayment) -> Self {
+        ExpectedForcedExitPayment {
+            request_id: val.request_id,
+            eth_tx_hash: H256::from_slice(
+                &hex::decode(val.eth_tx_hash).expect("Invalid payment tx hash has been stored"),
+            ),
+            registered_at: val.registered_at,
+            mismatched_amount: val.mismatched_amount,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbForcedExitProcessingFailure {
+    pub request_id: i64,
+    pub attempts: i32,
+    pub error: String,
+    pub failed_at: DateTime<Utc>,
+    pub submission_error: Option<serde_json::Value>,
+}
+
+impl From<DbForcedExitProcessingFailure> for ForcedExitProcessingFailure {
+    fn from(val: DbForcedExitProcessingFailure) -> Self {
+        let submission_error = val.submission_error.map(|submission_error| {
+            serde_json::from_value(submission_error)
+                .expect("Invalid submission error has been stored")
+        });
+
+        ForcedExitProcessingFailure {
+            request_id: val.request_id,
+            attempts: val.attempts as u32,
+            error: val.error,
+            failed_at: val.failed_at,
+            submission_error,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbForcedExitCancellation {
+    pub request_id: i64,
+    pub kind: String,
+    pub cancelled_at: DateTime<Utc>,
+}
+
+impl From<DbForcedExitCancellation> for ForcedExitCancellation {
+    fn from(val: DbForcedExitCancellation) -> Self {
+        ForcedExitCancellation {
+            request_id: val.request_id,
+            kind: ForcedExitCancellationKind::from_str(&val.kind)
+                .expect("Invalid cancellation kind has been stored"),
+            cancelled_at: val.cancelled_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbForcedExitRequestDelivery {
+    pub id: i64,
+    pub request_id: i64,
+    pub event: String,
+    pub created_at: DateTime<Utc>,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub sequence: i64,
+    pub pipeline_version: Option<i32>,
+    pub callback_url: Option<String>,
+    pub callback: Option<serde_json::Value>,
+    pub failed_at: Option<DateTime<Utc>>,
+}
+
+impl From<DbForcedExitRequestDelivery> for ForcedExitRequestDelivery {
+    fn from(val: DbForcedExitRequestDelivery) -> Self {
+        let event = ForcedExitRequestEvent::from_str(&val.event)
+            .expect("Invalid forced exit request event has been stored");
+
+        ForcedExitRequestDelivery {
+            id: val.id,
+            request_id: val.request_id,
+            sequence: val.sequence,
+            event,
+            created_at: val.created_at,
+            attempts: val.attempts as u32,
+            next_attempt_at: val.next_attempt_at,
+            delivered_at: val.delivered_at,
+            last_error: val.last_error,
+            pipeline_version: val.pipeline_version.map(|version| version as u32),
+            callback_url: val.callback_url,
+            callback: val.callback.map(|callback| {
+                serde_json::from_value(callback).expect("Invalid callback has been stored")
+            }),
+            failed_at: val.failed_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbForcedExitPipelineVersion {
+    pub request_id: i64,
+    pub stage: String,
+    pub version: i32,
+    pub config_hash: String,
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl From<DbForcedExitPipelineVersion> for ForcedExitPipelineVersion {
+    fn from(val: DbForcedExitPipelineVersion) -> Self {
+        ForcedExitPipelineVersion {
+            request_id: val.request_id,
+            stage: ForcedExitPipelineStage::from_str(&val.stage)
+                .expect("Invalid pipeline stage has been stored"),
+            version: val.version as u32,
+            config_hash: val.config_hash,
+            recorded_at: val.recorded_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbForcedExitRequestActiveTarget {
+    pub request_id: i64,
+    pub policy: String,
+    pub target_nonce: i64,
+    pub payment_amount: BigDecimal,
+    pub payment_tx_hash: Option<String>,
+    pub match_scheme: String,
+    pub paid_at: DateTime<Utc>,
+    pub detected_at: DateTime<Utc>,
+    pub hold_until: Option<DateTime<Utc>>,
+    pub resumed_at: Option<DateTime<Utc>>,
+    pub failed_at: Option<DateTime<Utc>>,
+}
+
+impl From<ForcedExitRequestActiveTarget> for DbForcedExitRequestActiveTarget {
+    fn from(active_target: ForcedExitRequestActiveTarget) -> Self {
+        Self {
+            request_id: active_target.request_id,
+            policy: active_target.policy.to_string(),
+            target_nonce: i64::from(*active_target.target_nonce),
+            payment_amount: amount_to_big_decimal(&active_target.payment_amount),
+            payment_tx_hash: active_target
+                .payment_tx_hash
+                .map(|hash| hex::encode(hash.as_bytes())),
+            match_scheme: active_target.match_scheme.to_string(),
+            paid_at: active_target.paid_at,
+            detected_at: active_target.detected_at,
+            hold_until: active_target.hold_until,
+            resumed_at: active_target.resumed_at,
+            failed_at: active_target.failed_at,
+        }
+    }
+}
+
+impl From<DbForcedExitRequestActiveTarget> for ForcedExitRequestActiveTarget {
+    fn from(val: DbForcedExitRequestActiveTarget) -> Self {
+        let payment_amount = big_decimal_to_amount(&val.payment_amount)
+            .expect("Invalid active target payment amount has been stored");
+        let payment_tx_hash = val.payment_tx_hash.map(|hash| {
+            H256::from_slice(&hex::decode(hash).expect("Invalid payment tx hash has been stored"))
+        });
+
+        ForcedExitRequestActiveTarget {
+            request_id: val.request_id,
+            policy: ActiveTargetPolicy::from_str(&val.policy)
+                .expect("Invalid active target policy has been stored"),
+            target_nonce: Nonce(val.target_nonce as u32),
+            payment_amount,
+            payment_tx_hash,
+            match_scheme: PaymentMatchScheme::from_str(&val.match_scheme)
+                .expect("Invalid payment match scheme has been stored"),
+            paid_at: val.paid_at,
+            detected_at: val.detected_at,
+            hold_until: val.hold_until,
+            resumed_at: val.resumed_at,
+            failed_at: val.failed_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbForcedExitRefund {
+    pub id: i64,
+    pub request_id: i64,
+    pub payment_tx_hash: String,
+    pub recipient: String,
+    pub amount: BigDecimal,
+    pub fee: BigDecimal,
+    pub reason: String,
+    pub status: String,
+    pub tx_hash: Option<String>,
+    pub attempts: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub approved_by: Option<String>,
+    pub approved_at: Option<DateTime<Utc>>,
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+impl From<DbForcedExitRefund> for ForcedExitRefund {
+    fn from(val: DbForcedExitRefund) -> Self {
+        ForcedExitRefund {
+            id: val.id,
+            request_id: val.request_id,
+            payment_tx_hash: H256::from_slice(
+                &hex::decode(val.payment_tx_hash).expect("Invalid payment tx hash has been stored"),
+            ),
+            recipient: stored_str_address_to_address(&val.recipient),
+            amount: big_decimal_to_amount(&val.amount)
+                .expect("Invalid refund amount has been stored"),
+            fee: big_decimal_to_amount(&val.fee).expect("Invalid refund fee has been stored"),
+            reason: ForcedExitRefundReason::from_str(&val.reason)
+                .expect("Invalid refund reason has been stored"),
+            status: ForcedExitRefundStatus::from_str(&val.status)
+                .expect("Invalid refund status has been stored"),
+            tx_hash: val
+                .tx_hash
+                .map(|hash| TxHash::from_str(&hash).expect("Invalid tx hash has been stored")),
+            attempts: val.attempts as u32,
+            approved_by: val.approved_by,
+            approved_at: val.approved_at,
+            sent_at: val.sent_at,
+            created_at: val.created_at,
+            updated_at: val.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbForcedExitRequestNote {
+    pub id: i64,
+    pub request_id: i64,
+    pub author: String,
+    pub text: String,
+    pub tags: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<DbForcedExitRequestNote> for ForcedExitRequestNote {
+    fn from(val: DbForcedExitRequestNote) -> Self {
+        ForcedExitRequestNote {
+            id: val.id,
+            request_id: val.request_id,
+            author: val.author,
+            text: val.text,
+            tags: val.tags,
+            created_at: val.created_at,
         }
     }
 }
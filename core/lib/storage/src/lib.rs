@@ -92,6 +92,7 @@ pub mod config;
 pub mod connection;
 pub mod data_restore;
 pub mod diff;
+pub mod encryption;
 pub mod ethereum;
 pub mod event;
 pub mod forced_exit_requests;
@@ -323,6 +323,124 @@ async fn test_get_balance(mut storage: StorageProcessor<'_>) -> QueryResult<()>
     Ok(())
 }
 
+/// Checks that the committed nonce and the balances of the requested tokens are loaded
+/// for the account holding thousands of tokens, as the full account state has them.
+#[db_test]
+async fn committed_nonce_and_balances_by_address(
+    mut storage: StorageProcessor<'_>,
+) -> QueryResult<()> {
+    let _lock = ACCOUNT_MUTEX.lock().await;
+    let address = Address::random();
+    let tokens: Vec<TokenId> = (1000..3000).map(TokenId).collect();
+    for &id in &tokens {
+        storage
+            .tokens_schema()
+            .store_or_update_token(Token {
+                id,
+                address: Address::random(),
+                symbol: format!("TKN{}", id),
+                decimals: 18,
+                kind: TokenKind::ERC20,
+                is_nft: false,
+            })
+            .await?;
+    }
+
+    // Every token is deposited in the verified block
+    let mut updates1 = vec![(
+        AccountId(1),
+        AccountUpdate::Create {
+            address,
+            nonce: Nonce(0),
+        },
+    )];
+    updates1.extend(tokens.iter().map(|&token| {
+        (
+            AccountId(1),
+            AccountUpdate::UpdateBalance {
+                old_nonce: Nonce(0),
+                new_nonce: Nonce(0),
+                balance_update: (token, BigUint::zero(), BigUint::from(*token)),
+            },
+        )
+    }));
+    storage
+        .chain()
+        .state_schema()
+        .commit_state_update(BlockNumber(1), &updates1, 0)
+        .await?;
+    storage
+        .chain()
+        .state_schema()
+        .apply_state_update(BlockNumber(1))
+        .await?;
+
+    // Some of them are withdrawn in the committed one
+    let updates2 = vec![
+        (
+            AccountId(1),
+            AccountUpdate::UpdateBalance {
+                old_nonce: Nonce(0),
+                new_nonce: Nonce(1),
+                balance_update: (TokenId(1000), BigUint::from(1000u32), BigUint::zero()),
+            },
+        ),
+        (
+            AccountId(1),
+            AccountUpdate::UpdateBalance {
+                old_nonce: Nonce(1),
+                new_nonce: Nonce(2),
+                balance_update: (TokenId(1001), BigUint::from(1001u32), BigUint::from(1u32)),
+            },
+        ),
+    ];
+    storage
+        .chain()
+        .state_schema()
+        .commit_state_update(BlockNumber(2), &updates2, updates1.len())
+        .await?;
+
+    let (_, committed) = AccountSchema(&mut storage)
+        .account_state_by_address(address)
+        .await?
+        .committed
+        .unwrap();
+    assert_eq!(committed.get_nonzero_balances().len(), tokens.len() - 1);
+
+    let nonce = AccountSchema(&mut storage)
+        .committed_nonce_by_address(address)
+        .await?;
+    assert_eq!(nonce, Some(committed.nonce));
+    assert_eq!(nonce, Some(Nonce(2)));
+
+    // Only the requested tokens are loaded, the token 5 has never been held by the account
+    let requested = [TokenId(1000), TokenId(1001), TokenId(2500), TokenId(5)];
+    let balances = AccountSchema(&mut storage)
+        .committed_balances_by_address(address, &requested)
+        .await?;
+    assert_eq!(balances.len(), 3);
+    for token in &requested[..3] {
+        assert_eq!(balances[token], committed.get_balance(*token));
+    }
+    assert_eq!(balances[&TokenId(1001)], BigUint::from(1u32));
+    assert_eq!(balances[&TokenId(2500)], BigUint::from(2500u32));
+
+    // Nothing is loaded for the account which does not exist
+    let unknown = Address::random();
+    assert_eq!(
+        AccountSchema(&mut storage)
+            .committed_nonce_by_address(unknown)
+            .await?,
+        None
+    );
+    assert!(AccountSchema(&mut storage)
+        .committed_balances_by_address(unknown, &requested)
+        .await?
+        .is_empty());
+
+    Ok(())
+}
+
 #[db_test]
 async fn test_get_account_nft_balance(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
     let address = Address::random();
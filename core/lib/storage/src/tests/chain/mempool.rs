@@ -6,7 +6,7 @@ use zksync_types::{
     block::{Block, ExecutedOperations},
     mempool::SignedTxVariant,
     priority_ops::FullExit,
-    tx::{ChangePubKey, Transfer, TxHash, Withdraw},
+    tx::{ChangePubKey, ForcedExit, Transfer, TxHash, Withdraw},
     AccountId, Address, BlockNumber, ExecutedPriorityOp, ExecutedTx, FullExitOp, Nonce, PriorityOp,
     SignedZkSyncTx, TokenId, ZkSyncOp, ZkSyncPriorityOp, ZkSyncTx, H256,
 };
@@ -512,3 +512,48 @@ async fn test_return_executed_txs_to_mempool(mut storage: StorageProcessor<'_>)
     assert_eq!(block_tx.variance_name(), "FullExit");
     Ok(())
 }
+
+/// Checks that the pending nonce follows the last transaction of the account in the mempool.
+#[db_test]
+async fn pending_nonce(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let sender = AccountId(7);
+    let forced_exit = |nonce| SignedZkSyncTx {
+        tx: ZkSyncTx::ForcedExit(Box::new(ForcedExit::new(
+            sender,
+            Address::random(),
+            TokenId(0),
+            10u32.into(),
+            Nonce(nonce),
+            Default::default(),
+            None,
+        ))),
+        eth_sign_data: None,
+        created_at: Utc::now(),
+    };
+
+    let mut mempool = MempoolSchema(&mut storage);
+    assert_eq!(mempool.get_pending_nonce(sender).await?, None);
+
+    // The transactions of the other accounts do not matter
+    for tx in gen_transfers(3) {
+        mempool.insert_tx(&tx).await?;
+    }
+    assert_eq!(mempool.get_pending_nonce(sender).await?, None);
+
+    let txs = vec![forced_exit(5), forced_exit(6), forced_exit(7)];
+    mempool.insert_batch(&txs[..2], vec![]).await?;
+    mempool.insert_tx(&txs[2]).await?;
+    assert_eq!(mempool.get_pending_nonce(sender).await?, Some(Nonce(8)));
+    // The initiators of the other kinds of the transactions are recognized as well
+    assert_eq!(
+        mempool.get_pending_nonce(AccountId(2)).await?,
+        Some(Nonce(11))
+    );
+
+    // Once executed, the transactions are not pending anymore
+    let hashes: Vec<_> = txs.iter().map(|tx| tx.hash()).collect();
+    mempool.remove_txs(&hashes).await?;
+    assert_eq!(mempool.get_pending_nonce(sender).await?, None);
+
+    Ok(())
+}
@@ -943,6 +943,48 @@ async fn tx_receipt(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
     Ok(())
 }
 
+/// Test `tx_receipts` method
+#[db_test]
+async fn tx_receipts(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let mut setup = TransactionsHistoryTestSetup::new();
+    setup.add_block(1);
+    commit_schema_data(&mut storage, &setup).await?;
+
+    let hashes = vec![
+        setup.get_tx_hash(0, 1),
+        setup.get_tx_hash(0, 2),
+        // The transaction which has not been executed
+        TxHash::from_slice(&[0xDE; 32]).unwrap(),
+    ];
+    let mut receipts = storage
+        .chain()
+        .operations_ext_schema()
+        .tx_receipts(&hashes)
+        .await?;
+    receipts.sort_by_key(|receipt| receipt.tx_hash.clone());
+
+    // The receipts are the same as the ones loaded one by one
+    let mut expected_receipts = Vec::new();
+    for hash in &hashes {
+        if let Some(receipt) = storage
+            .chain()
+            .operations_ext_schema()
+            .tx_receipt(hash.as_ref())
+            .await?
+        {
+            expected_receipts.push(receipt);
+        }
+    }
+    expected_receipts.sort_by_key(|receipt| receipt.tx_hash.clone());
+    assert_eq!(expected_receipts.len(), 2);
+    assert_eq!(
+        format!("{:?}", receipts),
+        format!("{:?}", expected_receipts)
+    );
+
+    Ok(())
+}
+
 /// Test `tx_data_api_v02` method
 #[db_test]
 async fn tx_data(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
@@ -3,16 +3,36 @@ use std::{
     str::FromStr,
 };
 
+use crate::chain::mempool::MempoolSchema;
+use crate::encryption::ColumnCipher;
 use crate::forced_exit_requests::ForcedExitRequestsSchema;
 use crate::tests::db_test;
+use crate::utils::address_to_stored_string;
 use crate::QueryResult;
 use crate::StorageProcessor;
 use chrono::{Duration, Timelike, Utc};
 use num::{BigUint, FromPrimitive};
+use zksync_api_types::v02::pagination::{PaginationDirection, PaginationQuery};
 use zksync_types::{
-    forced_exit_requests::{ForcedExitRequest, SaveForcedExitRequestQuery},
-    tx::TxHash,
-    Address,
+    forced_exit_requests::{
+        check_digit, legacy_pay_exactly, pay_exactly, ActiveTargetPolicy, ForcedExitBacklogReport,
+        ForcedExitCancellation, ForcedExitCancellationKind, ForcedExitConsistencyReport,
+        ForcedExitFulfilledCallback, ForcedExitFulfillment, ForcedExitFulfillmentMismatch,
+        ForcedExitInvariant, ForcedExitInvariantViolation, ForcedExitLifecycleStatus,
+        ForcedExitMoneyConfig, ForcedExitPacing, ForcedExitPacingState, ForcedExitPayment,
+        ForcedExitPaymentTerms, ForcedExitPipelineStage, ForcedExitPipelineVersion,
+        ForcedExitProcessingFailure, ForcedExitRefund, ForcedExitRefundReason,
+        ForcedExitRefundStatus, ForcedExitRequest, ForcedExitRequestActiveTarget,
+        ForcedExitRequestEscalation, ForcedExitRequestEvent, ForcedExitRequestsApiKey,
+        ForcedExitSenderState, ForcedExitTokenSkipReason, PaymentMatchScheme, PaymentSource,
+        PaymentSourceState, PreparedFullExit, SaveForcedExitRefundQuery,
+        SaveForcedExitRequestNoteQuery, SaveForcedExitRequestQuery,
+        SaveForcedExitRequestsApiKeyQuery, SaveInjectedForcedExitPaymentQuery, SkippedForcedExit,
+        SubmissionError, UnmatchedPaymentReason, FORCED_EXIT_PIPELINE_VERSION,
+    },
+    tx::{Transfer, TxHash},
+    AccountId, Address, Deposit, Nonce, PriorityOp, SignedZkSyncTx, ZkSyncPriorityOp, ZkSyncTx,
+    H256,
 };
 
 use std::ops::Add;
@@ -49,6 +69,9 @@ async fn get_oldest_unfulfilled_request(mut storage: StorageProcessor<'_>) -> Qu
             price_in_wei: BigUint::from_i32(212).unwrap(),
             created_at: now,
             valid_until: now,
+            metadata: None,
+            payment_terms: None,
+            callback_url: None,
         },
         SaveForcedExitRequestQuery {
             target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
@@ -56,6 +79,9 @@ async fn get_oldest_unfulfilled_request(mut storage: StorageProcessor<'_>) -> Qu
             price_in_wei: BigUint::from_i32(1).unwrap(),
             created_at: now,
             valid_until: now,
+            metadata: None,
+            payment_terms: None,
+            callback_url: None,
         },
         SaveForcedExitRequestQuery {
             target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
@@ -63,6 +89,9 @@ async fn get_oldest_unfulfilled_request(mut storage: StorageProcessor<'_>) -> Qu
             price_in_wei: BigUint::from_str("1000000000000000").unwrap(),
             created_at: now,
             valid_until: now,
+            metadata: None,
+            payment_terms: None,
+            callback_url: None,
         },
     ];
 
@@ -134,6 +163,9 @@ async fn delete_old_requests(mut storage: StorageProcessor<'_>) -> QueryResult<(
             created_at: now.sub(day.mul(8)),
             // Invalid for 6 days => should be deleted
             valid_until: now.sub(day.mul(6)),
+            metadata: None,
+            payment_terms: None,
+            callback_url: None,
         },
         SaveForcedExitRequestQuery {
             target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
@@ -142,6 +174,9 @@ async fn delete_old_requests(mut storage: StorageProcessor<'_>) -> QueryResult<(
             created_at: now.sub(day.mul(5)).sub(minute),
             // Invalid for 3 days and 1 minutes => should be deleted
             valid_until: now.sub(day.mul(3)).sub(minute),
+            metadata: None,
+            payment_terms: None,
+            callback_url: None,
         },
         SaveForcedExitRequestQuery {
             target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
@@ -150,6 +185,9 @@ async fn delete_old_requests(mut storage: StorageProcessor<'_>) -> QueryResult<(
             created_at: now.sub(day.mul(5)).add(minute.mul(5)),
             // Invalid for 3 days minus 5 minutes => should not be deleted
             valid_until: now.sub(day.mul(3)).add(minute.mul(5)),
+            metadata: None,
+            payment_terms: None,
+            callback_url: None,
         },
         SaveForcedExitRequestQuery {
             target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
@@ -158,6 +196,9 @@ async fn delete_old_requests(mut storage: StorageProcessor<'_>) -> QueryResult<(
             created_at: now.sub(day.mul(5)).add(minute.mul(5)),
             // Is valid => should not be deleted
             valid_until: now.sub(day.mul(3)).add(minute.mul(5)),
+            metadata: None,
+            payment_terms: None,
+            callback_url: None,
         },
     ];
 
@@ -172,7 +213,7 @@ async fn delete_old_requests(mut storage: StorageProcessor<'_>) -> QueryResult<(
     // Setting fullfilled_by for the oldest request
     // so that it should not be deleted
     ForcedExitRequestsSchema(&mut storage)
-        .set_fulfilled_by(stored_requests[0].id, Some(vec![transaction_hash]))
+        .set_fulfilled_by(stored_requests[0].id, Some(vec![transaction_hash]), true)
         .await?;
 
     ForcedExitRequestsSchema(&mut storage)
@@ -201,3 +242,3554 @@ async fn delete_old_requests(mut storage: StorageProcessor<'_>) -> QueryResult<(
 
     Ok(())
 }
+
+// Checks that the way the payment was matched is stored
+#[db_test]
+async fn set_match_scheme(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+
+    let requests = vec![
+        SaveForcedExitRequestQuery {
+            target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+            tokens: vec![TokenId(1)],
+            price_in_wei: BigUint::from_i32(212).unwrap(),
+            created_at: now,
+            valid_until: now.add(Duration::days(1)),
+            metadata: None,
+            payment_terms: None,
+            callback_url: None,
+        },
+        SaveForcedExitRequestQuery {
+            target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+            tokens: vec![TokenId(1)],
+            price_in_wei: BigUint::from_i32(212).unwrap(),
+            created_at: now,
+            valid_until: now.add(Duration::days(1)),
+            metadata: None,
+            payment_terms: None,
+            callback_url: None,
+        },
+    ];
+
+    let stored_requests = store_requests(&mut storage, requests).await;
+    // Freshly created requests have not been matched with any payment yet
+    assert!(stored_requests[0].match_scheme.is_none());
+
+    ForcedExitRequestsSchema(&mut storage)
+        .set_match_scheme(stored_requests[0].id, PaymentMatchScheme::ExplicitId, now)
+        .await?;
+    ForcedExitRequestsSchema(&mut storage)
+        .set_match_scheme(stored_requests[1].id, PaymentMatchScheme::AmountDigits, now)
+        .await?;
+
+    let first = ForcedExitRequestsSchema(&mut storage)
+        .get_request_by_id(stored_requests[0].id)
+        .await?
+        .unwrap();
+    assert_eq!(first.match_scheme, Some(PaymentMatchScheme::ExplicitId));
+
+    let second = ForcedExitRequestsSchema(&mut storage)
+        .get_request_by_id(stored_requests[1].id)
+        .await?
+        .unwrap();
+    assert_eq!(second.match_scheme, Some(PaymentMatchScheme::AmountDigits));
+
+    Ok(())
+}
+
+// Checks that the requests are found by the closest amount they are paid for with
+#[db_test]
+async fn request_by_closest_amount(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_i32(2000).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::days(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let stored = store_requests(&mut storage, vec![request; 3]).await;
+    let ids: Vec<_> = stored.iter().map(|request| request.id).collect();
+    let amount = |id: i64| BigUint::from(2000u32) + id as u64 * 10 + u64::from(check_digit(id));
+
+    let mut schema = ForcedExitRequestsSchema(&mut storage);
+    let closest_ids = |requests: Vec<ForcedExitRequest>| -> Vec<_> {
+        requests.into_iter().map(|request| request.id).collect()
+    };
+    assert_eq!(
+        closest_ids(
+            schema
+                .get_requests_by_closest_amount(&amount(ids[0]), now)
+                .await?
+        ),
+        vec![ids[0]]
+    );
+    // The closest requests are loaded along with the next one, so the caller can tell
+    // whether the overpayment may have been made for either of them
+    let overpaid = amount(ids[2]) + 5u32;
+    assert_eq!(
+        closest_ids(
+            schema
+                .get_requests_by_closest_amount(&overpaid, now)
+                .await?
+        ),
+        vec![ids[2], ids[1]]
+    );
+    assert!(schema
+        .get_requests_by_closest_amount(&(amount(ids[0]) - 1u32), now)
+        .await?
+        .is_empty());
+
+    // The requests which can not be paid for anymore do not hide the payable ones
+    schema.set_fulfilled_at(ids[2], now).await?;
+    schema
+        .cancel_request(ids[1], ForcedExitCancellationKind::UserCancelled, now, true)
+        .await?
+        .unwrap();
+    assert_eq!(
+        closest_ids(
+            schema
+                .get_requests_by_closest_amount(&overpaid, now)
+                .await?
+        ),
+        vec![ids[0]]
+    );
+    // The request cancelled to be sent again is still payable
+    schema
+        .cancel_request(ids[0], ForcedExitCancellationKind::SystemRetry, now, true)
+        .await?
+        .unwrap();
+    assert_eq!(
+        closest_ids(
+            schema
+                .get_requests_by_closest_amount(&overpaid, now)
+                .await?
+        ),
+        vec![ids[0]]
+    );
+    assert!(schema
+        .get_requests_by_closest_amount(&overpaid, now.add(Duration::days(2)))
+        .await?
+        .is_empty());
+
+    // The amount the request was paid for with is kept on the request
+    assert_eq!(stored[0].paid_amount, None);
+    schema.set_paid_amount(ids[0], &overpaid).await?;
+    let paid = schema.get_request_by_id(ids[0]).await?.unwrap();
+    assert_eq!(paid.paid_amount, Some(overpaid.to_string()));
+
+    Ok(())
+}
+
+// Checks that only the validity of the unprocessed requests can be changed
+#[db_test]
+async fn set_valid_until(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::hours(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let stored_requests = store_requests(&mut storage, vec![request.clone(), request]).await;
+
+    let extended = ForcedExitRequestsSchema(&mut storage)
+        .set_valid_until(stored_requests[0].id, now.add(Duration::days(1)))
+        .await?
+        .expect("The request is pending");
+    assert_eq!(extended.valid_until, now.add(Duration::days(1)));
+    assert_eq!(
+        ForcedExitRequestsSchema(&mut storage)
+            .get_request_by_id(stored_requests[0].id)
+            .await?,
+        Some(extended)
+    );
+
+    // The request is being fulfilled
+    ForcedExitRequestsSchema(&mut storage)
+        .set_fulfilled_by(stored_requests[1].id, Some(vec![TxHash::default()]), true)
+        .await?;
+    let extended = ForcedExitRequestsSchema(&mut storage)
+        .set_valid_until(stored_requests[1].id, now.add(Duration::days(1)))
+        .await?;
+    assert!(extended.is_none());
+
+    // Unknown request
+    let extended = ForcedExitRequestsSchema(&mut storage)
+        .set_valid_until(-1, now.add(Duration::days(1)))
+        .await?;
+    assert!(extended.is_none());
+
+    Ok(())
+}
+
+// Checks that the failures are counted per token of the request
+#[db_test]
+async fn record_failures(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+
+    let requests = vec![SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1), TokenId(2)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::days(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    }];
+    let id = store_requests(&mut storage, requests).await[0].id;
+
+    let mut schema = ForcedExitRequestsSchema(&mut storage);
+    assert_eq!(schema.record_failure(id, TokenId(1)).await?, 1);
+    assert_eq!(schema.record_failure(id, TokenId(1)).await?, 2);
+    assert_eq!(schema.record_failure(id, TokenId(2)).await?, 1);
+    assert_eq!(schema.record_failure(id, TokenId(1)).await?, 3);
+
+    Ok(())
+}
+
+// Checks that only the last processing failure of the known request is kept
+#[db_test]
+async fn processing_failures(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+
+    let requests = vec![SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::days(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    }];
+    let id = store_requests(&mut storage, requests).await[0].id;
+
+    let mut schema = ForcedExitRequestsSchema(&mut storage);
+    assert_eq!(schema.get_processing_failure(id).await?, None);
+
+    let first = ForcedExitProcessingFailure {
+        request_id: id,
+        attempts: 3,
+        error: "Connection refused".to_owned(),
+        failed_at: now,
+        submission_error: None,
+    };
+    schema.store_processing_failure(&first).await?;
+    assert_eq!(schema.get_processing_failure(id).await?, Some(first));
+
+    let last = ForcedExitProcessingFailure {
+        request_id: id,
+        attempts: 5,
+        error: "Nonce mismatch".to_owned(),
+        failed_at: now.add(Duration::minutes(1)),
+        submission_error: Some(SubmissionError::rejected(Some(105), "Nonce mismatch", true)),
+    };
+    schema.store_processing_failure(&last).await?;
+    assert_eq!(schema.get_processing_failure(id).await?, Some(last.clone()));
+
+    // The payments for the unknown requests are not recorded
+    let unknown = ForcedExitProcessingFailure {
+        request_id: id + 1,
+        ..last
+    };
+    schema.store_processing_failure(&unknown).await?;
+    assert_eq!(schema.get_processing_failure(id + 1).await?, None);
+
+    Ok(())
+}
+
+// Checks that the pipeline version is kept per stage and replaced by the later one
+#[db_test]
+async fn pipeline_versions(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+
+    let requests = vec![SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::days(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    }];
+    let id = store_requests(&mut storage, requests).await[0].id;
+
+    let mut schema = ForcedExitRequestsSchema(&mut storage);
+    assert!(schema.load_pipeline_versions(id).await?.is_empty());
+
+    let created = ForcedExitPipelineVersion {
+        request_id: id,
+        stage: ForcedExitPipelineStage::Created,
+        version: 1,
+        config_hash: "aa".to_owned(),
+        recorded_at: now,
+    };
+    schema.store_pipeline_version(&created).await?;
+    let fulfilled = ForcedExitPipelineVersion {
+        stage: ForcedExitPipelineStage::Fulfilled,
+        version: 2,
+        config_hash: "bb".to_owned(),
+        recorded_at: now.add(Duration::minutes(1)),
+        ..created.clone()
+    };
+    schema.store_pipeline_version(&fulfilled).await?;
+    assert_eq!(
+        schema.load_pipeline_versions(id).await?,
+        vec![created.clone(), fulfilled]
+    );
+
+    // The request processed again is stamped with the version of the last fulfillment
+    let refulfilled = ForcedExitPipelineVersion {
+        stage: ForcedExitPipelineStage::Fulfilled,
+        version: 3,
+        config_hash: "cc".to_owned(),
+        recorded_at: now.add(Duration::minutes(2)),
+        ..created.clone()
+    };
+    schema.store_pipeline_version(&refulfilled).await?;
+    assert_eq!(
+        schema.load_pipeline_versions(id).await?,
+        vec![created.clone(), refulfilled]
+    );
+
+    // Nothing is recorded for the unknown requests
+    let unknown = ForcedExitPipelineVersion {
+        request_id: id + 1,
+        ..created
+    };
+    schema.store_pipeline_version(&unknown).await?;
+    assert!(schema.load_pipeline_versions(id + 1).await?.is_empty());
+
+    Ok(())
+}
+
+// Checks the lifecycle of the request escalated to L1
+#[db_test]
+async fn escalate_request(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+
+    let target = Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap();
+    // The requests are already expired, but the escalated one must be kept
+    let requests = vec![
+        SaveForcedExitRequestQuery {
+            target,
+            tokens: vec![TokenId(1)],
+            price_in_wei: BigUint::from_i32(212).unwrap(),
+            created_at: now.sub(Duration::days(8)),
+            valid_until: now.sub(Duration::days(6)),
+            metadata: None,
+            payment_terms: None,
+            callback_url: None,
+        },
+        SaveForcedExitRequestQuery {
+            target,
+            tokens: vec![TokenId(1)],
+            price_in_wei: BigUint::from_i32(212).unwrap(),
+            created_at: now.sub(Duration::days(8)),
+            valid_until: now.sub(Duration::days(6)),
+            metadata: None,
+            payment_terms: None,
+            callback_url: None,
+        },
+    ];
+    let stored_requests = store_requests(&mut storage, requests).await;
+    let id = stored_requests[0].id;
+
+    let escalation = ForcedExitRequestEscalation {
+        request_id: id,
+        full_exits: vec![PreparedFullExit {
+            owner: target,
+            account_id: AccountId(12),
+            token: TokenId(1),
+            token_address: Address::repeat_byte(0x01),
+            contract: Address::repeat_byte(0x02),
+            calldata: vec![0x12, 0x34],
+        }],
+        created_at: now,
+        l1_tx_hash: None,
+        finalized_at: None,
+    };
+    ForcedExitRequestsSchema(&mut storage)
+        .store_escalation(escalation.clone())
+        .await?;
+    // Repeated escalation does not override the stored one
+    ForcedExitRequestsSchema(&mut storage)
+        .store_escalation(ForcedExitRequestEscalation {
+            full_exits: vec![],
+            ..escalation.clone()
+        })
+        .await?;
+
+    let stored = ForcedExitRequestsSchema(&mut storage)
+        .get_escalation(id)
+        .await?;
+    assert_eq!(stored, Some(escalation.clone()));
+    let pending = ForcedExitRequestsSchema(&mut storage)
+        .get_pending_escalations()
+        .await?;
+    assert_eq!(pending, vec![escalation]);
+
+    // The escalated request is not deleted along with the other expired ones
+    ForcedExitRequestsSchema(&mut storage)
+        .delete_old_unfulfilled_requests(Duration::days(3))
+        .await?;
+    assert!(ForcedExitRequestsSchema(&mut storage)
+        .get_request_by_id(id)
+        .await?
+        .is_some());
+    assert!(ForcedExitRequestsSchema(&mut storage)
+        .get_request_by_id(stored_requests[1].id)
+        .await?
+        .is_none());
+
+    let l1_tx_hash = H256::repeat_byte(0x12);
+    ForcedExitRequestsSchema(&mut storage)
+        .finalize_escalation(id, l1_tx_hash, now)
+        .await?;
+
+    let stored = ForcedExitRequestsSchema(&mut storage)
+        .get_escalation(id)
+        .await?
+        .unwrap();
+    assert_eq!(stored.l1_tx_hash, Some(l1_tx_hash));
+    assert_eq!(stored.finalized_at, Some(now));
+    assert!(!stored.is_awaiting());
+    assert!(ForcedExitRequestsSchema(&mut storage)
+        .get_pending_escalations()
+        .await?
+        .is_empty());
+
+    let request = ForcedExitRequestsSchema(&mut storage)
+        .get_request_by_id(id)
+        .await?
+        .unwrap();
+    assert_eq!(request.fulfilled_at, Some(now));
+
+    Ok(())
+}
+
+#[db_test]
+async fn active_targets(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+
+    let target = Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap();
+    // The requests are already expired, but the ones with the active targets must be kept
+    let request = SaveForcedExitRequestQuery {
+        target,
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now.sub(Duration::days(8)),
+        valid_until: now.sub(Duration::days(6)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let stored_requests = store_requests(
+        &mut storage,
+        vec![request.clone(), request.clone(), request],
+    )
+    .await;
+    let (held_id, failed_id) = (stored_requests[0].id, stored_requests[1].id);
+
+    let held = ForcedExitRequestActiveTarget {
+        request_id: held_id,
+        policy: ActiveTargetPolicy::Hold,
+        target_nonce: Nonce(1),
+        payment_amount: BigUint::from(212u32 + held_id as u32),
+        payment_tx_hash: Some(H256::repeat_byte(0x12)),
+        match_scheme: PaymentMatchScheme::AmountDigits,
+        paid_at: now.sub(Duration::days(7)),
+        detected_at: now.sub(Duration::days(7)),
+        hold_until: Some(now.add(Duration::hours(1))),
+        resumed_at: None,
+        failed_at: None,
+    };
+    let failed = ForcedExitRequestActiveTarget {
+        request_id: failed_id,
+        policy: ActiveTargetPolicy::Fail,
+        payment_tx_hash: None,
+        match_scheme: PaymentMatchScheme::ExplicitId,
+        hold_until: None,
+        failed_at: Some(now.sub(Duration::days(7))),
+        ..held.clone()
+    };
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    fe_schema.store_active_target(held.clone()).await?;
+    fe_schema.store_active_target(failed.clone()).await?;
+    // The policy applied first stays in effect
+    fe_schema
+        .store_active_target(ForcedExitRequestActiveTarget {
+            hold_until: None,
+            failed_at: Some(now),
+            ..held.clone()
+        })
+        .await?;
+
+    assert_eq!(
+        fe_schema.get_active_target(held_id).await?,
+        Some(held.clone())
+    );
+    assert_eq!(
+        fe_schema.get_active_target(failed_id).await?,
+        Some(failed.clone())
+    );
+    assert_eq!(fe_schema.load_held_active_targets().await?, vec![held]);
+    let matched: Vec<_> = fe_schema
+        .load_requests_by_payment(H256::repeat_byte(0x12))
+        .await?
+        .into_iter()
+        .map(|request| request.id)
+        .collect();
+    assert_eq!(matched, vec![held_id]);
+
+    // Neither of the requests is deleted along with the other expired ones
+    fe_schema
+        .delete_old_unfulfilled_requests(Duration::days(3))
+        .await?;
+    assert!(fe_schema.get_request_by_id(held_id).await?.is_some());
+    assert!(fe_schema.get_request_by_id(failed_id).await?.is_some());
+    assert!(fe_schema
+        .get_request_by_id(stored_requests[2].id)
+        .await?
+        .is_none());
+
+    fe_schema.fail_active_target(held_id, now).await?;
+    // The failed request can be neither resumed nor failed once again
+    fe_schema.resume_active_target(held_id, now).await?;
+    fe_schema
+        .fail_active_target(held_id, now.add(Duration::hours(1)))
+        .await?;
+
+    let stored = fe_schema.get_active_target(held_id).await?.unwrap();
+    assert_eq!(stored.failed_at, Some(now));
+    assert_eq!(stored.resumed_at, None);
+    assert!(stored.is_failed() && !stored.is_held());
+    assert!(fe_schema.load_held_active_targets().await?.is_empty());
+
+    // Both of the failures are notified about exactly once
+    let events: Vec<_> = fe_schema
+        .load_pending_deliveries(now.add(Duration::days(1)), 10, false)
+        .await?
+        .into_iter()
+        .map(|delivery| (delivery.request_id, delivery.event))
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            (failed_id, ForcedExitRequestEvent::Failed),
+            (held_id, ForcedExitRequestEvent::Failed),
+        ]
+    );
+
+    Ok(())
+}
+
+#[db_test]
+async fn requests_pagination(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let target = Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap();
+    let other_target = Address::repeat_byte(0x12);
+
+    let request = |target| SaveForcedExitRequestQuery {
+        target,
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::days(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let stored_requests = store_requests(
+        &mut storage,
+        vec![
+            request(target),
+            request(other_target),
+            request(target),
+            request(target),
+        ],
+    )
+    .await;
+    let ids: Vec<_> = stored_requests.iter().map(|r| r.id).collect();
+
+    assert_eq!(
+        ForcedExitRequestsSchema(&mut storage)
+            .get_requests_count(target)
+            .await?,
+        3
+    );
+    assert_eq!(
+        ForcedExitRequestsSchema(&mut storage)
+            .get_last_request_id(target)
+            .await?,
+        Some(ids[3])
+    );
+    assert_eq!(
+        ForcedExitRequestsSchema(&mut storage)
+            .get_last_request_id(Address::repeat_byte(0x34))
+            .await?,
+        None
+    );
+
+    let page = ForcedExitRequestsSchema(&mut storage)
+        .load_requests_page(
+            target,
+            &PaginationQuery {
+                from: ids[0],
+                limit: 2,
+                direction: PaginationDirection::Newer,
+            },
+        )
+        .await?;
+    assert_eq!(
+        page,
+        vec![stored_requests[0].clone(), stored_requests[2].clone()]
+    );
+
+    let page = ForcedExitRequestsSchema(&mut storage)
+        .load_requests_page(
+            target,
+            &PaginationQuery {
+                from: ids[3],
+                limit: 10,
+                direction: PaginationDirection::Older,
+            },
+        )
+        .await?;
+    assert_eq!(
+        page,
+        vec![
+            stored_requests[3].clone(),
+            stored_requests[2].clone(),
+            stored_requests[0].clone()
+        ]
+    );
+
+    Ok(())
+}
+
+#[db_test]
+async fn api_keys(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let key_hash = H256::repeat_byte(0x21);
+
+    let api_key = ForcedExitRequestsSchema(&mut storage)
+        .store_api_key(SaveForcedExitRequestsApiKeyQuery {
+            label: "partner".to_owned(),
+            key_hash,
+            max_tokens_per_request: Some(20),
+            max_requests_per_hour: None,
+            created_at: now,
+        })
+        .await?;
+    assert_eq!(api_key.label, "partner");
+    assert_eq!(api_key.max_tokens_per_request, Some(20));
+    assert_eq!(api_key.max_requests_per_hour, None);
+    assert!(!api_key.is_revoked());
+
+    assert_eq!(
+        ForcedExitRequestsSchema(&mut storage)
+            .get_api_key_by_hash(key_hash)
+            .await?,
+        Some(api_key.clone())
+    );
+    assert_eq!(
+        ForcedExitRequestsSchema(&mut storage)
+            .get_api_key_by_hash(H256::repeat_byte(0x22))
+            .await?,
+        None
+    );
+    assert_eq!(
+        ForcedExitRequestsSchema(&mut storage)
+            .load_api_keys()
+            .await?,
+        vec![api_key.clone()]
+    );
+
+    // The requests created with the key are attributed to it and counted separately
+    let request = SaveForcedExitRequestQuery {
+        target: Address::repeat_byte(0x12),
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from(1000u32),
+        created_at: now,
+        valid_until: now.add(Duration::hours(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let partner_request = ForcedExitRequestsSchema(&mut storage)
+        .store_request_with_api_key(request.clone(), api_key.id)
+        .await?;
+    let anonymous_requests = store_requests(&mut storage, vec![request.clone(), request]).await;
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    assert_eq!(
+        fe_schema.get_request_api_key_id(partner_request.id).await?,
+        Some(api_key.id)
+    );
+    assert_eq!(
+        fe_schema
+            .get_request_api_key_id(anonymous_requests[0].id)
+            .await?,
+        None
+    );
+    let since = now.sub(Duration::minutes(1));
+    assert_eq!(
+        fe_schema
+            .count_requests_created_since(Some(api_key.id), since)
+            .await?,
+        1
+    );
+    assert_eq!(
+        fe_schema.count_requests_created_since(None, since).await?,
+        2
+    );
+    assert_eq!(
+        fe_schema
+            .count_requests_created_since(Some(api_key.id), now.add(Duration::minutes(1)))
+            .await?,
+        0
+    );
+
+    // The key can only be revoked once
+    let revoked_at = now.add(Duration::minutes(5));
+    let revoked = fe_schema.revoke_api_key(api_key.id, revoked_at).await?;
+    assert_eq!(
+        revoked,
+        Some(ForcedExitRequestsApiKey {
+            revoked_at: Some(revoked_at),
+            ..api_key.clone()
+        })
+    );
+    assert_eq!(
+        fe_schema.revoke_api_key(api_key.id, revoked_at).await?,
+        None
+    );
+    assert!(fe_schema
+        .get_api_key(api_key.id)
+        .await?
+        .unwrap()
+        .is_revoked());
+
+    Ok(())
+}
+
+#[db_test]
+async fn store_payments(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let payment = |block_number: u64, request_id: Option<i64>| ForcedExitPayment {
+        amount: BigUint::from(1_000_000_012u64),
+        request_id,
+        block_number,
+        eth_tx_hash: Some(H256::from_low_u64_be(block_number)),
+        payer: Some(Address::repeat_byte(0x12)),
+        received_at: now,
+        source: PaymentSource::L1Event,
+    };
+    // The rescanned payment is recorded once per processing
+    let payments = vec![
+        payment(12, None),
+        payment(10, Some(34)),
+        payment(12, None),
+        ForcedExitPayment {
+            eth_tx_hash: None,
+            payer: None,
+            source: PaymentSource::Admin,
+            ..payment(15, None)
+        },
+    ];
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    for payment in &payments {
+        fe_schema.store_payment(payment).await?;
+    }
+
+    assert_eq!(fe_schema.load_payments(0, 100).await?, payments);
+    assert_eq!(
+        fe_schema.load_payments(10, 12).await?,
+        payments[..3].to_vec()
+    );
+    assert!(fe_schema.load_payments(13, 14).await?.is_empty());
+
+    // The range is read page by page in the same order
+    let first_page = fe_schema.load_payments_page(0, 100, None, 3).await?;
+    let ids: Vec<_> = first_page.iter().map(|(id, _)| *id).collect();
+    assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+    let last_id = ids.last().copied();
+    let second_page = fe_schema.load_payments_page(0, 100, last_id, 3).await?;
+    let paged: Vec<_> = first_page
+        .into_iter()
+        .chain(second_page)
+        .map(|(_, payment)| payment)
+        .collect();
+    assert_eq!(paged, payments);
+    assert!(fe_schema
+        .load_payments_page(10, 12, last_id, 3)
+        .await?
+        .is_empty());
+
+    Ok(())
+}
+
+#[db_test]
+async fn payment_sources(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    assert!(fe_schema.load_payment_source_states().await?.is_empty());
+
+    fe_schema
+        .set_payment_source_enabled(PaymentSource::L1Event, false, now)
+        .await?;
+    fe_schema
+        .set_payment_source_enabled(PaymentSource::Admin, false, now)
+        .await?;
+    // The state is overwritten rather than added
+    let later = now + Duration::minutes(1);
+    let state = fe_schema
+        .set_payment_source_enabled(PaymentSource::L1Event, true, later)
+        .await?;
+    assert_eq!(
+        state,
+        PaymentSourceState {
+            source: PaymentSource::L1Event,
+            enabled: true,
+            updated_at: later,
+        }
+    );
+
+    let states = fe_schema.load_payment_source_states().await?;
+    assert_eq!(
+        states,
+        vec![
+            PaymentSourceState {
+                source: PaymentSource::Admin,
+                enabled: false,
+                updated_at: now,
+            },
+            state,
+        ]
+    );
+
+    Ok(())
+}
+
+#[db_test]
+async fn injected_payments(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let query = |request_id: Option<i64>| SaveInjectedForcedExitPaymentQuery {
+        amount: BigUint::from(1_000_000_012u64),
+        request_id,
+        block_number: 10,
+        eth_tx_hash: request_id.map(|id| H256::from_low_u64_be(id as u64)),
+        created_at: now,
+    };
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    let first = fe_schema.store_injected_payment(query(Some(34))).await?;
+    let second = fe_schema.store_injected_payment(query(None)).await?;
+    assert_eq!(first.request_id, Some(34));
+    assert_eq!(first.eth_tx_hash, Some(H256::from_low_u64_be(34)));
+    assert_eq!(second.amount, BigUint::from(1_000_000_012u64));
+    assert_eq!(second.created_at, now);
+
+    assert_eq!(
+        fe_schema.load_injected_payments(10).await?,
+        vec![first.clone(), second.clone()]
+    );
+    assert_eq!(
+        fe_schema.load_injected_payments(1).await?,
+        vec![first.clone()]
+    );
+
+    // The processed payments are removed
+    fe_schema.delete_injected_payment(first.id).await?;
+    assert_eq!(fe_schema.load_injected_payments(10).await?, vec![second]);
+
+    Ok(())
+}
+
+#[db_test]
+async fn unmatched_payments(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    // Way more digits than the `NUMERIC` column could hold before the decimal point
+    let enormous = BigUint::from(10u32).pow(200_000) + 12u32;
+    let payment = ForcedExitPayment {
+        amount: enormous.clone(),
+        request_id: Some(34),
+        block_number: 10,
+        eth_tx_hash: Some(H256::from_low_u64_be(34)),
+        payer: None,
+        received_at: now,
+        source: PaymentSource::Admin,
+    };
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    let first = fe_schema
+        .store_unmatched_payment(&payment, UnmatchedPaymentReason::AmountOutOfRange)
+        .await?;
+    let second = fe_schema
+        .store_unmatched_payment(
+            &ForcedExitPayment {
+                amount: BigUint::from(u128::MAX),
+                source: PaymentSource::L1Event,
+                ..payment
+            },
+            UnmatchedPaymentReason::AmountOutOfRange,
+        )
+        .await?;
+    assert_eq!(first.amount.parse::<BigUint>().unwrap(), enormous);
+    assert_eq!(first.request_id, Some(34));
+    assert_eq!(first.eth_tx_hash, Some(H256::from_low_u64_be(34)));
+    assert_eq!(first.source, PaymentSource::Admin);
+    assert_eq!(first.reason, UnmatchedPaymentReason::AmountOutOfRange);
+    assert_eq!(first.received_at, now);
+    assert_eq!(second.amount, u128::MAX.to_string());
+
+    // The latest payments go first
+    assert_eq!(
+        fe_schema.load_unmatched_payments(10).await?,
+        vec![second.clone(), first]
+    );
+    assert_eq!(fe_schema.load_unmatched_payments(1).await?, vec![second]);
+
+    Ok(())
+}
+
+// Checks that the payments are found by the hash of the L1 transaction
+#[db_test]
+async fn payments_by_tx_hash(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::days(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let stored_requests = store_requests(&mut storage, vec![request.clone(), request]).await;
+    let payment = |eth_tx_hash: H256| ForcedExitPayment {
+        amount: BigUint::from(212u32),
+        request_id: None,
+        block_number: 10,
+        eth_tx_hash: Some(eth_tx_hash),
+        payer: None,
+        received_at: now,
+        source: PaymentSource::L1Event,
+    };
+    let split_hash = H256::repeat_byte(0x11);
+    let unmatched_hash = H256::repeat_byte(0x22);
+    let unknown_hash = H256::repeat_byte(0x33);
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    // A single transaction pays for both requests, the match recorded twice is stored once
+    fe_schema.store_payment(&payment(split_hash)).await?;
+    for request in stored_requests.iter().rev() {
+        fe_schema
+            .store_payment_match(request.id, split_hash, now)
+            .await?;
+    }
+    fe_schema
+        .store_payment_match(stored_requests[0].id, split_hash, now)
+        .await?;
+    fe_schema
+        .store_unmatched_payment(
+            &payment(unmatched_hash),
+            UnmatchedPaymentReason::AmountOutOfRange,
+        )
+        .await?;
+
+    assert_eq!(
+        fe_schema.load_requests_by_payment(split_hash).await?,
+        stored_requests
+    );
+    assert!(fe_schema.is_payment_recorded(split_hash).await?);
+
+    assert!(fe_schema
+        .load_requests_by_payment(unmatched_hash)
+        .await?
+        .is_empty());
+    let unmatched = fe_schema
+        .load_unmatched_payments_by_tx_hash(unmatched_hash)
+        .await?;
+    assert_eq!(unmatched.len(), 1);
+    assert_eq!(
+        unmatched[0].reason,
+        UnmatchedPaymentReason::AmountOutOfRange
+    );
+    // The payments set aside are not recorded among the processed ones
+    assert!(!fe_schema.is_payment_recorded(unmatched_hash).await?);
+
+    assert!(fe_schema
+        .load_requests_by_payment(unknown_hash)
+        .await?
+        .is_empty());
+    assert!(fe_schema
+        .load_unmatched_payments_by_tx_hash(unknown_hash)
+        .await?
+        .is_empty());
+    assert!(!fe_schema.is_payment_recorded(unknown_hash).await?);
+
+    Ok(())
+}
+
+// Checks that the payments of the request are the matched and the refunded ones,
+// the payments for the other requests made by the same transaction are left out
+#[db_test]
+async fn request_payments(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::days(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let stored_requests = store_requests(&mut storage, vec![request.clone(), request]).await;
+    let (first, second) = (&stored_requests[0], &stored_requests[1]);
+    let payment = |request_id, eth_tx_hash| ForcedExitPayment {
+        amount: BigUint::from(212u32),
+        request_id,
+        block_number: 10,
+        eth_tx_hash: Some(eth_tx_hash),
+        payer: None,
+        received_at: now,
+        source: PaymentSource::L1Event,
+    };
+    let split_hash = H256::repeat_byte(0x11);
+    let refunded_hash = H256::repeat_byte(0x22);
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    let first_payment = payment(Some(first.public_id), split_hash);
+    let second_payment = payment(Some(second.public_id), split_hash);
+    let refunded_payment = payment(None, refunded_hash);
+    for payment in [&first_payment, &second_payment, &refunded_payment] {
+        fe_schema.store_payment(payment).await?;
+    }
+    for request in &stored_requests {
+        fe_schema
+            .store_payment_match(request.id, split_hash, now)
+            .await?;
+    }
+    fe_schema
+        .store_refund(SaveForcedExitRefundQuery {
+            request_id: first.id,
+            payment_tx_hash: refunded_hash,
+            recipient: Address::repeat_byte(0x42),
+            amount: BigUint::from_i32(200).unwrap(),
+            fee: BigUint::from_i32(12).unwrap(),
+            reason: ForcedExitRefundReason::IncorrectAmount,
+            requires_approval: false,
+            created_at: now,
+        })
+        .await?;
+
+    assert_eq!(
+        fe_schema
+            .load_request_payments(first.id, first.public_id)
+            .await?,
+        vec![first_payment, refunded_payment]
+    );
+    assert_eq!(
+        fe_schema
+            .load_request_payments(second.id, second.public_id)
+            .await?,
+        vec![second_payment]
+    );
+    assert!(fe_schema
+        .load_request_payments(second.id + 1, second.public_id + 1)
+        .await?
+        .is_empty());
+
+    Ok(())
+}
+
+// Checks that the amounts are not altered by the `NUMERIC` columns
+#[db_test]
+async fn amounts_round_trip(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let one = BigUint::from(1u32);
+    let mut amounts = vec![
+        BigUint::from(0u32),
+        BigUint::from(u64::MAX),
+        BigUint::from(u128::MAX) - &one,
+        BigUint::from(u128::MAX),
+        BigUint::from(u128::MAX) + &one,
+        (BigUint::from(1u32) << 256) - &one,
+    ];
+    // The prices with the id encoded in the lowest digits
+    for digits in &[18u32, 30, 40, 77] {
+        amounts.push(BigUint::from(10u32).pow(*digits) + 1_000_000_012u64);
+    }
+
+    for amount in &amounts {
+        let request = ForcedExitRequestsSchema(&mut storage)
+            .store_request(SaveForcedExitRequestQuery {
+                target: Address::repeat_byte(0x12),
+                tokens: vec![TokenId(1)],
+                price_in_wei: amount.clone(),
+                created_at: now,
+                valid_until: now,
+                metadata: None,
+                payment_terms: None,
+                callback_url: None,
+            })
+            .await?;
+        let stored = ForcedExitRequestsSchema(&mut storage)
+            .get_request_by_id(request.id)
+            .await?
+            .expect("The request is not stored");
+        assert_eq!(&stored.price_in_wei, amount);
+        // The amount to pay is stored along with the id, as the price with the public id
+        // and its check digit added
+        assert_eq!(stored.pay_exactly, request.pay_exactly);
+        assert_eq!(
+            stored.pay_exactly,
+            (amount + request.public_id as u64 * 10 + u64::from(check_digit(request.public_id)))
+                .to_string()
+        );
+
+        ForcedExitRequestsSchema(&mut storage)
+            .store_payment(&ForcedExitPayment {
+                amount: amount.clone(),
+                request_id: Some(request.id),
+                block_number: 1,
+                eth_tx_hash: None,
+                payer: None,
+                received_at: now,
+                source: PaymentSource::L1Event,
+            })
+            .await?;
+    }
+
+    let stored_amounts: Vec<_> = ForcedExitRequestsSchema(&mut storage)
+        .load_payments(1, 1)
+        .await?
+        .into_iter()
+        .map(|payment| payment.amount)
+        .collect();
+    assert_eq!(stored_amounts, amounts);
+
+    Ok(())
+}
+
+// Checks that the payers are encrypted transparently and can be looked up by the index
+#[db_test]
+async fn encrypted_payments(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let payer = Address::repeat_byte(0x27);
+    let payment = |block_number: u64| ForcedExitPayment {
+        amount: BigUint::from(1_000_000_012u64),
+        request_id: None,
+        block_number,
+        eth_tx_hash: None,
+        payer: Some(payer),
+        received_at: now,
+        source: PaymentSource::L1Event,
+    };
+    let cipher = ColumnCipher::new([7u8; 32], false);
+    let mixed_mode_cipher = ColumnCipher::new([7u8; 32], true);
+
+    // The payment stored before the encryption was enabled
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    fe_schema
+        .store_payment_with_cipher(&payment(1), None)
+        .await?;
+    fe_schema
+        .store_payment_with_cipher(&payment(2), Some(&mixed_mode_cipher))
+        .await?;
+    fe_schema
+        .store_payment_with_cipher(
+            &ForcedExitPayment {
+                payer: Some(Address::repeat_byte(0x28)),
+                ..payment(3)
+            },
+            Some(&mixed_mode_cipher),
+        )
+        .await?;
+
+    // The payer is not stored in plaintext, so it can not be read without the key
+    assert!(fe_schema
+        .load_payments_with_cipher(2, 2, None)
+        .await
+        .is_err());
+
+    // Both the plaintext and the encrypted rows are read in the mixed mode only
+    let payments = fe_schema
+        .load_payments_with_cipher(1, 2, Some(&mixed_mode_cipher))
+        .await?;
+    assert_eq!(payments, vec![payment(1), payment(2)]);
+    assert!(fe_schema
+        .load_payments_with_cipher(1, 2, Some(&cipher))
+        .await
+        .is_err());
+    assert!(fe_schema
+        .load_payments_with_cipher(1, 2, None)
+        .await
+        .is_err());
+    let payments = fe_schema
+        .load_payments_by_payer_with_cipher(payer, Some(&mixed_mode_cipher))
+        .await?;
+    assert_eq!(payments, vec![payment(1), payment(2)]);
+
+    // Once the existing rows are encrypted, the mixed mode is not needed anymore
+    loop {
+        let encrypted = fe_schema.encrypt_payments_batch(&cipher, 1).await?;
+        if encrypted == 0 {
+            break;
+        }
+    }
+    assert_eq!(
+        fe_schema
+            .load_payments_with_cipher(1, 2, Some(&cipher))
+            .await?,
+        vec![payment(1), payment(2)]
+    );
+    let payments = fe_schema
+        .load_payments_by_payer_with_cipher(payer, Some(&cipher))
+        .await?;
+    assert_eq!(payments, vec![payment(1), payment(2)]);
+    assert!(fe_schema
+        .load_payments_by_payer_with_cipher(Address::repeat_byte(0x29), Some(&cipher))
+        .await?
+        .is_empty());
+
+    // The index depends on the key
+    let another_cipher = ColumnCipher::new([8u8; 32], false);
+    assert!(fe_schema
+        .load_payments_by_payer_with_cipher(payer, Some(&another_cipher))
+        .await?
+        .is_empty());
+
+    Ok(())
+}
+
+// Checks that the paid requests are queued in the order they were matched with the payments
+#[db_test]
+async fn queue_position(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let target = Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target,
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::days(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let ids: Vec<_> = store_requests(&mut storage, vec![request; 6])
+        .await
+        .into_iter()
+        .map(|request| request.id)
+        .collect();
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    // The requests are matched in the order other than the one they were created in
+    let matched = [
+        (ids[3], 1),
+        (ids[0], 2),
+        (ids[4], 3),
+        (ids[1], 4),
+        (ids[2], 4),
+    ];
+    for (id, seconds) in matched.iter() {
+        fe_schema
+            .set_match_scheme(
+                *id,
+                PaymentMatchScheme::AmountDigits,
+                now.add(Duration::seconds(*seconds)),
+            )
+            .await?;
+    }
+
+    assert_eq!(fe_schema.get_queue_position(ids[3]).await?, Some(0));
+    assert_eq!(fe_schema.get_queue_position(ids[0]).await?, Some(1));
+    assert_eq!(fe_schema.get_queue_position(ids[4]).await?, Some(2));
+    // The requests matched at the same time are ordered by the id
+    assert_eq!(fe_schema.get_queue_position(ids[1]).await?, Some(3));
+    assert_eq!(fe_schema.get_queue_position(ids[2]).await?, Some(4));
+    // The request is not queued until it is paid for
+    assert_eq!(fe_schema.get_queue_position(ids[5]).await?, None);
+
+    // The request matched once again after the failure keeps its place
+    fe_schema
+        .set_match_scheme(
+            ids[0],
+            PaymentMatchScheme::ExplicitId,
+            now.add(Duration::seconds(10)),
+        )
+        .await?;
+    assert_eq!(fe_schema.get_queue_position(ids[0]).await?, Some(1));
+
+    // Neither the fulfilled nor the escalated requests hold the queue
+    fe_schema.set_fulfilled_at(ids[3], now).await?;
+    fe_schema
+        .store_escalation(ForcedExitRequestEscalation {
+            request_id: ids[4],
+            full_exits: vec![],
+            created_at: now,
+            l1_tx_hash: None,
+            finalized_at: None,
+        })
+        .await?;
+    assert_eq!(fe_schema.get_queue_position(ids[3]).await?, None);
+    assert_eq!(fe_schema.get_queue_position(ids[4]).await?, None);
+    assert_eq!(fe_schema.get_queue_position(ids[0]).await?, Some(0));
+    assert_eq!(fe_schema.get_queue_position(ids[1]).await?, Some(1));
+    assert_eq!(fe_schema.get_queue_position(ids[2]).await?, Some(2));
+    assert_eq!(fe_schema.get_queue_position(-1).await?, None);
+
+    assert_eq!(
+        fe_schema
+            .count_fulfilled_since(now.sub(Duration::hours(1)))
+            .await?,
+        1
+    );
+    assert_eq!(
+        fe_schema
+            .count_fulfilled_since(now.add(Duration::seconds(1)))
+            .await?,
+        0
+    );
+
+    Ok(())
+}
+
+#[db_test]
+async fn awaiting_payment(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::minutes(5)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let ids: Vec<_> = store_requests(&mut storage, vec![request; 4])
+        .await
+        .into_iter()
+        .map(|request| request.id)
+        .collect();
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    assert_eq!(fe_schema.count_awaiting_payment(now).await?, 4);
+
+    // Neither the paid nor the fulfilled requests await the payment
+    fe_schema
+        .set_match_scheme(ids[0], PaymentMatchScheme::AmountDigits, now)
+        .await?;
+    fe_schema
+        .set_fulfilled_by(ids[1], Some(vec![TxHash::default()]), true)
+        .await?;
+    fe_schema.set_fulfilled_at(ids[1], now).await?;
+    assert_eq!(fe_schema.count_awaiting_payment(now).await?, 2);
+    let awaiting: Vec<_> = fe_schema
+        .load_awaiting_payment(now, 10)
+        .await?
+        .into_iter()
+        .map(|request| request.id)
+        .collect();
+    assert_eq!(awaiting, ids[2..].to_vec());
+    let awaiting = fe_schema.load_awaiting_payment(now, 1).await?;
+    assert_eq!(awaiting.len(), 1);
+    assert_eq!(awaiting[0].id, ids[2]);
+
+    // As well as the expired ones
+    assert_eq!(
+        fe_schema
+            .count_awaiting_payment(now.add(Duration::minutes(5)))
+            .await?,
+        0
+    );
+    assert!(fe_schema
+        .load_awaiting_payment(now.add(Duration::minutes(5)), 10)
+        .await?
+        .is_empty());
+
+    Ok(())
+}
+
+// Checks that the status transitions are recorded in the outbox and delivered with retries
+#[db_test]
+async fn outbox_deliveries(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let target = Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target,
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::hours(32)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let stored_requests = store_requests(&mut storage, vec![request.clone(), request]).await;
+    let id = stored_requests[0].id;
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    // Resetting the transactions is not a transition
+    fe_schema.set_fulfilled_by(id, None, true).await?;
+    assert!(fe_schema.load_request_deliveries(id).await?.is_empty());
+
+    fe_schema
+        .set_fulfilled_by(id, Some(vec![TxHash::default()]), true)
+        .await?;
+    fe_schema.set_fulfilled_at(id, now).await?;
+
+    let escalation = ForcedExitRequestEscalation {
+        request_id: stored_requests[1].id,
+        full_exits: vec![],
+        created_at: now,
+        l1_tx_hash: None,
+        finalized_at: None,
+    };
+    fe_schema.store_escalation(escalation.clone()).await?;
+    // The repeated escalation is ignored, so is its notification
+    fe_schema.store_escalation(escalation).await?;
+
+    let deliveries = fe_schema
+        .load_pending_deliveries(now.add(Duration::minutes(1)), 10, false)
+        .await?;
+    let events: Vec<_> = deliveries
+        .iter()
+        .map(|delivery| (delivery.request_id, delivery.event))
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            (id, ForcedExitRequestEvent::Submitted),
+            (id, ForcedExitRequestEvent::Fulfilled),
+            (stored_requests[1].id, ForcedExitRequestEvent::Escalated),
+        ]
+    );
+    assert!(deliveries
+        .iter()
+        .all(|delivery| delivery.attempts == 0 && !delivery.is_delivered()));
+    assert!(deliveries
+        .iter()
+        .all(|delivery| delivery.pipeline_version == Some(FORCED_EXIT_PIPELINE_VERSION)));
+    // The notifications of each request are numbered in the order of the transitions,
+    // even though the transitions of the first request were made at the same time
+    let sequences: Vec<_> = deliveries
+        .iter()
+        .map(|delivery| (delivery.request_id, delivery.sequence))
+        .collect();
+    assert_eq!(
+        sequences,
+        vec![(id, 1), (id, 2), (stored_requests[1].id, 1)]
+    );
+    assert_eq!(
+        fe_schema
+            .load_pending_deliveries(now.add(Duration::minutes(1)), 1, false)
+            .await?
+            .len(),
+        1
+    );
+
+    // The failed delivery is postponed until the next attempt is due
+    let retry_at = now.add(Duration::minutes(5));
+    fe_schema
+        .record_delivery_failure(deliveries[0].id, "Connection refused", retry_at)
+        .await?;
+    fe_schema.mark_delivered(deliveries[1].id, now).await?;
+
+    let pending = fe_schema
+        .load_pending_deliveries(now.add(Duration::minutes(1)), 10, false)
+        .await?;
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].event, ForcedExitRequestEvent::Escalated);
+
+    let pending = fe_schema
+        .load_pending_deliveries(retry_at, 10, false)
+        .await?;
+    assert_eq!(pending.len(), 2);
+    assert_eq!(pending[0].id, deliveries[0].id);
+    assert_eq!(pending[0].attempts, 1);
+    assert_eq!(pending[0].last_error.as_deref(), Some("Connection refused"));
+    // The key does not change between the attempts
+    assert_eq!(
+        pending[0].idempotency_key(),
+        deliveries[0].idempotency_key()
+    );
+
+    let request_deliveries = fe_schema.load_request_deliveries(id).await?;
+    assert_eq!(request_deliveries[1].delivered_at, Some(now));
+    assert_eq!(request_deliveries[1].attempts, 1);
+
+    // The next transition continues the numbering of the request
+    fe_schema
+        .set_fulfilled_at(stored_requests[1].id, now)
+        .await?;
+    let sequences: Vec<_> = fe_schema
+        .load_request_deliveries(stored_requests[1].id)
+        .await?
+        .iter()
+        .map(|delivery| (delivery.sequence, delivery.event))
+        .collect();
+    assert_eq!(
+        sequences,
+        vec![
+            (1, ForcedExitRequestEvent::Escalated),
+            (2, ForcedExitRequestEvent::Fulfilled)
+        ]
+    );
+
+    Ok(())
+}
+
+// Checks that the callbacks of the fulfilled requests are delivered apart from the webhook
+// notifications and are given up on until redelivered
+#[db_test]
+async fn outbox_callbacks(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target: Address::repeat_byte(0x37),
+        tokens: vec![TokenId(1), TokenId(2)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::hours(32)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: Some("https://exchange.example/forced_exits?token=42".to_owned()),
+    };
+    let without_callback = SaveForcedExitRequestQuery {
+        callback_url: None,
+        ..request.clone()
+    };
+    let stored_requests = store_requests(&mut storage, vec![request, without_callback]).await;
+    let id = stored_requests[0].id;
+    let tx_hash = TxHash::from_slice(&[0x11; 32]).unwrap();
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    for stored in &stored_requests {
+        fe_schema
+            .set_fulfilled_by(stored.id, Some(vec![tx_hash]), true)
+            .await?;
+        fe_schema.set_fulfilled_at(stored.id, now).await?;
+    }
+
+    // The callback follows the notification of the same transition
+    let callbacks = fe_schema
+        .load_pending_deliveries(now.add(Duration::minutes(1)), 10, true)
+        .await?;
+    assert_eq!(callbacks.len(), 1);
+    let callback = &callbacks[0];
+    assert_eq!(callback.request_id, id);
+    assert_eq!(callback.sequence, 3);
+    assert_eq!(
+        callback.callback_url.as_deref(),
+        Some("https://exchange.example/forced_exits?token=42")
+    );
+    assert_eq!(
+        callback.callback,
+        Some(ForcedExitFulfilledCallback {
+            request_id: stored_requests[0].public_id,
+            target: Address::repeat_byte(0x37),
+            tokens: vec![TokenId(1), TokenId(2)],
+            tx_hashes: vec![tx_hash],
+            fulfilled_at: now,
+        })
+    );
+    let notifications = fe_schema
+        .load_pending_deliveries(now.add(Duration::minutes(1)), 10, false)
+        .await?;
+    assert_eq!(notifications.len(), 4);
+    assert!(notifications.iter().all(|delivery| !delivery.is_callback()));
+
+    // The callback which has run out of the attempts is not loaded anymore
+    fe_schema
+        .mark_delivery_failed(callback.id, "Connection timed out", now)
+        .await?;
+    assert!(fe_schema
+        .load_pending_deliveries(now.add(Duration::days(1)), 10, true)
+        .await?
+        .is_empty());
+    let failed = fe_schema.load_failed_deliveries(10).await?;
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].id, callback.id);
+    assert_eq!(failed[0].attempts, 1);
+    assert_eq!(failed[0].failed_at, Some(now));
+    assert_eq!(
+        failed[0].last_error.as_deref(),
+        Some("Connection timed out")
+    );
+
+    // Only the failed callbacks are redelivered
+    assert!(fe_schema
+        .redeliver(notifications[0].id, now)
+        .await?
+        .is_none());
+    let redelivered = fe_schema.redeliver(callback.id, now).await?.unwrap();
+    assert!(!redelivered.is_failed());
+    assert_eq!(redelivered.attempts, 0);
+    assert!(fe_schema.redeliver(callback.id, now).await?.is_none());
+    assert!(fe_schema.load_failed_deliveries(10).await?.is_empty());
+    assert_eq!(
+        fe_schema
+            .load_pending_deliveries(now.add(Duration::minutes(1)), 10, true)
+            .await?,
+        vec![redelivered]
+    );
+
+    Ok(())
+}
+
+// Stores the request the way the servers preceding the `pay_exactly` column did
+async fn store_legacy_request(
+    storage: &mut StorageProcessor<'_>,
+    price_in_wei: &str,
+    created_at: chrono::DateTime<Utc>,
+) -> QueryResult<i64> {
+    let (id,): (i64,) = sqlx::query_as(
+        "
+            INSERT INTO forced_exit_requests ( target, tokens, price_in_wei, created_at, valid_until )
+            VALUES ( $1, $2, $3::NUMERIC, $4, $5 )
+            RETURNING id
+        ",
+    )
+    .bind(address_to_stored_string(&Address::repeat_byte(0x12)))
+    .bind("1")
+    .bind(price_in_wei)
+    .bind(created_at)
+    .bind(created_at.add(Duration::hours(1)))
+    .fetch_one(storage.conn())
+    .await?;
+    Ok(id)
+}
+
+#[db_test]
+async fn legacy_requests(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let sent_before_upgrade = store_legacy_request(&mut storage, "10000000000", now).await?;
+    let paid_after_upgrade = store_legacy_request(&mut storage, "20000000000", now).await?;
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    assert!(fe_schema.count_legacy_requests().await? >= 2);
+
+    // The legacy request is read with the amount it would have been stored with
+    let legacy = fe_schema
+        .get_request_by_id(sent_before_upgrade)
+        .await?
+        .expect("The legacy request is not read");
+    assert_eq!(
+        legacy.pay_exactly,
+        (BigUint::from(10_000_000_000u64) + sent_before_upgrade as u64).to_string()
+    );
+    assert_eq!(legacy.tokens, vec![TokenId(1)]);
+    // The pending payments are made for the id the request was created with
+    assert_eq!(legacy.public_id, sent_before_upgrade);
+    assert_eq!(
+        fe_schema
+            .get_request_by_public_id(sent_before_upgrade)
+            .await?
+            .map(|request| request.id),
+        Some(sent_before_upgrade)
+    );
+
+    // It is matched and sent before the upgrade, the rest happens after it
+    fe_schema
+        .set_match_scheme(sent_before_upgrade, PaymentMatchScheme::AmountDigits, now)
+        .await?;
+    fe_schema
+        .set_fulfilled_by(sent_before_upgrade, Some(vec![TxHash::default()]), true)
+        .await?;
+
+    // The upgrade is done in batches until there is nothing left
+    let mut upgraded = 0;
+    loop {
+        let batch = fe_schema.upgrade_legacy_requests(1).await?;
+        if batch == 0 {
+            break;
+        }
+        assert_eq!(batch, 1);
+        upgraded += batch;
+    }
+    assert!(upgraded >= 2);
+    assert_eq!(fe_schema.count_legacy_requests().await?, 0);
+
+    let upgraded = fe_schema
+        .get_request_by_id(sent_before_upgrade)
+        .await?
+        .unwrap();
+    assert_eq!(upgraded.pay_exactly, legacy.pay_exactly);
+    assert_eq!(upgraded.public_id, sent_before_upgrade);
+    assert_eq!(upgraded.fulfilled_by, Some(vec![TxHash::default()]));
+    fe_schema.set_fulfilled_at(sent_before_upgrade, now).await?;
+
+    let upgraded = fe_schema
+        .get_request_by_id(paid_after_upgrade)
+        .await?
+        .unwrap();
+    assert_eq!(
+        upgraded.pay_exactly,
+        (BigUint::from(20_000_000_000u64) + paid_after_upgrade as u64).to_string()
+    );
+    fe_schema
+        .set_match_scheme(paid_after_upgrade, PaymentMatchScheme::AmountDigits, now)
+        .await?;
+    fe_schema
+        .set_fulfilled_by(paid_after_upgrade, Some(vec![TxHash::default()]), true)
+        .await?;
+    fe_schema.set_fulfilled_at(paid_after_upgrade, now).await?;
+
+    // Both requests went through the same transitions
+    for id in [sent_before_upgrade, paid_after_upgrade] {
+        let request = fe_schema.get_request_by_id(id).await?.unwrap();
+        assert_eq!(request.match_scheme, Some(PaymentMatchScheme::AmountDigits));
+        assert_eq!(request.fulfilled_at, Some(now));
+
+        let events: Vec<_> = fe_schema
+            .load_request_deliveries(id)
+            .await?
+            .iter()
+            .map(|delivery| delivery.event)
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                ForcedExitRequestEvent::Submitted,
+                ForcedExitRequestEvent::Fulfilled
+            ]
+        );
+    }
+
+    Ok(())
+}
+
+// Checks that the requests are referred to by the public ids allocated from the counter
+#[db_test]
+async fn public_ids(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+
+    let first = fe_schema.reserve_public_ids(3).await?;
+    let second = fe_schema.reserve_public_ids(1).await?;
+    assert_eq!(first.end - first.start, 3);
+    assert_eq!(second.start, first.end);
+    assert_eq!(second.end - second.start, 1);
+
+    let request = fe_schema
+        .store_request(SaveForcedExitRequestQuery {
+            target: Address::repeat_byte(0x12),
+            tokens: vec![TokenId(1)],
+            price_in_wei: BigUint::from(10_000_000_000u64),
+            created_at: now,
+            valid_until: now.add(Duration::hours(1)),
+            metadata: None,
+            payment_terms: None,
+            callback_url: None,
+        })
+        .await?;
+    assert_eq!(request.public_id, second.end);
+    // The amount is paid for the public id rather than for the key of the row
+    assert_eq!(
+        request.pay_exactly,
+        pay_exactly(&request.price_in_wei, request.public_id)
+    );
+    let loaded = fe_schema
+        .get_request_by_public_id(request.public_id)
+        .await?
+        .expect("The request is not found by the public id");
+    assert_eq!(loaded.id, request.id);
+    assert_eq!(
+        fe_schema.get_request_by_id(request.id).await?.unwrap(),
+        loaded
+    );
+    assert_eq!(fe_schema.get_request_by_public_id(first.start).await?, None);
+
+    Ok(())
+}
+
+// Checks that the public ids allocated by the concurrent sessions never overlap
+#[db_test]
+async fn concurrent_public_ids(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    const SESSIONS: usize = 8;
+    const ALLOCATIONS: u32 = 20;
+
+    // Each of the servers allocates the ids with the session of its own
+    let allocations = (0..SESSIONS).map(|session| async move {
+        let mut storage = StorageProcessor::establish_connection().await?;
+        let mut ids = Vec::new();
+        for allocation in 0..ALLOCATIONS {
+            let count = 1 + (session as u32 + allocation) % 3;
+            let block = storage
+                .forced_exit_requests_schema()
+                .reserve_public_ids(count)
+                .await?;
+            assert_eq!(block.end - block.start, i64::from(count));
+            ids.extend(block);
+        }
+        QueryResult::Ok(ids)
+    });
+    let mut ids: Vec<_> = futures_util::future::try_join_all(allocations)
+        .await?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let allocated = ids.len();
+    ids.sort_unstable();
+    ids.dedup();
+    assert_eq!(ids.len(), allocated);
+
+    // The counter continues after all of them
+    let next = ForcedExitRequestsSchema(&mut storage)
+        .reserve_public_ids(1)
+        .await?;
+    assert!(ids.iter().all(|id| *id < next.start));
+
+    Ok(())
+}
+
+// The timestamps are compared by the database as well as in Rust, so none of them
+// may depend on the time zone of the database server. Every forced exit table is checked,
+// not only the ones of the requests
+#[db_test]
+async fn no_naive_timestamps(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let columns: Vec<(String, String, String)> = sqlx::query_as(
+        "
+            SELECT table_name::TEXT, column_name::TEXT, data_type::TEXT
+            FROM information_schema.columns
+            WHERE table_name LIKE 'forced_exit%' AND data_type LIKE 'timestamp%'
+        ",
+    )
+    .fetch_all(storage.conn())
+    .await?;
+
+    for table in [
+        "forced_exit_requests",
+        "forced_exit_fulfillments",
+        "forced_exit_refunds",
+        "forced_exit_config_history",
+    ] {
+        assert!(
+            columns.iter().any(|(name, _, _)| name == table),
+            "{} is not checked",
+            table
+        );
+    }
+    for (table, column, data_type) in columns {
+        assert_eq!(
+            data_type, "timestamp with time zone",
+            "{}.{} is stored without the time zone",
+            table, column
+        );
+    }
+
+    Ok(())
+}
+
+// Checks that the expiry does not shift if the database server runs in another time zone
+#[db_test]
+async fn non_utc_session_time_zone(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    // The offset is not a whole number of hours, so that any shift is noticed
+    sqlx::query("SET TIME ZONE 'Asia/Kathmandu'")
+        .execute(storage.conn())
+        .await?;
+
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::minutes(5)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let expired_request = SaveForcedExitRequestQuery {
+        created_at: now.sub(Duration::hours(1)),
+        valid_until: now.sub(Duration::minutes(1)),
+        ..request.clone()
+    };
+    let stored = store_requests(&mut storage, vec![request.clone(), expired_request]).await;
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    let loaded = fe_schema.get_request_by_id(stored[0].id).await?.unwrap();
+    assert_eq!(loaded.created_at, request.created_at);
+    assert_eq!(loaded.valid_until, request.valid_until);
+
+    // The expiry is the same moment for the database and for the component
+    assert_eq!(fe_schema.count_awaiting_payment(now).await?, 1);
+    assert_eq!(
+        fe_schema
+            .count_awaiting_payment(now.add(Duration::minutes(5)))
+            .await?,
+        0
+    );
+    fe_schema
+        .delete_old_unfulfilled_requests(Duration::zero())
+        .await?;
+    assert!(fe_schema.get_request_by_id(stored[0].id).await?.is_some());
+    assert!(fe_schema.get_request_by_id(stored[1].id).await?.is_none());
+
+    let matched_at = now.add(Duration::seconds(12));
+    fe_schema
+        .set_match_scheme(stored[0].id, PaymentMatchScheme::AmountDigits, matched_at)
+        .await?;
+    fe_schema.set_fulfilled_at(stored[0].id, matched_at).await?;
+    let loaded = fe_schema.get_request_by_id(stored[0].id).await?.unwrap();
+    assert_eq!(loaded.matched_at, Some(matched_at));
+    assert_eq!(loaded.fulfilled_at, Some(matched_at));
+
+    Ok(())
+}
+
+#[db_test]
+async fn singleton_lock(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    // Each of the instances takes the lock with the session of its own
+    let mut first = StorageProcessor::establish_connection().await?;
+    let mut second = StorageProcessor::establish_connection().await?;
+
+    assert!(
+        first
+            .forced_exit_requests_schema()
+            .try_acquire_singleton_lock("first:1")
+            .await?
+    );
+    assert!(
+        !second
+            .forced_exit_requests_schema()
+            .try_acquire_singleton_lock("second:2")
+            .await?
+    );
+    assert!(
+        first
+            .forced_exit_requests_schema()
+            .singleton_lock_held()
+            .await?
+    );
+    assert!(
+        !second
+            .forced_exit_requests_schema()
+            .singleton_lock_held()
+            .await?
+    );
+
+    let holder = ForcedExitRequestsSchema(&mut storage)
+        .load_singleton_holder()
+        .await?
+        .expect("The holder of the lock is not loaded");
+    assert_eq!(holder.instance, "first:1");
+
+    // The released lock is taken by the next instance
+    assert!(
+        first
+            .forced_exit_requests_schema()
+            .release_singleton_lock()
+            .await?
+    );
+    assert!(
+        !first
+            .forced_exit_requests_schema()
+            .singleton_lock_held()
+            .await?
+    );
+    assert!(
+        second
+            .forced_exit_requests_schema()
+            .try_acquire_singleton_lock("second:2")
+            .await?
+    );
+    let holder = ForcedExitRequestsSchema(&mut storage)
+        .load_singleton_holder()
+        .await?
+        .unwrap();
+    assert_eq!(holder.instance, "second:2");
+
+    // The lock is released with the session of its holder, the session
+    // is closed by the database shortly after the connection is dropped
+    drop(second);
+    let mut acquired = false;
+    for _ in 0..50 {
+        acquired = first
+            .forced_exit_requests_schema()
+            .try_acquire_singleton_lock("first:1")
+            .await?;
+        if acquired {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert!(acquired);
+    assert!(
+        first
+            .forced_exit_requests_schema()
+            .singleton_lock_held()
+            .await?
+    );
+
+    Ok(())
+}
+
+// Checks that the backlog consists of the paid requests not sent yet, in the order they are processed in
+#[db_test]
+async fn backlog(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::days(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let ids: Vec<_> = store_requests(&mut storage, vec![request; 5])
+        .await
+        .into_iter()
+        .map(|request| request.id)
+        .collect();
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    // The last request is not paid for
+    for (id, seconds) in [(ids[2], 1), (ids[0], 2), (ids[1], 3), (ids[3], 4)] {
+        fe_schema
+            .set_match_scheme(
+                id,
+                PaymentMatchScheme::AmountDigits,
+                now.add(Duration::seconds(seconds)),
+            )
+            .await?;
+    }
+    assert_eq!(
+        fe_schema.get_backlog_request_ids().await?,
+        vec![ids[2], ids[0], ids[1], ids[3]]
+    );
+
+    // Neither the sent nor the escalated requests are in the backlog
+    fe_schema
+        .set_fulfilled_by(ids[0], Some(vec![TxHash::default()]), true)
+        .await?;
+    fe_schema
+        .store_escalation(ForcedExitRequestEscalation {
+            request_id: ids[1],
+            full_exits: vec![],
+            created_at: now,
+            l1_tx_hash: None,
+            finalized_at: None,
+        })
+        .await?;
+    assert_eq!(
+        fe_schema.get_backlog_request_ids().await?,
+        vec![ids[2], ids[3]]
+    );
+
+    // The reports are loaded as they were stored
+    let mut report = ForcedExitBacklogReport::new(now, 30);
+    report.add_failure();
+    report.eta_secs = Some(120);
+    fe_schema.store_backlog_report(&report).await?;
+    assert!(fe_schema.load_backlog_reports(10).await?.contains(&report));
+
+    Ok(())
+}
+
+// Writes the transactions the way the servers preceding the fulfillments did
+async fn set_legacy_fulfilled_by(
+    storage: &mut StorageProcessor<'_>,
+    id: i64,
+    hashes: &[TxHash],
+) -> QueryResult<()> {
+    let hashes: Vec<_> = hashes.iter().map(|hash| hash.to_string()).collect();
+    sqlx::query("UPDATE forced_exit_requests SET fulfilled_by = $1 WHERE id = $2")
+        .bind(hashes.join(","))
+        .bind(id)
+        .execute(storage.conn())
+        .await?;
+    Ok(())
+}
+
+async fn load_legacy_fulfilled_by(
+    storage: &mut StorageProcessor<'_>,
+    id: i64,
+) -> QueryResult<Option<String>> {
+    let (fulfilled_by,): (Option<String>,) =
+        sqlx::query_as("SELECT fulfilled_by FROM forced_exit_requests WHERE id = $1")
+            .bind(id)
+            .fetch_one(storage.conn())
+            .await?;
+    Ok(fulfilled_by)
+}
+
+// Checks every stage of moving the transactions from the `fulfilled_by` column to the fulfillments
+#[db_test]
+async fn fulfillments(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1), TokenId(2)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::days(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let ids: Vec<_> = store_requests(&mut storage, vec![request; 3])
+        .await
+        .into_iter()
+        .map(|request| request.id)
+        .collect();
+    let hash = |byte: u8| TxHash::from_slice(&[byte; 32]).unwrap();
+    for id in &ids {
+        ForcedExitRequestsSchema(&mut storage)
+            .set_match_scheme(*id, PaymentMatchScheme::AmountDigits, now)
+            .await?;
+    }
+    let mismatched = |mismatches: Vec<ForcedExitFulfillmentMismatch>| -> Vec<i64> {
+        mismatches
+            .into_iter()
+            .map(|mismatch| mismatch.request_id)
+            .filter(|id| ids.contains(id))
+            .collect()
+    };
+
+    // Dual write: the column and the fulfillments have the same transactions
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    fe_schema
+        .set_fulfilled_by(ids[0], Some(vec![hash(1), hash(2)]), true)
+        .await?;
+    let fulfillments = fe_schema.load_fulfillments(ids[0]).await?;
+    assert_eq!(
+        fulfillments
+            .iter()
+            .map(|fulfillment| (fulfillment.position, fulfillment.token, fulfillment.tx_hash))
+            .collect::<Vec<_>>(),
+        vec![(0, TokenId(1), hash(1)), (1, TokenId(2), hash(2))]
+    );
+    assert!(fulfillments
+        .iter()
+        .all(|fulfillment| fulfillment.target_account_id.is_none()));
+    // The target id is recorded for all the transactions of the request
+    fe_schema
+        .set_target_account_id(ids[0], AccountId(34))
+        .await?;
+    assert!(fe_schema
+        .load_fulfillments(ids[0])
+        .await?
+        .iter()
+        .all(|fulfillment| fulfillment.target_account_id == Some(AccountId(34))));
+    assert_eq!(
+        load_legacy_fulfilled_by(fe_schema.0, ids[0]).await?,
+        Some(format!("{},{}", hash(1).to_string(), hash(2).to_string()))
+    );
+
+    // The request sent by the previous server is only known from the column,
+    // it is still treated as sent until backfilled
+    set_legacy_fulfilled_by(fe_schema.0, ids[1], &[hash(3), hash(4)]).await?;
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    assert!(fe_schema.load_fulfillments(ids[1]).await?.is_empty());
+    assert_eq!(
+        fe_schema
+            .get_request_by_id(ids[1])
+            .await?
+            .unwrap()
+            .fulfilled_by,
+        Some(vec![hash(3), hash(4)])
+    );
+    assert!(!fe_schema.get_backlog_request_ids().await?.contains(&ids[1]));
+    assert_eq!(
+        mismatched(fe_schema.load_fulfillment_mismatches(1000).await?),
+        vec![ids[1]]
+    );
+
+    // The backfill makes both representations consistent
+    assert!(fe_schema.count_unbackfilled_fulfillments().await? >= 1);
+    while fe_schema.backfill_fulfillments(1).await? > 0 {}
+    assert_eq!(fe_schema.count_unbackfilled_fulfillments().await?, 0);
+    let backfilled = fe_schema.load_fulfillments(ids[1]).await?;
+    assert_eq!(
+        backfilled
+            .iter()
+            .map(|fulfillment| (
+                fulfillment.token,
+                fulfillment.tx_hash,
+                fulfillment.created_at
+            ))
+            .collect::<Vec<_>>(),
+        vec![(TokenId(1), hash(3), now), (TokenId(2), hash(4), now)]
+    );
+    assert!(mismatched(fe_schema.load_fulfillment_mismatches(1000).await?).is_empty());
+
+    // With the column deprecated, the requests are read from the fulfillments only
+    fe_schema
+        .set_fulfilled_by(ids[2], Some(vec![hash(5), hash(6)]), false)
+        .await?;
+    assert_eq!(load_legacy_fulfilled_by(fe_schema.0, ids[2]).await?, None);
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    assert_eq!(
+        fe_schema
+            .get_request_by_id(ids[2])
+            .await?
+            .unwrap()
+            .fulfilled_by,
+        Some(vec![hash(5), hash(6)])
+    );
+    assert!(!fe_schema.get_backlog_request_ids().await?.contains(&ids[2]));
+    let mut unconfirmed: Vec<_> = fe_schema
+        .get_unconfirmed_requests()
+        .await?
+        .into_iter()
+        .filter(|request| ids.contains(&request.id))
+        .map(|request| (request.id, request.fulfilled_by))
+        .collect();
+    unconfirmed.sort_by_key(|(id, _)| *id);
+    assert_eq!(
+        unconfirmed,
+        vec![
+            (ids[0], Some(vec![hash(1), hash(2)])),
+            (ids[1], Some(vec![hash(3), hash(4)])),
+            (ids[2], Some(vec![hash(5), hash(6)])),
+        ]
+    );
+
+    // The failed transactions are reset in both places
+    fe_schema.set_fulfilled_by(ids[0], None, true).await?;
+    assert!(fe_schema.load_fulfillments(ids[0]).await?.is_empty());
+    assert_eq!(
+        fe_schema
+            .get_request_by_id(ids[0])
+            .await?
+            .unwrap()
+            .fulfilled_by,
+        None
+    );
+    assert_eq!(fe_schema.get_backlog_request_ids().await?, vec![ids[0]]);
+
+    Ok(())
+}
+
+// Checks that the transactions are recorded before the submission and marked once submitted
+#[db_test]
+async fn submissions(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1), TokenId(2)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::days(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let ids: Vec<_> = store_requests(&mut storage, vec![request; 2])
+        .await
+        .into_iter()
+        .map(|request| request.id)
+        .collect();
+    let hash = |byte: u8| TxHash::from_slice(&[byte; 32]).unwrap();
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    for id in &ids {
+        fe_schema
+            .set_match_scheme(*id, PaymentMatchScheme::AmountDigits, now)
+            .await?;
+    }
+    let unsubmitted = |fulfillments: Vec<ForcedExitFulfillment>| -> Vec<(i64, TxHash)> {
+        fulfillments
+            .into_iter()
+            .filter(|fulfillment| ids.contains(&fulfillment.request_id))
+            .map(|fulfillment| (fulfillment.request_id, fulfillment.tx_hash))
+            .collect()
+    };
+
+    // The recorded transactions reserve the request, though it is not sent yet
+    fe_schema
+        .record_submission(ids[0], &[hash(1), hash(2)])
+        .await?;
+    fe_schema
+        .record_submission(ids[1], &[hash(3), hash(4)])
+        .await?;
+    assert_eq!(
+        unsubmitted(fe_schema.load_unsubmitted_fulfillments().await?),
+        vec![
+            (ids[0], hash(1)),
+            (ids[0], hash(2)),
+            (ids[1], hash(3)),
+            (ids[1], hash(4)),
+        ]
+    );
+    let recorded = fe_schema.get_request_by_id(ids[0]).await?.unwrap();
+    assert_eq!(recorded.fulfilled_by, Some(vec![hash(1), hash(2)]));
+    assert_eq!(recorded.status, ForcedExitLifecycleStatus::PaymentReceived);
+    assert_eq!(load_legacy_fulfilled_by(fe_schema.0, ids[0]).await?, None);
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    assert!(!fe_schema.get_backlog_request_ids().await?.contains(&ids[0]));
+    assert!(!mismatched_ids(fe_schema.load_fulfillment_mismatches(1000).await?).contains(&ids[0]));
+
+    // The transactions accepted by the mempool are marked as submitted
+    fe_schema
+        .set_fulfilled_by(ids[0], Some(vec![hash(1), hash(2)]), true)
+        .await?;
+    assert!(fe_schema
+        .load_fulfillments(ids[0])
+        .await?
+        .iter()
+        .all(|fulfillment| fulfillment.submitted_at.is_some()));
+    assert_eq!(
+        fe_schema.get_request_by_id(ids[0]).await?.unwrap().status,
+        ForcedExitLifecycleStatus::TxsSent
+    );
+
+    // The refused ones are removed, the submitted ones are not
+    fe_schema.abort_submission(ids[1]).await?;
+    fe_schema.abort_submission(ids[0]).await?;
+    assert!(unsubmitted(fe_schema.load_unsubmitted_fulfillments().await?).is_empty());
+    assert_eq!(fe_schema.load_fulfillments(ids[0]).await?.len(), 2);
+    assert_eq!(
+        fe_schema
+            .get_request_by_id(ids[1])
+            .await?
+            .unwrap()
+            .fulfilled_by,
+        None
+    );
+    assert!(fe_schema.get_backlog_request_ids().await?.contains(&ids[1]));
+
+    Ok(())
+}
+
+// Checks that the chunks of the request are recorded following the submitted ones
+#[db_test]
+async fn chunk_submissions(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1), TokenId(2), TokenId(3)],
+        price_in_wei: BigUint::from_i32(312).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::days(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let id = store_requests(&mut storage, vec![request]).await[0].id;
+    let hash = |byte: u8| TxHash::from_slice(&[byte; 32]).unwrap();
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    fe_schema
+        .set_match_scheme(id, PaymentMatchScheme::AmountDigits, now)
+        .await?;
+    let tokens = |fulfillments: Vec<ForcedExitFulfillment>| -> Vec<(TokenId, TxHash, bool)> {
+        fulfillments
+            .into_iter()
+            .map(|fulfillment| {
+                (
+                    fulfillment.token,
+                    fulfillment.tx_hash,
+                    fulfillment.submitted_at.is_some(),
+                )
+            })
+            .collect()
+    };
+
+    fe_schema
+        .record_chunk_submission(id, 0, &[hash(1), hash(2)])
+        .await?;
+    fe_schema
+        .set_fulfilled_by(id, Some(vec![hash(1), hash(2)]), true)
+        .await?;
+
+    // The hashes beyond the tokens of the request are not recorded
+    fe_schema
+        .record_chunk_submission(id, 2, &[hash(3), hash(4)])
+        .await?;
+    assert_eq!(
+        tokens(fe_schema.load_fulfillments(id).await?),
+        vec![
+            (TokenId(1), hash(1), true),
+            (TokenId(2), hash(2), true),
+            (TokenId(3), hash(3), false),
+        ]
+    );
+
+    // Only the refused chunk is removed
+    fe_schema.abort_submission(id).await?;
+    assert_eq!(
+        tokens(fe_schema.load_fulfillments(id).await?),
+        vec![(TokenId(1), hash(1), true), (TokenId(2), hash(2), true)]
+    );
+
+    Ok(())
+}
+
+fn mismatched_ids(mismatches: Vec<ForcedExitFulfillmentMismatch>) -> Vec<i64> {
+    mismatches
+        .into_iter()
+        .map(|mismatch| mismatch.request_id)
+        .collect()
+}
+
+// Checks that the fulfillment of the same payment of the request can only be started once
+#[db_test]
+async fn fulfillment_keys(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::days(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let ids: Vec<_> = store_requests(&mut storage, vec![request; 2])
+        .await
+        .into_iter()
+        .map(|request| request.id)
+        .collect();
+    let payment = |byte: u8| Some(H256::repeat_byte(byte));
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    assert!(fe_schema.begin_fulfillment(ids[0], payment(1), now).await?);
+    // The replayed payment is refused
+    assert!(!fe_schema.begin_fulfillment(ids[0], payment(1), now).await?);
+    // The other payments and requests are independent
+    assert!(fe_schema.begin_fulfillment(ids[0], payment(2), now).await?);
+    assert!(fe_schema.begin_fulfillment(ids[1], payment(1), now).await?);
+    // The fulfillments not caused by a known payment share a single key
+    assert!(fe_schema.begin_fulfillment(ids[1], None, now).await?);
+    assert!(!fe_schema.begin_fulfillment(ids[1], None, now).await?);
+
+    // Resetting the failed transactions allows to fulfill the request again
+    fe_schema.set_fulfilled_by(ids[0], None, true).await?;
+    assert!(fe_schema.begin_fulfillment(ids[0], payment(1), now).await?);
+    assert!(!fe_schema.begin_fulfillment(ids[1], payment(1), now).await?);
+
+    Ok(())
+}
+
+// Checks that the skipped tokens are left out of the request, keeping the order of the rest
+#[db_test]
+async fn skipped_tokens(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1), TokenId(2), TokenId(3)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::days(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let ids: Vec<_> = store_requests(&mut storage, vec![request; 2])
+        .await
+        .into_iter()
+        .map(|request| request.id)
+        .collect();
+    let restricted = |token| SkippedForcedExit {
+        token,
+        reason: ForcedExitTokenSkipReason::L1TransferRestricted,
+    };
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    assert!(fe_schema.load_skipped_tokens(ids[0]).await?.is_empty());
+    fe_schema
+        .skip_tokens(
+            ids[0],
+            &[restricted(TokenId(3)), restricted(TokenId(1))],
+            now,
+        )
+        .await?;
+    // Skipping the token once again changes nothing
+    fe_schema
+        .skip_tokens(ids[0], &[restricted(TokenId(3))], now)
+        .await?;
+    assert_eq!(
+        fe_schema.load_skipped_tokens(ids[0]).await?,
+        vec![restricted(TokenId(1)), restricted(TokenId(3))]
+    );
+    let request = fe_schema.get_request_by_id(ids[0]).await?.unwrap();
+    assert_eq!(request.tokens, vec![TokenId(2)]);
+    let request = fe_schema.get_request_by_id(ids[1]).await?.unwrap();
+    assert_eq!(request.tokens, vec![TokenId(1), TokenId(2), TokenId(3)]);
+    assert!(fe_schema.load_skipped_tokens(ids[1]).await?.is_empty());
+
+    Ok(())
+}
+
+// Checks that the cancellations are recorded with their kinds, and only the system
+// cancellations reset the sent transactions
+#[db_test]
+async fn cancellations(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::days(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let ids: Vec<_> = store_requests(&mut storage, vec![request; 2])
+        .await
+        .into_iter()
+        .map(|request| request.id)
+        .collect();
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    let cancelled = fe_schema
+        .cancel_request(ids[0], ForcedExitCancellationKind::UserCancelled, now, true)
+        .await?
+        .unwrap();
+    assert_eq!(cancelled.valid_until, now);
+    assert_eq!(
+        cancelled.cancellation,
+        Some(ForcedExitCancellationKind::UserCancelled)
+    );
+    // Extending the request allows processing it again
+    let extended = fe_schema
+        .set_valid_until(ids[0], now.add(Duration::days(1)))
+        .await?
+        .unwrap();
+    assert_eq!(extended.cancellation, None);
+
+    // The request being fulfilled is only cancelled by the system
+    fe_schema
+        .set_fulfilled_by(ids[1], Some(vec![TxHash::default()]), true)
+        .await?;
+    assert!(fe_schema
+        .cancel_request(
+            ids[1],
+            ForcedExitCancellationKind::OperatorCancelled,
+            now,
+            true
+        )
+        .await?
+        .is_none());
+    let retried = fe_schema
+        .cancel_request(ids[1], ForcedExitCancellationKind::SystemRetry, now, true)
+        .await?
+        .unwrap();
+    assert_eq!(retried.fulfilled_by, None);
+    assert!(retried.valid_until > now);
+    assert!(fe_schema.load_fulfillments(ids[1]).await?.is_empty());
+
+    let kinds = |cancellations: Vec<ForcedExitCancellation>| {
+        cancellations
+            .into_iter()
+            .map(|cancellation| cancellation.kind)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        kinds(fe_schema.load_cancellations(ids[0]).await?),
+        vec![ForcedExitCancellationKind::UserCancelled]
+    );
+    assert_eq!(
+        kinds(fe_schema.load_cancellations(ids[1]).await?),
+        vec![ForcedExitCancellationKind::SystemRetry]
+    );
+
+    Ok(())
+}
+
+// Checks that the status of the request follows its transitions, and only the requests
+// with the transactions sent are awaited
+#[db_test]
+async fn lifecycle_status(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::days(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let expired = SaveForcedExitRequestQuery {
+        created_at: now.sub(Duration::days(2)),
+        valid_until: now.sub(Duration::days(1)),
+        ..request.clone()
+    };
+    let stored = store_requests(&mut storage, vec![request.clone(), request, expired]).await;
+    assert!(stored
+        .iter()
+        .all(|request| request.status == ForcedExitLifecycleStatus::Created));
+    let ids: Vec<_> = stored.into_iter().map(|request| request.id).collect();
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    let status = |request: Option<ForcedExitRequest>| request.unwrap().status;
+    let unconfirmed = |requests: Vec<ForcedExitRequest>| {
+        requests
+            .into_iter()
+            .map(|request| request.id)
+            .collect::<Vec<_>>()
+    };
+
+    fe_schema
+        .set_match_scheme(ids[0], PaymentMatchScheme::AmountDigits, now)
+        .await?;
+    assert_eq!(
+        status(fe_schema.get_request_by_id(ids[0]).await?),
+        ForcedExitLifecycleStatus::PaymentReceived
+    );
+    fe_schema
+        .set_fulfilled_by(ids[0], Some(vec![TxHash::default()]), true)
+        .await?;
+    assert_eq!(
+        status(fe_schema.get_request_by_id(ids[0]).await?),
+        ForcedExitLifecycleStatus::TxsSent
+    );
+    assert_eq!(
+        unconfirmed(fe_schema.get_unconfirmed_requests().await?),
+        vec![ids[0]]
+    );
+
+    // The failed transactions get the request back to be sent again
+    let retried = fe_schema
+        .cancel_request(ids[0], ForcedExitCancellationKind::SystemRetry, now, true)
+        .await?;
+    assert_eq!(status(retried), ForcedExitLifecycleStatus::PaymentReceived);
+    assert!(fe_schema.get_unconfirmed_requests().await?.is_empty());
+
+    fe_schema
+        .set_fulfilled_by(ids[0], Some(vec![TxHash::default()]), true)
+        .await?;
+    fe_schema.set_fulfilled_at(ids[0], now).await?;
+    assert_eq!(
+        status(fe_schema.get_request_by_id(ids[0]).await?),
+        ForcedExitLifecycleStatus::Fulfilled
+    );
+    assert!(fe_schema.get_unconfirmed_requests().await?.is_empty());
+
+    let cancelled = fe_schema
+        .cancel_request(ids[1], ForcedExitCancellationKind::UserCancelled, now, true)
+        .await?;
+    assert_eq!(status(cancelled), ForcedExitLifecycleStatus::Cancelled);
+    let extended = fe_schema
+        .set_valid_until(ids[1], now.add(Duration::days(1)))
+        .await?;
+    assert_eq!(status(extended), ForcedExitLifecycleStatus::Created);
+    fe_schema
+        .store_processing_failure(&ForcedExitProcessingFailure {
+            request_id: ids[1],
+            attempts: 3,
+            error: "Failed to process the payment".to_string(),
+            failed_at: now,
+            submission_error: None,
+        })
+        .await?;
+    assert_eq!(
+        status(fe_schema.get_request_by_id(ids[1]).await?),
+        ForcedExitLifecycleStatus::Failed
+    );
+
+    // The request not paid for in time is kept until the deleting threshold passes
+    fe_schema
+        .delete_old_unfulfilled_requests(Duration::days(7))
+        .await?;
+    assert_eq!(
+        status(fe_schema.get_request_by_id(ids[2]).await?),
+        ForcedExitLifecycleStatus::Expired
+    );
+
+    Ok(())
+}
+
+// Checks that the request not paid for within the grace period gives its public id up,
+// and the id is allocated to the next request without the expired one being found by it
+#[db_test]
+async fn expired_public_ids_reuse(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now.sub(Duration::days(3)),
+        valid_until: now.sub(Duration::days(2)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let within_grace = SaveForcedExitRequestQuery {
+        valid_until: now.sub(Duration::hours(1)),
+        ..request.clone()
+    };
+    let stored = store_requests(
+        &mut storage,
+        vec![request.clone(), request.clone(), within_grace],
+    )
+    .await;
+    let (expired, paid, within_grace) = (&stored[0], &stored[1], &stored[2]);
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    fe_schema
+        .set_match_scheme(paid.id, PaymentMatchScheme::AmountDigits, now)
+        .await?;
+    assert_eq!(
+        fe_schema
+            .expire_unpaid_requests(now, Duration::days(1), 10)
+            .await?,
+        1
+    );
+    // Nothing is left to expire
+    assert_eq!(
+        fe_schema
+            .expire_unpaid_requests(now, Duration::days(1), 10)
+            .await?,
+        0
+    );
+    assert_eq!(
+        fe_schema
+            .get_request_by_id(expired.id)
+            .await?
+            .unwrap()
+            .status,
+        ForcedExitLifecycleStatus::Expired
+    );
+    assert_eq!(
+        fe_schema
+            .get_request_by_public_id(expired.public_id)
+            .await?,
+        None
+    );
+    for kept in [paid, within_grace] {
+        assert_eq!(
+            fe_schema
+                .get_request_by_public_id(kept.public_id)
+                .await?
+                .map(|request| request.id),
+            Some(kept.id)
+        );
+    }
+    // The expired request can not be extended with the id given up
+    assert!(fe_schema
+        .set_valid_until(expired.id, now.add(Duration::days(1)))
+        .await?
+        .is_none());
+
+    let reused = fe_schema
+        .store_request(SaveForcedExitRequestQuery {
+            created_at: now,
+            valid_until: now.add(Duration::days(1)),
+            ..request.clone()
+        })
+        .await?;
+    assert_eq!(reused.public_id, expired.public_id);
+    assert_eq!(reused.pay_exactly, expired.pay_exactly);
+    let found = fe_schema
+        .get_request_by_public_id(expired.public_id)
+        .await?
+        .unwrap();
+    assert_eq!(found.id, reused.id);
+    let closest = fe_schema
+        .get_requests_by_closest_amount(&BigUint::from_str(&expired.pay_exactly).unwrap(), now)
+        .await?;
+    assert_eq!(closest[0].id, reused.id);
+
+    // The ids are allocated from the counter once no released ones are left
+    let next = fe_schema
+        .store_request(SaveForcedExitRequestQuery {
+            created_at: now,
+            valid_until: now.add(Duration::days(1)),
+            ..request
+        })
+        .await?;
+    assert!(next.public_id > within_grace.public_id);
+
+    Ok(())
+}
+
+// Checks that the deposits and the transfers creating the account are found in the queues
+#[db_test]
+async fn account_creation_pending(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let deposited = Address::repeat_byte(0x01);
+    let transferred = Address::repeat_byte(0x02);
+    let unknown = Address::repeat_byte(0x03);
+
+    let deposit = PriorityOp {
+        serial_id: 1,
+        data: ZkSyncPriorityOp::Deposit(Deposit {
+            from: Address::repeat_byte(0x10),
+            token: TokenId(0),
+            amount: 100u32.into(),
+            to: deposited,
+        }),
+        deadline_block: 100,
+        eth_hash: H256::repeat_byte(0x01),
+        eth_block: 10,
+        eth_block_index: Some(1),
+    };
+    MempoolSchema(&mut storage)
+        .insert_priority_ops(&[deposit], true)
+        .await?;
+    let transfer = Transfer::new(
+        AccountId(1),
+        Address::repeat_byte(0x10),
+        transferred,
+        TokenId(0),
+        100u32.into(),
+        10u32.into(),
+        Nonce(0),
+        Default::default(),
+        None,
+    );
+    MempoolSchema(&mut storage)
+        .insert_tx(&SignedZkSyncTx {
+            tx: ZkSyncTx::Transfer(Box::new(transfer)),
+            eth_sign_data: None,
+            created_at: Utc::now(),
+        })
+        .await?;
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    assert!(fe_schema.is_account_creation_pending(deposited).await?);
+    assert!(fe_schema.is_account_creation_pending(transferred).await?);
+    assert!(!fe_schema.is_account_creation_pending(unknown).await?);
+
+    Ok(())
+}
+
+// Checks that the sender state keeps the time it was entered at while it stays the same
+#[db_test]
+async fn sender_state(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let address = Address::repeat_byte(0x01);
+    let start = Utc::now().with_nanosecond(0).unwrap();
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    assert!(fe_schema.load_sender_status().await?.is_none());
+
+    fe_schema
+        .store_sender_state(address, ForcedExitSenderState::AddressUnknown, start)
+        .await?;
+    fe_schema
+        .store_sender_state(
+            address,
+            ForcedExitSenderState::AddressUnknown,
+            start + Duration::seconds(1),
+        )
+        .await?;
+    let status = fe_schema.load_sender_status().await?.unwrap();
+    assert_eq!(status.address, address);
+    assert_eq!(status.state, ForcedExitSenderState::AddressUnknown);
+    assert_eq!(status.since, start);
+
+    fe_schema
+        .store_sender_state(
+            address,
+            ForcedExitSenderState::CreationPending,
+            start + Duration::seconds(2),
+        )
+        .await?;
+    let status = fe_schema.load_sender_status().await?.unwrap();
+    assert_eq!(status.state, ForcedExitSenderState::CreationPending);
+    assert_eq!(status.since, start + Duration::seconds(2));
+
+    Ok(())
+}
+
+// Checks that the pacing override replaces and resets the stored limits, and the
+// reported pacing state is returned with the sender status
+#[db_test]
+async fn pacing(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let address = Address::repeat_byte(0x01);
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let pacing = ForcedExitPacing {
+        max_batches_per_minute: 6,
+        batches_per_block: 2,
+    };
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    assert!(fe_schema.load_pacing_override().await?.is_none());
+
+    fe_schema.set_pacing_override(Some(pacing), now).await?;
+    assert_eq!(fe_schema.load_pacing_override().await?, Some(pacing));
+
+    let relaxed = ForcedExitPacing {
+        max_batches_per_minute: 60,
+        batches_per_block: 0,
+    };
+    fe_schema.set_pacing_override(Some(relaxed), now).await?;
+    assert_eq!(fe_schema.load_pacing_override().await?, Some(relaxed));
+
+    fe_schema.set_pacing_override(None, now).await?;
+    assert!(fe_schema.load_pacing_override().await?.is_none());
+
+    fe_schema
+        .store_sender_state(address, ForcedExitSenderState::Ready, now)
+        .await?;
+    let status = fe_schema.load_sender_status().await?.unwrap();
+    assert!(status.pacing.is_none());
+
+    let state = ForcedExitPacingState {
+        pacing,
+        overridden: true,
+        available_batches: Some(3),
+        batches_in_block: 1,
+        paced_batches: 4,
+        reported_at: now,
+    };
+    fe_schema.store_pacing_state(address, &state).await?;
+    let status = fe_schema.load_sender_status().await?.unwrap();
+    assert_eq!(status.pacing, Some(state));
+
+    Ok(())
+}
+
+// Checks that the payment is registered for a single request, and the mismatched
+// amount is recorded with the registration
+#[db_test]
+async fn expected_payments(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::days(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let ids: Vec<_> = store_requests(&mut storage, vec![request; 2])
+        .await
+        .into_iter()
+        .map(|request| request.id)
+        .collect();
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    let eth_tx_hash = H256::repeat_byte(0x21);
+    assert!(fe_schema.get_expected_payment(eth_tx_hash).await?.is_none());
+    assert!(
+        fe_schema
+            .store_expected_payment(ids[0], eth_tx_hash, now)
+            .await?
+    );
+    // Registering the payment once again changes nothing
+    assert!(
+        fe_schema
+            .store_expected_payment(ids[0], eth_tx_hash, now.add(Duration::hours(1)))
+            .await?
+    );
+    // The payment can not pay for another request
+    assert!(
+        !fe_schema
+            .store_expected_payment(ids[1], eth_tx_hash, now)
+            .await?
+    );
+
+    let expected = fe_schema.get_expected_payment(eth_tx_hash).await?.unwrap();
+    assert_eq!(expected.request_id, ids[0]);
+    assert_eq!(expected.registered_at, now);
+    assert_eq!(expected.mismatched_amount, None);
+    assert!(fe_schema.load_expected_payments(ids[1]).await?.is_empty());
+
+    fe_schema
+        .flag_expected_payment(eth_tx_hash, &BigUint::from_i32(213).unwrap())
+        .await?;
+    let flagged = fe_schema.load_expected_payments(ids[0]).await?;
+    assert_eq!(flagged.len(), 1);
+    assert_eq!(flagged[0].mismatched_amount.as_deref(), Some("213"));
+
+    Ok(())
+}
+
+// Checks that a payment is refunded once, and the refund is settled after the transfer
+// is committed or has failed in all the attempts
+#[db_test]
+async fn refunds(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::days(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let id = store_requests(&mut storage, vec![request]).await[0].id;
+    let refund = |payment_tx_hash| SaveForcedExitRefundQuery {
+        request_id: id,
+        payment_tx_hash,
+        recipient: Address::repeat_byte(0x42),
+        amount: BigUint::from_i32(200).unwrap(),
+        fee: BigUint::from_i32(13).unwrap(),
+        reason: ForcedExitRefundReason::Expired,
+        requires_approval: false,
+        created_at: now,
+    };
+    let cipher = ColumnCipher::new([7u8; 32], false);
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    let stored = fe_schema
+        .store_refund(refund(H256::repeat_byte(0x21)))
+        .await?
+        .unwrap();
+    assert_eq!(stored.request_id, id);
+    assert_eq!(stored.recipient, Address::repeat_byte(0x42));
+    assert_eq!(stored.amount, BigUint::from_i32(200).unwrap());
+    assert_eq!(stored.fee, BigUint::from_i32(13).unwrap());
+    assert_eq!(stored.status, ForcedExitRefundStatus::Pending);
+    assert_eq!(stored.attempts, 0);
+    // The payment delivered once again is not refunded twice
+    assert!(fe_schema
+        .store_refund(refund(H256::repeat_byte(0x21)))
+        .await?
+        .is_none());
+    // The recipient is encrypted along with the payers of the payments
+    let encrypted = SaveForcedExitRefundQuery {
+        request_id: id + 1,
+        ..refund(H256::repeat_byte(0x22))
+    };
+    let encrypted = fe_schema
+        .store_refund_with_cipher(encrypted, Some(&cipher))
+        .await?
+        .unwrap();
+    assert_eq!(encrypted.recipient, Address::repeat_byte(0x42));
+    fe_schema
+        .set_refund_status(encrypted.id, ForcedExitRefundStatus::Completed, None)
+        .await?;
+
+    let unsettled = |refunds: Vec<ForcedExitRefund>| -> Vec<_> {
+        refunds
+            .into_iter()
+            .map(|refund| (refund.id, refund.status, refund.attempts))
+            .collect()
+    };
+    assert_eq!(
+        unsettled(fe_schema.load_unsettled_refunds(2).await?),
+        vec![(stored.id, ForcedExitRefundStatus::Pending, 0)]
+    );
+
+    let tx_hash = TxHash::from_str(
+        "sync-tx:796018689b3e323894f44fb0093856ec3832908c626dea357a9bd1b25f9d11bf",
+    )
+    .unwrap();
+    fe_schema
+        .set_refund_status(stored.id, ForcedExitRefundStatus::Sent, Some(tx_hash))
+        .await?;
+    fe_schema
+        .set_refund_status(stored.id, ForcedExitRefundStatus::Failed, None)
+        .await?;
+    // The failed refund is sent again until the attempts run out
+    assert_eq!(
+        unsettled(fe_schema.load_unsettled_refunds(2).await?),
+        vec![(stored.id, ForcedExitRefundStatus::Failed, 1)]
+    );
+    assert!(fe_schema.load_unsettled_refunds(1).await?.is_empty());
+
+    let refunds = fe_schema.load_request_refunds(id).await?;
+    assert_eq!(refunds.len(), 1);
+    assert_eq!(refunds[0].tx_hash, Some(tx_hash));
+    // The encrypted recipient can not be read without the key
+    assert!(fe_schema.load_request_refunds(id + 1).await.is_err());
+
+    Ok(())
+}
+
+// Checks that the refund of the payment, which has already been refunded, is blocked
+// no matter how far the first refund has got and which request the second one is for
+#[db_test]
+async fn double_refunds(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let refund = |request_id, requires_approval| SaveForcedExitRefundQuery {
+        request_id,
+        payment_tx_hash: H256::repeat_byte(0x31),
+        recipient: Address::repeat_byte(0x42),
+        amount: BigUint::from_i32(200).unwrap(),
+        fee: BigUint::from_i32(13).unwrap(),
+        reason: ForcedExitRefundReason::IncorrectAmount,
+        requires_approval,
+        created_at: now,
+    };
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    let stored = fe_schema.store_refund(refund(1, true)).await?.unwrap();
+    // Awaiting the approval
+    assert!(fe_schema.store_refund(refund(1, false)).await?.is_none());
+    fe_schema.approve_refund(stored.id, "on-call", now).await?;
+    fe_schema
+        .set_refund_status(stored.id, ForcedExitRefundStatus::Completed, None)
+        .await?;
+    // Completed, the payment is attributed to another request this time
+    assert!(fe_schema.store_refund(refund(2, false)).await?.is_none());
+
+    assert_eq!(fe_schema.load_request_refunds(1).await?.len(), 1);
+    assert!(fe_schema.load_request_refunds(2).await?.is_empty());
+
+    Ok(())
+}
+
+// Checks that the refunds above the threshold are only sent once approved, ahead of the rest,
+// and that the amount of the transfers sent recently is summed up for the hourly limit
+#[db_test]
+async fn refund_approvals(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let refund = |payment_byte, amount, requires_approval, created_at| SaveForcedExitRefundQuery {
+        request_id: 1,
+        payment_tx_hash: H256::repeat_byte(payment_byte),
+        recipient: Address::repeat_byte(0x42),
+        amount: BigUint::from_u64(amount).unwrap(),
+        fee: BigUint::from_i32(13).unwrap(),
+        reason: ForcedExitRefundReason::Expired,
+        requires_approval,
+        created_at,
+    };
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    let small = fe_schema
+        .store_refund(refund(0x41, 100, false, now - Duration::minutes(2)))
+        .await?
+        .unwrap();
+    let large = fe_schema
+        .store_refund(refund(0x42, 5000, true, now - Duration::minutes(1)))
+        .await?
+        .unwrap();
+    assert_eq!(large.status, ForcedExitRefundStatus::AwaitingApproval);
+    let ids = |refunds: Vec<ForcedExitRefund>| -> Vec<_> {
+        refunds.into_iter().map(|refund| refund.id).collect()
+    };
+    assert_eq!(
+        ids(fe_schema.load_unsettled_refunds(3).await?),
+        vec![small.id]
+    );
+    assert_eq!(
+        ids(fe_schema.load_refunds_awaiting_approval().await?),
+        vec![large.id]
+    );
+
+    let approved = fe_schema
+        .approve_refund(large.id, "on-call", now)
+        .await?
+        .unwrap();
+    assert_eq!(approved.status, ForcedExitRefundStatus::Pending);
+    assert_eq!(approved.approved_by.as_deref(), Some("on-call"));
+    assert_eq!(approved.approved_at, Some(now));
+    // The refund is approved once
+    assert!(fe_schema
+        .approve_refund(large.id, "on-call", now)
+        .await?
+        .is_none());
+    assert!(fe_schema
+        .approve_refund(small.id, "on-call", now)
+        .await?
+        .is_none());
+    assert!(fe_schema.load_refunds_awaiting_approval().await?.is_empty());
+    // The approved refund goes first, though recorded later
+    assert_eq!(
+        ids(fe_schema.load_unsettled_refunds(3).await?),
+        vec![large.id, small.id]
+    );
+
+    let since = Utc::now() - Duration::hours(1);
+    assert_eq!(
+        fe_schema.refunded_amount_since(since).await?,
+        BigUint::from(0u32)
+    );
+    let tx_hash = TxHash::from_slice(&[7; 32]).unwrap();
+    for id in &[small.id, large.id] {
+        fe_schema
+            .set_refund_status(*id, ForcedExitRefundStatus::Sent, Some(tx_hash))
+            .await?;
+    }
+    fe_schema
+        .set_refund_status(large.id, ForcedExitRefundStatus::Completed, None)
+        .await?;
+    assert_eq!(
+        fe_schema.refunded_amount_since(since).await?,
+        BigUint::from(5100u32)
+    );
+    // The failed transfer has not returned anything
+    fe_schema
+        .set_refund_status(small.id, ForcedExitRefundStatus::Failed, None)
+        .await?;
+    assert_eq!(
+        fe_schema.refunded_amount_since(since).await?,
+        BigUint::from(5000u32)
+    );
+    assert!(fe_schema
+        .get_refund(large.id)
+        .await?
+        .unwrap()
+        .sent_at
+        .is_some());
+    assert!(fe_schema.get_refund(-1).await?.is_none());
+
+    Ok(())
+}
+
+// Checks that the requests changed recently are loaded along with the records backing their statuses
+#[db_test]
+async fn consistency_evidence(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1), TokenId(2)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::days(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let ids: Vec<_> = store_requests(&mut storage, vec![request; 2])
+        .await
+        .into_iter()
+        .map(|request| request.id)
+        .collect();
+    let hash = |byte: u8| TxHash::from_slice(&[byte; 32]).unwrap();
+    let payment = ForcedExitPayment {
+        amount: BigUint::from_i32(1000).unwrap(),
+        request_id: Some(ids[0]),
+        block_number: 10,
+        eth_tx_hash: Some(H256::repeat_byte(0x01)),
+        payer: None,
+        received_at: now,
+        source: PaymentSource::L1Event,
+    };
+
+    // The first request is paid for and fulfilled
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    fe_schema.store_payment(&payment).await?;
+    fe_schema
+        .set_match_scheme(ids[0], PaymentMatchScheme::ExplicitId, now)
+        .await?;
+    fe_schema.set_paid_amount(ids[0], &payment.amount).await?;
+    fe_schema
+        .store_payment_match(ids[0], H256::repeat_byte(0x01), now)
+        .await?;
+    fe_schema
+        .set_fulfilled_by(ids[0], Some(vec![hash(1), hash(2)]), false)
+        .await?;
+    fe_schema.set_fulfilled_at(ids[0], now).await?;
+    // The second one has the payment refunded, which has never been received
+    fe_schema
+        .store_refund(SaveForcedExitRefundQuery {
+            request_id: ids[1],
+            payment_tx_hash: H256::repeat_byte(0x02),
+            recipient: Address::repeat_byte(0x42),
+            amount: BigUint::from_i32(200).unwrap(),
+            fee: BigUint::from_i32(13).unwrap(),
+            reason: ForcedExitRefundReason::Expired,
+            requires_approval: false,
+            created_at: now,
+        })
+        .await?;
+
+    let evidence: Vec<_> = fe_schema
+        .load_consistency_evidence(now - Duration::minutes(1), 1000)
+        .await?
+        .into_iter()
+        .filter(|evidence| ids.contains(&evidence.request.id))
+        .collect();
+    assert_eq!(evidence.len(), 2);
+    assert_eq!(evidence[0].request.id, ids[0]);
+    assert_eq!(evidence[0].fulfilled_tokens, vec![TokenId(1), TokenId(2)]);
+    assert_eq!(
+        evidence[0].events,
+        vec![
+            ForcedExitRequestEvent::Submitted,
+            ForcedExitRequestEvent::Fulfilled
+        ]
+    );
+    assert_eq!(evidence[0].matched_payments, vec![H256::repeat_byte(0x01)]);
+    assert!(!evidence[0].escalated);
+    assert_eq!(evidence[0].failed_at, None);
+
+    assert_eq!(evidence[1].refunds.len(), 1);
+    assert!(!evidence[1].refunds[0].payment_recorded);
+    assert_eq!(evidence[1].refunds[0].payment_amount, None);
+
+    // Nothing has changed since then
+    assert!(fe_schema
+        .load_consistency_evidence(now + Duration::minutes(1), 1000)
+        .await?
+        .is_empty());
+
+    let report = ForcedExitConsistencyReport {
+        created_at: now,
+        since: now - Duration::minutes(1),
+        checked_requests: evidence.len() as u32,
+        violations: vec![ForcedExitInvariantViolation {
+            request_id: ids[1],
+            invariant: ForcedExitInvariant::RefundedPayment,
+            details: "refund 1 returns the unknown payment".to_owned(),
+        }],
+    };
+    fe_schema.store_consistency_report(&report).await?;
+    assert_eq!(fe_schema.load_consistency_reports(1).await?, vec![report]);
+
+    Ok(())
+}
+
+// Checks that the money config is recorded once changed and is looked up as of the given time
+#[db_test]
+async fn config_history(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let start = Utc::now().with_nanosecond(0).unwrap();
+    let config = ForcedExitMoneyConfig {
+        price_per_token: 10000000000000000,
+        digits_in_id: 13,
+        validity_period: 450,
+        payment_address: Address::repeat_byte(0x01),
+        overpayment_tolerance: 0,
+        overpayment_tolerance_percent: 0,
+        refund_processing_fee: 1000000000000000,
+        legacy_amount_ids_enabled: true,
+    };
+    let repriced = ForcedExitMoneyConfig {
+        price_per_token: 20000000000000000,
+        ..config.clone()
+    };
+    let rotated = ForcedExitMoneyConfig {
+        payment_address: Address::repeat_byte(0x02),
+        ..repriced.clone()
+    };
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    assert_eq!(fe_schema.config_at(start).await?, None);
+
+    // The restarts with the same config do not record the snapshots
+    assert!(fe_schema.record_config(&config, start).await?);
+    assert!(
+        !fe_schema
+            .record_config(&config, start + Duration::hours(1))
+            .await?
+    );
+    assert!(
+        fe_schema
+            .record_config(&repriced, start + Duration::hours(2))
+            .await?
+    );
+    assert!(
+        fe_schema
+            .record_config(&rotated, start + Duration::hours(3))
+            .await?
+    );
+    // The config changed back is a new snapshot
+    assert!(
+        fe_schema
+            .record_config(&config, start + Duration::hours(4))
+            .await?
+    );
+
+    let at = |hours: i64, minutes: i64| start + Duration::hours(hours) + Duration::minutes(minutes);
+    assert_eq!(fe_schema.config_at(at(0, -1)).await?, None);
+    let snapshot = fe_schema.config_at(at(0, 0)).await?.unwrap();
+    assert_eq!(snapshot.effective_from, start);
+    assert_eq!(snapshot.config, config);
+    let snapshot = fe_schema.config_at(at(1, 30)).await?.unwrap();
+    assert_eq!(snapshot.effective_from, start);
+    assert_eq!(snapshot.config, config);
+    let snapshot = fe_schema.config_at(at(2, 30)).await?.unwrap();
+    assert_eq!(snapshot.effective_from, at(2, 0));
+    assert_eq!(snapshot.config, repriced);
+    let snapshot = fe_schema.config_at(at(3, 59)).await?.unwrap();
+    assert_eq!(snapshot.config, rotated);
+    let snapshot = fe_schema.config_at(at(10, 0)).await?.unwrap();
+    assert_eq!(snapshot.effective_from, at(4, 0));
+    assert_eq!(snapshot.config, config);
+
+    Ok(())
+}
+
+// Checks that the notes are listed per request and are searched by the tags
+#[db_test]
+async fn request_notes(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from_i32(212).unwrap(),
+        created_at: now,
+        valid_until: now.add(Duration::days(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let requests = store_requests(&mut storage, vec![request.clone(), request]).await;
+    let note = |request_id, text: &str, tags: &[&str]| SaveForcedExitRequestNoteQuery {
+        request_id,
+        author: "operator".to_string(),
+        text: text.to_string(),
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        created_at: now,
+    };
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    let first = fe_schema
+        .store_note(note(requests[0].id, "customer contacted", &["follow-up"]))
+        .await?;
+    assert_eq!(first.request_id, requests[0].id);
+    assert_eq!(first.author, "operator");
+    assert_eq!(first.tags, vec!["follow-up".to_string()]);
+    let second = fe_schema
+        .store_note(note(requests[0].id, "refund issued manually", &[]))
+        .await?;
+    let third = fe_schema
+        .store_note(note(requests[1].id, "escalated", &["follow-up", "l1"]))
+        .await?;
+
+    assert_eq!(
+        fe_schema.load_request_notes(requests[0].id).await?,
+        vec![first.clone(), second]
+    );
+    assert_eq!(
+        fe_schema.load_request_notes(requests[1].id).await?,
+        vec![third.clone()]
+    );
+
+    // The newest notes come first
+    assert_eq!(
+        fe_schema.load_notes_by_tag("follow-up", 10).await?,
+        vec![third.clone(), first]
+    );
+    assert_eq!(
+        fe_schema.load_notes_by_tag("follow-up", 1).await?,
+        vec![third.clone()]
+    );
+    assert_eq!(fe_schema.load_notes_by_tag("l1", 10).await?, vec![third]);
+    assert!(fe_schema.load_notes_by_tag("follow", 10).await?.is_empty());
+
+    Ok(())
+}
+
+// Checks that the payment terms are stored along with the request: the amount to be paid
+// is computed by them and the grace period of the expiration is taken from them
+#[db_test]
+async fn payment_terms(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let terms = ForcedExitPaymentTerms {
+        digits_in_id: 5,
+        check_digit: false,
+        overpayment_tolerance: BigUint::from(10u32),
+        expiration_grace_period: Duration::hours(1).num_milliseconds() as u64,
+    };
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from(1_000_000u32),
+        created_at: now.sub(Duration::days(1)),
+        valid_until: now.sub(Duration::hours(2)),
+        metadata: None,
+        payment_terms: Some(terms.clone()),
+        callback_url: None,
+    };
+    let stored = store_requests(
+        &mut storage,
+        vec![
+            request.clone(),
+            SaveForcedExitRequestQuery {
+                payment_terms: None,
+                ..request
+            },
+        ],
+    )
+    .await;
+    let (with_terms, without_terms) = (&stored[0], &stored[1]);
+    assert_eq!(with_terms.payment_terms, Some(terms));
+    assert_eq!(
+        with_terms.pay_exactly,
+        legacy_pay_exactly(&with_terms.price_in_wei, with_terms.public_id)
+    );
+    assert_eq!(without_terms.payment_terms, None);
+    assert_eq!(
+        without_terms.pay_exactly,
+        pay_exactly(&without_terms.price_in_wei, without_terms.public_id)
+    );
+
+    // Only the grace period of the request with the terms is over
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    assert_eq!(
+        fe_schema
+            .expire_unpaid_requests(now, Duration::days(1), 10)
+            .await?,
+        1
+    );
+    assert_eq!(
+        fe_schema
+            .get_request_by_id(with_terms.id)
+            .await?
+            .unwrap()
+            .status,
+        ForcedExitLifecycleStatus::Expired
+    );
+    assert_eq!(
+        fe_schema
+            .get_request_by_id(without_terms.id)
+            .await?
+            .unwrap()
+            .status,
+        ForcedExitLifecycleStatus::Created
+    );
+
+    Ok(())
+}
+
+// Checks that the retries stay pending until they are completed with the outcome
+#[db_test]
+async fn request_retries(mut storage: StorageProcessor<'_>) -> QueryResult<()> {
+    let now = Utc::now().with_nanosecond(0).unwrap();
+    let request = SaveForcedExitRequestQuery {
+        target: Address::from_str("c0f97CC918C9d6fA4E9fc6be61a6a06589D199b2").unwrap(),
+        tokens: vec![TokenId(1)],
+        price_in_wei: BigUint::from(1_000_000u32),
+        created_at: now,
+        valid_until: now.add(Duration::hours(1)),
+        metadata: None,
+        payment_terms: None,
+        callback_url: None,
+    };
+    let stored = store_requests(&mut storage, vec![request]).await;
+
+    let mut fe_schema = ForcedExitRequestsSchema(&mut storage);
+    let first = fe_schema.store_retry(stored[0].id, false, now).await?;
+    let second = fe_schema.store_retry(stored[0].id, true, now).await?;
+    assert!(!first.is_processed() && first.tx_hashes.is_empty());
+    assert_eq!(
+        fe_schema.load_pending_retries(10).await?,
+        vec![first.clone(), second.clone()]
+    );
+
+    let tx_hashes = vec![TxHash::default(); 2];
+    fe_schema
+        .complete_retry(first.id, &tx_hashes, None, now)
+        .await?;
+    fe_schema
+        .complete_retry(second.id, &[], Some("The request has expired"), now)
+        .await?;
+    assert!(fe_schema.load_pending_retries(10).await?.is_empty());
+
+    let first = fe_schema.get_retry(first.id).await?.unwrap();
+    assert_eq!(first.processed_at, Some(now));
+    assert_eq!(first.tx_hashes, tx_hashes);
+    assert_eq!(first.error, None);
+    let second = fe_schema.get_retry(second.id).await?.unwrap();
+    assert!(second.tx_hashes.is_empty());
+    assert_eq!(second.error.as_deref(), Some("The request has expired"));
+
+    Ok(())
+}
@@ -1,31 +1,374 @@
 use chrono::{DateTime, Utc};
-use num::BigUint;
+use num::{BigUint, Zero};
 use thiserror::Error;
-use zksync_basic_types::{Address, TokenId};
-use zksync_utils::BigUintSerdeAsRadix10Str;
+use zksync_basic_types::{AccountId, Address, Nonce, TokenId};
+use zksync_utils::{BigUintSerdeAsRadix10Str, BigUintSerdeWrapper, ZeroPrefixHexSerde};
 
 use serde::{Deserialize, Serialize};
 
 pub type ForcedExitRequestId = i64;
 
-use ethabi::{decode, ParamType};
-use std::convert::TryFrom;
-use zksync_basic_types::Log;
+use ethabi::{decode, long_signature, ParamType};
+use parity_crypto::digest::sha256;
+use std::{convert::TryFrom, fmt, str::FromStr};
+use zksync_basic_types::{Log, H256, U256};
 
 use crate::tx::TxHash;
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 #[serde(rename_all = "camelCase")]
 pub struct ForcedExitRequest {
+    /// The key of the request in the storage, used by the server only.
     pub id: ForcedExitRequestId,
+    /// The id the clients refer to the request with, encoded in the payment amount and
+    /// supplied as the explicit id of the payment. It is allocated by the server on its own,
+    /// the requests created before keep their ids as the public ones.
+    pub public_id: ForcedExitRequestId,
     pub target: Address,
     pub tokens: Vec<TokenId>,
     #[serde(with = "BigUintSerdeAsRadix10Str")]
     pub price_in_wei: BigUint,
+    /// The exact amount to be paid for the request as a decimal string, i.e. the price with
+    /// the public id and its check digit in its lowest digits: `"2000000000000001234567897"` for
+    /// the request 123456789 priced at 2·10^24 wei. It is computed once the request is created,
+    /// so the clients do not have to add the id to the price themselves.
+    pub pay_exactly: String,
     pub valid_until: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
     pub fulfilled_by: Option<Vec<TxHash>>,
     pub fulfilled_at: Option<DateTime<Utc>>,
+    pub match_scheme: Option<PaymentMatchScheme>,
+    /// The time the request was first matched with a payment, the paid requests
+    /// are processed in this order.
+    pub matched_at: Option<DateTime<Utc>>,
+    /// The kind of the last cancellation of the request, if any.
+    #[serde(default)]
+    pub cancellation: Option<ForcedExitCancellationKind>,
+    /// The amount the request was paid for with as a decimal string, set once the request
+    /// is matched with the payment. It exceeds `pay_exactly` for the overpaid requests.
+    #[serde(default)]
+    pub paid_amount: Option<String>,
+    /// The text attached to the request by the client, e.g. the reference in the system of the partner.
+    #[serde(default)]
+    pub metadata: Option<String>,
+    /// The stage of the lifecycle the request is at. Not reported by the servers preceding
+    /// the field, their requests are treated as just created.
+    #[serde(default)]
+    pub status: ForcedExitLifecycleStatus,
+    /// The terms the payments are matched with the request by, fixed once the request
+    /// is created. Not stored for the requests created before, the matcher derives them
+    /// from the current config instead.
+    #[serde(default)]
+    pub payment_terms: Option<ForcedExitPaymentTerms>,
+}
+
+/// The stage of the lifecycle of the request, stored along with it and changed
+/// by the same transaction that makes the transition.
+///
+/// The request normally goes `Created` → `PaymentReceived` → `TxsSent` → `Fulfilled`.
+/// The transactions which have failed get the request back to `PaymentReceived`,
+/// so they are sent again.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum ForcedExitLifecycleStatus {
+    /// The request awaits to be paid for.
+    Created,
+    /// The request has been matched with the payment, no transactions are sent for it yet.
+    PaymentReceived,
+    /// The `ForcedExit` transactions have been sent and await to be executed.
+    TxsSent,
+    /// The transactions (or the `FullExit` operations) have been executed.
+    Fulfilled,
+    /// The request will not be fulfilled, its payment is to be refunded.
+    Failed,
+    /// The request has not been paid for within its validity period.
+    Expired,
+    /// The request has been withdrawn by the user or by the operators.
+    Cancelled,
+}
+
+impl ForcedExitLifecycleStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::PaymentReceived => "payment_received",
+            Self::TxsSent => "txs_sent",
+            Self::Fulfilled => "fulfilled",
+            Self::Failed => "failed",
+            Self::Expired => "expired",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl Default for ForcedExitLifecycleStatus {
+    fn default() -> Self {
+        Self::Created
+    }
+}
+
+impl fmt::Display for ForcedExitLifecycleStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ForcedExitLifecycleStatus {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Ok(match string {
+            "created" => Self::Created,
+            "payment_received" => Self::PaymentReceived,
+            "txs_sent" => Self::TxsSent,
+            "fulfilled" => Self::Fulfilled,
+            "failed" => Self::Failed,
+            "expired" => Self::Expired,
+            "cancelled" => Self::Cancelled,
+            another => return Err(another.to_owned()),
+        })
+    }
+}
+
+/// The state of the transaction sent for the request, as told by its receipt.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum ForcedExitTxStatus {
+    /// The transaction has not been executed yet.
+    Pending,
+    /// The transaction has been executed in a committed block.
+    Committed,
+    /// The block with the transaction has been verified.
+    Verified,
+    /// The transaction has been rejected, the request is sent once again.
+    Failed,
+}
+
+impl ForcedExitTxStatus {
+    /// Whether the transaction has been executed, either successfully or not.
+    pub fn is_executed(&self) -> bool {
+        *self != Self::Pending
+    }
+}
+
+/// Who or what has cancelled the request. Only the requests cancelled by the system to be
+/// sent again are processed automatically afterwards, the rest are processed once again
+/// only if the operators extend them.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum ForcedExitCancellationKind {
+    /// The transactions of the request have failed or were not sent, they are sent again.
+    SystemRetry,
+    /// The user has withdrawn the request.
+    UserCancelled,
+    /// The operators have cancelled the request.
+    OperatorCancelled,
+    /// The request was paid for after it had expired.
+    Expired,
+    /// The target address has no account, so there is nothing to withdraw from it.
+    TargetNotFound,
+}
+
+impl ForcedExitCancellationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::SystemRetry => "system_retry",
+            Self::UserCancelled => "user_cancelled",
+            Self::OperatorCancelled => "operator_cancelled",
+            Self::Expired => "expired",
+            Self::TargetNotFound => "target_not_found",
+        }
+    }
+
+    /// Whether the request may be processed again without the operators.
+    pub fn allows_reprocessing(&self) -> bool {
+        matches!(self, Self::SystemRetry)
+    }
+}
+
+impl fmt::Display for ForcedExitCancellationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ForcedExitCancellationKind {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Ok(match string {
+            "system_retry" => Self::SystemRetry,
+            "user_cancelled" => Self::UserCancelled,
+            "operator_cancelled" => Self::OperatorCancelled,
+            "expired" => Self::Expired,
+            "target_not_found" => Self::TargetNotFound,
+            another => return Err(another.to_owned()),
+        })
+    }
+}
+
+/// The cancellation of the request, as recorded in its audit trail.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitCancellation {
+    pub request_id: ForcedExitRequestId,
+    pub kind: ForcedExitCancellationKind,
+    pub cancelled_at: DateTime<Utc>,
+}
+
+/// The way the payment was matched against the request.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum PaymentMatchScheme {
+    /// The id of the request was encoded in the lowest digits of the paid amount.
+    AmountDigits,
+    /// The id of the request was supplied explicitly in the calldata of the payment.
+    ExplicitId,
+    /// The hash of the payment transaction was registered for the request in advance.
+    ExpectedPayment,
+}
+
+impl PaymentMatchScheme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AmountDigits => "amount_digits",
+            Self::ExplicitId => "explicit_id",
+            Self::ExpectedPayment => "expected_payment",
+        }
+    }
+}
+
+impl fmt::Display for PaymentMatchScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PaymentMatchScheme {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Ok(match string {
+            "amount_digits" => Self::AmountDigits,
+            "explicit_id" => Self::ExplicitId,
+            "expected_payment" => Self::ExpectedPayment,
+            another => return Err(another.to_owned()),
+        })
+    }
+}
+
+/// Returns the number of the distinct ids which can be encoded in the lowest
+/// `digits_in_id` digits of the paid amount.
+pub fn id_space_size(digits_in_id: u8) -> BigUint {
+    BigUint::from(10u32).pow(digits_in_id.into())
+}
+
+/// Returns the number of the lowest digits of the paid amount taken by the id of the request
+/// followed by its check digit, see `check_digit`.
+pub fn amount_id_digits(digits_in_id: u8) -> u8 {
+    digits_in_id + 1
+}
+
+/// The Luhn check digit of the id, it follows the id in the amount paid for the request.
+///
+/// Any single mistyped digit of the id changes the check digit, as do most of the swapped
+/// adjacent digits, so such an amount is not matched against another request.
+pub fn check_digit(id: ForcedExitRequestId) -> u8 {
+    let mut rest = id as u64;
+    let mut sum = 0;
+    // The digits are doubled starting from the lowest one, since the check digit is appended
+    let mut doubled = true;
+    while rest > 0 {
+        let mut digit = rest % 10;
+        if doubled {
+            digit *= 2;
+            if digit > 9 {
+                digit -= 9;
+            }
+        }
+        sum += digit;
+        doubled = !doubled;
+        rest /= 10;
+    }
+    ((10 - sum % 10) % 10) as u8
+}
+
+/// The amount to pay for the request for the payment to be matched by the amount.
+///
+/// The id followed by its check digit is added to the price arithmetically, it takes
+/// the lowest `amount_id_digits` digits of the aligned price, so the zeros the id starts
+/// with are kept in the amount.
+pub fn pay_exactly(price: &BigUint, id: ForcedExitRequestId) -> String {
+    (price + id as u64 * 10 + u64::from(check_digit(id))).to_string()
+}
+
+/// The amount the requests created before the check digit was added are paid for with,
+/// the id alone takes the lowest `digits_in_id` digits of the price.
+pub fn legacy_pay_exactly(price: &BigUint, id: ForcedExitRequestId) -> String {
+    (price + id as u64).to_string()
+}
+
+/// How much the payment for the request with the given price may exceed the amount to be paid:
+/// the given percent of the price, but not less than the absolute tolerance in wei.
+pub fn overpayment_tolerance(price: &BigUint, absolute: u64, percent: u8) -> BigUint {
+    let relative = price * percent / 100u32;
+    relative.max(BigUint::from(absolute))
+}
+
+/// The terms the payments for the request are matched by, taken from the config
+/// the request is created with. The payments are matched by them even if the config
+/// has changed since, so the amount the client was told to pay stays valid.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitPaymentTerms {
+    /// The number of the lowest digits of the price the id takes.
+    pub digits_in_id: u8,
+    /// Whether the id is followed by its check digit in the amount.
+    pub check_digit: bool,
+    /// The amount in wei the payment matched by the amount may exceed `pay_exactly` by.
+    #[serde(with = "BigUintSerdeAsRadix10Str")]
+    pub overpayment_tolerance: BigUint,
+    /// The time in milliseconds the id is held for after the end of the validity period.
+    pub expiration_grace_period: u64,
+}
+
+impl ForcedExitPaymentTerms {
+    /// The number of the lowest digits of the paid amount taken by the id.
+    pub fn amount_id_digits(&self) -> u8 {
+        if self.check_digit {
+            amount_id_digits(self.digits_in_id)
+        } else {
+            self.digits_in_id
+        }
+    }
+
+    /// The amount to pay for the request with the `id` under these terms.
+    pub fn pay_exactly(&self, price: &BigUint, id: ForcedExitRequestId) -> String {
+        if self.check_digit {
+            pay_exactly(price, id)
+        } else {
+            legacy_pay_exactly(price, id)
+        }
+    }
+}
+
+/// The most digits of the id the amounts can be decoded with: the id followed by its
+/// check digit must fit into `i64`.
+pub const MAX_DIGITS_IN_ID: u32 = 17;
+
+/// Why the amount is not decoded to the id of a request.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Error)]
+#[serde(rename_all = "camelCase")]
+pub enum AmountIdError {
+    /// The id space does not fit into `i64`, i.e. the config is invalid.
+    #[error("{digits} digits of the id do not fit into i64")]
+    TooManyDigits { digits: u32 },
+    /// There is no price above the id, the amount is not paid for any request.
+    #[error("the amount is smaller than the id space")]
+    AmountTooSmall,
+    /// The check digit does not match the id, the amount is not paid for any request.
+    #[error("the check digit does not match the id")]
+    CheckDigitMismatch,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
@@ -33,15 +376,1906 @@ pub struct SaveForcedExitRequestQuery {
     pub target: Address,
     pub tokens: Vec<TokenId>,
     #[serde(with = "BigUintSerdeAsRadix10Str")]
-    pub price_in_wei: BigUint,
+    pub price_in_wei: BigUint,
+    pub created_at: DateTime<Utc>,
+    pub valid_until: DateTime<Utc>,
+    pub metadata: Option<String>,
+    /// Not set by the callers which do not take the terms from the config, the requests
+    /// are then matched as if created before the terms were stored.
+    #[serde(default)]
+    pub payment_terms: Option<ForcedExitPaymentTerms>,
+    /// The URL the request is posted to once fulfilled, see `ForcedExitFulfilledCallback`.
+    #[serde(default)]
+    pub callback_url: Option<String>,
+}
+
+/// The limits the new requests are checked against. They are reported to the clients
+/// as they are, so the requests can be checked before they are sent.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CreationLimits {
+    /// The API keys of the trusted partners may allow more tokens.
+    pub max_tokens_per_request: u8,
+    /// How long (in milliseconds) the created request awaits the payment.
+    pub max_validity: i64,
+    /// The longest metadata of the request in bytes, the metadata is refused if it is 0.
+    pub max_metadata_length: usize,
+    /// How many requests awaiting the payment a single account may be the target of.
+    pub max_active_per_target: u32,
+    /// The tokens the requests may be created for, any token if the list is empty.
+    pub allowed_tokens: Vec<TokenId>,
+}
+
+impl CreationLimits {
+    pub fn is_token_allowed(&self, token: TokenId) -> bool {
+        self.allowed_tokens.is_empty() || self.allowed_tokens.contains(&token)
+    }
+}
+
+/// `FullExit` priority operation prepared for the target of the request.
+///
+/// The operation is only applied if it is sent by the owner of the account,
+/// so it can not be sent by the server and is handed over to the operators.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PreparedFullExit {
+    /// Address which has to send the transaction.
+    pub owner: Address,
+    pub account_id: AccountId,
+    pub token: TokenId,
+    pub token_address: Address,
+    /// Address of the zkSync contract the transaction has to be sent to.
+    pub contract: Address,
+    /// Calldata of the `requestFullExit` call.
+    #[serde(with = "ZeroPrefixHexSerde")]
+    pub calldata: Vec<u8>,
+}
+
+/// The `ForcedExit` transaction sent to withdraw one of the tokens of the request.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitFulfillment {
+    pub request_id: ForcedExitRequestId,
+    /// The position of the token in the request.
+    pub position: u32,
+    pub token: TokenId,
+    pub tx_hash: TxHash,
+    /// The backfilled fulfillments have the time the request was matched with the payment.
+    pub created_at: DateTime<Utc>,
+    /// The id the target was resolved to when the transaction was signed, unknown
+    /// for the fulfillments recorded before the ids were.
+    #[serde(default)]
+    pub target_account_id: Option<AccountId>,
+    /// The time the mempool has accepted the transaction, unset while it is being submitted.
+    #[serde(default)]
+    pub submitted_at: Option<DateTime<Utc>>,
+}
+
+/// The batch recorded before its submission to the mempool, which has not been confirmed
+/// as submitted, e.g. since the server has crashed in between.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterruptedSubmission {
+    pub request_id: ForcedExitRequestId,
+    /// The transactions for the tokens of the request, in the same order.
+    pub tx_hashes: Vec<TxHash>,
+    /// Whether the mempool has accepted the transactions, i.e. they are in the mempool
+    /// or have been executed already.
+    pub reached_mempool: bool,
+}
+
+/// The request, the hashes of which differ between the legacy `fulfilled_by` column
+/// and the fulfillments, e.g. the one sent by the server preceding the fulfillments.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitFulfillmentMismatch {
+    pub request_id: ForcedExitRequestId,
+    pub legacy: Option<Vec<TxHash>>,
+    pub fulfillments: Option<Vec<TxHash>>,
+}
+
+/// The request, the L2 `ForcedExit` transactions of which have failed too many times,
+/// so it has to be fulfilled by the `FullExit` priority operations sent on L1.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitRequestEscalation {
+    pub request_id: ForcedExitRequestId,
+    pub full_exits: Vec<PreparedFullExit>,
+    pub created_at: DateTime<Utc>,
+    /// Hash of the L1 transaction with the `FullExit` operations, as reported by the operator.
+    pub l1_tx_hash: Option<H256>,
+    pub finalized_at: Option<DateTime<Utc>>,
+}
+
+impl ForcedExitRequestEscalation {
+    /// Whether the request is still awaiting for the `FullExit` operations to be sent.
+    pub fn is_awaiting(&self) -> bool {
+        self.finalized_at.is_none()
+    }
+}
+
+pub type ForcedExitRequestsApiKeyId = i64;
+
+/// Key of the trusted partner, which overrides the limits of the forced exit requests.
+///
+/// Only the hash of the key is stored, the key itself is shown once upon creation.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitRequestsApiKey {
+    pub id: ForcedExitRequestsApiKeyId,
+    pub label: String,
+    /// Overrides the maximum number of tokens per request, if set.
+    pub max_tokens_per_request: Option<u8>,
+    /// Overrides the maximum number of requests created per hour, if set.
+    pub max_requests_per_hour: Option<u32>,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ForcedExitRequestsApiKey {
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SaveForcedExitRequestsApiKeyQuery {
+    pub label: String,
+    pub key_hash: H256,
+    pub max_tokens_per_request: Option<u8>,
+    pub max_requests_per_hour: Option<u32>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FundsReceivedEvent {
+    pub amount: BigUint,
+    /// The public id of the request supplied by the payer in the calldata, if any.
+    pub request_id: Option<ForcedExitRequestId>,
+    pub block_number: u64,
+    pub eth_tx_hash: Option<H256>,
+    /// Sender of the payment transaction, it is not a part of the event
+    /// and has to be loaded separately.
+    pub payer: Option<Address>,
+    /// The contract which has emitted the event, `None` for the payments
+    /// which have not been observed on L1 directly.
+    pub recipient: Option<Address>,
+}
+
+/// The blocks within which the payments to the address are accepted.
+/// Upon the rotation of the payment address the old one is retired at
+/// some block, while the new one is already accepted for a while.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentAddressWindow {
+    pub address: Address,
+    /// The first block (inclusive) the payments to the address are accepted at.
+    pub first_block: u64,
+    /// The last block (inclusive) the payments to the address are accepted at,
+    /// `None` if the retirement of the address is not scheduled yet.
+    pub last_block: Option<u64>,
+}
+
+impl PaymentAddressWindow {
+    pub fn contains(&self, block: u64) -> bool {
+        block >= self.first_block && self.last_block.map_or(true, |last| block <= last)
+    }
+}
+
+/// How often the scheduled maintenance window repeats.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum MaintenanceRecurrence {
+    Daily,
+    Weekly,
+}
+
+impl MaintenanceRecurrence {
+    pub fn period(&self) -> chrono::Duration {
+        match self {
+            Self::Daily => chrono::Duration::days(1),
+            Self::Weekly => chrono::Duration::weeks(1),
+        }
+    }
+}
+
+impl FromStr for MaintenanceRecurrence {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Ok(match string {
+            "daily" => Self::Daily,
+            "weekly" => Self::Weekly,
+            another => return Err(another.to_owned()),
+        })
+    }
+}
+
+/// The scheduled maintenance, e.g. a migration of the database, during which
+/// no transactions are sent for the requests.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct MaintenanceWindow {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub reason: String,
+    /// The recurring windows repeat the first one every period, the window
+    /// has to be shorter than the period.
+    pub recurrence: Option<MaintenanceRecurrence>,
+}
+
+/// The maintenance in progress, the requests are not fulfilled until it is over.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitMaintenance {
+    pub until: DateTime<Utc>,
+    pub reason: String,
+}
+
+/// The way the payment has reached the watcher.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum PaymentSource {
+    /// The event emitted by the forced exit contract on L1.
+    L1Event,
+    /// The payment injected manually by the operators.
+    Admin,
+}
+
+impl PaymentSource {
+    pub const ALL: [PaymentSource; 2] = [Self::L1Event, Self::Admin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::L1Event => "l1_event",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+// The payments recorded before the sources were introduced are all the contract events
+impl Default for PaymentSource {
+    fn default() -> Self {
+        Self::L1Event
+    }
+}
+
+impl fmt::Display for PaymentSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PaymentSource {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Ok(match string {
+            "l1_event" => Self::L1Event,
+            "admin" => Self::Admin,
+            another => return Err(another.to_owned()),
+        })
+    }
+}
+
+/// The optional part of the component, each one is enabled by its own option of the config.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum ForcedExitFeature {
+    /// The requests failing on L2 are escalated to the priority operations on L1.
+    L1Escalation,
+    /// The status transitions of the requests are posted to the webhook.
+    Webhooks,
+    /// The payments exceeding the amount to be paid are matched within the tolerance.
+    Overpayments,
+    /// The payments for the expired, cancelled or differently priced requests are returned.
+    Refunds,
+    /// The requests may be created with the URL they are posted to once fulfilled.
+    Callbacks,
+}
+
+impl ForcedExitFeature {
+    pub const ALL: [ForcedExitFeature; 5] = [
+        Self::L1Escalation,
+        Self::Webhooks,
+        Self::Overpayments,
+        Self::Refunds,
+        Self::Callbacks,
+    ];
+}
+
+/// Whether the payments of the source are processed, as set by the operators at runtime.
+/// The sources without the state set follow the config.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentSourceState {
+    pub source: PaymentSource,
+    pub enabled: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Payment to the forced exit contract in the order it was processed by the watcher.
+///
+/// The recorded payments allow to replay the exact sequence of the matching decisions.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitPayment {
+    #[serde(with = "BigUintSerdeAsRadix10Str")]
+    pub amount: BigUint,
+    /// The id of the request supplied by the payer in the calldata, if any.
+    pub request_id: Option<ForcedExitRequestId>,
+    pub block_number: u64,
+    pub eth_tx_hash: Option<H256>,
+    pub payer: Option<Address>,
+    /// The time the payment is considered to be submitted at.
+    pub received_at: DateTime<Utc>,
+    #[serde(default)]
+    pub source: PaymentSource,
+}
+
+impl ForcedExitPayment {
+    pub fn new(
+        event: FundsReceivedEvent,
+        source: PaymentSource,
+        received_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            amount: event.amount,
+            request_id: event.request_id,
+            block_number: event.block_number,
+            eth_tx_hash: event.eth_tx_hash,
+            payer: event.payer,
+            received_at,
+            source,
+        }
+    }
+
+    pub fn event(&self) -> FundsReceivedEvent {
+        FundsReceivedEvent {
+            amount: self.amount.clone(),
+            request_id: self.request_id,
+            block_number: self.block_number,
+            eth_tx_hash: self.eth_tx_hash,
+            payer: self.payer,
+            recipient: None,
+        }
+    }
+}
+
+pub type InjectedForcedExitPaymentId = i64;
+
+/// Payment injected by the operators, e.g. the one that was sent to the contract
+/// with a wrong amount. It waits to be picked up by the watcher and is processed
+/// as if the payment was submitted at the time of the injection.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct InjectedForcedExitPayment {
+    pub id: InjectedForcedExitPaymentId,
+    #[serde(with = "BigUintSerdeAsRadix10Str")]
+    pub amount: BigUint,
+    pub request_id: Option<ForcedExitRequestId>,
+    pub block_number: u64,
+    pub eth_tx_hash: Option<H256>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl InjectedForcedExitPayment {
+    pub fn event(&self) -> FundsReceivedEvent {
+        FundsReceivedEvent {
+            amount: self.amount.clone(),
+            request_id: self.request_id,
+            block_number: self.block_number,
+            eth_tx_hash: self.eth_tx_hash,
+            payer: None,
+            recipient: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SaveInjectedForcedExitPaymentQuery {
+    pub amount: BigUint,
+    pub request_id: Option<ForcedExitRequestId>,
+    pub block_number: u64,
+    pub eth_tx_hash: Option<H256>,
+    pub created_at: DateTime<Utc>,
+}
+
+pub type ForcedExitRetryId = i64;
+
+/// Retry of the paid request requested by the operators once its fulfillment has failed.
+/// It waits to be picked up by the watcher, which sends the transactions of the request
+/// once again and records the outcome.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitRetry {
+    pub id: ForcedExitRetryId,
+    pub request_id: ForcedExitRequestId,
+    /// The request is checked as of the time it was paid, so the expired one is sent too.
+    pub force: bool,
+    pub created_at: DateTime<Utc>,
+    /// Not set until the watcher has processed the retry.
+    pub processed_at: Option<DateTime<Utc>>,
+    pub tx_hashes: Vec<TxHash>,
+    pub error: Option<String>,
+}
+
+impl ForcedExitRetry {
+    pub fn is_processed(&self) -> bool {
+        self.processed_at.is_some()
+    }
+}
+
+/// The reason the payment is set aside without being matched with any request.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum UnmatchedPaymentReason {
+    /// The amount is larger than any request could cost, see `max_payment_amount` in the config.
+    AmountOutOfRange,
+    /// The payment was sent to the address outside of its validity window, see
+    /// `payment_addresses` in the config.
+    AddressRetired,
+}
+
+impl UnmatchedPaymentReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AmountOutOfRange => "amount_out_of_range",
+            Self::AddressRetired => "address_retired",
+        }
+    }
+}
+
+impl fmt::Display for UnmatchedPaymentReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for UnmatchedPaymentReason {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Ok(match string {
+            "amount_out_of_range" => Self::AmountOutOfRange,
+            "address_retired" => Self::AddressRetired,
+            another => return Err(another.to_owned()),
+        })
+    }
+}
+
+pub type UnmatchedForcedExitPaymentId = i64;
+
+/// Payment which has not reached the matching at all.
+///
+/// The amount is kept as the decimal string, so any amount the payer could send
+/// is recorded as is.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct UnmatchedForcedExitPayment {
+    pub id: UnmatchedForcedExitPaymentId,
+    pub amount: String,
+    pub request_id: Option<ForcedExitRequestId>,
+    pub block_number: u64,
+    pub eth_tx_hash: Option<H256>,
+    pub source: PaymentSource,
+    pub reason: UnmatchedPaymentReason,
+    pub received_at: DateTime<Utc>,
+}
+
+/// The L1 transaction the payer has declared to pay for the request with, before it was
+/// even mined. Such a payment is matched by its hash rather than by its amount.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpectedForcedExitPayment {
+    pub request_id: ForcedExitRequestId,
+    pub eth_tx_hash: H256,
+    pub registered_at: DateTime<Utc>,
+    /// The amount paid by the transaction, set if it differs from the one the request
+    /// asks for. Such a payment is not accepted.
+    #[serde(default)]
+    pub mismatched_amount: Option<String>,
+}
+
+/// Why the request found for the payment is not paid for by it.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum PaymentRejection {
+    /// The request has already been fulfilled.
+    Fulfilled,
+    /// The request has been cancelled and is only paid for again once the operators extend it.
+    Cancelled,
+    /// The payment was submitted after the end of the validity period of the request.
+    Expired,
+}
+
+/// A single decision made while the payment is matched with a request, along with
+/// the inputs it was made on. The steps are reported in the order they are made,
+/// see the `explain_payment` admin endpoint.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "step", rename_all = "camelCase")]
+pub enum PaymentMatchStep {
+    /// The registration of the payment transaction made in advance is looked up.
+    #[serde(rename_all = "camelCase")]
+    ExpectedPaymentLookup {
+        eth_tx_hash: H256,
+        request_id: Option<ForcedExitRequestId>,
+    },
+    /// The amount paid by the registered transaction is compared with the one of the request.
+    #[serde(rename_all = "camelCase")]
+    ExpectedPaymentAmount {
+        request_id: ForcedExitRequestId,
+        #[serde(with = "BigUintSerdeAsRadix10Str")]
+        paid: BigUint,
+        pay_exactly: String,
+        matches: bool,
+    },
+    /// The payer has supplied the id in the calldata, the amount is not decoded.
+    #[serde(rename_all = "camelCase")]
+    ExplicitId { public_id: ForcedExitRequestId },
+    /// The ids the amount decodes to under any of the schemes, in the order they are tried.
+    #[serde(rename_all = "camelCase")]
+    CandidateIds {
+        digits_in_id: u32,
+        ids: Vec<ForcedExitRequestId>,
+    },
+    /// The request with the public id is looked up.
+    #[serde(rename_all = "camelCase")]
+    RequestLookup {
+        public_id: ForcedExitRequestId,
+        request_id: Option<ForcedExitRequestId>,
+    },
+    /// The terms the payments for the request are matched by. They are either stored
+    /// with the request or derived from the config, none if the legacy amounts are disabled.
+    #[serde(rename_all = "camelCase")]
+    PaymentTerms {
+        request_id: ForcedExitRequestId,
+        stored: bool,
+        legacy_amount_ids_enabled: bool,
+        terms: Option<ForcedExitPaymentTerms>,
+    },
+    /// The amount is decoded once again by the terms of the request, it has to yield
+    /// the public id of the request.
+    #[serde(rename_all = "camelCase")]
+    AmountDecoding {
+        request_id: ForcedExitRequestId,
+        public_id: ForcedExitRequestId,
+        decoded_id: Option<ForcedExitRequestId>,
+        error: Option<AmountIdError>,
+    },
+    /// The price of the request overlaps with the id, it can not be matched by the amount.
+    #[serde(rename_all = "camelCase")]
+    MisalignedPrice {
+        request_id: ForcedExitRequestId,
+        #[serde(with = "BigUintSerdeAsRadix10Str")]
+        price: BigUint,
+        amount_id_digits: u8,
+    },
+    /// The request is checked to still be payable at the submission time of the payment.
+    #[serde(rename_all = "camelCase")]
+    RequestPayability {
+        request_id: ForcedExitRequestId,
+        valid_until: DateTime<Utc>,
+        submission_time: DateTime<Utc>,
+        fulfilled_at: Option<DateTime<Utc>>,
+        cancellation: Option<ForcedExitCancellationKind>,
+        rejection: Option<PaymentRejection>,
+    },
+    /// The amount left after the id is extracted (or the whole amount paid with the explicit id)
+    /// is compared with the price of the request.
+    #[serde(rename_all = "camelCase")]
+    PriceComparison {
+        request_id: ForcedExitRequestId,
+        #[serde(with = "BigUintSerdeAsRadix10Str")]
+        paid: BigUint,
+        #[serde(with = "BigUintSerdeAsRadix10Str")]
+        price: BigUint,
+        explicit_id: bool,
+        matches: bool,
+    },
+    /// The paid amount is compared with the one stored with the request.
+    #[serde(rename_all = "camelCase")]
+    PayExactlyComparison {
+        request_id: ForcedExitRequestId,
+        #[serde(with = "BigUintSerdeAsRadix10Str")]
+        paid: BigUint,
+        pay_exactly: String,
+        matches: bool,
+    },
+    /// The payable request with the closest exact amount not exceeding the paid one is looked up.
+    #[serde(rename_all = "camelCase")]
+    OverpaymentLookup {
+        request_id: Option<ForcedExitRequestId>,
+    },
+    /// The excess of the payment is compared with the tolerance of the request.
+    #[serde(rename_all = "camelCase")]
+    OverpaymentComparison {
+        request_id: ForcedExitRequestId,
+        #[serde(with = "BigUintSerdeAsRadix10Str")]
+        paid: BigUint,
+        pay_exactly: String,
+        #[serde(with = "BigUintSerdeAsRadix10Str")]
+        tolerance: BigUint,
+        accepted: bool,
+    },
+    /// Another payable request has the exact amount within the tolerance as well,
+    /// so the payment may be the overpayment of either of them and is not matched.
+    #[serde(rename_all = "camelCase")]
+    AmbiguousOverpayment {
+        request_id: ForcedExitRequestId,
+        other_request_id: ForcedExitRequestId,
+    },
+    /// The payment is matched with the request.
+    #[serde(rename_all = "camelCase")]
+    Matched {
+        request_id: ForcedExitRequestId,
+        match_scheme: PaymentMatchScheme,
+    },
+    /// No request is paid for by the payment.
+    Unmatched,
+}
+
+/// The way the payment would be matched with a request, reported to the operators
+/// without anything being written.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitPaymentExplanation {
+    #[serde(with = "BigUintSerdeAsRadix10Str")]
+    pub amount: BigUint,
+    pub submission_time: DateTime<Utc>,
+    pub eth_tx_hash: Option<H256>,
+    pub request_id: Option<ForcedExitRequestId>,
+    pub match_scheme: Option<PaymentMatchScheme>,
+    pub steps: Vec<PaymentMatchStep>,
+    /// The money config in effect at the submission time, `None` if it was not recorded yet.
+    #[serde(default)]
+    pub config: Option<ForcedExitConfigSnapshot>,
+}
+
+/// The last payment for the request which could not be processed in any of the attempts.
+/// Recorded for the operators, the payment is not processed again by itself.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitProcessingFailure {
+    pub request_id: ForcedExitRequestId,
+    pub attempts: u32,
+    pub error: String,
+    pub failed_at: DateTime<Utc>,
+    /// Set if the last attempt has failed to submit the transactions.
+    #[serde(default)]
+    pub submission_error: Option<SubmissionError>,
+}
+
+/// The maximum length of the message of the submission error shown to the operators.
+pub const MAX_SUBMISSION_ERROR_MESSAGE_LENGTH: usize = 512;
+
+/// The layer the submission of the transactions has failed at.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SubmissionErrorKind {
+    /// The transactions have not reached the server or its response has not been read,
+    /// e.g. the connection has failed or the mempool has stopped.
+    Transport,
+    /// The server has responded with the error status.
+    Http,
+    /// The server has accepted the request, but has rejected the transactions.
+    Rejected,
+}
+
+/// The failure to submit the transactions, keeping the details of the layer it has happened at.
+#[derive(Debug, Error, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+#[error("{kind:?} error while submitting the transactions: {message}")]
+pub struct SubmissionError {
+    pub kind: SubmissionErrorKind,
+    pub http_status: Option<u16>,
+    /// The code of the error returned by the API, if any.
+    pub api_code: Option<u32>,
+    pub message: String,
+    /// Whether the same transactions may be accepted if submitted again.
+    pub retryable: bool,
+}
+
+impl SubmissionError {
+    pub fn transport(message: impl Into<String>) -> Self {
+        Self {
+            kind: SubmissionErrorKind::Transport,
+            http_status: None,
+            api_code: None,
+            message: message.into(),
+            retryable: true,
+        }
+    }
+
+    /// The server errors and the rate limiting are only temporary.
+    pub fn http(http_status: u16, api_code: Option<u32>, message: impl Into<String>) -> Self {
+        Self {
+            kind: SubmissionErrorKind::Http,
+            http_status: Some(http_status),
+            api_code,
+            message: message.into(),
+            retryable: http_status >= 500 || http_status == 429,
+        }
+    }
+
+    pub fn rejected(api_code: Option<u32>, message: impl Into<String>, retryable: bool) -> Self {
+        Self {
+            kind: SubmissionErrorKind::Rejected,
+            http_status: None,
+            api_code,
+            message: message.into(),
+            retryable,
+        }
+    }
+
+    /// The error as shown to the operators: the message is cut to
+    /// `MAX_SUBMISSION_ERROR_MESSAGE_LENGTH` characters and the control characters are dropped.
+    pub fn sanitized(&self) -> Self {
+        let message = self
+            .message
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(MAX_SUBMISSION_ERROR_MESSAGE_LENGTH)
+            .collect();
+        Self {
+            message,
+            ..self.clone()
+        }
+    }
+}
+
+/// The version of the rules the requests are priced, matched and fulfilled by.
+/// It must be bumped whenever their behavior changes.
+pub const FORCED_EXIT_PIPELINE_VERSION: u32 = 4;
+
+/// The part of the configuration the pricing and the matching of the requests depend on.
+/// Its hash is recorded along with the pipeline version, so it is known which rules were
+/// applied to the request even after the configuration has changed.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitPipelineConfig {
+    pub price_per_token: i64,
+    pub max_tokens_per_request: u8,
+    pub digits_in_id: u8,
+    #[serde(with = "BigUintSerdeAsRadix10Str")]
+    pub max_payment_amount: BigUint,
+    pub wait_confirmations: u64,
+    pub expected_payment_wait_confirmations: u64,
+    pub active_target_policy: ActiveTargetPolicy,
+    pub active_target_hold_period: u64,
+    pub l1_escalation_enabled: bool,
+    pub l1_escalation_failures_threshold: u32,
+    pub overpayment_tolerance: u64,
+    pub overpayment_tolerance_percent: u8,
+    pub legacy_amount_ids_enabled: bool,
+}
+
+impl ForcedExitPipelineConfig {
+    /// The hex-encoded SHA-256 hash of the serialized configuration.
+    pub fn hash(&self) -> String {
+        let serialized = serde_json::to_vec(self).expect("Failed to serialize the pipeline config");
+        hex::encode(sha256(&serialized).to_vec())
+    }
+}
+
+/// The part of the configuration the amounts paid by the users depend on. Its snapshot is
+/// recorded once it changes, so the values applied at any moment can be told afterwards.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitMoneyConfig {
+    pub price_per_token: i64,
+    pub digits_in_id: u8,
+    /// How long (in milliseconds) the new requests may be paid for.
+    pub validity_period: i64,
+    /// The payment address advertised to the payers.
+    pub payment_address: Address,
+    pub overpayment_tolerance: u64,
+    pub overpayment_tolerance_percent: u8,
+    /// The fee (in wei) deducted from the refunded payments.
+    pub refund_processing_fee: u64,
+    pub legacy_amount_ids_enabled: bool,
+}
+
+/// The money config in effect from the time it was recorded until the next snapshot.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitConfigSnapshot {
+    pub effective_from: DateTime<Utc>,
+    pub config: ForcedExitMoneyConfig,
+}
+
+/// The changes of the configuration the impact of which is estimated before they are made,
+/// see `ForcedExitConfigImpactReport`. The keys left out keep their current values.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ForcedExitConfigCandidate {
+    pub price_per_token: Option<i64>,
+    pub digits_in_id: Option<u8>,
+    pub max_tokens_per_request: Option<u8>,
+    pub max_payment_amount: Option<BigUintSerdeWrapper>,
+    pub max_metadata_length: Option<usize>,
+    pub max_active_requests_per_target: Option<u32>,
+    pub allowed_tokens: Option<Vec<TokenId>>,
+    pub overpayment_tolerance: Option<u64>,
+    pub overpayment_tolerance_percent: Option<u8>,
+    pub expiration_grace_period: Option<u64>,
+    pub legacy_amount_ids_enabled: Option<bool>,
+}
+
+/// The value of the configuration changed by the candidate.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitConfigChange {
+    pub key: String,
+    pub current: serde_json::Value,
+    pub candidate: serde_json::Value,
+}
+
+/// The request awaiting the payment, the exact amount of which is matched with it now,
+/// but would not be with the candidate configuration.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitUnmatchableRequest {
+    pub request_id: ForcedExitRequestId,
+    pub public_id: ForcedExitRequestId,
+    pub pay_exactly: String,
+    /// The steps of the matching with the candidate configuration.
+    pub steps: Vec<PaymentMatchStep>,
+}
+
+/// The request awaiting the payment, which would be refused if created
+/// with the candidate configuration.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitRejectedRequest {
+    pub request_id: ForcedExitRequestId,
+    pub public_id: ForcedExitRequestId,
+    /// The error the creation would be refused with.
+    pub reason: String,
+}
+
+/// The prices of the request for the number of tokens, `None` if the configuration
+/// does not allow that many tokens.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitQuoteChange {
+    pub tokens_count: u8,
+    pub current: Option<BigUintSerdeWrapper>,
+    pub candidate: Option<BigUintSerdeWrapper>,
+}
+
+/// The estimated impact of the configuration candidate on the requests awaiting the payment.
+///
+/// The requests are matched by the same matcher and checked by the same creation rules
+/// as with the current configuration, only the configuration differs. Nothing is applied.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitConfigImpactReport {
+    pub created_at: DateTime<Utc>,
+    /// The keys set by the candidate to other values than the current ones.
+    pub changes: Vec<ForcedExitConfigChange>,
+    pub current_pipeline_config_hash: String,
+    pub candidate_pipeline_config_hash: String,
+    /// The number of the requests awaiting the payment evaluated.
+    pub requests: u32,
+    /// Whether more requests await the payment than have been evaluated.
+    pub truncated: bool,
+    /// The requests the exact amounts of which would still be matched with them.
+    pub still_matched: u32,
+    pub unmatchable: Vec<ForcedExitUnmatchableRequest>,
+    /// The requests which would be refused or priced differently if created now.
+    pub rejected: Vec<ForcedExitRejectedRequest>,
+    /// For every number of tokens either configuration allows.
+    pub quotes: Vec<ForcedExitQuoteChange>,
+    /// The requests which could not be evaluated, e.g. the ones deleted in the meantime.
+    pub evaluation_failures: u32,
+}
+
+/// The stage of the request the version of the pipeline is recorded at.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum ForcedExitPipelineStage {
+    Created,
+    Fulfilled,
+}
+
+impl ForcedExitPipelineStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Fulfilled => "fulfilled",
+        }
+    }
+}
+
+impl fmt::Display for ForcedExitPipelineStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ForcedExitPipelineStage {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Ok(match string {
+            "created" => Self::Created,
+            "fulfilled" => Self::Fulfilled,
+            another => return Err(another.to_owned()),
+        })
+    }
+}
+
+/// The version of the pipeline and the hash of its configuration the request
+/// has been handled with at the stage.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitPipelineVersion {
+    pub request_id: ForcedExitRequestId,
+    pub stage: ForcedExitPipelineStage,
+    pub version: u32,
+    pub config_hash: String,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Status transition of the request the subscribers are notified about.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum ForcedExitRequestEvent {
+    /// The `ForcedExit` transactions have been sent to the mempool.
+    Submitted,
+    /// The `ForcedExit` transactions (or the `FullExit` operations) have been executed.
+    Fulfilled,
+    /// The request has been escalated to the `FullExit` priority operations on L1.
+    Escalated,
+    /// The request will not be fulfilled, its payment is to be refunded.
+    Failed,
+}
+
+impl ForcedExitRequestEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Submitted => "submitted",
+            Self::Fulfilled => "fulfilled",
+            Self::Escalated => "escalated",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+impl fmt::Display for ForcedExitRequestEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ForcedExitRequestEvent {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Ok(match string {
+            "submitted" => Self::Submitted,
+            "fulfilled" => Self::Fulfilled,
+            "escalated" => Self::Escalated,
+            "failed" => Self::Failed,
+            another => return Err(another.to_owned()),
+        })
+    }
+}
+
+pub type ForcedExitRequestDeliveryId = i64;
+
+/// Notification about the status transition of the request.
+///
+/// The notifications are stored in the outbox together with the transitions themselves
+/// and are delivered at least once, so the receivers have to deduplicate them
+/// by the idempotency key.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitRequestDelivery {
+    pub id: ForcedExitRequestDeliveryId,
+    pub request_id: ForcedExitRequestId,
+    /// The number of the notification among the ones of the request, starting from 1.
+    /// The notifications are ordered by it rather than by the time, which may be the same.
+    pub sequence: i64,
+    pub event: ForcedExitRequestEvent,
+    pub created_at: DateTime<Utc>,
+    /// The number of the delivery attempts made so far.
+    pub attempts: u32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    /// The version of the pipeline the transition has been made by,
+    /// unknown for the notifications enqueued before the versions were recorded.
+    #[serde(default)]
+    pub pipeline_version: Option<u32>,
+    /// The URL supplied with the request, set for the callbacks only. The rest of
+    /// the notifications are posted to the webhook of the config.
+    #[serde(default)]
+    pub callback_url: Option<String>,
+    /// The body of the callback, taken when the request was fulfilled.
+    #[serde(default)]
+    pub callback: Option<ForcedExitFulfilledCallback>,
+    /// Set once the callback has run out of the attempts, it is only attempted
+    /// again once redelivered by the operators.
+    #[serde(default)]
+    pub failed_at: Option<DateTime<Utc>>,
+}
+
+impl ForcedExitRequestDelivery {
+    /// The key stays the same for all the attempts to deliver the notification.
+    pub fn idempotency_key(&self) -> String {
+        format!("forced-exit-delivery-{}", self.id)
+    }
+
+    pub fn is_delivered(&self) -> bool {
+        self.delivered_at.is_some()
+    }
+
+    pub fn is_callback(&self) -> bool {
+        self.callback_url.is_some()
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.failed_at.is_some()
+    }
+}
+
+/// Body of the callback posted to the URL supplied with the request once it is fulfilled.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitFulfilledCallback {
+    /// The public id of the request, the one the client refers to it with.
+    pub request_id: ForcedExitRequestId,
+    pub target: Address,
+    pub tokens: Vec<TokenId>,
+    /// Empty for the requests fulfilled on L1.
+    pub tx_hashes: Vec<TxHash>,
+    pub fulfilled_at: DateTime<Utc>,
+}
+
+/// The reason the paid request is not fulfilled with the `ForcedExit` transactions.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ForcedExitBlocker {
+    /// The request has already been fulfilled.
+    Fulfilled,
+    /// The request has been cancelled in the way, which does not allow processing it again.
+    Cancelled,
+    /// The request can not be paid for anymore.
+    Expired,
+    /// The request is fulfilled on L1 by the operators.
+    Escalated,
+    /// The target account can not be forced to exit.
+    NotPossible,
+    /// The target account has sent a transaction after the request was created,
+    /// see `ActiveTargetPolicy` for what happens to such requests.
+    TargetBecameActive,
+}
+
+/// The reason the token of the paid request is not withdrawn, while the rest of its tokens are.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum ForcedExitTokenSkipReason {
+    /// The token contract on L1 is paused or has blacklisted the target, so the withdrawal
+    /// of the token would be stuck on L1 after the `ForcedExit` is executed.
+    L1TransferRestricted,
+    /// The token is an NFT, which `ForcedExit` can not withdraw. The NFTs are refused
+    /// once the request is created, only the requests created before may include them.
+    Nft,
+}
+
+impl ForcedExitTokenSkipReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::L1TransferRestricted => "l1_transfer_restricted",
+            Self::Nft => "nft",
+        }
+    }
+}
+
+impl fmt::Display for ForcedExitTokenSkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ForcedExitTokenSkipReason {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Ok(match string {
+            "l1_transfer_restricted" => Self::L1TransferRestricted,
+            "nft" => Self::Nft,
+            another => return Err(another.to_owned()),
+        })
+    }
+}
+
+/// The token of the request, the `ForcedExit` transaction for which is not sent.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedForcedExit {
+    pub token: TokenId,
+    pub reason: ForcedExitTokenSkipReason,
+}
+
+/// What is known about the account of the `ForcedExit` sender while the service starts.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum ForcedExitSenderState {
+    /// The account is committed, the requests can be fulfilled.
+    Ready,
+    /// The account is not committed yet, but the deposit or the transfer creating it
+    /// awaits in the priority queue or the mempool.
+    CreationPending,
+    /// Nothing is known to create the account, the sender address is likely misconfigured.
+    AddressUnknown,
+}
+
+impl ForcedExitSenderState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ready => "ready",
+            Self::CreationPending => "creation_pending",
+            Self::AddressUnknown => "address_unknown",
+        }
+    }
+}
+
+impl fmt::Display for ForcedExitSenderState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ForcedExitSenderState {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Ok(match string {
+            "ready" => Self::Ready,
+            "creation_pending" => Self::CreationPending,
+            "address_unknown" => Self::AddressUnknown,
+            another => return Err(another.to_owned()),
+        })
+    }
+}
+
+/// The state of the sender account as last observed by the starting service.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitSenderStatus {
+    pub address: Address,
+    pub state: ForcedExitSenderState,
+    /// When the account has entered the state.
+    pub since: DateTime<Utc>,
+    /// The pace the batches are sent at, as last reported by the running service.
+    #[serde(default)]
+    pub pacing: Option<ForcedExitPacingState>,
+}
+
+/// The limits of the pace the batches of the `ForcedExit` transactions are sent at, so the
+/// bursts of the paid requests do not crowd the transactions of the users out of the blocks.
+/// The zero values disable the limits.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitPacing {
+    /// The batches sent within any minute, the unused ones are not accumulated beyond that.
+    pub max_batches_per_minute: u32,
+    /// Once this number of batches is sent, the next one waits for the last of them
+    /// to be committed, i.e. for its block to be sealed.
+    pub batches_per_block: u32,
+}
+
+/// The pacing of the sender at the moment it was reported.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitPacingState {
+    pub pacing: ForcedExitPacing,
+    /// Whether the limits are set by the operators rather than taken from the config.
+    pub overridden: bool,
+    /// The batches which may be sent right away, not reported without the limit per minute.
+    pub available_batches: Option<u32>,
+    /// The batches sent since the block of the last awaited one was sealed.
+    pub batches_in_block: u32,
+    /// The batches waiting for their turn to be sent.
+    pub paced_batches: u32,
+    pub reported_at: DateTime<Utc>,
+}
+
+/// What happens to the paid request if its target sets the signing key before
+/// the `ForcedExit` transactions are sent.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum ActiveTargetPolicy {
+    /// The request fails right away and its payment is to be refunded.
+    Fail,
+    /// The request is held for the grace period, in case the account is reset
+    /// to the state it can be forced to exit from, and fails after that.
+    Hold,
+}
+
+impl ActiveTargetPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Fail => "fail",
+            Self::Hold => "hold",
+        }
+    }
+}
+
+impl Default for ActiveTargetPolicy {
+    fn default() -> Self {
+        Self::Fail
+    }
+}
+
+impl fmt::Display for ActiveTargetPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ActiveTargetPolicy {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Ok(match string {
+            "fail" => Self::Fail,
+            "hold" => Self::Hold,
+            another => return Err(another.to_owned()),
+        })
+    }
+}
+
+/// The paid request, the target of which has become active before it was fulfilled.
+///
+/// The record is kept for the audit: it states the policy applied to the request
+/// and, once the request fails, the payment to be refunded.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitRequestActiveTarget {
+    pub request_id: ForcedExitRequestId,
+    pub policy: ActiveTargetPolicy,
+    /// The nonce of the target when it was detected to be active.
+    pub target_nonce: Nonce,
+    #[serde(with = "BigUintSerdeAsRadix10Str")]
+    pub payment_amount: BigUint,
+    pub payment_tx_hash: Option<H256>,
+    pub match_scheme: PaymentMatchScheme,
+    /// The time the payment was made, the request is fulfilled as of it if the hold succeeds.
+    pub paid_at: DateTime<Utc>,
+    pub detected_at: DateTime<Utc>,
+    /// The request is not fulfilled after this time, set for the held requests only.
+    pub hold_until: Option<DateTime<Utc>>,
+    /// The time the held request was resumed, the target could be forced to exit again.
+    pub resumed_at: Option<DateTime<Utc>>,
+    pub failed_at: Option<DateTime<Utc>>,
+}
+
+impl ForcedExitRequestActiveTarget {
+    /// Whether the request has failed and its payment is to be refunded.
+    pub fn is_failed(&self) -> bool {
+        self.failed_at.is_some()
+    }
+
+    /// Whether the request is held waiting for the target to be reset.
+    pub fn is_held(&self) -> bool {
+        self.hold_until.is_some() && self.resumed_at.is_none() && !self.is_failed()
+    }
+
+    pub fn is_hold_expired(&self, now: DateTime<Utc>) -> bool {
+        self.hold_until
+            .map(|hold_until| hold_until <= now)
+            .unwrap_or(true)
+    }
+}
+
+pub type ForcedExitRefundId = i64;
+
+/// Why the payment is returned to the payer instead of fulfilling the request.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum ForcedExitRefundReason {
+    /// The request was paid for after it had expired.
+    Expired,
+    /// The request had been cancelled before it was paid for.
+    Cancelled,
+    /// The amount paid does not match the one the request asks for.
+    IncorrectAmount,
+    /// The target address of the request has no account to withdraw from.
+    TargetNotFound,
+}
+
+impl ForcedExitRefundReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Expired => "expired",
+            Self::Cancelled => "cancelled",
+            Self::IncorrectAmount => "incorrect_amount",
+            Self::TargetNotFound => "target_not_found",
+        }
+    }
+}
+
+impl fmt::Display for ForcedExitRefundReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ForcedExitRefundReason {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Ok(match string {
+            "expired" => Self::Expired,
+            "cancelled" => Self::Cancelled,
+            "incorrect_amount" => Self::IncorrectAmount,
+            "target_not_found" => Self::TargetNotFound,
+            another => return Err(another.to_owned()),
+        })
+    }
+}
+
+/// The stage of the refund transfer.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum ForcedExitRefundStatus {
+    /// The refund is above the automatic approval threshold and awaits to be approved
+    /// by the operators, nothing is sent until then.
+    AwaitingApproval,
+    /// The transfer has not been sent yet.
+    Pending,
+    /// The transfer has been sent and awaits to be committed.
+    Sent,
+    /// The transfer has been committed, the payment is returned.
+    Completed,
+    /// The transfer has failed, it is sent again until the attempts run out.
+    Failed,
+}
+
+impl ForcedExitRefundStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AwaitingApproval => "awaiting_approval",
+            Self::Pending => "pending",
+            Self::Sent => "sent",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+impl fmt::Display for ForcedExitRefundStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ForcedExitRefundStatus {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Ok(match string {
+            "awaiting_approval" => Self::AwaitingApproval,
+            "pending" => Self::Pending,
+            "sent" => Self::Sent,
+            "completed" => Self::Completed,
+            "failed" => Self::Failed,
+            another => return Err(another.to_owned()),
+        })
+    }
+}
+
+/// The payment returned to the payer as the transfer from the sender account on L2.
+///
+/// A payment is refunded at most once, the refunds are keyed by its L1 transaction.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitRefund {
+    pub id: ForcedExitRefundId,
+    pub request_id: ForcedExitRequestId,
+    pub payment_tx_hash: H256,
+    /// The payer of the refunded payment.
+    pub recipient: Address,
+    /// The amount transferred back, i.e. the payment without the processing fee.
+    #[serde(with = "BigUintSerdeAsRadix10Str")]
+    pub amount: BigUint,
+    #[serde(with = "BigUintSerdeAsRadix10Str")]
+    pub fee: BigUint,
+    pub reason: ForcedExitRefundReason,
+    pub status: ForcedExitRefundStatus,
+    /// The last transfer sent for the refund.
+    pub tx_hash: Option<TxHash>,
+    /// The number of the transfers which have failed.
+    pub attempts: u32,
+    /// The label of the API key of the operator who has approved the refund, only set
+    /// for the refunds above the automatic approval threshold.
+    #[serde(default)]
+    pub approved_by: Option<String>,
+    #[serde(default)]
+    pub approved_at: Option<DateTime<Utc>>,
+    /// The time the last transfer was sent, the refunds sent within the last hour
+    /// count towards the hourly limit.
+    #[serde(default)]
+    pub sent_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
-    pub valid_until: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone)]
-pub struct FundsReceivedEvent {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveForcedExitRefundQuery {
+    pub request_id: ForcedExitRequestId,
+    pub payment_tx_hash: H256,
+    pub recipient: Address,
     pub amount: BigUint,
-    pub block_number: u64,
+    pub fee: BigUint,
+    pub reason: ForcedExitRefundReason,
+    /// Whether the refund awaits the approval of the operators before it is sent.
+    pub requires_approval: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The movement of the funds recorded in the ledger of the request.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum ForcedExitLedgerEntryKind {
+    /// The payment received for the request, the only credit.
+    Payment,
+    /// The amount the request asks to be paid, consumed once its transactions are sent.
+    Price,
+    /// The part of the payment above the price accepted within the overpayment tolerance,
+    /// consumed along with the price.
+    Overpayment,
+    /// The payment returned to the payer without the processing fee.
+    Refund,
+    /// The processing fee withheld from the refunded payment.
+    RefundFee,
+}
+
+impl ForcedExitLedgerEntryKind {
+    pub fn is_credit(&self) -> bool {
+        matches!(self, Self::Payment)
+    }
+}
+
+/// The entry of the ledger of the request.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitLedgerEntry {
+    pub kind: ForcedExitLedgerEntryKind,
+    #[serde(with = "BigUintSerdeAsRadix10Str")]
+    pub amount: BigUint,
+    /// The L1 transaction of the payment credited, or the one of the payment refunded.
+    pub eth_tx_hash: Option<H256>,
+    /// The first transaction sent for the request for the consumed price,
+    /// the last transfer sent for the refund.
+    pub tx_hash: Option<TxHash>,
+    /// The stage of the refund, set for the refund entries only.
+    pub refund_status: Option<ForcedExitRefundStatus>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl ForcedExitLedgerEntry {
+    /// Whether the entry counts towards the balance. The refunds are only debited
+    /// once completed, until then the payment is still held for the request.
+    pub fn is_settled(&self) -> bool {
+        self.refund_status
+            .map_or(true, |status| status == ForcedExitRefundStatus::Completed)
+    }
+}
+
+/// The payments received for the request and what has become of them, derived
+/// from the stored payments, fulfillments and refunds of the request.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitRequestLedger {
+    pub request_id: ForcedExitRequestId,
+    pub status: ForcedExitLifecycleStatus,
+    /// The entries in the order they were recorded.
+    pub entries: Vec<ForcedExitLedgerEntry>,
+    #[serde(with = "BigUintSerdeAsRadix10Str")]
+    pub credited: BigUint,
+    #[serde(with = "BigUintSerdeAsRadix10Str")]
+    pub debited: BigUint,
+    /// The credited amount without the debited one as a decimal string, i.e. the funds
+    /// still held for the request. Negative if more has been debited than received.
+    pub balance: String,
+    /// Whether the balance is as expected: zero for the requests in a terminal state unless
+    /// a refund is pending, not negative for the requests still processed.
+    pub balanced: bool,
+}
+
+impl ForcedExitRequestLedger {
+    /// Builds the ledger of the request out of its payments, fulfillments and refunds.
+    ///
+    /// The price is consumed once the first transaction for the request is recorded, or once
+    /// the request is fulfilled for the escalated ones. The requests matched before the payments
+    /// were recorded are credited with the amount they were paid with.
+    pub fn new(
+        request: &ForcedExitRequest,
+        payments: &[ForcedExitPayment],
+        fulfillments: &[ForcedExitFulfillment],
+        refunds: &[ForcedExitRefund],
+    ) -> Self {
+        let paid_amount = request
+            .paid_amount
+            .as_deref()
+            .and_then(|amount| BigUint::from_str(amount).ok());
+
+        let mut entries: Vec<_> = payments
+            .iter()
+            .map(|payment| ForcedExitLedgerEntry {
+                kind: ForcedExitLedgerEntryKind::Payment,
+                amount: payment.amount.clone(),
+                eth_tx_hash: payment.eth_tx_hash,
+                tx_hash: None,
+                refund_status: None,
+                recorded_at: payment.received_at,
+            })
+            .collect();
+        if let Some(paid_amount) = paid_amount.as_ref().filter(|_| payments.is_empty()) {
+            entries.push(ForcedExitLedgerEntry {
+                kind: ForcedExitLedgerEntryKind::Payment,
+                amount: paid_amount.clone(),
+                eth_tx_hash: None,
+                tx_hash: None,
+                refund_status: None,
+                recorded_at: request.matched_at.unwrap_or(request.created_at),
+            });
+        }
+
+        let first_fulfillment = fulfillments
+            .iter()
+            .min_by_key(|fulfillment| (fulfillment.created_at, fulfillment.position));
+        let consumed_at = first_fulfillment
+            .map(|fulfillment| fulfillment.created_at)
+            .or(request.fulfilled_at);
+        if let Some(consumed_at) = consumed_at {
+            let pay_exactly = BigUint::from_str(&request.pay_exactly)
+                .unwrap_or_else(|_| request.price_in_wei.clone());
+            let consumed = paid_amount.unwrap_or_else(|| pay_exactly.clone());
+            let price = pay_exactly.min(consumed.clone());
+            let overpayment = consumed - &price;
+            let tx_hash = first_fulfillment.map(|fulfillment| fulfillment.tx_hash);
+            entries.push(ForcedExitLedgerEntry {
+                kind: ForcedExitLedgerEntryKind::Price,
+                amount: price,
+                eth_tx_hash: None,
+                tx_hash,
+                refund_status: None,
+                recorded_at: consumed_at,
+            });
+            if !overpayment.is_zero() {
+                entries.push(ForcedExitLedgerEntry {
+                    kind: ForcedExitLedgerEntryKind::Overpayment,
+                    amount: overpayment,
+                    eth_tx_hash: None,
+                    tx_hash,
+                    refund_status: None,
+                    recorded_at: consumed_at,
+                });
+            }
+        }
+
+        for refund in refunds {
+            let entry = |kind, amount: &BigUint| ForcedExitLedgerEntry {
+                kind,
+                amount: amount.clone(),
+                eth_tx_hash: Some(refund.payment_tx_hash),
+                tx_hash: refund.tx_hash,
+                refund_status: Some(refund.status),
+                recorded_at: refund.created_at,
+            };
+            entries.push(entry(ForcedExitLedgerEntryKind::Refund, &refund.amount));
+            if !refund.fee.is_zero() {
+                entries.push(entry(ForcedExitLedgerEntryKind::RefundFee, &refund.fee));
+            }
+        }
+        // The sort is stable, the entries recorded at the same time keep the order above
+        entries.sort_by_key(|entry| entry.recorded_at);
+
+        let mut credited = BigUint::zero();
+        let mut debited = BigUint::zero();
+        for entry in entries.iter().filter(|entry| entry.is_settled()) {
+            if entry.kind.is_credit() {
+                credited += &entry.amount;
+            } else {
+                debited += &entry.amount;
+            }
+        }
+        let held = credited > debited;
+        let balance = if credited >= debited {
+            (&credited - &debited).to_string()
+        } else {
+            format!("-{}", &debited - &credited)
+        };
+
+        let refund_pending = entries.iter().any(|entry| !entry.is_settled());
+        let terminal = matches!(
+            request.status,
+            ForcedExitLifecycleStatus::Fulfilled
+                | ForcedExitLifecycleStatus::Failed
+                | ForcedExitLifecycleStatus::Expired
+                | ForcedExitLifecycleStatus::Cancelled
+        );
+        let balanced = if !terminal {
+            credited >= debited
+        } else {
+            credited == debited || (held && refund_pending)
+        };
+
+        Self {
+            request_id: request.public_id,
+            status: request.status,
+            entries,
+            credited,
+            debited,
+            balance,
+            balanced,
+        }
+    }
+}
+
+pub type ForcedExitRequestNoteId = i64;
+
+/// The annotation left on the request by the operators, e.g. while handling an incident.
+/// The notes are only shown to the operators, the processing never looks at them.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitRequestNote {
+    pub id: ForcedExitRequestNoteId,
+    pub request_id: ForcedExitRequestId,
+    /// The label of the API key the note was added with.
+    pub author: String,
+    pub text: String,
+    /// The requests to follow up on are looked up by the tags.
+    pub tags: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveForcedExitRequestNoteQuery {
+    pub request_id: ForcedExitRequestId,
+    pub author: String,
+    pub text: String,
+    pub tags: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// State of the target account relevant for the `ForcedExit` operations.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitTargetCheck {
+    /// Whether the account has existed for long enough.
+    pub old_enough: bool,
+    /// The committed nonce of the account, `None` if the account does not exist.
+    pub nonce: Option<Nonce>,
+}
+
+/// The `ForcedExit` transaction to be sent for a single token of the request.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedForcedExit {
+    pub token: TokenId,
+    pub nonce: Nonce,
+    #[serde(with = "BigUintSerdeAsRadix10Str")]
+    pub fee: BigUint,
+}
+
+/// The transfer of zero amount from the sender account to itself following the `ForcedExit`
+/// transactions, which pays the fee of the whole batch in the fee token.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedFeePayment {
+    pub token: TokenId,
+    pub nonce: Nonce,
+    #[serde(with = "BigUintSerdeAsRadix10Str")]
+    pub fee: BigUint,
+}
+
+/// The outcome of fulfilling the paid request, evaluated without sending anything.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitPreflight {
+    pub request_id: ForcedExitRequestId,
+    /// The reason no transactions are sent, `None` if the request is fulfilled on L2.
+    pub blocker: Option<ForcedExitBlocker>,
+    /// Not checked if the request is blocked regardless of the target.
+    pub target: Option<ForcedExitTargetCheck>,
+    pub transactions: Vec<PlannedForcedExit>,
+    /// `None` if the transactions are sent without a fee.
+    #[serde(default)]
+    pub fee_payment: Option<PlannedFeePayment>,
+    #[serde(with = "BigUintSerdeAsRadix10Str")]
+    pub total_fee: BigUint,
+    /// The tokens of the request left out of the transactions.
+    #[serde(default)]
+    pub skipped: Vec<SkippedForcedExit>,
+}
+
+impl ForcedExitPreflight {
+    /// The number of the transactions sent for the request, the fee payment included.
+    pub fn batch_size(&self) -> usize {
+        self.transactions.len() + usize::from(self.fee_payment.is_some())
+    }
+}
+
+/// The fees of the `ForcedExit` transactions for a single token.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitTokenFees {
+    pub token: TokenId,
+    pub transactions: u32,
+    #[serde(with = "BigUintSerdeAsRadix10Str")]
+    pub total_fee: BigUint,
+}
+
+/// The number of the requests blocked for the same reason.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitBlockedRequests {
+    pub blocker: ForcedExitBlocker,
+    pub requests: u32,
+}
+
+/// The estimated outcome of fulfilling the paid requests, which have not been sent yet.
+///
+/// The report is built from the preflights of the requests (see `ForcedExitPreflight`),
+/// so it states what would happen if the requests were processed at the time it was created.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitBacklogReport {
+    pub created_at: DateTime<Utc>,
+    /// The number of the requests in the backlog.
+    pub requests: u32,
+    /// The requests which would be fulfilled with the `ForcedExit` transactions.
+    pub fulfilled: u32,
+    pub transactions: u32,
+    /// Sorted by the token id.
+    pub fees: Vec<ForcedExitTokenFees>,
+    /// The fulfilled requests which would withdraw nothing, since the target
+    /// has zero balances of all their tokens.
+    pub nothing_to_withdraw: u32,
+    /// The requests which would not be fulfilled on L2.
+    pub blocked: Vec<ForcedExitBlockedRequests>,
+    /// The requests which could not be evaluated, e.g. the ones deleted in the meantime.
+    pub evaluation_failures: u32,
+    /// The throughput the estimation of the time to fulfill the backlog is based on.
+    pub fulfilled_last_hour: u32,
+    /// `None` if no requests have been fulfilled during the last hour.
+    pub eta_secs: Option<u64>,
+}
+
+impl ForcedExitBacklogReport {
+    pub fn new(created_at: DateTime<Utc>, fulfilled_last_hour: u32) -> Self {
+        Self {
+            created_at,
+            requests: 0,
+            fulfilled: 0,
+            transactions: 0,
+            fees: Vec::new(),
+            nothing_to_withdraw: 0,
+            blocked: Vec::new(),
+            evaluation_failures: 0,
+            fulfilled_last_hour,
+            eta_secs: None,
+        }
+    }
+
+    pub fn add_failure(&mut self) {
+        self.requests += 1;
+        self.evaluation_failures += 1;
+    }
+}
+
+/// The invariant relating the status of the request to the records backing it.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum ForcedExitInvariant {
+    /// The request fulfilled on L2 has a fulfillment for each of its tokens.
+    FulfilledWithTransactions,
+    /// The request matched with a payment has the payment recorded.
+    PaidWithPayment,
+    /// The held request which has failed has its subscribers notified about that.
+    FailedWithEvent,
+    /// The refunded payment has been received by the contract.
+    RefundedPayment,
+    /// No more is paid out than has been received: the refund does not exceed the payment,
+    /// the payment of the fulfilled request is not refunded, the request is not underpaid.
+    Balance,
+}
+
+impl ForcedExitInvariant {
+    pub const ALL: [Self; 5] = [
+        Self::FulfilledWithTransactions,
+        Self::PaidWithPayment,
+        Self::FailedWithEvent,
+        Self::RefundedPayment,
+        Self::Balance,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::FulfilledWithTransactions => "fulfilled_with_transactions",
+            Self::PaidWithPayment => "paid_with_payment",
+            Self::FailedWithEvent => "failed_with_event",
+            Self::RefundedPayment => "refunded_payment",
+            Self::Balance => "balance",
+        }
+    }
+}
+
+impl fmt::Display for ForcedExitInvariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The refund of the payment for the request along with the payment itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForcedExitRefundEvidence {
+    pub refund_id: ForcedExitRefundId,
+    pub payment_tx_hash: H256,
+    pub amount: BigUint,
+    pub fee: BigUint,
+    pub status: ForcedExitRefundStatus,
+    /// Whether the payment is known either from the payment log, or as the unmatched
+    /// or the matched one.
+    pub payment_recorded: bool,
+    /// The amount of the payment, if it is in the payment log.
+    pub payment_amount: Option<BigUint>,
+}
+
+/// The request along with the records its status is checked against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForcedExitRequestEvidence {
+    pub request: ForcedExitRequest,
+    /// The tokens of the fulfillments of the request ordered by their positions.
+    pub fulfilled_tokens: Vec<TokenId>,
+    /// Whether the request is fulfilled with the `FullExit` operations on L1 instead.
+    pub escalated: bool,
+    /// The time the request held for the active target has failed.
+    pub failed_at: Option<DateTime<Utc>>,
+    /// The notifications about the transitions of the request in their order.
+    pub events: Vec<ForcedExitRequestEvent>,
+    /// The L1 transactions of the payments the request has been matched with.
+    pub matched_payments: Vec<H256>,
+    pub refunds: Vec<ForcedExitRefundEvidence>,
+}
+
+/// The request, the status of which is not backed by its records.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitInvariantViolation {
+    pub request_id: ForcedExitRequestId,
+    pub invariant: ForcedExitInvariant,
+    pub details: String,
+}
+
+/// The outcome of checking the requests changed recently against the invariants.
+///
+/// The violations are only reported, the records are left for the operators to fix,
+/// since it is not known which of them are wrong.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitConsistencyReport {
+    pub created_at: DateTime<Utc>,
+    /// The requests changed since this time have been checked.
+    pub since: DateTime<Utc>,
+    pub checked_requests: u32,
+    pub violations: Vec<ForcedExitInvariantViolation>,
+}
+
+impl ForcedExitConsistencyReport {
+    /// The number of the violations of the invariant.
+    pub fn count(&self, invariant: ForcedExitInvariant) -> usize {
+        self.violations
+            .iter()
+            .filter(|violation| violation.invariant == invariant)
+            .count()
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -49,30 +2283,118 @@ pub struct ForcedExitEligibilityResponse {
     pub eligible: bool,
 }
 
-impl TryFrom<Log> for FundsReceivedEvent {
-    type Error = FundsReceivedEventParseError;
+/// The server instance which holds the lock allowing it to process the requests,
+/// the rest of the instances only serve the API.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForcedExitSingletonHolder {
+    /// The name of the instance, i.e. its host and process id.
+    pub instance: String,
+    /// The id of the database session the lock is held by.
+    pub session_pid: i32,
+}
 
-    fn try_from(event: Log) -> Result<FundsReceivedEvent, FundsReceivedEventParseError> {
-        let mut dec_ev = decode(
-            &[
+/// Events emitted by the forced exit contract upon receiving the funds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FundsReceivedEventKind {
+    /// `FundsReceived(uint256 _amount)`
+    FundsReceived,
+    /// `FundsReceivedWithRequestId(uint256 _amount, uint256 _requestId)`
+    FundsReceivedWithRequestId,
+}
+
+impl FundsReceivedEventKind {
+    pub const ALL: [Self; 2] = [Self::FundsReceived, Self::FundsReceivedWithRequestId];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::FundsReceived => "FundsReceived",
+            Self::FundsReceivedWithRequestId => "FundsReceivedWithRequestId",
+        }
+    }
+
+    fn params(self) -> Vec<ParamType> {
+        match self {
+            Self::FundsReceived => vec![
                 ParamType::Uint(256), // amount
             ],
-            &event.data.0,
-        )?;
+            Self::FundsReceivedWithRequestId => vec![
+                ParamType::Uint(256), // amount
+                ParamType::Uint(256), // request id
+            ],
+        }
+    }
+
+    /// Hash of the event signature, i.e. the first topic of the emitted log.
+    pub fn topic(self) -> H256 {
+        long_signature(self.name(), &self.params())
+    }
+
+    pub fn from_topic(topic: &H256) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|kind| &kind.topic() == topic)
+    }
+}
+
+impl FundsReceivedEvent {
+    /// Decodes the log, which is known to be emitted as an event of the given kind.
+    pub fn from_log(
+        kind: FundsReceivedEventKind,
+        event: Log,
+    ) -> Result<FundsReceivedEvent, FundsReceivedEventParseError> {
+        let mut dec_ev = decode(&kind.params(), &event.data.0)?;
 
         let amount = dec_ev.remove(0).into_uint().unwrap();
+        let request_id = match kind {
+            FundsReceivedEventKind::FundsReceived => None,
+            FundsReceivedEventKind::FundsReceivedWithRequestId => {
+                let request_id = dec_ev.remove(0).into_uint().unwrap();
+                // The payer may put anything into the calldata. An id that
+                // does not fit into the id type could not belong to any request,
+                // so we fall back to matching by amount in this case
+                if request_id <= U256::from(i64::MAX as u64) {
+                    Some(request_id.as_u64() as ForcedExitRequestId)
+                } else {
+                    None
+                }
+            }
+        };
         let block_number = event
             .block_number
             .ok_or(FundsReceivedEventParseError::UnfinalizedBlockAccess)?
             .as_u64();
 
+        // The amount may not fit into `u128`, the conversion would panic in that case
+        let mut amount_bytes = [0u8; 32];
+        amount.to_big_endian(&mut amount_bytes);
+
         Ok(FundsReceivedEvent {
-            amount: BigUint::from(amount.as_u128()),
+            amount: BigUint::from_bytes_be(&amount_bytes),
+            request_id,
             block_number,
+            eth_tx_hash: event.transaction_hash,
+            payer: None,
+            recipient: Some(event.address),
         })
     }
 }
 
+impl TryFrom<Log> for FundsReceivedEvent {
+    type Error = FundsReceivedEventParseError;
+
+    fn try_from(event: Log) -> Result<FundsReceivedEvent, FundsReceivedEventParseError> {
+        let kind = event
+            .topics
+            .first()
+            .and_then(FundsReceivedEventKind::from_topic)
+            .unwrap_or(FundsReceivedEventKind::FundsReceived);
+
+        FundsReceivedEvent::from_log(kind, event)
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum FundsReceivedEventParseError {
     #[error("Cannot decode event data due to ETH abi error: {0}")]
@@ -80,3 +2402,450 @@ pub enum FundsReceivedEventParseError {
     #[error("Trying to access pending block")]
     UnfinalizedBlockAccess,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use ethabi::{encode, Token};
+    use web3::types::Bytes;
+
+    fn funds_received_log(topic: H256, data: Vec<Token>) -> Log {
+        Log {
+            address: Address::zero(),
+            topics: vec![topic],
+            data: Bytes(encode(&data)),
+            block_hash: None,
+            block_number: Some(12.into()),
+            transaction_hash: None,
+            transaction_index: None,
+            log_index: None,
+            transaction_log_index: None,
+            log_type: None,
+            removed: Some(false),
+        }
+    }
+
+    #[test]
+    fn parse_funds_received_event() {
+        let topic = long_signature("FundsReceived", &[ParamType::Uint(256)]);
+        assert_eq!(topic, FundsReceivedEventKind::FundsReceived.topic());
+        let log = funds_received_log(topic, vec![Token::Uint(U256::from(1212))]);
+
+        let event = FundsReceivedEvent::try_from(log).unwrap();
+        assert_eq!(event.amount, BigUint::from(1212u32));
+        assert_eq!(event.request_id, None);
+        assert_eq!(event.block_number, 12);
+    }
+
+    #[test]
+    fn parse_funds_received_with_request_id_event() {
+        let topic = long_signature(
+            "FundsReceivedWithRequestId",
+            &[ParamType::Uint(256), ParamType::Uint(256)],
+        );
+        assert_eq!(
+            topic,
+            FundsReceivedEventKind::FundsReceivedWithRequestId.topic()
+        );
+
+        let log = funds_received_log(
+            topic,
+            vec![Token::Uint(U256::from(1212)), Token::Uint(U256::from(34))],
+        );
+        let event = FundsReceivedEvent::try_from(log).unwrap();
+        assert_eq!(event.amount, BigUint::from(1212u32));
+        assert_eq!(event.request_id, Some(34));
+
+        // The id which can not belong to any request is ignored
+        let log = funds_received_log(
+            topic,
+            vec![Token::Uint(U256::from(1212)), Token::Uint(U256::MAX)],
+        );
+        let event = FundsReceivedEvent::try_from(log).unwrap();
+        assert_eq!(event.request_id, None);
+    }
+
+    #[test]
+    fn id_space() {
+        assert_eq!(id_space_size(3), BigUint::from(1000u32));
+        assert_eq!(id_space_size(0), BigUint::from(1u32));
+    }
+
+    #[test]
+    fn luhn_check_digit() {
+        assert_eq!(check_digit(7_992_739_871), 3);
+        assert_eq!(check_digit(7), 5);
+        assert_eq!(check_digit(0), 0);
+        // The leading zeros do not change the check digit
+        assert_eq!(check_digit(12), 5);
+
+        // Any single mistyped digit changes the check digit
+        let id = 123_456_789;
+        for position in 0..9 {
+            let unit = 10_i64.pow(position);
+            let digit = id / unit % 10;
+            for mistyped in (0..10).filter(|mistyped| *mistyped != digit) {
+                let another = id + (mistyped - digit) * unit;
+                assert_ne!(check_digit(another), check_digit(id), "{}", another);
+            }
+        }
+    }
+
+    #[test]
+    fn pay_exactly_amount() {
+        assert_eq!(pay_exactly(&BigUint::from(20000u32), 7), "20075");
+        assert_eq!(
+            pay_exactly(&BigUint::from(2_000_000_000_000_000u64), 123456789),
+            "2000001234567897"
+        );
+        assert_eq!(legacy_pay_exactly(&BigUint::from(2000u32), 7), "2007");
+
+        // The ids spread over the id space, checked against the amount composed
+        // of the digits of the price, the zero-padded id and its check digit
+        for digits_in_id in [1u8, 3, 9, 15] {
+            let id_space = 10_i64.pow(digits_in_id.into());
+            // Aligned to any of the id spaces
+            let price = BigUint::from(2_000_000_000_000_000_000u128);
+            let price_digits = price.to_string();
+            let price_digits = &price_digits[..price_digits.len() - digits_in_id as usize - 1];
+
+            for step in 0..100_i64 {
+                let id = step.wrapping_mul(7_919_111).rem_euclid(id_space);
+                let expected = format!(
+                    "{}{:0width$}{}",
+                    price_digits,
+                    id,
+                    check_digit(id),
+                    width = digits_in_id as usize
+                );
+                assert_eq!(pay_exactly(&price, id), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn token_skip_reason_format() {
+        assert_eq!(
+            "l1_transfer_restricted".parse(),
+            Ok(ForcedExitTokenSkipReason::L1TransferRestricted)
+        );
+        assert_eq!("nft".parse(), Ok(ForcedExitTokenSkipReason::Nft));
+        let skipped = SkippedForcedExit {
+            token: TokenId(3),
+            reason: ForcedExitTokenSkipReason::L1TransferRestricted,
+        };
+        assert_eq!(
+            serde_json::to_value(skipped).unwrap(),
+            serde_json::json!({ "token": 3, "reason": "l1TransferRestricted" })
+        );
+    }
+
+    #[test]
+    fn pipeline_config_fingerprint() {
+        let config = ForcedExitPipelineConfig {
+            price_per_token: 1,
+            max_tokens_per_request: 2,
+            digits_in_id: 3,
+            max_payment_amount: BigUint::from(4u32),
+            wait_confirmations: 5,
+            expected_payment_wait_confirmations: 6,
+            active_target_policy: ActiveTargetPolicy::Hold,
+            active_target_hold_period: 7,
+            l1_escalation_enabled: true,
+            l1_escalation_failures_threshold: 8,
+            overpayment_tolerance: 9,
+            overpayment_tolerance_percent: 10,
+            legacy_amount_ids_enabled: true,
+        };
+        let changed = ForcedExitPipelineConfig {
+            digits_in_id: 4,
+            ..config.clone()
+        };
+        assert_ne!(config.hash(), changed.hash());
+
+        let fingerprints: Vec<(u32, &str)> = include_str!("forced_exit_pipeline.fingerprint")
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let mut parts = line.splitn(2, ' ');
+                let version = parts.next().unwrap().parse().unwrap();
+                (version, parts.next().unwrap())
+            })
+            .collect();
+        assert!(fingerprints.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(
+            fingerprints.last().copied(),
+            Some((FORCED_EXIT_PIPELINE_VERSION, config.hash().as_str())),
+            "The serialized pipeline config has changed, bump `FORCED_EXIT_PIPELINE_VERSION` \
+             and append the new fingerprint to `forced_exit_pipeline.fingerprint`"
+        );
+    }
+
+    #[test]
+    fn unknown_config_keys_are_refused() {
+        let candidate: Result<ForcedExitConfigCandidate, _> =
+            serde_json::from_str(r#"{"digitsInId": 9, "pricePerTokn": 1000}"#);
+        assert!(candidate.is_err());
+    }
+
+    fn ledger_request(
+        status: ForcedExitLifecycleStatus,
+        paid_amount: Option<&str>,
+    ) -> ForcedExitRequest {
+        let created_at = Utc.timestamp(1_600_000_000, 0);
+        ForcedExitRequest {
+            id: 1,
+            public_id: 12,
+            target: Address::repeat_byte(0x12),
+            tokens: vec![TokenId(0)],
+            price_in_wei: BigUint::from(1000u32),
+            pay_exactly: "1125".to_owned(),
+            valid_until: created_at + chrono::Duration::days(1),
+            created_at,
+            fulfilled_by: None,
+            fulfilled_at: None,
+            match_scheme: None,
+            matched_at: None,
+            cancellation: None,
+            paid_amount: paid_amount.map(str::to_owned),
+            metadata: None,
+            status,
+            payment_terms: None,
+        }
+    }
+
+    fn ledger_payment(amount: u32, hash: u8, minutes: i64) -> ForcedExitPayment {
+        ForcedExitPayment {
+            amount: BigUint::from(amount),
+            request_id: Some(12),
+            block_number: 10,
+            eth_tx_hash: Some(H256::repeat_byte(hash)),
+            payer: Some(Address::repeat_byte(0x34)),
+            received_at: Utc.timestamp(1_600_000_000, 0) + chrono::Duration::minutes(minutes),
+            source: PaymentSource::L1Event,
+        }
+    }
+
+    fn ledger_fulfillment(minutes: i64) -> ForcedExitFulfillment {
+        ForcedExitFulfillment {
+            request_id: 1,
+            position: 0,
+            token: TokenId(0),
+            tx_hash: TxHash::from_slice(&[0x56; 32]).unwrap(),
+            created_at: Utc.timestamp(1_600_000_000, 0) + chrono::Duration::minutes(minutes),
+            target_account_id: None,
+            submitted_at: None,
+        }
+    }
+
+    fn ledger_refund(
+        amount: u32,
+        fee: u32,
+        hash: u8,
+        status: ForcedExitRefundStatus,
+        minutes: i64,
+    ) -> ForcedExitRefund {
+        let created_at = Utc.timestamp(1_600_000_000, 0) + chrono::Duration::minutes(minutes);
+        ForcedExitRefund {
+            id: 1,
+            request_id: 1,
+            payment_tx_hash: H256::repeat_byte(hash),
+            recipient: Address::repeat_byte(0x34),
+            amount: BigUint::from(amount),
+            fee: BigUint::from(fee),
+            reason: ForcedExitRefundReason::Expired,
+            status,
+            tx_hash: None,
+            attempts: 0,
+            approved_by: None,
+            approved_at: None,
+            sent_at: None,
+            created_at,
+            updated_at: created_at,
+        }
+    }
+
+    fn entry_kinds(ledger: &ForcedExitRequestLedger) -> Vec<(ForcedExitLedgerEntryKind, u32)> {
+        ledger
+            .entries
+            .iter()
+            .map(|entry| (entry.kind, entry.amount.to_string().parse().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn ledger_of_unpaid_request() {
+        for status in [
+            ForcedExitLifecycleStatus::Created,
+            ForcedExitLifecycleStatus::Expired,
+            ForcedExitLifecycleStatus::Cancelled,
+        ] {
+            let ledger = ForcedExitRequestLedger::new(&ledger_request(status, None), &[], &[], &[]);
+            assert!(ledger.entries.is_empty());
+            assert_eq!(ledger.balance, "0");
+            assert!(ledger.balanced);
+        }
+    }
+
+    #[test]
+    fn ledger_of_paid_request() {
+        use ForcedExitLedgerEntryKind::*;
+
+        // The payment is held until the transactions are sent
+        let request = ledger_request(ForcedExitLifecycleStatus::PaymentReceived, Some("1125"));
+        let payments = [ledger_payment(1125, 0x01, 1)];
+        let ledger = ForcedExitRequestLedger::new(&request, &payments, &[], &[]);
+        assert_eq!(entry_kinds(&ledger), vec![(Payment, 1125)]);
+        assert_eq!(ledger.balance, "1125");
+        assert!(ledger.balanced);
+
+        let request = ledger_request(ForcedExitLifecycleStatus::Fulfilled, Some("1125"));
+        let fulfillments = [ledger_fulfillment(2)];
+        let ledger = ForcedExitRequestLedger::new(&request, &payments, &fulfillments, &[]);
+        assert_eq!(entry_kinds(&ledger), vec![(Payment, 1125), (Price, 1125)]);
+        assert_eq!(ledger.entries[1].tx_hash, Some(fulfillments[0].tx_hash));
+        assert_eq!(ledger.balance, "0");
+        assert!(ledger.balanced);
+
+        // The requests matched before the payments were recorded are credited with the paid amount
+        let ledger = ForcedExitRequestLedger::new(&request, &[], &fulfillments, &[]);
+        assert_eq!(entry_kinds(&ledger), vec![(Payment, 1125), (Price, 1125)]);
+        assert_eq!(ledger.entries[0].eth_tx_hash, None);
+        assert!(ledger.balanced);
+
+        // The escalated requests have no transactions on L2
+        let request = ForcedExitRequest {
+            fulfilled_at: Some(Utc.timestamp(1_600_000_000, 0) + chrono::Duration::minutes(3)),
+            ..request
+        };
+        let ledger = ForcedExitRequestLedger::new(&request, &payments, &[], &[]);
+        assert_eq!(entry_kinds(&ledger), vec![(Payment, 1125), (Price, 1125)]);
+        assert_eq!(ledger.entries[1].tx_hash, None);
+        assert!(ledger.balanced);
+    }
+
+    #[test]
+    fn ledger_of_overpaid_request() {
+        use ForcedExitLedgerEntryKind::*;
+
+        let request = ledger_request(ForcedExitLifecycleStatus::Fulfilled, Some("1130"));
+        let payments = [ledger_payment(1130, 0x01, 1)];
+        let ledger =
+            ForcedExitRequestLedger::new(&request, &payments, &[ledger_fulfillment(2)], &[]);
+        assert_eq!(
+            entry_kinds(&ledger),
+            vec![(Payment, 1130), (Price, 1125), (Overpayment, 5)]
+        );
+        assert_eq!(ledger.credited, BigUint::from(1130u32));
+        assert_eq!(ledger.debited, BigUint::from(1130u32));
+        assert_eq!(ledger.balance, "0");
+        assert!(ledger.balanced);
+
+        // The payment above the tolerance is not matched and is refunded instead
+        let request = ledger_request(ForcedExitLifecycleStatus::Created, None);
+        let payments = [ledger_payment(2000, 0x02, 1)];
+        let refunds = [ledger_refund(
+            1990,
+            10,
+            0x02,
+            ForcedExitRefundStatus::Completed,
+            2,
+        )];
+        let ledger = ForcedExitRequestLedger::new(&request, &payments, &[], &refunds);
+        assert_eq!(
+            entry_kinds(&ledger),
+            vec![(Payment, 2000), (Refund, 1990), (RefundFee, 10)]
+        );
+        assert_eq!(ledger.balance, "0");
+        assert!(ledger.balanced);
+    }
+
+    #[test]
+    fn ledger_of_refunded_request() {
+        use ForcedExitLedgerEntryKind::*;
+
+        // Paid for once expired, the payment is held until the refund is completed
+        let request = ledger_request(ForcedExitLifecycleStatus::Expired, None);
+        let payments = [ledger_payment(1125, 0x01, 1)];
+        let pending = [ledger_refund(
+            1115,
+            10,
+            0x01,
+            ForcedExitRefundStatus::Pending,
+            2,
+        )];
+        let ledger = ForcedExitRequestLedger::new(&request, &payments, &[], &pending);
+        assert_eq!(
+            entry_kinds(&ledger),
+            vec![(Payment, 1125), (Refund, 1115), (RefundFee, 10)]
+        );
+        assert_eq!(ledger.entries[1].eth_tx_hash, Some(H256::repeat_byte(0x01)));
+        assert_eq!(ledger.debited, BigUint::from(0u32));
+        assert_eq!(ledger.balance, "1125");
+        assert!(ledger.balanced);
+
+        let completed = [ledger_refund(
+            1115,
+            10,
+            0x01,
+            ForcedExitRefundStatus::Completed,
+            2,
+        )];
+        let ledger = ForcedExitRequestLedger::new(&request, &payments, &[], &completed);
+        assert_eq!(ledger.balance, "0");
+        assert!(ledger.balanced);
+
+        // The second payment for the fulfilled request is refunded, the first one is consumed
+        let request = ledger_request(ForcedExitLifecycleStatus::Fulfilled, Some("1125"));
+        let payments = [ledger_payment(1125, 0x01, 1), ledger_payment(1125, 0x02, 3)];
+        let refunds = [ledger_refund(
+            1125,
+            0,
+            0x02,
+            ForcedExitRefundStatus::Completed,
+            4,
+        )];
+        let ledger =
+            ForcedExitRequestLedger::new(&request, &payments, &[ledger_fulfillment(2)], &refunds);
+        assert_eq!(
+            entry_kinds(&ledger),
+            vec![
+                (Payment, 1125),
+                (Price, 1125),
+                (Payment, 1125),
+                (Refund, 1125)
+            ]
+        );
+        assert_eq!(ledger.balance, "0");
+        assert!(ledger.balanced);
+
+        // Without the refund the second payment is stuck
+        let ledger =
+            ForcedExitRequestLedger::new(&request, &payments, &[ledger_fulfillment(2)], &[]);
+        assert_eq!(ledger.balance, "1125");
+        assert!(!ledger.balanced);
+    }
+
+    #[test]
+    fn ledger_of_inconsistent_request() {
+        // Refunded without the payment being recorded
+        let request = ledger_request(ForcedExitLifecycleStatus::Failed, None);
+        let refunds = [ledger_refund(
+            1125,
+            0,
+            0x01,
+            ForcedExitRefundStatus::Completed,
+            2,
+        )];
+        let ledger = ForcedExitRequestLedger::new(&request, &[], &[], &refunds);
+        assert_eq!(ledger.balance, "-1125");
+        assert!(!ledger.balanced);
+
+        // The failed request is not refunded
+        let payments = [ledger_payment(1125, 0x01, 1)];
+        let ledger = ForcedExitRequestLedger::new(&request, &payments, &[], &[]);
+        assert_eq!(ledger.balance, "1125");
+        assert!(!ledger.balanced);
+    }
+}
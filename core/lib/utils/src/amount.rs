@@ -0,0 +1,252 @@
+//! Conversions of the token amounts between the representations used across the stack.
+//!
+//! The amounts are `BigUint`s in the logic, `NUMERIC`s in Postgres and decimal strings in JSON.
+//! The strings are always formatted as plain integers (no exponent, no fractional part
+//! and no leading zeros), and only such strings are accepted back. A fractional or negative
+//! value is an error instead of being rounded silently, so an amount can't change its value
+//! while crossing a boundary.
+
+use std::fmt;
+
+use anyhow::{ensure, format_err};
+use bigdecimal::BigDecimal;
+use num::{bigint::ToBigUint, BigInt, BigUint, Zero};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::BigUintSerdeWrapper;
+
+/// Formats the amount as a decimal integer string.
+pub fn format_amount(amount: &BigUint) -> String {
+    amount.to_str_radix(10)
+}
+
+/// Parses the amount formatted by `format_amount`.
+pub fn parse_amount(value: &str) -> anyhow::Result<BigUint> {
+    ensure!(!value.is_empty(), "Amount is empty");
+    ensure!(
+        value.bytes().all(|byte| byte.is_ascii_digit()),
+        "Amount `{}` is not a decimal integer",
+        value
+    );
+    ensure!(
+        value == "0" || !value.starts_with('0'),
+        "Amount `{}` has leading zeros",
+        value
+    );
+
+    BigUint::parse_bytes(value.as_bytes(), 10)
+        .ok_or_else(|| format_err!("Amount `{}` is not a decimal integer", value))
+}
+
+/// Converts the amount to the value of the `NUMERIC` column.
+pub fn amount_to_big_decimal(amount: &BigUint) -> BigDecimal {
+    BigDecimal::new(BigInt::from(amount.clone()), 0)
+}
+
+/// Converts the value of the `NUMERIC` column to the amount, the value may have
+/// any scale as long as it is a non-negative integer.
+pub fn big_decimal_to_amount(value: &BigDecimal) -> anyhow::Result<BigUint> {
+    let (int, scale) = value.as_bigint_and_exponent();
+    let int = if scale <= 0 {
+        int * BigInt::from(10u32).pow((-scale) as u32)
+    } else {
+        let divisor = BigInt::from(10u32).pow(scale as u32);
+        ensure!(
+            (&int % &divisor).is_zero(),
+            "Amount {} is not an integer",
+            value
+        );
+        int / divisor
+    };
+
+    int.to_biguint()
+        .ok_or_else(|| format_err!("Amount {} is negative", value))
+}
+
+/// Used to serialize BigUint as radix 10 string. The integer JSON numbers are accepted
+/// as well as long as they are represented exactly.
+#[derive(Clone, Debug)]
+pub struct BigUintSerdeAsRadix10Str;
+
+impl BigUintSerdeAsRadix10Str {
+    pub fn serialize<S>(amount: &BigUint, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&format_amount(amount))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<BigUint, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(AmountVisitor)
+    }
+}
+
+/// Used to serialize the pair of BigUint as radix 10 strings.
+#[derive(Clone, Debug)]
+pub struct BigUintPairSerdeAsRadix10Str;
+
+impl BigUintPairSerdeAsRadix10Str {
+    pub fn serialize<S>(pair: &(BigUint, BigUint), serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        (format_amount(&pair.0), format_amount(&pair.1)).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<(BigUint, BigUint), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (first, second) =
+            <(BigUintSerdeWrapper, BigUintSerdeWrapper)>::deserialize(deserializer)?;
+        Ok((first.0, second.0))
+    }
+}
+
+struct AmountVisitor;
+
+impl<'de> de::Visitor<'de> for AmountVisitor {
+    type Value = BigUint;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a non-negative decimal integer string")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<BigUint, E> {
+        parse_amount(value).map_err(E::custom)
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<BigUint, E> {
+        Ok(BigUint::from(value))
+    }
+
+    fn visit_u128<E: de::Error>(self, value: u128) -> Result<BigUint, E> {
+        Ok(BigUint::from(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<BigUint, E> {
+        value
+            .to_biguint()
+            .ok_or_else(|| E::custom(format!("Amount {} is negative", value)))
+    }
+
+    // The floats can't represent the large amounts exactly
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<BigUint, E> {
+        Err(E::custom(format!(
+            "Amount {} must be a decimal integer string",
+            value
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Wrapper(#[serde(with = "BigUintSerdeAsRadix10Str")] BigUint);
+
+    // Mostly the values near the boundaries of the primitive types, but also the random ones
+    // up to 320 bits, which is beyond any amount the contracts may hold
+    fn sample_amounts() -> Vec<BigUint> {
+        let one = BigUint::from(1u32);
+        let mut amounts = vec![
+            BigUint::zero(),
+            one.clone(),
+            BigUint::from(u64::MAX),
+            BigUint::from(u64::MAX) + &one,
+            BigUint::from(u128::MAX) - &one,
+            BigUint::from(u128::MAX),
+            BigUint::from(u128::MAX) + &one,
+            (BigUint::from(1u32) << 256) - &one,
+            BigUint::from(10u32).pow(77u32),
+        ];
+
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        for _ in 0..1000 {
+            let mut bytes = vec![0u8; rng.gen_range(0, 41)];
+            rng.fill(&mut bytes[..]);
+            amounts.push(BigUint::from_bytes_be(&bytes));
+        }
+        amounts
+    }
+
+    #[test]
+    fn string_round_trip() {
+        for amount in sample_amounts() {
+            let formatted = format_amount(&amount);
+            assert!(formatted.bytes().all(|byte| byte.is_ascii_digit()));
+            assert!(formatted == "0" || !formatted.starts_with('0'));
+            assert_eq!(parse_amount(&formatted).unwrap(), amount);
+        }
+    }
+
+    #[test]
+    fn json_round_trip() {
+        for amount in sample_amounts() {
+            let value = serde_json::to_value(Wrapper(amount.clone())).unwrap();
+            assert_eq!(value, serde_json::Value::String(format_amount(&amount)));
+            assert_eq!(serde_json::from_value::<Wrapper>(value).unwrap().0, amount);
+        }
+    }
+
+    #[test]
+    fn big_decimal_round_trip() {
+        for amount in sample_amounts() {
+            let value = amount_to_big_decimal(&amount);
+            assert_eq!(big_decimal_to_amount(&value).unwrap(), amount);
+            // As if the value has been read from the column with another scale
+            let (int, _) = value.as_bigint_and_exponent();
+            let rescaled = BigDecimal::new(int * BigInt::from(1000u32), 3);
+            assert_eq!(big_decimal_to_amount(&rescaled).unwrap(), amount);
+        }
+    }
+
+    #[test]
+    fn invalid_strings() {
+        for value in &[
+            "", "01", "00", "1e3", "1E3", "1.0", "1.5", "-1", "+1", " 1", "1 ", "0x10", "1_000",
+        ] {
+            assert!(parse_amount(value).is_err(), "`{}` is accepted", value);
+        }
+    }
+
+    #[test]
+    fn json_numbers() {
+        let parse = |json: &str| serde_json::from_str::<Wrapper>(json).map(|wrapper| wrapper.0);
+
+        assert_eq!(parse("1000").unwrap(), BigUint::from(1000u32));
+        assert_eq!(
+            parse(&u64::MAX.to_string()).unwrap(),
+            BigUint::from(u64::MAX)
+        );
+        assert!(parse("-1").is_err());
+        // Would be rounded to the nearest float otherwise
+        assert!(parse("1.5").is_err());
+        assert!(parse("1e30").is_err());
+        assert!(parse("340282366920938463463374607431768211455").is_err());
+    }
+
+    #[test]
+    fn invalid_big_decimals() {
+        for value in &["1.5", "-1", "0.001"] {
+            let value = BigDecimal::from_str(value).unwrap();
+            assert!(big_decimal_to_amount(&value).is_err());
+        }
+        assert_eq!(
+            big_decimal_to_amount(&BigDecimal::from_str("1e3").unwrap()).unwrap(),
+            BigUint::from(1000u32)
+        );
+        assert_eq!(
+            big_decimal_to_amount(&BigDecimal::from_str("-0").unwrap()).unwrap(),
+            BigUint::zero()
+        );
+    }
+}
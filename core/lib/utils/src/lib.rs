@@ -1,5 +1,6 @@
 //! Various helpers used in the zkSync stack.
 
+mod amount;
 mod convert;
 mod env_tools;
 mod format;
@@ -8,6 +9,7 @@ pub mod panic_notify;
 mod serde_wrappers;
 mod string;
 
+pub use amount::*;
 pub use convert::*;
 pub use env_tools::*;
 pub use format::*;
@@ -1,10 +1,10 @@
 use std::str::FromStr;
 
 use bigdecimal::BigDecimal;
-use num::{bigint::ToBigInt, rational::Ratio, BigUint};
+use num::{rational::Ratio, BigUint};
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::convert::*;
+use crate::{amount::BigUintSerdeAsRadix10Str, convert::*};
 
 #[derive(Clone, Debug)]
 pub struct UnsignedRatioSerializeAsDecimal;
@@ -39,71 +39,6 @@ impl UnsignedRatioSerializeAsDecimal {
     }
 }
 
-/// Used to serialize BigUint as radix 10 string.
-#[derive(Clone, Debug)]
-pub struct BigUintSerdeAsRadix10Str;
-
-impl BigUintSerdeAsRadix10Str {
-    pub fn serialize<S>(val: &BigUint, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        let big_dec = BigDecimal::from(val.to_bigint().unwrap());
-        BigDecimal::serialize(&big_dec, serializer)
-    }
-
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<BigUint, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        use serde::de::Error;
-        BigDecimal::deserialize(deserializer).and_then(|bigdecimal| {
-            let big_int = bigdecimal
-                .to_bigint()
-                .ok_or_else(|| Error::custom("Expected integer value"))?;
-            big_int
-                .to_biguint()
-                .ok_or_else(|| Error::custom("Expected positive value"))
-        })
-    }
-}
-
-/// Used to serialize BigUint as radix 10 string.
-#[derive(Clone, Debug)]
-pub struct BigUintPairSerdeAsRadix10Str;
-
-impl BigUintPairSerdeAsRadix10Str {
-    pub fn serialize<S>(pair: &(BigUint, BigUint), serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        <(BigDecimal, BigDecimal)>::serialize(
-            &(
-                BigDecimal::from(pair.0.to_bigint().unwrap()),
-                BigDecimal::from(pair.1.to_bigint().unwrap()),
-            ),
-            serializer,
-        )
-    }
-
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<(BigUint, BigUint), D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        use serde::de::Error;
-        let convert = |bigdecimal: BigDecimal| {
-            bigdecimal
-                .to_bigint()
-                .ok_or_else(|| Error::custom("Expected integer value"))?
-                .to_biguint()
-                .ok_or_else(|| Error::custom("Expected positive value"))
-        };
-
-        <(BigDecimal, BigDecimal)>::deserialize(deserializer)
-            .and_then(|(a, b)| Ok((convert(a)?, convert(b)?)))
-    }
-}
-
 #[derive(Clone, Debug, Serialize, Deserialize, Default, Ord, PartialOrd, Eq, PartialEq, Hash)]
 pub struct BigUintSerdeWrapper(#[serde(with = "BigUintSerdeAsRadix10Str")] pub BigUint);
 